@@ -1,6 +1,8 @@
 //! Copy pasted from std::cell::SyncUnsafeCell
 use std::cell::UnsafeCell;
 
+pub mod init_order;
+
 /// [`UnsafeCell`], but [`Sync`].
 ///
 /// This is just an `UnsafeCell`, except it implements `Sync`
@@ -0,0 +1,319 @@
+//! 全局单例的初始化顺序
+//!
+//! 每个组件声明依赖的组件, `init_all`按拓扑顺序初始化, 已初始化的组件跳过;
+//! `diagnostics`列出每个组件是否已初始化及还未初始化的依赖, 用于排查初始化顺序错误.
+//! 组件可以用`with_probe`检查实际的状态(如`OnceLock::get().is_some()`), 不经过registry直接初始化的也能识别
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+pub type InitFuture<'a, E> = Pin<Box<dyn Future<Output = Result<(), E>> + Send + 'a>>;
+
+type InitFn<C, E> = Box<dyn for<'a> Fn(&'a C) -> InitFuture<'a, E> + Send + Sync>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InitOrderError<E> {
+    #[error("component registered twice: {0}")]
+    Duplicate(&'static str),
+    #[error("component {component} depends on unknown {dependency}")]
+    UnknownDependency {
+        component:  &'static str,
+        dependency: &'static str,
+    },
+    #[error("dependency cycle: {0:?}")]
+    Cycle(Vec<&'static str>),
+    #[error("init {component} err: {source}")]
+    Init {
+        component: &'static str,
+        source:    E,
+    },
+}
+
+struct Component<C, E> {
+    name:  &'static str,
+    deps:  Vec<&'static str>,
+    init:  InitFn<C, E>,
+    probe: Option<fn() -> bool>,
+}
+
+/// 组件的初始化状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitStatus {
+    pub name:         &'static str,
+    pub deps:         Vec<&'static str>,
+    pub initialized:  bool,
+    /// 还未初始化的依赖
+    pub missing_deps: Vec<&'static str>,
+}
+
+impl fmt::Display for InitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = if self.initialized { "[x]" } else { "[ ]" };
+        write!(f, "{} {}", flag, self.name)?;
+        if !self.deps.is_empty() {
+            write!(f, " <- {}", self.deps.join(","))?;
+        }
+        if !self.missing_deps.is_empty() {
+            write!(f, " (missing: {})", self.missing_deps.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// C: 初始化时的参数, 如数据库连接池, E: 初始化的错误
+pub struct InitRegistry<C, E> {
+    components:  Vec<Component<C, E>>,
+    initialized: Mutex<HashSet<&'static str>>,
+}
+
+impl<C, E> Default for InitRegistry<C, E> {
+    fn default() -> Self {
+        InitRegistry {
+            components:  Vec::new(),
+            initialized: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+impl<C, E> InitRegistry<C, E> {
+    pub fn new() -> InitRegistry<C, E> {
+        Self::default()
+    }
+
+    /// 添加组件, deps为依赖的组件名, 可以在依赖之前添加
+    pub fn with_component<F>(mut self, name: &'static str, deps: &[&'static str], init: F) -> Self
+    where
+        F: for<'a> Fn(&'a C) -> InitFuture<'a, E> + Send + Sync + 'static,
+    {
+        self.components.push(Component {
+            name,
+            deps: deps.to_vec(),
+            init: Box::new(init),
+            probe: None,
+        });
+        self
+    }
+
+    /// 组件实际是否已初始化的检查, 如`|| CELL.get().is_some()`, 先添加组件再设置
+    pub fn with_probe(mut self, name: &'static str, probe: fn() -> bool) -> Self {
+        if let Some(component) = self.components.iter_mut().find(|v| v.name == name) {
+            component.probe = Some(probe);
+        }
+        self
+    }
+
+    /// 按依赖排序的组件名, 没有依赖关系的组件保持添加的顺序
+    pub fn init_order(&self) -> Result<Vec<&'static str>, InitOrderError<E>> {
+        let mut index = HashMap::new();
+        for (i, component) in self.components.iter().enumerate() {
+            if index.insert(component.name, i).is_some() {
+                return Err(InitOrderError::Duplicate(component.name));
+            }
+        }
+        for component in &self.components {
+            let unknown = component.deps.iter().find(|v| !index.contains_key(*v));
+            if let Some(&dependency) = unknown {
+                return Err(InitOrderError::UnknownDependency {
+                    component: component.name,
+                    dependency,
+                });
+            }
+        }
+        let mut order = Vec::with_capacity(self.components.len());
+        let mut done = HashSet::new();
+        while order.len() < self.components.len() {
+            let next = self
+                .components
+                .iter()
+                .find(|v| !done.contains(v.name) && v.deps.iter().all(|d| done.contains(d)));
+            let Some(next) = next else {
+                let cycle = self
+                    .components
+                    .iter()
+                    .filter(|v| !done.contains(v.name))
+                    .map(|v| v.name)
+                    .collect();
+                return Err(InitOrderError::Cycle(cycle));
+            };
+            done.insert(next.name);
+            order.push(next.name);
+        }
+        Ok(order)
+    }
+
+    /// 按依赖顺序初始化所有组件, 已初始化的跳过, 出错时停止
+    pub async fn init_all(&self, ctx: &C) -> Result<(), InitOrderError<E>> {
+        for name in self.init_order()? {
+            if self.is_initialized(name) {
+                continue;
+            }
+            let component = self.components.iter().find(|v| v.name == name).unwrap();
+            (component.init)(ctx)
+                .await
+                .map_err(|source| InitOrderError::Init {
+                    component: name,
+                    source,
+                })?;
+            self.initialized.lock().unwrap().insert(name);
+        }
+        Ok(())
+    }
+
+    /// 标记为已初始化, 用于在registry之外初始化的组件
+    pub fn mark_initialized(&self, name: &'static str) {
+        self.initialized.lock().unwrap().insert(name);
+    }
+
+    /// 有probe时以probe为准, 否则看是否经过registry初始化或已标记
+    pub fn is_initialized(&self, name: &str) -> bool {
+        let probe = self
+            .components
+            .iter()
+            .find(|v| v.name == name)
+            .and_then(|v| v.probe);
+        match probe {
+            Some(probe) => probe(),
+            None => self.initialized.lock().unwrap().contains(name),
+        }
+    }
+
+    /// 所有组件的初始化状态, 按添加的顺序
+    pub fn diagnostics(&self) -> Vec<InitStatus> {
+        self.components
+            .iter()
+            .map(|v| InitStatus {
+                name:         v.name,
+                deps:         v.deps.clone(),
+                initialized:  self.is_initialized(v.name),
+                missing_deps: v
+                    .deps
+                    .iter()
+                    .filter(|d| !self.is_initialized(d))
+                    .copied()
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use super::{InitFuture, InitOrderError, InitRegistry};
+
+    type Log = Arc<Mutex<Vec<&'static str>>>;
+
+    fn component(
+        name: &'static str,
+        fail: &'static str,
+    ) -> impl for<'a> Fn(&'a Log) -> InitFuture<'a, String> + Send + Sync {
+        move |log| {
+            Box::pin(async move {
+                if name == fail {
+                    return Err(format!("{} failed", name));
+                }
+                log.lock().unwrap().push(name);
+                Ok(())
+            })
+        }
+    }
+
+    fn build(fail: &'static str) -> InitRegistry<Log, String> {
+        InitRegistry::new()
+            .with_component(
+                "convert_1m",
+                &["breed", "tx_time_range"],
+                component("convert_1m", fail),
+            )
+            .with_component("tx_time_range", &[], component("tx_time_range", fail))
+            .with_component("breed", &[], component("breed", fail))
+            .with_component("trading_day", &[], component("trading_day", fail))
+    }
+
+    #[tokio::test]
+    async fn test_init_all() {
+        let registry = build("");
+        assert_eq!(
+            registry.init_order().unwrap(),
+            ["tx_time_range", "breed", "convert_1m", "trading_day"]
+        );
+        let status = registry.diagnostics();
+        assert_eq!(status[0].missing_deps, ["breed", "tx_time_range"]);
+        assert_eq!(
+            status[0].to_string(),
+            "[ ] convert_1m <- breed,tx_time_range (missing: breed,tx_time_range)"
+        );
+
+        let log = Log::default();
+        registry.init_all(&log).await.unwrap();
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["tx_time_range", "breed", "convert_1m", "trading_day"]
+        );
+        assert!(registry.diagnostics().iter().all(|v| v.initialized));
+        // 已初始化的不再执行
+        registry.init_all(&log).await.unwrap();
+        assert_eq!(log.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_init_error() {
+        let registry = build("breed");
+        let log = Log::default();
+        let r = registry.init_all(&log).await;
+        assert!(matches!(
+            r,
+            Err(InitOrderError::Init {
+                component: "breed",
+                ..
+            })
+        ));
+        assert_eq!(*log.lock().unwrap(), ["tx_time_range"]);
+        assert!(registry.is_initialized("tx_time_range"));
+        assert!(!registry.is_initialized("convert_1m"));
+
+        let registry = build("").with_component("a", &["b"], |_| Box::pin(async { Ok(()) }));
+        assert!(matches!(
+            registry.init_order(),
+            Err(InitOrderError::UnknownDependency {
+                component:  "a",
+                dependency: "b",
+            })
+        ));
+        let registry = InitRegistry::<Log, String>::new()
+            .with_component("a", &["b"], |_| Box::pin(async { Ok(()) }))
+            .with_component("b", &["a"], |_| Box::pin(async { Ok(()) }))
+            .with_component("c", &[], |_| Box::pin(async { Ok(()) }));
+        assert!(matches!(registry.init_order(), Err(InitOrderError::Cycle(v)) if v == ["a", "b"]));
+        let registry = registry.with_component("c", &[], |_| Box::pin(async { Ok(()) }));
+        assert!(matches!(
+            registry.init_order(),
+            Err(InitOrderError::Duplicate("c"))
+        ));
+    }
+
+    static PROBED: AtomicBool = AtomicBool::new(false);
+
+    #[tokio::test]
+    async fn test_probe() {
+        let registry = build("").with_probe("breed", || PROBED.load(Ordering::Relaxed));
+        assert!(!registry.is_initialized("breed"));
+        // 不经过registry初始化
+        PROBED.store(true, Ordering::Relaxed);
+        assert!(registry.is_initialized("breed"));
+        let status = registry.diagnostics();
+        assert_eq!(status[0].missing_deps, ["tx_time_range"]);
+        assert!(status[2].initialized);
+
+        let log = Log::default();
+        registry.init_all(&log).await.unwrap();
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["tx_time_range", "convert_1m", "trading_day"]
+        );
+    }
+}
@@ -2,10 +2,18 @@ use once_cell::sync::Lazy;
 use rayon::{ThreadPool, ThreadPoolBuilder};
 
 mod contention_pool;
+pub mod dialect;
+pub mod limits;
 mod parser;
 pub mod read;
+pub mod sidecar;
 mod splitfields;
+#[cfg(all(feature = "mysqlx", feature = "sql-loader"))]
+pub mod stage;
+pub mod transform;
 mod utils;
+#[cfg(feature = "qh")]
+pub mod vendor;
 pub mod write;
 
 static POOL: Lazy<ThreadPool> = Lazy::new(|| {
@@ -0,0 +1,194 @@
+/// 分隔符候选, 按优先级排序
+const SEPARATORS: [u8; 4] = [b',', b'\t', b';', b'|'];
+/// 引号候选
+const QUOTE_CHARS: [u8; 2] = [b'"', b'\''];
+/// 用于检测的最大行数
+const MAX_SAMPLE_LINES: usize = 32;
+
+/// csv文件的格式
+/// escape_char: 为None时, 字段中的引号用两个引号表示(""), 否则用escape_char转义(\")
+/// crlf: 行尾是否为\r\n
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsvDialect {
+    pub separator:   u8,
+    pub quote_char:  Option<u8>,
+    pub escape_char: Option<u8>,
+    pub eol_char:    u8,
+    pub crlf:        bool,
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        CsvDialect {
+            separator:   b',',
+            quote_char:  Some(b'"'),
+            escape_char: None,
+            eol_char:    b'\n',
+            crlf:        false,
+        }
+    }
+}
+
+/// 从文件开头的一段数据检测格式, 检测不出的项使用默认值
+pub(crate) fn detect(sample: &[u8]) -> CsvDialect {
+    let (eol_char, crlf) = detect_eol(sample);
+    let lines = sample_lines(sample, eol_char);
+    let quote_char = detect_quote_char(&lines);
+    let separator = detect_separator(&lines, quote_char);
+    let escape_char = quote_char.and_then(|quote_char| detect_escape_char(&lines, quote_char));
+    CsvDialect {
+        separator,
+        quote_char,
+        escape_char,
+        eol_char,
+        crlf,
+    }
+}
+
+fn detect_eol(sample: &[u8]) -> (u8, bool) {
+    match memchr::memchr(b'\n', sample) {
+        Some(pos) => (b'\n', pos > 0 && sample[pos - 1] == b'\r'),
+        None if memchr::memchr(b'\r', sample).is_some() => (b'\r', false),
+        None => (b'\n', false),
+    }
+}
+
+/// 取非空行, sample不是以换行结束时最后一行可能不完整, 去掉
+fn sample_lines(sample: &[u8], eol_char: u8) -> Vec<&[u8]> {
+    let mut lines = sample
+        .split(|c| *c == eol_char)
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .collect::<Vec<_>>();
+    if lines.len() > 1 && !sample.ends_with(&[eol_char]) {
+        lines.pop();
+    }
+    lines
+        .into_iter()
+        .filter(|line| !line.is_empty())
+        .take(MAX_SAMPLE_LINES)
+        .collect()
+}
+
+/// 字段开头出现次数最多的引号字符
+fn detect_quote_char(lines: &[&[u8]]) -> Option<u8> {
+    QUOTE_CHARS
+        .iter()
+        .map(|quote_char| {
+            let count = lines
+                .iter()
+                .map(|line| {
+                    line.iter()
+                        .enumerate()
+                        .filter(|(idx, c)| {
+                            **c == *quote_char && (*idx == 0 || SEPARATORS.contains(&line[idx - 1]))
+                        })
+                        .count()
+                })
+                .sum::<usize>();
+            (*quote_char, count)
+        })
+        .filter(|(_, count)| *count > 0)
+        .max_by_key(|(_, count)| *count)
+        .map(|(quote_char, _)| quote_char)
+}
+
+/// 统计引号外的分隔符个数, 优先选每行个数都相同且不为0的分隔符
+fn detect_separator(lines: &[&[u8]], quote_char: Option<u8>) -> u8 {
+    let mut best = None;
+    let mut best_total = 0;
+    for separator in SEPARATORS {
+        let counts = lines
+            .iter()
+            .map(|line| count_unquoted(line, separator, quote_char))
+            .collect::<Vec<_>>();
+        let first = counts.first().copied().unwrap_or_default();
+        if first > 0 && counts.iter().all(|v| *v == first) {
+            return separator;
+        }
+        let total = counts.iter().sum::<usize>();
+        if total > best_total {
+            best_total = total;
+            best = Some(separator);
+        }
+    }
+    best.unwrap_or(b',')
+}
+
+fn count_unquoted(line: &[u8], separator: u8, quote_char: Option<u8>) -> usize {
+    let mut in_quote = false;
+    let mut escaped = false;
+    let mut count = 0;
+    for c in line {
+        if escaped {
+            escaped = false;
+        } else if *c == b'\\' && in_quote {
+            escaped = true;
+        } else if Some(*c) == quote_char {
+            in_quote = !in_quote;
+        } else if *c == separator && !in_quote {
+            count += 1;
+        }
+    }
+    count
+}
+
+/// 引号前出现反斜杠时认为是用\转义
+fn detect_escape_char(lines: &[&[u8]], quote_char: u8) -> Option<u8> {
+    lines
+        .iter()
+        .any(|line| line.windows(2).any(|w| w[0] == b'\\' && w[1] == quote_char))
+        .then_some(b'\\')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect, CsvDialect};
+    use crate::csv::read::CsvReader;
+
+    #[test]
+    fn test_detect() {
+        let dialect = detect(b"a,b,c\n1,2,3\n4,5,6\n");
+        println!("{:?}", dialect);
+        assert_eq!(dialect.separator, b',');
+        assert_eq!(dialect.quote_char, None);
+        assert_eq!(dialect.eol_char, b'\n');
+
+        let dialect = detect(b"\"a\";\"b;x\";\"c\"\r\n\"1\";\"2\";\"3\"\r\n\"4\";\"5\";\"6");
+        println!("{:?}", dialect);
+        assert_eq!(
+            dialect,
+            CsvDialect {
+                separator:   b';',
+                quote_char:  Some(b'"'),
+                escape_char: None,
+                eol_char:    b'\n',
+                crlf:        true,
+            }
+        );
+
+        let dialect = detect(b"code\tname\nag\t\"silver \\\"Ag\\\"\"\ncu\t\"copper\"\n");
+        println!("{:?}", dialect);
+        assert_eq!(dialect.separator, b'\t');
+        assert_eq!(dialect.quote_char, Some(b'"'));
+        assert_eq!(dialect.escape_char, Some(b'\\'));
+    }
+
+    #[test]
+    fn test_read_auto_dialect() {
+        let path = std::env::temp_dir().join("common-rs-csv-dialect.csv");
+        std::fs::write(&path, "ag;\"silver \\\"Ag\\\"\"\r\ncu;\"copper;Cu\"\r\n").unwrap();
+        let rows = CsvReader::new()
+            .with_auto_dialect()
+            .read_csv_file::<(String, String)>(&path)
+            .unwrap();
+        println!("{:?}", rows);
+        assert_eq!(
+            rows,
+            vec![
+                ("ag".to_string(), "silver \"Ag\"".to_string()),
+                ("cu".to_string(), "copper;Cu".to_string()),
+            ]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
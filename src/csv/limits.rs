@@ -0,0 +1,153 @@
+//! 字段长度, 行长度, 列数的限制
+//!
+//! 在分块解析前先扫描一遍原始数据(不分配内存), 损坏的文件(如缺少结束引号)在这里报错,
+//! 而不是在解析时为超大的字段分配内存
+use super::parser::SplitLines;
+use super::splitfields::SplitFields;
+
+/// offset为在文件中的字节位置
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CsvLimitError {
+    #[error("csv field too long at byte {offset}: column {column}, len {len} > {max}")]
+    FieldTooLong {
+        offset: usize,
+        column: usize,
+        len:    usize,
+        max:    usize,
+    },
+
+    #[error("csv row too long at byte {offset}: len {len} > {max}")]
+    RowTooLong {
+        offset: usize,
+        len:    usize,
+        max:    usize,
+    },
+
+    #[error("csv too many columns at byte {offset}: {columns} > {max}")]
+    TooManyColumns {
+        offset:  usize,
+        columns: usize,
+        max:     usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct CsvLimits {
+    pub(crate) max_field_len: Option<usize>,
+    pub(crate) max_row_len:   Option<usize>,
+    pub(crate) max_columns:   Option<usize>,
+}
+
+impl CsvLimits {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.max_field_len.is_none() && self.max_row_len.is_none() && self.max_columns.is_none()
+    }
+
+    /// 检查bytes中的每一行, base_offset为bytes在文件中的位置
+    pub(crate) fn check(
+        &self,
+        bytes: &[u8],
+        base_offset: usize,
+        separator: u8,
+        quote_char: Option<u8>,
+        eol_char: u8,
+    ) -> Result<(), CsvLimitError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let offset_of = |v: &[u8]| base_offset + (v.as_ptr() as usize - bytes.as_ptr() as usize);
+        let lines: Box<dyn Iterator<Item = &[u8]>> = match quote_char {
+            Some(quote_char) => Box::new(SplitLines::new(bytes, quote_char, eol_char)),
+            None => Box::new(bytes.split(|v| *v == eol_char)),
+        };
+        for line in lines {
+            if let Some(max) = self.max_row_len {
+                if line.len() > max {
+                    return Err(CsvLimitError::RowTooLong {
+                        offset: offset_of(line),
+                        len: line.len(),
+                        max,
+                    });
+                }
+            }
+            if self.max_field_len.is_none() && self.max_columns.is_none() {
+                continue;
+            }
+            let fields = SplitFields::new(line, separator, quote_char, eol_char);
+            for (column, (field, _)) in fields.enumerate() {
+                if let Some(max) = self.max_columns {
+                    if column >= max {
+                        return Err(CsvLimitError::TooManyColumns {
+                            offset: offset_of(line),
+                            columns: column + 1,
+                            max,
+                        });
+                    }
+                }
+                if let Some(max) = self.max_field_len {
+                    if field.len() > max {
+                        return Err(CsvLimitError::FieldTooLong {
+                            offset: offset_of(field),
+                            column,
+                            len: field.len(),
+                            max,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CsvLimitError, CsvLimits};
+
+    fn limits(field: Option<usize>, row: Option<usize>, columns: Option<usize>) -> CsvLimits {
+        CsvLimits {
+            max_field_len: field,
+            max_row_len:   row,
+            max_columns:   columns,
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let bytes = b"a,b,c\nag,\"x,y\",3\ncu,12345678,4\n";
+        let check = |v: CsvLimits| v.check(bytes, 100, b',', Some(b'"'), b'\n');
+        assert!(check(limits(Some(8), Some(16), Some(3))).is_ok());
+        assert_eq!(
+            check(limits(Some(7), None, None)),
+            Err(CsvLimitError::FieldTooLong {
+                offset: 120,
+                column: 1,
+                len:    8,
+                max:    7,
+            })
+        );
+        assert_eq!(
+            check(limits(None, Some(10), None)),
+            Err(CsvLimitError::RowTooLong {
+                offset: 117,
+                len:    13,
+                max:    10,
+            })
+        );
+        assert_eq!(
+            check(limits(None, None, Some(2))),
+            Err(CsvLimitError::TooManyColumns {
+                offset:  100,
+                columns: 3,
+                max:     2,
+            })
+        );
+
+        // 缺少结束引号, 后面的数据都在一行中
+        let bytes = b"a,b\n\"x,1\ny,2\nz,3\n";
+        let err = limits(None, Some(8), None)
+            .check(bytes, 0, b',', Some(b'"'), b'\n')
+            .unwrap_err();
+        assert!(matches!(err, CsvLimitError::RowTooLong { offset: 4, .. }));
+    }
+}
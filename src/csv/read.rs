@@ -1,19 +1,51 @@
+use std::collections::HashMap;
 use std::fs;
-use std::io::Read;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 
-use eyre::OptionExt;
-use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+use eyre::{eyre, OptionExt};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::de::DeserializeOwned;
 
+use super::dialect::{self, CsvDialect};
+use super::limits::CsvLimits;
 use super::parser::{
     get_line_stats, is_comment_line, next_line_position, next_line_position_naive, skip_bom,
     skip_line_ending, skip_this_line, skip_whitespace_exclude,
 };
+use super::sidecar::Sidecar;
+use super::transform::{ColumnTransform, TransformPlan};
 use super::utils::{flatten, get_file_chunks};
 use crate::csv::POOL;
 use crate::AResult;
 
+/// 自动检测格式时使用的样本大小
+const DIALECT_SAMPLE_BYTES: usize = 64 * 1024;
+
+/// 去掉首尾空白及BOM, lowercase为true时转为小写
+fn normalize_header(name: &str, lowercase: bool) -> String {
+    let name = name.trim().trim_start_matches('\u{feff}').trim();
+    if lowercase {
+        name.to_lowercase()
+    } else {
+        name.to_owned()
+    }
+}
+
+/// bytes中最后一个完整记录的结束位置(行尾符之后), 引号内的行尾符不算, 没有完整记录时为0
+fn last_record_end(bytes: &[u8], quote_char: Option<u8>, eol_char: u8) -> usize {
+    let mut in_field = false;
+    let mut end = 0;
+    for (i, &c) in bytes.iter().enumerate() {
+        if Some(c) == quote_char {
+            in_field = !in_field;
+        } else if c == eol_char && !in_field {
+            end = i + 1;
+        }
+    }
+    end
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub(crate) enum CommentPrefix {
@@ -53,7 +85,13 @@ pub struct CsvReader {
     sample_size:             usize,
     comment_prefix:          Option<CommentPrefix>,
     quote_char:              Option<u8>,
+    escape_char:             Option<u8>,
     eol_char:                u8,
+    auto_dialect:            bool,
+    header_aliases:          HashMap<String, String>,
+    header_lowercase:        bool,
+    limits:                  CsvLimits,
+    transforms:              Vec<ColumnTransform>,
 }
 
 impl Default for CsvReader {
@@ -74,7 +112,13 @@ impl CsvReader {
             sample_size:             1024,
             comment_prefix:          None,
             quote_char:              Some(b'"'),
+            escape_char:             None,
             eol_char:                b'\n',
+            auto_dialect:            false,
+            header_aliases:          HashMap::new(),
+            header_lowercase:        false,
+            limits:                  CsvLimits::default(),
+            transforms:              Vec::new(),
         }
     }
 
@@ -83,12 +127,128 @@ impl CsvReader {
         self
     }
 
+    pub fn with_separator(mut self, separator: u8) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    /// None: 不使用引号
+    pub fn with_quote_char(mut self, quote_char: Option<u8>) -> Self {
+        self.quote_char = quote_char;
+        self
+    }
+
+    /// None: 字段中的引号用两个引号表示("")
+    pub fn with_escape_char(mut self, escape_char: Option<u8>) -> Self {
+        self.escape_char = escape_char;
+        self
+    }
+
+    pub fn with_eol_char(mut self, eol_char: u8) -> Self {
+        self.eol_char = eol_char;
+        self
+    }
+
+    pub fn with_dialect(mut self, dialect: CsvDialect) -> Self {
+        self.separator = dialect.separator;
+        self.quote_char = dialect.quote_char;
+        self.escape_char = dialect.escape_char;
+        self.eol_char = dialect.eol_char;
+        self
+    }
+
+    /// 解析前先用文件开头的数据检测格式(分隔符, 引号, 转义, 换行), 覆盖已设置的值
+    pub fn with_auto_dialect(mut self) -> Self {
+        self.auto_dialect = true;
+        self
+    }
+
+    /// 表头的别名, (别名, 字段名), 如: ("vol", "volume"), ("成交量", "volume")
+    ///
+    /// 别名按规范化(去掉首尾空白及BOM, 转为小写)后比较, 匹配后替换为字段名再按serde的字段匹配
+    pub fn with_header_aliases(mut self, aliases: &[(&str, &str)]) -> Self {
+        for (alias, field) in aliases {
+            self.header_aliases
+                .insert(normalize_header(alias, true), (*field).to_owned());
+        }
+        self
+    }
+
+    /// 没有匹配别名的表头转为小写, 结构体的字段需要是小写
+    pub fn with_header_lowercase(mut self, header_lowercase: bool) -> Self {
+        self.header_lowercase = header_lowercase;
+        self
+    }
+
+    /// 字段的最大字节数, 超出时返回`CsvLimitError::FieldTooLong`
+    pub fn with_max_field_len(mut self, max_field_len: usize) -> Self {
+        self.limits.max_field_len = Some(max_field_len);
+        self
+    }
+
+    /// 一行的最大字节数, 超出时返回`CsvLimitError::RowTooLong`
+    pub fn with_max_row_len(mut self, max_row_len: usize) -> Self {
+        self.limits.max_row_len = Some(max_row_len);
+        self
+    }
+
+    /// 最大列数, 超出时返回`CsvLimitError::TooManyColumns`
+    pub fn with_max_columns(mut self, max_columns: usize) -> Self {
+        self.limits.max_columns = Some(max_columns);
+        self
+    }
+
+    /// 反序列化前的列转换, 按添加的顺序执行, 需要有表头, 列名为别名替换后的名称
+    pub fn with_transform(mut self, transform: ColumnTransform) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// 规范化表头并替换别名
+    fn map_header(&self, header: &csv::StringRecord) -> csv::StringRecord {
+        header
+            .iter()
+            .map(|name| {
+                let key = normalize_header(name, true);
+                match self.header_aliases.get(&key) {
+                    Some(field) => field.clone(),
+                    None if self.header_lowercase => key,
+                    None => normalize_header(name, false),
+                }
+            })
+            .collect()
+    }
+
+    fn csv_reader_builder(&self) -> csv::ReaderBuilder {
+        let terminator = if self.eol_char == b'\n' {
+            csv::Terminator::CRLF
+        } else {
+            csv::Terminator::Any(self.eol_char)
+        };
+        let mut builder = csv::ReaderBuilder::new();
+        builder
+            .has_headers(false)
+            .delimiter(self.separator)
+            .quoting(self.quote_char.is_some())
+            .quote(self.quote_char.unwrap_or(b'"'))
+            .escape(self.escape_char)
+            .double_quote(self.escape_char.is_none())
+            .terminator(terminator);
+        builder
+    }
+
+    /// 从一段样本数据检测csv格式
+    pub fn detect_dialect(sample: &[u8]) -> CsvDialect {
+        dialect::detect(sample)
+    }
+
+    #[allow(clippy::type_complexity)]
     fn find_starting_point<'b>(
         &self,
         mut bytes: &'b [u8],
         quote_char: Option<u8>,
         eol_char: u8,
-    ) -> AResult<(&'b [u8], Option<usize>)> {
+    ) -> AResult<(&'b [u8], Option<usize>, Option<&'b [u8]>)> {
         let starting_point_offset = bytes.as_ptr() as usize;
         // Skip all leading white space and the occasional utf8-bom
         bytes = skip_whitespace_exclude(skip_bom(bytes), self.separator);
@@ -111,8 +271,11 @@ impl CsvReader {
             bytes = skip_this_line(bytes, quote_char, eol_char);
         }
         // skip header row
+        let mut header = None;
         if self.has_header {
-            bytes = skip_this_line(bytes, quote_char, eol_char);
+            let rest = skip_this_line(bytes, quote_char, eol_char);
+            header = Some(&bytes[..bytes.len() - rest.len()]);
+            bytes = rest;
         }
 
         // skip 'n' rows following the header
@@ -137,7 +300,7 @@ impl CsvReader {
             Some(bytes.as_ptr() as usize - starting_point_offset)
         };
 
-        Ok((bytes, starting_point_offset))
+        Ok((bytes, starting_point_offset, header))
     }
 
     /// Estimates number of rows and optionally ensure we don't read more than `n_rows`
@@ -210,9 +373,9 @@ impl CsvReader {
         n_threads: &mut usize,
         bytes: &'a [u8],
         logging: bool,
-    ) -> AResult<(Vec<(usize, usize)>, &'a [u8])> {
+    ) -> AResult<(Vec<(usize, usize)>, &'a [u8], Option<&'a [u8]>)> {
         // Make the variable mutable so that we can reassign the sliced file to this variable.
-        let (bytes, _) = self.find_starting_point(bytes, self.quote_char, self.eol_char)?;
+        let (bytes, _, header) = self.find_starting_point(bytes, self.quote_char, self.eol_char)?;
 
         let (bytes, total_rows, _) = self.estimate_rows_and_set_upper_bound(bytes, logging, true);
 
@@ -243,30 +406,105 @@ impl CsvReader {
             );
         }
 
-        Ok((chunks, bytes))
+        Ok((chunks, bytes, header))
+    }
+
+    fn apply_auto_dialect(&mut self, bytes: &[u8]) {
+        let sample = &bytes[..bytes.len().min(DIALECT_SAMPLE_BYTES)];
+        let dialect = Self::detect_dialect(skip_bom(sample));
+        self.separator = dialect.separator;
+        self.quote_char = dialect.quote_char;
+        self.escape_char = dialect.escape_char;
+        self.eol_char = dialect.eol_char;
     }
 
     fn parse_csv<R>(&mut self, bytes: &[u8]) -> AResult<Vec<R>>
+    where
+        R: DeserializeOwned + Send + Clone,
+    {
+        if self.auto_dialect {
+            self.apply_auto_dialect(bytes);
+        }
+        self.parse_csv_at(bytes, 0, None)
+    }
+
+    /// base_offset: bytes在文件中的位置, preset_header: 已从文件开头读取的表头
+    fn parse_csv_at<R>(
+        &self,
+        bytes: &[u8],
+        base_offset: usize,
+        preset_header: Option<csv::StringRecord>,
+    ) -> AResult<Vec<R>>
     where
         R: DeserializeOwned + Send + Clone,
     {
         let mut n_threads = self.n_threads.unwrap_or_else(|| POOL.current_num_threads());
 
         let logging = false;
-        let (file_chunks, bytes) =
+        let file_start = bytes.as_ptr() as usize - base_offset;
+        let (file_chunks, bytes, header) =
             self.determine_file_chunks_and_statistics(&mut n_threads, bytes, logging)?;
+        let bytes_offset = bytes.as_ptr() as usize - file_start;
+        if let Some(header) = header {
+            self.limits.check(
+                header,
+                header.as_ptr() as usize - file_start,
+                self.separator,
+                self.quote_char,
+                self.eol_char,
+            )?;
+        }
+
+        // 表头已在find_starting_point中跳过, 单独解析后用于每个分块
+        let header = match header {
+            _ if preset_header.is_some() => preset_header,
+            Some(header) => self
+                .csv_reader_builder()
+                .from_reader(header)
+                .records()
+                .next()
+                .transpose()?
+                .map(|header| self.map_header(&header)),
+            None => None,
+        };
+        let plan = if self.transforms.is_empty() {
+            None
+        } else {
+            Some(TransformPlan::new(&self.transforms, header.as_ref())?)
+        };
+        let target_header = plan.as_ref().map(|v| v.header()).or(header.as_ref());
 
         let ds_vec = POOL.install(|| {
             file_chunks
                 .into_par_iter()
-                .enumerate()
-                .map(|(idx, (bytes_offset_thread, stop_at_nbytes))| {
+                .map(|(bytes_offset_thread, stop_at_nbytes)| {
                     let local_bytes = &bytes[bytes_offset_thread..stop_at_nbytes];
-                    let has_header = if idx == 0 { self.has_header } else { false };
-                    let mut rdr = csv::ReaderBuilder::new()
-                        .has_headers(has_header)
-                        .from_reader(local_bytes);
-                    rdr.deserialize::<R>().collect::<Result<Vec<_>, _>>()
+                    self.limits.check(
+                        local_bytes,
+                        base_offset + bytes_offset + bytes_offset_thread,
+                        self.separator,
+                        self.quote_char,
+                        self.eol_char,
+                    )?;
+                    let mut rdr = self.csv_reader_builder().from_reader(local_bytes);
+                    let items = rdr
+                        .records()
+                        .map(|record| {
+                            let record = record?;
+                            let item = match (&plan, &header) {
+                                (Some(plan), Some(header)) => plan
+                                    .apply(
+                                        &record,
+                                        header,
+                                        base_offset + bytes_offset + bytes_offset_thread,
+                                    )?
+                                    .deserialize::<R>(target_header)?,
+                                _ => record.deserialize::<R>(target_header)?,
+                            };
+                            AResult::Ok(item)
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    AResult::Ok(items)
                 })
                 .collect::<Result<Vec<_>, _>>()
         })?;
@@ -285,6 +523,94 @@ impl CsvReader {
         self.parse_csv::<R>(&bytes)
     }
 
+    /// 用数据商提供的`.md5`/`.cnt`校验文件检查后读取, 防止读入下载不完整的文件
+    ///
+    /// 解析前检查md5, 解析后检查数据行数, 不一致时返回`CsvSidecarError`;
+    /// 只有一个校验文件时只检查该项, 都没有时返回`CsvSidecarError::Missing`
+    pub fn verify_sidecar<R>(&mut self, path: impl AsRef<Path>) -> AResult<Vec<R>>
+    where
+        R: DeserializeOwned + Send + Clone,
+    {
+        let path = path.as_ref();
+        let sidecar = Sidecar::load(path)?;
+        let bytes = fs::read(path)?;
+        sidecar.check_md5(&bytes)?;
+        let rows = self.parse_csv::<R>(&bytes)?;
+        sidecar.check_rows(rows.len())?;
+        Ok(rows)
+    }
+
+    /// 增量读取只追加写入的文件, 返回读取的数据和下一次读取的offset
+    ///
+    /// 只读取到最后一个完整的记录(以行尾符结束), 正在写入的最后一行留到下一次读取.
+    /// offset为0时和`read_csv_file`一样处理表头和跳过的行; 大于0时必须是上一次返回的offset,
+    /// 有表头时表头从文件开头读取. 文件比offset短(被截断或替换)时返回错误
+    pub fn read_from_offset<R>(
+        &mut self,
+        path: impl AsRef<Path>,
+        offset: u64,
+    ) -> AResult<(Vec<R>, u64)>
+    where
+        R: DeserializeOwned + Send + Clone,
+    {
+        let mut file = fs::File::open(path)?;
+        let len = file.metadata()?.len();
+        if offset > len {
+            return Err(eyre!(
+                "csv offset {} is beyond file length {}, file truncated?",
+                offset,
+                len
+            ));
+        }
+        let mut head = Vec::new();
+        (&mut file)
+            .take(DIALECT_SAMPLE_BYTES as u64)
+            .read_to_end(&mut head)?;
+        if self.auto_dialect {
+            self.apply_auto_dialect(&head);
+        }
+
+        let header = if offset > 0 && self.has_header {
+            let (_, _, header) = self.find_starting_point(&head, self.quote_char, self.eol_char)?;
+            let header = header.ok_or_eyre("csv header not found")?;
+            self.csv_reader_builder()
+                .from_reader(header)
+                .records()
+                .next()
+                .transpose()?
+                .map(|header| self.map_header(&header))
+        } else {
+            None
+        };
+
+        file.seek(SeekFrom::Start(offset))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let end = last_record_end(&bytes, self.quote_char, self.eol_char);
+        let bytes = &bytes[..end];
+        let next_offset = offset + end as u64;
+        if bytes.is_empty() {
+            return Ok((Vec::new(), next_offset));
+        }
+        if offset == 0 {
+            return Ok((self.parse_csv_at(bytes, 0, None)?, next_offset));
+        }
+
+        // 从记录边界开始, 没有表头和需要跳过的行
+        let reader = CsvReader {
+            skip_rows_before_header: 0,
+            skip_rows_after_header: 0,
+            n_rows: None,
+            has_header: false,
+            comment_prefix: self.comment_prefix.clone(),
+            header_aliases: HashMap::new(),
+            transforms: self.transforms.clone(),
+            ..*self
+        };
+        let rows = reader.parse_csv_at(bytes, offset as usize, header)?;
+        Ok((rows, next_offset))
+    }
+
     #[cfg(feature = "csv-zip")]
     pub fn read_zip_file<R>(&mut self, path: impl AsRef<Path>) -> AResult<(Vec<R>, String)>
     where
@@ -303,3 +629,264 @@ impl CsvReader {
         Ok((r_vec, zip_file.name().to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::CsvReader;
+    use crate::csv::limits::CsvLimitError;
+    use crate::csv::sidecar::CsvSidecarError;
+
+    #[derive(Debug, Clone, PartialEq, Deserialize)]
+    struct Bar {
+        code:   String,
+        close:  f64,
+        volume: i64,
+    }
+
+    #[test]
+    fn test_header_aliases() {
+        let dir = std::env::temp_dir();
+        let rows = [
+            (
+                "common-rs-header-1.csv",
+                "\u{feff}Code, Close ,Volume\nag,7800.5,12\ncu,1.5,3\n",
+            ),
+            (
+                "common-rs-header-2.csv",
+                "CODE,close,vol\nag,7800.5,12\ncu,1.5,3\n",
+            ),
+            (
+                "common-rs-header-3.csv",
+                "合约,收盘价,成交量\nag,7800.5,12\ncu,1.5,3\n",
+            ),
+        ];
+        for (name, content) in rows {
+            let path = dir.join(name);
+            std::fs::write(&path, content).unwrap();
+            let bars = CsvReader::new()
+                .has_header(true)
+                .with_header_lowercase(true)
+                .with_header_aliases(&[
+                    ("vol", "volume"),
+                    ("成交量", "volume"),
+                    ("合约", "code"),
+                    ("收盘价", "close"),
+                ])
+                .read_csv_file::<Bar>(&path)
+                .unwrap();
+            assert_eq!(
+                bars,
+                vec![
+                    Bar {
+                        code:   "ag".into(),
+                        close:  7800.5,
+                        volume: 12,
+                    },
+                    Bar {
+                        code:   "cu".into(),
+                        close:  1.5,
+                        volume: 3,
+                    },
+                ],
+                "{}",
+                name
+            );
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_transform() {
+        use chrono::NaiveDateTime;
+
+        use crate::csv::transform::{ColumnTransform, CsvTransformError};
+
+        #[derive(Debug, Clone, PartialEq, Deserialize)]
+        struct Tick {
+            code:     String,
+            datetime: NaiveDateTime,
+            update:   Option<NaiveDateTime>,
+        }
+
+        let path = std::env::temp_dir().join("common-rs-transform.csv");
+        std::fs::write(
+            &path,
+            "code,date,time,ts\nag,20240102,93000,20240102093000500\ncu,2024-01-02,21:00:00,\n",
+        )
+        .unwrap();
+        let reader = || {
+            CsvReader::new()
+                .has_header(true)
+                .with_header_aliases(&[("ts", "update")])
+                .with_transform(ColumnTransform::merge_datetime("date", "time", "datetime"))
+                .with_transform(ColumnTransform::compact_datetime("update", "update"))
+        };
+        let ticks = reader().read_csv_file::<Tick>(&path).unwrap();
+        let dt = |v: &str| NaiveDateTime::parse_from_str(v, "%Y-%m-%d %H:%M:%S%.f").unwrap();
+        assert_eq!(
+            ticks,
+            [
+                Tick {
+                    code:     "ag".into(),
+                    datetime: dt("2024-01-02 09:30:00"),
+                    update:   Some(dt("2024-01-02 09:30:00.500")),
+                },
+                Tick {
+                    code:     "cu".into(),
+                    datetime: dt("2024-01-02 21:00:00"),
+                    update:   None,
+                },
+            ]
+        );
+
+        std::fs::write(&path, "code,date,time,ts\nag,20240102,93000,\ncu,20240132,93000,\n").unwrap();
+        let err = reader().read_csv_file::<Tick>(&path).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<CsvTransformError>(),
+            Some(&CsvTransformError::InvalidValue {
+                offset: 37,
+                column: "date".into(),
+                value:  "20240132".into(),
+            })
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_limits() {
+        let path = std::env::temp_dir().join("common-rs-limits.csv");
+        // 第3行缺少结束引号
+        std::fs::write(&path, "code,close,volume\nag,1.5,3\n\"cu,2.5,4\nzn,3.5,5\n").unwrap();
+        let err = CsvReader::new()
+            .has_header(true)
+            .with_max_row_len(18)
+            .read_csv_file::<Bar>(&path)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<CsvLimitError>(),
+            Some(&CsvLimitError::RowTooLong {
+                offset: 27,
+                len:    19,
+                max:    18,
+            })
+        );
+
+        let err = CsvReader::new()
+            .has_header(true)
+            .with_max_columns(2)
+            .read_csv_file::<Bar>(&path)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CsvLimitError>(),
+            Some(CsvLimitError::TooManyColumns { offset: 0, .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_from_offset() {
+        use std::io::Write;
+
+        let path = std::env::temp_dir().join("common-rs-offset.csv");
+        // 最后一行还没写完
+        std::fs::write(&path, "code,close,volume\nag,1.5,3\ncu,2.5").unwrap();
+        let mut reader = CsvReader::new().has_header(true);
+        let (bars, offset) = reader.read_from_offset::<Bar>(&path, 0).unwrap();
+        assert_eq!(bars.len(), 1);
+        assert_eq!(offset, 27);
+
+        let (bars, offset) = reader.read_from_offset::<Bar>(&path, offset).unwrap();
+        assert!(bars.is_empty());
+        assert_eq!(offset, 27);
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b",4\n\"zn\",3.5,5\n").unwrap();
+        let (bars, offset) = reader.read_from_offset::<Bar>(&path, offset).unwrap();
+        assert_eq!(
+            bars,
+            [
+                Bar {
+                    code:   "cu".to_string(),
+                    close:  2.5,
+                    volume: 4,
+                },
+                Bar {
+                    code:   "zn".to_string(),
+                    close:  3.5,
+                    volume: 5,
+                },
+            ]
+        );
+        assert_eq!(offset, std::fs::metadata(&path).unwrap().len());
+
+        assert!(reader.read_from_offset::<Bar>(&path, offset + 1).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_verify_sidecar() {
+        let dir = std::env::temp_dir().join("common-rs-sidecar");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bars.csv");
+        let content = "code,close,volume\nag,1.5,3\ncu,2.5,4\n";
+        std::fs::write(&path, content).unwrap();
+        let mut reader = CsvReader::new().has_header(true);
+
+        let err = reader.verify_sidecar::<Bar>(&path).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CsvSidecarError>(),
+            Some(CsvSidecarError::Missing { .. })
+        ));
+
+        // md5sum的输出格式
+        std::fs::write(
+            dir.join("bars.csv.md5"),
+            "48b1dc9a1c8e3a7a0c35c7b0f5d8b2a1  bars.csv\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("bars.cnt"), "2\n").unwrap();
+        let err = reader.verify_sidecar::<Bar>(&path).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CsvSidecarError>(),
+            Some(CsvSidecarError::ChecksumMismatch { expected, .. })
+                if expected == "48b1dc9a1c8e3a7a0c35c7b0f5d8b2a1"
+        ));
+
+        std::fs::write(dir.join("bars.csv.md5"), "8D0C5B5A3E1B1C8D7E6F5A4B3C2D1E0F").unwrap();
+        let err = reader.verify_sidecar::<Bar>(&path).unwrap_err();
+        let Some(CsvSidecarError::ChecksumMismatch { actual, .. }) =
+            err.downcast_ref::<CsvSidecarError>()
+        else {
+            panic!("{:?}", err);
+        };
+        std::fs::write(dir.join("bars.csv.md5"), actual).unwrap();
+        assert_eq!(reader.verify_sidecar::<Bar>(&path).unwrap().len(), 2);
+
+        // 下载不完整
+        std::fs::remove_file(dir.join("bars.csv.md5")).unwrap();
+        std::fs::write(&path, &content[..27]).unwrap();
+        let err = reader.verify_sidecar::<Bar>(&path).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<CsvSidecarError>(),
+            Some(&CsvSidecarError::RowCountMismatch {
+                path:     path.clone(),
+                expected: 2,
+                actual:   1,
+            })
+        );
+
+        std::fs::write(dir.join("bars.cnt"), "two").unwrap();
+        let err = reader.verify_sidecar::<Bar>(&path).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CsvSidecarError>(),
+            Some(CsvSidecarError::Invalid { .. })
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
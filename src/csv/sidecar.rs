@@ -0,0 +1,146 @@
+//! 数据商随csv提供的校验文件: `.md5`(文件的md5)和`.cnt`(数据行数, 不含表头)
+//!
+//! 校验文件名为`data.csv.md5`或`data.md5`, md5文件的内容可以是`md5sum`的输出格式(`hash  文件名`)
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use md5::{Digest, Md5};
+
+use crate::AResult;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CsvSidecarError {
+    #[error("csv sidecar not found: {path}.md5|.cnt")]
+    Missing { path: PathBuf },
+
+    #[error("csv sidecar invalid: {path}, {content}")]
+    Invalid { path: PathBuf, content: String },
+
+    #[error("csv checksum mismatch: {path}, expected {expected}, actual {actual}")]
+    ChecksumMismatch {
+        path:     PathBuf,
+        expected: String,
+        actual:   String,
+    },
+
+    #[error("csv row count mismatch: {path}, expected {expected}, actual {actual}")]
+    RowCountMismatch {
+        path:     PathBuf,
+        expected: usize,
+        actual:   usize,
+    },
+}
+
+fn md5_hex(bytes: &[u8]) -> String {
+    Md5::digest(bytes)
+        .iter()
+        .map(|v| format!("{:02x}", v))
+        .collect()
+}
+
+/// 先找`data.csv.ext`, 再找`data.ext`, 都不存在时为None
+fn read_sidecar(path: &Path, ext: &str) -> io::Result<Option<(PathBuf, String)>> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(ext);
+    for sidecar_path in [path.with_file_name(name), path.with_extension(ext)] {
+        match fs::read_to_string(&sidecar_path) {
+            Ok(content) => return Ok(Some((sidecar_path, content.trim().to_owned()))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Sidecar {
+    path: PathBuf,
+    md5:  Option<String>,
+    rows: Option<usize>,
+}
+
+impl Sidecar {
+    /// 两个校验文件都不存在时返回`CsvSidecarError::Missing`
+    pub(crate) fn load(path: &Path) -> AResult<Sidecar> {
+        let md5 = match read_sidecar(path, "md5")? {
+            Some((sidecar_path, content)) => {
+                let hash = content
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+                if hash.len() != 32 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                    Err(CsvSidecarError::Invalid {
+                        path: sidecar_path,
+                        content,
+                    })?;
+                }
+                Some(hash)
+            },
+            None => None,
+        };
+        let rows = match read_sidecar(path, "cnt")? {
+            Some((sidecar_path, content)) => {
+                Some(
+                    content
+                        .parse::<usize>()
+                        .map_err(|_| CsvSidecarError::Invalid {
+                            path: sidecar_path,
+                            content,
+                        })?,
+                )
+            },
+            None => None,
+        };
+        if md5.is_none() && rows.is_none() {
+            Err(CsvSidecarError::Missing {
+                path: path.to_path_buf(),
+            })?;
+        }
+        Ok(Sidecar {
+            path: path.to_path_buf(),
+            md5,
+            rows,
+        })
+    }
+
+    /// 解析前检查文件内容
+    pub(crate) fn check_md5(&self, bytes: &[u8]) -> Result<(), CsvSidecarError> {
+        let Some(expected) = &self.md5 else {
+            return Ok(());
+        };
+        let actual = md5_hex(bytes);
+        if *expected != actual {
+            return Err(CsvSidecarError::ChecksumMismatch {
+                path: self.path.clone(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// 解析后检查数据行数
+    pub(crate) fn check_rows(&self, actual: usize) -> Result<(), CsvSidecarError> {
+        match self.rows {
+            Some(expected) if expected != actual => Err(CsvSidecarError::RowCountMismatch {
+                path: self.path.clone(),
+                expected,
+                actual,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::md5_hex;
+
+    #[test]
+    fn test_md5_hex() {
+        assert_eq!(md5_hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(md5_hex(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+    }
+}
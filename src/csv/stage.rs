@@ -0,0 +1,396 @@
+//! 供应商csv文件按映射转换为规范的csv后, 通过LOAD DATA导入数据库
+//!
+//! 映射文件(toml):
+//! ```toml
+//! has-header = true
+//! # 不设置时自动检测
+//! separator = ","
+//!
+//! [[column]]
+//! source = "TradingDay"  # 表头中的名称, 没有表头时用index
+//! target = "trade_day"   # 导入的表中的列名
+//! type = "date"          # string, int, decimal, date, datetime, time
+//! format = "%Y%m%d"      # 日期时间的解析格式
+//! ```
+//!
+//! 输出的文件没有表头, 按column的顺序输出, 转义字符和行结束符按`with_output_dialect`的设置(默认为`\`和`\n`),
+//! 空值输出为`\N`(没有转义字符时为`NULL`). 导入时按target的列名和输出的格式生成LOAD DATA,
+//! LOAD DATA配置中只使用LOCAL, 列分隔符和SET, col-*和ignore-rows不使用
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use eyre::{eyre, OptionExt};
+use serde::Deserialize;
+use sqlx::types::Decimal;
+use sqlx::MySqlPool;
+use uuid::Uuid;
+
+use super::dialect::CsvDialect;
+use super::read::CsvReader;
+use super::write::{CsvRow, CsvWriter};
+use crate::mysqlx::column as column_ident;
+use crate::mysqlx::exec::{exec_sql, ExecInfo};
+use crate::sql_loader::{LdiFormat, SqlLoader};
+use crate::{toml, AResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    #[default]
+    String,
+    Int,
+    Decimal,
+    Date,
+    Datetime,
+    Time,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StageColumn {
+    #[serde(rename = "source", default)]
+    source:      Option<String>,
+    #[serde(rename = "index", default)]
+    index:       Option<usize>,
+    #[serde(rename = "target")]
+    target:      String,
+    #[serde(rename = "type", default)]
+    column_type: ColumnType,
+    #[serde(rename = "format", default)]
+    format:      Option<String>,
+}
+
+impl StageColumn {
+    /// 转换单元格, 空值返回None
+    fn coerce(&self, value: &str) -> AResult<Option<String>> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(None);
+        }
+        let format = self.format.as_deref();
+        let value = match self.column_type {
+            ColumnType::String => value.to_owned(),
+            ColumnType::Int => value.parse::<i64>()?.to_string(),
+            ColumnType::Decimal => Decimal::from_str(value)?.to_string(),
+            ColumnType::Date => {
+                let date = NaiveDate::parse_from_str(value, format.unwrap_or("%Y-%m-%d"))?;
+                date.format("%Y-%m-%d").to_string()
+            },
+            ColumnType::Datetime => {
+                let dt =
+                    NaiveDateTime::parse_from_str(value, format.unwrap_or("%Y-%m-%d %H:%M:%S%.f"))?;
+                dt.format("%Y-%m-%d %H:%M:%S%.f").to_string()
+            },
+            ColumnType::Time => {
+                let time = NaiveTime::parse_from_str(value, format.unwrap_or("%H:%M:%S%.f"))?;
+                time.format("%H:%M:%S%.f").to_string()
+            },
+        };
+        Ok(Some(value))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CsvStage {
+    #[serde(rename = "has-header", default)]
+    has_header:  bool,
+    #[serde(rename = "separator", default)]
+    separator:   Option<char>,
+    #[serde(rename = "column")]
+    columns:     Vec<StageColumn>,
+    #[serde(skip)]
+    staging_dir: Option<PathBuf>,
+    #[serde(skip, default = "default_output")]
+    output:      CsvDialect,
+}
+
+/// LOAD DATA默认的格式, 用`\`转义, 行结束符为`\n`
+fn default_output() -> CsvDialect {
+    CsvDialect {
+        quote_char: None,
+        escape_char: Some(b'\\'),
+        ..Default::default()
+    }
+}
+
+fn line_terminator(output: &CsvDialect) -> String {
+    if output.crlf {
+        "\r\n".to_owned()
+    } else {
+        char::from(output.eol_char).to_string()
+    }
+}
+
+/// 空值, 没有转义字符时LOAD DATA把`NULL`作为空值
+fn null(output: &CsvDialect) -> String {
+    match output.escape_char {
+        Some(escape_char) => format!("{}N", char::from(escape_char)),
+        None => "NULL".to_owned(),
+    }
+}
+
+/// 导入结果
+#[derive(Debug)]
+pub struct StageInfo {
+    /// 写入临时文件的行数
+    pub rows: usize,
+    pub exec: ExecInfo,
+}
+
+struct StagedRow(String);
+
+impl CsvRow for StagedRow {
+    fn csv_row(&self) -> String {
+        self.0.clone()
+    }
+}
+
+/// 按output的转义字符转义, 没有转义字符时值中不能有分隔符和换行
+fn escape(value: &str, separator: &str, output: &CsvDialect) -> AResult<String> {
+    let Some(escape_char) = output.escape_char.map(char::from) else {
+        if value.contains(separator) || value.contains(['\r', '\n']) {
+            Err(eyre!("separator or line break without escape char"))?;
+        }
+        return Ok(value.to_owned());
+    };
+    Ok(value
+        .replace(escape_char, &format!("{0}{0}", escape_char))
+        .replace(separator, &format!("{}{}", escape_char, separator))
+        .replace('\r', &format!("{}r", escape_char))
+        .replace('\n', &format!("{}n", escape_char)))
+}
+
+impl CsvStage {
+    pub fn from_toml(path: impl AsRef<Path>) -> AResult<CsvStage> {
+        let stage = toml::parse_from_file::<_, CsvStage>(path)?;
+        if stage.columns.is_empty() {
+            Err(eyre!("stage mapping has no column"))?;
+        }
+        for column in stage.columns.iter() {
+            if column.source.is_none() && column.index.is_none() {
+                Err(eyre!("column {} need source or index", column.target))?;
+            }
+            if column.source.is_some() && !stage.has_header {
+                Err(eyre!("column {} use source without header", column.target))?;
+            }
+            column_ident(&column.target)?;
+        }
+        Ok(stage)
+    }
+
+    /// 临时文件的目录, 不是LOCAL时需要数据库服务器能读取到, 默认为系统临时目录
+    pub fn with_staging_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.staging_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// 输出文件的转义字符和行结束符, 列分隔符由LOAD DATA配置决定
+    pub fn with_output_dialect(mut self, output: CsvDialect) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// 读取src, 转换后写入dst, 返回行数
+    pub fn normalize(
+        &self,
+        src: impl AsRef<Path>,
+        dst: impl AsRef<Path>,
+        separator: &str,
+    ) -> AResult<usize> {
+        let src = src.as_ref();
+        let mut reader = CsvReader::new().has_header(false);
+        reader = match self.separator {
+            Some(separator) => reader.with_separator(separator as u8),
+            None => reader.with_auto_dialect(),
+        };
+        let records = reader.read_csv_file::<Vec<String>>(src)?;
+        let (header, records) = if self.has_header {
+            let (header, records) = records
+                .split_first()
+                .ok_or_eyre(format!("empty csv: {}", src.display()))?;
+            (Some(header), records)
+        } else {
+            (None, records.as_slice())
+        };
+        let indexes = self
+            .columns
+            .iter()
+            .map(|column| match (&column.source, column.index) {
+                (Some(source), _) => header
+                    .and_then(|header| header.iter().position(|v| v.trim() == source))
+                    .ok_or_eyre(format!(
+                        "column {} not in header: {}",
+                        source,
+                        src.display()
+                    )),
+                (None, Some(index)) => Ok(index),
+                (None, None) => Err(eyre!("column {} need source or index", column.target)),
+            })
+            .collect::<AResult<Vec<_>>>()?;
+
+        let null = null(&self.output);
+        let mut rows = Vec::with_capacity(records.len());
+        for (line, record) in records.iter().enumerate() {
+            let mut fields = Vec::with_capacity(indexes.len());
+            for (column, index) in self.columns.iter().zip(indexes.iter()) {
+                let value = record.get(*index).map(|v| v.as_str()).unwrap_or_default();
+                let field = column
+                    .coerce(value)
+                    .and_then(|v| match v {
+                        Some(v) => escape(&v, separator, &self.output),
+                        None => Ok(null.clone()),
+                    })
+                    .map_err(|e| {
+                        eyre!(
+                            "{} row {} column {}: {:?} {}",
+                            src.display(),
+                            line + 1,
+                            column.target,
+                            value,
+                            e
+                        )
+                    })?;
+                fields.push(field);
+            }
+            rows.push(StagedRow(fields.join(separator)));
+        }
+        let file = BufWriter::new(File::create(dst)?);
+        let mut writer = CsvWriter::new(file);
+        writer.line_terminator = line_terminator(&self.output);
+        writer.finish(&rows)?;
+        Ok(rows.len())
+    }
+
+    /// 转换src后用ldi_name的LOAD DATA配置导入database.tbl_name, 完成后删除临时文件
+    pub async fn load(
+        &self,
+        pool: &MySqlPool,
+        src: impl AsRef<Path>,
+        ldi_name: &str,
+        database: &str,
+        tbl_name: &str,
+    ) -> AResult<StageInfo> {
+        let sql_loader = SqlLoader::get();
+        let separator = sql_loader.load_data_infile_terminated(ldi_name)?;
+        let dir = self.staging_dir.clone().unwrap_or_else(std::env::temp_dir);
+        let dst = dir.join(format!("stage-{}.csv", Uuid::now_v7().simple()));
+        let rows = self.normalize(src, &dst, separator)?;
+        let ldi_file = dst.to_string_lossy().replace('\\', "/");
+        let format = LdiFormat {
+            columns:          self.columns.iter().map(|v| v.target.clone()).collect(),
+            escaped_by:       self.output.escape_char.map(char::from),
+            lines_terminated: line_terminator(&self.output),
+        };
+        let sql = sql_loader.load_data_infile_with(ldi_name, &ldi_file, database, tbl_name, &format)?;
+        let r = exec_sql(pool, &sql).await;
+        let _ = fs::remove_file(&dst);
+        Ok(StageInfo { rows, exec: r? })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::CsvStage;
+    use crate::csv::dialect::CsvDialect;
+
+    const MAPPING: &str = r#"
+has-header = true
+separator = ","
+
+[[column]]
+source = "InstrumentID"
+target = "code"
+
+[[column]]
+source = "TradingDay"
+target = "trade_day"
+type = "date"
+format = "%Y%m%d"
+
+[[column]]
+source = "LastPrice"
+target = "price"
+type = "decimal"
+
+[[column]]
+index = 3
+target = "volume"
+type = "int"
+"#;
+
+    #[test]
+    fn test_normalize() {
+        let dir = std::env::temp_dir().join("common-rs-stage");
+        fs::create_dir_all(&dir).unwrap();
+        let mapping = dir.join("mapping.toml");
+        fs::write(&mapping, MAPPING).unwrap();
+        let src = dir.join("vendor.csv");
+        fs::write(
+            &src,
+            "InstrumentID,TradingDay,LastPrice,Volume\nag2408,20240603,7800.5,12\nau\\2408,20240603,,3\n",
+        )
+        .unwrap();
+
+        let stage = CsvStage::from_toml(&mapping).unwrap();
+        let dst = dir.join("stage.csv");
+        let rows = stage.normalize(&src, &dst, ",").unwrap();
+        assert_eq!(rows, 2);
+        let content = fs::read_to_string(&dst).unwrap();
+        assert_eq!(
+            content,
+            "ag2408,2024-06-03,7800.5,12\nau\\\\2408,2024-06-03,\\N,3\n"
+        );
+
+        fs::write(
+            &src,
+            "InstrumentID,TradingDay,LastPrice,Volume\nag2408,2024-06,1,1\n",
+        )
+        .unwrap();
+        let err = stage.normalize(&src, &dst, ",").unwrap_err();
+        println!("{}", err);
+
+        // decimal不通过f64解析
+        for price in ["NaN", "inf", "1.2.3"] {
+            fs::write(
+                &src,
+                format!("InstrumentID,TradingDay,LastPrice,Volume\nag2408,20240603,{},1\n", price),
+            )
+            .unwrap();
+            assert!(stage.normalize(&src, &dst, ",").is_err(), "{}", price);
+        }
+        fs::write(
+            &src,
+            "InstrumentID,TradingDay,LastPrice,Volume\nag2408,20240603,12345678901234567.891,1\n",
+        )
+        .unwrap();
+        stage.normalize(&src, &dst, ",").unwrap();
+        let content = fs::read_to_string(&dst).unwrap();
+        assert_eq!(content, "ag2408,2024-06-03,12345678901234567.891,1\n");
+
+        // 没有转义字符, 行结束符为\r\n
+        let stage = stage.with_output_dialect(CsvDialect {
+            escape_char: None,
+            crlf: true,
+            ..Default::default()
+        });
+        fs::write(
+            &src,
+            "InstrumentID,TradingDay,LastPrice,Volume\nag2408,20240603,,12\n",
+        )
+        .unwrap();
+        stage.normalize(&src, &dst, ",").unwrap();
+        let content = fs::read_to_string(&dst).unwrap();
+        assert_eq!(content, "ag2408,2024-06-03,NULL,12\r\n");
+        fs::write(
+            &src,
+            "InstrumentID,TradingDay,LastPrice,Volume\n\"ag,2408\",20240603,,12\n",
+        )
+        .unwrap();
+        assert!(stage.normalize(&src, &dst, ",").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,338 @@
+//! 反序列化前对列的转换, 把数据商的日期时间列转为`NaiveDateTime`能解析的格式
+//!
+//! 转换后的值为`%Y-%m-%dT%H:%M:%S%.f`, 结构体的字段直接用`NaiveDateTime`; 空值保持为空,
+//! 可以用`Option<NaiveDateTime>`. 列按表头(别名替换后)的名称查找, 需要有表头
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+/// offset为记录在文件中的字节位置
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CsvTransformError {
+    #[error("csv transform needs header")]
+    NoHeader,
+
+    #[error("csv transform column not found: {0}")]
+    ColumnNotFound(String),
+
+    #[error("csv transform invalid value at byte {offset}: column {column}, {value}")]
+    InvalidValue {
+        offset: usize,
+        column: String,
+        value:  String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnTransform {
+    /// 日期列和时间列合并为into列, 在日期列的位置, 原来的两列去掉
+    ///
+    /// 格式为None时自动识别: 日期`20240102`, `2024-01-02`, `2024/01/02`;
+    /// 时间`093000`, `93000`, `09:30:00`, `09:30:00.500`, `09:30`
+    MergeDateTime {
+        date:     String,
+        time:     String,
+        into:     String,
+        date_fmt: Option<String>,
+        time_fmt: Option<String>,
+    },
+    /// 整数形式的日期时间转换后改名为into, 位置不变; 支持的长度:
+    /// 8(`yyyymmdd`), 12(`yyyymmddHHMM`), 14(`yyyymmddHHMMSS`), 17(`yyyymmddHHMMSSmmm`)
+    CompactDateTime { column: String, into: String },
+}
+
+impl ColumnTransform {
+    pub fn merge_datetime(date: &str, time: &str, into: &str) -> ColumnTransform {
+        ColumnTransform::MergeDateTime {
+            date:     date.to_owned(),
+            time:     time.to_owned(),
+            into:     into.to_owned(),
+            date_fmt: None,
+            time_fmt: None,
+        }
+    }
+
+    /// chrono的格式, 如: ("%d/%m/%Y", "%H%M%S")
+    pub fn merge_datetime_fmt(
+        date: &str,
+        time: &str,
+        into: &str,
+        date_fmt: &str,
+        time_fmt: &str,
+    ) -> ColumnTransform {
+        ColumnTransform::MergeDateTime {
+            date:     date.to_owned(),
+            time:     time.to_owned(),
+            into:     into.to_owned(),
+            date_fmt: Some(date_fmt.to_owned()),
+            time_fmt: Some(time_fmt.to_owned()),
+        }
+    }
+
+    /// column和into相同时只转换不改名
+    pub fn compact_datetime(column: &str, into: &str) -> ColumnTransform {
+        ColumnTransform::CompactDateTime {
+            column: column.to_owned(),
+            into:   into.to_owned(),
+        }
+    }
+}
+
+fn format_datetime(datetime: NaiveDateTime) -> String {
+    datetime.format("%Y-%m-%dT%H:%M:%S%.f").to_string()
+}
+
+fn parse_date(v: &str, fmt: Option<&str>) -> Option<NaiveDate> {
+    if let Some(fmt) = fmt {
+        return NaiveDate::parse_from_str(v, fmt).ok();
+    }
+    if v.len() == 8 && v.bytes().all(|c| c.is_ascii_digit()) {
+        return NaiveDate::parse_from_str(v, "%Y%m%d").ok();
+    }
+    NaiveDate::parse_from_str(&v.replace('/', "-"), "%Y-%m-%d").ok()
+}
+
+fn parse_time(v: &str, fmt: Option<&str>) -> Option<NaiveTime> {
+    if let Some(fmt) = fmt {
+        return NaiveTime::parse_from_str(v, fmt).ok();
+    }
+    if v.bytes().all(|c| c.is_ascii_digit()) {
+        // 整数形式时9点前的时间没有前导0
+        if v.is_empty() || v.len() > 6 {
+            return None;
+        }
+        return NaiveTime::parse_from_str(&format!("{:0>6}", v), "%H%M%S").ok();
+    }
+    NaiveTime::parse_from_str(v, "%H:%M:%S%.f")
+        .or_else(|_| NaiveTime::parse_from_str(v, "%H:%M"))
+        .ok()
+}
+
+fn parse_compact(v: &str) -> Option<NaiveDateTime> {
+    if !v.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    match v.len() {
+        8 => NaiveDate::parse_from_str(v, "%Y%m%d")
+            .ok()
+            .map(|v| v.and_time(NaiveTime::MIN)),
+        12 => NaiveDateTime::parse_from_str(&format!("{}00", v), "%Y%m%d%H%M%S").ok(),
+        14 => NaiveDateTime::parse_from_str(v, "%Y%m%d%H%M%S").ok(),
+        17 => {
+            let datetime = NaiveDateTime::parse_from_str(&v[..14], "%Y%m%d%H%M%S").ok()?;
+            let millis = v[14..].parse::<u32>().ok()?;
+            datetime.with_nanosecond(millis * 1_000_000)
+        },
+        _ => None,
+    }
+}
+
+enum Step {
+    Merge {
+        date:     usize,
+        time:     usize,
+        date_fmt: Option<String>,
+        time_fmt: Option<String>,
+    },
+    Compact(usize),
+}
+
+/// 按表头解析后的转换, 每个分块共用
+pub(crate) struct TransformPlan {
+    steps:  Vec<(Step, String)>,
+    /// 转换后每一列的来源, Ok为原始列的下标, Err为转换结果(steps的下标)
+    layout: Vec<Result<usize, usize>>,
+    header: csv::StringRecord,
+}
+
+impl TransformPlan {
+    pub(crate) fn new(
+        transforms: &[ColumnTransform],
+        header: Option<&csv::StringRecord>,
+    ) -> Result<TransformPlan, CsvTransformError> {
+        let header = header.ok_or(CsvTransformError::NoHeader)?;
+        let position = |name: &str| {
+            header
+                .iter()
+                .position(|v| v == name)
+                .ok_or_else(|| CsvTransformError::ColumnNotFound(name.to_owned()))
+        };
+        let mut layout = (0..header.len()).map(Ok).collect::<Vec<_>>();
+        let mut steps = Vec::with_capacity(transforms.len());
+        for (i, transform) in transforms.iter().enumerate() {
+            let (step, into, at, removed) = match transform {
+                ColumnTransform::MergeDateTime {
+                    date,
+                    time,
+                    into,
+                    date_fmt,
+                    time_fmt,
+                } => {
+                    let (date, time) = (position(date)?, position(time)?);
+                    let step = Step::Merge {
+                        date,
+                        time,
+                        date_fmt: date_fmt.clone(),
+                        time_fmt: time_fmt.clone(),
+                    };
+                    (step, into, date, Some(time))
+                },
+                ColumnTransform::CompactDateTime { column, into } => {
+                    let column = position(column)?;
+                    (Step::Compact(column), into, column, None)
+                },
+            };
+            // 同一列被前面的转换用掉时找不到
+            let slot = layout
+                .iter()
+                .position(|v| *v == Ok(at))
+                .ok_or_else(|| CsvTransformError::ColumnNotFound(header[at].to_owned()))?;
+            layout[slot] = Err(i);
+            if let Some(removed) = removed {
+                if !layout.contains(&Ok(removed)) {
+                    return Err(CsvTransformError::ColumnNotFound(
+                        header[removed].to_owned(),
+                    ));
+                }
+                layout.retain(|v| *v != Ok(removed));
+            }
+            steps.push((step, into.clone()));
+        }
+        let header = layout
+            .iter()
+            .map(|v| match v {
+                Ok(idx) => header[*idx].to_owned(),
+                Err(step) => steps[*step].1.clone(),
+            })
+            .collect();
+        Ok(TransformPlan {
+            steps,
+            layout,
+            header,
+        })
+    }
+
+    /// 转换后的表头
+    pub(crate) fn header(&self) -> &csv::StringRecord {
+        &self.header
+    }
+
+    /// base_offset为record所在分块在文件中的位置
+    pub(crate) fn apply(
+        &self,
+        record: &csv::StringRecord,
+        source_header: &csv::StringRecord,
+        base_offset: usize,
+    ) -> Result<csv::StringRecord, CsvTransformError> {
+        let field = |idx: usize| record.get(idx).unwrap_or("").trim();
+        let invalid = |idx: usize, value: String| CsvTransformError::InvalidValue {
+            offset: base_offset + record.position().map_or(0, |v| v.byte() as usize),
+            column: source_header.get(idx).unwrap_or("").to_owned(),
+            value,
+        };
+        let values = self
+            .steps
+            .iter()
+            .map(|(step, _)| match step {
+                Step::Merge {
+                    date,
+                    time,
+                    date_fmt,
+                    time_fmt,
+                } => {
+                    let (date_v, time_v) = (field(*date), field(*time));
+                    if date_v.is_empty() && time_v.is_empty() {
+                        return Ok(String::new());
+                    }
+                    let date_v = parse_date(date_v, date_fmt.as_deref())
+                        .ok_or_else(|| invalid(*date, date_v.to_owned()))?;
+                    let time_v = parse_time(time_v, time_fmt.as_deref())
+                        .ok_or_else(|| invalid(*time, time_v.to_owned()))?;
+                    Ok(format_datetime(date_v.and_time(time_v)))
+                },
+                Step::Compact(idx) => {
+                    let v = field(*idx);
+                    if v.is_empty() {
+                        return Ok(String::new());
+                    }
+                    parse_compact(v)
+                        .map(format_datetime)
+                        .ok_or_else(|| invalid(*idx, v.to_owned()))
+                },
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut out = csv::StringRecord::with_capacity(record.as_slice().len(), self.layout.len());
+        for v in self.layout.iter() {
+            match v {
+                Ok(idx) => out.push_field(record.get(*idx).unwrap_or("")),
+                Err(step) => out.push_field(&values[*step]),
+            }
+        }
+        out.set_position(record.position().cloned());
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColumnTransform, CsvTransformError, TransformPlan};
+
+    #[test]
+    fn test_transform_plan() {
+        let header = csv::StringRecord::from(vec!["code", "date", "time", "close", "ts"]);
+        let plan = TransformPlan::new(
+            &[
+                ColumnTransform::merge_datetime("date", "time", "datetime"),
+                ColumnTransform::compact_datetime("ts", "ts"),
+            ],
+            Some(&header),
+        )
+        .unwrap();
+        assert_eq!(plan.header(), &vec!["code", "datetime", "close", "ts"]);
+
+        let cases = [
+            (
+                vec!["ag", "20240102", "93000", "1.5", "20240102093000"],
+                vec!["ag", "2024-01-02T09:30:00", "1.5", "2024-01-02T09:30:00"],
+            ),
+            (
+                vec!["ag", "2024/01/02", "21:00:00.500", "1.5", "20240102210000500"],
+                vec![
+                    "ag",
+                    "2024-01-02T21:00:00.500",
+                    "1.5",
+                    "2024-01-02T21:00:00.500",
+                ],
+            ),
+            (
+                vec!["ag", "", "", "1.5", "202401022359"],
+                vec!["ag", "", "1.5", "2024-01-02T23:59:00"],
+            ),
+        ];
+        for (record, expected) in cases {
+            let record = csv::StringRecord::from(record);
+            assert_eq!(plan.apply(&record, &header, 0).unwrap(), expected);
+        }
+
+        let record = csv::StringRecord::from(vec!["ag", "20240102", "250000", "1.5", ""]);
+        assert_eq!(
+            plan.apply(&record, &header, 0).unwrap_err(),
+            CsvTransformError::InvalidValue {
+                offset: 0,
+                column: "time".into(),
+                value:  "250000".into(),
+            }
+        );
+
+        assert_eq!(
+            TransformPlan::new(
+                &[ColumnTransform::compact_datetime("datetime", "datetime")],
+                Some(&header)
+            )
+            .err(),
+            Some(CsvTransformError::ColumnNotFound("datetime".into()))
+        );
+        assert_eq!(
+            TransformPlan::new(&[ColumnTransform::compact_datetime("ts", "ts")], None).err(),
+            Some(CsvTransformError::NoHeader)
+        );
+    }
+}
@@ -0,0 +1,426 @@
+//! 常用行情数据源的csv格式, 直接读取为KLineItem/Tick
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use eyre::{eyre, OptionExt};
+use rust_decimal::Decimal;
+
+use super::read::CsvReader;
+use crate::qh::klineitem::KLineItem;
+use crate::qh::period::Period;
+use crate::qh::tick2bar::Tick;
+use crate::AResult;
+
+const DATE_FORMATS: [&str; 3] = ["%Y%m%d", "%Y/%m/%d", "%Y-%m-%d"];
+const TIME_FORMATS: [&str; 5] = ["%H:%M:%S%.f", "%H:%M:%S", "%H%M%S", "%H:%M", "%H%M"];
+
+/// 数据源
+/// CtpTick: CTP深度行情落地的csv, 有表头, 按列名取值
+///     (InstrumentID, ActionDay, UpdateTime, UpdateMillisec, LastPrice, Volume, OpenInterest)
+/// Tdx1m: 通达信导出的1分钟线, 前两行为标题, 最后一行为"数据来源:通达信",
+///     列为: 日期(2024/06/03),时间(0931),开盘,最高,最低,收盘,成交量,成交额, 代码取自文件名(SH#600000.txt)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vendor {
+    CtpTick,
+    Tdx1m,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Column {
+    Index(usize),
+    Name(&'static str),
+}
+
+#[derive(Debug)]
+struct VendorColumns {
+    code:          Option<Column>,
+    date:          Column,
+    time:          Option<Column>,
+    millis:        Option<Column>,
+    open:          Option<Column>,
+    high:          Option<Column>,
+    low:           Option<Column>,
+    close:         Option<Column>,
+    price:         Option<Column>,
+    volume:        Column,
+    amount:        Option<Column>,
+    open_interest: Option<Column>,
+}
+
+impl Vendor {
+    fn columns(&self) -> VendorColumns {
+        match self {
+            Vendor::CtpTick => VendorColumns {
+                code:          Some(Column::Name("InstrumentID")),
+                date:          Column::Name("ActionDay"),
+                time:          Some(Column::Name("UpdateTime")),
+                millis:        Some(Column::Name("UpdateMillisec")),
+                open:          None,
+                high:          None,
+                low:           None,
+                close:         None,
+                price:         Some(Column::Name("LastPrice")),
+                volume:        Column::Name("Volume"),
+                amount:        Some(Column::Name("Turnover")),
+                open_interest: Some(Column::Name("OpenInterest")),
+            },
+            Vendor::Tdx1m => VendorColumns {
+                code:          None,
+                date:          Column::Index(0),
+                time:          Some(Column::Index(1)),
+                millis:        None,
+                open:          Some(Column::Index(2)),
+                high:          Some(Column::Index(3)),
+                low:           Some(Column::Index(4)),
+                close:         Some(Column::Index(5)),
+                price:         None,
+                volume:        Column::Index(6),
+                amount:        Some(Column::Index(7)),
+                open_interest: None,
+            },
+        }
+    }
+
+    fn has_header(&self) -> bool {
+        matches!(self, Vendor::CtpTick)
+    }
+
+    fn skip_rows(&self) -> usize {
+        match self {
+            Vendor::CtpTick => 0,
+            Vendor::Tdx1m => 2,
+        }
+    }
+
+    fn period(&self) -> Option<Period> {
+        match self {
+            Vendor::CtpTick => None,
+            Vendor::Tdx1m => Some(Period::M1),
+        }
+    }
+}
+
+/// 按数据源的列映射解析出的一行
+#[derive(Debug, Clone, PartialEq)]
+pub struct VendorRecord {
+    pub vendor:        Vendor,
+    pub code:          String,
+    pub datetime:      NaiveDateTime,
+    pub open:          Option<Decimal>,
+    pub high:          Option<Decimal>,
+    pub low:           Option<Decimal>,
+    pub close:         Option<Decimal>,
+    pub price:         Option<Decimal>,
+    pub volume:        i64,
+    pub amount:        Option<Decimal>,
+    pub open_interest: Option<i64>,
+}
+
+/// 从VendorRecord转换
+pub trait FromVendorRecord: Sized {
+    fn from_vendor_record(record: VendorRecord) -> AResult<Self>;
+}
+
+impl FromVendorRecord for VendorRecord {
+    fn from_vendor_record(record: VendorRecord) -> AResult<Self> {
+        Ok(record)
+    }
+}
+
+impl FromVendorRecord for KLineItem {
+    fn from_vendor_record(record: VendorRecord) -> AResult<Self> {
+        let period = record
+            .vendor
+            .period()
+            .ok_or_else(|| eyre!("{:?} has no kline data", record.vendor))?;
+        let field = |v: Option<Decimal>, name: &str| {
+            v.ok_or_else(|| eyre!("{:?} missing {}", record.vendor, name))
+        };
+        let mut item = KLineItem::new(&record.code, &record.datetime, period.into());
+        item.open = field(record.open, "open")?;
+        item.high = field(record.high, "high")?;
+        item.low = field(record.low, "low")?;
+        item.close = field(record.close, "close")?;
+        item.volume = record.volume;
+        item.open_oi = record.open_interest.unwrap_or_default();
+        item.close_oi = item.open_oi;
+        Ok(item)
+    }
+}
+
+impl FromVendorRecord for Tick {
+    fn from_vendor_record(record: VendorRecord) -> AResult<Self> {
+        let price = record
+            .price
+            .or(record.close)
+            .ok_or_else(|| eyre!("{:?} missing price", record.vendor))?;
+        Ok(Tick {
+            code: record.code,
+            datetime: record.datetime,
+            price,
+            total_volume: record.volume,
+            open_interest: record.open_interest.unwrap_or_default(),
+        })
+    }
+}
+
+/// 按数据源格式读取csv, 通过`CsvReader::preset`创建
+#[derive(Debug, Clone)]
+pub struct VendorCsvReader {
+    vendor: Vendor,
+    code:   Option<String>,
+}
+
+impl CsvReader {
+    pub fn preset(vendor: Vendor) -> VendorCsvReader {
+        VendorCsvReader { vendor, code: None }
+    }
+}
+
+fn parse_date(s: &str) -> Option<NaiveDate> {
+    DATE_FORMATS
+        .iter()
+        .find_map(|f| NaiveDate::parse_from_str(s, f).ok())
+}
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    TIME_FORMATS
+        .iter()
+        .find_map(|f| NaiveTime::parse_from_str(s, f).ok())
+}
+
+/// 代码取文件名中#后的部分, 如SH#600000.txt -> 600000
+fn code_from_path(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let code = stem.rsplit('#').next().unwrap_or(&stem);
+    (!code.is_empty()).then(|| code.to_owned())
+}
+
+impl VendorCsvReader {
+    /// 指定代码, 优先于文件名和数据中的代码
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_owned());
+        self
+    }
+
+    pub fn read_csv_file<R>(&self, path: impl AsRef<Path>) -> AResult<Vec<R>>
+    where
+        R: FromVendorRecord,
+    {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        let code = self.code.clone().or_else(|| code_from_path(path));
+        self.parse(&bytes, code)
+    }
+
+    pub fn read_bytes<R>(&self, bytes: &[u8]) -> AResult<Vec<R>>
+    where
+        R: FromVendorRecord,
+    {
+        self.parse(bytes, self.code.clone())
+    }
+
+    fn parse<R>(&self, bytes: &[u8], code: Option<String>) -> AResult<Vec<R>>
+    where
+        R: FromVendorRecord,
+    {
+        let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+        // 通达信的标题是GBK编码, 按行跳过
+        let mut bytes = bytes;
+        for _ in 0..self.vendor.skip_rows() {
+            let pos = memchr::memchr(b'\n', bytes).map_or(bytes.len(), |v| v + 1);
+            bytes = &bytes[pos..];
+        }
+        let separator = CsvReader::detect_dialect(&bytes[..bytes.len().min(4096)]).separator;
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(self.vendor.has_header())
+            .delimiter(separator)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(bytes);
+
+        let headers = if self.vendor.has_header() {
+            rdr.byte_headers()?
+                .iter()
+                .map(|v| String::from_utf8_lossy(v).into_owned())
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+        let columns = self.vendor.columns();
+        let index = |column: Column| -> AResult<usize> {
+            match column {
+                Column::Index(idx) => Ok(idx),
+                Column::Name(name) => headers
+                    .iter()
+                    .position(|v| v == name)
+                    .ok_or_else(|| eyre!("{:?} column not found: {}", self.vendor, name)),
+            }
+        };
+        let opt_index = |column: Option<Column>| column.map(index).transpose();
+
+        let date_idx = index(columns.date)?;
+        let time_idx = opt_index(columns.time)?;
+        let millis_idx = opt_index(columns.millis)?;
+        let code_idx = opt_index(columns.code)?;
+        let open_idx = opt_index(columns.open)?;
+        let high_idx = opt_index(columns.high)?;
+        let low_idx = opt_index(columns.low)?;
+        let close_idx = opt_index(columns.close)?;
+        let price_idx = opt_index(columns.price)?;
+        let volume_idx = index(columns.volume)?;
+        let amount_idx = opt_index(columns.amount)?;
+        let oi_idx = opt_index(columns.open_interest)?;
+        let min_fields = [date_idx, volume_idx]
+            .into_iter()
+            .chain(
+                [time_idx, open_idx, high_idx, low_idx, close_idx, price_idx]
+                    .into_iter()
+                    .flatten(),
+            )
+            .max()
+            .unwrap_or_default()
+            + 1;
+
+        let mut r = Vec::new();
+        for record in rdr.byte_records() {
+            let record = record?;
+            // 结尾的说明行
+            if record.len() < min_fields {
+                continue;
+            }
+            let line = record.position().map(|v| v.line()).unwrap_or_default();
+            let field = |idx: usize| -> &str {
+                record
+                    .get(idx)
+                    .and_then(|v| std::str::from_utf8(v).ok())
+                    .unwrap_or_default()
+            };
+            let decimal = |idx: Option<usize>| -> AResult<Option<Decimal>> {
+                idx.map(|idx| {
+                    Decimal::from_str(field(idx))
+                        .map_err(|e| eyre!("line {}: {} '{}'", line, e, field(idx)))
+                })
+                .transpose()
+            };
+            let int = |idx: usize| -> AResult<i64> {
+                // 成交量可能带小数点, 如 100.0
+                let v = field(idx);
+                v.split('.')
+                    .next()
+                    .unwrap_or_default()
+                    .parse::<i64>()
+                    .map_err(|e| eyre!("line {}: {} '{}'", line, e, v))
+            };
+
+            let date = parse_date(field(date_idx))
+                .ok_or_else(|| eyre!("line {}: date err '{}'", line, field(date_idx)))?;
+            let mut time = match time_idx {
+                Some(idx) => parse_time(field(idx))
+                    .ok_or_else(|| eyre!("line {}: time err '{}'", line, field(idx)))?,
+                None => NaiveTime::MIN,
+            };
+            if let Some(idx) = millis_idx {
+                time += chrono::Duration::try_milliseconds(int(idx)?).unwrap_or_default();
+            }
+            let code = match (&self.code, code_idx) {
+                (Some(code), _) => code.clone(),
+                (None, Some(idx)) => field(idx).to_owned(),
+                (None, None) => code.clone().ok_or_eyre("code not found")?,
+            };
+
+            let record = VendorRecord {
+                vendor: self.vendor,
+                code,
+                datetime: date.and_time(time),
+                open: decimal(open_idx)?,
+                high: decimal(high_idx)?,
+                low: decimal(low_idx)?,
+                close: decimal(close_idx)?,
+                price: decimal(price_idx)?,
+                volume: int(volume_idx)?,
+                amount: decimal(amount_idx)?,
+                open_interest: oi_idx.map(int).transpose()?,
+            };
+            r.push(R::from_vendor_record(record)?);
+        }
+        Ok(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    use super::Vendor;
+    use crate::csv::read::CsvReader;
+    use crate::qh::klineitem::KLineItem;
+    use crate::qh::tick2bar::Tick;
+
+    #[test]
+    fn test_tdx_1m() {
+        let dir = std::env::temp_dir().join("common-rs-csv-vendor");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("SH#600000.txt");
+        let mut content =
+            b"600000 \xc6\xd6\xb7\xa2\xd2\xf8\xd0\xd0 1\xb7\xd6\xd6\xd3\xcf\xdf\r\n".to_vec();
+        content.extend_from_slice(
+            "日期,时间,开盘,最高,最低,收盘,成交量,成交额\r\n\
+             2024/06/03,0931,7.10,7.12,7.09,7.11,12300,87453.00\r\n\
+             2024/06/03,0932,7.11,7.13,7.10,7.12,8800,62656.00\r\n\
+             数据来源:通达信\r\n"
+                .as_bytes(),
+        );
+        std::fs::write(&path, content).unwrap();
+
+        let items = CsvReader::preset(Vendor::Tdx1m)
+            .read_csv_file::<KLineItem>(&path)
+            .unwrap();
+        for item in items.iter() {
+            println!("{}", item);
+        }
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].code, "600000");
+        assert_eq!(items[0].period, 1);
+        assert_eq!(
+            items[1].datetime,
+            NaiveDate::from_ymd_opt(2024, 6, 3)
+                .unwrap()
+                .and_hms_opt(9, 32, 0)
+                .unwrap()
+        );
+        assert_eq!(items[1].close, Decimal::new(712, 2));
+        assert_eq!(items[1].volume, 8800);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_ctp_tick() {
+        let csv = "TradingDay,InstrumentID,ActionDay,UpdateTime,UpdateMillisec,LastPrice,Volume,Turnover,OpenInterest\n\
+                   20240604,ag2408,20240603,21:00:01,500,7800,120,14040000,300000\n\
+                   20240604,ag2408,20240603,21:00:02,0,7801,150,17550000,300010\n";
+        let ticks = CsvReader::preset(Vendor::CtpTick)
+            .read_bytes::<Tick>(csv.as_bytes())
+            .unwrap();
+        println!("{:?}", ticks);
+        assert_eq!(ticks.len(), 2);
+        assert_eq!(ticks[0].code, "ag2408");
+        assert_eq!(
+            ticks[0].datetime,
+            NaiveDate::from_ymd_opt(2024, 6, 3)
+                .unwrap()
+                .and_hms_milli_opt(21, 0, 1, 500)
+                .unwrap()
+        );
+        assert_eq!(ticks[1].total_volume, 150);
+        assert_eq!(ticks[1].open_interest, 300010);
+
+        assert!(CsvReader::preset(Vendor::CtpTick)
+            .read_bytes::<KLineItem>(csv.as_bytes())
+            .is_err());
+    }
+}
@@ -1,11 +1,25 @@
+#[cfg(feature = "file")]
+use std::collections::{HashMap, HashSet, VecDeque};
+#[cfg(feature = "file")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "file")]
+use std::io::BufWriter;
 use std::io::Write;
+#[cfg(feature = "file")]
+use std::path::PathBuf;
 
+#[cfg(feature = "file")]
+use chrono::NaiveDate;
 use rayon::iter::{IntoParallelIterator, ParallelExtend, ParallelIterator};
 
 use super::contention_pool::LowContentionPool;
 use super::POOL;
+#[cfg(feature = "file")]
+use crate::file::path_template::{PathContext, PathTemplate};
 use crate::AResult;
 
+const BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+
 pub trait CsvRow {
     fn csv_row(&self) -> String;
 }
@@ -17,6 +31,9 @@ pub struct CsvWriter<W: Write> {
     bom:                 bool,
     batch_size:          usize,
     n_threads:           usize,
+    /// 分文件写入时同时打开的文件数上限
+    #[cfg(feature = "file")]
+    max_open_files:      usize,
     /// Used as separator.
     pub separator:       u8,
     /// String appended after every row.
@@ -34,6 +51,8 @@ where
             bom: false,
             batch_size: 1024,
             n_threads: POOL.current_num_threads(),
+            #[cfg(feature = "file")]
+            max_open_files: 64,
             separator: b',',
             line_terminator: "\n".into(),
         }
@@ -45,10 +64,15 @@ where
         self
     }
 
+    pub fn with_bom(mut self, bom: bool) -> Self {
+        self.bom = bom;
+        self
+    }
+
     /// Writes a CSV header to `writer`.
     fn write_header(&mut self) -> AResult<()> {
         if let Some(header) = &self.header {
-            let header = header.join(&self.separator.to_string());
+            let header = header.join(&char::from(self.separator).to_string());
             self.buffer.write_all(header.as_bytes())?;
             self.buffer.write_all(self.line_terminator.as_bytes())?;
         }
@@ -57,7 +81,6 @@ where
 
     /// Writes a UTF-8 BOM to `writer`.
     fn write_bom(&mut self) -> AResult<()> {
-        const BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
         self.buffer.write_all(&BOM)?;
         Ok(())
     }
@@ -75,6 +98,7 @@ where
         let mut n_rows_finished = 0;
 
         let mut result_buf = Vec::<Vec<u8>>::with_capacity(n_threads);
+        let line_terminator = self.line_terminator.as_bytes();
 
         while n_rows_finished < len {
             let buf_writer = |thread_no: usize| {
@@ -94,7 +118,7 @@ where
 
                 for data in datas {
                     write!(write_buffer, "{}", data.csv_row()).unwrap();
-                    write_buffer.extend_from_slice("\n".as_bytes());
+                    write_buffer.extend_from_slice(line_terminator);
                 }
 
                 write_buffer
@@ -130,3 +154,162 @@ where
         Ok(())
     }
 }
+
+/// 按(品种, 日期)把行写到不同的文件
+#[cfg(feature = "file")]
+impl<W: Write> CsvWriter<W> {
+    /// 同时打开的文件数上限, 默认64, 见`write_partitioned`
+    pub fn with_max_open_files(mut self, max_open_files: usize) -> Self {
+        self.max_open_files = max_open_files.max(1);
+        self
+    }
+
+    /// 新建文件时写入bom和表头, 关闭后再次写入时以追加方式打开
+    fn open_partition(
+        &self,
+        path: &PathBuf,
+        created: &mut HashSet<PathBuf>,
+    ) -> AResult<BufWriter<File>> {
+        if created.contains(path) {
+            return Ok(BufWriter::new(OpenOptions::new().append(true).open(path)?));
+        }
+        let mut file = BufWriter::new(File::create(path)?);
+        if self.bom {
+            file.write_all(&BOM)?;
+        }
+        if let Some(header) = &self.header {
+            file.write_all(header.join(&char::from(self.separator).to_string()).as_bytes())?;
+            file.write_all(self.line_terminator.as_bytes())?;
+        }
+        created.insert(path.clone());
+        Ok(file)
+    }
+
+    /// 文件路径由模板生成, 如: `out/{breed}/{yyyymmdd}.csv`, 使用当前的表头, bom和行结束符的设置,
+    /// 不写入buffer, 只用于分文件写入时buffer可以为`std::io::sink()`
+    ///
+    /// key: 行 => (品种, 日期), 对应模板中的`{breed}`和日期占位符
+    ///
+    /// 文件在第一次写入时创建, 同时打开的文件数超过上限时关闭最早打开的,
+    /// 再次写入时以追加方式打开. 返回写入的文件, 按路径排序
+    pub fn write_partitioned<T, I, F>(
+        &self,
+        template: &PathTemplate,
+        items: I,
+        key: F,
+    ) -> AResult<Vec<PathBuf>>
+    where
+        T: CsvRow,
+        I: IntoIterator<Item = T>,
+        F: Fn(&T) -> (String, NaiveDate),
+    {
+        let mut paths = HashMap::<(String, NaiveDate), PathBuf>::new();
+        let mut created = HashSet::new();
+        let mut open_files = HashMap::<PathBuf, BufWriter<File>>::new();
+        let mut open_order = VecDeque::new();
+
+        for item in items {
+            let item_key = key(&item);
+            let path = match paths.get(&item_key) {
+                Some(path) => path.clone(),
+                None => {
+                    let ctx = PathContext::new()
+                        .with_var("breed", &item_key.0)
+                        .with_date(item_key.1);
+                    let path = template.render(&ctx)?;
+                    paths.insert(item_key, path.clone());
+                    path
+                },
+            };
+            if !open_files.contains_key(&path) {
+                if open_files.len() >= self.max_open_files {
+                    if let Some(oldest) = open_order.pop_front() {
+                        if let Some(mut file) = open_files.remove(&oldest) {
+                            file.flush()?;
+                        }
+                    }
+                }
+                let file = self.open_partition(&path, &mut created)?;
+                open_files.insert(path.clone(), file);
+                open_order.push_back(path.clone());
+            }
+            let file = open_files.get_mut(&path).unwrap();
+            file.write_all(item.csv_row().as_bytes())?;
+            file.write_all(self.line_terminator.as_bytes())?;
+        }
+        for (_, mut file) in open_files {
+            file.flush()?;
+        }
+        let mut created = created.into_iter().collect::<Vec<_>>();
+        created.sort();
+        Ok(created)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CsvRow;
+
+    struct Row {
+        breed: &'static str,
+        price: i32,
+    }
+
+    impl CsvRow for Row {
+        fn csv_row(&self) -> String {
+            format!("{},{}", self.breed, self.price)
+        }
+    }
+
+    /// 分文件写入时带上日期
+    impl CsvRow for (u32, Row) {
+        fn csv_row(&self) -> String {
+            self.1.csv_row()
+        }
+    }
+
+    #[test]
+    fn test_write() {
+        let rows = [Row { breed: "ag", price: 1 }, Row { breed: "rb", price: 3 }];
+        let mut buf = Vec::new();
+        let mut writer = super::CsvWriter::new(&mut buf).with_header(&["breed", "price"]);
+        writer.line_terminator = "\r\n".into();
+        writer.finish(&rows).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "breed,price\r\nag,1\r\nrb,3\r\n");
+    }
+
+    #[cfg(feature = "file")]
+    #[test]
+    fn test_write_partitioned() {
+        use std::fs;
+
+        use chrono::NaiveDate;
+
+        use super::CsvWriter;
+        use crate::file::path_template::PathTemplate;
+
+        let dir = "./_data/csv-partitioned";
+        let _ = fs::remove_dir_all(dir);
+        let template = PathTemplate::new(&format!("{}/{{breed}}/{{yyyymmdd}}.csv", dir)).unwrap();
+        let writer = CsvWriter::new(std::io::sink())
+            .with_header(&["breed", "price"])
+            .with_max_open_files(1);
+        let rows = [("ag", 3, 1), ("ag", 4, 2), ("rb", 3, 3), ("ag", 3, 4)]
+            .map(|(breed, day, price)| (day, Row { breed, price }));
+        let paths = writer
+            .write_partitioned(&template, rows, |(day, row)| {
+                (
+                    row.breed.to_owned(),
+                    NaiveDate::from_ymd_opt(2024, 6, *day).unwrap(),
+                )
+            })
+            .unwrap();
+        assert_eq!(paths.len(), 3);
+        let content = fs::read_to_string(format!("{}/ag/20240603.csv", dir)).unwrap();
+        // 关闭后重新打开时追加, 不重复写表头
+        assert_eq!(content, "breed,price\nag,1\nag,4\n");
+        let content = fs::read_to_string(format!("{}/rb/20240603.csv", dir)).unwrap();
+        assert_eq!(content, "breed,price\nrb,3\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -2,6 +2,9 @@ use std::fmt;
 
 use eyre::eyre;
 
+#[cfg(feature = "api-error")]
+pub mod api;
+
 pub trait EyreExt<T> {
     #[track_caller]
     fn eyre(self) -> Result<T, eyre::Error>;
@@ -0,0 +1,316 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::Serialize;
+
+/// 返回给客户端的错误
+/// details: 错误链中的下层错误信息, 只有开启`with_details`时才有
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ApiError {
+    pub code:    String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.code, self.message)
+    }
+}
+
+/// 附加在eyre::Report上的错误码, 通过`ApiCodeExt::api_code`添加
+/// message: 给客户端看的信息, 为None时使用错误本身的信息(会做脱敏处理)
+#[derive(Debug, Clone)]
+pub struct ApiCode {
+    code:    String,
+    message: Option<String>,
+}
+
+impl ApiCode {
+    pub fn new(code: &str) -> ApiCode {
+        ApiCode {
+            code:    code.to_owned(),
+            message: None,
+        }
+    }
+
+    pub fn with_message(self, message: &str) -> ApiCode {
+        ApiCode {
+            message: Some(message.to_owned()),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for ApiCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "[{}] {}", self.code, message),
+            None => write!(f, "[{}]", self.code),
+        }
+    }
+}
+
+/// 带ApiCode的错误, 原错误作为source, 这样在错误链中可以downcast找到ApiCode
+#[derive(Debug)]
+struct ApiCodeError {
+    api_code: ApiCode,
+    source:   Box<dyn Error + Send + Sync + 'static>,
+}
+
+impl fmt::Display for ApiCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.api_code.fmt(f)
+    }
+}
+
+impl Error for ApiCodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+pub trait ApiCodeExt<T> {
+    fn api_code(self, code: ApiCode) -> Result<T, eyre::Report>;
+}
+
+impl<T> ApiCodeExt<T> for Result<T, eyre::Report> {
+    fn api_code(self, api_code: ApiCode) -> Result<T, eyre::Report> {
+        self.map_err(|e| {
+            eyre::Report::new(ApiCodeError {
+                api_code,
+                source: e.into(),
+            })
+        })
+    }
+}
+
+const SQL_KEYWORDS: [&str; 8] = [
+    "SELECT ", "INSERT ", "UPDATE ", "DELETE ", "REPLACE ", "CREATE ", "ALTER ", "DROP ",
+];
+
+/// 把eyre::Report转换为ApiError
+/// 错误码取错误链中最外层的ApiCode, 没有时使用default_code
+#[derive(Debug, Clone)]
+pub struct ApiErrorMapper {
+    default_code:    String,
+    default_message: Option<String>,
+    redact_sql:      bool,
+    redact_paths:    bool,
+    with_details:    bool,
+}
+
+impl Default for ApiErrorMapper {
+    fn default() -> Self {
+        ApiErrorMapper {
+            default_code:    "INTERNAL".to_owned(),
+            default_message: None,
+            redact_sql:      true,
+            redact_paths:    true,
+            with_details:    false,
+        }
+    }
+}
+
+impl ApiErrorMapper {
+    pub fn with_default_code(self, default_code: &str) -> ApiErrorMapper {
+        ApiErrorMapper {
+            default_code: default_code.to_owned(),
+            ..self
+        }
+    }
+
+    /// 没有ApiCode的错误统一使用这个信息, 不暴露错误内容
+    pub fn with_default_message(self, default_message: &str) -> ApiErrorMapper {
+        ApiErrorMapper {
+            default_message: Some(default_message.to_owned()),
+            ..self
+        }
+    }
+
+    pub fn with_redact_sql(self, redact_sql: bool) -> ApiErrorMapper {
+        ApiErrorMapper { redact_sql, ..self }
+    }
+
+    pub fn with_redact_paths(self, redact_paths: bool) -> ApiErrorMapper {
+        ApiErrorMapper {
+            redact_paths,
+            ..self
+        }
+    }
+
+    pub fn with_details(self, with_details: bool) -> ApiErrorMapper {
+        ApiErrorMapper {
+            with_details,
+            ..self
+        }
+    }
+
+    pub fn map(&self, report: &eyre::Report) -> ApiError {
+        let api_code = report
+            .chain()
+            .find_map(|e| e.downcast_ref::<ApiCodeError>())
+            .map(|e| &e.api_code);
+        // ApiCode本身不作为错误信息
+        let mut messages = report
+            .chain()
+            .filter(|e| !e.is::<ApiCodeError>())
+            .map(|e| self.redact(&e.to_string()));
+
+        let (code, message) = match api_code {
+            Some(api_code) => {
+                let message = match &api_code.message {
+                    Some(message) => message.clone(),
+                    None => messages.next().unwrap_or_default(),
+                };
+                (api_code.code.clone(), message)
+            },
+            None => {
+                let message = match &self.default_message {
+                    Some(message) => message.clone(),
+                    None => messages.next().unwrap_or_default(),
+                };
+                (self.default_code.clone(), message)
+            },
+        };
+
+        let details = if self.with_details {
+            messages.filter(|v| *v != message).collect()
+        } else {
+            Vec::new()
+        };
+
+        ApiError {
+            code,
+            message,
+            details,
+        }
+    }
+
+    /// 脱敏处理
+    pub fn redact(&self, msg: &str) -> String {
+        let mut msg = msg.to_owned();
+        if self.redact_sql {
+            msg = redact_sql(&msg);
+        }
+        if self.redact_paths {
+            msg = redact_paths(&msg);
+        }
+        msg
+    }
+}
+
+/// `Sql: [...]`(mysqlx::exec::ExecError的格式)和以SQL关键字开始到行尾的内容替换为<sql>
+fn redact_sql(msg: &str) -> String {
+    let mut msg = msg.to_owned();
+    while let Some(start) = msg.find("Sql: [") {
+        let body_start = start + "Sql: [".len();
+        let Some(len) = msg[body_start..].find(']') else {
+            break;
+        };
+        msg.replace_range(start..body_start + len + 1, "<sql>");
+    }
+    msg.lines()
+        .map(|line| {
+            let upper = line.to_uppercase();
+            let pos = SQL_KEYWORDS
+                .iter()
+                .filter_map(|keyword| upper.find(keyword))
+                .min();
+            match pos {
+                // to_uppercase可能改变长度, 只处理ASCII能对上的情况
+                Some(pos) if line.is_char_boundary(pos) && upper.len() == line.len() => {
+                    format!("{}<sql>", &line[..pos])
+                },
+                _ => line.to_owned(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn is_path(word: &str) -> bool {
+    let word = word.trim_matches(|c: char| matches!(c, '"' | '\'' | '(' | ')' | ',' | ':'));
+    let bytes = word.as_bytes();
+    (word.starts_with('/') && word.len() > 1)
+        || word.starts_with("./")
+        || word.starts_with("../")
+        || word.starts_with("~/")
+        || (bytes.len() > 2
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && bytes[2] == b'\\')
+}
+
+/// 文件路径替换为<path>
+fn redact_paths(msg: &str) -> String {
+    let mut r = String::with_capacity(msg.len());
+    let mut word = String::new();
+    for c in msg.chars().chain(std::iter::once(' ')) {
+        if c.is_whitespace() {
+            if is_path(&word) {
+                r.push_str("<path>");
+            } else {
+                r.push_str(&word);
+            }
+            word.clear();
+            r.push(c);
+        } else {
+            word.push(c);
+        }
+    }
+    r.pop();
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use eyre::eyre;
+
+    use super::{ApiCode, ApiCodeExt, ApiErrorMapper};
+
+    fn read_config() -> Result<(), eyre::Report> {
+        Err(eyre!("读取文件失败: /etc/app/config.toml No such file"))
+    }
+
+    #[test]
+    fn test_map() {
+        let mapper = ApiErrorMapper::default().with_details(true);
+
+        let report = read_config()
+            .api_code(ApiCode::new("CONFIG").with_message("配置错误"))
+            .unwrap_err();
+        let api_error = mapper.map(&report);
+        println!("{:?}", api_error);
+        assert_eq!(api_error.code, "CONFIG");
+        assert_eq!(api_error.message, "配置错误");
+        assert_eq!(api_error.details, vec!["读取文件失败: <path> No such file"]);
+
+        let report = eyre!("Sql: [\nSELECT * FROM `db`.`tbl`\n]\nerr: timeout");
+        let api_error = ApiErrorMapper::default().map(&report);
+        println!("{:?}", api_error);
+        assert_eq!(api_error.code, "INTERNAL");
+        assert_eq!(api_error.message, "<sql>\nerr: timeout");
+        assert!(api_error.details.is_empty());
+
+        let api_error = ApiErrorMapper::default()
+            .with_default_message("服务器错误")
+            .map(&report);
+        assert_eq!(api_error.message, "服务器错误");
+
+        let yaml = serde_yaml::to_string(&api_error).unwrap();
+        println!("{}", yaml);
+        assert!(!yaml.contains("details"));
+    }
+
+    #[test]
+    fn test_redact() {
+        let mapper = ApiErrorMapper::default();
+        let msg = mapper.redact("exec err: select a from t where b=1\nfile: ./_data/a.csv");
+        println!("{}", msg);
+        assert_eq!(msg, "exec err: <sql>\nfile: <path>");
+        let msg = mapper.redact(r"open C:\data\a.csv failed");
+        assert_eq!(msg, "open <path> failed");
+    }
+}
@@ -1 +1,6 @@
+pub mod locked;
+pub mod path_template;
+pub mod record_log;
 pub mod unzip;
+
+pub use locked::locked_update;
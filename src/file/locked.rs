@@ -0,0 +1,226 @@
+//! 多进程共享的状态文件(检查点, 日历等)加锁更新
+//!
+//! 锁加在旁边的`{file}.lock`文件上(系统的建议锁), 持有时写入`pid 时间戳`, 释放时清空;
+//! 获取到锁时锁文件不为空, 说明上一个持有者没有正常释放(进程崩溃), 视为过期的锁
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 默认的获取锁超时时间
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum FileLockError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    #[error("lock timeout: {path}, holder: {holder}")]
+    Timeout { path: PathBuf, holder: String },
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    path.with_file_name(name)
+}
+
+fn read_holder(file: &mut File) -> io::Result<String> {
+    let mut holder = String::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_string(&mut holder)?;
+    Ok(holder.trim().to_owned())
+}
+
+#[derive(Debug, Clone)]
+pub struct FileLock {
+    path:          PathBuf,
+    timeout:       Duration,
+    poll_interval: Duration,
+}
+
+impl FileLock {
+    pub fn new(path: impl AsRef<Path>) -> FileLock {
+        FileLock {
+            path:          path.as_ref().to_path_buf(),
+            timeout:       DEFAULT_LOCK_TIMEOUT,
+            poll_interval: Duration::from_millis(20),
+        }
+    }
+
+    /// 等待其他进程释放锁的最长时间
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        FileLock { timeout, ..self }
+    }
+
+    /// 等待时重试获取锁的间隔
+    pub fn with_poll_interval(self, poll_interval: Duration) -> Self {
+        FileLock {
+            poll_interval: poll_interval.max(Duration::from_millis(1)),
+            ..self
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 获取锁, 超时时返回当前持有者的信息
+    pub fn acquire(&self) -> Result<FileLockGuard, FileLockError> {
+        let lock_path = lock_path(&self.path);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)?;
+        let start = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) if start.elapsed() < self.timeout => {
+                    thread::sleep(self.poll_interval);
+                },
+                Err(TryLockError::WouldBlock) => {
+                    return Err(FileLockError::Timeout {
+                        path:   lock_path,
+                        holder: read_holder(&mut file).unwrap_or_default(),
+                    });
+                },
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+        let stale_holder = Some(read_holder(&mut file)?).filter(|v| !v.is_empty());
+        let since = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(file, "{} {}", std::process::id(), since)?;
+        file.sync_data()?;
+        Ok(FileLockGuard {
+            file,
+            path: self.path.clone(),
+            stale_holder,
+        })
+    }
+
+    /// 加锁读取文件内容, 用f的返回值替换, 文件不存在时内容为空
+    ///
+    /// 先写入临时文件再重命名, 中途失败不会留下写了一半的文件
+    pub fn update<F>(&self, f: F) -> Result<(), FileLockError>
+    where
+        F: FnOnce(&[u8]) -> Vec<u8>,
+    {
+        let guard = self.acquire()?;
+        let bytes = match fs::read(&self.path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        guard.replace(&f(&bytes))?;
+        Ok(())
+    }
+}
+
+/// 持有锁, drop时释放
+#[derive(Debug)]
+pub struct FileLockGuard {
+    file:         File,
+    path:         PathBuf,
+    stale_holder: Option<String>,
+}
+
+impl FileLockGuard {
+    /// 获取到锁时发现的未正常释放的持有者(`pid 时间戳`)
+    pub fn stale_holder(&self) -> Option<&str> {
+        self.stale_holder.as_deref()
+    }
+
+    /// 替换被锁文件的内容
+    pub fn replace(&self, bytes: &[u8]) -> io::Result<()> {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".tmp");
+        let tmp_path = self.path.with_file_name(name);
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(bytes)?;
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, &self.path)
+    }
+}
+
+impl Drop for FileLockGuard {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+        let _ = self.file.unlock();
+    }
+}
+
+/// 用默认超时加锁更新文件, 见`FileLock::update`
+pub fn locked_update<F>(path: impl AsRef<Path>, f: F) -> Result<(), FileLockError>
+where
+    F: FnOnce(&[u8]) -> Vec<u8>,
+{
+    FileLock::new(path).update(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use super::{lock_path, locked_update, FileLock, FileLockError};
+
+    #[test]
+    fn test_locked_update() {
+        let dir = std::env::temp_dir().join("common-rs-locked-update");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("counter.txt");
+
+        let handles = (0..4)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        locked_update(&path, |bytes| {
+                            let n = std::str::from_utf8(bytes)
+                                .unwrap()
+                                .parse::<u32>()
+                                .unwrap_or(0);
+                            (n + 1).to_string().into_bytes()
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "100");
+        assert_eq!(fs::read_to_string(lock_path(&path)).unwrap(), "");
+
+        let guard = FileLock::new(&path).acquire().unwrap();
+        assert!(guard.stale_holder().is_none());
+        let r = FileLock::new(&path)
+            .with_timeout(Duration::from_millis(50))
+            .update(|_| Vec::new());
+        match r {
+            Err(FileLockError::Timeout { holder, .. }) => {
+                assert!(holder.starts_with(&std::process::id().to_string()))
+            },
+            r => panic!("{:?}", r),
+        }
+        drop(guard);
+
+        // 崩溃时留下的持有者信息
+        fs::write(lock_path(&path), "1 1700000000").unwrap();
+        let guard = FileLock::new(&path).acquire().unwrap();
+        assert_eq!(guard.stale_holder(), Some("1 1700000000"));
+        drop(guard);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,262 @@
+//! 按日期分区的输出路径模板, 如`logs/{yyyy}/{mm}/{breed}_{yyyymmdd}.csv`
+//!
+//! 内置的日期占位符: `yyyy`, `mm`, `dd`, `yyyymmdd`, `hh`, 其他占位符从[`PathContext`]的变量中取值
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::{fs, io};
+
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+
+const DATE_KEYS: [&str; 4] = ["yyyy", "mm", "dd", "yyyymmdd"];
+const TIME_KEYS: [&str; 1] = ["hh"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum PathTemplateError {
+    #[error("template {template}: {reason}")]
+    Syntax { template: String, reason: String },
+
+    #[error("placeholder {{{0}}} has no value")]
+    MissingValue(String),
+
+    #[error("placeholder {{{key}}} value {value:?} is not a valid path segment")]
+    InvalidValue { key: String, value: String },
+
+    #[error("{0}")]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Placeholder(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplate {
+    template: String,
+    parts:    Vec<Part>,
+}
+
+/// 渲染模板的日期和变量
+#[derive(Debug, Clone, Default)]
+pub struct PathContext {
+    datetime: Option<NaiveDateTime>,
+    vars:     HashMap<String, String>,
+}
+
+impl PathContext {
+    pub fn new() -> PathContext {
+        PathContext::default()
+    }
+
+    pub fn with_date(self, date: NaiveDate) -> Self {
+        self.with_datetime(date.and_hms_opt(0, 0, 0).unwrap())
+    }
+
+    pub fn with_datetime(mut self, datetime: NaiveDateTime) -> Self {
+        self.datetime = Some(datetime);
+        self
+    }
+
+    pub fn with_var(mut self, key: &str, value: impl ToString) -> Self {
+        self.vars.insert(key.to_owned(), value.to_string());
+        self
+    }
+
+    fn value(&self, key: &str) -> Option<String> {
+        if let Some(value) = self.vars.get(key) {
+            return Some(value.clone());
+        }
+        let dt = self.datetime?;
+        let value = match key {
+            "yyyy" => dt.format("%Y").to_string(),
+            "mm" => dt.format("%m").to_string(),
+            "dd" => dt.format("%d").to_string(),
+            "yyyymmdd" => dt.format("%Y%m%d").to_string(),
+            "hh" => format!("{:02}", dt.hour()),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+impl PathTemplate {
+    /// 解析模板, 占位符只能包含字母, 数字和`_`, `{{`和`}}`输出为`{`和`}`
+    pub fn new(template: &str) -> Result<PathTemplate, PathTemplateError> {
+        let syntax = |reason: &str| PathTemplateError::Syntax {
+            template: template.to_owned(),
+            reason:   reason.to_owned(),
+        };
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                },
+                '{' => {
+                    let mut key = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) if c.is_ascii_alphanumeric() || c == '_' => key.push(c),
+                            Some(c) => {
+                                return Err(syntax(&format!("invalid char {:?} in placeholder", c)))
+                            },
+                            None => return Err(syntax("unclosed placeholder")),
+                        }
+                    }
+                    if key.is_empty() {
+                        return Err(syntax("empty placeholder"));
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(key));
+                },
+                '}' => return Err(syntax("unmatched }")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(PathTemplate {
+            template: template.to_owned(),
+            parts,
+        })
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// 模板中的占位符, 按出现顺序, 不去重
+    pub fn placeholders(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Placeholder(key) => Some(key.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    /// 是否包含日期时间占位符
+    pub fn is_dated(&self) -> bool {
+        self.placeholders()
+            .any(|key| DATE_KEYS.contains(&key) || TIME_KEYS.contains(&key))
+    }
+
+    /// 检查ctx是否能提供所有占位符的值
+    pub fn validate(&self, ctx: &PathContext) -> Result<(), PathTemplateError> {
+        self.expand(ctx).map(|_| ())
+    }
+
+    /// 展开模板, 不创建目录
+    ///
+    /// 值不能为空, 不能包含路径分隔符, 也不能为`.`或`..`, 避免写到模板之外的目录
+    pub fn expand(&self, ctx: &PathContext) -> Result<PathBuf, PathTemplateError> {
+        let mut path = String::with_capacity(self.template.len());
+        for part in self.parts.iter() {
+            match part {
+                Part::Literal(s) => path.push_str(s),
+                Part::Placeholder(key) => {
+                    let value = ctx
+                        .value(key)
+                        .ok_or_else(|| PathTemplateError::MissingValue(key.clone()))?;
+                    if value.is_empty()
+                        || value == "."
+                        || value == ".."
+                        || value.contains(['/', '\\'])
+                    {
+                        return Err(PathTemplateError::InvalidValue {
+                            key: key.clone(),
+                            value,
+                        });
+                    }
+                    path.push_str(&value);
+                },
+            }
+        }
+        Ok(PathBuf::from(path))
+    }
+
+    /// 展开模板并创建上级目录
+    pub fn render(&self, ctx: &PathContext) -> Result<PathBuf, PathTemplateError> {
+        let path = self.expand(ctx)?;
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::NaiveDate;
+
+    use super::{PathContext, PathTemplate, PathTemplateError};
+
+    #[test]
+    fn test_parse() {
+        let tpl = PathTemplate::new("logs/{yyyy}/{mm}/{breed}_{yyyymmdd}.csv").unwrap();
+        assert_eq!(
+            tpl.placeholders().collect::<Vec<_>>(),
+            ["yyyy", "mm", "breed", "yyyymmdd"]
+        );
+        assert!(tpl.is_dated());
+        assert!(!PathTemplate::new("a/{{b}}.csv").unwrap().is_dated());
+        for template in ["a/{yyyy", "a/{}", "a/{b-c}", "a/b}"] {
+            let err = PathTemplate::new(template).unwrap_err();
+            println!("{}", err);
+            assert!(matches!(err, PathTemplateError::Syntax { .. }));
+        }
+    }
+
+    #[test]
+    fn test_expand() {
+        let tpl = PathTemplate::new("logs/{yyyy}/{mm}/{breed}_{yyyymmdd}_{{x}}.csv").unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let ctx = PathContext::new().with_date(date).with_var("breed", "ag");
+        assert_eq!(
+            tpl.expand(&ctx).unwrap().to_str().unwrap(),
+            "logs/2024/06/ag_20240603_{x}.csv"
+        );
+
+        let err = tpl
+            .validate(&PathContext::new().with_date(date))
+            .unwrap_err();
+        assert!(matches!(err, PathTemplateError::MissingValue(key) if key == "breed"));
+        let err = tpl
+            .validate(&PathContext::new().with_var("breed", "ag"))
+            .unwrap_err();
+        assert!(matches!(err, PathTemplateError::MissingValue(key) if key == "yyyy"));
+        let err = tpl
+            .validate(&ctx.clone().with_var("breed", "../ag"))
+            .unwrap_err();
+        assert!(matches!(err, PathTemplateError::InvalidValue { .. }));
+    }
+
+    #[test]
+    fn test_render() {
+        let dir = std::env::temp_dir().join("common-rs-path-template");
+        let _ = fs::remove_dir_all(&dir);
+        let template = format!("{}/{{yyyy}}/{{mm}}/{{breed}}.csv", dir.display());
+        let tpl = PathTemplate::new(&template).unwrap();
+        let ctx = PathContext::new()
+            .with_date(NaiveDate::from_ymd_opt(2024, 6, 3).unwrap())
+            .with_var("breed", "ag");
+        let path = tpl.render(&ctx).unwrap();
+        assert_eq!(path, dir.join("2024/06/ag.csv"));
+        assert!(path.parent().unwrap().is_dir());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
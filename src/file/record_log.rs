@@ -0,0 +1,393 @@
+//! 按大小分段的追加写二进制记录文件
+//!
+//! 每条记录: `len(u32 LE) | crc32(u32 LE) | payload`, 分段文件名为`{name}.{seq:06}.rlog`
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// 记录头长度
+const HEADER_LEN: u64 = 8;
+/// 默认分段大小
+pub const DEFAULT_SEGMENT_SIZE: u64 = 64 * 1024 * 1024;
+/// 单条记录最大长度, 超过时认为数据已损坏
+pub const MAX_RECORD_LEN: u32 = 64 * 1024 * 1024;
+
+const SEGMENT_EXT: &str = "rlog";
+
+/// 写入后的fsync策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// 不主动fsync, 由系统决定
+    Never,
+    /// 每条记录后fsync
+    Always,
+    /// 每N条记录fsync一次
+    EveryN(usize),
+    /// 距上次fsync超过间隔时fsync
+    Interval(Duration),
+}
+
+fn segment_path(dir: &Path, name: &str, seq: u64) -> PathBuf {
+    dir.join(format!("{}.{:06}.{}", name, seq, SEGMENT_EXT))
+}
+
+/// 目录下该名称的所有分段, 按序号排序
+pub fn segments(dir: impl AsRef<Path>, name: &str) -> io::Result<Vec<(u64, PathBuf)>> {
+    let dir = dir.as_ref();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let prefix = format!("{}.", name);
+    let suffix = format!(".{}", SEGMENT_EXT);
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        let seq = file_name
+            .strip_prefix(&prefix)
+            .and_then(|s| s.strip_suffix(&suffix))
+            .and_then(|s| s.parse::<u64>().ok());
+        if let Some(seq) = seq {
+            segments.push((seq, entry.path()));
+        }
+    }
+    segments.sort_by_key(|(seq, _)| *seq);
+    Ok(segments)
+}
+
+/// 读取一条记录, 文件结束返回None, 记录损坏或不完整返回Err(InvalidData)
+fn read_record<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0u8; HEADER_LEN as usize];
+    let mut filled = 0;
+    while filled < header.len() {
+        let n = reader.read(&mut header[filled..])?;
+        if n == 0 {
+            if filled == 0 {
+                return Ok(None);
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "incomplete header",
+            ));
+        }
+        filled += n;
+    }
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap());
+    let crc = u32::from_le_bytes(header[4..].try_into().unwrap());
+    if len > MAX_RECORD_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("record too long: {}", len),
+        ));
+    }
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload).map_err(|e| {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            io::Error::new(io::ErrorKind::InvalidData, "incomplete payload")
+        } else {
+            e
+        }
+    })?;
+    if crc32fast::hash(&payload) != crc {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "crc mismatch"));
+    }
+    Ok(Some(payload))
+}
+
+/// 扫描分段, 返回完整记录结束处的长度
+fn valid_len(path: &Path) -> io::Result<u64> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut len = 0;
+    loop {
+        match read_record(&mut reader) {
+            Ok(Some(payload)) => len += HEADER_LEN + payload.len() as u64,
+            Ok(None) => return Ok(len),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => return Ok(len),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub struct RecordLogWriter {
+    dir:          PathBuf,
+    name:         String,
+    segment_size: u64,
+    fsync:        FsyncPolicy,
+    seq:          u64,
+    size:         u64,
+    writer:       BufWriter<File>,
+    unsynced:     usize,
+    last_sync:    Instant,
+}
+
+impl RecordLogWriter {
+    /// 打开或创建记录文件, 从最后一个分段继续追加
+    ///
+    /// 最后一个分段末尾有损坏的数据时会截断
+    pub fn open(dir: impl AsRef<Path>, name: &str) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let (seq, path) = match segments(&dir, name)?.pop() {
+            Some(last) => last,
+            None => (0, segment_path(&dir, name, 0)),
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&path)?;
+        let size = valid_len(&path)?;
+        if file.metadata()?.len() > size {
+            file.set_len(size)?;
+        }
+        let mut writer = BufWriter::new(file);
+        writer.seek(SeekFrom::Start(size))?;
+        Ok(Self {
+            dir,
+            name: name.to_string(),
+            segment_size: DEFAULT_SEGMENT_SIZE,
+            fsync: FsyncPolicy::Never,
+            seq,
+            size,
+            writer,
+            unsynced: 0,
+            last_sync: Instant::now(),
+        })
+    }
+
+    /// 分段大小, 当前分段写入后超过该大小时开始新分段
+    pub fn with_segment_size(mut self, segment_size: u64) -> Self {
+        self.segment_size = segment_size;
+        self
+    }
+
+    pub fn with_fsync(mut self, fsync: FsyncPolicy) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    /// 当前分段文件
+    pub fn segment(&self) -> PathBuf {
+        segment_path(&self.dir, &self.name, self.seq)
+    }
+
+    pub fn append(&mut self, payload: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(payload.len())
+            .ok()
+            .filter(|len| *len <= MAX_RECORD_LEN)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("record too long: {}", payload.len()),
+                )
+            })?;
+        let record_size = HEADER_LEN + len as u64;
+        if self.size > 0 && self.size + record_size > self.segment_size {
+            self.roll()?;
+        }
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer
+            .write_all(&crc32fast::hash(payload).to_le_bytes())?;
+        self.writer.write_all(payload)?;
+        self.size += record_size;
+        self.unsynced += 1;
+
+        let need_sync = match self.fsync {
+            FsyncPolicy::Never => false,
+            FsyncPolicy::Always => true,
+            FsyncPolicy::EveryN(n) => self.unsynced >= n,
+            FsyncPolicy::Interval(interval) => self.last_sync.elapsed() >= interval,
+        };
+        if need_sync {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// 写入缓冲并fsync
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()?;
+        self.unsynced = 0;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    fn roll(&mut self) -> io::Result<()> {
+        if self.fsync == FsyncPolicy::Never {
+            self.writer.flush()?;
+        } else {
+            self.sync()?;
+        }
+        self.seq += 1;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.segment())?;
+        self.writer = BufWriter::new(file);
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Drop for RecordLogWriter {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+/// 读取时跳过的损坏数据
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Corruption {
+    pub path:    PathBuf,
+    /// 损坏开始的位置
+    pub offset:  u64,
+    /// 跳过的字节数
+    pub skipped: u64,
+    pub reason:  String,
+}
+
+/// 按顺序读取所有分段的记录
+///
+/// 遇到损坏的记录时跳过该分段剩余的数据, 继续读下一个分段
+pub struct RecordLogReader {
+    segments:    std::vec::IntoIter<(u64, PathBuf)>,
+    current:     Option<(PathBuf, BufReader<File>, u64)>,
+    corruptions: Vec<Corruption>,
+}
+
+impl RecordLogReader {
+    pub fn open(dir: impl AsRef<Path>, name: &str) -> io::Result<Self> {
+        Ok(Self {
+            segments:    segments(dir, name)?.into_iter(),
+            current:     None,
+            corruptions: Vec::new(),
+        })
+    }
+
+    /// 已跳过的损坏数据
+    pub fn corruptions(&self) -> &[Corruption] {
+        &self.corruptions
+    }
+}
+
+impl Iterator for RecordLogReader {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.current.is_none() {
+                let (_, path) = self.segments.next()?;
+                match File::open(&path) {
+                    Ok(file) => self.current = Some((path, BufReader::new(file), 0)),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            let (path, reader, offset) = self.current.as_mut().unwrap();
+            match read_record(reader) {
+                Ok(Some(payload)) => {
+                    *offset += HEADER_LEN + payload.len() as u64;
+                    return Some(Ok(payload));
+                },
+                Ok(None) => {
+                    self.current = None;
+                },
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    let file_len = fs::metadata(&*path).map(|m| m.len()).unwrap_or(*offset);
+                    self.corruptions.push(Corruption {
+                        path:    path.clone(),
+                        offset:  *offset,
+                        skipped: file_len.saturating_sub(*offset),
+                        reason:  e.to_string(),
+                    });
+                    self.current = None;
+                },
+                Err(e) => {
+                    self.current = None;
+                    return Some(Err(e));
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+
+    use super::{segments, FsyncPolicy, RecordLogReader, RecordLogWriter};
+
+    #[test]
+    fn test_record_log_roll_and_read() {
+        let dir = std::env::temp_dir().join("common-rs-record-log-roll");
+        let _ = fs::remove_dir_all(&dir);
+        {
+            let mut writer = RecordLogWriter::open(&dir, "tick")
+                .unwrap()
+                .with_segment_size(64)
+                .with_fsync(FsyncPolicy::EveryN(3));
+            for i in 0..10u32 {
+                writer.append(format!("record-{}", i).as_bytes()).unwrap();
+            }
+        }
+        let segments = segments(&dir, "tick").unwrap();
+        assert!(segments.len() > 1);
+        let records = RecordLogReader::open(&dir, "tick")
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 10);
+        assert_eq!(records[9], b"record-9");
+
+        // 重新打开后继续追加
+        let mut writer = RecordLogWriter::open(&dir, "tick").unwrap();
+        writer.append(b"record-10").unwrap();
+        drop(writer);
+        let count = RecordLogReader::open(&dir, "tick").unwrap().count();
+        assert_eq!(count, 11);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_record_log_damaged_tail() {
+        let dir = std::env::temp_dir().join("common-rs-record-log-damaged");
+        let _ = fs::remove_dir_all(&dir);
+        let segment = {
+            let mut writer = RecordLogWriter::open(&dir, "tick").unwrap();
+            writer.append(b"a").unwrap();
+            writer.append(b"bb").unwrap();
+            writer.segment()
+        };
+        // 模拟写入中断
+        let mut file = OpenOptions::new().append(true).open(&segment).unwrap();
+        file.write_all(&[5, 0, 0, 0, 1, 2]).unwrap();
+        drop(file);
+
+        let mut reader = RecordLogReader::open(&dir, "tick").unwrap();
+        let records = reader.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records, vec![b"a".to_vec(), b"bb".to_vec()]);
+        assert_eq!(reader.corruptions().len(), 1);
+        assert_eq!(reader.corruptions()[0].offset, 19);
+        assert_eq!(reader.corruptions()[0].skipped, 6);
+
+        // 打开写入时截断损坏的数据
+        let mut writer = RecordLogWriter::open(&dir, "tick").unwrap();
+        writer.append(b"ccc").unwrap();
+        drop(writer);
+        let mut reader = RecordLogReader::open(&dir, "tick").unwrap();
+        let records = reader.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(reader.corruptions().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
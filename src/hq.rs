@@ -1,3 +1,8 @@
+pub mod daily_stats;
 pub mod future;
 pub mod period;
+pub mod quote;
+pub mod report;
+pub mod snapshot;
 pub mod stock;
+pub mod trade;
@@ -0,0 +1,416 @@
+//! 每个品种每个交易日每个周期的K线统计(第一根/最后一根K线时间, K线数, 成交量), 用于监控数据是否完整
+//!
+//! 行情处理时用`DailyStatsCollector`累计增量, 定时用`DailyStatsUtil::flush`累加到表`tbl_daily_stats`,
+//! 重启后继续累加不会覆盖已有的统计; `qh::write_behind`可以用`WriteBehindConfig::with_daily_stats`
+//! 在K线写入成功后累计. 用SqlLoader管理表结构时见`daily_stats_toml`
+//!
+//! 同一品种同一周期的K线要按时间顺序记录: 不晚于已记录的最后一根K线时间的K线视为重复, 不再累计.
+//! 重启后先用`DailyStatsUtil::seed`读入已保存的最后一根K线时间, 重新处理的K线不会重复累加
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::mysql::MySqlArguments;
+use sqlx::{Arguments, MySqlPool};
+
+use crate::mysqlx::table::TableCreator;
+use crate::mysqlx::{ident, validate_ident, IdentError};
+
+/// 批量保存时每条语句的行数
+pub const UPSERT_CHUNK_SIZE: usize = 500;
+
+const TABLE_NAME: &str = "tbl_daily_stats";
+
+/// 表结构, 建表语句和SqlLoader模板都由这里生成
+fn table_creator(db: &str) -> TableCreator {
+    TableCreator::new(db, TABLE_NAME)
+        .add_field("breed", "varchar(8)", false, "''", "品种")
+        .add_field("trade_day", "date", false, "", "交易日")
+        .add_field("period", "int(11)", false, "0", "周期(分钟)")
+        .add_field("first_time", "datetime", false, "", "第一根K线时间")
+        .add_field("last_time", "datetime", false, "", "最后一根K线时间")
+        .add_field("bar_count", "int(11)", false, "0", "K线数")
+        .add_field("volume", "bigint(20)", false, "0", "成交量")
+        .add_field("update_time", "datetime(6)", false, "", "更新时间")
+        .add_index("idx_trade_day", &["trade_day"])
+        .primary_keys(&["breed", "trade_day", "period"])
+}
+
+/// SqlLoader的表模板, 加入SqlLoader的配置文件后用`table_create_sql_from_template`创建
+pub fn daily_stats_toml() -> String {
+    table_creator("").sql_loader_toml("tbl-daily-stats-tmpl")
+}
+
+const COLUMNS: &str = "breed,trade_day,period,first_time,last_time,bar_count,volume";
+
+/// 一个品种一个交易日一个周期的统计
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct DailyStat {
+    pub breed:      String,
+    pub trade_day:  NaiveDate,
+    /// 周期的分钟数, 见`Period::minutes`
+    pub period:     i32,
+    pub first_time: NaiveDateTime,
+    pub last_time:  NaiveDateTime,
+    pub bar_count:  i64,
+    pub volume:     i64,
+}
+
+impl DailyStat {
+    fn merge(&mut self, other: &DailyStat) {
+        self.first_time = self.first_time.min(other.first_time);
+        self.last_time = self.last_time.max(other.last_time);
+        self.bar_count += other.bar_count;
+        self.volume += other.volume;
+    }
+}
+
+type StatKey = (String, NaiveDate, i32);
+
+/// 累计未保存的增量
+#[derive(Debug, Default)]
+pub struct DailyStatsCollector {
+    pending: HashMap<StatKey, DailyStat>,
+    /// 已记录的最后一根K线时间, 包括已保存的
+    marks:   HashMap<StatKey, NaiveDateTime>,
+}
+
+impl DailyStatsCollector {
+    pub fn new() -> DailyStatsCollector {
+        Self::default()
+    }
+
+    /// 记录一根完成的K线, K线时间不晚于已记录的最后一根时视为重复, 不累计, 返回false
+    pub fn record(
+        &mut self,
+        breed: &str,
+        trade_day: NaiveDate,
+        period: i32,
+        bar_time: NaiveDateTime,
+        volume: i64,
+    ) -> bool {
+        let key = (breed.to_owned(), trade_day, period);
+        if self.marks.get(&key).is_some_and(|v| bar_time <= *v) {
+            return false;
+        }
+        self.marks.insert(key, bar_time);
+        self.merge(DailyStat {
+            breed: breed.to_owned(),
+            trade_day,
+            period,
+            first_time: bar_time,
+            last_time: bar_time,
+            bar_count: 1,
+            volume,
+        });
+        true
+    }
+
+    /// 已保存的统计, 最后一根K线时间及之前的K线不再累计
+    pub fn seed(&mut self, stats: &[DailyStat]) {
+        for stat in stats {
+            let key = (stat.breed.clone(), stat.trade_day, stat.period);
+            let mark = self.marks.entry(key).or_insert(stat.last_time);
+            *mark = (*mark).max(stat.last_time);
+        }
+    }
+
+    /// 去掉trade_day之前的交易日的记录, 未保存的增量保留
+    pub fn prune(&mut self, trade_day: NaiveDate) {
+        self.marks.retain(|(_, day, _), _| *day >= trade_day);
+    }
+
+    fn merge(&mut self, stat: DailyStat) {
+        let key = (stat.breed.clone(), stat.trade_day, stat.period);
+        match self.pending.get_mut(&key) {
+            Some(v) => v.merge(&stat),
+            None => {
+                self.pending.insert(key, stat);
+            },
+        }
+    }
+
+    /// 未保存的增量
+    pub fn get(&self, breed: &str, trade_day: NaiveDate, period: i32) -> Option<&DailyStat> {
+        self.pending.get(&(breed.to_owned(), trade_day, period))
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// 取出所有增量, 按品种, 交易日, 周期排序
+    pub fn take(&mut self) -> Vec<DailyStat> {
+        let mut stats = self.pending.drain().map(|(_, v)| v).collect::<Vec<_>>();
+        stats.sort_by(|a, b| {
+            (&a.breed, a.trade_day, a.period).cmp(&(&b.breed, b.trade_day, b.period))
+        });
+        stats
+    }
+}
+
+/// 多行累加的语句, 已有的行第一根/最后一根K线时间取范围,
+/// 增量的第一根K线晚于已保存的最后一根时K线数和成交量相加, 否则是重复的增量, 不相加.
+/// 赋值按顺序执行, 判断要在更新last_time之前
+fn upsert_sql(table_name: &str, rows: usize) -> String {
+    let row = "(?,?,?,?,?,?,?,NOW(6))";
+    format!(
+        "INSERT INTO {}({},update_time) VALUES {} ON DUPLICATE KEY UPDATE \
+         bar_count=IF(VALUES(first_time)>last_time,bar_count+VALUES(bar_count),bar_count),\
+         volume=IF(VALUES(first_time)>last_time,volume+VALUES(volume),volume),\
+         first_time=LEAST(first_time,VALUES(first_time)),\
+         last_time=GREATEST(last_time,VALUES(last_time)),\
+         update_time=VALUES(update_time)",
+        table_name,
+        COLUMNS,
+        vec![row; rows].join(",")
+    )
+}
+
+#[derive(Debug)]
+pub struct DailyStatsUtil {
+    db: String,
+}
+
+impl DailyStatsUtil {
+    /// db中的`-`换成`_`后校验
+    pub fn new(db: &str) -> Result<DailyStatsUtil, IdentError> {
+        let db = db.replace('-', "_");
+        if !db.is_empty() {
+            validate_ident(&db)?;
+        }
+        Ok(DailyStatsUtil { db })
+    }
+
+    fn table_name(&self) -> Result<String, IdentError> {
+        ident(&self.db, TABLE_NAME)
+    }
+
+    pub async fn create_table(&self, pool: &MySqlPool) -> Result<String, sqlx::Error> {
+        let table_name = self.table_name()?;
+        sqlx::query(&table_creator(&self.db).to_string())
+            .execute(pool)
+            .await?;
+        Ok(table_name)
+    }
+
+    /// 读入交易日已保存的统计, 之后重复记录的K线不再累计, 重启后在记录前调用
+    pub async fn seed(
+        &self,
+        pool: &MySqlPool,
+        collector: &mut DailyStatsCollector,
+        trade_day: NaiveDate,
+    ) -> Result<(), sqlx::Error> {
+        let stats = self.stats_by_day(pool, trade_day, None).await?;
+        collector.seed(&stats);
+        Ok(())
+    }
+
+    /// 把增量累加到表中, 在一个事务内按`UPSERT_CHUNK_SIZE`分批, 返回影响的行数
+    pub async fn upsert(&self, pool: &MySqlPool, stats: &[DailyStat]) -> Result<u64, sqlx::Error> {
+        let table_name = self.table_name()?;
+        let mut affected = 0;
+        let mut transaction = pool.begin().await?;
+        for chunk in stats.chunks(UPSERT_CHUNK_SIZE) {
+            let mut args = MySqlArguments::default();
+            for stat in chunk {
+                args.add(&stat.breed);
+                args.add(stat.trade_day);
+                args.add(stat.period);
+                args.add(stat.first_time);
+                args.add(stat.last_time);
+                args.add(stat.bar_count);
+                args.add(stat.volume);
+            }
+            affected += sqlx::query_with(&upsert_sql(&table_name, chunk.len()), args)
+                .execute(&mut *transaction)
+                .await?
+                .rows_affected();
+        }
+        transaction.commit().await?;
+        Ok(affected)
+    }
+
+    /// 保存collector中的增量, 失败时增量放回collector, 下次重试
+    pub async fn flush(
+        &self,
+        pool: &MySqlPool,
+        collector: &mut DailyStatsCollector,
+    ) -> Result<u64, sqlx::Error> {
+        let stats = collector.take();
+        if stats.is_empty() {
+            return Ok(0);
+        }
+        match self.upsert(pool, &stats).await {
+            Ok(affected) => Ok(affected),
+            Err(e) => {
+                stats.into_iter().for_each(|v| collector.merge(v));
+                Err(e)
+            },
+        }
+    }
+
+    /// 交易日的统计, breed为None时为所有品种, 按品种, 周期排序
+    pub async fn stats_by_day(
+        &self,
+        pool: &MySqlPool,
+        trade_day: NaiveDate,
+        breed: Option<&str>,
+    ) -> Result<Vec<DailyStat>, sqlx::Error> {
+        let table_name = self.table_name()?;
+        let breed_where = if breed.is_some() { " AND breed=?" } else { "" };
+        let sql = format!(
+            "SELECT {} FROM {} WHERE trade_day=?{} ORDER BY breed,period",
+            COLUMNS, table_name, breed_where
+        );
+        let mut args = MySqlArguments::default();
+        args.add(trade_day);
+        if let Some(breed) = breed {
+            args.add(breed);
+        }
+        sqlx::query_as_with::<_, DailyStat, _>(&sql, args)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// 品种在交易日范围内的统计, 按交易日, 周期排序
+    pub async fn stats_by_breed(
+        &self,
+        pool: &MySqlPool,
+        breed: &str,
+        sday: NaiveDate,
+        eday: NaiveDate,
+    ) -> Result<Vec<DailyStat>, sqlx::Error> {
+        let table_name = self.table_name()?;
+        let sql = format!(
+            "SELECT {} FROM {} WHERE breed=? AND trade_day>=? AND trade_day<=? ORDER BY \
+             trade_day,period",
+            COLUMNS, table_name
+        );
+        let mut args = MySqlArguments::default();
+        args.add(breed);
+        args.add(sday);
+        args.add(eday);
+        sqlx::query_as_with::<_, DailyStat, _>(&sql, args)
+            .fetch_all(pool)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::{upsert_sql, DailyStatsCollector, DailyStatsUtil};
+    use crate::mysqlx_test_pool::test_db_guard;
+
+    fn dt(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn day() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 10, 8).unwrap()
+    }
+
+    #[test]
+    fn test_collector() {
+        let mut collector = DailyStatsCollector::new();
+        assert!(collector.record("ag", day(), 1, dt("2024-10-07 21:01:00"), 5));
+        assert!(collector.record("ag", day(), 1, dt("2024-10-08 09:01:00"), 10));
+        assert!(collector.record("ag", day(), 5, dt("2024-10-08 09:05:00"), 15));
+        assert!(collector.record("au", day(), 1, dt("2024-10-08 09:01:00"), 1));
+        // 重复的K线
+        assert!(!collector.record("ag", day(), 1, dt("2024-10-08 09:01:00"), 10));
+        assert!(!collector.record("ag", day(), 1, dt("2024-10-07 21:01:00"), 5));
+        let stat = collector.get("ag", day(), 1).unwrap();
+        assert_eq!(stat.first_time, dt("2024-10-07 21:01:00"));
+        assert_eq!(stat.last_time, dt("2024-10-08 09:01:00"));
+        assert_eq!((stat.bar_count, stat.volume), (2, 15));
+
+        let stats = collector.take();
+        assert_eq!(
+            stats
+                .iter()
+                .map(|v| (v.breed.as_str(), v.period))
+                .collect::<Vec<_>>(),
+            [("ag", 1), ("ag", 5), ("au", 1)]
+        );
+        assert!(collector.is_empty());
+        // 保存后仍按最后一根K线时间去重
+        assert!(!collector.record("ag", day(), 1, dt("2024-10-08 09:01:00"), 10));
+        assert!(collector.record("ag", day(), 1, dt("2024-10-08 09:02:00"), 10));
+
+        let mut collector = DailyStatsCollector::new();
+        collector.seed(&stats);
+        assert!(!collector.record("ag", day(), 5, dt("2024-10-08 09:05:00"), 15));
+        collector.prune(day().succ_opt().unwrap());
+        assert!(collector.record("ag", day(), 5, dt("2024-10-08 09:05:00"), 15));
+    }
+
+    #[test]
+    fn test_upsert_sql() {
+        let sql = upsert_sql("t", 2);
+        assert!(sql.starts_with(
+            "INSERT INTO t(breed,trade_day,period,first_time,last_time,bar_count,volume,update_time) \
+             VALUES (?,?,?,?,?,?,?,NOW(6)),(?,?,?,?,?,?,?,NOW(6)) ON DUPLICATE KEY UPDATE"
+        ));
+        assert!(sql.contains(
+            "bar_count=IF(VALUES(first_time)>last_time,bar_count+VALUES(bar_count),bar_count)"
+        ));
+        assert!(sql.find("bar_count=").unwrap() < sql.find("last_time=").unwrap());
+    }
+
+    #[test]
+    fn test_daily_stats_toml() {
+        let toml = super::daily_stats_toml();
+        assert!(toml.contains(r#"tbl-private-key = ["breed", "trade-day", "period"]"#));
+        assert!(toml.contains(
+            r#"breed = { type = "VARCHAR(8)", not-null = true, default = "", comment = "品种" }"#
+        ));
+        #[cfg(feature = "sql-loader")]
+        {
+            let loader = ::toml::from_str::<crate::sql_loader::SqlLoader>(&toml);
+            assert!(loader.is_ok(), "{:?}", loader.err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flush_query() {
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        let util = DailyStatsUtil::new(guard.db()).unwrap();
+        util.create_table(&pool).await.unwrap();
+
+        let mut collector = DailyStatsCollector::new();
+        collector.record("test", day(), 1, dt("2024-10-07 21:01:00"), 5);
+        util.flush(&pool, &mut collector).await.unwrap();
+        collector.record("test", day(), 1, dt("2024-10-08 09:01:00"), 10);
+        let increment = collector.get("test", day(), 1).cloned().unwrap();
+        util.flush(&pool, &mut collector).await.unwrap();
+        // 同一增量再次保存不重复累加
+        util.upsert(&pool, &[increment]).await.unwrap();
+
+        let stats = util.stats_by_day(&pool, day(), Some("test")).await.unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].first_time, dt("2024-10-07 21:01:00"));
+        assert_eq!((stats[0].bar_count, stats[0].volume), (2, 15));
+        let stats = util
+            .stats_by_breed(&pool, "test", day(), day())
+            .await
+            .unwrap();
+        assert_eq!(stats.len(), 1);
+
+        // 重启后重新处理已保存的K线
+        let mut collector = DailyStatsCollector::new();
+        util.seed(&pool, &mut collector, day()).await.unwrap();
+        assert!(!collector.record("test", day(), 1, dt("2024-10-08 09:01:00"), 10));
+        assert!(collector.record("test", day(), 1, dt("2024-10-08 09:02:00"), 7));
+        util.flush(&pool, &mut collector).await.unwrap();
+        let stats = util.stats_by_day(&pool, day(), None).await.unwrap();
+        assert_eq!((stats[0].bar_count, stats[0].volume), (3, 22));
+        guard.cleanup().await;
+    }
+}
@@ -1,4 +1,12 @@
+use std::collections::HashMap;
 use std::ops::RangeInclusive;
+use std::sync::{OnceLock, RwLock};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::exchange::Exchange;
+use crate::human::format_price;
 
 const A_Z_LOWER_RANGE: RangeInclusive<char> = 'a'..='z';
 const A_Z_UPPER_RANGE: RangeInclusive<char> = 'A'..='Z';
@@ -15,8 +23,187 @@ pub fn breed_from_contract(contract: &str) -> String {
         .collect::<String>()
 }
 
+/// 合约类型, 期货期权的集合竞价时间和期货不同
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+pub enum InstrumentKind {
+    #[default]
+    Future,
+    Option,
+}
+
+impl InstrumentKind {
+    /// 期权合约为月份后跟C/P及行权价, 如m2409-C-3000, ag2412C6000, SR409P6000
+    pub fn from_contract(contract: &str) -> InstrumentKind {
+        let rest = contract
+            .trim_start_matches(|c: char| c.is_ascii_alphabetic())
+            .trim_start_matches(|c: char| c.is_ascii_digit());
+        let rest = rest.strip_prefix('-').unwrap_or(rest);
+        let is_option = rest.len() < contract.len()
+            && rest
+                .strip_prefix(['C', 'P'])
+                .map(|v| v.strip_prefix('-').unwrap_or(v))
+                .is_some_and(|v| !v.is_empty() && v.chars().all(|c| c.is_ascii_digit()));
+        if is_option {
+            InstrumentKind::Option
+        } else {
+            InstrumentKind::Future
+        }
+    }
+
+    /// 开盘前集合竞价的分钟数, 这些分钟的Tick属于开盘第一分钟
+    ///
+    /// 期货只有撮合的最后一分钟(20:59)有成交, 期权在整个竞价时段(20:55~21:00)都会推送行情
+    pub fn auction_minutes(&self) -> u32 {
+        match self {
+            InstrumentKind::Future => 1,
+            InstrumentKind::Option => 5,
+        }
+    }
+}
+
+/// 品种的合约信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BreedMeta {
+    /// 最小变动价位
+    pub tick:       Decimal,
+    /// 合约乘数
+    pub multiplier: Decimal,
+    /// 所属交易所
+    pub exchange:   Option<Exchange>,
+}
+
+impl BreedMeta {
+    pub fn new(tick: Decimal, multiplier: Decimal) -> BreedMeta {
+        BreedMeta {
+            tick,
+            multiplier,
+            exchange: None,
+        }
+    }
+
+    pub fn with_exchange(self, exchange: Exchange) -> BreedMeta {
+        BreedMeta {
+            exchange: Some(exchange),
+            ..self
+        }
+    }
+
+    /// 按最小变动价位格式化价格
+    pub fn format_price(&self, value: Decimal) -> String {
+        format_price(value, self.tick)
+    }
+}
+
+type BreedMetaMap = HashMap<(String, InstrumentKind), BreedMeta>;
+
+static BREED_META_HMAP: OnceLock<RwLock<BreedMetaMap>> = OnceLock::new();
+
+fn breed_meta_hmap() -> &'static RwLock<BreedMetaMap> {
+    BREED_META_HMAP.get_or_init(Default::default)
+}
+
+/// 注册品种信息, 已存在时覆盖
+pub fn register_breed_meta(breed: &str, meta: BreedMeta) {
+    register_breed_meta_of(breed, InstrumentKind::Future, meta);
+}
+
+/// 注册品种期货或期权的合约信息, 期权的最小变动价位, 合约乘数一般和期货不同
+pub fn register_breed_meta_of(breed: &str, kind: InstrumentKind, meta: BreedMeta) {
+    breed_meta_hmap()
+        .write()
+        .unwrap()
+        .insert((breed.to_owned(), kind), meta);
+}
+
+pub fn breed_meta(breed: &str) -> Option<BreedMeta> {
+    breed_meta_of(breed, InstrumentKind::Future)
+}
+
+pub fn breed_meta_of(breed: &str, kind: InstrumentKind) -> Option<BreedMeta> {
+    breed_meta_hmap()
+        .read()
+        .unwrap()
+        .get(&(breed.to_owned(), kind))
+        .cloned()
+}
+
+/// 合约所属品种及类型的信息, 期权没有注册时用期货的信息
+pub fn contract_meta(contract: &str) -> Option<BreedMeta> {
+    let breed = breed_from_contract(contract);
+    match InstrumentKind::from_contract(contract) {
+        InstrumentKind::Future => breed_meta(&breed),
+        kind => breed_meta_of(&breed, kind).or_else(|| breed_meta(&breed)),
+    }
+}
+
+/// 快照中保存的一条品种信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct BreedMetaItem {
+    pub(crate) breed: String,
+    pub(crate) kind:  InstrumentKind,
+    pub(crate) meta:  BreedMeta,
+}
+
+/// 已注册的品种信息, 按(品种, 类型)排序
+pub(crate) fn items() -> Vec<BreedMetaItem> {
+    let mut items = breed_meta_hmap()
+        .read()
+        .unwrap()
+        .iter()
+        .map(|((breed, kind), meta)| BreedMetaItem {
+            breed: breed.clone(),
+            kind:  *kind,
+            meta:  meta.clone(),
+        })
+        .collect::<Vec<_>>();
+    items.sort_by(|a, b| (&a.breed, a.kind).cmp(&(&b.breed, b.kind)));
+    items
+}
+
+/// 注册快照中的品种信息, 已注册的品种不覆盖
+pub(crate) fn init_from_items(items: Vec<BreedMetaItem>) {
+    let mut hmap = breed_meta_hmap().write().unwrap();
+    for item in items {
+        hmap.entry((item.breed, item.kind)).or_insert(item.meta);
+    }
+}
+
+/// 品种所属的交易所, 没有注册品种信息或没有设置交易所时为None
+pub fn breed_exchange(breed: &str) -> Option<Exchange> {
+    breed_meta(breed).and_then(|v| v.exchange)
+}
+
+/// 已注册的品种按交易所分组, 品种按名称排序
+pub fn breeds_by_exchange() -> HashMap<Exchange, Vec<String>> {
+    let mut groups = HashMap::<Exchange, Vec<String>>::new();
+    for ((breed, _), meta) in breed_meta_hmap().read().unwrap().iter() {
+        if let Some(exchange) = meta.exchange {
+            groups.entry(exchange).or_default().push(breed.clone());
+        }
+    }
+    groups.values_mut().for_each(|v| {
+        v.sort();
+        v.dedup();
+    });
+    groups
+}
+
+/// 按合约所属品种的最小变动价位格式化价格, 没有注册品种信息时去掉末尾的0
+pub fn format_contract_price(contract: &str, value: Decimal) -> String {
+    let tick = contract_meta(contract).map(|v| v.tick).unwrap_or_default();
+    format_price(value, tick)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+
+    use super::{
+        contract_meta, format_contract_price, register_breed_meta, register_breed_meta_of,
+        BreedMeta, InstrumentKind,
+    };
     use crate::hq::future::breed::breed_from_contract;
 
     #[test]
@@ -28,4 +215,37 @@ mod tests {
         let breed = breed_from_contract(&String::from("APL9"));
         println!("3: {}", breed);
     }
+
+    #[test]
+    fn test_format_contract_price() {
+        let d = |s: &str| Decimal::from_str(s).unwrap();
+        register_breed_meta("au", BreedMeta::new(d("0.02"), d("1000")));
+        assert_eq!(format_contract_price("au2412", d("560.3")), "560.30");
+        assert_eq!(format_contract_price("xx2412", d("560.300")), "560.3");
+    }
+
+    #[test]
+    fn test_instrument_kind() {
+        for contract in ["m2409-C-3000", "ag2412C6000", "SR409P6000", "IO2409-P-3800"] {
+            assert_eq!(
+                InstrumentKind::from_contract(contract),
+                InstrumentKind::Option
+            );
+        }
+        for contract in ["agL9", "ag2412", "APL8", "IF2409", "m", "C2409"] {
+            assert_eq!(
+                InstrumentKind::from_contract(contract),
+                InstrumentKind::Future
+            );
+        }
+
+        let d = |s: &str| Decimal::from_str(s).unwrap();
+        register_breed_meta("cu", BreedMeta::new(d("10"), d("5")));
+        register_breed_meta_of("cu", InstrumentKind::Option, BreedMeta::new(d("1"), d("5")));
+        assert_eq!(contract_meta("cu2412").unwrap().tick, d("10"));
+        assert_eq!(contract_meta("cu2412C70000").unwrap().tick, d("1"));
+        assert_eq!(format_contract_price("cu2412C70000", d("1204")), "1204");
+        register_breed_meta("zn", BreedMeta::new(d("5"), d("5")));
+        assert_eq!(contract_meta("zn2412P20000").unwrap().tick, d("5"));
+    }
 }
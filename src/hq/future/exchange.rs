@@ -0,0 +1,334 @@
+//! 交易所, 以及按交易所区分的交易日历和夜盘规则
+//!
+//! 没有注册交易所日历时使用全局的日历(`trade_day`), 中金所没有夜盘.
+//! `TimeRange`按品种的交易所取`Calendar`, 品种的交易所(`register_breed_meta`)需要在
+//! `period_convert::init`之前注册
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::breed::breed_exchange;
+use super::trade_day::calendar::CalendarRow;
+use super::trade_day::{self, night_override, TradeDay};
+
+#[derive(Debug, thiserror::Error)]
+#[error("exchange #{0}# parse err")]
+pub struct ExchangeParseError(String);
+
+/// 期货交易所
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Exchange {
+    /// 上期所
+    SHFE,
+    /// 大商所
+    DCE,
+    /// 郑商所
+    CZCE,
+    /// 中金所
+    CFFEX,
+    /// 上期能源
+    INE,
+    /// 广期所
+    GFEX,
+}
+
+impl Exchange {
+    pub const ALL: [Exchange; 6] = [
+        Exchange::SHFE,
+        Exchange::DCE,
+        Exchange::CZCE,
+        Exchange::CFFEX,
+        Exchange::INE,
+        Exchange::GFEX,
+    ];
+
+    /// 是否有品种开夜盘
+    pub fn has_night_session(&self) -> bool {
+        !matches!(self, Exchange::CFFEX)
+    }
+}
+
+impl fmt::Display for Exchange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Exchange::SHFE => "SHFE",
+            Exchange::DCE => "DCE",
+            Exchange::CZCE => "CZCE",
+            Exchange::CFFEX => "CFFEX",
+            Exchange::INE => "INE",
+            Exchange::GFEX => "GFEX",
+        };
+        f.write_str(s)
+    }
+}
+
+/// 不区分大小写
+impl FromStr for Exchange {
+    type Err = ExchangeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_uppercase();
+        Exchange::ALL
+            .into_iter()
+            .find(|v| v.to_string() == name)
+            .ok_or_else(|| ExchangeParseError(s.to_owned()))
+    }
+}
+
+type ExchangeCalendar = Arc<BTreeMap<NaiveDate, CalendarRow>>;
+
+static EXCHANGE_CALENDARS: OnceLock<RwLock<HashMap<Exchange, ExchangeCalendar>>> = OnceLock::new();
+
+fn exchange_calendars() -> &'static RwLock<HashMap<Exchange, ExchangeCalendar>> {
+    EXCHANGE_CALENDARS.get_or_init(Default::default)
+}
+
+/// 注册交易所单独的日历, 已存在时覆盖
+pub fn register_exchange_calendar(exchange: Exchange, rows: &[CalendarRow]) {
+    let calendar = rows.iter().map(|v| (v.day, *v)).collect();
+    exchange_calendars()
+        .write()
+        .unwrap()
+        .insert(exchange, Arc::new(calendar));
+}
+
+fn exchange_calendar(exchange: Exchange) -> Option<ExchangeCalendar> {
+    exchange_calendars().read().unwrap().get(&exchange).cloned()
+}
+
+/// 是否交易所的交易日
+pub fn is_trade_day(exchange: Exchange, day: &NaiveDate) -> bool {
+    match exchange_calendar(exchange) {
+        Some(calendar) => calendar.contains_key(day),
+        None => trade_day::is_trade_day(day),
+    }
+}
+
+/// 交易所day(夜盘开始的交易日)是否有夜盘, 有夜盘覆盖时使用覆盖的值, 交易所的覆盖优先
+pub fn has_night(exchange: Exchange, day: &NaiveDate) -> bool {
+    if !exchange.has_night_session() {
+        return false;
+    }
+    if let Some(has_night) = exchange_night_override(exchange, day) {
+        return has_night;
+    }
+    match exchange_calendar(exchange) {
+        Some(calendar) => {
+            night_override(day).unwrap_or_else(|| calendar.get(day).is_some_and(|v| v.night))
+        },
+        None => trade_day::has_night(day),
+    }
+}
+
+/// 交易所day之后的下一交易日, day是自然日期, 超出日历范围时为None
+pub fn next_trade_day(exchange: Exchange, day: &NaiveDate) -> Option<NaiveDate> {
+    match exchange_calendar(exchange) {
+        Some(calendar) => calendar
+            .range(day.succ_opt()?..)
+            .next()
+            .map(|(day, _)| *day),
+        None => Some(trade_day::next_trade_day(day).day),
+    }
+}
+
+/// 交易所单独的夜盘覆盖, 优先于全局的覆盖(`trade_day::set_night_override`)
+static EXCHANGE_NIGHT_OVERRIDES: OnceLock<RwLock<HashMap<(Exchange, NaiveDate), bool>>> =
+    OnceLock::new();
+
+fn exchange_night_overrides() -> &'static RwLock<HashMap<(Exchange, NaiveDate), bool>> {
+    EXCHANGE_NIGHT_OVERRIDES.get_or_init(Default::default)
+}
+
+/// 临时取消或增加交易所day(夜盘开始的交易日)的夜盘
+pub fn set_exchange_night_override(exchange: Exchange, day: NaiveDate, has_night: bool) {
+    exchange_night_overrides()
+        .write()
+        .unwrap()
+        .insert((exchange, day), has_night);
+}
+
+pub fn remove_exchange_night_override(exchange: Exchange, day: &NaiveDate) -> Option<bool> {
+    exchange_night_overrides()
+        .write()
+        .unwrap()
+        .remove(&(exchange, *day))
+}
+
+pub fn clear_exchange_night_overrides() {
+    exchange_night_overrides().write().unwrap().clear();
+}
+
+fn exchange_night_override(exchange: Exchange, day: &NaiveDate) -> Option<bool> {
+    exchange_night_overrides()
+        .read()
+        .unwrap()
+        .get(&(exchange, *day))
+        .copied()
+}
+
+/// 交易所交易日的前一交易日, 不是交易日时为None
+fn prev_trade_day(exchange: Exchange, day: &NaiveDate) -> Option<NaiveDate> {
+    match exchange_calendar(exchange) {
+        Some(calendar) => calendar.get(day).map(|v| v.prev),
+        None => trade_day::prev_trade_day(day),
+    }
+}
+
+/// 同`trade_day::trade_day`, 超出交易所日历范围时为None
+fn exchange_trade_day(exchange: Exchange, day: &NaiveDate) -> Option<TradeDay> {
+    let calendar = exchange_calendar(exchange)?;
+    if let Some(row) = calendar.get(day) {
+        return Some(TradeDay {
+            is_trade_day: true,
+            day:          *day,
+            td_next:      row.next,
+            td_prev:      row.prev,
+            has_night:    row.night && exchange.has_night_session(),
+        });
+    }
+    let (_, row) = calendar.range(day..).next()?;
+    Some(TradeDay {
+        is_trade_day: false,
+        day:          *day,
+        td_next:      row.day,
+        td_prev:      row.prev,
+        has_night:    false,
+    })
+}
+
+/// 品种使用的交易日历, 有交易所时按交易所的日历和夜盘规则, 否则为全局日历
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Calendar {
+    exchange: Option<Exchange>,
+}
+
+impl Calendar {
+    /// 全局日历
+    pub fn global() -> Calendar {
+        Calendar::default()
+    }
+
+    pub fn of_exchange(exchange: Exchange) -> Calendar {
+        Calendar {
+            exchange: Some(exchange),
+        }
+    }
+
+    /// 按品种所属的交易所, 没有交易所信息时为全局日历
+    pub fn of_breed(breed: &str) -> Calendar {
+        Calendar {
+            exchange: breed_exchange(breed),
+        }
+    }
+
+    pub fn exchange(&self) -> Option<Exchange> {
+        self.exchange
+    }
+
+    pub fn is_trade_day(&self, day: &NaiveDate) -> bool {
+        match self.exchange {
+            Some(exchange) => is_trade_day(exchange, day),
+            None => trade_day::is_trade_day(day),
+        }
+    }
+
+    /// day(夜盘开始的交易日)是否有夜盘, 有夜盘覆盖时使用覆盖的值
+    pub fn has_night(&self, day: &NaiveDate) -> bool {
+        match self.exchange {
+            Some(exchange) => has_night(exchange, day),
+            None => trade_day::has_night(day),
+        }
+    }
+
+    /// 交易日的前一交易日, 即该交易日夜盘开始的交易日, 不是交易日时为None
+    pub fn prev_trade_day(&self, day: &NaiveDate) -> Option<NaiveDate> {
+        match self.exchange {
+            Some(exchange) => prev_trade_day(exchange, day),
+            None => trade_day::prev_trade_day(day),
+        }
+    }
+
+    /// 同`trade_day::trade_day`, has_night为日历中的值, 不含夜盘覆盖
+    ///
+    /// 超出交易所日历范围时使用全局日历
+    pub fn trade_day(&self, day: &NaiveDate) -> TradeDay {
+        self.exchange
+            .and_then(|v| exchange_trade_day(v, day))
+            .unwrap_or_else(|| trade_day::trade_day(day).as_ref().clone())
+    }
+}
+
+/// 按品种所属交易所判断是否交易日, 没有交易所信息时使用全局日历
+pub fn breed_is_trade_day(breed: &str, day: &NaiveDate) -> bool {
+    Calendar::of_breed(breed).is_trade_day(day)
+}
+
+/// 按品种所属交易所判断是否有夜盘, 没有交易所信息时使用全局日历
+pub fn breed_has_night(breed: &str, day: &NaiveDate) -> bool {
+    Calendar::of_breed(breed).has_night(day)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    use super::{
+        breed_has_night, breed_is_trade_day, has_night, next_trade_day, register_exchange_calendar,
+        Exchange,
+    };
+    use crate::hq::future::breed::{breeds_by_exchange, register_breed_meta, BreedMeta};
+    use crate::hq::future::trade_day::calendar::CalendarRules;
+    use crate::hq::future::trade_day::test_overrides;
+
+    fn d(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("shfe".parse::<Exchange>().unwrap(), Exchange::SHFE);
+        assert_eq!(" CFFEX ".parse::<Exchange>().unwrap(), Exchange::CFFEX);
+        assert!("SSE".parse::<Exchange>().is_err());
+        assert_eq!(Exchange::GFEX.to_string(), "GFEX");
+    }
+
+    #[test]
+    fn test_exchange_calendar() {
+        let _overrides = test_overrides::shared();
+        // 2098-01-06是周一
+        let rules = CalendarRules::new().with_holiday(d("2098-01-08"));
+        let rows = rules.generate(d("2098-01-06"), d("2098-01-10")).unwrap();
+        register_exchange_calendar(Exchange::DCE, &rows);
+        register_exchange_calendar(Exchange::CFFEX, &rows);
+        register_breed_meta(
+            "exm",
+            BreedMeta::new(Decimal::ONE, Decimal::TEN).with_exchange(Exchange::DCE),
+        );
+        register_breed_meta(
+            "exif",
+            BreedMeta::new(Decimal::ONE, Decimal::TEN).with_exchange(Exchange::CFFEX),
+        );
+
+        assert!(breed_is_trade_day("exm", &d("2098-01-06")));
+        assert!(!breed_is_trade_day("exm", &d("2098-01-08")));
+        assert!(breed_has_night("exm", &d("2098-01-06")));
+        // 节假日前没有夜盘
+        assert!(!has_night(Exchange::DCE, &d("2098-01-07")));
+        // 中金所没有夜盘
+        assert!(!breed_has_night("exif", &d("2098-01-06")));
+        assert_eq!(
+            next_trade_day(Exchange::DCE, &d("2098-01-07")),
+            Some(d("2098-01-09"))
+        );
+        assert_eq!(next_trade_day(Exchange::DCE, &d("2098-01-10")), None);
+
+        let groups = breeds_by_exchange();
+        assert!(groups[&Exchange::DCE].contains(&"exm".to_owned()));
+        assert!(groups[&Exchange::CFFEX].contains(&"exif".to_owned()));
+    }
+}
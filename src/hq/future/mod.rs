@@ -1,5 +1,9 @@
+pub use self::sync_minutes::{sync_minutes, SyncMinutes};
+
 pub mod breed;
 pub mod db;
+pub mod exchange;
 pub mod period_convert;
+pub mod sync_minutes;
 pub mod time_range;
 pub mod trade_day;
@@ -1,14 +1,16 @@
 use std::collections::HashMap;
 use std::sync::{Arc, OnceLock};
 
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use sqlx::MySqlPool;
 
 use self::d1::Converter1d;
 use self::m1::Converter1m;
 use self::xm::ConverterXm;
+use super::breed::{breed_from_contract, InstrumentKind};
 use super::time_range::{self, TimeRangeError};
 use super::trade_day;
+use crate::hq::period::Period;
 
 pub(crate) mod d1;
 pub(crate) mod m1;
@@ -32,7 +34,8 @@ pub enum PeriodConvertError {
     TimeError(NaiveDateTime),
 }
 
-static BREED_CONVERTER_MAP: OnceLock<HashMap<String, Arc<Converter>>> = OnceLock::new();
+static BREED_CONVERTER_MAP: OnceLock<HashMap<(String, InstrumentKind), Arc<Converter>>> =
+    OnceLock::new();
 
 pub async fn init(pool: Arc<MySqlPool>) -> Result<(), PeriodConvertError> {
     trade_day::init_from_db(pool.clone()).await?;
@@ -40,28 +43,37 @@ pub async fn init(pool: Arc<MySqlPool>) -> Result<(), PeriodConvertError> {
     m1::init_from_time_range(pool.clone()).await?;
     xm::init_from_time_range(pool.clone()).await?;
     d1::init_from_time_range(pool).await?;
+    init_from_time_range();
+    Ok(())
+}
+
+/// 用已初始化的TimeRange生成所有周期的转换器, 已经初始化过时忽略
+pub(crate) fn init_from_time_range() {
+    m1::init();
+    xm::init();
+    d1::init();
 
     if BREED_CONVERTER_MAP.get().is_some() {
-        return Ok(());
+        return;
     }
     let mut breed_converter_map = HashMap::new();
     let time_range_hmap = time_range::hash_map();
     for breed in time_range_hmap.keys() {
-        let converter1m = m1::by_breed(breed).unwrap();
         let converterxm = xm::by_breed(breed).unwrap();
         let converter1d = d1::by_breed(breed).unwrap();
-        breed_converter_map.insert(
-            breed.to_string(),
-            Arc::new(Converter {
-                converter1m,
-                converterxm,
-                converter1d,
-            }),
-        );
+        for kind in [InstrumentKind::Future, InstrumentKind::Option] {
+            let converter1m = m1::by_breed_kind(breed, kind).unwrap();
+            breed_converter_map.insert(
+                (breed.to_string(), kind),
+                Arc::new(Converter {
+                    converter1m,
+                    converterxm: converterxm.clone(),
+                    converter1d: converter1d.clone(),
+                }),
+            );
+        }
     }
-    BREED_CONVERTER_MAP.set(breed_converter_map).unwrap();
-
-    Ok(())
+    let _ = BREED_CONVERTER_MAP.set(breed_converter_map);
 }
 
 #[derive(Debug)]
@@ -76,11 +88,24 @@ impl Converter {
         self.converter1m.convert(dt)
     }
 
+    /// period按`Period`解析, 见`to_period`
     pub fn to_xm(
         &self,
         period: &str,
         dt: &NaiveDateTime,
         trade_date: &NaiveDate,
+    ) -> Result<NaiveDateTime, PeriodConvertError> {
+        let period = period
+            .parse::<Period>()
+            .map_err(|_| PeriodConvertError::PeriodError(period.to_owned()))?;
+        self.to_period(period, dt, trade_date)
+    }
+
+    pub fn to_period(
+        &self,
+        period: Period,
+        dt: &NaiveDateTime,
+        trade_date: &NaiveDate,
     ) -> Result<NaiveDateTime, PeriodConvertError> {
         self.converterxm.convert(period, dt, trade_date)
     }
@@ -88,18 +113,53 @@ impl Converter {
     pub fn to_1d(&self, trade_date: &NaiveDate) -> NaiveDateTime {
         self.converter1d.convert(trade_date)
     }
+
+    /// 一个交易日内该周期所有K线的结束时间
+    pub fn bar_ends(&self, period: Period) -> Result<Vec<NaiveTime>, PeriodConvertError> {
+        self.converterxm.bar_ends(period)
+    }
+
+    /// 按trade_date实际的交易时间段, 该周期所有K线的结束时间
+    pub fn bar_ends_on(
+        &self,
+        period: Period,
+        trade_date: &NaiveDate,
+    ) -> Result<Vec<NaiveTime>, PeriodConvertError> {
+        self.converterxm.bar_ends_on(period, trade_date)
+    }
+
+    /// 一个交易日内该周期的K线数量
+    pub fn bars_per_day(&self, period: Period, has_night: bool) -> Result<usize, PeriodConvertError> {
+        self.converterxm.bars_per_day(period, has_night)
+    }
 }
 
 pub fn converter_by_breed(breed: &str) -> Result<Arc<Converter>, PeriodConvertError> {
+    converter_by_breed_kind(breed, InstrumentKind::Future)
+}
+
+/// 期权和标的期货的交易时间相同, 只是集合竞价的Tick转1m不同
+pub fn converter_by_breed_kind(
+    breed: &str,
+    kind: InstrumentKind,
+) -> Result<Arc<Converter>, PeriodConvertError> {
     let converter = BREED_CONVERTER_MAP
         .get()
         .unwrap()
-        .get(breed)
+        .get(&(breed.to_owned(), kind))
         .ok_or(PeriodConvertError::BreedError(breed.to_string()))?
         .clone();
     Ok(converter)
 }
 
+/// 按合约的品种及类型, 如m2409-C-3000为m的期权
+pub fn converter_by_contract(contract: &str) -> Result<Arc<Converter>, PeriodConvertError> {
+    converter_by_breed_kind(
+        &breed_from_contract(contract),
+        InstrumentKind::from_contract(contract),
+    )
+}
+
 pub fn converter_qh_base() -> Arc<Converter> {
     converter_by_breed("QHbase").unwrap()
 }
@@ -10,10 +10,16 @@ use crate::hq::future::time_range;
 static BREED_CONVERTER1D_MAP: OnceLock<HashMap<String, Arc<Converter1d>>> = OnceLock::new();
 
 pub async fn init_from_time_range(pool: Arc<MySqlPool>) -> Result<(), PeriodConvertError> {
+    time_range::init_from_db(pool).await?;
+    init();
+    Ok(())
+}
+
+/// 用已初始化的TimeRange生成, 已经初始化过时忽略
+pub(crate) fn init() {
     if BREED_CONVERTER1D_MAP.get().is_some() {
-        return Ok(());
+        return;
     }
-    time_range::init_from_db(pool).await?;
 
     let mut breed_converter1d_map = HashMap::new();
     let time_range_hmap = time_range::hash_map();
@@ -26,8 +32,7 @@ pub async fn init_from_time_range(pool: Arc<MySqlPool>) -> Result<(), PeriodConv
             }),
         );
     }
-    BREED_CONVERTER1D_MAP.set(breed_converter1d_map).unwrap();
-    Ok(())
+    let _ = BREED_CONVERTER1D_MAP.set(breed_converter1d_map);
 }
 
 #[derive(Debug)]
@@ -5,63 +5,37 @@ use chrono::{Duration, NaiveDateTime, NaiveTime, Timelike};
 use sqlx::MySqlPool;
 
 use super::PeriodConvertError;
+use crate::hq::future::breed::InstrumentKind;
 use crate::hq::future::time_range::{self, TimeRange};
 use crate::ymdhms::Hms;
 
 // TODO 这块的Arc还没有做
-static BREED_CONVERTER1M_HAMP: OnceLock<HashMap<String, Arc<Converter1m>>> = OnceLock::new();
+static BREED_CONVERTER1M_HAMP: OnceLock<HashMap<(String, InstrumentKind), Arc<Converter1m>>> =
+    OnceLock::new();
 
 pub async fn init_from_time_range(pool: Arc<MySqlPool>) -> Result<(), PeriodConvertError> {
+    time_range::init_from_db(pool).await?;
+    init();
+    Ok(())
+}
+
+/// 用已初始化的TimeRange生成, 已经初始化过时忽略
+pub(crate) fn init() {
     if BREED_CONVERTER1M_HAMP.get().is_some() {
-        return Ok(());
+        return;
     }
-    time_range::init_from_db(pool).await?;
 
     let mut breed_converter1m_hmap = HashMap::new();
     let time_range_hmap = time_range::hash_map();
     for (breed, time_range) in time_range_hmap {
-        let times_vec = time_range.times_vec();
-        let mut hhmm_time_map = HashMap::new();
-        for (idx, (open_time, close_time)) in times_vec.iter().enumerate() {
-            if idx == 0 {
-                let hhmmss: Hms = open_time.into();
-                match hhmmss.hhmmss {
-                    9_00_00 => {
-                        hhmm_time_map.insert(859, NaiveTime::from_hms_opt(9, 1, 0).unwrap());
-                    },
-                    9_30_00 => {
-                        hhmm_time_map.insert(929, NaiveTime::from_hms_opt(9, 31, 0).unwrap());
-                    },
-                    21_00_00 => {
-                        hhmm_time_map.insert(2059, NaiveTime::from_hms_opt(21, 1, 0).unwrap());
-                    },
-                    start => panic!("error start: {}", start),
-                }
-            }
-            // let hhmmss: Hms = open_time.into();
-            // hhmm_time_map.insert(hhmmss.hhmm, *open_time + Duration::minutes(1));
-            let hhmmss: Hms = close_time.into();
-            hhmm_time_map.insert(hhmmss.hhmm, *close_time);
+        for kind in [InstrumentKind::Future, InstrumentKind::Option] {
+            breed_converter1m_hmap.insert(
+                (breed.to_string(), kind),
+                Arc::new(Converter1m::new(breed, time_range.clone(), kind)),
+            );
         }
-
-        let (_, first_close_time) = unsafe { times_vec.get_unchecked(0) };
-
-        if *first_close_time < NaiveTime::from_hms_opt(3, 0, 0).unwrap() {
-            hhmm_time_map.insert(0u16, NaiveTime::from_hms_opt(0, 0, 0).unwrap());
-        }
-        let breed = breed.to_string();
-        breed_converter1m_hmap.insert(
-            breed.clone(),
-            Arc::new(Converter1m {
-                breed,
-                time_range: time_range.clone(),
-                hhmm_time_map,
-            }),
-        );
     }
-    BREED_CONVERTER1M_HAMP.set(breed_converter1m_hmap).unwrap();
-
-    Ok(())
+    let _ = BREED_CONVERTER1M_HAMP.set(breed_converter1m_hmap);
 }
 
 #[derive(Debug)]
@@ -73,9 +47,33 @@ pub struct Converter1m {
 }
 
 impl Converter1m {
+    fn new(breed: &str, time_range: Arc<TimeRange>, kind: InstrumentKind) -> Converter1m {
+        let mut hhmm_time_map = HashMap::new();
+        for (auction_minute, first_minute) in time_range.auction_minutes(kind) {
+            let hhmmss: Hms = (&auction_minute).into();
+            hhmm_time_map.insert(hhmmss.hhmm, first_minute);
+        }
+        for (_, close_time) in time_range.times_vec() {
+            let hhmmss: Hms = close_time.into();
+            hhmm_time_map.insert(hhmmss.hhmm, *close_time);
+        }
+
+        let (_, first_close_time) = unsafe { time_range.times_vec().get_unchecked(0) };
+
+        if *first_close_time < NaiveTime::from_hms_opt(3, 0, 0).unwrap() {
+            hhmm_time_map.insert(0u16, NaiveTime::from_hms_opt(0, 0, 0).unwrap());
+        }
+        Converter1m {
+            breed: breed.to_string(),
+            time_range,
+            hhmm_time_map,
+        }
+    }
+
     /// Tick时间转成1m时间
     /// 特殊时间点
-    /// 1. 开盘的前一分钟及第一分钟是属于开盘的时间, 如20:59:xx~21:00:59的K线时间为 21:01:00
+    /// 1. 集合竞价的分钟及开盘第一分钟是属于开盘的时间, 如20:59:xx~21:00:59的K线时间为 21:01:00,
+    ///    期权为20:55:00~21:00:59, 见`TimeRange::auction_minutes`
     /// 2. 每个交易段的最后时间是属于该段结束时间,  如11:30:00K线时间为11:30:00
     /// 3. 00:00:00时间是属于00:00:00, 而不是 00:01:00
     /// 其他时间
@@ -122,11 +120,14 @@ impl Converter1m {
     }
 }
 
-pub(crate) fn by_breed(breed: &str) -> Result<Arc<Converter1m>, PeriodConvertError> {
+pub(crate) fn by_breed_kind(
+    breed: &str,
+    kind: InstrumentKind,
+) -> Result<Arc<Converter1m>, PeriodConvertError> {
     let converter1m = BREED_CONVERTER1M_HAMP
         .get()
         .unwrap()
-        .get(breed)
+        .get(&(breed.to_owned(), kind))
         .ok_or(PeriodConvertError::BreedError(breed.to_string()))?
         .clone();
     Ok(converter1m)
@@ -135,9 +136,13 @@ pub(crate) fn by_breed(breed: &str) -> Result<Arc<Converter1m>, PeriodConvertErr
 #[cfg(test)]
 mod tests {
 
-    use chrono::NaiveDateTime;
+    use std::sync::Arc;
 
-    use super::{by_breed, init_from_time_range};
+    use chrono::{NaiveDateTime, NaiveTime};
+
+    use super::{by_breed_kind, init_from_time_range, Converter1m};
+    use crate::hq::future::breed::InstrumentKind;
+    use crate::hq::future::time_range::TimeRangeBuilder;
     use crate::mysqlx::MySqlPools;
     use crate::mysqlx_test_pool::init_test_mysql_pools;
 
@@ -158,7 +163,7 @@ mod tests {
         for (source, target) in results {
             let dt = NaiveDateTime::parse_from_str(source, "%Y-%m-%d %H:%M:%S").unwrap();
 
-            let converter1m = by_breed(breed).unwrap();
+            let converter1m = by_breed_kind(breed, InstrumentKind::Future).unwrap();
 
             let time_1m = converter1m.convert(&dt);
             let Ok(time_1m) = time_1m else {
@@ -337,4 +342,63 @@ mod tests {
         ];
         test_1m("ag", &results).await;
     }
+
+    #[test]
+    fn test_option_auction() {
+        let t = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let time_range = TimeRangeBuilder::from_times(&[
+            (t(21, 0), t(2, 30)),
+            (t(9, 0), t(10, 15)),
+            (t(10, 30), t(11, 30)),
+            (t(13, 30), t(15, 0)),
+        ])
+        .build()
+        .unwrap();
+        let time_range = Arc::new(time_range);
+        let future = Converter1m::new("ag", time_range.clone(), InstrumentKind::Future);
+        let option = Converter1m::new("ag", time_range, InstrumentKind::Option);
+        let dt = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap();
+
+        let cases = [
+            ("2022-06-10 20:55:00", None, Some("2022-06-10 21:01:00")),
+            ("2022-06-10 20:58:59", None, Some("2022-06-10 21:01:00")),
+            (
+                "2022-06-10 20:59:00",
+                Some("2022-06-10 21:01:00"),
+                Some("2022-06-10 21:01:00"),
+            ),
+            (
+                "2022-06-10 21:00:30",
+                Some("2022-06-10 21:01:00"),
+                Some("2022-06-10 21:01:00"),
+            ),
+            (
+                "2022-06-10 21:01:30",
+                Some("2022-06-10 21:02:00"),
+                Some("2022-06-10 21:02:00"),
+            ),
+            // 无夜盘时白盘的集合竞价
+            ("2022-06-13 08:56:10", None, Some("2022-06-13 09:01:00")),
+            (
+                "2022-06-13 08:59:10",
+                Some("2022-06-13 09:01:00"),
+                Some("2022-06-13 09:01:00"),
+            ),
+            ("2022-06-13 20:54:59", None, None),
+        ];
+        for (source, future_target, option_target) in cases {
+            assert_eq!(
+                future.convert(&dt(source)).ok(),
+                future_target.map(dt),
+                "future {}",
+                source
+            );
+            assert_eq!(
+                option.convert(&dt(source)).ok(),
+                option_target.map(dt),
+                "option {}",
+                source
+            );
+        }
+    }
 }
@@ -1,12 +1,12 @@
 use std::collections::HashMap;
-use std::sync::{Arc, OnceLock};
+use std::sync::{Arc, OnceLock, RwLock};
 
 use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
 use sqlx::MySqlPool;
 
 use super::PeriodConvertError;
-use crate::hq::future::time_range;
-use crate::hq::period::PeriodValue;
+use crate::hq::future::time_range::{self, TimeRange};
+use crate::hq::period::Period;
 
 #[allow(unused)]
 #[derive(Debug, Clone)]
@@ -26,145 +26,238 @@ struct PeriodTimeInfo {
 static BREED_CONVERTERXM_HMAP: OnceLock<HashMap<String, Arc<ConverterXm>>> = OnceLock::new();
 
 pub async fn init_from_time_range(pool: Arc<MySqlPool>) -> Result<(), PeriodConvertError> {
+    time_range::init_from_db(pool).await?;
+    init();
+    Ok(())
+}
+
+/// 用已初始化的TimeRange生成, 已经初始化过时忽略
+pub(crate) fn init() {
     if BREED_CONVERTERXM_HMAP.get().is_some() {
-        return Ok(());
+        return;
     }
-    time_range::init_from_db(pool).await?;
 
     let mut breed_period_time = HashMap::new();
-    let periods = &["5m", "15m", "30m", "60m", "120m"];
+    let time_range_hmap = time_range::hash_map();
+
+    let mut period_time_info_map = HashMap::new();
+
+    for breed in time_range_hmap.keys() {
+        // 按品种的交易所日历划分当天实际的交易时间段
+        let time_range = time_range::time_range_by_breed(breed).unwrap();
+        let full = build_tables(time_range.times_vec(), &mut period_time_info_map);
+        breed_period_time.insert(
+            breed.to_string(),
+            Arc::new(ConverterXm {
+                time_range,
+                full:       Arc::new(full),
+                shortened:  Default::default(),
+            }),
+        );
+    }
+    let _ = BREED_CONVERTERXM_HMAP.set(breed_period_time);
+}
+
+/// 按交易时间段划分各周期, period_time_info_map用于共用相同的PeriodTimeInfo
+fn build_tables(
+    times_vec: &[(NaiveTime, NaiveTime)],
+    period_time_info_map: &mut HashMap<String, Arc<PeriodTimeInfo>>,
+) -> PeriodTables {
+    let periods = [Period::M5, Period::M15, Period::M30, Period::M60, Period::M120];
 
     let date = NaiveDate::default();
-    let time_range_hmap = time_range::hash_map();
 
     let time_2059 = NaiveTime::from_hms_opt(20, 59, 0).unwrap();
     let time_235959 = NaiveTime::from_hms_opt(23, 59, 59).unwrap();
     let time_0300 = NaiveTime::from_hms_opt(3, 0, 0).unwrap();
     let time_0859 = NaiveTime::from_hms_opt(8, 59, 0).unwrap();
 
-    let mut period_time_info_map = HashMap::new();
-
-    for (breed, time_range) in time_range_hmap {
-        let times_vec = time_range.times_vec();
-
-        let mut period_time_map = HashMap::new();
-
-        for period in periods {
-            let pv = PeriodValue::pv(period).unwrap();
-            let mut idx = 0;
-            let mut period_s_dt = None;
-            let mut time_vec = Vec::new();
-            let mut time_ptime_map = HashMap::new();
-            for (open_time, close_time) in times_vec.iter() {
-                let open_dt = date.and_time(*open_time);
-                let close_dt = if open_time > close_time {
-                    date.succ_opt().unwrap().and_time(*close_time)
-                } else {
-                    date.and_time(*close_time)
-                };
-                let mut time = open_dt + Duration::try_minutes(1).unwrap();
-                while time <= close_dt {
-                    if period_s_dt.is_none() {
-                        period_s_dt = Some(time);
+    let mut period_time_map = HashMap::new();
+    let mut period_bars_map = HashMap::new();
+
+    for period in periods {
+        let pv = i32::from(period);
+        let mut idx = 0;
+        let mut period_s_dt = None;
+        let mut time_vec = Vec::new();
+        let mut time_ptime_map = HashMap::new();
+        let mut bars = Vec::new();
+        for (open_time, close_time) in times_vec.iter() {
+            let open_dt = date.and_time(*open_time);
+            let close_dt = if open_time > close_time {
+                date.succ_opt().unwrap().and_time(*close_time)
+            } else {
+                date.and_time(*close_time)
+            };
+            let mut time = open_dt + Duration::try_minutes(1).unwrap();
+            while time <= close_dt {
+                if period_s_dt.is_none() {
+                    period_s_dt = Some(time);
+                }
+                idx += 1;
+                let start_time = period_s_dt.unwrap();
+                time_vec.push((start_time, time));
+                if idx % pv == 0 {
+                    let start_dt = period_s_dt.take().unwrap();
+                    let end_dt = time;
+                    let mut night_diff_day = false;
+                    let mut use_trade_date = false;
+                    let s_time = start_dt.time();
+                    let e_time = end_dt.time();
+                    if s_time > time_2059 && e_time < time_0300 {
+                        night_diff_day = true;
+                    } else if s_time < time_0300 && e_time > time_0859 {
+                        use_trade_date = true
                     }
-                    idx += 1;
-                    let start_time = period_s_dt.unwrap();
-                    time_vec.push((start_time, time));
-                    if idx % pv == 0 {
-                        let start_dt = period_s_dt.take().unwrap();
-                        let end_dt = time;
-                        let mut night_diff_day = false;
-                        let mut use_trade_date = false;
-                        let s_time = start_dt.time();
-                        let e_time = end_dt.time();
-                        if s_time > time_2059 && e_time < time_0300 {
-                            night_diff_day = true;
-                        } else if s_time < time_0300 && e_time > time_0859 {
-                            use_trade_date = true
-                        }
-                        for (_, dt) in time_vec.iter() {
-                            let time = dt.time();
-                            let day_add_1 =
-                                night_diff_day && time >= time_2059 && time <= time_235959;
-
-                            let key =
-                                format!("{}-{}-{}-{}", s_time, e_time, day_add_1, use_trade_date);
-                            let period_time_info = period_time_info_map
-                                .entry(key)
-                                .or_insert_with(|| {
-                                    Arc::new(PeriodTimeInfo {
-                                        s_time,
-                                        e_time,
-                                        day_add_1,
-                                        use_trade_date,
-                                    })
+                    for (_, dt) in time_vec.iter() {
+                        let time = dt.time();
+                        let day_add_1 = night_diff_day && time >= time_2059 && time <= time_235959;
+
+                        let key = format!("{}-{}-{}-{}", s_time, e_time, day_add_1, use_trade_date);
+                        let period_time_info = period_time_info_map
+                            .entry(key)
+                            .or_insert_with(|| {
+                                Arc::new(PeriodTimeInfo {
+                                    s_time,
+                                    e_time,
+                                    day_add_1,
+                                    use_trade_date,
                                 })
-                                .clone();
+                            })
+                            .clone();
 
-                            time_ptime_map.insert(time, period_time_info.clone());
-                        }
-                        time_vec.clear();
+                        time_ptime_map.insert(time, period_time_info.clone());
                     }
-                    time += Duration::try_minutes(1).unwrap();
+                    bars.push((s_time, e_time));
+                    time_vec.clear();
                 }
+                time += Duration::try_minutes(1).unwrap();
             }
+        }
 
-            if !time_vec.is_empty() {
-                let (start_dt, _) = time_vec.first().unwrap();
-                let (_, end_dt) = time_vec.last().unwrap();
-                let mut night_diff_day = false;
-                let mut use_trade_date = false;
-                let s_time = start_dt.time();
-                let e_time = end_dt.time();
-                if s_time > time_2059 && e_time < time_0300 {
-                    night_diff_day = true;
-                } else if s_time < time_0300 && e_time > time_0859 {
-                    use_trade_date = true
-                }
-                for (_, dt) in time_vec {
-                    let time = dt.time();
-                    let day_add_1 = night_diff_day && time >= time_2059 && time <= time_235959;
-
-                    let key = format!("{}-{}-{}-{}", s_time, e_time, day_add_1, use_trade_date);
-                    let period_time_info = period_time_info_map
-                        .entry(key)
-                        .or_insert_with(|| {
-                            Arc::new(PeriodTimeInfo {
-                                s_time,
-                                e_time,
-                                day_add_1,
-                                use_trade_date,
-                            })
+        if !time_vec.is_empty() {
+            let (start_dt, _) = time_vec.first().unwrap();
+            let (_, end_dt) = time_vec.last().unwrap();
+            let mut night_diff_day = false;
+            let mut use_trade_date = false;
+            let s_time = start_dt.time();
+            let e_time = end_dt.time();
+            if s_time > time_2059 && e_time < time_0300 {
+                night_diff_day = true;
+            } else if s_time < time_0300 && e_time > time_0859 {
+                use_trade_date = true
+            }
+            for (_, dt) in time_vec {
+                let time = dt.time();
+                let day_add_1 = night_diff_day && time >= time_2059 && time <= time_235959;
+
+                let key = format!("{}-{}-{}-{}", s_time, e_time, day_add_1, use_trade_date);
+                let period_time_info = period_time_info_map
+                    .entry(key)
+                    .or_insert_with(|| {
+                        Arc::new(PeriodTimeInfo {
+                            s_time,
+                            e_time,
+                            day_add_1,
+                            use_trade_date,
                         })
-                        .clone();
-                    time_ptime_map.insert(time, period_time_info.clone());
-                }
+                    })
+                    .clone();
+                time_ptime_map.insert(time, period_time_info.clone());
             }
-            period_time_map.insert(period.to_string(), time_ptime_map);
+            bars.push((s_time, e_time));
         }
-        breed_period_time.insert(breed.to_string(), Arc::new(ConverterXm { period_time_map }));
+        period_time_map.insert(period, time_ptime_map);
+        period_bars_map.insert(period, bars);
+    }
+    PeriodTables {
+        period_time_map,
+        period_bars_map,
+    }
+}
+
+type SessionTimes = Vec<(NaiveTime, NaiveTime)>;
+
+#[derive(Debug)]
+struct PeriodTables {
+    period_time_map: HashMap<Period, HashMap<NaiveTime, Arc<PeriodTimeInfo>>>,
+    // 按交易时段顺序排列的每根K线的(开始分钟, 结束时间)
+    period_bars_map: HashMap<Period, Vec<(NaiveTime, NaiveTime)>>,
+}
+
+impl PeriodTables {
+    fn bars(&self, period: Period) -> Result<&Vec<(NaiveTime, NaiveTime)>, PeriodConvertError> {
+        self.period_bars_map
+            .get(&period)
+            .ok_or(PeriodConvertError::PeriodError(period.to_string()))
     }
-    BREED_CONVERTERXM_HMAP.set(breed_period_time).unwrap();
-    Ok(())
 }
 
 #[derive(Debug)]
 pub struct ConverterXm {
-    period_time_map: HashMap<String, HashMap<NaiveTime, Arc<PeriodTimeInfo>>>,
+    time_range: Arc<TimeRange>,
+    // 完整交易时间段的周期划分
+    full:       Arc<PeriodTables>,
+    // 无夜盘, 提前收盘时按实际交易时间段的周期划分, 按交易时间段缓存
+    shortened:  RwLock<HashMap<SessionTimes, Arc<PeriodTables>>>,
 }
 
 impl ConverterXm {
+    fn tables(&self, times_vec: SessionTimes) -> Arc<PeriodTables> {
+        if times_vec == *self.time_range.times_vec() {
+            return self.full.clone();
+        }
+        if let Some(tables) = self.shortened.read().unwrap().get(&times_vec) {
+            return tables.clone();
+        }
+        let tables = Arc::new(build_tables(&times_vec, &mut HashMap::new()));
+        self.shortened
+            .write()
+            .unwrap()
+            .entry(times_vec)
+            .or_insert(tables)
+            .clone()
+    }
+
+    /// 一个交易日内该周期所有K线的结束时间, 按交易时段顺序(夜盘在前)
+    pub fn bar_ends(&self, period: Period) -> Result<Vec<NaiveTime>, PeriodConvertError> {
+        let bars = self.full.bars(period)?;
+        Ok(bars.iter().map(|(_, e_time)| *e_time).collect())
+    }
+
+    /// 同bar_ends, 按trade_date实际的交易时间段, 无夜盘或提前收盘时K线会减少
+    pub fn bar_ends_on(
+        &self,
+        period: Period,
+        trade_date: &NaiveDate,
+    ) -> Result<Vec<NaiveTime>, PeriodConvertError> {
+        let tables = self.tables(self.time_range.session_times(trade_date));
+        let bars = tables.bars(period)?;
+        Ok(bars.iter().map(|(_, e_time)| *e_time).collect())
+    }
+
+    /// 一个交易日内该周期的K线数量
     ///
-    /// trade_date
+    /// has_night为false时按只有白盘的交易时间段划分
+    pub fn bars_per_day(&self, period: Period, has_night: bool) -> Result<usize, PeriodConvertError> {
+        if has_night || !self.time_range.has_night() {
+            return Ok(self.full.bars(period)?.len());
+        }
+        let times_vec = self.time_range.times_vec()[1..].to_vec();
+        Ok(self.tables(times_vec).bars(period)?.len())
+    }
+
+    /// 转换成周期K线的时间, 按trade_date实际的交易时间段划分周期
     pub fn convert(
         &self,
-        period: &str,
+        period: Period,
         dt: &NaiveDateTime,
         trade_date: &NaiveDate,
     ) -> Result<NaiveDateTime, PeriodConvertError> {
-        let time_period_info_map = self
+        let tables = self.tables(self.time_range.session_times(trade_date));
+        let time_period_info_map = tables
             .period_time_map
-            .get(period)
+            .get(&period)
             .ok_or(PeriodConvertError::PeriodError(period.to_string()))?;
 
         let time_key = dt.time();
@@ -199,12 +292,16 @@ pub(crate) fn by_breed(breed: &str) -> Result<Arc<ConverterXm>, PeriodConvertErr
 mod tests {
     use std::collections::HashMap;
 
-    use chrono::{NaiveDate, NaiveDateTime};
+    use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 
     use super::init_from_time_range;
     use crate::hq::future::period_convert::xm::by_breed;
     use crate::hq::future::time_range;
-    use crate::hq::period::PeriodValue;
+    use crate::hq::future::trade_day::{
+        remove_early_close, remove_night_override, set_early_close, set_night_override,
+        test_overrides,
+    };
+    use crate::hq::period::Period;
     use crate::mysqlx::MySqlPools;
     use crate::mysqlx_test_pool::init_test_mysql_pools;
 
@@ -229,7 +326,8 @@ mod tests {
         println!();
     }
 
-    async fn print_breed_period_info(breed: &str, period: &str, day: &NaiveDate) {
+    async fn print_breed_period_info(breed: &str, period: Period, day: &NaiveDate) {
+        let _overrides = test_overrides::shared();
         println!("==== {} {} ======", breed, period);
         init_test_mysql_pools();
         init_from_time_range(MySqlPools::pool_default().await.unwrap())
@@ -267,7 +365,7 @@ mod tests {
             idx += 1;
         }
         println!();
-        let pv = PeriodValue::pv(period).unwrap();
+        let pv = period.minutes();
         for period_time in ptime_vec {
             let time_vec = ptime_time_map.get(&period_time).unwrap();
             let time_vec_len = time_vec.len();
@@ -277,7 +375,7 @@ mod tests {
                 "{} {:3}[{:5}] [{} .. {}]",
                 period_time,
                 time_vec_len,
-                time_vec_len == *pv as usize,
+                time_vec_len == pv as usize,
                 start_time,
                 end_time
             )
@@ -285,6 +383,110 @@ mod tests {
         println!();
     }
 
+    async fn check_bar_ends(breed: &str, period: Period, day: &NaiveDate) {
+        let _overrides = test_overrides::shared();
+        init_test_mysql_pools();
+        init_from_time_range(MySqlPools::pool_default().await.unwrap())
+            .await
+            .unwrap();
+        let time_range = time_range::time_range_by_breed(breed).unwrap();
+        let converterxm = by_breed(breed).unwrap();
+
+        let (minutes, trade_date) = time_range.day_minutes(day);
+        let mut expected = Vec::new();
+        for minute in minutes {
+            let period_time = converterxm.convert(period, &minute, &trade_date).unwrap();
+            if expected.last() != Some(&period_time.time()) {
+                expected.push(period_time.time());
+            }
+        }
+        let bar_ends = converterxm.bar_ends(period).unwrap();
+        println!("{} {} {:?}", breed, period, bar_ends);
+        assert_eq!(bar_ends, expected);
+        assert_eq!(
+            converterxm.bars_per_day(period, true).unwrap(),
+            bar_ends.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bar_ends() {
+        let day = NaiveDate::from_ymd_opt(2023, 6, 26).unwrap();
+        for breed in ["LR", "IC", "TF", "SA", "zn", "ag"] {
+            for period in [Period::M5, Period::M15, Period::M30, Period::M60, Period::M120] {
+                check_bar_ends(breed, period, &day).await;
+            }
+        }
+        let converterxm = by_breed("ag").unwrap();
+        // 21:00 ~ 02:30 共330分钟, 无夜盘时白盘225分钟重新划分
+        assert_eq!(converterxm.bars_per_day(Period::M120, true).unwrap(), 5);
+        assert_eq!(converterxm.bars_per_day(Period::M120, false).unwrap(), 2);
+        assert!(converterxm.bar_ends(Period::D1).is_err());
+    }
+
+    fn check_day_bars(breed: &str, period: Period, night_day: &NaiveDate) -> Vec<NaiveDateTime> {
+        let time_range = time_range::time_range_by_breed(breed).unwrap();
+        let converterxm = by_breed(breed).unwrap();
+        let (minutes, trade_date) = time_range.day_minutes(night_day);
+        let mut expected = Vec::new();
+        for minute in minutes {
+            let period_time = converterxm.convert(period, &minute, &trade_date).unwrap();
+            if expected.last() != Some(&period_time) {
+                expected.push(period_time);
+            }
+        }
+        assert_eq!(time_range.day_bars(night_day, period), expected);
+        assert_eq!(
+            converterxm.bar_ends_on(period, &trade_date).unwrap(),
+            expected.iter().map(|v| v.time()).collect::<Vec<_>>()
+        );
+        expected
+    }
+
+    #[tokio::test]
+    async fn test_shortened_session() {
+        let _overrides = test_overrides::exclusive();
+        init_test_mysql_pools();
+        init_from_time_range(MySqlPools::pool_default().await.unwrap())
+            .await
+            .unwrap();
+        let night_day = NaiveDate::from_ymd_opt(2023, 7, 4).unwrap();
+        let trade_date = NaiveDate::from_ymd_opt(2023, 7, 5).unwrap();
+        let dt = |day: &NaiveDate, h, m| day.and_hms_opt(h, m, 0).unwrap();
+        let converterxm = by_breed("ag").unwrap();
+        assert_eq!(
+            converterxm.bar_ends_on(Period::M120, &trade_date).unwrap(),
+            converterxm.bar_ends(Period::M120).unwrap()
+        );
+
+        // 取消夜盘, 白盘从09:01开始重新划分
+        set_night_override(night_day, false);
+        let bars = check_day_bars("ag", Period::M120, &night_day);
+        assert_eq!(bars, [dt(&trade_date, 11, 15), dt(&trade_date, 15, 0)]);
+        remove_night_override(&night_day);
+
+        // 夜盘23:00提前收盘
+        set_early_close(night_day, NaiveTime::from_hms_opt(23, 0, 0).unwrap());
+        let bars = check_day_bars("ag", Period::M120, &night_day);
+        assert_eq!(
+            bars,
+            [
+                dt(&night_day, 23, 0),
+                dt(&trade_date, 11, 15),
+                dt(&trade_date, 15, 0)
+            ]
+        );
+        assert!(converterxm
+            .convert(Period::M120, &dt(&night_day, 23, 30), &trade_date)
+            .is_err());
+        remove_early_close(&night_day, true);
+
+        assert_eq!(
+            check_day_bars("ag", Period::M120, &night_day).len(),
+            converterxm.bars_per_day(Period::M120, true).unwrap()
+        );
+    }
+
     #[tokio::test]
     async fn test_print_period_info_lr() {
         // 09:00:00 ~ 10:15:00
@@ -298,11 +500,11 @@ mod tests {
         // let day = NaiveDate::from_ymd_opt(2023, 6, 26).unwrap();
         //跨周
         let day = NaiveDate::from_ymd_opt(2023, 6, 30).unwrap();
-        print_breed_period_info(breed, "5m", &day).await;
-        // print_breed_period_info(breed, "15m", &day).await;
-        // print_breed_period_info(breed, "30m", &day).await;
-        // print_breed_period_info(breed, "60m", &day).await;
-        // print_breed_period_info(breed, "120m", &day).await;
+        print_breed_period_info(breed, Period::M5, &day).await;
+        // print_breed_period_info(breed, Period::M15, &day).await;
+        // print_breed_period_info(breed, Period::M30, &day).await;
+        // print_breed_period_info(breed, Period::M60, &day).await;
+        // print_breed_period_info(breed, Period::M120, &day).await;
     }
 
     #[tokio::test]
@@ -317,11 +519,11 @@ mod tests {
         // let day = NaiveDate::from_ymd_opt(2023, 6, 26).unwrap();
         //跨周
         let day = NaiveDate::from_ymd_opt(2023, 6, 30).unwrap();
-        print_breed_period_info(breed, "5m", &day).await;
-        // print_breed_period_info(breed, "15m", &day).await;
-        // print_breed_period_info(breed, "30m", &day).await;
-        // print_breed_period_info(breed, "60m", &day).await;
-        // print_breed_period_info(breed, "120m", &day).await;
+        print_breed_period_info(breed, Period::M5, &day).await;
+        // print_breed_period_info(breed, Period::M15, &day).await;
+        // print_breed_period_info(breed, Period::M30, &day).await;
+        // print_breed_period_info(breed, Period::M60, &day).await;
+        // print_breed_period_info(breed, Period::M120, &day).await;
     }
 
     #[tokio::test]
@@ -336,11 +538,11 @@ mod tests {
         // let day = NaiveDate::from_ymd_opt(2023, 6, 26).unwrap();
         //跨周
         let day = NaiveDate::from_ymd_opt(2023, 6, 30).unwrap();
-        // print_breed_period_info(breed, "5m", &day).await;
-        // print_breed_period_info(breed, "15m", &day).await;
-        // print_breed_period_info(breed, "30m", &day).await;
-        // print_breed_period_info(breed, "60m", &day).await;
-        print_breed_period_info(breed, "120m", &day).await;
+        // print_breed_period_info(breed, Period::M5, &day).await;
+        // print_breed_period_info(breed, Period::M15, &day).await;
+        // print_breed_period_info(breed, Period::M30, &day).await;
+        // print_breed_period_info(breed, Period::M60, &day).await;
+        print_breed_period_info(breed, Period::M120, &day).await;
     }
 
     #[tokio::test]
@@ -357,11 +559,11 @@ mod tests {
         // let day = NaiveDate::from_ymd_opt(2023, 6, 26).unwrap();
         //跨周
         let day = NaiveDate::from_ymd_opt(2023, 6, 30).unwrap();
-        // print_breed_period_info(breed, "5m", &day).await;
-        // print_breed_period_info(breed, "15m", &day).await;
-        // print_breed_period_info(breed, "30m", &day).await;
-        // print_breed_period_info(breed, "60m", &day).await;
-        print_breed_period_info(breed, "120m", &day).await;
+        // print_breed_period_info(breed, Period::M5, &day).await;
+        // print_breed_period_info(breed, Period::M15, &day).await;
+        // print_breed_period_info(breed, Period::M30, &day).await;
+        // print_breed_period_info(breed, Period::M60, &day).await;
+        print_breed_period_info(breed, Period::M120, &day).await;
     }
 
     #[tokio::test]
@@ -378,11 +580,11 @@ mod tests {
         // let day = NaiveDate::from_ymd_opt(2023, 6, 26).unwrap();
         //跨周
         let day = NaiveDate::from_ymd_opt(2023, 6, 30).unwrap();
-        // print_breed_period_info(breed, "5m", &day).await;
-        // print_breed_period_info(breed, "15m", &day).await;
-        // print_breed_period_info(breed, "30m", &day).await;
-        // print_breed_period_info(breed, "60m", &day).await;
-        print_breed_period_info(breed, "120m", &day).await;
+        // print_breed_period_info(breed, Period::M5, &day).await;
+        // print_breed_period_info(breed, Period::M15, &day).await;
+        // print_breed_period_info(breed, Period::M30, &day).await;
+        // print_breed_period_info(breed, Period::M60, &day).await;
+        print_breed_period_info(breed, Period::M120, &day).await;
     }
 
     #[tokio::test]
@@ -400,10 +602,10 @@ mod tests {
         //跨周
         let day = NaiveDate::from_ymd_opt(2023, 6, 30).unwrap();
 
-        // print_breed_period_info(breed, "5m", &day).await;
-        // print_breed_period_info(breed, "15m", &day).await;
-        // print_breed_period_info(breed, "30m", &day).await;
-        print_breed_period_info(breed, "60m", &day).await;
-        // print_breed_period_info(breed, "120m", &day).await;
+        // print_breed_period_info(breed, Period::M5, &day).await;
+        // print_breed_period_info(breed, Period::M15, &day).await;
+        // print_breed_period_info(breed, Period::M30, &day).await;
+        print_breed_period_info(breed, Period::M60, &day).await;
+        // print_breed_period_info(breed, Period::M120, &day).await;
     }
 }
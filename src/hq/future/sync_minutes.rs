@@ -0,0 +1,108 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+use super::time_range::{time_range_by_breed, TimeRangeError};
+
+/// 多个品种的分钟对齐后的迭代器
+/// 每项为(分钟, 每个品种在这一分钟是否交易), Vec的顺序和传入的品种顺序相同
+#[derive(Debug, Clone)]
+pub struct SyncMinutes {
+    minutes: Vec<Vec<NaiveDateTime>>,
+    pos:     Vec<usize>,
+}
+
+impl SyncMinutes {
+    /// minutes: 每个品种按时间排序的分钟
+    fn new(minutes: Vec<Vec<NaiveDateTime>>) -> SyncMinutes {
+        let pos = vec![0; minutes.len()];
+        SyncMinutes { minutes, pos }
+    }
+}
+
+impl Iterator for SyncMinutes {
+    type Item = (NaiveDateTime, Vec<bool>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let minute = self
+            .minutes
+            .iter()
+            .zip(self.pos.iter())
+            .filter_map(|(minutes, pos)| minutes.get(*pos))
+            .min()
+            .copied()?;
+        let present = self
+            .minutes
+            .iter()
+            .zip(self.pos.iter_mut())
+            .map(|(minutes, pos)| {
+                let present = minutes.get(*pos) == Some(&minute);
+                if present {
+                    *pos += 1;
+                }
+                present
+            })
+            .collect();
+        Some((minute, present))
+    }
+}
+
+/// 多个品种某天(同TimeRange::day_minutes的day)的分钟对齐, 用于横截面计算
+pub fn sync_minutes(breeds: &[&str], day: &NaiveDate) -> Result<SyncMinutes, TimeRangeError> {
+    let minutes = breeds
+        .iter()
+        .map(|breed| Ok(time_range_by_breed(breed)?.day_minutes(day).0))
+        .collect::<Result<Vec<_>, TimeRangeError>>()?;
+    Ok(SyncMinutes::new(minutes))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::{sync_minutes, SyncMinutes};
+    use crate::hq::future::time_range::init_from_db;
+    use crate::mysqlx::MySqlPools;
+    use crate::mysqlx_test_pool::init_test_mysql_pools;
+
+    fn dt(h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 6, 3)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sync_minutes_merge() {
+        let minutes = vec![
+            vec![dt(9, 1), dt(9, 2), dt(9, 3)],
+            vec![dt(9, 2), dt(9, 4)],
+            vec![],
+        ];
+        let items = SyncMinutes::new(minutes).collect::<Vec<_>>();
+        println!("{:?}", items);
+        assert_eq!(
+            items,
+            vec![
+                (dt(9, 1), vec![true, false, false]),
+                (dt(9, 2), vec![true, true, false]),
+                (dt(9, 3), vec![true, false, false]),
+                (dt(9, 4), vec![false, true, false]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync_minutes() {
+        init_test_mysql_pools();
+        init_from_db(MySqlPools::pool_default().await.unwrap())
+            .await
+            .unwrap();
+        let day = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let items = sync_minutes(&["ag", "IF"], &day)
+            .unwrap()
+            .collect::<Vec<_>>();
+        for (minute, present) in items.iter().take(5) {
+            println!("{} {:?}", minute, present);
+        }
+        assert!(sync_minutes(&["not_exist"], &day).is_err());
+    }
+}
@@ -1,12 +1,15 @@
 use std::collections::HashMap;
-use std::sync::{Arc, OnceLock};
+use std::sync::{Arc, OnceLock, RwLock};
 
 use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
 use itertools::Itertools;
 use sqlx::MySqlPool;
 
 use self::minutes::Minutes;
+use super::breed::InstrumentKind;
+use super::exchange::{Calendar, Exchange};
 use super::trade_day;
+use crate::hq::period::Period;
 use crate::mysqlx::types::VecType;
 
 pub mod minutes;
@@ -38,15 +41,31 @@ struct TimeRangeDbItem {
     ks1_md:      i32,
 }
 
-impl TimeRangeDbItem {
-    pub fn times_vec_unique(&self) -> (Vec<NaiveTime>, Vec<NaiveTime>) {
-        let open_times = self.open_times.iter().unique().copied().collect::<Vec<_>>();
-        let close_times = self
-            .close_times
-            .iter()
-            .unique()
-            .copied()
-            .collect::<Vec<_>>();
+/// 初始化TimeRange需要的字段, 快照中保存的也是这个结构
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct TimeRangeItem {
+    pub(crate) breed:       String,
+    pub(crate) open_times:  Vec<NaiveTime>,
+    pub(crate) close_times: Vec<NaiveTime>,
+}
+
+impl From<TimeRangeDbItem> for TimeRangeItem {
+    fn from(value: TimeRangeDbItem) -> Self {
+        TimeRangeItem {
+            breed:       value.breed,
+            open_times:  value.open_times.to_vec(),
+            close_times: value.close_times.to_vec(),
+        }
+    }
+}
+
+impl TimeRangeItem {
+    fn unique_times(
+        open_times: &[NaiveTime],
+        close_times: &[NaiveTime],
+    ) -> (Vec<NaiveTime>, Vec<NaiveTime>) {
+        let open_times = open_times.iter().unique().copied().collect::<Vec<_>>();
+        let close_times = close_times.iter().unique().copied().collect::<Vec<_>>();
         (open_times, close_times)
     }
 }
@@ -61,9 +80,9 @@ async fn time_range_list_from_db(
     Ok(items)
 }
 
-// 夜盘结束点,收盘点的特殊时间
-#[derive(Debug)]
-pub(crate) struct CloseTimeInfo {
+/// 夜盘结束点,收盘点的特殊时间
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CloseTimeInfo {
     next:                 NaiveTime, // 有夜盘情况下的开盘时间
     non_night_next:       NaiveTime, // 无夜盘情况下的开盘时间
     is_night_close_2300:  bool,      // 是否夜盘结束点: 23:00
@@ -71,8 +90,40 @@ pub(crate) struct CloseTimeInfo {
     is_day_close:         bool,      // 是否收市时间点
 }
 
+impl CloseTimeInfo {
+    /// 收盘后下一个交易时间段的第一分钟, td_has_night: 当天是否有夜盘, 只对收市时间点有区别
+    pub fn next_minute(&self, td_has_night: bool) -> NaiveTime {
+        if td_has_night {
+            self.next
+        } else {
+            self.non_night_next
+        }
+    }
+
+    /// 是否夜盘结束点: 23:00, 1:00, 2:30
+    pub fn is_night_close(&self) -> bool {
+        self.is_night_close_2300 || self.is_night_close_other
+    }
+
+    /// 是否23:00结束的夜盘, 结束后为下一交易日
+    pub fn is_night_close_2300(&self) -> bool {
+        self.is_night_close_2300
+    }
+
+    /// 是否收市时间点, 如15:00, 15:15
+    pub fn is_day_close(&self) -> bool {
+        self.is_day_close
+    }
+}
+
+/// 交易时间段的唯一标识, 格式: "开盘时间列表-收盘时间列表"
+pub type TimeRangeKey = String;
+
 #[derive(Debug)]
 pub struct TimeRange {
+    key:                        TimeRangeKey,
+    open_times:                 Vec<NaiveTime>, // 数据库中的原始开盘时间列表
+    close_times:                Vec<NaiveTime>, // 数据库中的原始收盘时间列表
     times_vec:                  Vec<(NaiveTime, NaiveTime)>, // Vec<(open_time,close_time)>
     has_night:                  bool,
     night_open_time:            NaiveTime,
@@ -80,9 +131,126 @@ pub struct TimeRange {
     close_time_info_map:        HashMap<NaiveTime, CloseTimeInfo>,
     non_night_first_close_time: NaiveTime,
     minutes:                    Minutes,
+    calendar:                   Calendar,
 }
 
 impl TimeRange {
+    /// 格式: "开盘时间列表-收盘时间列表"
+    fn times_key(open_times: &[NaiveTime], close_times: &[NaiveTime]) -> TimeRangeKey {
+        format!(
+            "{}-{}",
+            open_times.iter().join(","),
+            close_times.iter().join(",")
+        )
+    }
+
+    /// 数据库中的原始时间列表, 第一个为夜盘, 无夜盘时夜盘与白盘第一个时间段相同
+    fn from_raw_times(
+        open_times_raw: Vec<NaiveTime>,
+        close_times_raw: Vec<NaiveTime>,
+    ) -> TimeRange {
+        let time_2300 = NaiveTime::from_hms_opt(23, 0, 0).unwrap();
+        let key = TimeRange::times_key(&open_times_raw, &close_times_raw);
+        let has_night = unsafe { open_times_raw.get_unchecked(0) }
+            != unsafe { open_times_raw.get_unchecked(1) };
+        let (open_times, close_times) =
+            TimeRangeItem::unique_times(&open_times_raw, &close_times_raw);
+        let (night_open_time, non_night_open_time) = if has_night {
+            unsafe { (open_times.get_unchecked(0), open_times.get_unchecked(1)) }
+        } else {
+            let open_time = unsafe { open_times.get_unchecked(0) };
+            (open_time, open_time)
+        };
+
+        let night_open_time = *night_open_time + Duration::try_minutes(1).unwrap();
+        let non_night_open_time = *non_night_open_time + Duration::try_minutes(1).unwrap();
+
+        let mut close_time_info_map = HashMap::new();
+
+        let time_len = open_times.len();
+        let mut times_vec = Vec::new();
+
+        for i in 0..time_len {
+            let open_time = unsafe { *open_times.get_unchecked(i) };
+            let close_time = unsafe { *close_times.get_unchecked(i) };
+            times_vec.push((open_time, close_time));
+
+            let next_idx = (i + 1) % time_len;
+            let time_next =
+                unsafe { *open_times.get_unchecked(next_idx) + Duration::try_minutes(1).unwrap() };
+            let mut non_night_next = time_next;
+            let mut is_night_close_2300 = false;
+            let mut is_night_close_other = false;
+            let mut is_day_close = false;
+            if has_night {
+                if i == 0 {
+                    if close_time == time_2300 {
+                        is_night_close_2300 = true;
+                    } else {
+                        is_night_close_other = true;
+                    }
+                }
+                if i == time_len - 1 {
+                    non_night_next =
+                        unsafe { *open_times.get_unchecked(1) + Duration::try_minutes(1).unwrap() };
+                }
+            }
+
+            if i == time_len - 1 {
+                is_day_close = true;
+            }
+
+            close_time_info_map.insert(
+                close_time,
+                CloseTimeInfo {
+                    next: time_next,
+                    non_night_next,
+                    is_night_close_2300,
+                    is_night_close_other,
+                    is_day_close,
+                },
+            );
+        }
+
+        let non_night_first_close_time_idx = if has_night { 1 } else { 0 };
+
+        let non_night_first_close_time =
+            *unsafe { close_times.get_unchecked(non_night_first_close_time_idx) };
+
+        let minutes = Minutes::new_from_times_vec(&times_vec);
+
+        TimeRange {
+            key,
+            open_times: open_times_raw,
+            close_times: close_times_raw,
+            times_vec,
+            has_night,
+            night_open_time,
+            non_night_open_time,
+            close_time_info_map,
+            non_night_first_close_time,
+            minutes,
+            calendar: Calendar::global(),
+        }
+    }
+
+    /// 相同的交易时间段, 使用calendar判断交易日和夜盘
+    fn with_calendar(&self, calendar: Calendar) -> TimeRange {
+        TimeRange {
+            calendar,
+            ..TimeRange::from_raw_times(self.open_times.clone(), self.close_times.clone())
+        }
+    }
+
+    /// 判断交易日和夜盘的日历, 见`exchange::Calendar`
+    pub fn calendar(&self) -> Calendar {
+        self.calendar
+    }
+
+    pub fn key(&self) -> &TimeRangeKey {
+        &self.key
+    }
+
     pub fn has_night(&self) -> bool {
         self.has_night
     }
@@ -91,11 +259,146 @@ impl TimeRange {
         &self.times_vec
     }
 
+    /// 各交易时间段的开盘时间, 有夜盘时第一个为夜盘, 如21:00, 9:00, 10:30, 13:30
+    pub fn open_times(&self) -> Vec<NaiveTime> {
+        self.times_vec.iter().map(|v| v.0).collect()
+    }
+
+    /// 各交易时间段的收盘时间, 与open_times一一对应
+    pub fn close_times(&self) -> Vec<NaiveTime> {
+        self.times_vec.iter().map(|v| v.1).collect()
+    }
+
+    /// 夜盘第一分钟K线的时间(开盘时间加1分钟), 无夜盘时为None
+    pub fn night_first_minute(&self) -> Option<NaiveTime> {
+        self.has_night.then_some(self.night_open_time)
+    }
+
+    /// 白盘第一分钟K线的时间(开盘时间加1分钟), 当天无夜盘时为一个交易日的第一分钟
+    pub fn day_first_minute(&self) -> NaiveTime {
+        self.non_night_open_time
+    }
+
+    /// 开盘前集合竞价的分钟及其所属的开盘第一分钟, 如期货的(20:59, 21:01), (8:59, 9:01)
+    ///
+    /// 夜盘和白盘第一个时间段前都有集合竞价, 白盘的在当天无夜盘时才有
+    pub fn auction_minutes(&self, kind: InstrumentKind) -> Vec<(NaiveTime, NaiveTime)> {
+        let mut first_minutes = vec![self.non_night_open_time];
+        if self.has_night {
+            first_minutes.insert(0, self.night_open_time);
+        }
+        first_minutes
+            .into_iter()
+            .flat_map(|first_minute| {
+                (2..=kind.auction_minutes() as i64 + 1).rev().map(move |v| {
+                    (
+                        first_minute - Duration::try_minutes(v).unwrap(),
+                        first_minute,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// 收盘时间点的信息, 不是收盘时间点时为None
+    pub fn close_time_info(&self, time: &NaiveTime) -> Option<CloseTimeInfo> {
+        self.close_time_info_map.get(time).copied()
+    }
+
+    /// 是否夜盘结束点
+    pub fn is_night_close(&self, time: &NaiveTime) -> bool {
+        self.close_time_info_map
+            .get(time)
+            .is_some_and(|v| v.is_night_close())
+    }
+
+    /// 白盘收市时间, 如15:00, 15:15
+    pub fn day_close_time(&self) -> NaiveTime {
+        self.times_vec.last().map(|v| v.1).unwrap_or_default()
+    }
+
     /// day为开始的自然日
     /// 无夜盘的品种, day为交易日返回day的分钟集, day为非交易日返回下一交易日的分钟集
     /// 有夜盘的品种, day为非交易日返回下一交易日白盘的分钟集, day为交易日时, 返回夜盘分钟集(有夜盘)加白盘分钟集
     pub fn day_minutes(&self, day: &NaiveDate) -> (Vec<NaiveDateTime>, NaiveDate) {
-        let trade_day = trade_day::trade_day(day);
+        let (minutes, daytime, _) = self.day_minutes_night(day);
+        (minutes, daytime)
+    }
+
+    /// 同day_minutes, 每个分钟带上minute_idx及当天是否有夜盘
+    /// 没有提前收盘时, minute_idx和`minute_idx(time, has_night)`的结果一致
+    pub fn day_minutes_with_idx(
+        &self,
+        day: &NaiveDate,
+    ) -> (Vec<(NaiveDateTime, i16, bool)>, NaiveDate) {
+        let (minutes, daytime, has_night) = self.day_minutes_night(day);
+        let minutes = minutes
+            .into_iter()
+            .enumerate()
+            .map(|(idx, dt)| (dt, idx as i16 + 1, has_night))
+            .collect();
+        (minutes, daytime)
+    }
+
+    /// day_minutes中周期K线的结束时间, 和period_convert中转换的结果一致
+    /// 周期按当天实际的交易时间划分, 无夜盘或提前收盘时从实际的第一分钟开始计数
+    pub fn day_bars(&self, day: &NaiveDate, period: Period) -> Vec<NaiveDateTime> {
+        let pv = period.minutes() as usize;
+        let (minutes, ..) = self.day_minutes_night(day);
+        let len = minutes.len();
+        minutes
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| (idx + 1).is_multiple_of(pv) || idx + 1 == len)
+            .map(|(_, dt)| dt)
+            .collect()
+    }
+
+    /// 交易日实际的交易时间段, 夜盘在前
+    /// 夜盘取消时去掉夜盘, 提前收盘时截短或去掉之后的时间段, 见`trade_day::set_early_close`
+    /// trade_date不是日历中的交易日时为完整的交易时间段
+    pub fn session_times(&self, trade_date: &NaiveDate) -> Vec<(NaiveTime, NaiveTime)> {
+        match self.calendar.prev_trade_day(trade_date) {
+            Some(night_day) => {
+                let night_day = Some(night_day).filter(|v| self.calendar.has_night(v));
+                self.actual_times(night_day, trade_date)
+            },
+            None => self.times_vec.clone(),
+        }
+    }
+
+    /// night_day: 夜盘开始的交易日, None时不包含夜盘
+    fn actual_times(
+        &self,
+        night_day: Option<NaiveDate>,
+        trade_date: &NaiveDate,
+    ) -> Vec<(NaiveTime, NaiveTime)> {
+        let night_close = night_day.and_then(|v| trade_day::early_close(&v, true));
+        let day_close = trade_day::early_close(trade_date, false);
+        self.times_vec
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (open_time, close_time))| {
+                let early_close = if self.has_night && i == 0 {
+                    night_day?;
+                    night_close
+                } else {
+                    day_close
+                };
+                match early_close {
+                    Some(early) if session_order(&early) <= session_order(open_time) => None,
+                    Some(early) if session_order(&early) < session_order(close_time) => {
+                        Some((*open_time, early))
+                    },
+                    _ => Some((*open_time, *close_time)),
+                }
+            })
+            .collect()
+    }
+
+    /// (分钟集, 白盘日期, 是否包含夜盘)
+    fn day_minutes_night(&self, day: &NaiveDate) -> (Vec<NaiveDateTime>, NaiveDate, bool) {
+        let trade_day = self.calendar.trade_day(day);
         let night_day;
         let daytime;
 
@@ -108,7 +411,7 @@ impl TimeRange {
                 daytime = trade_day.td_next
             }
         } else if trade_day.is_trade_day {
-            if trade_day.has_night {
+            if self.calendar.has_night(&trade_day.day) {
                 night_day = Some(trade_day.day);
             } else {
                 night_day = None;
@@ -121,13 +424,8 @@ impl TimeRange {
 
         let mut minutes = Vec::new();
 
-        for (i, (open_time, close_time)) in self.times_vec.iter().enumerate() {
-            let open_time = *open_time;
-            let close_time = *close_time;
-            if i == 0 && self.has_night && night_day.is_none() {
-                continue;
-            }
-            let day = if self.has_night && i == 0 {
+        for (open_time, close_time) in self.actual_times(night_day, &daytime) {
+            let day = if self.has_night && open_time == self.times_vec[0].0 {
                 night_day.unwrap()
             } else {
                 daytime
@@ -145,13 +443,13 @@ impl TimeRange {
             }
         }
 
-        (minutes, daytime)
+        (minutes, daytime, night_day.is_some())
     }
 
     /// dt为自然时间
     pub fn is_first_minute(&self, dt: &NaiveDateTime) -> bool {
         if self.has_night {
-            if trade_day::has_night(&dt.date()) {
+            if self.calendar.has_night(&dt.date()) {
                 dt.time() == self.night_open_time
             } else {
                 dt.time() == self.non_night_open_time
@@ -175,7 +473,8 @@ impl TimeRange {
     ///     其他, 返回None
     pub fn next_minute(&self, dt: &NaiveDateTime) -> (NaiveDateTime, Option<NaiveDate>) {
         let date = dt.date();
-        let td_info = trade_day::trade_day(&date);
+        let td_info = self.calendar.trade_day(&date);
+        let td_has_night = td_info.is_trade_day && self.calendar.has_night(&date);
         self.close_time_info_map.get(&dt.time()).map_or_else(
             || (*dt + Duration::try_minutes(1).unwrap(), None),
             |v| {
@@ -188,7 +487,7 @@ impl TimeRange {
                         td_info.td_next
                     }
                 } else if v.is_day_close {
-                    if self.has_night && td_info.has_night {
+                    if self.has_night && td_has_night {
                         date
                     } else {
                         td_info.td_next
@@ -197,7 +496,7 @@ impl TimeRange {
                     date
                 };
                 if v.is_day_close {
-                    if td_info.has_night {
+                    if td_has_night {
                         (date.and_time(v.next), Some(td_info.td_next))
                     } else {
                         (date.and_time(v.non_night_next), Some(td_info.td_next))
@@ -217,7 +516,7 @@ impl TimeRange {
     pub fn next_close_time(&self, dt: &NaiveDateTime) -> Result<NaiveDateTime, String> {
         let next_close_time = self
             .minutes
-            .next_close_time(dt, &self.non_night_first_close_time);
+            .next_close_time(dt, &self.non_night_first_close_time, &self.calendar);
         let dt_default = NaiveDateTime::default();
         if next_close_time == dt_default {
             Err(format!("get a default time:{} ", dt_default))
@@ -335,6 +634,59 @@ impl TimeRange {
     }
 }
 
+/// 不通过数据库构造TimeRange, 用于测试及其他数据源(券商接口, toml等)
+#[derive(Debug, Clone)]
+pub struct TimeRangeBuilder {
+    times: Vec<(NaiveTime, NaiveTime)>,
+}
+
+impl TimeRangeBuilder {
+    /// 按时间顺序的交易时间段(开盘时间, 收盘时间), 有夜盘时第一个为夜盘
+    ///
+    /// 如: [(21:00, 2:30), (9:00, 10:15), (10:30, 11:30), (13:30, 15:00)]
+    pub fn from_times(times: &[(NaiveTime, NaiveTime)]) -> TimeRangeBuilder {
+        TimeRangeBuilder {
+            times: times.to_vec(),
+        }
+    }
+
+    pub fn build(&self) -> Result<TimeRange, TimeRangeError> {
+        let night_start = NaiveTime::from_hms_opt(18, 0, 0).unwrap();
+        let Some(&(first_open, first_close)) = self.times.first() else {
+            return Err(TimeRangeError::TimesError("empty times".to_owned()));
+        };
+        let has_night = first_open >= night_start;
+        let day_times = if has_night {
+            &self.times[1..]
+        } else {
+            &self.times[..]
+        };
+        if day_times.is_empty() {
+            return Err(TimeRangeError::TimesError("no day times".to_owned()));
+        }
+        for (i, (open_time, close_time)) in day_times.iter().enumerate() {
+            if open_time >= close_time || (i > 0 && day_times[i - 1].1 > *open_time) {
+                return Err(TimeRangeError::TimesError(format!(
+                    "invalid times: {}~{}",
+                    open_time, close_time
+                )));
+            }
+        }
+        // 和数据库中的格式一致, 无夜盘时用白盘第一个时间段占位
+        let mut open_times = Vec::with_capacity(day_times.len() + 1);
+        let mut close_times = Vec::with_capacity(day_times.len() + 1);
+        if !has_night {
+            open_times.push(first_open);
+            close_times.push(first_close);
+        }
+        for (open_time, close_time) in self.times.iter() {
+            open_times.push(*open_time);
+            close_times.push(*close_time);
+        }
+        Ok(TimeRange::from_raw_times(open_times, close_times))
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum TimeRangeError {
     #[error("{0}")]
@@ -345,124 +697,139 @@ pub enum TimeRangeError {
 
     #[error("breed err: {0}")]
     BreedError(String),
+
+    #[error("group err: {0}")]
+    GroupError(String),
+
+    #[error("times err: {0}")]
+    TimesError(String),
 }
 
 static TX_TIME_RANGE_DATA: OnceLock<HashMap<String, Arc<TimeRange>>> = OnceLock::new();
 
+type ExchangeTimeRanges = RwLock<HashMap<(TimeRangeKey, Exchange), Arc<TimeRange>>>;
+
+/// 使用交易所日历的TimeRange, 相同交易所相同交易时间段的品种共用
+static EXCHANGE_TIME_RANGES: OnceLock<ExchangeTimeRanges> = OnceLock::new();
+
 pub async fn init_from_db(pool: Arc<MySqlPool>) -> Result<(), TimeRangeError> {
     if TX_TIME_RANGE_DATA.get().is_some() {
         return Ok(());
     }
     trade_day::init_from_db(pool.clone()).await?;
-    let items = time_range_list_from_db(pool).await?;
+    let items = time_range_list_from_db(pool)
+        .await?
+        .into_iter()
+        .map(TimeRangeItem::from)
+        .collect();
+    init_from_items(items)
+}
+
+/// 用数据库的记录初始化, 已经初始化过时忽略, 需要先初始化trade_day
+pub(crate) fn init_from_items(items: Vec<TimeRangeItem>) -> Result<(), TimeRangeError> {
+    if TX_TIME_RANGE_DATA.get().is_some() {
+        return Ok(());
+    }
     let mut tr_hmap = HashMap::new();
     let mut hmap = HashMap::new();
-    let time_2300 = NaiveTime::from_hms_opt(23, 0, 0).unwrap();
     for item in items {
         if item.open_times.len() != item.close_times.len() {
             Err(TimeRangeError::OpenCloseTimeCountError(item.breed.clone()))?;
         }
-        let open_times_str = item.open_times.iter().join(",");
-        let close_times_str = item.close_times.iter().join(",");
-        let key = format!("{}-{}", open_times_str, close_times_str);
-        let has_night = unsafe { item.open_times.get_unchecked(0) }
-            != unsafe { item.open_times.get_unchecked(1) };
-
+        let key = TimeRange::times_key(&item.open_times, &item.close_times);
         let time_range = tr_hmap.entry(key).or_insert_with(|| {
-            let (open_times, close_times) = item.times_vec_unique();
-            let (night_open_time, non_night_open_time) = if has_night {
-                unsafe { (open_times.get_unchecked(0), open_times.get_unchecked(1)) }
-            } else {
-                let open_time = unsafe { open_times.get_unchecked(0) };
-                (open_time, open_time)
-            };
-
-            let night_open_time = *night_open_time + Duration::try_minutes(1).unwrap();
-            let non_night_open_time = *non_night_open_time + Duration::try_minutes(1).unwrap();
-
-            let mut close_time_info_map = HashMap::new();
-
-            let time_len = open_times.len();
-            let mut times_vec = Vec::new();
-
-            for i in 0..time_len {
-                let open_time = unsafe { *open_times.get_unchecked(i) };
-                let close_time = unsafe { *close_times.get_unchecked(i) };
-                times_vec.push((open_time, close_time));
-
-                let next_idx = (i + 1) % time_len;
-                let time_next = unsafe {
-                    *open_times.get_unchecked(next_idx) + Duration::try_minutes(1).unwrap()
-                };
-                let mut non_night_next = time_next;
-                let mut is_night_close_2300 = false;
-                let mut is_night_close_other = false;
-                let mut is_day_close = false;
-                if has_night {
-                    if i == 0 {
-                        if close_time == time_2300 {
-                            is_night_close_2300 = true;
-                        } else {
-                            is_night_close_other = true;
-                        }
-                    }
-                    if i == time_len - 1 {
-                        non_night_next = unsafe {
-                            *open_times.get_unchecked(1) + Duration::try_minutes(1).unwrap()
-                        };
-                    }
-                }
-
-                if i == time_len - 1 {
-                    is_day_close = true;
-                }
-
-                close_time_info_map.insert(
-                    close_time,
-                    CloseTimeInfo {
-                        next: time_next,
-                        non_night_next,
-                        is_night_close_2300,
-                        is_night_close_other,
-                        is_day_close,
-                    },
-                );
-            }
-
-            let non_night_first_close_time_idx = if has_night { 1 } else { 0 };
-
-            let non_night_first_close_time =
-                *unsafe { close_times.get_unchecked(non_night_first_close_time_idx) };
-
-            let minutes = Minutes::new_from_times_vec(&times_vec);
-
-            Arc::new(TimeRange {
-                times_vec,
-                has_night,
-                night_open_time,
-                non_night_open_time,
-                close_time_info_map,
-                non_night_first_close_time,
-                minutes,
-            })
+            Arc::new(TimeRange::from_raw_times(
+                item.open_times.clone(),
+                item.close_times.clone(),
+            ))
         });
 
         hmap.insert(item.breed.clone(), time_range.clone());
     }
-    TX_TIME_RANGE_DATA.set(hmap).unwrap();
+    let _ = TX_TIME_RANGE_DATA.set(hmap);
     Ok(())
 }
 
+/// 已初始化的品种交易时间段记录, 按品种排序
+pub(crate) fn items() -> Option<Vec<TimeRangeItem>> {
+    let hmap = TX_TIME_RANGE_DATA.get()?;
+    let items = hmap
+        .iter()
+        .map(|(breed, time_range)| TimeRangeItem {
+            breed:       breed.clone(),
+            open_times:  time_range.open_times.clone(),
+            close_times: time_range.close_times.clone(),
+        })
+        .sorted_by(|a, b| a.breed.cmp(&b.breed))
+        .collect();
+    Some(items)
+}
+
+/// 一个交易日内的先后顺序, 从18:00开始的分钟数
+fn session_order(time: &NaiveTime) -> i64 {
+    (*time - NaiveTime::from_hms_opt(18, 0, 0).unwrap())
+        .num_minutes()
+        .rem_euclid(24 * 60)
+}
+
 pub(crate) fn hash_map<'a>() -> &'a HashMap<String, Arc<TimeRange>> {
     TX_TIME_RANGE_DATA.get().unwrap()
 }
 
+/// 品种有交易所信息时, 返回的TimeRange使用交易所的日历
 pub fn time_range_by_breed(breed: &str) -> Result<Arc<TimeRange>, TimeRangeError> {
     let hmap = TX_TIME_RANGE_DATA.get().unwrap();
     let time_range = hmap
         .get(breed)
         .ok_or(TimeRangeError::BreedError(breed.to_string()))?;
-    Ok(time_range.clone())
+    let calendar = Calendar::of_breed(breed);
+    let Some(exchange) = calendar.exchange() else {
+        return Ok(time_range.clone());
+    };
+    let cache = EXCHANGE_TIME_RANGES.get_or_init(Default::default);
+    let key = (time_range.key.clone(), exchange);
+    if let Some(v) = cache.read().unwrap().get(&key) {
+        return Ok(v.clone());
+    }
+    let exchange_time_range = cache
+        .write()
+        .unwrap()
+        .entry(key)
+        .or_insert_with(|| Arc::new(time_range.with_calendar(calendar)))
+        .clone();
+    Ok(exchange_time_range)
+}
+
+/// 按交易时间段对品种分组, 同一组的品种共用同一个TimeRange
+/// 组内品种按名称排序, 组按key排序
+pub fn groups() -> Vec<(TimeRangeKey, Vec<String>)> {
+    hash_map()
+        .iter()
+        .map(|(breed, time_range)| (time_range.key.clone(), breed.clone()))
+        .into_group_map()
+        .into_iter()
+        .map(|(key, mut breeds)| {
+            breeds.sort();
+            (key, breeds)
+        })
+        .sorted_by(|a, b| a.0.cmp(&b.0))
+        .collect()
+}
+
+/// 交易时间段组的TimeRange, group为`groups`中的key, 使用默认的日历
+pub fn time_range_by_group(group: &str) -> Result<Arc<TimeRange>, TimeRangeError> {
+    hash_map()
+        .values()
+        .find(|v| v.key == group)
+        .cloned()
+        .ok_or(TimeRangeError::GroupError(group.to_string()))
+}
+
+/// 两个品种是否使用相同的交易时间段
+pub fn same_schedule(breed_a: &str, breed_b: &str) -> Result<bool, TimeRangeError> {
+    let time_range_a = time_range_by_breed(breed_a)?;
+    let time_range_b = time_range_by_breed(breed_b)?;
+    Ok(time_range_a.key == time_range_b.key)
 }
 
 pub fn time_range_qh_base() -> Arc<TimeRange> {
@@ -489,11 +856,162 @@ mod tests {
 
     use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
 
-    use super::{init_from_db, time_range_list_from_db};
-    use crate::hq::future::time_range::{day_all_minutes, time_range_by_breed};
+    use super::{init_from_db, time_range_list_from_db, TimeRangeBuilder, TimeRangeError};
+    use crate::hq::future::period_convert;
+    use crate::hq::future::trade_day::test_overrides;
+    use crate::hq::future::time_range::{
+        day_all_minutes, groups, same_schedule, time_range_by_breed,
+    };
+    use crate::hq::period::Period;
     use crate::mysqlx::MySqlPools;
     use crate::mysqlx_test_pool::init_test_mysql_pools;
 
+    fn hm(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_time_range_builder() {
+        let ag = TimeRangeBuilder::from_times(&[
+            (hm(21, 0), hm(2, 30)),
+            (hm(9, 0), hm(10, 15)),
+            (hm(10, 30), hm(11, 30)),
+            (hm(13, 30), hm(15, 0)),
+        ])
+        .build()
+        .unwrap();
+        assert!(ag.has_night());
+        assert_eq!(ag.times_vec().len(), 4);
+        assert_eq!(ag.day_close_time(), hm(15, 0));
+        assert!(ag.is_close_time(&hm(2, 30)));
+        assert!(ag.minute_in_range(&hm(0, 1)));
+        assert!(!ag.minute_in_range(&hm(10, 20)));
+        assert_eq!(ag.minute_idx(&hm(9, 1), false), Ok(1));
+        assert_eq!(
+            ag.open_times(),
+            vec![hm(21, 0), hm(9, 0), hm(10, 30), hm(13, 30)]
+        );
+        assert_eq!(
+            ag.close_times(),
+            vec![hm(2, 30), hm(10, 15), hm(11, 30), hm(15, 0)]
+        );
+        assert_eq!(ag.night_first_minute(), Some(hm(21, 1)));
+        assert_eq!(ag.day_first_minute(), hm(9, 1));
+        assert!(ag.is_night_close(&hm(2, 30)));
+        assert!(!ag.is_night_close(&hm(15, 0)));
+        assert!(!ag.is_night_close(&hm(2, 29)));
+        let info = ag.close_time_info(&hm(2, 30)).unwrap();
+        assert!(!info.is_night_close_2300());
+        assert!(!info.is_day_close());
+        assert_eq!(info.next_minute(true), hm(9, 1));
+        let info = ag.close_time_info(&hm(15, 0)).unwrap();
+        assert!(info.is_day_close());
+        assert_eq!(info.next_minute(true), hm(21, 1));
+        assert_eq!(info.next_minute(false), hm(9, 1));
+        assert_eq!(ag.close_time_info(&hm(14, 0)), None);
+
+        let tf = TimeRangeBuilder::from_times(&[(hm(9, 30), hm(11, 30)), (hm(13, 0), hm(15, 15))])
+            .build()
+            .unwrap();
+        assert!(!tf.has_night());
+        assert_eq!(
+            tf.times_vec(),
+            &vec![(hm(9, 30), hm(11, 30)), (hm(13, 0), hm(15, 15))]
+        );
+        assert_eq!(tf.day_close_time(), hm(15, 15));
+        assert_eq!(tf.minute_idx(&hm(9, 31), false), Ok(1));
+        assert_eq!(tf.night_first_minute(), None);
+        assert_eq!(tf.day_first_minute(), hm(9, 31));
+        let info = tf.close_time_info(&hm(15, 15)).unwrap();
+        assert!(info.is_day_close() && !info.is_night_close());
+        assert_eq!(info.next_minute(true), hm(9, 31));
+
+        assert!(matches!(
+            TimeRangeBuilder::from_times(&[]).build(),
+            Err(TimeRangeError::TimesError(_))
+        ));
+        assert!(matches!(
+            TimeRangeBuilder::from_times(&[(hm(13, 0), hm(15, 0)), (hm(9, 0), hm(11, 30))]).build(),
+            Err(TimeRangeError::TimesError(_))
+        ));
+    }
+
+    #[test]
+    fn test_exchange_calendar() {
+        use crate::hq::future::exchange::{
+            register_exchange_calendar, remove_exchange_night_override,
+            set_exchange_night_override, Calendar, Exchange,
+        };
+        use crate::hq::future::trade_day::calendar::CalendarRules;
+
+        let _overrides = test_overrides::exclusive();
+        let d = |day: u32| NaiveDate::from_ymd_opt(2097, 1, day).unwrap();
+        // 2097-01-07是周一, 01-09休市, 01-08没有夜盘
+        let rows = CalendarRules::new()
+            .with_holiday(d(9))
+            .generate(d(7), d(11))
+            .unwrap();
+        register_exchange_calendar(Exchange::GFEX, &rows);
+        let ag = TimeRangeBuilder::from_times(&[
+            (hm(21, 0), hm(2, 30)),
+            (hm(9, 0), hm(10, 15)),
+            (hm(10, 30), hm(11, 30)),
+            (hm(13, 30), hm(15, 0)),
+        ])
+        .build()
+        .unwrap()
+        .with_calendar(Calendar::of_exchange(Exchange::GFEX));
+
+        let trade_day = ag.calendar().trade_day(&d(9));
+        assert!(!trade_day.is_trade_day);
+        assert_eq!((trade_day.td_prev, trade_day.td_next), (d(8), d(10)));
+        assert_eq!(ag.session_times(&d(8)).len(), 4);
+        assert_eq!(ag.session_times(&d(10)).len(), 3);
+        let (minutes, daytime) = ag.day_minutes(&d(8));
+        assert_eq!((minutes.len(), daytime), (225, d(10)));
+        assert_eq!(
+            ag.next_minute(&d(7).and_time(hm(15, 0))).0,
+            d(7).and_time(hm(21, 1))
+        );
+        assert_eq!(
+            ag.next_minute(&d(8).and_time(hm(15, 0))).0,
+            d(10).and_time(hm(9, 1))
+        );
+
+        assert_eq!(
+            ag.next_close_time(&d(8).and_time(hm(15, 30))).unwrap(),
+            d(10).and_time(hm(10, 15))
+        );
+
+        set_exchange_night_override(Exchange::GFEX, d(8), true);
+        assert_eq!(ag.session_times(&d(10)).len(), 4);
+        assert!(ag.is_first_minute(&d(8).and_time(hm(21, 1))));
+        assert_eq!(
+            ag.next_close_time(&d(8).and_time(hm(15, 30))).unwrap(),
+            d(9).and_time(hm(2, 30))
+        );
+        assert_eq!(
+            ag.next_close_time(&d(9).and_time(hm(1, 0))).unwrap(),
+            d(9).and_time(hm(2, 30))
+        );
+        assert_eq!(
+            remove_exchange_night_override(Exchange::GFEX, &d(8)),
+            Some(true)
+        );
+        assert!(!ag.is_first_minute(&d(8).and_time(hm(21, 1))));
+
+        // 取消01-07的夜盘, 收盘后到下一交易日白盘的第一个收盘点
+        assert_eq!(
+            ag.next_close_time(&d(7).and_time(hm(21, 30))).unwrap(),
+            d(8).and_time(hm(2, 30))
+        );
+        set_exchange_night_override(Exchange::GFEX, d(7), false);
+        assert_eq!(
+            ag.next_close_time(&d(7).and_time(hm(21, 30))).unwrap(),
+            d(8).and_time(hm(10, 15))
+        );
+    }
+
     #[test]
     fn test_chrono() {
         let time = NaiveTime::from_hms_milli_opt(23, 59, 59, 999).unwrap();
@@ -585,12 +1103,29 @@ mod tests {
         print_time_range("ag").await;
     }
 
+    #[tokio::test]
+    async fn test_groups() {
+        init_test_mysql_pools();
+        init_from_db(MySqlPools::pool_default().await.unwrap())
+            .await
+            .unwrap();
+        for (key, breeds) in groups() {
+            println!("{}: {:?}", key, breeds);
+        }
+        // 09:30:00 ~ 11:30:00, 13:00:00 ~ 15:00:00
+        assert!(same_schedule("IC", "IF").unwrap());
+        // 21:00:00 ~ 01:00:00 vs 21:00:00 ~ 02:30:00
+        assert!(!same_schedule("zn", "ag").unwrap());
+        assert!(same_schedule("zn", "xxx").is_err());
+    }
+
     // #[tokio::test]
     // async fn test_init_from_db_and_get_ag() {
     // }
     //
 
     async fn test_next_minute(breed: &str, results: &[(&str, &str)]) {
+        let _overrides = test_overrides::shared();
         init_test_mysql_pools();
         init_from_db(MySqlPools::pool_default().await.unwrap())
             .await
@@ -760,6 +1295,7 @@ mod tests {
     }
 
     async fn print_day_minutes(breed: &str, day: &NaiveDate) {
+        let _overrides = test_overrides::shared();
         init_test_mysql_pools();
         init_from_db(MySqlPools::pool_default().await.unwrap())
             .await
@@ -807,6 +1343,40 @@ mod tests {
         print_day_minutes("ag", &day).await;
     }
 
+    #[tokio::test]
+    async fn test_day_minutes_with_idx_and_bars() {
+        let _overrides = test_overrides::shared();
+        init_test_mysql_pools();
+        let pool = MySqlPools::pool_default().await.unwrap();
+        period_convert::init(pool).await.unwrap();
+        // 2023-06-30 周五有夜盘, 2023-06-21 节假日前无夜盘
+        let days = [
+            NaiveDate::from_ymd_opt(2023, 6, 30).unwrap(),
+            NaiveDate::from_ymd_opt(2023, 6, 21).unwrap(),
+        ];
+        for breed in ["LR", "IC", "TF", "SA", "zn", "ag"] {
+            let time_range = time_range_by_breed(breed).unwrap();
+            let converter = period_convert::converter_by_breed(breed).unwrap();
+            for day in days.iter() {
+                let (minutes, trade_date) = time_range.day_minutes_with_idx(day);
+                for (dt, idx, has_night) in minutes.iter() {
+                    assert_eq!(time_range.minute_idx(&dt.time(), *has_night), Ok(*idx));
+                }
+                for period in [Period::M5, Period::M15, Period::M30, Period::M60, Period::M120] {
+                    let mut expected = Vec::<NaiveDateTime>::new();
+                    for (dt, _, _) in minutes.iter() {
+                        let bar = converter.to_period(period, dt, &trade_date).unwrap();
+                        if expected.last() != Some(&bar) {
+                            expected.push(bar);
+                        }
+                    }
+                    let bars = time_range.day_bars(day, period);
+                    assert_eq!(bars, expected, "{} {} {}", breed, day, period);
+                }
+            }
+        }
+    }
+
     async fn print_next_close_time_range(breeds: &[&str]) {
         init_test_mysql_pools();
         init_from_db(MySqlPools::pool_default().await.unwrap())
@@ -855,6 +1425,7 @@ mod tests {
     }
 
     async fn test_next_close_time(breed: &str, results: &[(&str, &str)]) {
+        let _overrides = test_overrides::shared();
         println!("========= {} ============", breed);
         init_test_mysql_pools();
         init_from_db(MySqlPools::pool_default().await.unwrap())
@@ -881,6 +1452,7 @@ mod tests {
     }
 
     async fn test_next_close_time_all(breed: &str, day: &NaiveDate) {
+        let _overrides = test_overrides::shared();
         println!("========= {} ============", breed);
         init_test_mysql_pools();
         init_from_db(MySqlPools::pool_default().await.unwrap())
@@ -3,7 +3,7 @@ use std::sync::Arc;
 
 use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
 
-use crate::hq::future::trade_day;
+use crate::hq::future::exchange::Calendar;
 
 #[derive(Debug)]
 pub struct MinuteStrategyInfo {
@@ -230,16 +230,18 @@ impl Minutes {
         }
     }
 
+    /// 夜盘按`calendar.has_night`判断, 包括临时取消或增加的夜盘
     pub fn next_close_time(
         &self,
         dt: &NaiveDateTime,
         non_night_first_close: &NaiveTime,
+        calendar: &Calendar,
     ) -> NaiveDateTime {
         let time = dt.time();
         let time = NaiveTime::from_hms_opt(time.hour(), time.minute(), 0).unwrap();
         let strategy = self.minute_strategy_hmap.get(&time).unwrap();
         let day = dt.date();
-        let trade_day = trade_day::trade_day(&day);
+        let trade_day = calendar.trade_day(&day);
         if strategy.is_use_next_td_first_close {
             trade_day.td_next.and_time(*non_night_first_close)
         } else if strategy.is_check_day {
@@ -249,21 +251,20 @@ impl Minutes {
                 trade_day.td_next.and_time(*non_night_first_close)
             }
         } else if strategy.is_check_night_2300 {
-            if trade_day.has_night {
+            if calendar.has_night(&day) {
                 day.and_time(strategy.close_time)
             } else {
                 trade_day.td_next.and_time(*non_night_first_close)
             }
         } else if strategy.is_check_night_next_day_0100_0230 {
-            if trade_day.has_night {
+            if calendar.has_night(&day) {
                 day.succ_opt().unwrap().and_time(strategy.close_time)
             } else {
                 trade_day.td_next.and_time(*non_night_first_close)
             }
         } else if strategy.is_check_prev_night_0100_0230 {
             let prev_day = day.pred_opt().unwrap();
-            let prev_trade_day = trade_day::trade_day(&prev_day);
-            if prev_trade_day.has_night {
+            if calendar.has_night(&prev_day) {
                 day.and_time(strategy.close_time)
             } else {
                 trade_day.td_next.and_time(*non_night_first_close)
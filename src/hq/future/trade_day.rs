@@ -1,21 +1,26 @@
 use std::collections::HashMap;
-use std::sync::{Arc, OnceLock};
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
 
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use itertools::Itertools;
 use sqlx::MySqlPool;
 
+use crate::toml::{parse_from_file, TomlParseError};
 use crate::ymdhms::Hms;
 
-#[derive(Debug, Clone, sqlx::FromRow)]
-struct TradeDayDbItem {
+pub mod calendar;
+
+#[derive(Debug, Clone, sqlx::FromRow, serde::Serialize, serde::Deserialize)]
+pub(crate) struct TradeDayDbItem {
     #[sqlx(rename = "TDday")]
-    td_day:  NaiveDate,
+    pub(crate) td_day:  NaiveDate,
     #[sqlx(rename = "TDNext")]
-    td_next: NaiveDate,
+    pub(crate) td_next: NaiveDate,
     #[sqlx(rename = "TDREF")]
-    td_prev: NaiveDate,
+    pub(crate) td_prev: NaiveDate,
     #[sqlx(rename = "Night")]
-    night:   i8,
+    pub(crate) night:   i8,
 }
 
 async fn trade_days_from_db(pool: Arc<MySqlPool>) -> Result<Vec<TradeDayDbItem>, sqlx::Error> {
@@ -27,7 +32,7 @@ async fn trade_days_from_db(pool: Arc<MySqlPool>) -> Result<Vec<TradeDayDbItem>,
 }
 
 #[allow(unused)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TradeDay {
     pub is_trade_day: bool,
     pub day:          NaiveDate,
@@ -54,8 +59,17 @@ pub async fn init_from_db(pool: Arc<MySqlPool>) -> Result<(), sqlx::Error> {
     if TRADE_DAY_HMAP.get().is_some() {
         return Ok(());
     }
-    let mut hmap = HashMap::new();
     let trade_day_vec = trade_days_from_db(pool).await?;
+    init_from_items(trade_day_vec);
+    Ok(())
+}
+
+/// 用数据库的记录初始化, 已经初始化过时忽略
+pub(crate) fn init_from_items(trade_day_vec: Vec<TradeDayDbItem>) {
+    if TRADE_DAY_HMAP.get().is_some() {
+        return;
+    }
+    let mut hmap = HashMap::new();
 
     let mut prev_day_info: Option<Arc<TradeDay>> = None;
 
@@ -81,11 +95,172 @@ pub async fn init_from_db(pool: Arc<MySqlPool>) -> Result<(), sqlx::Error> {
         prev_day_info = Some(day_info)
     }
 
-    TRADE_DAY_HMAP.set(hmap).unwrap();
-    Ok(())
+    let _ = TRADE_DAY_HMAP.set(hmap);
+}
+
+/// 已初始化的交易日记录(不含非交易日), 按日期排序
+pub(crate) fn items() -> Option<Vec<TradeDayDbItem>> {
+    let hmap = TRADE_DAY_HMAP.get()?;
+    let items = hmap
+        .values()
+        .filter(|v| v.is_trade_day)
+        .map(|v| TradeDayDbItem {
+            td_day:  v.day,
+            td_next: v.td_next,
+            td_prev: v.td_prev,
+            night:   v.has_night as i8,
+        })
+        .sorted_by_key(|v| v.td_day)
+        .collect();
+    Some(items)
+}
+
+/// 临时取消或增加的夜盘, 优先于日历数据
+static NIGHT_OVERRIDES: OnceLock<RwLock<HashMap<NaiveDate, bool>>> = OnceLock::new();
+
+fn night_overrides_lock() -> &'static RwLock<HashMap<NaiveDate, bool>> {
+    NIGHT_OVERRIDES.get_or_init(Default::default)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum NightOverrideError {
+    #[error("{0}")]
+    Toml(#[from] TomlParseError),
+    #[error("Night override date err: {0}")]
+    Date(String),
+    #[error("Early close time err: {0}")]
+    Time(String),
+}
+
+/// 覆盖day(夜盘开始的交易日)是否有夜盘, 如台风临时取消夜盘
+pub fn set_night_override(day: NaiveDate, has_night: bool) {
+    night_overrides_lock()
+        .write()
+        .unwrap()
+        .insert(day, has_night);
+}
+
+pub fn remove_night_override(day: &NaiveDate) -> Option<bool> {
+    night_overrides_lock().write().unwrap().remove(day)
+}
+
+pub fn clear_night_overrides() {
+    night_overrides_lock().write().unwrap().clear();
+}
+
+/// 当前的夜盘覆盖, 按日期排序
+pub fn night_overrides() -> Vec<(NaiveDate, bool)> {
+    night_overrides_lock()
+        .read()
+        .unwrap()
+        .iter()
+        .map(|(day, has_night)| (*day, *has_night))
+        .sorted()
+        .collect()
+}
+
+/// 提前收盘, key为(交易时段开始的日期, 是否夜盘)
+static EARLY_CLOSES: OnceLock<RwLock<HashMap<(NaiveDate, bool), NaiveTime>>> = OnceLock::new();
+
+fn early_closes_lock() -> &'static RwLock<HashMap<(NaiveDate, bool), NaiveTime>> {
+    EARLY_CLOSES.get_or_init(Default::default)
+}
+
+/// 18:00 ~ 次日03:00为夜盘时间
+fn is_night_close(close: &NaiveTime) -> bool {
+    close.hour() >= 18 || *close <= NaiveTime::from_hms_opt(3, 0, 0).unwrap()
+}
+
+/// 设置提前收盘, close为夜盘时间时是day(夜盘开始的交易日)的夜盘提前收盘, 否则是day的白盘提前收盘
+pub fn set_early_close(day: NaiveDate, close: NaiveTime) {
+    early_closes_lock()
+        .write()
+        .unwrap()
+        .insert((day, is_night_close(&close)), close);
+}
+
+pub fn remove_early_close(day: &NaiveDate, night: bool) -> Option<NaiveTime> {
+    early_closes_lock().write().unwrap().remove(&(*day, night))
+}
+
+pub fn clear_early_closes() {
+    early_closes_lock().write().unwrap().clear();
+}
+
+/// 当前的提前收盘, 按日期, 收盘时间排序
+pub fn early_closes() -> Vec<(NaiveDate, NaiveTime)> {
+    early_closes_lock()
+        .read()
+        .unwrap()
+        .iter()
+        .map(|((day, _), close)| (*day, *close))
+        .sorted()
+        .collect()
+}
+
+pub(crate) fn early_close(day: &NaiveDate, night: bool) -> Option<NaiveTime> {
+    EARLY_CLOSES
+        .get()?
+        .read()
+        .unwrap()
+        .get(&(*day, night))
+        .copied()
+}
+
+#[derive(serde::Deserialize)]
+struct NightOverridesToml {
+    #[serde(default)]
+    night:       HashMap<String, bool>,
+    #[serde(default)]
+    early_close: HashMap<String, String>,
+}
+
+fn parse_day(day: String) -> Result<NaiveDate, NightOverrideError> {
+    NaiveDate::parse_from_str(&day, "%Y-%m-%d").map_err(|_| NightOverrideError::Date(day))
+}
+
+/// 从toml文件加载夜盘覆盖及提前收盘, 追加到已有的覆盖中, 返回加载的条数
+/// ```toml
+/// [night]
+/// 2024-09-13 = false
+///
+/// [early_close]
+/// 2024-09-30 = "23:00"
+/// ```
+pub fn load_night_overrides(path: impl AsRef<Path>) -> Result<usize, NightOverrideError> {
+    let config = parse_from_file::<_, NightOverridesToml>(path)?;
+    let items = config
+        .night
+        .into_iter()
+        .map(|(day, has_night)| Ok((parse_day(day)?, has_night)))
+        .collect::<Result<Vec<_>, NightOverrideError>>()?;
+    let closes = config
+        .early_close
+        .into_iter()
+        .map(|(day, close)| {
+            let close = NaiveTime::parse_from_str(&close, "%H:%M")
+                .or_else(|_| NaiveTime::parse_from_str(&close, "%H:%M:%S"))
+                .map_err(|_| NightOverrideError::Time(close))?;
+            Ok((parse_day(day)?, close))
+        })
+        .collect::<Result<Vec<_>, NightOverrideError>>()?;
+    let count = items.len() + closes.len();
+    night_overrides_lock().write().unwrap().extend(items);
+    for (day, close) in closes {
+        set_early_close(day, close);
+    }
+    Ok(count)
 }
 
+pub(crate) fn night_override(day: &NaiveDate) -> Option<bool> {
+    NIGHT_OVERRIDES.get()?.read().unwrap().get(day).copied()
+}
+
+/// 是否有夜盘, 有覆盖时使用覆盖的值
 pub fn has_night(day: &NaiveDate) -> bool {
+    if let Some(has_night) = night_override(day) {
+        return has_night;
+    }
     TRADE_DAY_HMAP
         .get()
         .unwrap()
@@ -125,17 +300,82 @@ pub fn night_start_trade_day(day: &NaiveDate) -> &Arc<TradeDay> {
         .unwrap()
 }
 
+/// 交易日的前一交易日, 即该交易日夜盘开始的交易日, 未初始化或不是交易日时为None
+pub(crate) fn prev_trade_day(day: &NaiveDate) -> Option<NaiveDate> {
+    TRADE_DAY_HMAP
+        .get()?
+        .get(day)
+        .filter(|v| v.is_trade_day)
+        .map(|v| v.td_prev)
+}
+
+/// 是否交易日, 未初始化或超出日历范围时为false
+pub fn is_trade_day(day: &NaiveDate) -> bool {
+    TRADE_DAY_HMAP
+        .get()
+        .and_then(|v| v.get(day))
+        .is_some_and(|v| v.is_trade_day)
+}
+
 /// 返回trade_day, 以目前的情况不会出现None
 pub fn trade_day(day: &NaiveDate) -> &Arc<TradeDay> {
     TRADE_DAY_HMAP.get().unwrap().get(day).unwrap()
 }
 
+/// `cargo test`并行执行测试, 修改覆盖的测试独占, 按交易日计算交易时间的测试共享, 避免互相影响
+#[cfg(test)]
+pub(crate) mod test_overrides {
+    use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+    use crate::hq::future::exchange::clear_exchange_night_overrides;
+
+    static OVERRIDES_LOCK: RwLock<()> = RwLock::new(());
+
+    /// 独占期间可以修改覆盖, 开始时和drop时清除夜盘覆盖, 提前收盘和交易所的夜盘覆盖
+    pub(crate) struct Exclusive {
+        _guard: RwLockWriteGuard<'static, ()>,
+    }
+
+    impl Drop for Exclusive {
+        fn drop(&mut self) {
+            clear_overrides();
+        }
+    }
+
+    /// 共享期间没有测试修改覆盖
+    pub(crate) struct Shared {
+        _guard: RwLockReadGuard<'static, ()>,
+    }
+
+    pub(crate) fn exclusive() -> Exclusive {
+        let guard = OVERRIDES_LOCK.write().unwrap_or_else(PoisonError::into_inner);
+        clear_overrides();
+        Exclusive { _guard: guard }
+    }
+
+    pub(crate) fn shared() -> Shared {
+        Shared {
+            _guard: OVERRIDES_LOCK.read().unwrap_or_else(PoisonError::into_inner),
+        }
+    }
+
+    fn clear_overrides() {
+        super::clear_night_overrides();
+        super::clear_early_closes();
+        clear_exchange_night_overrides();
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
-    use chrono::NaiveDate;
+    use chrono::{NaiveDate, NaiveTime};
 
-    use super::init_from_db;
+    use super::{
+        clear_early_closes, clear_night_overrides, early_close, early_closes, has_night,
+        init_from_db, load_night_overrides, night_overrides, remove_early_close,
+        remove_night_override, set_early_close, set_night_override, test_overrides,
+    };
     use crate::hq::future::trade_day::{next_trade_day, night_start_trade_day};
     use crate::mysqlx::MySqlPools;
     use crate::mysqlx_test_pool::init_test_mysql_pools;
@@ -206,4 +446,50 @@ mod tests {
         ];
         print_night_start_trade_day(&results).await;
     }
+
+    #[test]
+    fn test_night_override() {
+        let _overrides = test_overrides::exclusive();
+        let day = NaiveDate::from_ymd_opt(2099, 9, 13).unwrap();
+        set_night_override(day, true);
+        assert!(has_night(&day));
+        assert_eq!(night_overrides(), vec![(day, true)]);
+        assert_eq!(remove_night_override(&day), Some(true));
+
+        let path = std::env::temp_dir().join("common-rs-night-override.toml");
+        std::fs::write(&path, "[night]\n2099-09-13 = true\n2099-09-14 = false\n").unwrap();
+        let count = load_night_overrides(&path).unwrap();
+        assert_eq!(count, 2);
+        assert!(has_night(&day));
+        clear_night_overrides();
+        assert!(night_overrides().is_empty());
+
+        std::fs::write(&path, "[night]\n\"2099-13-01\" = true\n").unwrap();
+        assert!(load_night_overrides(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_early_close() {
+        let _overrides = test_overrides::exclusive();
+        let day = NaiveDate::from_ymd_opt(2099, 9, 30).unwrap();
+        let time_2300 = NaiveTime::from_hms_opt(23, 0, 0).unwrap();
+        let time_1130 = NaiveTime::from_hms_opt(11, 30, 0).unwrap();
+        set_early_close(day, time_2300);
+        set_early_close(day, time_1130);
+        assert_eq!(early_close(&day, true), Some(time_2300));
+        assert_eq!(early_close(&day, false), Some(time_1130));
+        assert_eq!(early_closes(), vec![(day, time_1130), (day, time_2300)]);
+        assert_eq!(remove_early_close(&day, false), Some(time_1130));
+
+        let path = std::env::temp_dir().join("common-rs-early-close.toml");
+        std::fs::write(&path, "[early_close]\n2099-09-30 = \"01:00\"\n").unwrap();
+        assert_eq!(load_night_overrides(&path).unwrap(), 1);
+        assert_eq!(early_close(&day, true), NaiveTime::from_hms_opt(1, 0, 0));
+        std::fs::write(&path, "[early_close]\n2099-09-30 = \"25:00\"\n").unwrap();
+        assert!(load_night_overrides(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+        clear_early_closes();
+        assert!(early_closes().is_empty());
+    }
 }
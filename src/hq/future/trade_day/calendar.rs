@@ -0,0 +1,323 @@
+//! 交易日历的生成和校验
+//!
+//! 每年12月交易所公布下一年的节假日后, 按规则(周末+节假日)生成下一年的日历,
+//! 输出SQL或TOML供人工检查后导入, 并可以和外部的交易日列表对比
+//!
+//! 节假日配置:
+//! ```toml
+//! # 单日或闭区间
+//! holidays = ["2025-01-01", "2025-01-28..2025-02-04"]
+//! # 额外取消夜盘的交易日(夜盘开始的那天)
+//! no_night = []
+//! ```
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+
+use super::TradeDayDbItem;
+use crate::toml::{parse_from_file, TomlParseError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum CalendarError {
+    #[error("{0}")]
+    Toml(#[from] TomlParseError),
+    #[error("Calendar date err: {0}")]
+    Date(String),
+    #[error("No trade day in {0} ~ {1}")]
+    Empty(NaiveDate, NaiveDate),
+}
+
+fn parse_day(s: &str) -> Result<NaiveDate, CalendarError> {
+    NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").map_err(|_| CalendarError::Date(s.to_owned()))
+}
+
+#[derive(serde::Deserialize)]
+struct CalendarRulesToml {
+    #[serde(default)]
+    holidays: Vec<String>,
+    #[serde(default)]
+    no_night: Vec<String>,
+}
+
+/// 日历规则, 周末和节假日休市, 节假日前的交易日没有夜盘
+#[derive(Debug, Clone, Default)]
+pub struct CalendarRules {
+    holidays: BTreeSet<NaiveDate>,
+    no_night: BTreeSet<NaiveDate>,
+}
+
+impl CalendarRules {
+    pub fn new() -> CalendarRules {
+        CalendarRules::default()
+    }
+
+    pub fn with_holiday(mut self, day: NaiveDate) -> Self {
+        self.holidays.insert(day);
+        self
+    }
+
+    /// 闭区间
+    pub fn with_holidays(mut self, start: NaiveDate, end: NaiveDate) -> Self {
+        self.holidays
+            .extend(start.iter_days().take_while(|v| *v <= end));
+        self
+    }
+
+    pub fn with_no_night(mut self, day: NaiveDate) -> Self {
+        self.no_night.insert(day);
+        self
+    }
+
+    /// 从toml文件加载
+    pub fn from_file(path: impl AsRef<Path>) -> Result<CalendarRules, CalendarError> {
+        let config = parse_from_file::<_, CalendarRulesToml>(path)?;
+        let mut rules = CalendarRules::new();
+        for item in config.holidays {
+            rules = match item.split_once("..") {
+                Some((start, end)) => rules.with_holidays(parse_day(start)?, parse_day(end)?),
+                None => rules.with_holiday(parse_day(&item)?),
+            };
+        }
+        for item in config.no_night {
+            rules = rules.with_no_night(parse_day(&item)?);
+        }
+        Ok(rules)
+    }
+
+    pub fn is_holiday(&self, day: &NaiveDate) -> bool {
+        self.holidays.contains(day)
+    }
+
+    pub fn is_trade_day(&self, day: &NaiveDate) -> bool {
+        !matches!(day.weekday(), Weekday::Sat | Weekday::Sun) && !self.is_holiday(day)
+    }
+
+    fn next_trade_day(&self, day: NaiveDate) -> NaiveDate {
+        day.iter_days()
+            .skip(1)
+            .find(|v| self.is_trade_day(v))
+            .unwrap()
+    }
+
+    fn prev_trade_day(&self, day: NaiveDate) -> NaiveDate {
+        let mut day = day.pred_opt().unwrap();
+        while !self.is_trade_day(&day) {
+            day = day.pred_opt().unwrap();
+        }
+        day
+    }
+
+    /// 和下一交易日之间有节假日时没有夜盘, 只隔周末时有
+    fn has_night(&self, day: NaiveDate, next: NaiveDate) -> bool {
+        !self.no_night.contains(&day)
+            && !day
+                .iter_days()
+                .skip(1)
+                .take_while(|v| *v < next)
+                .any(|v| self.is_holiday(&v))
+    }
+
+    /// 生成start ~ end(闭区间)的交易日
+    ///
+    /// 第一天的上一交易日和最后一天的下一交易日也按规则计算,
+    /// 跨年的节假日(如下一年元旦)需要一并配置
+    pub fn generate(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<CalendarRow>, CalendarError> {
+        let rows = start
+            .iter_days()
+            .take_while(|v| *v <= end)
+            .filter(|v| self.is_trade_day(v))
+            .map(|day| {
+                let next = self.next_trade_day(day);
+                CalendarRow {
+                    day,
+                    next,
+                    prev: self.prev_trade_day(day),
+                    night: self.has_night(day, next),
+                }
+            })
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            return Err(CalendarError::Empty(start, end));
+        }
+        Ok(rows)
+    }
+
+    /// 生成一整年的交易日
+    pub fn generate_year(&self, year: i32) -> Result<Vec<CalendarRow>, CalendarError> {
+        let start =
+            NaiveDate::from_ymd_opt(year, 1, 1).ok_or(CalendarError::Date(year.to_string()))?;
+        let end = start
+            .with_month(12)
+            .and_then(|v| v.with_day(31))
+            .ok_or(CalendarError::Date(year.to_string()))?;
+        self.generate(start, end)
+    }
+}
+
+/// 日历中的一个交易日, 对应`basedata.tbl_calendar_data`的一行
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalendarRow {
+    pub day:   NaiveDate,
+    pub next:  NaiveDate,
+    pub prev:  NaiveDate,
+    /// 当天晚上是否有夜盘
+    pub night: bool,
+}
+
+impl From<&TradeDayDbItem> for CalendarRow {
+    fn from(value: &TradeDayDbItem) -> Self {
+        CalendarRow {
+            day:   value.td_day,
+            next:  value.td_next,
+            prev:  value.td_prev,
+            night: value.night == 1,
+        }
+    }
+}
+
+/// 已初始化的日历, 未初始化时为None
+pub fn current_rows() -> Option<Vec<CalendarRow>> {
+    Some(super::items()?.iter().map(CalendarRow::from).collect())
+}
+
+/// 生成INSERT语句
+pub fn to_sql(table: &str, rows: &[CalendarRow]) -> String {
+    let mut sql = String::new();
+    for row in rows {
+        let _ = writeln!(
+            sql,
+            "INSERT INTO {} (TDday,TDNext,TDREF,Night) VALUES ('{}','{}','{}',{});",
+            table, row.day, row.next, row.prev, row.night as i8
+        );
+    }
+    sql
+}
+
+/// 生成TOML, 每个交易日一个`[[day]]`
+pub fn to_toml(rows: &[CalendarRow]) -> String {
+    let mut toml = String::new();
+    for row in rows {
+        let _ = writeln!(
+            toml,
+            "[[day]]\nday = {}\nnext = {}\nprev = {}\nnight = {}\n",
+            row.day, row.next, row.prev, row.night
+        );
+    }
+    toml
+}
+
+/// 解析外部的交易日列表, 每行一个日期, 忽略空行和`#`开头的行
+pub fn parse_reference(content: &str) -> Result<Vec<NaiveDate>, CalendarError> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && !v.starts_with('#'))
+        .map(parse_day)
+        .collect()
+}
+
+/// 日历和外部列表的差异, 只比较外部列表的日期范围内
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CalendarDiff {
+    /// 外部列表中有, 日历中没有
+    pub missing:     Vec<NaiveDate>,
+    /// 日历中有, 外部列表中没有
+    pub extra:       Vec<NaiveDate>,
+    /// 上一/下一交易日和日历中的前后日期不一致
+    pub broken_link: Vec<NaiveDate>,
+}
+
+impl CalendarDiff {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.broken_link.is_empty()
+    }
+}
+
+/// 对比日历和外部的交易日列表
+pub fn validate(rows: &[CalendarRow], reference: &[NaiveDate]) -> CalendarDiff {
+    let reference = reference.iter().copied().collect::<BTreeSet<_>>();
+    let (Some(first), Some(last)) = (reference.first(), reference.last()) else {
+        return CalendarDiff::default();
+    };
+    let ours = rows
+        .iter()
+        .filter(|v| (first..=last).contains(&&v.day))
+        .map(|v| v.day)
+        .collect::<BTreeSet<_>>();
+
+    let mut sorted = rows.to_vec();
+    sorted.sort_by_key(|v| v.day);
+    let broken_link = sorted
+        .windows(2)
+        .filter(|v| v[0].next != v[1].day || v[1].prev != v[0].day)
+        .map(|v| v[0].day)
+        .collect();
+
+    CalendarDiff {
+        missing: reference.difference(&ours).copied().collect(),
+        extra: ours.difference(&reference).copied().collect(),
+        broken_link,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::{parse_reference, to_sql, validate, CalendarRules};
+
+    fn d(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_generate() {
+        let rules = CalendarRules::new()
+            .with_holiday(d("2025-01-01"))
+            .with_holidays(d("2025-01-28"), d("2025-02-04"));
+        let rows = rules.generate(d("2024-12-30"), d("2025-02-07")).unwrap();
+        let days = rows.iter().map(|v| v.day.to_string()).collect::<Vec<_>>();
+        assert_eq!(days[..3], ["2024-12-30", "2024-12-31", "2025-01-02"]);
+        // 元旦前没有夜盘, 周五有夜盘
+        assert!(rows[0].night);
+        assert!(!rows[1].night);
+        assert_eq!(rows[1].next, d("2025-01-02"));
+        assert_eq!(rows[2].prev, d("2024-12-31"));
+        let friday = rows.iter().find(|v| v.day == d("2025-01-03")).unwrap();
+        assert!(friday.night);
+        assert_eq!(friday.next, d("2025-01-06"));
+        // 春节
+        let before = rows.iter().find(|v| v.day == d("2025-01-27")).unwrap();
+        assert_eq!(before.next, d("2025-02-05"));
+        assert!(!before.night);
+
+        let sql = to_sql("basedata.tbl_calendar_data", &rows[..1]);
+        assert_eq!(
+            sql,
+            "INSERT INTO basedata.tbl_calendar_data (TDday,TDNext,TDREF,Night) VALUES \
+             ('2024-12-30','2024-12-31','2024-12-27',1);\n"
+        );
+
+        assert!(rules.generate(d("2025-01-01"), d("2025-01-01")).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let rules = CalendarRules::new().with_holiday(d("2025-01-01"));
+        let rows = rules.generate(d("2024-12-30"), d("2025-01-03")).unwrap();
+        let reference =
+            parse_reference("# 外部列表\n2024-12-30\n2024-12-31\n2025-01-01\n2025-01-03\n")
+                .unwrap();
+        let diff = validate(&rows, &reference);
+        assert_eq!(diff.missing, [d("2025-01-01")]);
+        assert_eq!(diff.extra, [d("2025-01-02")]);
+        assert!(diff.broken_link.is_empty());
+        assert!(parse_reference("2025-13-01").is_err());
+    }
+}
@@ -1,37 +1,30 @@
-use std::collections::HashMap;
-use std::sync::OnceLock;
+pub use crate::period::{Period, PeriodError};
 
-static PERIOD_MAP: OnceLock<HashMap<String, i32>> = OnceLock::new();
+/// 和`Period::ALL`一一对应的分钟数, `PeriodValue::pv`返回其中的引用
+static PERIOD_MINUTES: [i32; 10] = [1, 3, 5, 15, 30, 60, 120, 1440, 10080, 43200];
 
+#[deprecated(note = "使用`Period`")]
 pub struct PeriodValue;
 
+#[allow(deprecated)]
 impl PeriodValue {
+    /// 按`Period`解析, 返回分钟数
+    #[deprecated(note = "使用`str::parse::<Period>`和`i32::from(Period)`")]
     pub fn pv(period: &str) -> Option<&i32> {
-        PERIOD_MAP
-            .get_or_init(|| {
-                let mut hmap = HashMap::new();
-                hmap.insert("1m".to_owned(), 1);
-                hmap.insert("3m".to_owned(), 3);
-                hmap.insert("5m".to_owned(), 5);
-                hmap.insert("15m".to_owned(), 15);
-                hmap.insert("30m".to_owned(), 30);
-                hmap.insert("60m".to_owned(), 60);
-                hmap.insert("120m".to_owned(), 120);
-                hmap.insert("1d".to_owned(), 1440);
-                hmap.insert("1w".to_owned(), 10080); // 60*24*7
-                hmap.insert("1mth".to_owned(), 43200); // 60*24*30
-                hmap.insert("1month".to_owned(), 43200); // 60*24*30
-                hmap
-            })
-            .get(period)
+        let period = period.parse::<Period>().ok()?;
+        Period::ALL
+            .iter()
+            .position(|v| *v == period)
+            .map(|idx| &PERIOD_MINUTES[idx])
     }
 }
 
 #[cfg(test)]
+#[allow(deprecated)]
 mod tests {
     use tokio::runtime::Runtime;
 
-    use super::{PeriodValue, PERIOD_MAP};
+    use super::{Period, PeriodValue, PERIOD_MINUTES};
 
     #[test]
     fn test_get_pv() {
@@ -39,7 +32,9 @@ mod tests {
         println!("{:?}", v);
         let v = v.take().unwrap().to_owned();
         println!("{}", v);
-        println!("{:?}", PERIOD_MAP.get());
+        for (idx, period) in Period::ALL.iter().enumerate() {
+            assert_eq!(PERIOD_MINUTES[idx], i32::from(*period));
+        }
     }
 
     #[test]
@@ -54,7 +49,6 @@ mod tests {
                     println!("{:?}", v);
                     let v = v.take().unwrap().to_owned();
                     println!("{}", v);
-                    println!("{:?}", PERIOD_MAP.get());
                 }))
             }
             for handle in handles {
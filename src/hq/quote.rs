@@ -0,0 +1,279 @@
+//! 行情快照(盘口, 最新价, 成交量, 持仓量)及Redis存储
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "redis")]
+pub use self::store::{QuoteStoreError, SnapshotStore};
+
+/// 盘口的一档
+///
+/// Decimal按字符串序列化, bincode不支持Decimal默认的deserialize_any
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceLevel {
+    #[serde(with = "rust_decimal::serde::str")]
+    pub price:  Decimal,
+    pub volume: i64,
+}
+
+impl PriceLevel {
+    pub fn new(price: Decimal, volume: i64) -> PriceLevel {
+        PriceLevel { price, volume }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuoteSnapshot {
+    pub code:          String,
+    pub datetime:      NaiveDateTime,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub last:          Decimal,
+    /// 累计成交量
+    pub volume:        i64,
+    pub open_interest: i64,
+    /// 买盘, 价格从高到低
+    pub bids:          Vec<PriceLevel>,
+    /// 卖盘, 价格从低到高
+    pub asks:          Vec<PriceLevel>,
+}
+
+impl QuoteSnapshot {
+    pub fn new(code: &str, datetime: NaiveDateTime, last: Decimal) -> QuoteSnapshot {
+        QuoteSnapshot {
+            code: code.to_owned(),
+            datetime,
+            last,
+            volume: 0,
+            open_interest: 0,
+            bids: Vec::new(),
+            asks: Vec::new(),
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<&PriceLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&PriceLevel> {
+        self.asks.first()
+    }
+
+    /// 买一卖一的价差
+    pub fn spread(&self) -> Option<Decimal> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    /// 买一卖一的中间价
+    pub fn mid(&self) -> Option<Decimal> {
+        Some((self.best_ask()?.price + self.best_bid()?.price) / Decimal::TWO)
+    }
+}
+
+#[cfg(feature = "redis")]
+mod store {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use redis::{ConnectionLike, RedisError};
+
+    use super::QuoteSnapshot;
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum QuoteStoreError {
+        #[error("{0}")]
+        Redis(#[from] RedisError),
+
+        #[error("{0}")]
+        Bincode(#[from] bincode::Error),
+    }
+
+    /// 快照保存在一个Hash中, 每个合约一个字段, 值为bincode数据
+    ///
+    /// 设置ttl时每个字段单独过期(HPEXPIRE), 需要Redis 7.4以上
+    #[derive(Debug, Clone)]
+    pub struct SnapshotStore {
+        key: String,
+        ttl: Option<Duration>,
+    }
+
+    impl SnapshotStore {
+        pub fn new(key: &str) -> SnapshotStore {
+            SnapshotStore {
+                key: key.to_owned(),
+                ttl: None,
+            }
+        }
+
+        /// 快照写入后的过期时间, 行情中断时过期的快照不再返回
+        pub fn with_ttl(mut self, ttl: Duration) -> Self {
+            self.ttl = Some(ttl);
+            self
+        }
+
+        pub fn key(&self) -> &str {
+            &self.key
+        }
+
+        pub fn set<C: ConnectionLike>(
+            &self,
+            con: &mut C,
+            snapshot: &QuoteSnapshot,
+        ) -> Result<(), QuoteStoreError> {
+            self.set_many(con, std::slice::from_ref(snapshot))
+        }
+
+        /// 一次往返写入多个快照
+        pub fn set_many<C: ConnectionLike>(
+            &self,
+            con: &mut C,
+            snapshots: &[QuoteSnapshot],
+        ) -> Result<(), QuoteStoreError> {
+            if snapshots.is_empty() {
+                return Ok(());
+            }
+            let mut pipe = redis::pipe();
+            let mut hset = redis::cmd("HSET");
+            hset.arg(&self.key);
+            for snapshot in snapshots {
+                hset.arg(&snapshot.code).arg(bincode::serialize(snapshot)?);
+            }
+            pipe.add_command(hset).ignore();
+            if let Some(ttl) = self.ttl {
+                let mut expire = redis::cmd("HPEXPIRE");
+                expire
+                    .arg(&self.key)
+                    .arg(ttl.as_millis() as u64)
+                    .arg("FIELDS")
+                    .arg(snapshots.len());
+                for snapshot in snapshots {
+                    expire.arg(&snapshot.code);
+                }
+                pipe.add_command(expire).ignore();
+            }
+            pipe.query::<()>(con)?;
+            Ok(())
+        }
+
+        pub fn get<C: ConnectionLike>(
+            &self,
+            con: &mut C,
+            code: &str,
+        ) -> Result<Option<QuoteSnapshot>, QuoteStoreError> {
+            let bytes: Option<Vec<u8>> = redis::cmd("HGET").arg(&self.key).arg(code).query(con)?;
+            Ok(bytes.map(|v| bincode::deserialize(&v)).transpose()?)
+        }
+
+        /// 批量获取, 不存在的合约不在结果中
+        pub fn get_many<C, S>(
+            &self,
+            con: &mut C,
+            codes: &[S],
+        ) -> Result<HashMap<String, QuoteSnapshot>, QuoteStoreError>
+        where
+            C: ConnectionLike,
+            S: AsRef<str>,
+        {
+            let mut snapshots = HashMap::with_capacity(codes.len());
+            if codes.is_empty() {
+                return Ok(snapshots);
+            }
+            let mut cmd = redis::cmd("HMGET");
+            cmd.arg(&self.key);
+            for code in codes {
+                cmd.arg(code.as_ref());
+            }
+            let values: Vec<Option<Vec<u8>>> = cmd.query(con)?;
+            for (code, bytes) in codes.iter().zip(values) {
+                if let Some(bytes) = bytes {
+                    snapshots.insert(code.as_ref().to_owned(), bincode::deserialize(&bytes)?);
+                }
+            }
+            Ok(snapshots)
+        }
+
+        pub fn remove<C: ConnectionLike>(
+            &self,
+            con: &mut C,
+            code: &str,
+        ) -> Result<bool, QuoteStoreError> {
+            let n: usize = redis::cmd("HDEL").arg(&self.key).arg(code).query(con)?;
+            Ok(n > 0)
+        }
+
+        /// 已保存的合约
+        pub fn codes<C: ConnectionLike>(
+            &self,
+            con: &mut C,
+        ) -> Result<Vec<String>, QuoteStoreError> {
+            Ok(redis::cmd("HKEYS").arg(&self.key).query(con)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    use super::{PriceLevel, QuoteSnapshot};
+
+    fn snapshot(code: &str) -> QuoteSnapshot {
+        let datetime = NaiveDate::from_ymd_opt(2024, 6, 3)
+            .unwrap()
+            .and_hms_milli_opt(9, 1, 0, 500)
+            .unwrap();
+        let mut snapshot = QuoteSnapshot::new(code, datetime, Decimal::from(7801));
+        snapshot.volume = 100;
+        snapshot.open_interest = 2000;
+        snapshot.bids = vec![
+            PriceLevel::new(Decimal::from(7800), 5),
+            PriceLevel::new(Decimal::from(7799), 8),
+        ];
+        snapshot.asks = vec![PriceLevel::new(Decimal::from(7803), 2)];
+        snapshot
+    }
+
+    #[test]
+    fn test_quote_snapshot() {
+        let snapshot = snapshot("ag2408");
+        assert_eq!(snapshot.spread(), Some(Decimal::from(3)));
+        assert_eq!(snapshot.mid(), Some(Decimal::new(78015, 1)));
+        assert_eq!(
+            QuoteSnapshot::new("ag2408", snapshot.datetime, snapshot.last).spread(),
+            None
+        );
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn test_quote_snapshot_bincode() {
+        let snapshot = snapshot("ag2408");
+        let bytes = bincode::serialize(&snapshot).unwrap();
+        assert_eq!(
+            bincode::deserialize::<QuoteSnapshot>(&bytes).unwrap(),
+            snapshot
+        );
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn test_snapshot_store() {
+        use std::time::Duration;
+
+        use super::SnapshotStore;
+        use crate::redis::RedisClients;
+
+        RedisClients::init_clients("./_cfg/c-redis-rs.yaml").unwrap();
+        let mut con = RedisClients::client().get_connection().unwrap();
+        let store = SnapshotStore::new("hq:test:quote").with_ttl(Duration::from_secs(60));
+        store
+            .set_many(&mut con, &[snapshot("ag2408"), snapshot("au2408")])
+            .unwrap();
+        let snapshots = store
+            .get_many(&mut con, &["ag2408", "au2408", "cu2408"])
+            .unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert!(store.remove(&mut con, "ag2408").unwrap());
+        assert!(store.get(&mut con, "ag2408").unwrap().is_none());
+    }
+}
@@ -0,0 +1,272 @@
+//! 收盘后的行情日报
+//!
+//! 按品种汇总一个交易日的K线数量, 缺失的K线数, 成交量和入库延迟,
+//! 输出文本报表(用于邮件正文)和CSV(用于附件或再处理)
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::NaiveDate;
+
+use super::future::breed::breed_from_contract;
+use super::future::db::kline::KLineItem;
+use crate::human::{format_duration, HumanCountFixPad, Table};
+
+const CSV_HEADER: &str = "trade_day,breed,bars,gaps,volume,latency_count,latency_mean_ms,\
+                          latency_p50_ms,latency_p95_ms,latency_max_ms";
+
+/// 入库延迟统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub mean:  Duration,
+    pub p50:   Duration,
+    pub p95:   Duration,
+    pub max:   Duration,
+}
+
+impl LatencySummary {
+    fn from_samples(mut samples: Vec<Duration>) -> LatencySummary {
+        if samples.is_empty() {
+            return LatencySummary::default();
+        }
+        samples.sort_unstable();
+        let count = samples.len();
+        let quantile = |q: f64| samples[((count as f64 * q).ceil() as usize).clamp(1, count) - 1];
+        LatencySummary {
+            count: count as u64,
+            mean:  samples.iter().sum::<Duration>() / count as u32,
+            p50:   quantile(0.5),
+            p95:   quantile(0.95),
+            max:   samples[count - 1],
+        }
+    }
+}
+
+/// 一个品种的日汇总
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreedSummary {
+    pub breed:   String,
+    pub bars:    u64,
+    pub gaps:    u64,
+    pub volume:  i64,
+    pub latency: LatencySummary,
+}
+
+#[derive(Debug, Default)]
+struct BreedAcc {
+    bars:      u64,
+    gaps:      u64,
+    volume:    i64,
+    latencies: Vec<Duration>,
+}
+
+/// 收集一个交易日的数据, 合约按品种汇总
+#[derive(Debug)]
+pub struct DailyReportBuilder {
+    trade_day: NaiveDate,
+    breeds:    BTreeMap<String, BreedAcc>,
+}
+
+impl DailyReportBuilder {
+    pub fn new(trade_day: NaiveDate) -> DailyReportBuilder {
+        DailyReportBuilder {
+            trade_day,
+            breeds: BTreeMap::new(),
+        }
+    }
+
+    fn acc(&mut self, contract: &str) -> &mut BreedAcc {
+        self.breeds
+            .entry(breed_from_contract(contract))
+            .or_default()
+    }
+
+    pub fn add_bar(&mut self, contract: &str, volume: i64) {
+        let acc = self.acc(contract);
+        acc.bars += 1;
+        acc.volume += volume;
+    }
+
+    /// 不是该交易日的K线忽略, 返回是否已添加
+    pub fn add_kline(&mut self, item: &KLineItem) -> bool {
+        if item.trade_date != self.trade_day {
+            return false;
+        }
+        self.add_bar(&item.code, item.volume);
+        true
+    }
+
+    /// 校验时发现的缺失K线数
+    pub fn add_gaps(&mut self, contract: &str, gaps: u64) {
+        self.acc(contract).gaps += gaps;
+    }
+
+    /// K线结束到入库的延迟
+    pub fn add_latency(&mut self, contract: &str, latency: Duration) {
+        self.acc(contract).latencies.push(latency);
+    }
+
+    pub fn build(self) -> DailyReport {
+        let breeds = self
+            .breeds
+            .into_iter()
+            .map(|(breed, acc)| BreedSummary {
+                breed,
+                bars: acc.bars,
+                gaps: acc.gaps,
+                volume: acc.volume,
+                latency: LatencySummary::from_samples(acc.latencies),
+            })
+            .collect();
+        DailyReport {
+            trade_day: self.trade_day,
+            breeds,
+        }
+    }
+}
+
+/// 行情日报, 品种按名称排序
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DailyReport {
+    pub trade_day: NaiveDate,
+    pub breeds:    Vec<BreedSummary>,
+}
+
+impl DailyReport {
+    pub fn total_bars(&self) -> u64 {
+        self.breeds.iter().map(|v| v.bars).sum()
+    }
+
+    pub fn total_gaps(&self) -> u64 {
+        self.breeds.iter().map(|v| v.gaps).sum()
+    }
+
+    /// 有缺失K线的品种
+    pub fn breeds_with_gaps(&self) -> Vec<&str> {
+        self.breeds
+            .iter()
+            .filter(|v| v.gaps > 0)
+            .map(|v| v.breed.as_str())
+            .collect()
+    }
+
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new(&[
+            "breed", "bars", "gaps", "volume", "lat_p50", "lat_p95", "lat_max",
+        ])
+        .with_right_align(&[1, 2, 3, 4, 5, 6]);
+        for v in &self.breeds {
+            let latency = |d: Duration| {
+                if v.latency.count == 0 {
+                    "-".to_string()
+                } else {
+                    format_duration(&Duration::from_millis(d.as_millis() as u64))
+                }
+            };
+            table.push_row(vec![
+                v.breed.clone(),
+                HumanCountFixPad(v.bars).to_string(),
+                HumanCountFixPad(v.gaps).to_string(),
+                v.volume.to_string(),
+                latency(v.latency.p50),
+                latency(v.latency.p95),
+                latency(v.latency.max),
+            ]);
+        }
+        table
+    }
+
+    /// 文本报表, 第一行为标题
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(
+            text,
+            "行情日报 {} 品种:{} K线:{} 缺失:{}",
+            self.trade_day,
+            self.breeds.len(),
+            HumanCountFixPad(self.total_bars()),
+            self.total_gaps()
+        );
+        let with_gaps = self.breeds_with_gaps();
+        if !with_gaps.is_empty() {
+            let _ = writeln!(text, "有缺失的品种: {}", with_gaps.join(","));
+        }
+        text.push('\n');
+        text.push_str(&self.to_table().to_string());
+        text
+    }
+
+    /// CSV, 有表头, 延迟为毫秒
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        csv.push_str(CSV_HEADER);
+        csv.push('\n');
+        for v in &self.breeds {
+            let l = &v.latency;
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{}",
+                self.trade_day,
+                v.breed,
+                v.bars,
+                v.gaps,
+                v.volume,
+                l.count,
+                l.mean.as_millis(),
+                l.p50.as_millis(),
+                l.p95.as_millis(),
+                l.max.as_millis()
+            );
+        }
+        csv
+    }
+
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_csv())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use super::DailyReportBuilder;
+
+    #[test]
+    fn test_daily_report() {
+        let day = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let mut builder = DailyReportBuilder::new(day);
+        for i in 0..10 {
+            builder.add_bar("ag2408", 10);
+            builder.add_latency("ag2408", Duration::from_millis(100 * (i + 1)));
+        }
+        builder.add_bar("cu2407", 5);
+        builder.add_bar("cu2408", 7);
+        builder.add_gaps("cu2407", 2);
+        let report = builder.build();
+
+        assert_eq!(report.total_bars(), 12);
+        assert_eq!(report.breeds_with_gaps(), ["cu"]);
+        let ag = &report.breeds[0];
+        assert_eq!(ag.breed, "ag");
+        assert_eq!(ag.volume, 100);
+        assert_eq!(ag.latency.count, 10);
+        assert_eq!(ag.latency.mean, Duration::from_millis(550));
+        assert_eq!(ag.latency.p50, Duration::from_millis(500));
+        assert_eq!(ag.latency.p95, Duration::from_millis(1000));
+
+        let csv = report.to_csv();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "2024-06-03,ag,10,0,100,10,550,500,1000,1000");
+        assert_eq!(lines[2], "2024-06-03,cu,2,2,12,0,0,0,0,0");
+
+        let text = report.to_text();
+        println!("{}", text);
+        assert!(text.starts_with("行情日报 2024-06-03 品种:2 K线:12 缺失:2\n有缺失的品种: cu\n"));
+    }
+}
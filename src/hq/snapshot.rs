@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::future::breed::{self, BreedMetaItem};
+use super::future::period_convert;
+use super::future::time_range::{self, TimeRangeError, TimeRangeItem};
+use super::future::trade_day::{self, TradeDayDbItem};
+use crate::yaml::{self, YamlError};
+
+/// 快照文件格式版本, 快照结构有变化时加1
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("{0}")]
+    Yaml(#[from] YamlError),
+
+    #[error("{0}")]
+    TimeRange(#[from] TimeRangeError),
+
+    #[error("snapshot version err: expected {expected}, found {found}")]
+    Version { expected: u32, found: u32 },
+
+    #[error("not init: {0}")]
+    NotInit(&'static str),
+}
+
+/// 行情元数据快照
+/// 只保存数据库中的原始记录, 周期转换器在加载时由交易时间段重新生成
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    version:     u32,
+    trade_days:  Vec<TradeDayDbItem>,
+    time_ranges: Vec<TimeRangeItem>,
+    /// 品种的合约信息及交易所, 交易时间段按交易所取日历
+    breeds:      Vec<BreedMetaItem>,
+}
+
+/// 把已初始化的交易日, 品种交易时间段, 以及已注册的品种信息保存到文件
+pub fn save(path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+    let snapshot = Snapshot {
+        version:     SNAPSHOT_VERSION,
+        trade_days:  trade_day::items().ok_or(SnapshotError::NotInit("trade_day"))?,
+        time_ranges: time_range::items().ok_or(SnapshotError::NotInit("time_range"))?,
+        breeds:      breed::items(),
+    };
+    yaml::write_to_file(path, snapshot)?;
+    Ok(())
+}
+
+fn read(path: impl AsRef<Path>) -> Result<Snapshot, SnapshotError> {
+    let snapshot = yaml::parse_from_file_simple::<_, Snapshot>(path)?;
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(SnapshotError::Version {
+            expected: SNAPSHOT_VERSION,
+            found:    snapshot.version,
+        });
+    }
+    Ok(snapshot)
+}
+
+/// 从文件加载品种信息, 交易日, 品种交易时间段, 并生成周期转换器, 不需要连接数据库
+/// 已经初始化过(包括从数据库初始化)的数据不会被覆盖
+pub fn load(path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+    let snapshot = read(path)?;
+    // 品种的交易所需要在生成交易时间段之前注册
+    breed::init_from_items(snapshot.breeds);
+    trade_day::init_from_items(snapshot.trade_days);
+    time_range::init_from_items(snapshot.time_ranges)?;
+    period_convert::init_from_time_range();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime};
+
+    use rust_decimal::Decimal;
+
+    use super::{read, save, Snapshot, SnapshotError, SNAPSHOT_VERSION};
+    use crate::hq::future::breed::{self, BreedMeta, BreedMetaItem, InstrumentKind};
+    use crate::hq::future::exchange::Exchange;
+    use crate::hq::future::period_convert;
+    use crate::hq::future::time_range::TimeRangeItem;
+    use crate::hq::future::trade_day::TradeDayDbItem;
+    use crate::mysqlx::MySqlPools;
+    use crate::mysqlx_test_pool::init_test_mysql_pools;
+    use crate::yaml;
+
+    fn hms(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_read() {
+        let path = std::env::temp_dir().join("common-rs-hq-snapshot.yaml");
+        let day = NaiveDate::from_ymd_opt(2023, 6, 21).unwrap();
+        let mut snapshot = Snapshot {
+            version:     SNAPSHOT_VERSION,
+            trade_days:  vec![TradeDayDbItem {
+                td_day:  day,
+                td_next: NaiveDate::from_ymd_opt(2023, 6, 26).unwrap(),
+                td_prev: NaiveDate::from_ymd_opt(2023, 6, 20).unwrap(),
+                night:   0,
+            }],
+            time_ranges: vec![TimeRangeItem {
+                breed:       "ag".to_string(),
+                open_times:  vec![hms(21, 0), hms(9, 0), hms(10, 30), hms(13, 30)],
+                close_times: vec![hms(2, 30), hms(10, 15), hms(11, 30), hms(15, 0)],
+            }],
+            breeds:      vec![BreedMetaItem {
+                breed: "snapshot_ag".to_string(),
+                kind:  InstrumentKind::Future,
+                meta:  BreedMeta::new(Decimal::new(1, 0), Decimal::new(15, 0))
+                    .with_exchange(Exchange::SHFE),
+            }],
+        };
+        yaml::write_to_file(&path, &snapshot).unwrap();
+        let r = read(&path).unwrap();
+        println!("{:?}", r);
+        assert_eq!(r.trade_days[0].td_day, day);
+        assert_eq!(r.time_ranges[0].close_times[0], hms(2, 30));
+        assert_eq!(r.breeds, snapshot.breeds);
+        breed::init_from_items(r.breeds);
+        assert_eq!(breed::breed_exchange("snapshot_ag"), Some(Exchange::SHFE));
+        assert!(breed::items().contains(&snapshot.breeds[0]));
+
+        snapshot.version = SNAPSHOT_VERSION + 1;
+        yaml::write_to_file(&path, &snapshot).unwrap();
+        let r = read(&path);
+        println!("{:?}", r);
+        assert!(matches!(r, Err(SnapshotError::Version { .. })));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_save() {
+        init_test_mysql_pools();
+        period_convert::init(MySqlPools::pool_default().await.unwrap())
+            .await
+            .unwrap();
+        save("./_data/hq-snapshot.yaml").unwrap();
+        let r = read("./_data/hq-snapshot.yaml").unwrap();
+        println!("{} {}", r.trade_days.len(), r.time_ranges.len());
+    }
+}
@@ -0,0 +1,571 @@
+//! 委托和成交记录, 及数据库表的创建, 批量保存和按时间范围查询
+//!
+//! 表名为`tbl_order_{suffix}`和`tbl_trade_{suffix}`, 用SqlLoader管理表结构时见`trade_tables_toml`
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use futures_util::TryStreamExt;
+use rust_decimal::Decimal;
+use sqlx::mysql::MySqlArguments;
+use sqlx::{Arguments, MySqlPool};
+
+use crate::mysqlx::table::TableCreator;
+use crate::mysqlx::types::StrEnum;
+use crate::mysqlx::{ident, validate_ident, IdentError};
+
+/// 批量保存时每条REPLACE语句的行数
+pub const SAVE_CHUNK_SIZE: usize = 500;
+
+fn order_table_creator(db: &str, tbl_name: &str) -> TableCreator {
+    TableCreator::new(db, tbl_name)
+        .add_field("order_id", "varchar(64)", false, "''", "委托编号")
+        .add_field("account", "varchar(32)", false, "''", "账户")
+        .add_field("code", "varchar(12)", false, "''", "合约")
+        .add_field("side", "varchar(8)", false, "''", "买卖方向")
+        .add_field("offset", "varchar(16)", false, "''", "开平")
+        .add_field("price", "decimal(18,3)", false, "0", "委托价")
+        .add_field("volume", "int(11)", false, "0", "委托量")
+        .add_field("traded_volume", "int(11)", false, "0", "成交量")
+        .add_field("status", "varchar(16)", false, "''", "状态")
+        .add_field("insert_time", "datetime(6)", false, "", "委托时间")
+        .add_field("update_time", "datetime(6)", false, "", "状态更新时间")
+        .add_field("trade_day", "date", false, "", "交易日")
+        .add_index("idx_code_insert_time", &["code", "insert_time"])
+        .add_index("idx_trade_day", &["trade_day"])
+        .primary_keys(&["order_id"])
+}
+
+fn trade_table_creator(db: &str, tbl_name: &str) -> TableCreator {
+    TableCreator::new(db, tbl_name)
+        .add_field("trade_id", "varchar(64)", false, "''", "成交编号")
+        .add_field("order_id", "varchar(64)", false, "''", "委托编号")
+        .add_field("account", "varchar(32)", false, "''", "账户")
+        .add_field("code", "varchar(12)", false, "''", "合约")
+        .add_field("side", "varchar(8)", false, "''", "买卖方向")
+        .add_field("offset", "varchar(16)", false, "''", "开平")
+        .add_field("price", "decimal(18,3)", false, "0", "成交价")
+        .add_field("volume", "int(11)", false, "0", "成交量")
+        .add_field("commission", "decimal(18,4)", false, "0", "手续费")
+        .add_field("trade_time", "datetime(6)", false, "", "成交时间")
+        .add_field("trade_day", "date", false, "", "交易日")
+        .add_index("idx_code_trade_time", &["code", "trade_time"])
+        .add_index("idx_order_id", &["order_id"])
+        .add_index("idx_trade_day", &["trade_day"])
+        .primary_keys(&["trade_id"])
+}
+
+/// SqlLoader的表模板, 和`create_tables`由同一个表结构生成,
+/// 加入SqlLoader的配置文件后用`table_create_sql_from_template`创建
+pub fn trade_tables_toml() -> String {
+    format!(
+        "{}\n\n{}\n",
+        order_table_creator("", "").sql_loader_toml("tbl-order-tmpl"),
+        trade_table_creator("", "").sql_loader_toml("tbl-trade-tmpl")
+    )
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{kind} #{value}# parse err")]
+pub struct TradeEnumParseError {
+    kind:  &'static str,
+    value: String,
+}
+
+/// 定义保存为字符串的枚举, 实现Display, FromStr, 以及从`StrEnum`读取
+macro_rules! str_enum {
+    ($(#[$meta:meta])* $name:ident { $($(#[$vmeta:meta])* $variant:ident => $s:literal,)+ }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$vmeta])* $variant,)+
+        }
+
+        impl $name {
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $s,)+
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = TradeEnumParseError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s.trim().to_lowercase().as_str() {
+                    $($s => Ok($name::$variant),)+
+                    _ => Err(TradeEnumParseError {
+                        kind:  stringify!($name),
+                        value: s.to_owned(),
+                    }),
+                }
+            }
+        }
+
+        impl From<StrEnum<$name>> for $name {
+            fn from(value: StrEnum<$name>) -> Self {
+                value.0
+            }
+        }
+    };
+}
+
+str_enum!(
+    /// 买卖方向
+    Side {
+        Buy => "buy",
+        Sell => "sell",
+    }
+);
+
+str_enum!(
+    /// 开平
+    Offset {
+        Open => "open",
+        Close => "close",
+        /// 平今
+        CloseToday => "close_today",
+        /// 平昨
+        CloseYesterday => "close_yesterday",
+    }
+);
+
+str_enum!(
+    /// 委托状态
+    OrderStatus {
+        Submitted => "submitted",
+        PartTraded => "part_traded",
+        AllTraded => "all_traded",
+        Canceled => "canceled",
+        Rejected => "rejected",
+    }
+);
+
+impl OrderStatus {
+    /// 不会再变化的状态
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::AllTraded | OrderStatus::Canceled | OrderStatus::Rejected
+        )
+    }
+}
+
+/// 委托记录
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct OrderRecord {
+    pub order_id:      String,
+    pub account:       String,
+    pub code:          String,
+    #[sqlx(try_from = "StrEnum<Side>")]
+    pub side:          Side,
+    #[sqlx(try_from = "StrEnum<Offset>")]
+    pub offset:        Offset,
+    pub price:         Decimal,
+    pub volume:        i64,
+    pub traded_volume: i64,
+    #[sqlx(try_from = "StrEnum<OrderStatus>")]
+    pub status:        OrderStatus,
+    pub insert_time:   NaiveDateTime,
+    pub update_time:   NaiveDateTime,
+    pub trade_day:     NaiveDate,
+}
+
+/// 成交记录
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct TradeRecord {
+    pub trade_id:   String,
+    pub order_id:   String,
+    pub account:    String,
+    pub code:       String,
+    #[sqlx(try_from = "StrEnum<Side>")]
+    pub side:       Side,
+    #[sqlx(try_from = "StrEnum<Offset>")]
+    pub offset:     Offset,
+    pub price:      Decimal,
+    pub volume:     i64,
+    pub commission: Decimal,
+    pub trade_time: NaiveDateTime,
+    pub trade_day:  NaiveDate,
+}
+
+impl TradeRecord {
+    /// 成交金额, 不含合约乘数
+    pub fn amount(&self) -> Decimal {
+        self.price * Decimal::from(self.volume)
+    }
+}
+
+/// 表的列及每行的参数
+trait RecordRow {
+    const COLUMNS: &'static str;
+    const COLUMN_COUNT: usize;
+
+    fn add_args(&self, args: &mut MySqlArguments);
+}
+
+impl RecordRow for OrderRecord {
+    const COLUMNS: &'static str = "order_id,account,code,side,offset,price,volume,traded_volume,status,insert_time,update_time,trade_day";
+    const COLUMN_COUNT: usize = 12;
+
+    fn add_args(&self, args: &mut MySqlArguments) {
+        args.add(&self.order_id);
+        args.add(&self.account);
+        args.add(&self.code);
+        args.add(self.side.as_str());
+        args.add(self.offset.as_str());
+        args.add(self.price);
+        args.add(self.volume);
+        args.add(self.traded_volume);
+        args.add(self.status.as_str());
+        args.add(self.insert_time);
+        args.add(self.update_time);
+        args.add(self.trade_day);
+    }
+}
+
+impl RecordRow for TradeRecord {
+    const COLUMNS: &'static str =
+        "trade_id,order_id,account,code,side,offset,price,volume,commission,trade_time,trade_day";
+    const COLUMN_COUNT: usize = 11;
+
+    fn add_args(&self, args: &mut MySqlArguments) {
+        args.add(&self.trade_id);
+        args.add(&self.order_id);
+        args.add(&self.account);
+        args.add(&self.code);
+        args.add(self.side.as_str());
+        args.add(self.offset.as_str());
+        args.add(self.price);
+        args.add(self.volume);
+        args.add(self.commission);
+        args.add(self.trade_time);
+        args.add(self.trade_day);
+    }
+}
+
+/// 多行REPLACE语句, rows为行数
+fn replace_sql<T: RecordRow>(table_name: &str, rows: usize) -> String {
+    let row = format!("({})", vec!["?"; T::COLUMN_COUNT].join(","));
+    format!(
+        "REPLACE INTO {}({}) VALUES {}",
+        table_name,
+        T::COLUMNS,
+        vec![row; rows].join(",")
+    )
+}
+
+/// 时间范围查询, code为None时查询所有合约
+fn range_sql<T: RecordRow>(table_name: &str, time_column: &str, with_code: bool) -> String {
+    let code_where = if with_code { " AND code=?" } else { "" };
+    format!(
+        "SELECT {} FROM {} WHERE {time_column}>=? AND {time_column}<=?{} ORDER BY {time_column} LIMIT ?",
+        T::COLUMNS,
+        table_name,
+        code_where,
+    )
+}
+
+#[derive(Debug)]
+pub struct TradeRecordUtil {
+    db: String,
+}
+
+impl TradeRecordUtil {
+    /// 库名和`sql_loader`建库时一样把`-`换成`_`, 不是合法的标识符时返回错误
+    pub fn new(db: &str) -> Result<TradeRecordUtil, IdentError> {
+        let db = db.replace('-', "_");
+        if !db.is_empty() {
+            validate_ident(&db)?;
+        }
+        Ok(TradeRecordUtil { db })
+    }
+
+    fn order_table_name(&self, tbl_suffix: &str) -> Result<String, IdentError> {
+        ident(&self.db, &format!("tbl_order_{}", tbl_suffix))
+    }
+
+    fn trade_table_name(&self, tbl_suffix: &str) -> Result<String, IdentError> {
+        ident(&self.db, &format!("tbl_trade_{}", tbl_suffix))
+    }
+}
+
+/// 创建数据库表
+impl TradeRecordUtil {
+    /// 创建委托表和成交表, 返回(委托表名, 成交表名)
+    pub async fn create_tables(
+        &self,
+        pool: &MySqlPool,
+        tbl_suffix: &str,
+    ) -> Result<(String, String), sqlx::Error> {
+        let order_table = self.order_table_name(tbl_suffix)?;
+        let trade_table = self.trade_table_name(tbl_suffix)?;
+        for creator in [
+            order_table_creator(&self.db, &format!("tbl_order_{}", tbl_suffix)),
+            trade_table_creator(&self.db, &format!("tbl_trade_{}", tbl_suffix)),
+        ] {
+            sqlx::query(&creator.to_string()).execute(pool).await?;
+        }
+        Ok((order_table, trade_table))
+    }
+}
+
+/// 批量保存, 相同编号的记录覆盖
+impl TradeRecordUtil {
+    async fn save<T: RecordRow>(
+        pool: &MySqlPool,
+        table_name: &str,
+        records: &[T],
+    ) -> Result<u64, sqlx::Error> {
+        let mut affected = 0;
+        let mut transaction = pool.begin().await?;
+        for chunk in records.chunks(SAVE_CHUNK_SIZE) {
+            let mut args = MySqlArguments::default();
+            for record in chunk {
+                record.add_args(&mut args);
+            }
+            let sql = replace_sql::<T>(table_name, chunk.len());
+            affected += sqlx::query_with(&sql, args)
+                .execute(&mut *transaction)
+                .await?
+                .rows_affected();
+        }
+        transaction.commit().await?;
+        Ok(affected)
+    }
+
+    /// 保存委托, 在一个事务内按`SAVE_CHUNK_SIZE`分批REPLACE, 返回影响的行数
+    pub async fn save_orders(
+        &self,
+        pool: &MySqlPool,
+        tbl_suffix: &str,
+        orders: &[OrderRecord],
+    ) -> Result<u64, sqlx::Error> {
+        Self::save(pool, &self.order_table_name(tbl_suffix)?, orders).await
+    }
+
+    /// 保存成交, 见`save_orders`
+    pub async fn save_trades(
+        &self,
+        pool: &MySqlPool,
+        tbl_suffix: &str,
+        trades: &[TradeRecord],
+    ) -> Result<u64, sqlx::Error> {
+        Self::save(pool, &self.trade_table_name(tbl_suffix)?, trades).await
+    }
+}
+
+/// 列表相关的操作
+impl TradeRecordUtil {
+    /// 委托时间范围内的委托, code为None时为所有合约, 时间正序
+    pub async fn order_vec_range(
+        &self,
+        pool: &MySqlPool,
+        tbl_suffix: &str,
+        code: Option<&str>,
+        sdatetime: &NaiveDateTime,
+        edatetime: &NaiveDateTime,
+        limit: u32,
+    ) -> Result<Vec<OrderRecord>, sqlx::Error> {
+        let table_name = self.order_table_name(tbl_suffix)?;
+        let sql = range_sql::<OrderRecord>(&table_name, "insert_time", code.is_some());
+        let mut args = MySqlArguments::default();
+        args.add(sdatetime);
+        args.add(edatetime);
+        if let Some(code) = code {
+            args.add(code);
+        }
+        args.add(limit);
+
+        sqlx::query_as_with::<_, OrderRecord, _>(&sql, args)
+            .fetch(pool)
+            .try_collect()
+            .await
+    }
+
+    /// 成交时间范围内的成交, code为None时为所有合约, 时间正序
+    pub async fn trade_vec_range(
+        &self,
+        pool: &MySqlPool,
+        tbl_suffix: &str,
+        code: Option<&str>,
+        sdatetime: &NaiveDateTime,
+        edatetime: &NaiveDateTime,
+        limit: u32,
+    ) -> Result<Vec<TradeRecord>, sqlx::Error> {
+        let table_name = self.trade_table_name(tbl_suffix)?;
+        let sql = range_sql::<TradeRecord>(&table_name, "trade_time", code.is_some());
+        let mut args = MySqlArguments::default();
+        args.add(sdatetime);
+        args.add(edatetime);
+        if let Some(code) = code {
+            args.add(code);
+        }
+        args.add(limit);
+
+        sqlx::query_as_with::<_, TradeRecord, _>(&sql, args)
+            .fetch(pool)
+            .try_collect()
+            .await
+    }
+
+    /// 委托的所有成交, 时间正序
+    pub async fn trade_vec_by_order(
+        &self,
+        pool: &MySqlPool,
+        tbl_suffix: &str,
+        order_id: &str,
+    ) -> Result<Vec<TradeRecord>, sqlx::Error> {
+        let table_name = self.trade_table_name(tbl_suffix)?;
+        let sql = format!(
+            "SELECT {} FROM {} WHERE order_id=? ORDER BY trade_time",
+            TradeRecord::COLUMNS,
+            table_name
+        );
+        let mut args = MySqlArguments::default();
+        args.add(order_id);
+
+        sqlx::query_as_with::<_, TradeRecord, _>(&sql, args)
+            .fetch(pool)
+            .try_collect()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+    use rust_decimal::Decimal;
+
+    use super::{
+        range_sql, replace_sql, Offset, OrderRecord, OrderStatus, RecordRow, Side, TradeRecord,
+        TradeRecordUtil,
+    };
+    use crate::mysqlx_test_pool::test_db_guard;
+
+    fn dt(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn trade(id: &str, time: &str) -> TradeRecord {
+        TradeRecord {
+            trade_id:   id.to_owned(),
+            order_id:   "o1".to_owned(),
+            account:    "acc".to_owned(),
+            code:       "ag2412".to_owned(),
+            side:       Side::Buy,
+            offset:     Offset::CloseToday,
+            price:      Decimal::new(75005, 1),
+            volume:     2,
+            commission: Decimal::new(15, 1),
+            trade_time: dt(time),
+            trade_day:  NaiveDate::from_ymd_opt(2024, 10, 8).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_enum() {
+        assert_eq!("BUY".parse::<Side>().unwrap(), Side::Buy);
+        assert_eq!(Offset::CloseToday.to_string(), "close_today");
+        assert_eq!(
+            "close_yesterday".parse::<Offset>().unwrap(),
+            Offset::CloseYesterday
+        );
+        assert!("x".parse::<OrderStatus>().is_err());
+        assert!(OrderStatus::Canceled.is_finished());
+        assert!(!OrderStatus::PartTraded.is_finished());
+        assert_eq!(
+            trade("t1", "2024-10-08 09:00:01").amount(),
+            Decimal::new(150010, 1)
+        );
+    }
+
+    #[test]
+    fn test_sql() {
+        let sql = replace_sql::<TradeRecord>("`db`.`tbl_trade_x`", 2);
+        assert_eq!(sql.matches('?').count(), TradeRecord::COLUMN_COUNT * 2);
+        assert_eq!(
+            TradeRecord::COLUMNS.split(',').count(),
+            TradeRecord::COLUMN_COUNT
+        );
+        assert_eq!(
+            OrderRecord::COLUMNS.split(',').count(),
+            OrderRecord::COLUMN_COUNT
+        );
+        assert_eq!(
+            range_sql::<OrderRecord>("t", "insert_time", true),
+            format!(
+                "SELECT {} FROM t WHERE insert_time>=? AND insert_time<=? AND code=? ORDER BY insert_time LIMIT ?",
+                OrderRecord::COLUMNS
+            )
+        );
+    }
+
+    #[test]
+    fn test_sql_loader_toml() {
+        let toml = super::trade_tables_toml();
+        assert!(toml.contains(r#"tbl-index = [["code", "trade-time"], ["order-id"], ["trade-day"]]"#));
+        assert!(toml.contains(
+            r#"price = { type = "DECIMAL(18,3)", not-null = true, default = "0", comment = "成交价" }"#
+        ));
+        #[cfg(feature = "sql-loader")]
+        {
+            let loader = ::toml::from_str::<crate::sql_loader::SqlLoader>(&toml);
+            assert!(loader.is_ok(), "{:?}", loader.err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_query() {
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        let util = TradeRecordUtil::new(guard.db()).unwrap();
+        util.create_tables(&pool, "test").await.unwrap();
+
+        let order = OrderRecord {
+            order_id:      "o1".to_owned(),
+            account:       "acc".to_owned(),
+            code:          "ag2412".to_owned(),
+            side:          Side::Buy,
+            offset:        Offset::Open,
+            price:         Decimal::new(75005, 1),
+            volume:        4,
+            traded_volume: 4,
+            status:        OrderStatus::AllTraded,
+            insert_time:   dt("2024-10-08 09:00:00"),
+            update_time:   dt("2024-10-08 09:00:02"),
+            trade_day:     NaiveDate::from_ymd_opt(2024, 10, 8).unwrap(),
+        };
+        util.save_orders(&pool, "test", std::slice::from_ref(&order))
+            .await
+            .unwrap();
+        let trades = [
+            trade("t1", "2024-10-08 09:00:01"),
+            trade("t2", "2024-10-08 09:00:02"),
+        ];
+        util.save_trades(&pool, "test", &trades).await.unwrap();
+
+        let orders = util
+            .order_vec_range(
+                &pool,
+                "test",
+                Some("ag2412"),
+                &dt("2024-10-08 09:00:00"),
+                &dt("2024-10-08 15:00:00"),
+                100,
+            )
+            .await
+            .unwrap();
+        assert_eq!(orders, [order]);
+        let by_order = util.trade_vec_by_order(&pool, "test", "o1").await.unwrap();
+        assert_eq!(by_order, trades);
+        guard.cleanup().await;
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt::{self, Write};
+use std::time::Duration;
 
 use rust_decimal::Decimal;
 
@@ -59,13 +60,441 @@ impl fmt::Display for HumanCountFixPad {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HumanParseError {
+    kind:  &'static str,
+    input: String,
+}
+
+impl fmt::Display for HumanParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {}: '{}'", self.kind, self.input)
+    }
+}
+
+impl std::error::Error for HumanParseError {
+}
+
+/// 拆分成(数字, 单位)的列表, 如"1h 30m" -> [(1, "h"), (30, "m")]
+fn split_num_unit(s: &str) -> Option<Vec<(f64, &str)>> {
+    let mut r = Vec::new();
+    let mut rest = s.trim();
+    while !rest.is_empty() {
+        let num_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let num = rest[..num_end].parse::<f64>().ok()?;
+        rest = rest[num_end..].trim_start();
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        r.push((num, &rest[..unit_end]));
+        rest = rest[unit_end..].trim_start();
+    }
+    (!r.is_empty()).then_some(r)
+}
+
+/// 解析时间长度, 如"30s", "15m", "1h30m", "500ms", 没有单位时为秒
+/// 单位: ns, us, ms, s, m, h, d
+pub fn parse_duration(s: &str) -> Result<Duration, HumanParseError> {
+    let err = || HumanParseError {
+        kind:  "duration",
+        input: s.to_owned(),
+    };
+    let mut nanos = 0f64;
+    for (num, unit) in split_num_unit(s).ok_or_else(err)? {
+        let unit_nanos = match unit {
+            "ns" => 1.0,
+            "us" | "µs" => 1e3,
+            "ms" => 1e6,
+            "" | "s" => 1e9,
+            "m" => 60e9,
+            "h" => 3600e9,
+            "d" => 86400e9,
+            _ => return Err(err()),
+        };
+        nanos += num * unit_nanos;
+    }
+    if !nanos.is_finite() || nanos > u64::MAX as f64 {
+        return Err(err());
+    }
+    Ok(Duration::from_nanos(nanos.round() as u64))
+}
+
+/// parse_duration的反向, 整秒时用h/m/s组合, 否则用ms或ns
+pub fn format_duration(d: &Duration) -> String {
+    format_duration_units(d, Locale::En.duration_units())
+}
+
+/// units: 天, 时, 分, 秒, 毫秒, 纳秒
+fn format_duration_units(d: &Duration, units: [&str; 6]) -> String {
+    let [day, hour, minute, second, milli, nano] = units;
+    if d.subsec_nanos() != 0 {
+        return if d.subsec_nanos().is_multiple_of(1_000_000) {
+            format!("{}{}", d.as_millis(), milli)
+        } else {
+            format!("{}{}", d.as_nanos(), nano)
+        };
+    }
+    let secs = d.as_secs();
+    if secs == 0 {
+        return format!("0{}", second);
+    }
+    let parts = [
+        (secs / 86400, day),
+        (secs % 86400 / 3600, hour),
+        (secs % 3600 / 60, minute),
+        (secs % 60, second),
+    ];
+    let mut r = String::new();
+    for (v, unit) in parts {
+        if v > 0 {
+            let _ = write!(r, "{}{}", v, unit);
+        }
+    }
+    r
+}
+
+/// 解析字节数, 如"1GiB", "512MB", "10k", 没有单位时为字节
+/// K/M/G/T(B)为1000进制, KiB/MiB/GiB/TiB为1024进制, 不区分大小写
+pub fn parse_bytes(s: &str) -> Result<u64, HumanParseError> {
+    let err = || HumanParseError {
+        kind:  "byte size",
+        input: s.to_owned(),
+    };
+    let items = split_num_unit(s).ok_or_else(err)?;
+    let [(num, unit)] = items.as_slice() else {
+        return Err(err());
+    };
+    let unit = unit.to_ascii_lowercase();
+    let scale = match unit.as_str() {
+        "" | "b" => 1u64,
+        "k" | "kb" => 1000,
+        "kib" => 1 << 10,
+        "m" | "mb" => 1_000_000,
+        "mib" => 1 << 20,
+        "g" | "gb" => 1_000_000_000,
+        "gib" => 1 << 30,
+        "t" | "tb" => 1_000_000_000_000,
+        "tib" => 1 << 40,
+        _ => return Err(err()),
+    };
+    let v = num * scale as f64;
+    if !v.is_finite() || v > u64::MAX as f64 {
+        return Err(err());
+    }
+    Ok(v.round() as u64)
+}
+
+/// parse_bytes的反向, 用能整除的最大的1024进制单位
+pub fn format_bytes(bytes: u64) -> String {
+    for (unit, shift) in [("TiB", 40), ("GiB", 30), ("MiB", 20), ("KiB", 10)] {
+        if bytes != 0 && bytes.is_multiple_of(1u64 << shift) {
+            return format!("{}{}", bytes >> shift, unit);
+        }
+    }
+    format!("{}B", bytes)
+}
+
+/// 格式化使用的语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    /// 简体中文, 如"3小时25分", "1.2万"
+    ZhCn,
+}
+
+impl Locale {
+    fn duration_units(&self) -> [&'static str; 6] {
+        match self {
+            Locale::En => ["d", "h", "m", "s", "ms", "ns"],
+            Locale::ZhCn => ["天", "小时", "分", "秒", "毫秒", "纳秒"],
+        }
+    }
+
+    /// 数量的单位, 从大到小
+    fn count_units(&self) -> &'static [(f64, &'static str)] {
+        match self {
+            Locale::En => &[(1e12, "T"), (1e9, "B"), (1e6, "M"), (1e3, "K")],
+            Locale::ZhCn => &[(1e12, "万亿"), (1e8, "亿"), (1e4, "万")],
+        }
+    }
+}
+
+/// 去掉小数末尾的0
+fn trim_fraction(mut s: String) -> String {
+    if s.contains('.') {
+        let len = s.trim_end_matches('0').trim_end_matches('.').len();
+        s.truncate(len);
+    }
+    s
+}
+
+/// 按语言格式化时长, 数量, 字节数, 用于看板等给人看的地方
+#[derive(Debug, Clone, Copy)]
+pub struct HumanFormatter {
+    locale:   Locale,
+    decimals: usize,
+}
+
+impl Default for HumanFormatter {
+    fn default() -> Self {
+        HumanFormatter::new(Locale::default())
+    }
+}
+
+impl HumanFormatter {
+    pub fn new(locale: Locale) -> HumanFormatter {
+        HumanFormatter {
+            locale,
+            decimals: 1,
+        }
+    }
+
+    /// 数量, 字节数缩写时保留的最多小数位数, 默认1位
+    pub fn with_decimals(self, decimals: usize) -> Self {
+        HumanFormatter { decimals, ..self }
+    }
+
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    /// 时长, 如"3h25m", "3小时25分"
+    pub fn duration(&self, d: &Duration) -> String {
+        format_duration_units(d, self.locale.duration_units())
+    }
+
+    /// 数量缩写, 如"1.2K", "1.2万", 小于最小的单位时原样输出
+    pub fn count(&self, count: u64) -> String {
+        let decimals = self.decimals;
+        let units = self.locale.count_units();
+        if units
+            .last()
+            .is_none_or(|(scale, _)| (count as f64) < *scale)
+        {
+            return count.to_string();
+        }
+        for (scale, unit) in units {
+            let v = format!("{:.decimals$}", count as f64 / scale);
+            // 按四舍五入后的值选单位, 如99_999_999为"1亿"而不是"10000万"
+            if v.parse::<f64>().unwrap_or_default() >= 1.0 {
+                return format!("{}{}", trim_fraction(v), unit);
+            }
+        }
+        count.to_string()
+    }
+
+    /// 字节数缩写, 1024进制, 如"1.5GiB", 小于1KiB时为"512B", "512字节"
+    pub fn bytes(&self, bytes: u64) -> String {
+        let decimals = self.decimals;
+        for (unit, shift) in [("TiB", 40), ("GiB", 30), ("MiB", 20), ("KiB", 10)] {
+            let v = format!("{:.decimals$}", bytes as f64 / (1u64 << shift) as f64);
+            if bytes >= 1 << 10 && v.parse::<f64>().unwrap_or_default() >= 1.0 {
+                return format!("{}{}", trim_fraction(v), unit);
+            }
+        }
+        match self.locale {
+            Locale::En => format!("{}B", bytes),
+            Locale::ZhCn => format!("{}字节", bytes),
+        }
+    }
+}
+
+/// 按最小变动价位格式化价格, 四舍五入到tick的整数倍, 小数位数和tick一致
+/// tick为0时只去掉末尾的0
+pub fn format_price(value: Decimal, tick: Decimal) -> String {
+    let tick = tick.abs().normalize();
+    if tick.is_zero() {
+        return value.normalize().to_string();
+    }
+    let mut v = (value / tick).round() * tick;
+    v.rescale(tick.scale());
+    v.to_string()
+}
+
+/// 带符号的百分比, 如0.0123 -> "+1.23%", 0 -> "0.00%"
+pub fn format_signed_pct(ratio: Decimal, decimals: u32) -> String {
+    let mut v = (ratio * Decimal::ONE_HUNDRED).round_dp(decimals);
+    v.rescale(decimals);
+    if v.is_zero() {
+        v.set_sign_positive(true);
+        format!("{}%", v)
+    } else if v.is_sign_positive() {
+        format!("+{}%", v)
+    } else {
+        format!("{}%", v)
+    }
+}
+
+/// 文本表格, 按每列最宽的内容对齐, 中文按2个字符宽度计算
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    header:      Vec<String>,
+    rows:        Vec<Vec<String>>,
+    right_align: Vec<usize>,
+}
+
+impl Table {
+    pub fn new(header: &[&str]) -> Table {
+        Table {
+            header: header.iter().map(|v| v.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// 右对齐的列, 一般为数字列
+    pub fn with_right_align(self, columns: &[usize]) -> Self {
+        Table {
+            right_align: columns.to_vec(),
+            ..self
+        }
+    }
+
+    /// 列数和表头不一致时, 多的忽略, 少的为空
+    pub fn push_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+fn display_width(s: &str) -> usize {
+    s.chars()
+        .map(|c| if (c as u32) < 0x1100 { 1 } else { 2 })
+        .sum()
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut widths = self
+            .header
+            .iter()
+            .map(|v| display_width(v))
+            .collect::<Vec<_>>();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(display_width(cell));
+            }
+        }
+        let write_row = |f: &mut fmt::Formatter<'_>, row: &[String]| -> fmt::Result {
+            let mut line = String::new();
+            for (i, width) in widths.iter().enumerate() {
+                let cell = row.get(i).map(String::as_str).unwrap_or("");
+                let pad = " ".repeat(width - display_width(cell));
+                if i > 0 {
+                    line.push_str("  ");
+                }
+                if self.right_align.contains(&i) {
+                    line.push_str(&pad);
+                    line.push_str(cell);
+                } else {
+                    line.push_str(cell);
+                    line.push_str(&pad);
+                }
+            }
+            writeln!(f, "{}", line.trim_end())
+        };
+        write_row(f, &self.header)?;
+        let total = widths.iter().sum::<usize>() + widths.len().saturating_sub(1) * 2;
+        writeln!(f, "{}", "-".repeat(total))?;
+        for row in &self.rows {
+            write_row(f, row)?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
+    use std::time::Duration;
 
     use rust_decimal::Decimal;
 
-    use super::{HumanCountFixPad, HumanDecimal};
+    use super::{
+        format_bytes, format_duration, format_price, format_signed_pct, parse_bytes,
+        parse_duration, HumanCountFixPad, HumanDecimal, HumanFormatter, Locale, Table,
+    };
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("1h 30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("1.5d").unwrap(), Duration::from_secs(129600));
+        assert_eq!(parse_duration("60").unwrap(), Duration::from_secs(60));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10x").is_err());
+        println!("{}", parse_duration("1y").unwrap_err());
+
+        assert_eq!(format_duration(&Duration::from_secs(5400)), "1h30m");
+        assert_eq!(format_duration(&Duration::from_secs(90061)), "1d1h1m1s");
+        assert_eq!(format_duration(&Duration::from_millis(1500)), "1500ms");
+        assert_eq!(format_duration(&Duration::ZERO), "0s");
+    }
+
+    #[test]
+    fn test_format_price() {
+        let d = |s: &str| Decimal::from_str(s).unwrap();
+        assert_eq!(format_price(d("5123.4"), d("1")), "5123");
+        assert_eq!(format_price(d("5123"), d("0.5")), "5123.0");
+        assert_eq!(format_price(d("5123.26"), d("0.5")), "5123.5");
+        assert_eq!(format_price(d("3.1"), d("0.005")), "3.100");
+        assert_eq!(format_price(d("3.1234"), d("0.20")), "3.2");
+        assert_eq!(format_price(d("3.1200"), d("0")), "3.12");
+
+        assert_eq!(format_signed_pct(d("0.01234"), 2), "+1.23%");
+        assert_eq!(format_signed_pct(d("-0.005"), 1), "-0.5%");
+        assert_eq!(format_signed_pct(d("-0.00001"), 2), "0.00%");
+        assert_eq!(format_signed_pct(d("0"), 2), "0.00%");
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("1GiB").unwrap(), 1 << 30);
+        assert_eq!(parse_bytes("512MB").unwrap(), 512_000_000);
+        assert_eq!(parse_bytes("1.5kib").unwrap(), 1536);
+        assert_eq!(parse_bytes("4096").unwrap(), 4096);
+        assert!(parse_bytes("1 GiB 2MiB").is_err());
+        assert!(parse_bytes("1GB/s").is_err());
+
+        assert_eq!(format_bytes(1 << 30), "1GiB");
+        assert_eq!(format_bytes(1536), "1536B");
+        assert_eq!(format_bytes(0), "0B");
+    }
+
+    #[test]
+    fn test_human_formatter() {
+        let en = HumanFormatter::default();
+        let zh = HumanFormatter::new(Locale::ZhCn);
+        let d = Duration::from_secs(3 * 3600 + 25 * 60);
+        assert_eq!(en.duration(&d), "3h25m");
+        assert_eq!(zh.duration(&d), "3小时25分");
+        assert_eq!(zh.duration(&Duration::from_secs(90061)), "1天1小时1分1秒");
+        assert_eq!(zh.duration(&Duration::from_millis(1500)), "1500毫秒");
+        assert_eq!(zh.duration(&Duration::ZERO), "0秒");
+
+        assert_eq!(en.count(999), "999");
+        assert_eq!(en.count(12_345), "12.3K");
+        assert_eq!(en.count(2_000_000), "2M");
+        assert_eq!(zh.count(9_999), "9999");
+        assert_eq!(zh.count(12_345), "1.2万");
+        assert_eq!(zh.count(99_999_999), "1亿");
+        assert_eq!(zh.with_decimals(2).count(345_670_000), "3.46亿");
+
+        assert_eq!(en.bytes(512), "512B");
+        assert_eq!(zh.bytes(512), "512字节");
+        assert_eq!(zh.bytes(1536 << 20), "1.5GiB");
+    }
 
     #[test]
     fn test_human_count() {
@@ -141,4 +570,15 @@ mod tests {
             println!("{}: {}", i, char);
         }
     }
+
+    #[test]
+    fn test_table() {
+        let mut table = Table::new(&["品种", "bars"]).with_right_align(&[1]);
+        table.push_row(vec!["ag".to_string(), "555".to_string()]);
+        table.push_row(vec!["cu".to_string(), "12345".to_string()]);
+        assert_eq!(
+            table.to_string(),
+            "品种   bars\n-----------\nag      555\ncu    12345\n"
+        );
+    }
 }
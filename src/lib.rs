@@ -15,6 +15,8 @@ pub mod mysqlx;
 mod mysqlx_test_pool;
 #[cfg(feature = "path-plain")]
 pub mod path_plain;
+#[cfg(any(feature = "hq", feature = "qh"))]
+pub mod period;
 #[cfg(feature = "progress-bar")]
 pub mod progress_bar;
 #[cfg(feature = "qh")]
@@ -23,10 +25,14 @@ pub mod qh;
 pub mod redis;
 #[cfg(feature = "running")]
 pub mod running;
+#[cfg(any(feature = "toml", feature = "yaml"))]
+pub mod schema;
 #[cfg(feature = "serde-extend")]
 pub mod serde_extend;
 #[cfg(feature = "sizehmap")]
 pub mod sizehmap;
+#[cfg(any(feature = "mysqlx", feature = "sql-loader"))]
+pub mod sql_ident;
 #[cfg(feature = "sql-loader")]
 pub mod sql_loader;
 #[cfg(feature = "ssh")]
@@ -1,30 +1,58 @@
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::{Arc, OnceLock};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use eyre::eyre;
 use log::{debug, error, trace};
 use serde::Deserialize;
 use sqlx::mysql::{MySqlConnectOptions, MySqlPoolOptions, MySqlSslMode};
-use sqlx::{ConnectOptions, Executor, MySqlPool};
+use sqlx::pool::PoolConnection;
+use sqlx::{ConnectOptions, Executor, MySql, MySqlPool};
 use tokio::sync::Mutex;
 
+pub use self::copy_rows::{copy_rows, CopyProgress};
+pub use self::count::{count, exists};
+pub use self::explain::{explain, ExplainReport, ExplainRow};
+pub use self::fetch_map::{fetch_grouped, fetch_map};
+use self::pool_metrics::PoolMetrics;
+pub use self::retry::{is_retryable, retry};
+pub use self::timeout::{with_timeout, QueryTimeoutError};
+pub use self::transaction::{transaction, Tx};
+pub use crate::sql_ident::{column, ident, table_ident, validate_ident, IdentError};
 use crate::ssh::connect::Ssh;
 use crate::ssh::tunnel::{ForwarderMessage, SshTunnel};
 use crate::toml::{self, TomlParseError};
 use crate::yaml::{self, YamlError};
 
+#[cfg(feature = "mysqlx-arrow")]
+pub mod arrow;
 #[cfg(feature = "mysqlx-batch")]
 pub mod batch_exec;
 #[cfg(feature = "mysqlx-batch")]
 pub mod batch_exec_merger;
+#[cfg(feature = "mysqlx-batch")]
+pub mod entity;
+#[cfg(feature = "mysqlx-batch")]
+pub mod idempotency;
 
+pub mod aggregate;
+pub mod copy_rows;
+pub mod count;
 pub mod exec;
+pub mod explain;
+pub mod fetch_map;
+pub mod paginate;
+pub mod pool_metrics;
+pub mod retry;
 pub mod sql_builder;
+pub mod stats;
 pub mod table;
+pub mod timeout;
+pub mod transaction;
 pub mod types;
 pub mod variables;
+pub mod versioned;
 
 #[derive(Debug, Deserialize)]
 struct PoolConfig {
@@ -91,8 +119,6 @@ pub enum PoolConnError {
 
     #[error(r#"db connect "{0}" not exists!"#)]
     KeyNotExist(String),
-    // #[error("{0}")]
-    // Sqlx(#[from] sqlx::Error),
     // #[error("init err when read: {0}")]
     // InitLoclRead(#[from] PoisonError<RwLockReadGuard<'static, MySqlPools>>),
 
@@ -100,7 +126,17 @@ pub enum PoolConnError {
     // InitLockWrite(#[from] PoisonError<RwLockWriteGuard<'static, MySqlPools>>),
 }
 
-async fn connect_pool(config: &PoolConfig) -> Result<MySqlPool, PoolConnError> {
+/// `MySqlPools::acquire`的错误
+#[derive(Debug, thiserror::Error)]
+pub enum AcquireError {
+    #[error("{0}")]
+    Pool(#[from] PoolConnError),
+
+    #[error("{0}")]
+    Sqlx(#[from] sqlx::Error),
+}
+
+async fn connect_pool(key: &str, config: &PoolConfig) -> Result<MySqlPool, PoolConnError> {
     let (host, port) = if let Some(ssh) = &config.ssh {
         let target_addr = format!("{}:{}", config.host, config.port);
         let ssh_tunnel = SshTunnel::new_by_ssh(ssh.clone(), target_addr)?;
@@ -147,12 +183,19 @@ async fn connect_pool(config: &PoolConfig) -> Result<MySqlPool, PoolConnError> {
         connect_opts = connect_opts.log_statements(log::LevelFilter::Off);
     }
 
+    let checkout_stats = pool_metrics::acquire_stats(key);
+    let connect_stats = checkout_stats.clone();
     let pool_mysql = MySqlPoolOptions::new()
         .min_connections(config.min_conns)
         .max_connections(config.max_conns)
         .idle_timeout(Duration::from_secs(config.idle_timeout_secs))
         .acquire_timeout(Duration::from_secs(config.acquire_timeout_secs))
-        .after_connect(|conn, _meta| {
+        .before_acquire(move |_conn, _meta| {
+            checkout_stats.record_checkout();
+            Box::pin(async { Ok(true) })
+        })
+        .after_connect(move |conn, _meta| {
+            connect_stats.record_connect();
             // fix: time_zone = '+00:00'
             Box::pin(async move {
                 // let mut options = String::new();
@@ -229,7 +272,7 @@ impl MySqlPools {
             let pool = if let Some(pool) = pools.get(key) {
                 pool.clone()
             } else {
-                let pool = connect_pool(config).await?;
+                let pool = connect_pool(key, config).await?;
                 let pool = Arc::new(pool);
                 pools.insert(key.to_owned(), pool.clone());
                 pool
@@ -246,6 +289,36 @@ impl MySqlPools {
         Self::pool(&pool_configs.default).await
     }
 
+    /// 从连接池获取连接, 并统计等待时间和超时次数, 见`pool_metrics`
+    pub async fn acquire(key: &str) -> Result<PoolConnection<MySql>, AcquireError> {
+        let pool = Self::pool(key).await?;
+        let stats = pool_metrics::acquire_stats(key);
+        let start = Instant::now();
+        let r = pool.acquire().await;
+        stats.record(start.elapsed(), matches!(r, Err(sqlx::Error::PoolTimedOut)));
+        Ok(r?)
+    }
+
+    pub async fn acquire_default() -> Result<PoolConnection<MySql>, AcquireError> {
+        let pool_configs = POOL_CONFIGS.get().unwrap();
+        Self::acquire(&pool_configs.default).await
+    }
+
+    /// 已创建的连接池的指标, 按key排序
+    pub async fn pool_metrics() -> Vec<PoolMetrics> {
+        let Some(pools) = POOLS.get() else {
+            return Vec::new();
+        };
+        let mut metrics = pools
+            .lock()
+            .await
+            .iter()
+            .map(|(key, pool)| pool_metrics::pool_metrics(key, pool))
+            .collect::<Vec<_>>();
+        metrics.sort_by(|a, b| a.key.cmp(&b.key));
+        metrics
+    }
+
     pub fn pool_ssh(key: &str) -> Arc<Ssh> {
         POOL_CONFIGS
             .get()
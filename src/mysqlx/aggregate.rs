@@ -0,0 +1,181 @@
+use rust_decimal::Decimal;
+use sqlx::mysql::MySqlArguments;
+use sqlx::MySqlPool;
+
+use super::sql_builder::WhereArgsBuilder;
+
+/// decimal字段的聚合查询
+/// 结果在SQL中CAST为DECIMAL(65,scale)后返回, 避免中间转换为double丢失精度
+/// table: 已处理过的表名, 如: `table_name(db, tbl)`
+#[derive(Clone)]
+pub struct DecimalAgg {
+    table:         String,
+    where_builder: WhereArgsBuilder,
+    scale:         u32,
+}
+
+impl DecimalAgg {
+    pub fn new(table: &str) -> DecimalAgg {
+        DecimalAgg {
+            table:         table.to_owned(),
+            where_builder: WhereArgsBuilder::default(),
+            scale:         8,
+        }
+    }
+
+    pub fn where_args(mut self, where_builder: WhereArgsBuilder) -> Self {
+        self.where_builder = where_builder;
+        self
+    }
+
+    /// 结果保留的小数位数, 默认8位, 最大为Decimal支持的28位
+    pub fn scale(mut self, scale: u32) -> Self {
+        self.scale = scale.min(Decimal::MAX_SCALE);
+        self
+    }
+
+    fn cast(&self, expr: &str) -> String {
+        format!("CAST({} AS DECIMAL(65,{}))", expr, self.scale)
+    }
+
+    fn sql_args(&self, select_exprs: &[String]) -> (String, MySqlArguments) {
+        let (where_str, args) = self.where_builder.str_args();
+        let mut sql = format!("SELECT {} FROM {}", select_exprs.join(","), self.table);
+        if !where_str.is_empty() {
+            sql.push(' ');
+            sql.push_str(&where_str);
+        }
+        (sql, args)
+    }
+
+    async fn agg(
+        &self,
+        pool: &MySqlPool,
+        func: &str,
+        column: &str,
+    ) -> Result<Option<Decimal>, sqlx::Error> {
+        let expr = self.cast(&format!("{}(`{}`)", func, column));
+        let (sql, args) = self.sql_args(&[expr]);
+        let (v,) = sqlx::query_as_with::<_, (Option<Decimal>,), _>(&sql, args)
+            .fetch_one(pool)
+            .await?;
+        Ok(v)
+    }
+
+    /// 没有记录时返回None, 下同
+    pub async fn sum(
+        &self,
+        pool: &MySqlPool,
+        column: &str,
+    ) -> Result<Option<Decimal>, sqlx::Error> {
+        self.agg(pool, "SUM", column).await
+    }
+
+    pub async fn avg(
+        &self,
+        pool: &MySqlPool,
+        column: &str,
+    ) -> Result<Option<Decimal>, sqlx::Error> {
+        self.agg(pool, "AVG", column).await
+    }
+
+    pub async fn min(
+        &self,
+        pool: &MySqlPool,
+        column: &str,
+    ) -> Result<Option<Decimal>, sqlx::Error> {
+        self.agg(pool, "MIN", column).await
+    }
+
+    pub async fn max(
+        &self,
+        pool: &MySqlPool,
+        column: &str,
+    ) -> Result<Option<Decimal>, sqlx::Error> {
+        self.agg(pool, "MAX", column).await
+    }
+
+    fn weighted_avg_sql_args(
+        &self,
+        price_column: &str,
+        volume_column: &str,
+    ) -> (String, MySqlArguments) {
+        let amount_expr = self.cast(&format!("SUM(`{}`*`{}`)", price_column, volume_column));
+        let volume_expr = self.cast(&format!("SUM(`{}`)", volume_column));
+        self.sql_args(&[amount_expr, volume_expr])
+    }
+
+    /// 按volume加权的平均价: SUM(price*volume)/SUM(volume)
+    /// 除法在Decimal中计算, 不受MySQL的div_precision_increment影响
+    /// 没有记录或SUM(volume)为0时返回None
+    pub async fn weighted_avg(
+        &self,
+        pool: &MySqlPool,
+        price_column: &str,
+        volume_column: &str,
+    ) -> Result<Option<Decimal>, sqlx::Error> {
+        let (sql, args) = self.weighted_avg_sql_args(price_column, volume_column);
+        let (amount, volume) =
+            sqlx::query_as_with::<_, (Option<Decimal>, Option<Decimal>), _>(&sql, args)
+                .fetch_one(pool)
+                .await?;
+        let r = match (amount, volume) {
+            (Some(amount), Some(volume)) => {
+                amount.checked_div(volume).map(|v| v.round_dp(self.scale))
+            },
+            _ => None,
+        };
+        Ok(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DecimalAgg;
+    use crate::mysqlx::sql_builder::WhereArgsBuilder;
+    use crate::mysqlx::table::table_name;
+    use crate::mysqlx::MySqlPools;
+    use crate::mysqlx_test_pool::init_test_mysql_pools;
+
+    #[test]
+    fn test_sql() {
+        let mut where_builder = WhereArgsBuilder::default();
+        where_builder.add("code", "agL9");
+        let agg = DecimalAgg::new(&table_name("hqdb", "tbl_code_agL9"))
+            .where_args(where_builder)
+            .scale(4);
+
+        let (sql, _) = agg.sql_args(&[agg.cast("SUM(`close`)")]);
+        println!("{}", sql);
+        assert_eq!(
+            sql,
+            "SELECT CAST(SUM(`close`) AS DECIMAL(65,4)) FROM `hqdb`.`tbl_code_agL9` WHERE `code`=?"
+        );
+
+        let (sql, _) = agg.weighted_avg_sql_args("close", "volume");
+        println!("{}", sql);
+        assert_eq!(
+            sql,
+            "SELECT CAST(SUM(`close`*`volume`) AS DECIMAL(65,4)),CAST(SUM(`volume`) AS DECIMAL(65,4)) FROM `hqdb`.`tbl_code_agL9` WHERE `code`=?"
+        );
+
+        assert_eq!(DecimalAgg::new("t").scale(100).scale, 28);
+    }
+
+    #[tokio::test]
+    async fn test_agg() {
+        init_test_mysql_pools();
+        let pool = MySqlPools::pool_default().await.unwrap();
+        let mut where_builder = WhereArgsBuilder::default();
+        where_builder.add("code", "agL9");
+        let agg = DecimalAgg::new(&table_name("hqdb", "tbl_code_agL9")).where_args(where_builder);
+        println!("sum: {:?}", agg.sum(&pool, "close").await);
+        println!("avg: {:?}", agg.avg(&pool, "close").await);
+        println!("min: {:?}", agg.min(&pool, "low").await);
+        println!("max: {:?}", agg.max(&pool, "high").await);
+        println!(
+            "weighted_avg: {:?}",
+            agg.weighted_avg(&pool, "close", "volume").await
+        );
+    }
+}
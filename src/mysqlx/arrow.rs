@@ -0,0 +1,314 @@
+//! 查询结果按批解码为Arrow的RecordBatch, 供polars/datafusion等直接使用
+//!
+//! 类型对应:
+//! - 整数: Int64, 无符号整数: UInt64, BOOLEAN(TINYINT(1)): Boolean
+//! - FLOAT: Float32, DOUBLE: Float64
+//! - DECIMAL: Decimal128(38, scale), scale取第一批数据中该列所有非空值的最大scale
+//!   (sqlx没有公开列定义的小数位数), 之后的批次按这个scale转换
+//! - DATETIME/TIMESTAMP: Timestamp(Microsecond), DATE: Date32, TIME: Time64(Microsecond)
+//! - 字符串类: Utf8, 二进制类: Binary
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    BinaryBuilder, BooleanBuilder, Date32Builder, Decimal128Builder, Float32Builder,
+    Float64Builder, Int64Builder, StringBuilder, Time64MicrosecondBuilder,
+    TimestampMicrosecondBuilder, UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use futures_util::stream::BoxStream;
+use futures_util::{StreamExt, TryStreamExt};
+use rust_decimal::Decimal;
+use sqlx::mysql::{MySqlArguments, MySqlRow};
+use sqlx::{Column, MySqlPool, Row, TypeInfo, ValueRef};
+
+/// 默认每批的行数
+pub const DEFAULT_BATCH_ROWS: usize = 8192;
+
+const DECIMAL_PRECISION: u8 = 38;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArrowFetchError {
+    #[error("{0}")]
+    Sqlx(#[from] sqlx::Error),
+
+    #[error("{0}")]
+    Arrow(#[from] ArrowError),
+
+    #[error("column `{column}` type {type_name} not supported")]
+    UnsupportedType {
+        column:    String,
+        type_name: String,
+    },
+}
+
+pub type RecordBatchStream<'a> = BoxStream<'a, Result<RecordBatch, ArrowFetchError>>;
+
+/// 执行查询, 每batch_rows行生成一个RecordBatch
+pub fn fetch_arrow<'a>(
+    pool: &'a MySqlPool,
+    sql: &'a str,
+    args: MySqlArguments,
+    batch_rows: usize,
+) -> RecordBatchStream<'a> {
+    let mut schema: Option<SchemaRef> = None;
+    sqlx::query_with(sql, args)
+        .fetch(pool)
+        .try_chunks(batch_rows.max(1))
+        .map_err(|e| ArrowFetchError::Sqlx(e.1))
+        .and_then(move |rows| {
+            let r = rows_to_batch(&rows, &mut schema);
+            async move { r }
+        })
+        .boxed()
+}
+
+/// 第一次调用时根据rows生成schema, 之后的批次使用相同的schema
+fn rows_to_batch(
+    rows: &[MySqlRow],
+    schema: &mut Option<SchemaRef>,
+) -> Result<RecordBatch, ArrowFetchError> {
+    let schema = match schema {
+        Some(schema) => schema.clone(),
+        None => schema.insert(rows_schema(rows)?).clone(),
+    };
+    let mut columns = schema
+        .fields()
+        .iter()
+        .map(|field| ColumnBuilder::new(field.data_type(), rows.len()))
+        .collect::<Vec<_>>();
+    for row in rows {
+        for (idx, column) in columns.iter_mut().enumerate() {
+            column.append(row, idx)?;
+        }
+    }
+    let arrays = columns
+        .into_iter()
+        .map(ColumnBuilder::finish)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(RecordBatch::try_new(schema, arrays)?)
+}
+
+fn rows_schema(rows: &[MySqlRow]) -> Result<SchemaRef, ArrowFetchError> {
+    let Some(first) = rows.first() else {
+        return Ok(Arc::new(Schema::empty()));
+    };
+    let mut fields = Vec::with_capacity(first.columns().len());
+    for column in first.columns() {
+        let type_name = column.type_info().name();
+        let data_type = match type_name {
+            "BOOLEAN" => DataType::Boolean,
+            "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "BIGINT" | "YEAR" => DataType::Int64,
+            "TINYINT UNSIGNED" | "SMALLINT UNSIGNED" | "MEDIUMINT UNSIGNED" | "INT UNSIGNED"
+            | "BIGINT UNSIGNED" | "BIT" => DataType::UInt64,
+            "FLOAT" => DataType::Float32,
+            "DOUBLE" => DataType::Float64,
+            "DECIMAL" => DataType::Decimal128(DECIMAL_PRECISION, decimal_scale(rows, column)?),
+            "DATETIME" | "TIMESTAMP" => DataType::Timestamp(TimeUnit::Microsecond, None),
+            "DATE" => DataType::Date32,
+            "TIME" => DataType::Time64(TimeUnit::Microsecond),
+            "CHAR" | "VARCHAR" | "TINYTEXT" | "TEXT" | "MEDIUMTEXT" | "LONGTEXT" | "ENUM"
+            | "SET" | "JSON" => DataType::Utf8,
+            "BINARY" | "VARBINARY" | "TINYBLOB" | "BLOB" | "MEDIUMBLOB" | "LONGBLOB" => {
+                DataType::Binary
+            },
+            _ => {
+                return Err(ArrowFetchError::UnsupportedType {
+                    column:    column.name().to_owned(),
+                    type_name: type_name.to_owned(),
+                })
+            },
+        };
+        fields.push(Field::new(column.name(), data_type, true));
+    }
+    Ok(Arc::new(Schema::new(fields)))
+}
+
+/// MySQL返回的DECIMAL一般带有列定义的全部小数位, 计算列(如SUM/AVG的结果)各值可能不同,
+/// 取所有非空值中最大的scale, 避免截掉其它值的小数
+fn decimal_scale(
+    rows: &[MySqlRow],
+    column: &sqlx::mysql::MySqlColumn,
+) -> Result<i8, ArrowFetchError> {
+    let mut scale = 0;
+    for row in rows {
+        if let Some(v) = row.try_get::<Option<Decimal>, _>(column.ordinal())? {
+            scale = scale.max(v.scale());
+        }
+    }
+    Ok(scale as i8)
+}
+
+fn date32(date: NaiveDate) -> i32 {
+    (date - NaiveDate::default()).num_days() as i32
+}
+
+fn time64_us(time: NaiveTime) -> i64 {
+    time.num_seconds_from_midnight() as i64 * 1_000_000 + (time.nanosecond() / 1_000) as i64
+}
+
+enum ColumnBuilder {
+    Boolean(BooleanBuilder),
+    Int64(Int64Builder),
+    UInt64(UInt64Builder),
+    Float32(Float32Builder),
+    Float64(Float64Builder),
+    Decimal(Decimal128Builder, u8, i8),
+    Timestamp(TimestampMicrosecondBuilder),
+    Date32(Date32Builder),
+    Time64(Time64MicrosecondBuilder),
+    Utf8(StringBuilder),
+    Binary(BinaryBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(data_type: &DataType, capacity: usize) -> ColumnBuilder {
+        match data_type {
+            DataType::Boolean => ColumnBuilder::Boolean(BooleanBuilder::with_capacity(capacity)),
+            DataType::Int64 => ColumnBuilder::Int64(Int64Builder::with_capacity(capacity)),
+            DataType::UInt64 => ColumnBuilder::UInt64(UInt64Builder::with_capacity(capacity)),
+            DataType::Float32 => ColumnBuilder::Float32(Float32Builder::with_capacity(capacity)),
+            DataType::Float64 => ColumnBuilder::Float64(Float64Builder::with_capacity(capacity)),
+            DataType::Decimal128(precision, scale) => ColumnBuilder::Decimal(
+                Decimal128Builder::with_capacity(capacity),
+                *precision,
+                *scale,
+            ),
+            DataType::Timestamp(..) => {
+                ColumnBuilder::Timestamp(TimestampMicrosecondBuilder::with_capacity(capacity))
+            },
+            DataType::Date32 => ColumnBuilder::Date32(Date32Builder::with_capacity(capacity)),
+            DataType::Time64(_) => {
+                ColumnBuilder::Time64(Time64MicrosecondBuilder::with_capacity(capacity))
+            },
+            DataType::Binary => ColumnBuilder::Binary(BinaryBuilder::new()),
+            _ => ColumnBuilder::Utf8(StringBuilder::new()),
+        }
+    }
+
+    fn append(&mut self, row: &MySqlRow, idx: usize) -> Result<(), sqlx::Error> {
+        match self {
+            ColumnBuilder::Boolean(b) => b.append_option(row.try_get::<Option<bool>, _>(idx)?),
+            ColumnBuilder::Int64(b) => b.append_option(row.try_get::<Option<i64>, _>(idx)?),
+            ColumnBuilder::UInt64(b) => b.append_option(row.try_get::<Option<u64>, _>(idx)?),
+            ColumnBuilder::Float32(b) => b.append_option(row.try_get::<Option<f32>, _>(idx)?),
+            ColumnBuilder::Float64(b) => b.append_option(row.try_get::<Option<f64>, _>(idx)?),
+            ColumnBuilder::Decimal(b, _, scale) => {
+                let v = row.try_get::<Option<Decimal>, _>(idx)?.map(|mut v| {
+                    v.rescale(*scale as u32);
+                    v.mantissa()
+                });
+                b.append_option(v)
+            },
+            ColumnBuilder::Timestamp(b) => b.append_option(
+                row.try_get::<Option<NaiveDateTime>, _>(idx)?
+                    .map(|v| v.and_utc().timestamp_micros()),
+            ),
+            ColumnBuilder::Date32(b) => {
+                b.append_option(row.try_get::<Option<NaiveDate>, _>(idx)?.map(date32))
+            },
+            ColumnBuilder::Time64(b) => {
+                b.append_option(row.try_get::<Option<NaiveTime>, _>(idx)?.map(time64_us))
+            },
+            ColumnBuilder::Utf8(b) => {
+                // JSON等类型不能直接解码为String时, 取原始的文本
+                if row.try_get_raw(idx)?.is_null() {
+                    b.append_null();
+                } else {
+                    match row.try_get::<String, _>(idx) {
+                        Ok(v) => b.append_value(v),
+                        Err(_) => {
+                            let bytes = row.try_get_unchecked::<Vec<u8>, _>(idx)?;
+                            b.append_value(String::from_utf8_lossy(&bytes))
+                        },
+                    }
+                }
+            },
+            ColumnBuilder::Binary(b) => {
+                b.append_option(row.try_get_unchecked::<Option<Vec<u8>>, _>(idx)?)
+            },
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<ArrayRef, ArrowError> {
+        let array: ArrayRef = match self {
+            ColumnBuilder::Boolean(mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Int64(mut b) => Arc::new(b.finish()),
+            ColumnBuilder::UInt64(mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Float32(mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Float64(mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Decimal(mut b, precision, scale) => {
+                Arc::new(b.finish().with_precision_and_scale(precision, scale)?)
+            },
+            ColumnBuilder::Timestamp(mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Date32(mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Time64(mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Utf8(mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Binary(mut b) => Arc::new(b.finish()),
+        };
+        Ok(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Decimal128Type, Int64Type, TimestampMicrosecondType};
+    use chrono::{NaiveDate, NaiveTime};
+    use futures_util::TryStreamExt;
+    use sqlx::mysql::MySqlArguments;
+
+    use super::{date32, fetch_arrow, time64_us};
+    use crate::mysqlx::MySqlPools;
+    use crate::mysqlx_test_pool::init_test_mysql_pools;
+
+    #[test]
+    fn test_convert() {
+        assert_eq!(date32(NaiveDate::from_ymd_opt(1970, 1, 2).unwrap()), 1);
+        assert_eq!(
+            time64_us(NaiveTime::from_hms_micro_opt(0, 0, 1, 5).unwrap()),
+            1_000_005
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_arrow() {
+        init_test_mysql_pools();
+        let pool = MySqlPools::pool_default().await.unwrap();
+        let sql = "SELECT 1 AS id, CAST(12.345 AS DECIMAL(10,3)) AS price, \
+                   CAST('2024-06-03 09:01:00' AS DATETIME) AS dt, 'ag2408' AS code \
+                   UNION ALL SELECT 2, NULL, NULL, NULL";
+        let batches = fetch_arrow(&pool, sql, MySqlArguments::default(), 1)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.len(), 2);
+        let batch = &batches[0];
+        println!("{:?}", batch.schema());
+        assert_eq!(batch.column(0).as_primitive::<Int64Type>().value(0), 1);
+        assert_eq!(
+            batch.column(1).as_primitive::<Decimal128Type>().value(0),
+            12345
+        );
+        println!(
+            "{}",
+            batch
+                .column(2)
+                .as_primitive::<TimestampMicrosecondType>()
+                .value(0)
+        );
+        assert_eq!(batches[1].column(1).null_count(), 1);
+
+        // 第一行为空时scale取后面的值
+        let sql = "SELECT NULL AS v UNION ALL SELECT CAST(1.25 AS DECIMAL(10,2))";
+        let batches = fetch_arrow(&pool, sql, MySqlArguments::default(), 2)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let column = batches[0].column(0).as_primitive::<Decimal128Type>();
+        assert_eq!(column.scale(), 2);
+        assert_eq!(column.value(1), 125);
+    }
+}
@@ -2,22 +2,30 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use log::info;
 use sqlx::mysql::MySqlArguments;
 use sqlx::MySqlPool;
 use thiserror::Error;
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
+use super::idempotency::IdempotencyStore;
+
+/// 审计日志的target
+pub const AUDIT_TARGET: &str = "mysqlx::audit";
+
 pub trait SqlEntityReplace: Send {
     fn sql_entity_replace(&self, key: &str, db: &str, tbl_name: &str) -> SqlEntity;
 }
 
 #[derive(Debug, Clone)]
 pub struct SqlEntity {
-    key:  String,
-    idx:  u16,
-    sql:  String,
-    args: MySqlArguments,
+    key:           String,
+    /// key为空时自动生成, 不记录到幂等表
+    key_generated: bool,
+    idx:           u16,
+    sql:           String,
+    args:          MySqlArguments,
 }
 
 impl std::fmt::Display for SqlEntity {
@@ -28,19 +36,29 @@ impl std::fmt::Display for SqlEntity {
 
 impl SqlEntity {
     pub fn new(key: &str, sql: &str, args: MySqlArguments) -> SqlEntity {
-        let key = if key.is_empty() {
+        let key_generated = key.is_empty();
+        let key = if key_generated {
             Uuid::now_v7().to_string()
         } else {
             key.to_owned()
         };
         SqlEntity {
             key,
+            key_generated,
             idx: 0,
             sql: sql.to_owned(),
             args,
         }
     }
 
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
     // pub fn add_arg<T>(&mut self, value: T)
     // where
     //     T: Send + for<'a> Encode<'a, MySql> + Type<MySql>,
@@ -51,6 +69,16 @@ impl SqlEntity {
     // }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AuditMode {
+    #[default]
+    Off,
+    /// 执行前记录sql
+    Log,
+    /// 只记录sql, 不执行
+    DryRun,
+}
+
 type Result = std::result::Result<BatchExecInfo, BatchExecError>;
 
 /// RA: rows affected
@@ -59,27 +87,40 @@ type Result = std::result::Result<BatchExecInfo, BatchExecError>;
 #[derive(Debug, Default)]
 pub struct BatchExecInfo {
     is_exec:          bool,
+    is_dry_run:       bool,
     exec_threshold:   usize,
     pub entity_count: usize,
     rows_affected:    u64,
     elapsed:          Duration,
+    /// 幂等记录中已执行而跳过的key
+    skipped:          Vec<String>,
 }
 
 impl std::fmt::Display for BatchExecInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.is_exec {
+        if self.is_dry_run {
+            write!(
+                f,
+                "*Dry Run* C:{:>4}/T:{:>4}",
+                self.entity_count, self.exec_threshold,
+            )?;
+        } else if self.is_exec {
             write!(
                 f,
                 "[{:>9.3?}] Rows affected:{:>4}/{:>4} (T:{:>4})",
                 self.elapsed, self.rows_affected, self.entity_count, self.exec_threshold
-            )
+            )?;
         } else {
             write!(
                 f,
                 "*Not Exec* C:{:>4}/T:{:>4}",
                 self.entity_count, self.exec_threshold,
-            )
+            )?;
+        }
+        if !self.skipped.is_empty() {
+            write!(f, " Skipped:{:>4}", self.skipped.len())?;
         }
+        Ok(())
     }
 }
 
@@ -87,6 +128,15 @@ impl BatchExecInfo {
     pub fn is_exec(&self) -> bool {
         self.is_exec
     }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.is_dry_run
+    }
+
+    /// 重放时已执行过而跳过的key, 按添加的顺序
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
 }
 
 #[derive(Error, Debug)]
@@ -95,6 +145,9 @@ pub enum BatchExecError {
     Query { sql: String, err: sqlx::Error },
     #[error("{0}")]
     Sqlx(#[from] sqlx::Error),
+    #[cfg(feature = "redis")]
+    #[error("idempotency store err: {0}")]
+    Redis(#[from] redis::RedisError),
 }
 
 /// 只支持单线程
@@ -104,6 +157,8 @@ pub struct BatchExec {
     entity_idx:     u16,
     entity_map:     HashMap<String, SqlEntity>,
     lock:           Arc<Mutex<()>>,
+    audit_mode:     AuditMode,
+    idempotency:    Option<IdempotencyStore>,
 }
 
 impl BatchExec {
@@ -114,6 +169,38 @@ impl BatchExec {
             entity_idx: 0,
             entity_map: Default::default(),
             lock: Arc::new(Mutex::new(())),
+            audit_mode: AuditMode::Off,
+            idempotency: None,
+        }
+    }
+
+    /// 记录已执行的key, 执行时跳过记录中已有的key(自动生成的key除外)
+    pub fn with_idempotency(mut self, store: IdempotencyStore) -> Self {
+        self.idempotency = Some(store);
+        self
+    }
+
+    /// sql记录到`AUDIT_TARGET`, 参数不记录, DryRun时不执行, 达到阈值的sql记录后清除
+    pub fn with_audit(mut self, audit_mode: AuditMode) -> Self {
+        self.audit_mode = audit_mode;
+        self
+    }
+
+    fn audit(&self, entity_vec: &[SqlEntity]) {
+        let mode = match self.audit_mode {
+            AuditMode::Off => return,
+            AuditMode::Log => "exec",
+            AuditMode::DryRun => "dry-run",
+        };
+        for entity in entity_vec {
+            info!(
+                target: AUDIT_TARGET,
+                "[{}] key:{} idx:{} {}",
+                mode,
+                entity.key,
+                entity.idx,
+                entity.sql
+            );
         }
     }
 
@@ -159,10 +246,43 @@ impl BatchExec {
 
         let pool = &*self.pool.clone();
 
-        let sql_entity_vec = self.sorted_entity_vec().await;
+        let mut sql_entity_vec = self.sorted_entity_vec().await;
+
+        // MySQL的幂等记录先开启事务, 查询和执行在同一个事务中;
+        // 没有幂等记录或只有Redis时DryRun不连接数据库
+        let mut transaction = None;
+        let mut recorded_keys = Vec::new();
+        if let Some(store) = &self.idempotency {
+            let keys = sql_entity_vec
+                .iter()
+                .filter(|v| !v.key_generated)
+                .map(|v| v.key.as_str())
+                .collect::<Vec<_>>();
+            let applied = store.applied(pool, &mut transaction, &keys).await?;
+            let (skipped, entity_vec) = sql_entity_vec
+                .into_iter()
+                .partition::<Vec<_>, _>(|v| applied.contains(&v.key));
+            exec_info.skipped = skipped.into_iter().map(|v| v.key).collect();
+            sql_entity_vec = entity_vec;
+            recorded_keys = sql_entity_vec
+                .iter()
+                .filter(|v| !v.key_generated)
+                .map(|v| v.key.clone())
+                .collect();
+        }
 
-        let mut transaction = pool.begin().await?;
+        self.audit(&sql_entity_vec);
+        if self.audit_mode == AuditMode::DryRun {
+            drop(lock);
+            exec_info.is_dry_run = true;
+            exec_info.elapsed = start.elapsed();
+            return Ok(exec_info);
+        }
 
+        let mut transaction = match transaction {
+            Some(transaction) => transaction,
+            None => pool.begin().await?,
+        };
         let mut rows_affected = 0;
         for SqlEntity { sql, args, .. } in sql_entity_vec {
             let result = sqlx::query_with(&sql, args)
@@ -177,7 +297,14 @@ impl BatchExec {
                 },
             }
         }
+        let recorded_keys = recorded_keys.iter().map(String::as_str).collect::<Vec<_>>();
+        if let Some(store) = &self.idempotency {
+            store.record_in_tx(&mut transaction, &recorded_keys).await?;
+        }
         transaction.commit().await?;
+        if let Some(store) = &self.idempotency {
+            store.record_after_commit(&recorded_keys).await?;
+        }
 
         drop(lock);
 
@@ -221,6 +348,20 @@ mod botch_exec_tests {
         println!("{}", BatchExecInfo::default());
     }
 
+    #[tokio::test]
+    async fn test_dry_run() {
+        init_test_mysql_pools();
+        let mut be = batch_exec()
+            .await
+            .with_audit(AuditMode::DryRun);
+        let info = be.execute_all().await.unwrap();
+        println!("{}", info);
+        assert!(info.is_dry_run());
+        assert!(!info.is_exec());
+        assert_eq!(info.entity_count, 4);
+        assert!(be.entity_map.is_empty());
+    }
+
     #[test]
     fn test_sql_entity_new() {
         let mut args = MySqlArguments::default();
@@ -0,0 +1,438 @@
+//! 按主键分批复制表中的行, 用于分库迁移
+//!
+//! 两个连接池在同一个服务器时用`INSERT IGNORE ... SELECT`, 数据不经过本机;
+//! 不在同一个服务器时逐批查询再插入. 目标表已有的行(主键或唯一键重复)跳过, 中断后可以重新执行
+//!
+//! 是否同一个服务器按`@@server_uuid`判断, 通过代理或隧道连接时hostname和端口不可靠
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use sqlx::mysql::{MySqlArguments, MySqlRow};
+use sqlx::{Arguments, MySqlPool, Row, TypeInfo, ValueRef};
+
+use super::sql_builder::WhereArgsBuilder;
+use crate::sql_ident::{column, ident};
+
+/// 一条预处理语句最多的占位符数
+const MAX_PLACEHOLDERS: usize = 65535;
+
+/// 每批完成后的进度
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CopyProgress {
+    /// 已复制的行数, 不含目标表已存在被跳过的行
+    pub rows:        u64,
+    pub batches:     u64,
+    pub same_server: bool,
+    pub elapsed:     Duration,
+}
+
+/// 查询结果中的值, 跨服务器复制时原样写入目标表
+#[derive(Debug, Clone, PartialEq)]
+enum CopyValue {
+    Null,
+    Int(i64),
+    UInt(u64),
+    Double(f64),
+    /// 字符串和DECIMAL
+    Text(String),
+    Bytes(Vec<u8>),
+    Date(NaiveDate),
+    Time(NaiveTime),
+    DateTime(NaiveDateTime),
+}
+
+impl CopyValue {
+    fn decode(row: &MySqlRow, idx: usize) -> Result<CopyValue, sqlx::Error> {
+        let raw = row.try_get_raw(idx)?;
+        if raw.is_null() {
+            return Ok(CopyValue::Null);
+        }
+        let type_name = raw.type_info().name().to_owned();
+        let value = match type_name.as_str() {
+            "FLOAT" => CopyValue::Double(row.try_get::<f32, _>(idx)? as f64),
+            "DOUBLE" => CopyValue::Double(row.try_get(idx)?),
+            "DECIMAL" => CopyValue::Text(row.try_get_unchecked(idx)?),
+            "DATE" => CopyValue::Date(row.try_get(idx)?),
+            "TIME" => CopyValue::Time(row.try_get(idx)?),
+            "DATETIME" | "TIMESTAMP" => CopyValue::DateTime(row.try_get(idx)?),
+            "BIT" => CopyValue::Bytes(row.try_get_unchecked(idx)?),
+            "CHAR" | "VARCHAR" | "TEXT" | "TINYTEXT" | "MEDIUMTEXT" | "LONGTEXT" | "ENUM"
+            | "SET" | "JSON" => CopyValue::Text(row.try_get_unchecked(idx)?),
+            v if v.ends_with("UNSIGNED") => CopyValue::UInt(row.try_get_unchecked(idx)?),
+            "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "BIGINT" | "BOOLEAN" | "YEAR" => {
+                CopyValue::Int(row.try_get_unchecked(idx)?)
+            },
+            _ => CopyValue::Bytes(row.try_get_unchecked(idx)?),
+        };
+        Ok(value)
+    }
+
+    fn add_to(&self, args: &mut MySqlArguments) {
+        match self {
+            CopyValue::Null => args.add(None::<i64>),
+            CopyValue::Int(v) => args.add(*v),
+            CopyValue::UInt(v) => args.add(*v),
+            CopyValue::Double(v) => args.add(*v),
+            CopyValue::Text(v) => args.add(v.clone()),
+            CopyValue::Bytes(v) => args.add(v.clone()),
+            CopyValue::Date(v) => args.add(*v),
+            CopyValue::Time(v) => args.add(*v),
+            CopyValue::DateTime(v) => args.add(*v),
+        }
+    }
+}
+
+/// `db.table`格式的表名
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TableRef<'a> {
+    db:    &'a str,
+    table: &'a str,
+    ident: String,
+}
+
+impl<'a> TableRef<'a> {
+    fn parse(name: &'a str) -> Result<TableRef<'a>, sqlx::Error> {
+        let (db, table) = name.split_once('.').ok_or_else(|| {
+            sqlx::Error::Configuration(format!("table must be db.table: {}", name).into())
+        })?;
+        Ok(TableRef {
+            db,
+            table,
+            ident: ident(db, table)?,
+        })
+    }
+}
+
+/// 源表的列(不含生成列)和主键列
+async fn table_columns(
+    pool: &MySqlPool,
+    table: &TableRef<'_>,
+) -> Result<(Vec<String>, Vec<String>), sqlx::Error> {
+    let sql = "SELECT CAST(COLUMN_NAME AS CHAR), CAST(EXTRA AS CHAR) FROM information_schema.COLUMNS WHERE TABLE_SCHEMA=? AND TABLE_NAME=? ORDER BY \
+               ORDINAL_POSITION";
+    let mut args = MySqlArguments::default();
+    args.add(table.db);
+    args.add(table.table);
+    let rows = sqlx::query_as_with::<_, (String, String), _>(sql, args)
+        .fetch_all(pool)
+        .await?;
+    let columns = rows
+        .iter()
+        .filter(|(_, extra)| !extra.to_ascii_uppercase().contains("GENERATED"))
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    if columns.is_empty() {
+        return Err(sqlx::Error::Configuration(
+            format!("table not found: {}", table.ident).into(),
+        ));
+    }
+    // 复合主键按主键定义的顺序, 不是列的顺序
+    let sql = "SELECT CAST(COLUMN_NAME AS CHAR) FROM information_schema.KEY_COLUMN_USAGE WHERE \
+               TABLE_SCHEMA=? AND TABLE_NAME=? AND CONSTRAINT_NAME='PRIMARY' ORDER BY \
+               ORDINAL_POSITION";
+    let mut args = MySqlArguments::default();
+    args.add(table.db);
+    args.add(table.table);
+    let keys = sqlx::query_as_with::<_, (String,), _>(sql, args)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|v| v.0)
+        .collect::<Vec<_>>();
+    if keys.is_empty() {
+        return Err(sqlx::Error::Configuration(
+            format!("table has no primary key: {}", table.ident).into(),
+        ));
+    }
+    Ok((columns, keys))
+}
+
+async fn server_id(pool: &MySqlPool) -> Result<String, sqlx::Error> {
+    let (id,) = sqlx::query_as::<_, (String,)>("SELECT CAST(@@server_uuid AS CHAR)")
+        .fetch_one(pool)
+        .await?;
+    Ok(id)
+}
+
+/// 每批的查询条件, after为上一批最后一行的主键, upto为本批最后一行的主键
+fn batch_where(
+    where_builder: &WhereArgsBuilder,
+    keys: &str,
+    after: Option<&[CopyValue]>,
+    upto: Option<&[CopyValue]>,
+) -> (String, MySqlArguments) {
+    let (where_str, mut args) = where_builder.str_args();
+    let mut conditions = Vec::new();
+    for (cmp, values) in [(">", after), ("<=", upto)] {
+        if let Some(values) = values {
+            let marks = vec!["?"; values.len()].join(",");
+            conditions.push(format!("({}){}({})", keys, cmp, marks));
+            values.iter().for_each(|v| v.add_to(&mut args));
+        }
+    }
+    let where_str = match (where_str.is_empty(), conditions.is_empty()) {
+        (true, true) => String::new(),
+        (true, false) => format!(" WHERE {}", conditions.join(" AND ")),
+        (false, true) => format!(" {}", where_str),
+        (false, false) => format!(" {} AND {}", where_str, conditions.join(" AND ")),
+    };
+    (where_str, args)
+}
+
+/// 跨服务器时每批的行数, 插入语句的占位符数为行数*列数
+fn cross_server_batch(batch: u32, column_count: usize) -> u32 {
+    let max_rows = (MAX_PLACEHOLDERS / column_count.max(1)).max(1);
+    batch.clamp(1, max_rows as u32)
+}
+
+fn row_keys(row: &MySqlRow, key_count: usize) -> Result<Vec<CopyValue>, sqlx::Error> {
+    (0..key_count).map(|i| CopyValue::decode(row, i)).collect()
+}
+
+/// 把src中符合条件的行按主键顺序每批batch行复制到dst, 每批完成后调用progress
+///
+/// src, dst为`(连接池, "db.table")`, 源表必须有主键, 目标表的列需要包含源表的列(生成列除外)
+///
+/// 跨服务器复制时每批的行数不超过`65535 / 列数`, 保证插入语句的占位符数不超限
+pub async fn copy_rows<F>(
+    src: (&Arc<MySqlPool>, &str),
+    dst: (&Arc<MySqlPool>, &str),
+    where_builder: &WhereArgsBuilder,
+    batch: u32,
+    progress: F,
+) -> Result<CopyProgress, sqlx::Error>
+where
+    F: FnMut(&CopyProgress),
+{
+    let same_server =
+        Arc::ptr_eq(src.0, dst.0) || server_id(src.0).await? == server_id(dst.0).await?;
+    copy_rows_with(src, dst, where_builder, batch, same_server, progress).await
+}
+
+async fn copy_rows_with<F>(
+    src: (&Arc<MySqlPool>, &str),
+    dst: (&Arc<MySqlPool>, &str),
+    where_builder: &WhereArgsBuilder,
+    batch: u32,
+    same_server: bool,
+    mut progress: F,
+) -> Result<CopyProgress, sqlx::Error>
+where
+    F: FnMut(&CopyProgress),
+{
+    let (src_pool, src_table) = (src.0, TableRef::parse(src.1)?);
+    let (dst_pool, dst_table) = (dst.0, TableRef::parse(dst.1)?);
+    let (columns, keys) = table_columns(src_pool, &src_table).await?;
+    let batch = if same_server {
+        batch.max(1)
+    } else {
+        cross_server_batch(batch, columns.len())
+    };
+    let columns = columns
+        .iter()
+        .map(|v| column(v))
+        .collect::<Result<Vec<_>, _>>()?
+        .join(",");
+    let keys_str = keys
+        .iter()
+        .map(|v| column(v))
+        .collect::<Result<Vec<_>, _>>()?
+        .join(",");
+
+    let start = Instant::now();
+    let mut stat = CopyProgress {
+        same_server,
+        ..Default::default()
+    };
+    let mut after: Option<Vec<CopyValue>> = None;
+    loop {
+        let last = if same_server {
+            // 本批最后一行的主键, 为None时剩下的不足一批
+            let (where_str, mut args) =
+                batch_where(where_builder, &keys_str, after.as_deref(), None);
+            let sql = format!(
+                "SELECT {} FROM {}{} ORDER BY {} LIMIT 1 OFFSET ?",
+                keys_str, src_table.ident, where_str, keys_str
+            );
+            args.add(batch - 1);
+            let upto = match sqlx::query_with(&sql, args)
+                .fetch_optional(&**src_pool)
+                .await?
+            {
+                Some(row) => Some(row_keys(&row, keys.len())?),
+                None => None,
+            };
+            let (where_str, args) =
+                batch_where(where_builder, &keys_str, after.as_deref(), upto.as_deref());
+            let sql = format!(
+                "INSERT IGNORE INTO {}({}) SELECT {} FROM {}{}",
+                dst_table.ident, columns, columns, src_table.ident, where_str
+            );
+            let r = sqlx::query_with(&sql, args).execute(&**dst_pool).await?;
+            stat.rows += r.rows_affected();
+            upto
+        } else {
+            let (where_str, mut args) =
+                batch_where(where_builder, &keys_str, after.as_deref(), None);
+            let sql = format!(
+                "SELECT {},{} FROM {}{} ORDER BY {} LIMIT ?",
+                keys_str, columns, src_table.ident, where_str, keys_str
+            );
+            args.add(batch);
+            let rows = sqlx::query_with(&sql, args).fetch_all(&**src_pool).await?;
+            if !rows.is_empty() {
+                let mut args = MySqlArguments::default();
+                for row in rows.iter() {
+                    for i in keys.len()..row.columns().len() {
+                        CopyValue::decode(row, i)?.add_to(&mut args);
+                    }
+                }
+                let marks = format!(
+                    "({})",
+                    vec!["?"; rows[0].columns().len() - keys.len()].join(",")
+                );
+                let sql = format!(
+                    "INSERT IGNORE INTO {}({}) VALUES {}",
+                    dst_table.ident,
+                    columns,
+                    vec![marks; rows.len()].join(",")
+                );
+                let r = sqlx::query_with(&sql, args).execute(&**dst_pool).await?;
+                stat.rows += r.rows_affected();
+            }
+            match rows.last() {
+                Some(row) if rows.len() as u32 == batch => Some(row_keys(row, keys.len())?),
+                _ => None,
+            }
+        };
+        stat.batches += 1;
+        stat.elapsed = start.elapsed();
+        progress(&stat);
+        match last {
+            Some(last) => after = Some(last),
+            None => break,
+        }
+    }
+    Ok(stat)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use sqlx::MySqlPool;
+
+    use super::{batch_where, copy_rows, copy_rows_with, cross_server_batch, CopyValue, TableRef};
+    use crate::mysqlx::sql_builder::WhereArgsBuilder;
+    use crate::mysqlx_test_pool::test_db_guard;
+
+    #[test]
+    fn test_batch_where() {
+        let keys = "`code`,`datetime`";
+        let after = [
+            CopyValue::Text("ag2412".into()),
+            CopyValue::Date(NaiveDate::from_ymd_opt(2024, 10, 8).unwrap()),
+        ];
+        let empty = WhereArgsBuilder::default();
+        assert_eq!(batch_where(&empty, keys, None, None).0, "");
+        assert_eq!(
+            batch_where(&empty, keys, Some(&after), Some(&after)).0,
+            " WHERE (`code`,`datetime`)>(?,?) AND (`code`,`datetime`)<=(?,?)"
+        );
+        let mut where_builder = WhereArgsBuilder::default();
+        where_builder.add("period", 1);
+        assert_eq!(
+            batch_where(&where_builder, keys, Some(&after), None).0,
+            " WHERE `period`=? AND (`code`,`datetime`)>(?,?)"
+        );
+
+        assert_eq!(TableRef::parse("hqdb.tbl").unwrap().ident, "`hqdb`.`tbl`");
+        assert!(TableRef::parse("tbl").is_err());
+        assert!(TableRef::parse("hqdb.tbl`x").is_err());
+    }
+
+    #[test]
+    fn test_cross_server_batch() {
+        assert_eq!(cross_server_batch(0, 3), 1);
+        assert_eq!(cross_server_batch(1000, 3), 1000);
+        assert_eq!(cross_server_batch(100_000, 3), 21845);
+        assert_eq!(cross_server_batch(100_000, 70_000), 1);
+    }
+
+    async fn create_tables(pool: &MySqlPool, src: &str, dst: &str) {
+        for sql in [
+            format!("CREATE TABLE {} (id INT PRIMARY KEY, v VARCHAR(8), d DECIMAL(10,2))", src),
+            format!("CREATE TABLE {} LIKE {}", dst, src),
+            format!(
+                "INSERT INTO {} VALUES (1,'a',1.5),(2,NULL,2),(3,'c',3),(4,'d',4),(5,'e',5)",
+                src
+            ),
+            format!("INSERT INTO {} VALUES (1,'a',1.5)", dst),
+        ] {
+            sqlx::query(&sql).execute(pool).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_copy_rows() {
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        let (src, dst) = (
+            format!("{}.tmp_copy_src", guard.db()),
+            format!("{}.tmp_copy_dst", guard.db()),
+        );
+        create_tables(&pool, &guard.table("tmp_copy_src"), &guard.table("tmp_copy_dst")).await;
+        let mut where_builder = WhereArgsBuilder::default();
+        where_builder.add_str("`id`<=4");
+        let mut batches = 0;
+        let stat = copy_rows((&pool, &src), (&pool, &dst), &where_builder, 2, |v| {
+            batches = v.batches
+        })
+        .await
+        .unwrap();
+        assert!(stat.same_server);
+        assert_eq!(stat.rows, 3);
+        assert_eq!(batches, stat.batches);
+        let sql = format!("SELECT COUNT(*) FROM {}", guard.table("tmp_copy_dst"));
+        let (count,) = sqlx::query_as::<_, (i64,)>(&sql)
+            .fetch_one(&*pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 4);
+        guard.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn test_copy_rows_cross_server() {
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        let (src, dst) = (
+            format!("{}.tmp_copy_src", guard.db()),
+            format!("{}.tmp_copy_dst", guard.db()),
+        );
+        create_tables(&pool, &guard.table("tmp_copy_src"), &guard.table("tmp_copy_dst")).await;
+        let stat = copy_rows_with(
+            (&pool, &src),
+            (&pool, &dst),
+            &WhereArgsBuilder::default(),
+            2,
+            false,
+            |_| {},
+        )
+        .await
+        .unwrap();
+        assert!(!stat.same_server);
+        assert_eq!(stat.rows, 4);
+        assert_eq!(stat.batches, 3);
+        let sql = format!(
+            "SELECT CAST(d AS CHAR) FROM {} WHERE v IS NULL",
+            guard.table("tmp_copy_dst")
+        );
+        let (d,) = sqlx::query_as::<_, (String,)>(&sql)
+            .fetch_one(&*pool)
+            .await
+            .unwrap();
+        assert_eq!(d, "2.00");
+        guard.cleanup().await;
+    }
+}
@@ -0,0 +1,96 @@
+//! COUNT/EXISTS查询, 表名校验后拼接到SQL中
+use sqlx::mysql::MySqlArguments;
+use sqlx::MySqlPool;
+
+use crate::sql_ident::{table_ident, IdentError};
+
+/// where_fragment: 不带WHERE的条件, 如`Code=? AND Date>=?`, 为空时不加条件
+fn count_sql(table: &str, where_fragment: &str) -> Result<String, IdentError> {
+    let mut sql = format!("SELECT COUNT(*) FROM {}", table_ident(table)?);
+    if !where_fragment.trim().is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(where_fragment);
+    }
+    Ok(sql)
+}
+
+/// 满足条件的记录数
+///
+/// table: `db.tbl`或`tbl`, 会校验名称
+pub async fn count(
+    pool: &MySqlPool,
+    table: &str,
+    where_fragment: &str,
+    args: MySqlArguments,
+) -> Result<u64, sqlx::Error> {
+    let sql = count_sql(table, where_fragment)?;
+    let (count,) = sqlx::query_as_with::<_, (i64,), _>(&sql, args)
+        .fetch_one(pool)
+        .await?;
+    Ok(count as u64)
+}
+
+/// 是否存在满足条件的记录, 找到一条即返回
+pub async fn exists(
+    pool: &MySqlPool,
+    table: &str,
+    where_fragment: &str,
+    args: MySqlArguments,
+) -> Result<bool, sqlx::Error> {
+    let inner = count_sql(table, where_fragment)?.replacen("COUNT(*)", "1", 1);
+    let sql = format!("SELECT EXISTS({} LIMIT 1)", inner);
+    let (exists,) = sqlx::query_as_with::<_, (i64,), _>(&sql, args)
+        .fetch_one(pool)
+        .await?;
+    Ok(exists == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::mysql::MySqlArguments;
+    use sqlx::Arguments;
+
+    use super::{count, count_sql, exists};
+    use crate::mysqlx::exec::exec_sql;
+    use crate::mysqlx_test_pool::test_db_guard;
+    use crate::sql_ident::IdentError;
+
+    #[test]
+    fn test_count_sql() {
+        assert_eq!(
+            count_sql("basedata.tbl_time_range", "Breed=?").unwrap(),
+            "SELECT COUNT(*) FROM `basedata`.`tbl_time_range` WHERE Breed=?"
+        );
+        assert_eq!(count_sql("tbl", " ").unwrap(), "SELECT COUNT(*) FROM `tbl`");
+        assert!(matches!(
+            count_sql("tbl; DROP TABLE x", ""),
+            Err(IdentError::Invalid(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_count_exists() {
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        let table = format!("{}.tbl_time_range", guard.db());
+        exec_sql(
+            &pool,
+            "CREATE TABLE `tbl_time_range`(`Breed` varchar(8), `Seq` int, PRIMARY KEY (`Breed`, `Seq`))",
+        )
+        .await
+        .unwrap();
+        exec_sql(&pool, "INSERT INTO `tbl_time_range` VALUES ('agL9', 1), ('agL9', 2)")
+            .await
+            .unwrap();
+        let args = |breed: &str| {
+            let mut args = MySqlArguments::default();
+            args.add(breed.to_owned());
+            args
+        };
+        let n = count(&pool, &table, "Breed=?", args("agL9")).await.unwrap();
+        assert_eq!(n, 2);
+        assert!(exists(&pool, &table, "Breed=?", args("agL9")).await.unwrap());
+        assert!(!exists(&pool, &table, "Breed=?", args("cuL9")).await.unwrap());
+        guard.cleanup().await;
+    }
+}
@@ -0,0 +1,101 @@
+use super::batch_exec::SqlEntity;
+use super::sql_builder::InsertSqlArgsBuilder;
+
+/// 结构体的字段映射为表的列, 生成INSERT/REPLACE的SqlEntity
+/// 一般通过`impl_to_sql_entity!`实现
+pub trait ToSqlEntity {
+    /// 按列的顺序把字段加入builder
+    fn add_fields(&self, builder: &mut InsertSqlArgsBuilder<'static>);
+
+    fn sql_entity_insert(&self, key: &str, db: &str, tbl_name: &str) -> SqlEntity {
+        let mut builder = InsertSqlArgsBuilder::new(db, tbl_name);
+        self.add_fields(&mut builder);
+        let (sql, args) = builder.insert_sql_args();
+        SqlEntity::new(key, &sql, args)
+    }
+
+    fn sql_entity_replace(&self, key: &str, db: &str, tbl_name: &str) -> SqlEntity {
+        let mut builder = InsertSqlArgsBuilder::new(db, tbl_name);
+        self.add_fields(&mut builder);
+        let (sql, args) = builder.replace_sql_args();
+        SqlEntity::new(key, &sql, args)
+    }
+}
+
+/// 为结构体实现ToSqlEntity, 列名默认为字段名, 可以用`=> "列名"`指定
+/// ```ignore
+/// impl_to_sql_entity!(KLineItem {
+///     code,
+///     datetime,
+///     total_volume => "TotalVolume",
+/// });
+/// ```
+#[macro_export]
+macro_rules! impl_to_sql_entity {
+    ($ty:ty { $($field:ident $(=> $column:literal)?),* $(,)? }) => {
+        impl $crate::mysqlx::entity::ToSqlEntity for $ty {
+            fn add_fields(
+                &self,
+                builder: &mut $crate::mysqlx::sql_builder::InsertSqlArgsBuilder<'static>,
+            ) {
+                $(
+                    builder.add(
+                        $crate::impl_to_sql_entity!(@column $field $($column)?),
+                        &self.$field,
+                    );
+                )*
+            }
+        }
+    };
+    (@column $field:ident) => {
+        stringify!($field)
+    };
+    (@column $field:ident $column:literal) => {
+        $column
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+    use rust_decimal::Decimal;
+
+    use super::ToSqlEntity;
+
+    struct Quote {
+        code:         String,
+        datetime:     NaiveDateTime,
+        price:        Decimal,
+        total_volume: i64,
+        remark:       Option<String>,
+    }
+
+    impl_to_sql_entity!(Quote {
+        code,
+        datetime,
+        price,
+        total_volume => "TotalVolume",
+        remark,
+    });
+
+    #[test]
+    fn test_sql_entity() {
+        let quote = Quote {
+            code:         "ag2408".to_string(),
+            datetime:     NaiveDateTime::default(),
+            price:        Decimal::from(7500),
+            total_volume: 10,
+            remark:       None,
+        };
+        let entity = quote.sql_entity_replace("k", "hqdb", "tbl_quote");
+        println!("{}", entity);
+        assert_eq!(
+            entity.sql(),
+            "REPLACE INTO `hqdb`.`tbl_quote`(`code`,`datetime`,`price`,`TotalVolume`,`remark`) VALUES (?,?,?,?,?)"
+        );
+        let entity = quote.sql_entity_insert("", "hqdb", "tbl_quote");
+        assert!(entity
+            .sql()
+            .starts_with("INSERT INTO `hqdb`.`tbl_quote`(`code`"));
+    }
+}
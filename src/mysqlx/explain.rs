@@ -0,0 +1,205 @@
+//! 查询计划(EXPLAIN), 用于检查模板生成的查询是否用到了索引
+use log::warn;
+use sqlx::mysql::{MySqlArguments, MySqlRow};
+use sqlx::{MySqlPool, Row};
+
+/// 全表扫描的预估行数超过这个值时打印警告
+pub const FULL_SCAN_WARN_ROWS: u64 = 10000;
+
+/// EXPLAIN的一行
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExplainRow {
+    pub id:            Option<i64>,
+    pub select_type:   String,
+    pub table:         Option<String>,
+    /// 访问类型: const, eq_ref, ref, range, index, ALL...
+    pub access_type:   Option<String>,
+    pub possible_keys: Vec<String>,
+    /// 使用的索引
+    pub key:           Option<String>,
+    /// 预估扫描行数
+    pub rows:          Option<u64>,
+    pub filtered:      Option<f64>,
+    pub extra:         Option<String>,
+}
+
+impl ExplainRow {
+    /// 全表扫描
+    pub fn is_full_scan(&self) -> bool {
+        self.access_type.as_deref() == Some("ALL")
+    }
+
+    fn from_row(row: &MySqlRow) -> Result<ExplainRow, sqlx::Error> {
+        let keys = |v: Option<String>| {
+            v.map(|v| v.split(',').map(|v| v.to_owned()).collect())
+                .unwrap_or_default()
+        };
+        Ok(ExplainRow {
+            id:            int(row, "id")?,
+            select_type:   row.try_get("select_type")?,
+            table:         row.try_get("table")?,
+            access_type:   row.try_get("type")?,
+            possible_keys: keys(row.try_get("possible_keys")?),
+            key:           row.try_get("key")?,
+            rows:          int(row, "rows")?.map(|v| v.max(0) as u64),
+            filtered:      float(row, "filtered")?,
+            extra:         row.try_get("Extra")?,
+        })
+    }
+}
+
+/// 各版本的列类型不同(有无符号, FLOAT/DOUBLE/DECIMAL)
+fn int(row: &MySqlRow, column: &str) -> Result<Option<i64>, sqlx::Error> {
+    row.try_get::<Option<i64>, _>(column).or_else(|_| {
+        row.try_get::<Option<u64>, _>(column)
+            .map(|v| v.map(|v| v as i64))
+    })
+}
+
+fn float(row: &MySqlRow, column: &str) -> Result<Option<f64>, sqlx::Error> {
+    row.try_get::<Option<f64>, _>(column).or_else(|_| {
+        row.try_get::<Option<f32>, _>(column)
+            .map(|v| v.map(f64::from))
+    })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExplainReport {
+    pub sql:  String,
+    pub rows: Vec<ExplainRow>,
+    /// FORMAT=JSON的结果, 不支持时为None
+    pub json: Option<String>,
+}
+
+impl ExplainReport {
+    /// 预估行数不小于min_rows的全表扫描
+    pub fn full_scans(&self, min_rows: u64) -> Vec<&ExplainRow> {
+        self.rows
+            .iter()
+            .filter(|v| v.is_full_scan() && v.rows.unwrap_or_default() >= min_rows)
+            .collect()
+    }
+
+    /// 使用到的索引
+    pub fn used_keys(&self) -> Vec<&str> {
+        self.rows.iter().filter_map(|v| v.key.as_deref()).collect()
+    }
+
+    /// 各表预估扫描行数的乘积, 用来粗略比较查询的代价
+    pub fn estimated_rows(&self) -> u64 {
+        self.rows
+            .iter()
+            .filter_map(|v| v.rows)
+            .fold(1u64, |acc, v| acc.saturating_mul(v.max(1)))
+    }
+}
+
+impl std::fmt::Display for ExplainReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.sql)?;
+        for row in self.rows.iter() {
+            writeln!(
+                f,
+                "  {:<12} type:{:<6} key:{:<20} rows:{:>10} {}",
+                row.table.as_deref().unwrap_or("-"),
+                row.access_type.as_deref().unwrap_or("-"),
+                row.key.as_deref().unwrap_or("-"),
+                row.rows.unwrap_or_default(),
+                row.extra.as_deref().unwrap_or_default()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// 执行EXPLAIN, 全表扫描的预估行数不小于`FULL_SCAN_WARN_ROWS`时打印警告
+pub async fn explain(
+    pool: &MySqlPool,
+    sql: &str,
+    args: MySqlArguments,
+) -> Result<ExplainReport, sqlx::Error> {
+    let json = sqlx::query_with(&format!("EXPLAIN FORMAT=JSON {}", sql), args.clone())
+        .fetch_one(pool)
+        .await
+        .and_then(|row| row.try_get::<String, _>(0))
+        .ok();
+    let rows = sqlx::query_with(&format!("EXPLAIN {}", sql), args)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(ExplainRow::from_row)
+        .collect::<Result<Vec<_>, _>>()?;
+    let report = ExplainReport {
+        sql: sql.to_owned(),
+        rows,
+        json,
+    };
+    for row in report.full_scans(FULL_SCAN_WARN_ROWS) {
+        warn!(
+            "full scan on {} (rows: {}): {}",
+            row.table.as_deref().unwrap_or("-"),
+            row.rows.unwrap_or_default(),
+            sql
+        );
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::mysql::MySqlArguments;
+    use sqlx::Arguments;
+
+    use super::{explain, ExplainReport, ExplainRow};
+    use crate::mysqlx::exec::exec_sql;
+    use crate::mysqlx_test_pool::test_db_guard;
+
+    #[test]
+    fn test_report() {
+        let report = ExplainReport {
+            sql:  "SELECT * FROM a JOIN b".to_owned(),
+            rows: vec![
+                ExplainRow {
+                    table: Some("a".to_owned()),
+                    access_type: Some("ALL".to_owned()),
+                    rows: Some(20000),
+                    ..Default::default()
+                },
+                ExplainRow {
+                    table: Some("b".to_owned()),
+                    access_type: Some("ref".to_owned()),
+                    key: Some("idx_code".to_owned()),
+                    rows: Some(3),
+                    ..Default::default()
+                },
+            ],
+            json: None,
+        };
+        println!("{}", report);
+        assert_eq!(report.full_scans(10000).len(), 1);
+        assert!(report.full_scans(30000).is_empty());
+        assert_eq!(report.used_keys(), ["idx_code"]);
+        assert_eq!(report.estimated_rows(), 60000);
+    }
+
+    #[tokio::test]
+    async fn test_explain() {
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        exec_sql(
+            &pool,
+            "CREATE TABLE `tbl_time_range`(`Breed` varchar(8), `Seq` int, PRIMARY KEY (`Breed`, `Seq`))",
+        )
+        .await
+        .unwrap();
+        let mut args = MySqlArguments::default();
+        args.add("agL9");
+        let report = explain(&pool, "SELECT * FROM tbl_time_range WHERE Breed=?", args)
+            .await
+            .unwrap();
+        println!("{}", report);
+        println!("{:?}", report.json);
+        assert_eq!(report.rows.len(), 1);
+        guard.cleanup().await;
+    }
+}
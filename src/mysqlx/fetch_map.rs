@@ -0,0 +1,87 @@
+//! 查询结果直接收集到以某列为key的map中, 不用先fetch_all再转换
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use futures_util::TryStreamExt;
+use sqlx::mysql::{MySqlArguments, MySqlRow};
+use sqlx::{FromRow, MySqlPool};
+
+/// 每行按key_fn取key收集到map中, map可以是HashMap, BTreeMap, IndexMap等
+///
+/// key重复时后面的行覆盖前面的行
+pub async fn fetch_map<M, K, V, F>(
+    pool: &MySqlPool,
+    sql: &str,
+    args: MySqlArguments,
+    key_fn: F,
+) -> Result<M, sqlx::Error>
+where
+    M: Default + Extend<(K, V)>,
+    V: for<'r> FromRow<'r, MySqlRow> + Send + Unpin,
+    F: Fn(&V) -> K,
+{
+    let mut rows = sqlx::query_as_with::<_, V, _>(sql, args).fetch(pool);
+    let mut map = M::default();
+    while let Some(v) = rows.try_next().await? {
+        map.extend(Some((key_fn(&v), v)));
+    }
+    Ok(map)
+}
+
+/// 每行按key_fn分组, 组内保持查询结果的顺序
+pub async fn fetch_grouped<K, V, F>(
+    pool: &MySqlPool,
+    sql: &str,
+    args: MySqlArguments,
+    key_fn: F,
+) -> Result<HashMap<K, Vec<V>>, sqlx::Error>
+where
+    K: Eq + Hash,
+    V: for<'r> FromRow<'r, MySqlRow> + Send + Unpin,
+    F: Fn(&V) -> K,
+{
+    let mut rows = sqlx::query_as_with::<_, V, _>(sql, args).fetch(pool);
+    let mut map = HashMap::<K, Vec<V>>::new();
+    while let Some(v) = rows.try_next().await? {
+        map.entry(key_fn(&v)).or_default().push(v);
+    }
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use chrono::NaiveDate;
+    use sqlx::mysql::MySqlArguments;
+    use sqlx::Arguments;
+
+    use super::{fetch_grouped, fetch_map};
+    use crate::mysqlx::MySqlPools;
+    use crate::mysqlx_test_pool::init_test_mysql_pools;
+
+    #[tokio::test]
+    async fn test_fetch_map() {
+        init_test_mysql_pools();
+        let pool = MySqlPools::pool_default().await.unwrap();
+        let sql = "SELECT Breed,TDDay FROM basedata.tbl_time_range";
+        let map: BTreeMap<String, (String, NaiveDate)> = fetch_map(
+            &pool,
+            sql,
+            MySqlArguments::default(),
+            |v: &(String, NaiveDate)| v.0.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(!map.is_empty());
+
+        let mut args = MySqlArguments::default();
+        args.add("agL9");
+        let sql = "SELECT Breed,TDDay FROM basedata.tbl_time_range WHERE Breed=?";
+        let grouped: HashMap<NaiveDate, Vec<(String, NaiveDate)>> =
+            fetch_grouped(&pool, sql, args, |v: &(String, NaiveDate)| v.1)
+                .await
+                .unwrap();
+        assert!(grouped.values().flatten().all(|v| v.0 == "agL9"));
+    }
+}
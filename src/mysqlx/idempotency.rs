@@ -0,0 +1,278 @@
+//! 批量执行的幂等记录: 记录已执行的SqlEntity的key, 重放同一批次(崩溃恢复, 重试)时跳过已执行的
+//!
+//! MySQL表中的记录和批次在同一个事务中写入, 执行和记录是原子的;
+//! Redis在事务提交后写入, 提交后写入前崩溃时重放会再执行一次
+use std::collections::HashSet;
+#[cfg(feature = "redis")]
+use std::sync::Arc;
+#[cfg(feature = "redis")]
+use std::time::Duration;
+
+use sqlx::{Arguments, MySql, MySqlConnection, MySqlPool, Transaction};
+
+use super::batch_exec::BatchExecError;
+use crate::sql_ident::{ident, IdentError};
+
+/// 每次查询, 写入的key数量
+const CHUNK_SIZE: usize = 500;
+
+#[derive(Debug, Clone)]
+pub enum IdempotencyStore {
+    /// 表名为校验后的`` `db`.`tbl` ``
+    MySql { table: String },
+    /// key为`{prefix}:{key}`, ttl后过期
+    #[cfg(feature = "redis")]
+    Redis {
+        client: Arc<redis::Client>,
+        prefix: String,
+        ttl:    Duration,
+    },
+}
+
+impl IdempotencyStore {
+    /// table: `db.tbl`或`tbl`, 需要先调用`create_table`
+    pub fn mysql(table: &str) -> Result<IdempotencyStore, IdentError> {
+        let table = match table.split_once('.') {
+            Some((db, tbl)) => ident(db, tbl)?,
+            None => ident("", table)?,
+        };
+        Ok(IdempotencyStore::MySql { table })
+    }
+
+    #[cfg(feature = "redis")]
+    pub fn redis(client: Arc<redis::Client>, prefix: &str, ttl: Duration) -> IdempotencyStore {
+        IdempotencyStore::Redis {
+            client,
+            prefix: prefix.to_owned(),
+            ttl,
+        }
+    }
+
+    /// 创建记录的表, Redis时不处理
+    pub async fn create_table(&self, pool: &MySqlPool) -> Result<(), sqlx::Error> {
+        let table = match self {
+            IdempotencyStore::MySql { table } => table,
+            #[cfg(feature = "redis")]
+            IdempotencyStore::Redis { .. } => return Ok(()),
+        };
+        let sql = format!(
+            "CREATE TABLE IF NOT EXISTS {} (\n  `idem_key` VARCHAR(191) NOT NULL,\n  `created_at` \
+             DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6),\n  PRIMARY KEY(`idem_key`)\n) \
+             ENGINE=INNODB DEFAULT CHARSET=utf8mb4;",
+            table
+        );
+        sqlx::query(&sql).execute(pool).await?;
+        Ok(())
+    }
+
+    /// 删除created_at早于before的记录, 返回删除的数量, Redis按ttl过期不处理
+    pub async fn purge_before(
+        &self,
+        pool: &MySqlPool,
+        before: chrono::NaiveDateTime,
+    ) -> Result<u64, sqlx::Error> {
+        match self {
+            IdempotencyStore::MySql { table } => {
+                let sql = format!("DELETE FROM {} WHERE `created_at`<?", table);
+                let r = sqlx::query(&sql).bind(before).execute(pool).await?;
+                Ok(r.rows_affected())
+            },
+            #[cfg(feature = "redis")]
+            IdempotencyStore::Redis { .. } => Ok(0),
+        }
+    }
+
+    /// keys中已执行的key
+    ///
+    /// MySQL在批次的事务中查询, transaction为None时开启; Redis不开启事务
+    pub(crate) async fn applied(
+        &self,
+        pool: &MySqlPool,
+        transaction: &mut Option<Transaction<'static, MySql>>,
+        keys: &[&str],
+    ) -> Result<HashSet<String>, BatchExecError> {
+        let mut applied = HashSet::new();
+        if keys.is_empty() {
+            return Ok(applied);
+        }
+        match self {
+            IdempotencyStore::MySql { table } => {
+                if transaction.is_none() {
+                    *transaction = Some(pool.begin().await?);
+                }
+                let con = &mut **transaction.as_mut().unwrap();
+                for chunk in keys.chunks(CHUNK_SIZE) {
+                    let sql = format!(
+                        "SELECT `idem_key` FROM {} WHERE `idem_key` IN ({})",
+                        table,
+                        vec!["?"; chunk.len()].join(",")
+                    );
+                    let mut query = sqlx::query_as::<_, (String,)>(&sql);
+                    for key in chunk {
+                        query = query.bind(*key);
+                    }
+                    let rows = query.fetch_all(&mut *con).await?;
+                    applied.extend(rows.into_iter().map(|(v,)| v));
+                }
+            },
+            #[cfg(feature = "redis")]
+            IdempotencyStore::Redis { client, prefix, .. } => {
+                let mut con = client.get_multiplexed_tokio_connection().await?;
+                for chunk in keys.chunks(CHUNK_SIZE) {
+                    let mut cmd = redis::cmd("MGET");
+                    for key in chunk {
+                        cmd.arg(format!("{}:{}", prefix, key));
+                    }
+                    let values: Vec<Option<String>> = cmd.query_async(&mut con).await?;
+                    for (key, value) in chunk.iter().zip(values) {
+                        if value.is_some() {
+                            applied.insert((*key).to_owned());
+                        }
+                    }
+                }
+            },
+        }
+        Ok(applied)
+    }
+
+    /// 在批次的事务中记录, 只有MySQL需要
+    pub(crate) async fn record_in_tx(
+        &self,
+        con: &mut MySqlConnection,
+        keys: &[&str],
+    ) -> Result<(), BatchExecError> {
+        let table = match self {
+            IdempotencyStore::MySql { table } => table,
+            #[cfg(feature = "redis")]
+            IdempotencyStore::Redis { .. } => return Ok(()),
+        };
+        for chunk in keys.chunks(CHUNK_SIZE) {
+            let sql = format!(
+                "INSERT INTO {}(`idem_key`) VALUES {}",
+                table,
+                vec!["(?)"; chunk.len()].join(",")
+            );
+            let mut args = sqlx::mysql::MySqlArguments::default();
+            for key in chunk {
+                args.add(*key);
+            }
+            sqlx::query_with(&sql, args).execute(&mut *con).await?;
+        }
+        Ok(())
+    }
+
+    /// 事务提交后记录, 只有Redis需要
+    pub(crate) async fn record_after_commit(&self, keys: &[&str]) -> Result<(), BatchExecError> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        match self {
+            IdempotencyStore::MySql { .. } => {},
+            #[cfg(feature = "redis")]
+            IdempotencyStore::Redis {
+                client,
+                prefix,
+                ttl,
+            } => {
+                let mut con = client.get_multiplexed_tokio_connection().await?;
+                let mut pipe = redis::pipe();
+                for key in keys {
+                    pipe.cmd("SET")
+                        .arg(format!("{}:{}", prefix, key))
+                        .arg(1)
+                        .arg("EX")
+                        .arg(ttl.as_secs().max(1))
+                        .ignore();
+                }
+                pipe.query_async::<_, ()>(&mut con).await?;
+            },
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::mysql::MySqlArguments;
+    use sqlx::Arguments;
+
+    use super::IdempotencyStore;
+    use crate::mysqlx::batch_exec::{BatchExec, SqlEntity};
+    use crate::mysqlx::exec::exec_sql;
+    use crate::mysqlx_test_pool::test_db_guard;
+    use crate::sql_ident::IdentError;
+
+    #[test]
+    fn test_mysql_store() {
+        assert!(matches!(
+            IdempotencyStore::mysql("tmp.tbl_idem").unwrap(),
+            IdempotencyStore::MySql { table } if table == "`tmp`.`tbl_idem`"
+        ));
+        assert!(matches!(
+            IdempotencyStore::mysql("tmp.tbl;x"),
+            Err(IdentError::Invalid(_))
+        ));
+    }
+
+    #[cfg(feature = "redis")]
+    #[tokio::test]
+    async fn test_redis_dry_run() {
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        use sqlx::mysql::MySqlPoolOptions;
+
+        use crate::mysqlx::batch_exec::AuditMode;
+
+        // 只配置Redis时DryRun不连接数据库, 连接不上的连接池也能执行
+        let pool = MySqlPoolOptions::new()
+            .connect_lazy("mysql://root@127.0.0.1:1/tmp")
+            .unwrap();
+        let client = redis::Client::open("redis://127.0.0.1:1").unwrap();
+        let store = IdempotencyStore::redis(Arc::new(client), "idem", Duration::from_secs(60));
+        let mut be = BatchExec::new(Arc::new(pool), 0)
+            .with_idempotency(store)
+            .with_audit(AuditMode::DryRun);
+        be.add(SqlEntity::new("", "SELECT 1", MySqlArguments::default()));
+        let info = be.execute_all().await.unwrap();
+        assert!(info.is_dry_run());
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        let store = IdempotencyStore::mysql(&format!("{}.tbl_idem", guard.db())).unwrap();
+        store.create_table(&pool).await.unwrap();
+        exec_sql(
+            &pool,
+            "CREATE TABLE `tbl_tmp`(`id` int PRIMARY KEY, `v_v` varchar(32))",
+        )
+        .await
+        .unwrap();
+
+        let batch = |keys: &[&str]| {
+            let mut be = BatchExec::new(pool.clone(), 10).with_idempotency(store.clone());
+            for (i, key) in keys.iter().enumerate() {
+                let mut args = MySqlArguments::default();
+                args.add(format!("v-{}", key));
+                args.add(100 + i as i32);
+                let sql = "REPLACE INTO tbl_tmp(v_v,id) VALUES(?,?)";
+                be.add(SqlEntity::new(key, sql, args));
+            }
+            be
+        };
+        let info = batch(&["replay-1", "replay-2"])
+            .execute_all()
+            .await
+            .unwrap();
+        assert!(info.skipped().is_empty());
+        let info = batch(&["replay-1", "replay-2", "replay-3"])
+            .execute_all()
+            .await
+            .unwrap();
+        println!("{}", info);
+        assert_eq!(info.skipped(), ["replay-1", "replay-2"]);
+        guard.cleanup().await;
+    }
+}
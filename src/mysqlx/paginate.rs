@@ -0,0 +1,222 @@
+use std::marker::PhantomData;
+
+use futures_util::TryStreamExt;
+use sqlx::mysql::{MySqlArguments, MySqlRow};
+use sqlx::{Arguments, Encode, FromRow, MySql, MySqlPool, Type};
+
+use super::sql_builder::WhereArgsBuilder;
+
+/// 分页结果
+/// total: 只有在开启`with_total`时才会查询, 为符合条件的总记录数(不含keyset条件)
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items:    Vec<T>,
+    pub has_more: bool,
+    pub total:    Option<u64>,
+}
+
+impl<T> Page<T> {
+    pub fn last(&self) -> Option<&T> {
+        self.items.last()
+    }
+}
+
+/// 分页查询
+/// select_sql: 不带WHERE, ORDER BY, LIMIT的查询语句, 如: SELECT a,b FROM `db`.`tbl`
+/// order_column: 排序字段, keyset分页时用于定位上一页的最后一条记录
+#[derive(Clone)]
+pub struct Paginator<T> {
+    select_sql:    String,
+    where_builder: WhereArgsBuilder,
+    order_column:  String,
+    desc:          bool,
+    page_size:     u32,
+    with_total:    bool,
+    _marker:       PhantomData<T>,
+}
+
+impl<T> Paginator<T>
+where
+    T: for<'r> FromRow<'r, MySqlRow> + Send + Unpin,
+{
+    pub fn new(select_sql: &str, order_column: &str, page_size: u32) -> Paginator<T> {
+        Paginator {
+            select_sql:    select_sql.to_owned(),
+            where_builder: WhereArgsBuilder::default(),
+            order_column:  order_column.to_owned(),
+            desc:          false,
+            page_size:     page_size.max(1),
+            with_total:    false,
+            _marker:       PhantomData,
+        }
+    }
+
+    pub fn where_args(mut self, where_builder: WhereArgsBuilder) -> Self {
+        self.where_builder = where_builder;
+        self
+    }
+
+    /// 按倒序分页
+    pub fn desc(mut self) -> Self {
+        self.desc = true;
+        self
+    }
+
+    /// 同时查询总记录数
+    pub fn with_total(mut self) -> Self {
+        self.with_total = true;
+        self
+    }
+
+    fn where_str(where_builder: &WhereArgsBuilder) -> (String, MySqlArguments) {
+        let (where_str, args) = where_builder.str_args();
+        if where_str.is_empty() {
+            (where_str, args)
+        } else {
+            (format!(" {}", where_str), args)
+        }
+    }
+
+    fn order_str(&self) -> String {
+        format!(
+            "ORDER BY `{}`{}",
+            self.order_column,
+            if self.desc { " DESC" } else { "" }
+        )
+    }
+
+    fn offset_sql_args(&self, offset: u64) -> (String, MySqlArguments) {
+        let (where_str, mut args) = Self::where_str(&self.where_builder);
+        let sql = format!(
+            "{}{} {} LIMIT ? OFFSET ?",
+            self.select_sql,
+            where_str,
+            self.order_str()
+        );
+        args.add(self.page_size + 1);
+        args.add(offset);
+        (sql, args)
+    }
+
+    fn keyset_sql_args<K>(&self, after: Option<K>) -> (String, MySqlArguments)
+    where
+        K: for<'q> Encode<'q, MySql> + Type<MySql> + Send,
+    {
+        let mut where_builder = self.where_builder.clone();
+        if let Some(after) = after {
+            let cmp = if self.desc { "<" } else { ">" };
+            where_builder.add_combine(&format!("`{}`{}?", self.order_column, cmp), after);
+        }
+        let (where_str, mut args) = Self::where_str(&where_builder);
+        let sql = format!(
+            "{}{} {} LIMIT ?",
+            self.select_sql,
+            where_str,
+            self.order_str()
+        );
+        args.add(self.page_size + 1);
+        (sql, args)
+    }
+
+    fn count_sql_args(&self) -> (String, MySqlArguments) {
+        let (where_str, args) = Self::where_str(&self.where_builder);
+        let sql = format!(
+            "SELECT COUNT(*) FROM ({}{}) AS T",
+            self.select_sql, where_str
+        );
+        (sql, args)
+    }
+
+    async fn total(&self, pool: &MySqlPool) -> Result<Option<u64>, sqlx::Error> {
+        if !self.with_total {
+            return Ok(None);
+        }
+        let (sql, args) = self.count_sql_args();
+        let (total,) = sqlx::query_as_with::<_, (i64,), _>(&sql, args)
+            .fetch_one(pool)
+            .await?;
+        Ok(Some(total as u64))
+    }
+
+    async fn fetch_page(
+        &self,
+        pool: &MySqlPool,
+        sql: &str,
+        args: MySqlArguments,
+    ) -> Result<Page<T>, sqlx::Error> {
+        let mut items = sqlx::query_as_with::<_, T, _>(sql, args)
+            .fetch(pool)
+            .try_collect::<Vec<_>>()
+            .await?;
+        let has_more = items.len() > self.page_size as usize;
+        items.truncate(self.page_size as usize);
+        let total = self.total(pool).await?;
+        Ok(Page {
+            items,
+            has_more,
+            total,
+        })
+    }
+
+    /// offset分页, page从0开始
+    pub async fn fetch_offset(&self, pool: &MySqlPool, page: u64) -> Result<Page<T>, sqlx::Error> {
+        let (sql, args) = self.offset_sql_args(page * self.page_size as u64);
+        self.fetch_page(pool, &sql, args).await
+    }
+
+    /// keyset分页, after为上一页最后一条记录的order_column值, None为第一页
+    pub async fn fetch_after<K>(
+        &self,
+        pool: &MySqlPool,
+        after: Option<K>,
+    ) -> Result<Page<T>, sqlx::Error>
+    where
+        K: for<'q> Encode<'q, MySql> + Type<MySql> + Send,
+    {
+        let (sql, args) = self.keyset_sql_args(after);
+        self.fetch_page(pool, &sql, args).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::Paginator;
+    use crate::mysqlx::sql_builder::WhereArgsBuilder;
+
+    #[test]
+    fn test_paginator_sql() {
+        let mut where_builder = WhereArgsBuilder::default();
+        where_builder.add("code", "agL9");
+        let paginator =
+            Paginator::<(String,)>::new("SELECT code FROM `hqdb`.`tbl_code_agL9`", "datetime", 10)
+                .where_args(where_builder)
+                .with_total();
+
+        let (sql, _) = paginator.offset_sql_args(20);
+        println!("{}", sql);
+        assert_eq!(
+            sql,
+            "SELECT code FROM `hqdb`.`tbl_code_agL9` WHERE `code`=? ORDER BY `datetime` LIMIT ? OFFSET ?"
+        );
+
+        let after = NaiveDate::from_ymd_opt(2023, 6, 21)
+            .unwrap()
+            .and_hms_opt(9, 1, 0)
+            .unwrap();
+        let (sql, _) = paginator.clone().desc().keyset_sql_args(Some(after));
+        println!("{}", sql);
+        assert_eq!(
+            sql,
+            "SELECT code FROM `hqdb`.`tbl_code_agL9` WHERE `code`=? AND `datetime`<? ORDER BY `datetime` DESC LIMIT ?"
+        );
+
+        let (sql, _) = paginator.count_sql_args();
+        println!("{}", sql);
+        assert_eq!(
+            sql,
+            "SELECT COUNT(*) FROM (SELECT code FROM `hqdb`.`tbl_code_agL9` WHERE `code`=?) AS T"
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use log::info;
+use tokio::task::JoinHandle;
+
+use super::MySqlPools;
+
+/// 连接池的指标
+/// checkouts, connects: 连接池的回调统计, 包括`&*pool`执行语句, `begin`等所有途径
+/// acquires, acquire_timeouts, acquire_wait_*: sqlx不提供等待时间, 只统计通过`MySqlPools::acquire`获取的连接
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolMetrics {
+    pub key:                String,
+    pub size:               u32,
+    pub idle:               usize,
+    pub max_conns:          u32,
+    /// 取出空闲连接的次数
+    pub checkouts:          u64,
+    /// 新建连接的次数
+    pub connects:           u64,
+    pub acquires:           u64,
+    pub acquire_timeouts:   u64,
+    pub acquire_wait_total: Duration,
+    pub acquire_wait_max:   Duration,
+}
+
+impl std::fmt::Display for PoolMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] size:{}/{} idle:{} checkouts:{} connects:{} acquires:{} timeouts:{} wait_avg:{:.3?} wait_max:{:.3?}",
+            self.key,
+            self.size,
+            self.max_conns,
+            self.idle,
+            self.checkouts,
+            self.connects,
+            self.acquires,
+            self.acquire_timeouts,
+            self.acquire_wait_avg(),
+            self.acquire_wait_max
+        )
+    }
+}
+
+impl PoolMetrics {
+    pub fn acquire_wait_avg(&self) -> Duration {
+        if self.acquires == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos((self.acquire_wait_total.as_nanos() / self.acquires as u128) as u64)
+    }
+}
+
+/// 接收连接池指标, 闭包`Fn(&PoolMetrics)`也实现了这个trait
+pub trait PoolMetricsRecorder: Send + Sync {
+    fn record(&self, metrics: &PoolMetrics);
+}
+
+impl<F> PoolMetricsRecorder for F
+where
+    F: Fn(&PoolMetrics) + Send + Sync,
+{
+    fn record(&self, metrics: &PoolMetrics) {
+        self(metrics)
+    }
+}
+
+/// 默认的recorder, 用日志输出
+#[derive(Debug, Default)]
+pub struct LogPoolMetricsRecorder;
+
+impl PoolMetricsRecorder for LogPoolMetricsRecorder {
+    fn record(&self, metrics: &PoolMetrics) {
+        info!(target: "mysqlx::pool_metrics", "{}", metrics);
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct AcquireStats {
+    checkouts:        AtomicU64,
+    connects:         AtomicU64,
+    acquires:         AtomicU64,
+    timeouts:         AtomicU64,
+    wait_total_nanos: AtomicU64,
+    wait_max_nanos:   AtomicU64,
+}
+
+impl AcquireStats {
+    pub(crate) fn record_checkout(&self) {
+        self.checkouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_connect(&self) {
+        self.connects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record(&self, wait: Duration, timeout: bool) {
+        let nanos = wait.as_nanos() as u64;
+        if timeout {
+            self.timeouts.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.acquires.fetch_add(1, Ordering::Relaxed);
+            self.wait_total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        }
+        self.wait_max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    fn fill(&self, metrics: &mut PoolMetrics) {
+        metrics.checkouts = self.checkouts.load(Ordering::Relaxed);
+        metrics.connects = self.connects.load(Ordering::Relaxed);
+        metrics.acquires = self.acquires.load(Ordering::Relaxed);
+        metrics.acquire_timeouts = self.timeouts.load(Ordering::Relaxed);
+        metrics.acquire_wait_total =
+            Duration::from_nanos(self.wait_total_nanos.load(Ordering::Relaxed));
+        metrics.acquire_wait_max =
+            Duration::from_nanos(self.wait_max_nanos.load(Ordering::Relaxed));
+    }
+}
+
+static ACQUIRE_STATS: OnceLock<Mutex<HashMap<String, Arc<AcquireStats>>>> = OnceLock::new();
+
+pub(crate) fn acquire_stats(key: &str) -> Arc<AcquireStats> {
+    ACQUIRE_STATS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .entry(key.to_owned())
+        .or_default()
+        .clone()
+}
+
+pub(crate) fn pool_metrics(key: &str, pool: &sqlx::MySqlPool) -> PoolMetrics {
+    let mut metrics = PoolMetrics {
+        key:                key.to_owned(),
+        size:               pool.size(),
+        idle:               pool.num_idle(),
+        max_conns:          pool.options().get_max_connections(),
+        checkouts:          0,
+        connects:           0,
+        acquires:           0,
+        acquire_timeouts:   0,
+        acquire_wait_total: Duration::ZERO,
+        acquire_wait_max:   Duration::ZERO,
+    };
+    acquire_stats(key).fill(&mut metrics);
+    metrics
+}
+
+/// 每隔interval把所有已创建连接池的指标交给recorder
+pub fn spawn_pool_metrics_reporter<R>(interval: Duration, recorder: R) -> JoinHandle<()>
+where
+    R: PoolMetricsRecorder + 'static,
+{
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            for metrics in MySqlPools::pool_metrics().await {
+                recorder.record(&metrics);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::{acquire_stats, spawn_pool_metrics_reporter, PoolMetrics, PoolMetricsRecorder};
+    use crate::mysqlx::MySqlPools;
+    use crate::mysqlx_test_pool::init_test_mysql_pools;
+
+    #[test]
+    fn test_acquire_stats() {
+        let stats = acquire_stats("test_acquire_stats");
+        stats.record(Duration::from_millis(10), false);
+        stats.record(Duration::from_millis(30), false);
+        stats.record(Duration::from_millis(50), true);
+        stats.record_checkout();
+        stats.record_connect();
+        let mut metrics = PoolMetrics {
+            key:                "test_acquire_stats".to_string(),
+            size:               2,
+            idle:               1,
+            max_conns:          5,
+            checkouts:          0,
+            connects:           0,
+            acquires:           0,
+            acquire_timeouts:   0,
+            acquire_wait_total: Duration::ZERO,
+            acquire_wait_max:   Duration::ZERO,
+        };
+        acquire_stats("test_acquire_stats").fill(&mut metrics);
+        println!("{}", metrics);
+        assert_eq!(metrics.checkouts, 1);
+        assert_eq!(metrics.connects, 1);
+        assert_eq!(metrics.acquires, 2);
+        assert_eq!(metrics.acquire_timeouts, 1);
+        assert_eq!(metrics.acquire_wait_avg(), Duration::from_millis(20));
+        assert_eq!(metrics.acquire_wait_max, Duration::from_millis(50));
+
+        // 不截断到u32
+        metrics.acquires = u32::MAX as u64 + 1;
+        metrics.acquire_wait_total = Duration::from_nanos(3 * (u32::MAX as u64 + 1));
+        assert_eq!(metrics.acquire_wait_avg(), Duration::from_nanos(3));
+
+        let records = Mutex::new(Vec::new());
+        let recorder = |metrics: &PoolMetrics| records.lock().unwrap().push(metrics.key.clone());
+        recorder.record(&metrics);
+        assert_eq!(records.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reporter() {
+        init_test_mysql_pools();
+        let _conn = MySqlPools::acquire_default().await.unwrap();
+        sqlx::query("SELECT 1")
+            .execute(&*MySqlPools::pool_default().await.unwrap())
+            .await
+            .unwrap();
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let records_clone = records.clone();
+        let handle =
+            spawn_pool_metrics_reporter(Duration::from_millis(50), move |metrics: &PoolMetrics| {
+                records_clone.lock().unwrap().push(metrics.clone())
+            });
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        handle.abort();
+        let records = records.lock().unwrap();
+        println!("{:?}", records);
+        assert!(records.iter().any(|v| v.acquires > 0));
+        assert!(records.iter().any(|v| v.checkouts + v.connects > 1));
+    }
+}
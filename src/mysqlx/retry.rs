@@ -0,0 +1,101 @@
+//! 临时错误的重试: 死锁, 锁等待超时, 连接断开, 获取连接超时, 等待时间由`Backoff`决定
+//!
+//! ```ignore
+//! let backoff = Backoff::exponential(Duration::from_millis(50)).with_max_attempts(3);
+//! let r = retry(backoff, || sqlx::query("UPDATE ...").execute(&*pool)).await?;
+//! ```
+use std::future::Future;
+
+use log::warn;
+use sqlx::mysql::MySqlDatabaseError;
+
+use crate::timer::Backoff;
+
+/// 死锁
+const ER_LOCK_DEADLOCK: u16 = 1213;
+/// 锁等待超时
+const ER_LOCK_WAIT_TIMEOUT: u16 = 1205;
+
+/// 重新执行可能成功的错误
+pub fn is_retryable(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut => true,
+        sqlx::Error::Database(e) => e
+            .try_downcast_ref::<MySqlDatabaseError>()
+            .is_some_and(|v| matches!(v.number(), ER_LOCK_DEADLOCK | ER_LOCK_WAIT_TIMEOUT)),
+        _ => false,
+    }
+}
+
+/// 执行f, 出现可重试的错误时按backoff等待后重新执行, 次数用完或其它错误时返回错误
+///
+/// f每次重新生成语句, 事务需要在f中整个重新执行
+pub async fn retry<T, F, Fut>(mut backoff: Backoff, mut f: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    loop {
+        match f().await {
+            Err(e) if is_retryable(&e) => match backoff.next() {
+                Some(delay) => {
+                    warn!("mysql retry {} after {:?}: {}", backoff.attempt(), delay, e);
+                    tokio::time::sleep(delay).await;
+                },
+                None => return Err(e),
+            },
+            r => return r,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::time::Duration;
+
+    use super::{is_retryable, retry};
+    use crate::timer::Backoff;
+
+    #[tokio::test]
+    async fn test_retry() {
+        assert!(is_retryable(&sqlx::Error::PoolTimedOut));
+        assert!(!is_retryable(&sqlx::Error::RowNotFound));
+
+        let backoff = Backoff::constant(Duration::from_millis(1)).with_max_attempts(3);
+        let mut calls = 0;
+        let r = retry(backoff.clone(), || {
+            calls += 1;
+            let n = calls;
+            async move {
+                if n < 3 {
+                    Err(sqlx::Error::Io(io::Error::other("reset")))
+                } else {
+                    Ok(n)
+                }
+            }
+        })
+        .await;
+        assert_eq!(r.unwrap(), 3);
+
+        // 次数用完
+        let mut calls = 0;
+        let r = retry(backoff.clone(), || {
+            calls += 1;
+            async { Err::<(), _>(sqlx::Error::PoolTimedOut) }
+        })
+        .await;
+        assert!(matches!(r, Err(sqlx::Error::PoolTimedOut)));
+        assert_eq!(calls, 4);
+
+        // 不可重试的错误直接返回
+        let mut calls = 0;
+        let r = retry(backoff, || {
+            calls += 1;
+            async { Err::<(), _>(sqlx::Error::RowNotFound) }
+        })
+        .await;
+        assert!(matches!(r, Err(sqlx::Error::RowNotFound)));
+        assert_eq!(calls, 1);
+    }
+}
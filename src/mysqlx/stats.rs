@@ -0,0 +1,163 @@
+//! 库, 表的大小统计, 来自information_schema.TABLES
+//!
+//! InnoDB的行数是估算值, 大小包含已分配未使用的页
+use std::fmt;
+
+use sqlx::mysql::MySqlArguments;
+use sqlx::{Arguments, MySqlPool};
+
+use crate::human::{HumanCountFixPad, HumanFormatter, Table};
+
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct TableSize {
+    pub table:        String,
+    pub rows:         u64,
+    pub data_length:  u64,
+    pub index_length: u64,
+}
+
+impl TableSize {
+    pub fn total_length(&self) -> u64 {
+        self.data_length + self.index_length
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseSize {
+    pub db:           String,
+    pub tables:       u64,
+    pub rows:         u64,
+    pub data_length:  u64,
+    pub index_length: u64,
+}
+
+impl DatabaseSize {
+    pub fn total_length(&self) -> u64 {
+        self.data_length + self.index_length
+    }
+}
+
+impl fmt::Display for DatabaseSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let human = HumanFormatter::default();
+        write!(
+            f,
+            "{} tables:{} rows:{} data:{} index:{} total:{}",
+            self.db,
+            self.tables,
+            HumanCountFixPad(self.rows),
+            human.bytes(self.data_length),
+            human.bytes(self.index_length),
+            human.bytes(self.total_length())
+        )
+    }
+}
+
+/// 库中所有表的大小, 按总大小从大到小排序, 不包含视图
+pub async fn table_sizes(pool: &MySqlPool, db: &str) -> Result<Vec<TableSize>, sqlx::Error> {
+    let sql = "SELECT CAST(TABLE_NAME AS CHAR) AS `table`,CAST(IFNULL(TABLE_ROWS,0) AS UNSIGNED) \
+               AS `rows`,CAST(IFNULL(DATA_LENGTH,0) AS UNSIGNED) AS data_length,\
+               CAST(IFNULL(INDEX_LENGTH,0) AS UNSIGNED) AS index_length FROM \
+               information_schema.TABLES WHERE TABLE_SCHEMA=? AND TABLE_TYPE='BASE TABLE'";
+    let mut args = MySqlArguments::default();
+    args.add(db);
+    let mut sizes = sqlx::query_as_with::<_, TableSize, _>(sql, args)
+        .fetch_all(pool)
+        .await?;
+    sizes.sort_by(|a, b| {
+        b.total_length()
+            .cmp(&a.total_length())
+            .then_with(|| a.table.cmp(&b.table))
+    });
+    Ok(sizes)
+}
+
+/// 库的汇总大小, 库不存在时各项为0
+pub async fn database_size(pool: &MySqlPool, db: &str) -> Result<DatabaseSize, sqlx::Error> {
+    let sql = "SELECT CAST(COUNT(*) AS UNSIGNED),CAST(IFNULL(SUM(TABLE_ROWS),0) AS UNSIGNED),\
+               CAST(IFNULL(SUM(DATA_LENGTH),0) AS UNSIGNED),CAST(IFNULL(SUM(INDEX_LENGTH),0) AS \
+               UNSIGNED) FROM information_schema.TABLES WHERE TABLE_SCHEMA=? AND \
+               TABLE_TYPE='BASE TABLE'";
+    let mut args = MySqlArguments::default();
+    args.add(db);
+    let (tables, rows, data_length, index_length) =
+        sqlx::query_as_with::<_, (u64, u64, u64, u64), _>(sql, args)
+            .fetch_one(pool)
+            .await?;
+    Ok(DatabaseSize {
+        db: db.to_owned(),
+        tables,
+        rows,
+        data_length,
+        index_length,
+    })
+}
+
+/// 表大小的报表, 最后一行为合计
+pub fn size_report(sizes: &[TableSize]) -> Table {
+    let human = HumanFormatter::default();
+    let mut table =
+        Table::new(&["table", "rows", "data", "index", "total"]).with_right_align(&[1, 2, 3, 4]);
+    for v in sizes {
+        table.push_row(vec![
+            v.table.clone(),
+            HumanCountFixPad(v.rows).to_string(),
+            human.bytes(v.data_length),
+            human.bytes(v.index_length),
+            human.bytes(v.total_length()),
+        ]);
+    }
+    let sum = |f: fn(&TableSize) -> u64| sizes.iter().map(f).sum::<u64>();
+    table.push_row(vec![
+        "(total)".to_string(),
+        HumanCountFixPad(sum(|v| v.rows)).to_string(),
+        human.bytes(sum(|v| v.data_length)),
+        human.bytes(sum(|v| v.index_length)),
+        human.bytes(sum(TableSize::total_length)),
+    ]);
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{database_size, size_report, table_sizes, TableSize};
+    use crate::mysqlx::MySqlPools;
+    use crate::mysqlx_test_pool::init_test_mysql_pools;
+
+    #[test]
+    fn test_size_report() {
+        let sizes = [
+            TableSize {
+                table:        "tbl_code_agL9".to_string(),
+                rows:         1_234_567,
+                data_length:  96 << 20,
+                index_length: 16 << 20,
+            },
+            TableSize {
+                table:        "tbl_tmp".to_string(),
+                rows:         3,
+                data_length:  16 << 10,
+                index_length: 0,
+            },
+        ];
+        let report = size_report(&sizes).to_string();
+        println!("{}", report);
+        let lines = report.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[2].starts_with("tbl_code_agL9"));
+        assert!(lines[2].ends_with("112MiB"));
+        assert!(lines[4].starts_with("(total)"));
+        assert!(lines[4].contains("1,234,570"));
+    }
+
+    #[tokio::test]
+    async fn test_table_sizes() {
+        init_test_mysql_pools();
+        let pool = MySqlPools::pool_default().await.unwrap();
+        let sizes = table_sizes(&pool, "basedata").await.unwrap();
+        println!("{}", size_report(&sizes));
+        let db_size = database_size(&pool, "basedata").await.unwrap();
+        println!("{}", db_size);
+        assert_eq!(db_size.tables, sizes.len() as u64);
+    }
+}
@@ -134,6 +134,8 @@ pub struct TableCreator {
     field_vec:    Vec<TableField>,
     indexs:       Vec<String>,
     primary_keys: String,
+    index_fields: Vec<Vec<String>>,
+    pk_fields:    Vec<String>,
 }
 
 impl std::fmt::Display for TableCreator {
@@ -196,6 +198,8 @@ impl TableCreator {
             field_vec: Vec::new(),
             indexs: Vec::new(),
             primary_keys: String::new(),
+            index_fields: Vec::new(),
+            pk_fields: Vec::new(),
         }
     }
 
@@ -221,15 +225,57 @@ impl TableCreator {
         let fields_str = fields.iter().map(|v| format!("`{}`", v)).join(",");
         self.indexs
             .push(format!("INDEX {} ({}),", index_name, fields_str));
+        self.index_fields
+            .push(fields.iter().map(|v| v.to_string()).collect());
         self
     }
 
     pub fn primary_keys(mut self, fields: &[&str]) -> Self {
         let fields_str = fields.iter().map(|v| format!("`{}`", v)).join(",");
         self.primary_keys = format!("PRIMARY KEY ({})", fields_str);
+        self.pk_fields = fields.iter().map(|v| v.to_string()).collect();
         self
     }
 
+    /// 同样表结构的SqlLoader表模板, 加入SqlLoader的配置文件后用`table_create_sql_from_template`创建
+    ///
+    /// 字段名中的`_`写成`-`, 字符类型默认值去掉两边的`'`
+    pub fn sql_loader_toml(&self, tmpl_name: &str) -> String {
+        let key = |v: &str| v.trim_matches('`').replace('_', "-");
+        let keys = |v: &[String]| v.iter().map(|v| toml_str(&key(v))).join(", ");
+        let mut lines = vec![
+            "[[table]]".to_owned(),
+            "tbl-is-template = true".to_owned(),
+            format!("tbl-name = {}", toml_str(tmpl_name)),
+            format!("tbl-private-key = [{}]", keys(&self.pk_fields)),
+        ];
+        if !self.index_fields.is_empty() {
+            let indexs = self
+                .index_fields
+                .iter()
+                .map(|v| format!("[{}]", keys(v)))
+                .join(", ");
+            lines.push(format!("tbl-index = [{}]", indexs));
+        }
+        for field in self.field_vec.iter() {
+            let mut attrs = vec![format!("type = {}", toml_str(&field.r#type.to_uppercase()))];
+            if !field.null {
+                attrs.push("not-null = true".to_owned());
+            }
+            if !field.default.is_empty() {
+                let default = field
+                    .default
+                    .strip_prefix('\'')
+                    .and_then(|v| v.strip_suffix('\''))
+                    .unwrap_or(&field.default);
+                attrs.push(format!("default = {}", toml_str(default)));
+            }
+            attrs.push(format!("comment = {}", toml_str(&field.comment)));
+            lines.push(format!("{} = {{ {} }}", key(&field.name), attrs.join(", ")));
+        }
+        lines.join("\n")
+    }
+
     pub async fn create(&self, pool: &MySqlPool) -> Result<TableExecInfo, ExecError> {
         let sql = self.to_string();
         let exec_info = exec_sql(pool, &sql).await?;
@@ -240,6 +286,11 @@ impl TableCreator {
     }
 }
 
+/// toml的基本字符串
+fn toml_str(v: &str) -> String {
+    format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
 #[derive(Debug)]
 pub struct TableExecInfo {
     pub table_name: String,
@@ -277,11 +328,10 @@ pub async fn create_table(
 #[cfg(test)]
 mod tests {
     use super::TableCreator;
-    use crate::mysqlx::MySqlPools;
-    use crate::mysqlx_test_pool::init_test_mysql_pools;
+    use crate::mysqlx_test_pool::test_db_guard;
 
-    fn table_creator() -> TableCreator {
-        TableCreator::new("basedata", "tmp")
+    fn table_creator(db: &str) -> TableCreator {
+        TableCreator::new(db, "tmp")
             .add_field("f22222", "int(11)", true, "0.0", "字段2")
             .add_field("f3", "char(8)", false, "", "字段3")
             .add_field("f4", "char(8)", true, "", "字段4")
@@ -293,28 +343,51 @@ mod tests {
 
     #[test]
     fn test_table_creator_debug() {
-        let tb = table_creator();
+        let tb = table_creator("basedata");
         println!("{:?}", tb);
     }
 
     #[test]
     fn test_table_creator_display() {
-        let tb = table_creator();
+        let tb = table_creator("basedata");
 
         println!("{}", tb);
     }
 
+    #[test]
+    fn test_sql_loader_toml() {
+        let toml = TableCreator::new("", "tmp")
+            .add_field("f_1", "varchar(8)", false, "''", "字段\"1\"")
+            .add_field("f2", "datetime", true, "", "时间")
+            .add_index("index_f2", &["f2"])
+            .primary_keys(&["f_1"])
+            .sql_loader_toml("tbl-tmp-tmpl");
+        assert_eq!(
+            toml,
+            r#"[[table]]
+tbl-is-template = true
+tbl-name = "tbl-tmp-tmpl"
+tbl-private-key = ["f-1"]
+tbl-index = [["f2"]]
+f-1 = { type = "VARCHAR(8)", not-null = true, default = "", comment = "字段\"1\"" }
+f2 = { type = "DATETIME", comment = "时间" }"#
+        );
+        #[cfg(feature = "sql-loader")]
+        {
+            let loader = ::toml::from_str::<crate::sql_loader::SqlLoader>(&toml);
+            assert!(loader.is_ok(), "{:?}", loader.err());
+        }
+    }
+
     #[tokio::test]
     async fn test_create_table() {
-        init_test_mysql_pools();
-        let tb = table_creator();
-        let r = tb
-            .create(MySqlPools::pool_default().await.unwrap().as_ref())
-            .await;
-        if let Err(err) = r {
-            println!("{}", err);
-            return;
+        let guard = test_db_guard().await;
+        let tb = table_creator(guard.db());
+        let r = tb.create(guard.pool().as_ref()).await;
+        match r {
+            Ok(r) => println!("{}", r),
+            Err(err) => println!("{}", err),
         }
-        println!("{}", r.unwrap());
+        guard.cleanup().await;
     }
 }
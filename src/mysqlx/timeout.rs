@@ -0,0 +1,146 @@
+//! 查询超时: 客户端超时后取消查询, 并尽量在服务端`KILL QUERY`, 避免长时间的查询一直占用连接池中的连接
+//!
+//! KILL使用单独新建的连接, 不从连接池获取: 超时时连接池可能已经被慢查询占满
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use log::warn;
+use sqlx::{ConnectOptions, Connection, Executor, MySqlConnection, MySqlPool};
+
+/// KILL用的连接的建立超时
+const KILL_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, thiserror::Error)]
+pub enum QueryTimeoutError {
+    /// killed: 服务端的语句是否已成功KILL
+    #[error("query timeout after {timeout:?}, connection id: {connection_id}, killed: {killed}")]
+    Timeout {
+        timeout:       Duration,
+        connection_id: u64,
+        killed:        bool,
+    },
+    #[error("{0}")]
+    Sqlx(#[from] sqlx::Error),
+}
+
+impl QueryTimeoutError {
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, QueryTimeoutError::Timeout { .. })
+    }
+}
+
+/// 从连接池取一个连接执行f, 超过timeout时取消并KILL服务端的语句
+///
+/// 超时的连接状态未知, 不再放回连接池, 直接关闭.
+/// 执行f前在同一个连接上取一次`CONNECTION_ID()`, 超时时KILL这个id
+///
+/// ```ignore
+/// let rows = with_timeout(&pool, Duration::from_secs(5), |con| {
+///     Box::pin(async move { sqlx::query("SELECT ...").fetch_all(con).await })
+/// })
+/// .await?;
+/// ```
+pub async fn with_timeout<T, F>(
+    pool: &MySqlPool,
+    timeout: Duration,
+    f: F,
+) -> Result<T, QueryTimeoutError>
+where
+    F: for<'c> FnOnce(&'c mut MySqlConnection) -> BoxFuture<'c, Result<T, sqlx::Error>>,
+{
+    let mut con = pool.acquire().await?;
+    let (connection_id,) = sqlx::query_as::<_, (u64,)>("SELECT CONNECTION_ID()")
+        .fetch_one(&mut *con)
+        .await?;
+    let result = tokio::time::timeout(timeout, f(&mut con)).await;
+    match result {
+        Ok(v) => Ok(v?),
+        Err(_) => {
+            drop(con.detach());
+            let killed = kill_query(pool, connection_id, KILL_CONNECT_TIMEOUT).await;
+            Err(QueryTimeoutError::Timeout {
+                timeout,
+                connection_id,
+                killed,
+            })
+        },
+    }
+}
+
+/// 用连接池的连接参数新建一个连接KILL服务端的语句, 用完关闭; 失败时只记录日志
+async fn kill_query(pool: &MySqlPool, connection_id: u64, connect_timeout: Duration) -> bool {
+    let options = pool.connect_options();
+    let mut con = match tokio::time::timeout(connect_timeout, options.connect()).await {
+        Ok(Ok(con)) => con,
+        Ok(Err(e)) => {
+            warn!("kill query of connection {} connect err: {}", connection_id, e);
+            return false;
+        },
+        Err(_) => {
+            warn!(
+                "kill query of connection {} connect timeout after {:?}",
+                connection_id, connect_timeout
+            );
+            return false;
+        },
+    };
+    let sql = format!("KILL QUERY {}", connection_id);
+    let killed = match con.execute(sql.as_str()).await {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("kill query of connection {} err: {}", connection_id, e);
+            false
+        },
+    };
+    let _ = con.close().await;
+    killed
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use sqlx::mysql::{MySqlConnectOptions, MySqlPoolOptions};
+
+    use super::{kill_query, with_timeout, QueryTimeoutError};
+    use crate::mysqlx::MySqlPools;
+    use crate::mysqlx_test_pool::init_test_mysql_pools;
+
+    #[tokio::test]
+    async fn test_kill_query_connect_timeout() {
+        // 不可路由的地址, 连接一直挂起
+        let options = MySqlConnectOptions::new().host("10.255.255.1").port(3306);
+        let pool = MySqlPoolOptions::new().connect_lazy_with(options);
+        let start = Instant::now();
+        assert!(!kill_query(&pool, 1, Duration::from_millis(200)).await);
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_with_timeout() {
+        init_test_mysql_pools();
+        let pool = MySqlPools::pool_default().await.unwrap();
+        let (v,) = with_timeout(&pool, Duration::from_secs(5), |con| {
+            Box::pin(async move { sqlx::query_as::<_, (i64,)>("SELECT 1").fetch_one(con).await })
+        })
+        .await
+        .unwrap();
+        assert_eq!(v, 1);
+
+        let start = Instant::now();
+        let r = with_timeout(&pool, Duration::from_millis(200), |con| {
+            Box::pin(async move {
+                sqlx::query_as::<_, (i64,)>("SELECT SLEEP(10)")
+                    .fetch_one(con)
+                    .await
+            })
+        })
+        .await;
+        println!("{:?}", r);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(matches!(
+            r,
+            Err(QueryTimeoutError::Timeout { killed: true, .. })
+        ));
+    }
+}
@@ -0,0 +1,178 @@
+//! 事务及嵌套的savepoint: 内层出错时只回滚到savepoint, 外层可以重试或跳过后继续
+use std::ops::{Deref, DerefMut};
+
+use futures_util::future::BoxFuture;
+use sqlx::{Executor, MySql, MySqlConnection, MySqlPool, Transaction};
+
+use crate::sql_ident::validate_ident;
+
+/// 事务中的连接, 通过`&mut **tx`执行语句
+pub struct Tx {
+    inner:      Transaction<'static, MySql>,
+    savepoints: Vec<String>,
+}
+
+impl Deref for Tx {
+    type Target = MySqlConnection;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for Tx {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl Tx {
+    /// 当前所在的savepoint, 由外到内
+    pub fn savepoints(&self) -> &[String] {
+        &self.savepoints
+    }
+
+    /// 在savepoint中执行f, 出错时回滚到savepoint并返回错误, 不影响事务中之前的修改
+    ///
+    /// name只允许字母, 数字, _, $, 不能和外层的savepoint同名
+    ///
+    /// ```ignore
+    /// tx.savepoint("backfill_1", |tx| {
+    ///     Box::pin(async move { sqlx::query("INSERT ...").execute(&mut **tx).await })
+    /// })
+    /// .await?;
+    /// ```
+    pub async fn savepoint<T, E, F>(&mut self, name: &str, f: F) -> Result<T, E>
+    where
+        E: From<sqlx::Error>,
+        F: for<'c> FnOnce(&'c mut Tx) -> BoxFuture<'c, Result<T, E>>,
+    {
+        let name = validate_ident(name).map_err(|e| sqlx::Error::Configuration(Box::new(e)))?;
+        if self.savepoints.iter().any(|v| v == name) {
+            Err(sqlx::Error::Configuration(
+                format!("savepoint {} already active", name).into(),
+            ))?;
+        }
+        self.exec(&format!("SAVEPOINT `{}`", name)).await?;
+        self.savepoints.push(name.to_owned());
+        let r = f(self).await;
+        self.savepoints.pop();
+        match r {
+            Ok(v) => {
+                self.exec(&format!("RELEASE SAVEPOINT `{}`", name)).await?;
+                Ok(v)
+            },
+            Err(e) => {
+                self.exec(&format!("ROLLBACK TO SAVEPOINT `{}`", name))
+                    .await?;
+                self.exec(&format!("RELEASE SAVEPOINT `{}`", name)).await?;
+                Err(e)
+            },
+        }
+    }
+
+    async fn exec(&mut self, sql: &str) -> Result<(), sqlx::Error> {
+        self.inner.execute(sql).await?;
+        Ok(())
+    }
+}
+
+/// 在事务中执行f, 成功时提交, 出错时回滚
+///
+/// ```ignore
+/// transaction(&pool, |tx| {
+///     Box::pin(async move {
+///         sqlx::query("DELETE ...").execute(&mut **tx).await?;
+///         tx.savepoint("retry", |tx| Box::pin(async move { ... })).await
+///     })
+/// })
+/// .await?;
+/// ```
+pub async fn transaction<T, E, F>(pool: &MySqlPool, f: F) -> Result<T, E>
+where
+    E: From<sqlx::Error>,
+    F: for<'c> FnOnce(&'c mut Tx) -> BoxFuture<'c, Result<T, E>>,
+{
+    let mut tx = Tx {
+        inner:      pool.begin().await?,
+        savepoints: Vec::new(),
+    };
+    match f(&mut tx).await {
+        Ok(v) => {
+            tx.inner.commit().await?;
+            Ok(v)
+        },
+        Err(e) => {
+            tx.inner.rollback().await?;
+            Err(e)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::transaction;
+    use crate::mysqlx_test_pool::test_db_guard;
+
+    #[tokio::test]
+    async fn test_savepoint() {
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        let rows = transaction::<_, sqlx::Error, _>(&pool, |tx| {
+            Box::pin(async move {
+                sqlx::query("CREATE TEMPORARY TABLE tmp_savepoint (v INT)")
+                    .execute(&mut **tx)
+                    .await?;
+                sqlx::query("INSERT INTO tmp_savepoint VALUES (1)")
+                    .execute(&mut **tx)
+                    .await?;
+                let r = tx
+                    .savepoint("sp_outer", |tx| {
+                        Box::pin(async move {
+                            sqlx::query("INSERT INTO tmp_savepoint VALUES (2)")
+                                .execute(&mut **tx)
+                                .await?;
+                            let r = tx
+                                .savepoint("sp_inner", |tx| {
+                                    Box::pin(async move {
+                                        assert_eq!(tx.savepoints(), ["sp_outer", "sp_inner"]);
+                                        sqlx::query("INSERT INTO tmp_savepoint VALUES (3)")
+                                            .execute(&mut **tx)
+                                            .await?;
+                                        sqlx::query("INSERT INTO not_exists VALUES (4)")
+                                            .execute(&mut **tx)
+                                            .await
+                                    })
+                                })
+                                .await;
+                            assert!(r.is_err());
+                            // 同名的savepoint
+                            let r = tx
+                                .savepoint("sp_outer", |_| Box::pin(async { Ok(()) }))
+                                .await;
+                            assert!(matches!(r, Err(sqlx::Error::Configuration(_))));
+                            Err::<(), _>(sqlx::Error::RowNotFound)
+                        })
+                    })
+                    .await;
+                assert!(matches!(r, Err(sqlx::Error::RowNotFound)));
+                tx.savepoint("sp_ok", |tx| {
+                    Box::pin(async move {
+                        sqlx::query("INSERT INTO tmp_savepoint VALUES (5)")
+                            .execute(&mut **tx)
+                            .await
+                    })
+                })
+                .await?;
+                let rows = sqlx::query_as::<_, (i32,)>("SELECT v FROM tmp_savepoint ORDER BY v")
+                    .fetch_all(&mut **tx)
+                    .await?;
+                Ok(rows.into_iter().map(|v| v.0).collect::<Vec<_>>())
+            })
+        })
+        .await
+        .unwrap();
+        assert_eq!(rows, [1, 5]);
+        guard.cleanup().await;
+    }
+}
@@ -1,9 +1,11 @@
+use std::fmt::Display;
 use std::ops::Deref;
+use std::str::FromStr;
 
 use chrono::NaiveTime;
 use sqlx::error::BoxDynError;
 use sqlx::mysql::{MySqlTypeInfo, MySqlValueRef};
-use sqlx::{Decode, MySql, Type};
+use sqlx::{Decode, MySql, Type, ValueRef};
 
 // String -> Vec<T>
 #[derive(Debug, Clone)]
@@ -45,3 +47,156 @@ impl Decode<'_, MySql> for VecType<NaiveTime> {
         Ok(VecType(vec))
     }
 }
+
+/// 整数列 -> 无符号整数, 有符号列的值为负数或超出T的范围时报错
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Unsigned<T>(pub T);
+
+impl<T> Deref for Unsigned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> Type<MySql> for Unsigned<T> {
+    fn type_info() -> MySqlTypeInfo {
+        <u64 as Type<MySql>>::type_info()
+    }
+
+    fn compatible(ty: &MySqlTypeInfo) -> bool {
+        <u64 as Type<MySql>>::compatible(ty) || <i64 as Type<MySql>>::compatible(ty)
+    }
+}
+
+impl<T> Decode<'_, MySql> for Unsigned<T>
+where
+    T: TryFrom<u64>,
+    <T as TryFrom<u64>>::Error: Display,
+{
+    fn decode(value: MySqlValueRef<'_>) -> Result<Self, BoxDynError> {
+        let unsigned = <u64 as Type<MySql>>::compatible(&value.type_info());
+        let value = if unsigned {
+            <u64 as Decode<MySql>>::decode(value)?
+        } else {
+            let value = <i64 as Decode<MySql>>::decode(value)?;
+            u64::try_from(value).map_err(|_| format!("negative value {} for unsigned", value))?
+        };
+        let value = T::try_from(value).map_err(|e| {
+            format!(
+                "value {} out of range for {}: {}",
+                value,
+                std::any::type_name::<T>(),
+                e
+            )
+        })?;
+        Ok(Unsigned(value))
+    }
+}
+
+/// 字符串列 -> 实现了FromStr的枚举
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StrEnum<T>(pub T);
+
+impl<T> Deref for StrEnum<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> Type<MySql> for StrEnum<T> {
+    fn type_info() -> MySqlTypeInfo {
+        <&str as Type<MySql>>::type_info()
+    }
+
+    fn compatible(ty: &MySqlTypeInfo) -> bool {
+        <&str as Type<MySql>>::compatible(ty)
+    }
+}
+
+impl<T> Decode<'_, MySql> for StrEnum<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn decode(value: MySqlValueRef<'_>) -> Result<Self, BoxDynError> {
+        let value = <&str as Decode<MySql>>::decode(value)?;
+        let value = value.parse::<T>().map_err(|e| {
+            format!(
+                "{} parse {:?} err: {}",
+                std::any::type_name::<T>(),
+                value,
+                e
+            )
+        })?;
+        Ok(StrEnum(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use sqlx::{MySql, Type};
+
+    use super::{StrEnum, Unsigned};
+    use crate::mysqlx_test_pool::test_db_guard;
+
+    #[derive(Debug, PartialEq)]
+    enum Direction {
+        Buy,
+        Sell,
+    }
+
+    impl FromStr for Direction {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "B" => Ok(Direction::Buy),
+                "S" => Ok(Direction::Sell),
+                _ => Err(format!("unknown direction: {}", s)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_compatible() {
+        let unsigned = <u64 as Type<MySql>>::type_info();
+        let signed = <i32 as Type<MySql>>::type_info();
+        let string = <String as Type<MySql>>::type_info();
+        assert!(<Unsigned<u32> as Type<MySql>>::compatible(&unsigned));
+        assert!(<Unsigned<u32> as Type<MySql>>::compatible(&signed));
+        assert!(!<Unsigned<u32> as Type<MySql>>::compatible(&string));
+        assert!(<StrEnum<Direction> as Type<MySql>>::compatible(&string));
+    }
+
+    #[tokio::test]
+    async fn test_decode() {
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        let (a, b, direction) = sqlx::query_as::<
+            _,
+            (Unsigned<u64>, Unsigned<u8>, StrEnum<Direction>),
+        >("SELECT CAST(18446744073709551615 AS UNSIGNED), 200, 'S'")
+        .fetch_one(&*pool)
+        .await
+        .unwrap();
+        assert_eq!(*a, u64::MAX);
+        assert_eq!(*b, 200);
+        assert_eq!(*direction, Direction::Sell);
+
+        let r = sqlx::query_as::<_, (Unsigned<u64>,)>("SELECT -1")
+            .fetch_one(&*pool)
+            .await;
+        assert!(r.is_err());
+        let r = sqlx::query_as::<_, (Unsigned<u8>,)>("SELECT 300")
+            .fetch_one(&*pool)
+            .await;
+        assert!(r.is_err());
+        guard.cleanup().await;
+    }
+}
@@ -0,0 +1,135 @@
+//! 乐观锁更新: 更新时检查版本列, 成功后版本加1
+use itertools::Itertools;
+use sqlx::mysql::MySqlArguments;
+use sqlx::MySqlPool;
+
+use super::exec::{exec_sql_args, ExecError, ExecInfo};
+use crate::sql_ident::{column, table_ident, IdentError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum VersionedUpdateError {
+    #[error("{0}")]
+    Ident(#[from] IdentError),
+
+    #[error("{0}")]
+    Exec(#[from] ExecError),
+
+    #[error("version conflict: {table}, record not exist or updated by others")]
+    Conflict { table: String },
+}
+
+impl VersionedUpdateError {
+    pub fn is_conflict(&self) -> bool {
+        matches!(self, VersionedUpdateError::Conflict { .. })
+    }
+}
+
+/// `UPDATE table SET a=?,b=?,version=version+1 WHERE k1=? AND k2=? AND version=?`
+///
+/// table: `db.tbl`或`tbl`, 会校验名称
+pub fn update_with_version_sql(
+    table: &str,
+    set_cols: &[&str],
+    key_cols: &[&str],
+    version_col: &str,
+) -> Result<String, VersionedUpdateError> {
+    let table = table_ident(table)?;
+    let version = column(version_col)?;
+    let sets = set_cols
+        .iter()
+        .map(|v| column(v).map(|v| format!("{}=?", v)))
+        .chain(std::iter::once(Ok(format!("{0}={0}+1", version))))
+        .collect::<Result<Vec<_>, _>>()?;
+    let wheres = key_cols
+        .iter()
+        .map(|v| column(v).map(|v| format!("{}=?", v)))
+        .chain(std::iter::once(Ok(format!("{}=?", version))))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(format!(
+        "UPDATE {} SET {} WHERE {}",
+        table,
+        sets.iter().join(","),
+        wheres.iter().join(" AND ")
+    ))
+}
+
+/// 按版本更新一条记录, 没有记录被更新时返回Conflict
+///
+/// table: `db.tbl`或`tbl`, 会校验名称
+///
+/// args的顺序: set_cols的值, key_cols的值, 读取时的版本
+pub async fn update_with_version(
+    pool: &MySqlPool,
+    table: &str,
+    set_cols: &[&str],
+    key_cols: &[&str],
+    version_col: &str,
+    args: MySqlArguments,
+) -> Result<ExecInfo, VersionedUpdateError> {
+    let sql = update_with_version_sql(table, set_cols, key_cols, version_col)?;
+    let info = exec_sql_args(pool, &sql, args).await?;
+    if info.rows_affected == 0 {
+        return Err(VersionedUpdateError::Conflict {
+            table: table.to_owned(),
+        });
+    }
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::mysql::MySqlArguments;
+    use sqlx::Arguments;
+
+    use super::{update_with_version, update_with_version_sql, VersionedUpdateError};
+    use crate::mysqlx::exec::exec_sql;
+    use crate::mysqlx_test_pool::test_db_guard;
+
+    #[test]
+    fn test_update_with_version_sql() {
+        let sql =
+            update_with_version_sql("db.cfg", &["value", "note"], &["name"], "version").unwrap();
+        assert_eq!(
+            sql,
+            "UPDATE `db`.`cfg` SET `value`=?,`note`=?,`version`=`version`+1 WHERE `name`=? AND \
+             `version`=?"
+        );
+        let err = update_with_version_sql("cfg", &["value;"], &["name"], "version").unwrap_err();
+        assert!(matches!(err, VersionedUpdateError::Ident(_)));
+        let table = "cfg SET `value`=0 WHERE 1=1 OR";
+        let err = update_with_version_sql(table, &["value"], &["name"], "version").unwrap_err();
+        assert!(matches!(err, VersionedUpdateError::Ident(_)));
+    }
+
+    #[tokio::test]
+    async fn test_update_with_version() {
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        let table = format!("{}._test_versioned", guard.db());
+        exec_sql(
+            &pool,
+            "CREATE TABLE `_test_versioned`(`name` varchar(32) PRIMARY KEY, `value` int, `version` \
+             int)",
+        )
+        .await
+        .unwrap();
+        exec_sql(&pool, "INSERT INTO `_test_versioned` VALUES ('a', 1, 1)")
+            .await
+            .unwrap();
+        let args = || {
+            let mut args = MySqlArguments::default();
+            args.add(2);
+            args.add("a");
+            args.add(1);
+            args
+        };
+        update_with_version(&pool, &table, &["value"], &["name"], "version", args())
+            .await
+            .unwrap();
+        let err = update_with_version(&pool, &table, &["value"], &["name"], "version", args())
+            .await
+            .unwrap_err();
+        assert!(err.is_conflict());
+        guard.cleanup().await;
+    }
+}
@@ -1,3 +1,6 @@
+#[cfg(test)]
+pub(crate) use self::test_db::test_db_guard;
+
 #[cfg(test)]
 pub(crate) fn init_test_mysql_pools() {
     use crate::mysqlx::MySqlPools;
@@ -6,3 +9,113 @@ pub(crate) fn init_test_mysql_pools() {
         println!("conn err: {}", e)
     }
 }
+
+#[cfg(test)]
+mod test_db {
+    use std::sync::Arc;
+
+    use sqlx::mysql::{MySqlConnectOptions, MySqlPoolOptions};
+    use sqlx::{ConnectOptions, Connection, Executor, MySqlPool};
+    use uuid::Uuid;
+
+    use super::init_test_mysql_pools;
+    use crate::mysqlx::{ident, MySqlPools};
+
+    /// 测试用的临时数据库, Drop时删除
+    ///
+    /// 通过ssh隧道连接时, Drop中新建的连接可能无法使用隧道, 需要调用`cleanup`
+    pub(crate) struct TestDbGuard {
+        db:      String,
+        pool:    Arc<MySqlPool>,
+        options: MySqlConnectOptions,
+        dropped: bool,
+    }
+
+    /// 在默认连接中创建一个唯一名称的数据库
+    pub(crate) async fn test_db_guard() -> TestDbGuard {
+        init_test_mysql_pools();
+        let default_pool = MySqlPools::pool_default().await.unwrap();
+        let db = format!("test_{}", Uuid::now_v7().simple());
+        default_pool
+            .execute(format!("CREATE DATABASE {}", ident("", &db).unwrap()).as_str())
+            .await
+            .unwrap();
+        let options = default_pool.connect_options().as_ref().clone();
+        let pool = MySqlPoolOptions::new()
+            .max_connections(4)
+            .connect_with(options.clone().database(&db))
+            .await
+            .unwrap();
+        TestDbGuard {
+            db,
+            pool: Arc::new(pool),
+            options,
+            dropped: false,
+        }
+    }
+
+    impl TestDbGuard {
+        pub(crate) fn db(&self) -> &str {
+            &self.db
+        }
+
+        /// 默认数据库为临时数据库的连接池
+        pub(crate) fn pool(&self) -> Arc<MySqlPool> {
+            self.pool.clone()
+        }
+
+        /// `db`.`table`
+        pub(crate) fn table(&self, table: &str) -> String {
+            ident(&self.db, table).unwrap()
+        }
+
+        fn drop_sql(&self) -> String {
+            format!("DROP DATABASE IF EXISTS {}", ident("", &self.db).unwrap())
+        }
+
+        pub(crate) async fn cleanup(mut self) {
+            self.pool.close().await;
+            let mut conn = self.options.connect().await.unwrap();
+            conn.execute(self.drop_sql().as_str()).await.unwrap();
+            let _ = conn.close().await;
+            self.dropped = true;
+        }
+    }
+
+    impl Drop for TestDbGuard {
+        fn drop(&mut self) {
+            if self.dropped {
+                return;
+            }
+            // 测试的runtime可能是单线程的, 在新线程的runtime中删除
+            let sql = self.drop_sql();
+            let options = self.options.clone();
+            let r = std::thread::spawn(move || {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap()
+                    .block_on(async move {
+                        let mut conn = options.connect().await?;
+                        conn.execute(sql.as_str()).await?;
+                        conn.close().await
+                    })
+            })
+            .join();
+            if let Ok(Err(e)) = r {
+                println!("drop test db {} err: {}", self.db, e);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_test_db_guard() {
+        let guard = test_db_guard().await;
+        let table = guard.table("tbl_tmp");
+        sqlx::query(&format!("CREATE TABLE {} (id INT PRIMARY KEY)", table))
+            .execute(&*guard.pool())
+            .await
+            .unwrap();
+        println!("{} {}", guard.db(), table);
+    }
+}
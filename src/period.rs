@@ -0,0 +1,199 @@
+//! K线周期, `qh`和`hq`共用
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PeriodError {
+    #[error("period #{0}# parse err")]
+    Parse(String),
+
+    #[error("period value #{0}# not support")]
+    Value(i64),
+}
+
+/// K线周期
+/// 排序按周期的分钟数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Period {
+    M1,
+    M3,
+    M5,
+    M15,
+    M30,
+    M60,
+    M120,
+    D1,
+    W1,
+    Month1,
+}
+
+impl Period {
+    pub const ALL: [Period; 10] = [
+        Period::M1,
+        Period::M3,
+        Period::M5,
+        Period::M15,
+        Period::M30,
+        Period::M60,
+        Period::M120,
+        Period::D1,
+        Period::W1,
+        Period::Month1,
+    ];
+
+    /// 周期对应的分钟数, 与数据库中period字段的值一致
+    /// 1d: 60*24, 1w: 60*24*7, 1month: 60*24*30
+    pub fn minutes(&self) -> u16 {
+        match self {
+            Period::M1 => 1,
+            Period::M3 => 3,
+            Period::M5 => 5,
+            Period::M15 => 15,
+            Period::M30 => 30,
+            Period::M60 => 60,
+            Period::M120 => 120,
+            Period::D1 => 1440,
+            Period::W1 => 10080,
+            Period::Month1 => 43200,
+        }
+    }
+
+    pub fn from_minutes(minutes: u16) -> Option<Period> {
+        Self::ALL.into_iter().find(|v| v.minutes() == minutes)
+    }
+
+    /// 是否日内周期
+    pub fn is_intraday(&self) -> bool {
+        *self < Period::D1
+    }
+
+    /// 当前周期的K线是否可以由smaller周期的K线合成
+    /// 日内周期: 分钟数整除
+    /// 1d: 所有日内周期
+    /// 1w: 日内周期及1d
+    /// 1month: 日内周期及1d, 周线会跨月, 不能合成月线
+    pub fn contains(&self, smaller: &Period) -> bool {
+        if self == smaller {
+            return true;
+        }
+        if smaller > self {
+            return false;
+        }
+        match self {
+            Period::D1 => smaller.is_intraday(),
+            Period::W1 | Period::Month1 => smaller.is_intraday() || *smaller == Period::D1,
+            _ => self.minutes().is_multiple_of(smaller.minutes()),
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Period::M1 => "1m",
+            Period::M3 => "3m",
+            Period::M5 => "5m",
+            Period::M15 => "15m",
+            Period::M30 => "30m",
+            Period::M60 => "60m",
+            Period::M120 => "120m",
+            Period::D1 => "1d",
+            Period::W1 => "1w",
+            Period::Month1 => "1month",
+        };
+        f.write_str(s)
+    }
+}
+
+/// 支持: 1m 3m 5m 15m 30m 60m 1h 120m 2h 1d 1w 1mth 1month, 不区分大小写
+impl FromStr for Period {
+    type Err = PeriodError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let period = match s.trim().to_lowercase().as_str() {
+            "1m" => Period::M1,
+            "3m" => Period::M3,
+            "5m" => Period::M5,
+            "15m" => Period::M15,
+            "30m" => Period::M30,
+            "60m" | "1h" => Period::M60,
+            "120m" | "2h" => Period::M120,
+            "1d" => Period::D1,
+            "1w" => Period::W1,
+            "1mth" | "1month" => Period::Month1,
+            _ => return Err(PeriodError::Parse(s.to_owned())),
+        };
+        Ok(period)
+    }
+}
+
+impl TryFrom<i32> for Period {
+    type Error = PeriodError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        u16::try_from(value)
+            .ok()
+            .and_then(Period::from_minutes)
+            .ok_or(PeriodError::Value(value as i64))
+    }
+}
+
+impl TryFrom<u16> for Period {
+    type Error = PeriodError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        Period::from_minutes(value).ok_or(PeriodError::Value(value as i64))
+    }
+}
+
+impl From<Period> for i32 {
+    fn from(value: Period) -> Self {
+        value.minutes() as i32
+    }
+}
+
+impl From<Period> for u16 {
+    fn from(value: Period) -> Self {
+        value.minutes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Period;
+
+    #[test]
+    fn test_period_parse_display() {
+        for period in Period::ALL {
+            let s = period.to_string();
+            let p = s.parse::<Period>().unwrap();
+            println!("{:>6} {:>5}", s, p.minutes());
+            assert_eq!(p, period);
+            assert_eq!(Period::from_minutes(p.minutes()), Some(period));
+        }
+        assert_eq!("1h".parse::<Period>().unwrap(), Period::M60);
+        assert_eq!("2H".parse::<Period>().unwrap(), Period::M120);
+        assert_eq!("1mth".parse::<Period>().unwrap(), Period::Month1);
+        assert!("7m".parse::<Period>().is_err());
+    }
+
+    #[test]
+    fn test_period_convert() {
+        assert_eq!(Period::try_from(1440i32).unwrap(), Period::D1);
+        assert_eq!(i32::from(Period::M15), 15);
+        assert!(Period::try_from(-1i32).is_err());
+        assert!(Period::try_from(7u16).is_err());
+    }
+
+    #[test]
+    fn test_period_contains() {
+        assert!(Period::M3 < Period::M5);
+        assert!(Period::M15.contains(&Period::M5));
+        assert!(Period::M60.contains(&Period::M15));
+        assert!(!Period::M5.contains(&Period::M3));
+        assert!(!Period::M5.contains(&Period::M15));
+        assert!(Period::D1.contains(&Period::M120));
+        assert!(Period::W1.contains(&Period::D1));
+        assert!(!Period::Month1.contains(&Period::W1));
+    }
+}
@@ -6,11 +6,20 @@ use log::{error, info};
 use rand::Rng;
 use tokio::task::JoinHandle;
 
+pub use self::checkpoint::CheckpointedProgress;
+#[cfg(feature = "redis")]
+pub use self::distributed::{DistributedSnapshot, ProgressCoordinator, ProgressWorker};
+pub use self::rate::{ProgressStats, ProgressTracker};
 use crate::AResult;
 
+pub mod checkpoint;
+#[cfg(feature = "redis")]
+pub mod distributed;
+pub mod rate;
+
 fn progress_bar(len: u64) -> ProgressBar {
     let process_style = ProgressStyle::with_template(
-        "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] ({pos}/{len}|{percent:>2}%)",
+        "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] ({pos}/{len}|{percent:>2}%) {msg}",
     )
     .unwrap();
 
@@ -32,6 +41,35 @@ pub async fn parallel<T, F, FnOut, FnOutT>(
     progress_bar_share_prefix: &str,
     f: F,
 ) -> AResult<Vec<FnOutT>>
+where
+    T: std::fmt::Debug + Send + 'static,
+    F: Fn(T, ProgressBar, ProgressBar) -> FnOut,
+    F: Send + Sync + Clone + 'static,
+    FnOut: Future<Output = AResult<FnOutT>> + Send,
+    FnOutT: Send + 'static,
+{
+    let tracker = ProgressTracker::new(Some(data_vec.len() as u64));
+    parallel_tracked(
+        par_flag,
+        data_vec,
+        parallel_limit,
+        progress_bar_share_prefix,
+        tracker,
+        f,
+    )
+    .await
+}
+
+/// 同`parallel`, 每完成一项tracker加1, 可以在外部通过`tracker.snapshot()`获取速率和ETA,
+/// 按字节统计时在f中调用`tracker.inc_bytes`
+pub async fn parallel_tracked<T, F, FnOut, FnOutT>(
+    par_flag: &str,
+    data_vec: Vec<T>,
+    parallel_limit: usize,
+    progress_bar_share_prefix: &str,
+    tracker: ProgressTracker,
+    f: F,
+) -> AResult<Vec<FnOutT>>
 where
     T: std::fmt::Debug + Send + 'static,
     F: Fn(T, ProgressBar, ProgressBar) -> FnOut,
@@ -107,6 +145,7 @@ where
         let pb_task = unsafe { pb_task_vec.get_unchecked(task_idx) }.clone();
 
         let pb_share = pb_share.clone();
+        let tracker = tracker.clone();
 
         let f = f.clone();
         let rx = rx.clone();
@@ -124,6 +163,8 @@ where
                 // m.println(&msg).unwrap();
                 // pb.set_message(msg);
                 pb_progress.inc(1);
+                tracker.inc(1);
+                pb_progress.set_message(tracker.snapshot().to_string());
                 // 让线程能及时的分配出去
                 tokio::time::sleep(Duration::from_nanos(1)).await;
             }
@@ -162,12 +203,14 @@ where
     // m.clear().unwrap();
 
     let elapsed = start.elapsed();
+    let stats = tracker.snapshot();
     info!(
-        "{} [{}] 结束 {:.3?} {:#}",
+        "{} [{}] 结束 {:.3?} {:#} {:.1}/s",
         par_flag,
         HumanCount(data_len as u64),
         elapsed,
-        HumanDuration(elapsed)
+        HumanDuration(elapsed),
+        stats.items as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
     );
     info!("==================");
 
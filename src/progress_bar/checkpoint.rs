@@ -0,0 +1,181 @@
+//! 断点续传的进度, 用于按天(或其他单位)回补多天的数据
+//!
+//! 每完成一个单位就把每组(如品种)最后完成的单位写入状态文件,
+//! 写入时先写临时文件再改名, 中断后重新运行会从上次停止的地方继续
+//!
+//! 状态文件格式:
+//! ```toml
+//! [done]
+//! ag = "2024-06-03"
+//! rb = "2024-06-04"
+//! ```
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use indicatif::{MultiProgress, ProgressBar};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::progress_bar;
+use crate::AResult;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CheckpointState {
+    /// 组 => 最后完成的单位
+    #[serde(default)]
+    done: BTreeMap<String, String>,
+}
+
+/// 可断点续传的进度
+///
+/// 显示两个进度条: 总进度(包括之前运行已完成的)和本次运行的进度
+pub struct CheckpointedProgress {
+    path:    PathBuf,
+    state:   Mutex<CheckpointState>,
+    _m:      MultiProgress,
+    overall: ProgressBar,
+    session: ProgressBar,
+}
+
+impl CheckpointedProgress {
+    /// 读取状态文件, 文件不存在时从头开始
+    pub fn open(path: impl AsRef<Path>) -> AResult<CheckpointedProgress> {
+        let path = path.as_ref().to_path_buf();
+        let state = if path.exists() {
+            toml::from_str::<CheckpointState>(&fs::read_to_string(&path)?)?
+        } else {
+            CheckpointState::default()
+        };
+        if !state.done.is_empty() {
+            info!("resume from {:?}: {} groups", path, state.done.len());
+        }
+        let m = MultiProgress::new();
+        let overall = m.add(progress_bar(0)).with_message("overall");
+        let session = m.add(progress_bar(0)).with_message("session");
+        Ok(CheckpointedProgress {
+            path,
+            state: Mutex::new(state),
+            _m: m,
+            overall,
+            session,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 组最后完成的单位
+    pub fn last_done(&self, group: &str) -> Option<String> {
+        self.state.lock().unwrap().done.get(group).cloned()
+    }
+
+    /// 按顺序排列的units中还没有完成的部分, 并把数量计入进度条
+    ///
+    /// 最后完成的单位不在units中时全部返回
+    pub fn pending<T>(&self, group: &str, units: &[T]) -> Vec<T>
+    where
+        T: ToString + Clone,
+    {
+        let start = self
+            .last_done(group)
+            .and_then(|last| units.iter().position(|v| v.to_string() == last))
+            .map_or(0, |idx| idx + 1);
+        let pending = units[start..].to_vec();
+        self.overall.inc_length(units.len() as u64);
+        self.overall.inc(start as u64);
+        self.session.inc_length(pending.len() as u64);
+        pending
+    }
+
+    /// 完成一个单位, 立即写入状态文件
+    pub fn complete(&self, group: &str, unit: &impl ToString) -> AResult<()> {
+        let mut state = self.state.lock().unwrap();
+        state.done.insert(group.to_owned(), unit.to_string());
+        self.save(&state)?;
+        self.overall.inc(1);
+        self.session.inc(1);
+        Ok(())
+    }
+
+    /// 清除组的进度, 下次从头开始
+    pub fn reset(&self, group: &str) -> AResult<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.done.remove(group).is_some() {
+            self.save(&state)?;
+        }
+        Ok(())
+    }
+
+    /// (总完成数, 总数, 本次完成数, 本次总数)
+    pub fn position(&self) -> (u64, u64, u64, u64) {
+        (
+            self.overall.position(),
+            self.overall.length().unwrap_or_default(),
+            self.session.position(),
+            self.session.length().unwrap_or_default(),
+        )
+    }
+
+    pub fn finish(&self) {
+        self.session.finish_with_message("session finish");
+        self.overall.finish_with_message("finish");
+    }
+
+    /// 先写临时文件再改名, 避免中断时留下不完整的状态文件
+    fn save(&self, state: &CheckpointState) -> AResult<()> {
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let mut tmp_name = self.path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = self.path.with_file_name(tmp_name);
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(toml::to_string(state)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::CheckpointedProgress;
+
+    #[test]
+    fn test_checkpoint_resume() {
+        let path = "./_data/checkpoint-test/state.toml";
+        let _ = fs::remove_file(path);
+        let days = ["2024-06-03", "2024-06-04", "2024-06-05"];
+
+        let progress = CheckpointedProgress::open(path).unwrap();
+        assert_eq!(progress.pending("ag", &days), days);
+        progress.complete("ag", &days[0]).unwrap();
+        progress.complete("ag", &days[1]).unwrap();
+        assert_eq!(progress.position(), (2, 3, 2, 3));
+        // 中断
+        drop(progress);
+
+        let progress = CheckpointedProgress::open(path).unwrap();
+        assert_eq!(progress.last_done("ag").as_deref(), Some(days[1]));
+        assert_eq!(progress.pending("ag", &days), [days[2]]);
+        assert_eq!(progress.pending("rb", &days), days);
+        assert_eq!(progress.position(), (2, 6, 0, 4));
+        progress.complete("ag", &days[2]).unwrap();
+        assert_eq!(progress.position(), (3, 6, 1, 4));
+        progress.finish();
+
+        progress.reset("ag").unwrap();
+        let progress = CheckpointedProgress::open(path).unwrap();
+        assert_eq!(progress.last_done("ag"), None);
+
+        fs::remove_dir_all("./_data/checkpoint-test").unwrap();
+    }
+}
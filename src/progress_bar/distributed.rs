@@ -0,0 +1,225 @@
+//! 多台机器一起回补数据时的总进度
+//!
+//! 每个worker完成后在redis的hash中累加自己的数量(field为worker名),
+//! 由一个coordinator定时读取所有worker的数量, 显示合并后的进度条
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use eyre::eyre;
+use redis::aio::MultiplexedConnection;
+use redis::{Client, Connection, RedisResult};
+
+use super::{progress_bar, ProgressTracker};
+use crate::AResult;
+
+/// 在redis中累加worker的进度
+pub struct ProgressWorker {
+    client: Arc<Client>,
+    key:    String,
+    worker: String,
+    ttl:    Duration,
+    con:    Mutex<Option<Connection>>,
+}
+
+impl ProgressWorker {
+    /// key: 保存进度的hash, 同一个任务的worker和coordinator使用相同的key
+    /// worker: worker名, 如主机名加序号, 不能重复
+    pub fn new(client: Arc<Client>, key: &str, worker: &str) -> ProgressWorker {
+        ProgressWorker {
+            client,
+            key: key.to_owned(),
+            worker: worker.to_owned(),
+            ttl: Duration::from_secs(86400),
+            con: Mutex::new(None),
+        }
+    }
+
+    /// 进度的过期时间, 每次累加时刷新, 默认1天
+    pub fn with_ttl(self, ttl: Duration) -> Self {
+        ProgressWorker { ttl, ..self }
+    }
+
+    /// 累加完成的数量, 返回当前worker的累计数量
+    pub fn inc(&self, n: u64) -> RedisResult<u64> {
+        let mut con = self.con.lock().unwrap();
+        if con.is_none() {
+            *con = Some(self.client.get_connection()?);
+        }
+        let (count,): (u64,) = redis::pipe()
+            .cmd("HINCRBY")
+            .arg(&self.key)
+            .arg(&self.worker)
+            .arg(n)
+            .cmd("EXPIRE")
+            .arg(&self.key)
+            .arg(self.ttl.as_secs().max(1))
+            .ignore()
+            .query(con.as_mut().unwrap())
+            .inspect_err(|_| *con = None)?;
+        Ok(count)
+    }
+}
+
+/// 所有worker的累计数量
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DistributedSnapshot {
+    pub workers: BTreeMap<String, u64>,
+}
+
+impl DistributedSnapshot {
+    pub fn done(&self) -> u64 {
+        self.workers.values().sum()
+    }
+}
+
+impl std::fmt::Display for DistributedSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "workers: {}", self.workers.len())?;
+        for (worker, count) in &self.workers {
+            write!(f, " {}:{}", worker, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// 读取所有worker的进度并显示合并后的进度条
+pub struct ProgressCoordinator {
+    client:       Arc<Client>,
+    key:          String,
+    total:        u64,
+    interval:     Duration,
+    idle_timeout: Option<Duration>,
+}
+
+impl ProgressCoordinator {
+    /// total: 所有worker的总数量
+    pub fn new(client: Arc<Client>, key: &str, total: u64) -> ProgressCoordinator {
+        ProgressCoordinator {
+            client,
+            key: key.to_owned(),
+            total,
+            interval: Duration::from_secs(1),
+            idle_timeout: None,
+        }
+    }
+
+    /// 读取redis的间隔, 默认1秒
+    pub fn with_interval(self, interval: Duration) -> Self {
+        ProgressCoordinator { interval, ..self }
+    }
+
+    /// 超过该时间进度没有变化时结束并返回错误, 避免worker退出后一直等待
+    pub fn with_idle_timeout(self, idle_timeout: Duration) -> Self {
+        ProgressCoordinator {
+            idle_timeout: Some(idle_timeout),
+            ..self
+        }
+    }
+
+    pub fn snapshot(&self) -> RedisResult<DistributedSnapshot> {
+        let mut con = self.client.get_connection()?;
+        let workers = redis::cmd("HGETALL").arg(&self.key).query(&mut con)?;
+        Ok(DistributedSnapshot { workers })
+    }
+
+    /// 清除之前的进度, 在启动worker前调用
+    pub fn reset(&self) -> RedisResult<()> {
+        let mut con = self.client.get_connection()?;
+        redis::cmd("DEL").arg(&self.key).query(&mut con)
+    }
+
+    async fn snapshot_async(
+        &self,
+        con: &mut MultiplexedConnection,
+    ) -> RedisResult<DistributedSnapshot> {
+        let workers = redis::cmd("HGETALL")
+            .arg(&self.key)
+            .query_async(con)
+            .await?;
+        Ok(DistributedSnapshot { workers })
+    }
+
+    /// 显示进度条直到所有worker的数量达到total, 整个过程使用一个异步连接
+    pub async fn run(&self) -> AResult<DistributedSnapshot> {
+        let mut con = self.client.get_multiplexed_tokio_connection().await?;
+        let pb = progress_bar(self.total);
+        let tracker = ProgressTracker::new(Some(self.total));
+        let mut done = 0;
+        let mut changed_at = Instant::now();
+        loop {
+            let snapshot = self.snapshot_async(&mut con).await?;
+            let current = snapshot.done();
+            if current > done {
+                tracker.inc(current - done);
+                done = current;
+                changed_at = Instant::now();
+            }
+            pb.set_position(done.min(self.total));
+            pb.set_message(format!("{} {}", tracker.snapshot(), snapshot));
+            if done >= self.total {
+                pb.finish_with_message(format!("finish {}", snapshot));
+                return Ok(snapshot);
+            }
+            if self.idle_timeout.is_some_and(|v| changed_at.elapsed() >= v) {
+                pb.abandon_with_message(format!("idle {}", snapshot));
+                return Err(eyre!(
+                    "distributed progress idle for {:?}: {}/{}",
+                    changed_at.elapsed(),
+                    done,
+                    self.total
+                ));
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use super::{DistributedSnapshot, ProgressCoordinator, ProgressWorker};
+    use crate::redis::{Keyspace, RedisClients};
+
+    #[test]
+    fn test_snapshot() {
+        let snapshot = DistributedSnapshot {
+            workers: BTreeMap::from([("h1-0".to_string(), 3), ("h2-0".to_string(), 5)]),
+        };
+        assert_eq!(snapshot.done(), 8);
+        assert_eq!(snapshot.to_string(), "workers: 2 h1-0:3 h2-0:5");
+    }
+
+    #[tokio::test]
+    async fn test_distributed() {
+        RedisClients::init_clients("./_cfg/c-redis-rs.yaml").unwrap();
+        let key = Keyspace::new("hq", "test", 1).key("progress", &["backfill"]);
+        let coordinator = ProgressCoordinator::new(RedisClients::client(), &key, 20)
+            .with_interval(Duration::from_millis(100))
+            .with_idle_timeout(Duration::from_secs(5));
+        coordinator.reset().unwrap();
+
+        let handles = (0..2)
+            .map(|i| {
+                let key = key.clone();
+                tokio::spawn(async move {
+                    let worker =
+                        ProgressWorker::new(RedisClients::client(), &key, &format!("w{}", i));
+                    for _ in 0..10 {
+                        worker.inc(1).unwrap();
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        let snapshot = coordinator.run().await.unwrap();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(snapshot.done(), 20);
+        assert_eq!(snapshot.workers.len(), 2);
+        coordinator.reset().unwrap();
+    }
+}
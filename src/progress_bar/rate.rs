@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use indicatif::{HumanBytes, HumanDuration};
+
+/// 默认的统计窗口
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(10);
+/// ETA平滑系数, 越大越接近当前值
+const ETA_ALPHA: f64 = 0.3;
+
+/// 进度统计
+/// items_per_sec, bytes_per_sec: 窗口内的速率
+/// eta: 平滑后的剩余时间, 没有总数或速率为0时为None
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressStats {
+    pub total:         Option<u64>,
+    pub items:         u64,
+    pub bytes:         u64,
+    pub elapsed:       Duration,
+    pub items_per_sec: f64,
+    pub bytes_per_sec: f64,
+    pub eta:           Option<Duration>,
+}
+
+impl fmt::Display for ProgressStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1}/s", self.items_per_sec)?;
+        if self.bytes > 0 {
+            write!(f, " {}/s", HumanBytes(self.bytes_per_sec as u64))?;
+        }
+        if let Some(eta) = self.eta {
+            write!(f, " ETA {}", HumanDuration(eta))?;
+        }
+        Ok(())
+    }
+}
+
+/// 滑动窗口速率估算, 样本为(时间, 累计数量, 累计字节)
+#[derive(Debug)]
+struct RateEstimator {
+    start:   Instant,
+    window:  Duration,
+    total:   Option<u64>,
+    items:   u64,
+    bytes:   u64,
+    samples: VecDeque<(Instant, u64, u64)>,
+    eta:     Option<f64>,
+}
+
+impl RateEstimator {
+    fn new(start: Instant, window: Duration, total: Option<u64>) -> RateEstimator {
+        RateEstimator {
+            start,
+            window,
+            total,
+            items: 0,
+            bytes: 0,
+            samples: VecDeque::from([(start, 0, 0)]),
+            eta: None,
+        }
+    }
+
+    fn record_at(&mut self, now: Instant, items: u64, bytes: u64) {
+        self.items += items;
+        self.bytes += bytes;
+        self.samples.push_back((now, self.items, self.bytes));
+        // 保留一个窗口外的样本作为起点
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= self.window {
+            self.samples.pop_front();
+        }
+        self.update_eta(now);
+    }
+
+    fn rates(&self, now: Instant) -> (f64, f64) {
+        let Some((t0, items0, bytes0)) = self.samples.front() else {
+            return (0.0, 0.0);
+        };
+        let secs = now.duration_since(*t0).as_secs_f64();
+        if secs <= 0.0 {
+            return (0.0, 0.0);
+        }
+        (
+            (self.items - items0) as f64 / secs,
+            (self.bytes - bytes0) as f64 / secs,
+        )
+    }
+
+    fn update_eta(&mut self, now: Instant) {
+        let (items_per_sec, _) = self.rates(now);
+        let Some(total) = self.total else {
+            return;
+        };
+        if items_per_sec <= 0.0 {
+            return;
+        }
+        let eta = total.saturating_sub(self.items) as f64 / items_per_sec;
+        self.eta = Some(match self.eta {
+            Some(prev) => ETA_ALPHA * eta + (1.0 - ETA_ALPHA) * prev,
+            None => eta,
+        });
+    }
+
+    fn snapshot_at(&self, now: Instant) -> ProgressStats {
+        let (items_per_sec, bytes_per_sec) = self.rates(now);
+        let eta = match self.total {
+            Some(total) if self.items >= total => Some(0.0),
+            _ => self.eta,
+        };
+        ProgressStats {
+            total: self.total,
+            items: self.items,
+            bytes: self.bytes,
+            elapsed: now.duration_since(self.start),
+            items_per_sec,
+            bytes_per_sec,
+            eta: eta.map(Duration::from_secs_f64),
+        }
+    }
+}
+
+/// 可以在多个任务间共享的进度统计
+#[derive(Debug, Clone)]
+pub struct ProgressTracker {
+    inner: Arc<Mutex<RateEstimator>>,
+}
+
+impl ProgressTracker {
+    pub fn new(total: Option<u64>) -> ProgressTracker {
+        ProgressTracker::with_window(total, DEFAULT_WINDOW)
+    }
+
+    pub fn with_window(total: Option<u64>, window: Duration) -> ProgressTracker {
+        ProgressTracker {
+            inner: Arc::new(Mutex::new(RateEstimator::new(
+                Instant::now(),
+                window,
+                total,
+            ))),
+        }
+    }
+
+    pub fn inc(&self, items: u64) {
+        self.record(items, 0);
+    }
+
+    pub fn inc_bytes(&self, bytes: u64) {
+        self.record(0, bytes);
+    }
+
+    pub fn record(&self, items: u64, bytes: u64) {
+        self.inner
+            .lock()
+            .unwrap()
+            .record_at(Instant::now(), items, bytes);
+    }
+
+    pub fn snapshot(&self) -> ProgressStats {
+        self.inner.lock().unwrap().snapshot_at(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::RateEstimator;
+
+    #[test]
+    fn test_rate_estimator() {
+        let start = Instant::now();
+        let secs = |v: u64| start + Duration::from_secs(v);
+        let mut estimator = RateEstimator::new(start, Duration::from_secs(10), Some(100));
+        for i in 1..=10 {
+            estimator.record_at(secs(i), 2, 1024 * 1024);
+        }
+        let stats = estimator.snapshot_at(secs(10));
+        println!("{:?} {}", stats, stats);
+        assert_eq!(stats.items, 20);
+        assert!((stats.items_per_sec - 2.0).abs() < 1e-9);
+        assert!((stats.bytes_per_sec - 1024.0 * 1024.0).abs() < 1e-6);
+        // 平滑后的ETA略滞后于当前值40s
+        let eta = stats.eta.unwrap().as_secs();
+        assert!((40..45).contains(&eta));
+
+        // 变慢后窗口内速率下降, ETA变长
+        for i in 1..=10 {
+            estimator.record_at(secs(10 + i * 2), 1, 0);
+        }
+        let stats = estimator.snapshot_at(secs(30));
+        println!("{}", stats);
+        assert!((stats.items_per_sec - 0.5).abs() < 0.1);
+        assert!(stats.eta.unwrap() > Duration::from_secs(40));
+
+        estimator.record_at(secs(31), 70, 0);
+        assert_eq!(estimator.snapshot_at(secs(31)).eta, Some(Duration::ZERO));
+    }
+}
@@ -1,5 +1,15 @@
+pub mod aggregate;
 pub mod breed;
+pub mod fanout;
+pub mod fill;
+pub mod indicators;
 pub mod klineitem;
 pub mod klinetime;
+pub mod latency;
 pub mod period;
+pub mod replay;
+pub mod tick2bar;
 pub mod trading_day;
+pub mod validate;
+#[cfg(feature = "redis")]
+pub mod write_behind;
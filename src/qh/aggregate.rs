@@ -0,0 +1,104 @@
+//! 1m K线合成大周期K线
+
+use chrono::NaiveDateTime;
+
+use super::klineitem::KLineItem;
+use super::latency::{LatencyStage, StageTimer};
+use super::period::Period;
+
+/// 把后一根小周期K线合并到大周期K线中
+pub(crate) fn merge_bar(item: &mut KLineItem, bar: &KLineItem) {
+    item.high = item.high.max(bar.high);
+    item.low = item.low.min(bar.low);
+    item.close = bar.close;
+    item.volume += bar.volume;
+    item.total_volume = bar.total_volume;
+    item.close_oi = bar.close_oi;
+    item.last_item_time = item.last_item_time.max(bar.last_item_time);
+}
+
+/// 把按时间正序的小周期K线合成为period周期的K线
+///
+/// bar_time: K线时间转成所属大周期K线的时间, 返回None的K线忽略,
+/// 可以使用`ConvertToXm::time_range_period`的结束时间
+pub fn aggregate_bars<F>(bars: &[KLineItem], period: Period, bar_time: F) -> Vec<KLineItem>
+where
+    F: Fn(&NaiveDateTime) -> Option<NaiveDateTime>,
+{
+    let timer = StageTimer::start(LatencyStage::Aggregate);
+    let mut items: Vec<KLineItem> = Vec::new();
+    for bar in bars {
+        let Some(datetime) = bar_time(&bar.datetime) else {
+            continue;
+        };
+        match items.last_mut() {
+            Some(item) if item.datetime == datetime && item.code == bar.code => {
+                merge_bar(item, bar);
+            },
+            _ => {
+                let mut item = bar.clone();
+                item.datetime = datetime;
+                item.period = period.minutes() as i32;
+                items.push(item);
+            },
+        }
+    }
+    timer.finish();
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDateTime, Timelike};
+    use rust_decimal::Decimal;
+
+    use super::aggregate_bars;
+    use crate::qh::klineitem::{fixture, KLineItem};
+    use crate::qh::period::Period;
+
+    fn bar(minute: i64, price: i64, volume: i64, total_volume: i64) -> KLineItem {
+        KLineItem {
+            high: Decimal::from(price + 2),
+            low: Decimal::from(price - 2),
+            close: Decimal::from(price + 1),
+            volume,
+            total_volume,
+            open_oi: total_volume,
+            close_oi: total_volume + 1,
+            ..fixture::bar("ag2408", minute, price)
+        }
+    }
+
+    fn bar_time_5m(dt: &NaiveDateTime) -> Option<NaiveDateTime> {
+        let offset = (5 - dt.minute() % 5) % 5;
+        Some(*dt + Duration::try_minutes(offset as i64).unwrap())
+    }
+
+    #[test]
+    fn test_aggregate_bars() {
+        let bars = vec![
+            bar(1, 100, 10, 10),
+            bar(2, 105, 20, 30),
+            bar(5, 98, 5, 35),
+            bar(6, 101, 7, 42),
+        ];
+        let items = aggregate_bars(&bars, Period::M5, bar_time_5m);
+        for item in items.iter() {
+            println!("{}", item);
+        }
+        assert_eq!(items.len(), 2);
+        let first = &items[0];
+        assert_eq!(first.datetime.minute(), 5);
+        assert_eq!(first.period, 5);
+        assert_eq!(first.open, Decimal::from(100));
+        assert_eq!(first.high, Decimal::from(107));
+        assert_eq!(first.low, Decimal::from(96));
+        assert_eq!(first.close, Decimal::from(99));
+        assert_eq!(first.volume, 35);
+        assert_eq!(first.total_volume, 35);
+        assert_eq!(first.open_oi, 10);
+        assert_eq!(first.close_oi, 36);
+        assert_eq!(items[1].datetime.minute(), 10);
+        assert_eq!(items[1].volume, 7);
+    }
+}
@@ -7,11 +7,47 @@ use sqlx::MySqlPool;
 const A_Z_LOWER_RANGE: RangeInclusive<char> = 'a'..='z';
 const A_Z_UPPER_RANGE: RangeInclusive<char> = 'A'..='Z';
 
+/// 连续合约的后缀, 按序号从大到小
+const CONTINUOUS_RANKS: [u8; 2] = [9, 8];
+
+/// 拆分连续合约代码为品种和序号, 如agL9为("ag", 9)
+fn split_continuous(code: &str) -> Option<(&str, u8)> {
+    let (breed, suffix) = code.split_at_checked(code.len().checked_sub(2)?)?;
+    let rank = suffix.strip_prefix('L')?.parse::<u8>().ok()?;
+    if breed.is_empty()
+        || !CONTINUOUS_RANKS.contains(&rank)
+        || !breed.chars().all(|c| c.is_ascii_alphabetic())
+    {
+        return None;
+    }
+    Some((breed, rank))
+}
+
+/// 是否为连续合约, 如agL9, agL8
+pub fn is_continuous(code: &str) -> bool {
+    split_continuous(code).is_some()
+}
+
+/// 连续合约的序号, agL9为9, agL8为8, 不是连续合约时为None
+pub fn continuous_rank(code: &str) -> Option<u8> {
+    split_continuous(code).map(|(_, rank)| rank)
+}
+
+/// code所属品种的全部连续合约代码, 按序号从大到小
+pub fn family(code: &str) -> Vec<String> {
+    let breed = breed_from_symbol(code);
+    if breed.is_empty() {
+        return Vec::new();
+    }
+    CONTINUOUS_RANKS
+        .iter()
+        .map(|rank| format!("{}L{}", breed, rank))
+        .collect()
+}
+
 pub fn breed_from_symbol(symbol: &str) -> String {
-    if symbol.ends_with("L9") {
-        return symbol.replace("L9", "");
-    } else if symbol.ends_with("L8") {
-        return symbol.replace("L8", "");
+    if let Some((breed, _)) = split_continuous(symbol) {
+        return breed.to_owned();
     }
     symbol
         .chars()
@@ -49,8 +85,13 @@ impl BreedInfoVec {
         BREED_INFO_VEC.get().unwrap()
     }
 
+    /// 是否已初始化
+    pub fn is_initialized() -> bool {
+        BREED_INFO_VEC.get().is_some()
+    }
+
     pub async fn init(pool: &MySqlPool) -> Result<(), sqlx::Error> {
-        if !Self::current().is_empty() {
+        if BREED_INFO_VEC.get().is_some() {
             return Ok(());
         }
         let breed_info_vec = Self::init_from_db(pool).await?;
@@ -80,7 +121,7 @@ impl BreedInfoVec {
 
 #[cfg(test)]
 mod tests {
-    use super::{breed_from_symbol, BreedInfoVec};
+    use super::{breed_from_symbol, continuous_rank, family, is_continuous, BreedInfoVec};
     use crate::mysqlx::MySqlPools;
     use crate::mysqlx_test_pool::init_test_mysql_pools;
 
@@ -94,6 +135,22 @@ mod tests {
         println!("3: {}", breed);
     }
 
+    #[test]
+    fn test_continuous() {
+        assert!(is_continuous("agL9"));
+        assert!(is_continuous("APL8"));
+        assert!(!is_continuous("ag2408"));
+        assert!(!is_continuous("L9"));
+        assert!(!is_continuous("agL7"));
+        assert_eq!(continuous_rank("agL9"), Some(9));
+        assert_eq!(continuous_rank("agL8"), Some(8));
+        assert_eq!(continuous_rank("ag2408"), None);
+        assert_eq!(family("ag2408"), vec!["agL9", "agL8"]);
+        assert_eq!(family("agL8"), vec!["agL9", "agL8"]);
+        assert!(family("2408").is_empty());
+        assert_eq!(breed_from_symbol("APL9"), "AP");
+    }
+
     #[tokio::test]
     async fn test_breed_list_from_db() {
         init_test_mysql_pools();
@@ -0,0 +1,183 @@
+//! 由1m K线流同时合成多个周期的K线
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+
+use super::aggregate::merge_bar;
+use super::klineitem::KLineItem;
+use super::klinetime::convert_to_xm::ConvertToXm;
+use super::klinetime::{KLineTimeError, TimeRangeDateTime};
+use super::period::Period;
+
+/// 默认合成的周期
+pub const DEFAULT_PERIODS: [Period; 6] = [
+    Period::M5,
+    Period::M15,
+    Period::M30,
+    Period::M60,
+    Period::M120,
+    Period::D1,
+];
+
+type TimeRangeFn =
+    dyn Fn(&str, Period, &NaiveDateTime) -> Result<TimeRangeDateTime, KLineTimeError> + Send + Sync;
+
+/// 多周期合成, 每个合约每个周期保留一根未完成的K线
+///
+/// 1m K线到达所属时间范围的最后一分钟时输出该周期的K线,
+/// 缺少最后一分钟时, 在下一个时间范围的K线到达时输出
+pub struct PeriodFanout {
+    periods:    Vec<Period>,
+    time_range: Box<TimeRangeFn>,
+    open_bars:  HashMap<(String, Period), (KLineItem, TimeRangeDateTime)>,
+}
+
+impl PeriodFanout {
+    /// 使用`ConvertToXm`计算时间范围, 需要先初始化klinetime
+    pub fn new(periods: &[Period]) -> PeriodFanout {
+        let convert = ConvertToXm::default();
+        PeriodFanout::with_time_range(periods, move |breed, period, datetime| {
+            convert.time_range_period(breed, period, datetime)
+        })
+    }
+
+    /// time_range: (品种, 周期, 1m时间) => 所属K线的时间范围
+    pub fn with_time_range<F>(periods: &[Period], time_range: F) -> PeriodFanout
+    where
+        F: Fn(&str, Period, &NaiveDateTime) -> Result<TimeRangeDateTime, KLineTimeError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        PeriodFanout {
+            periods:    periods.to_vec(),
+            time_range: Box::new(time_range),
+            open_bars:  HashMap::new(),
+        }
+    }
+
+    pub fn periods(&self) -> &[Period] {
+        &self.periods
+    }
+
+    /// 未完成的K线
+    pub fn open_bar(&self, code: &str, period: Period) -> Option<&KLineItem> {
+        self.open_bars
+            .get(&(code.to_owned(), period))
+            .map(|(item, _)| item)
+    }
+
+    /// 输入一根已完成的1m K线, 返回这次完成的各周期K线
+    pub fn update(&mut self, bar: &KLineItem) -> Result<Vec<KLineItem>, KLineTimeError> {
+        let breed = bar.breed();
+        let mut closed = Vec::new();
+        for period in self.periods.iter().copied() {
+            let range = (self.time_range)(&breed, period, &bar.datetime)?;
+            let key = (bar.code.clone(), period);
+            match self.open_bars.get_mut(&key) {
+                Some((item, open_range)) if *open_range == range => merge_bar(item, bar),
+                _ => {
+                    let mut item = bar.clone();
+                    item.datetime = range.end;
+                    item.period = period.minutes() as i32;
+                    if let Some((prev, _)) = self.open_bars.insert(key.clone(), (item, range)) {
+                        closed.push(prev);
+                    }
+                },
+            }
+            if bar.datetime >= range.end {
+                if let Some((item, _)) = self.open_bars.remove(&key) {
+                    closed.push(item);
+                }
+            }
+        }
+        Ok(closed)
+    }
+
+    /// 输出合约所有未完成的K线, 如收盘后数据缺少最后一分钟
+    pub fn flush(&mut self, code: &str) -> Vec<KLineItem> {
+        let keys = self
+            .open_bars
+            .keys()
+            .filter(|(v, _)| v == code)
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut items = keys
+            .iter()
+            .filter_map(|key| self.open_bars.remove(key).map(|(item, _)| item))
+            .collect::<Vec<_>>();
+        items.sort_by_key(|v| v.period);
+        items
+    }
+
+    /// 输出所有未完成的K线
+    pub fn flush_all(&mut self) -> Vec<KLineItem> {
+        let mut items = self
+            .open_bars
+            .drain()
+            .map(|(_, (item, _))| item)
+            .collect::<Vec<_>>();
+        items.sort_by(|a, b| a.code.cmp(&b.code).then(a.period.cmp(&b.period)));
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDateTime, Timelike};
+    use rust_decimal::Decimal;
+
+    use super::PeriodFanout;
+    use crate::qh::klineitem::{fixture, KLineItem};
+    use crate::qh::klinetime::{KLineTimeError, TimeRangeDateTime};
+    use crate::qh::period::Period;
+
+    fn bar(minute: i64, price: i64) -> KLineItem {
+        KLineItem {
+            volume: 1,
+            ..fixture::bar("ag2408", minute, price)
+        }
+    }
+
+    /// 按自然时间整除的时间范围
+    fn time_range(
+        _breed: &str,
+        period: Period,
+        dt: &NaiveDateTime,
+    ) -> Result<TimeRangeDateTime, KLineTimeError> {
+        let minutes = period.minutes() as u32;
+        let offset = (minutes - dt.minute() % minutes) % minutes;
+        let end = *dt + Duration::minutes(offset as i64);
+        Ok(TimeRangeDateTime {
+            start: end - Duration::minutes(minutes as i64 - 1),
+            end,
+        })
+    }
+
+    #[test]
+    fn test_fanout() {
+        let mut fanout = PeriodFanout::with_time_range(&[Period::M5, Period::M15], time_range);
+        let mut closed = Vec::new();
+        // 缺少09:10
+        for minute in [1, 2, 3, 4, 5, 6, 7, 8, 9, 11, 12] {
+            closed.extend(fanout.update(&bar(minute, minute)).unwrap());
+        }
+        let times = closed
+            .iter()
+            .map(|v| (v.period, v.datetime.minute(), v.volume))
+            .collect::<Vec<_>>();
+        assert_eq!(times, [(5, 5, 5), (5, 10, 4)]);
+        assert_eq!(closed[0].open, Decimal::from(1));
+        assert_eq!(closed[0].close, Decimal::from(5));
+
+        let open = fanout.open_bar("ag2408", Period::M15).unwrap();
+        assert_eq!(open.volume, 11);
+        assert_eq!(open.datetime.minute(), 15);
+
+        let rest = fanout.flush("ag2408");
+        assert_eq!(rest.len(), 2);
+        assert_eq!(rest[0].period, 5);
+        assert_eq!(rest[0].volume, 2);
+        assert!(fanout.flush_all().is_empty());
+    }
+}
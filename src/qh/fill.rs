@@ -0,0 +1,94 @@
+//! 按交易时间的分钟补齐缺失的1分钟K线
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+
+use super::klineitem::KLineItem;
+
+/// synthetic: 补出来的K线
+#[derive(Debug, Clone)]
+pub struct FilledBar {
+    pub bar:       KLineItem,
+    pub synthetic: bool,
+}
+
+/// 按expected_minutes(如TimeRange::day_minutes的结果)补齐缺失的K线,
+/// 补的K线成交量为0, 开高低收都为上一根K线的收盘价, 持仓不变.
+/// 第一根实际K线之前缺失的分钟没有可用的收盘价, 不补;
+/// 不在expected_minutes中的K线不输出.
+pub fn forward_fill(bars: &[KLineItem], expected_minutes: &[NaiveDateTime]) -> Vec<FilledBar> {
+    let bar_map = bars
+        .iter()
+        .map(|v| (v.datetime, v))
+        .collect::<HashMap<_, _>>();
+
+    let mut r = Vec::with_capacity(expected_minutes.len());
+    let mut prev: Option<&KLineItem> = None;
+    for minute in expected_minutes {
+        if let Some(bar) = bar_map.get(minute) {
+            r.push(FilledBar {
+                bar:       (*bar).clone(),
+                synthetic: false,
+            });
+            prev = Some(bar);
+        } else if let Some(prev) = prev {
+            let mut bar = KLineItem::new(&prev.code, minute, prev.period);
+            bar.open = prev.close;
+            bar.high = prev.close;
+            bar.low = prev.close;
+            bar.close = prev.close;
+            bar.total_volume = prev.total_volume;
+            bar.open_oi = prev.close_oi;
+            bar.close_oi = prev.close_oi;
+            r.push(FilledBar {
+                bar,
+                synthetic: true,
+            });
+        }
+    }
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::forward_fill;
+    use crate::qh::klineitem::fixture::dt;
+    use crate::qh::klineitem::{fixture, KLineItem};
+
+    fn bar(minute: i64, close: i64) -> KLineItem {
+        KLineItem {
+            volume: 10,
+            total_volume: 100 + minute,
+            close_oi: 500,
+            ..fixture::bar("agL9", minute, close)
+        }
+    }
+
+    #[test]
+    fn test_forward_fill() {
+        let bars = vec![bar(2, 100), bar(4, 101), bar(30, 99)];
+        let expected = (1..=6).map(dt).collect::<Vec<_>>();
+        let filled = forward_fill(&bars, &expected);
+        for v in filled.iter() {
+            println!("{} {}", v.synthetic, v.bar);
+        }
+        assert_eq!(
+            filled.iter().map(|v| v.bar.datetime).collect::<Vec<_>>(),
+            (2..=6).map(dt).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            filled.iter().map(|v| v.synthetic).collect::<Vec<_>>(),
+            vec![false, true, false, true, true]
+        );
+        let synthetic = &filled[1].bar;
+        assert_eq!(synthetic.open, Decimal::from(100));
+        assert_eq!(synthetic.low, Decimal::from(100));
+        assert_eq!(synthetic.volume, 0);
+        assert_eq!(synthetic.total_volume, 102);
+        assert_eq!(synthetic.close_oi, 500);
+        assert_eq!(filled[4].bar.close, Decimal::from(101));
+    }
+}
@@ -0,0 +1,289 @@
+//! 逐根K线增量计算的指标, 每次更新O(1)
+//!
+//! 数据不足一个周期时update返回None
+use std::collections::VecDeque;
+
+use rust_decimal::Decimal;
+
+use super::klineitem::KLineItem;
+
+/// 按K线逐根更新的指标
+pub trait Indicator {
+    type Output;
+
+    fn update_bar(&mut self, bar: &KLineItem) -> Option<Self::Output>;
+
+    fn reset(&mut self);
+}
+
+fn check_period(period: usize) -> usize {
+    assert!(period > 0, "period must be greater than 0");
+    period
+}
+
+/// 简单移动平均
+#[derive(Debug, Clone)]
+pub struct Sma {
+    period: usize,
+    window: VecDeque<Decimal>,
+    sum:    Decimal,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Sma {
+        Sma {
+            period: check_period(period),
+            window: VecDeque::with_capacity(period),
+            sum:    Decimal::ZERO,
+        }
+    }
+
+    pub fn update(&mut self, value: Decimal) -> Option<Decimal> {
+        self.window.push_back(value);
+        self.sum += value;
+        if self.window.len() > self.period {
+            self.sum -= self.window.pop_front().unwrap();
+        }
+        self.value()
+    }
+
+    pub fn value(&self) -> Option<Decimal> {
+        (self.window.len() == self.period).then(|| self.sum / Decimal::from(self.period))
+    }
+}
+
+impl Indicator for Sma {
+    type Output = Decimal;
+
+    /// 使用收盘价
+    fn update_bar(&mut self, bar: &KLineItem) -> Option<Decimal> {
+        self.update(bar.close)
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+        self.sum = Decimal::ZERO;
+    }
+}
+
+/// 指数移动平均, alpha=2/(period+1), 第一个值为前period个值的简单平均
+#[derive(Debug, Clone)]
+pub struct Ema {
+    alpha: Decimal,
+    seed:  Sma,
+    value: Option<Decimal>,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Ema {
+        Ema {
+            alpha: Decimal::TWO / Decimal::from(check_period(period) + 1),
+            seed:  Sma::new(period),
+            value: None,
+        }
+    }
+
+    pub fn update(&mut self, value: Decimal) -> Option<Decimal> {
+        self.value = match self.value {
+            Some(prev) => Some(prev + self.alpha * (value - prev)),
+            None => self.seed.update(value),
+        };
+        self.value
+    }
+
+    pub fn value(&self) -> Option<Decimal> {
+        self.value
+    }
+}
+
+impl Indicator for Ema {
+    type Output = Decimal;
+
+    /// 使用收盘价
+    fn update_bar(&mut self, bar: &KLineItem) -> Option<Decimal> {
+        self.update(bar.close)
+    }
+
+    fn reset(&mut self) {
+        self.seed.reset();
+        self.value = None;
+    }
+}
+
+/// 平均真实波幅, Wilder平滑, 第一个值为前period个真实波幅的简单平均
+#[derive(Debug, Clone)]
+pub struct Atr {
+    period:     Decimal,
+    seed:       Sma,
+    prev_close: Option<Decimal>,
+    value:      Option<Decimal>,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Atr {
+        Atr {
+            period:     Decimal::from(check_period(period)),
+            seed:       Sma::new(period),
+            prev_close: None,
+            value:      None,
+        }
+    }
+
+    /// 真实波幅, 第一根K线为high-low
+    fn true_range(&self, high: Decimal, low: Decimal) -> Decimal {
+        let range = high - low;
+        match self.prev_close {
+            Some(prev_close) => range
+                .max((high - prev_close).abs())
+                .max((low - prev_close).abs()),
+            None => range,
+        }
+    }
+
+    pub fn update(&mut self, high: Decimal, low: Decimal, close: Decimal) -> Option<Decimal> {
+        let tr = self.true_range(high, low);
+        self.prev_close = Some(close);
+        self.value = match self.value {
+            Some(prev) => Some((prev * (self.period - Decimal::ONE) + tr) / self.period),
+            None => self.seed.update(tr),
+        };
+        self.value
+    }
+
+    pub fn value(&self) -> Option<Decimal> {
+        self.value
+    }
+}
+
+impl Indicator for Atr {
+    type Output = Decimal;
+
+    fn update_bar(&mut self, bar: &KLineItem) -> Option<Decimal> {
+        self.update(bar.high, bar.low, bar.close)
+    }
+
+    fn reset(&mut self) {
+        self.seed.reset();
+        self.prev_close = None;
+        self.value = None;
+    }
+}
+
+/// 最近period根K线的最高价和最低价, 单调队列实现, 均摊O(1)
+#[derive(Debug, Clone)]
+pub struct HighLowRange {
+    period: usize,
+    count:  usize,
+    highs:  VecDeque<(usize, Decimal)>,
+    lows:   VecDeque<(usize, Decimal)>,
+}
+
+impl HighLowRange {
+    pub fn new(period: usize) -> HighLowRange {
+        HighLowRange {
+            period: check_period(period),
+            count:  0,
+            highs:  VecDeque::new(),
+            lows:   VecDeque::new(),
+        }
+    }
+
+    /// 返回(最高价, 最低价)
+    pub fn update(&mut self, high: Decimal, low: Decimal) -> Option<(Decimal, Decimal)> {
+        let idx = self.count;
+        self.count += 1;
+        while self.highs.back().is_some_and(|v| v.1 <= high) {
+            self.highs.pop_back();
+        }
+        self.highs.push_back((idx, high));
+        while self.lows.back().is_some_and(|v| v.1 >= low) {
+            self.lows.pop_back();
+        }
+        self.lows.push_back((idx, low));
+        if let Some(start) = self.count.checked_sub(self.period) {
+            while self.highs.front().is_some_and(|v| v.0 < start) {
+                self.highs.pop_front();
+            }
+            while self.lows.front().is_some_and(|v| v.0 < start) {
+                self.lows.pop_front();
+            }
+        }
+        self.value()
+    }
+
+    pub fn value(&self) -> Option<(Decimal, Decimal)> {
+        if self.count < self.period {
+            return None;
+        }
+        Some((self.highs.front()?.1, self.lows.front()?.1))
+    }
+
+    /// 最高价减最低价
+    pub fn range(&self) -> Option<Decimal> {
+        self.value().map(|(high, low)| high - low)
+    }
+}
+
+impl Indicator for HighLowRange {
+    type Output = (Decimal, Decimal);
+
+    fn update_bar(&mut self, bar: &KLineItem) -> Option<(Decimal, Decimal)> {
+        self.update(bar.high, bar.low)
+    }
+
+    fn reset(&mut self) {
+        self.count = 0;
+        self.highs.clear();
+        self.lows.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::{Atr, Ema, HighLowRange, Sma};
+
+    fn d(v: i64) -> Decimal {
+        Decimal::from(v)
+    }
+
+    #[test]
+    fn test_sma_ema() {
+        let mut sma = Sma::new(3);
+        let values = [1, 2, 3, 4, 5].map(|v| sma.update(d(v)));
+        assert_eq!(values, [None, None, Some(d(2)), Some(d(3)), Some(d(4))]);
+
+        let mut ema = Ema::new(3);
+        let values = [1, 2, 3, 4].map(|v| ema.update(d(v)));
+        // alpha=0.5, 第一个值为sma: 2, 之后 2+0.5*(4-2)=3
+        assert_eq!(values, [None, None, Some(d(2)), Some(d(3))]);
+    }
+
+    #[test]
+    fn test_atr() {
+        let mut atr = Atr::new(2);
+        // tr: 2, max(3, |13-10|, |10-10|)=3, max(1, |12-12|, |11-12|)=1
+        assert_eq!(atr.update(d(10), d(8), d(10)), None);
+        assert_eq!(atr.update(d(13), d(10), d(12)), Some(Decimal::new(25, 1)));
+        assert_eq!(atr.update(d(12), d(11), d(11)), Some(Decimal::new(175, 2)));
+    }
+
+    #[test]
+    fn test_high_low_range() {
+        let mut hl = HighLowRange::new(3);
+        let bars = [(5, 3), (7, 4), (6, 1), (4, 2), (3, 2)];
+        let values = bars.map(|(h, l)| hl.update(d(h), d(l)));
+        assert_eq!(
+            values,
+            [
+                None,
+                None,
+                Some((d(7), d(1))),
+                Some((d(7), d(1))),
+                Some((d(6), d(1)))
+            ]
+        );
+        assert_eq!(hl.range(), Some(d(5)));
+    }
+}
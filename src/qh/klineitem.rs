@@ -1,14 +1,33 @@
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
 use std::sync::{Arc, OnceLock};
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use futures_util::{StreamExt, TryStreamExt};
+use log::{info, warn};
 use rust_decimal::Decimal;
 use sqlx::mysql::MySqlArguments;
 use sqlx::{Arguments, MySqlPool};
 
+use super::aggregate::aggregate_bars;
 use super::breed;
+use super::klinetime::convert_to_xm::ConvertToXm;
+use super::klinetime::KLineTimeError;
+use super::latency::{LatencyStage, StageTimer};
+use super::period::{Period, PeriodError};
+use super::trading_day::TradingDayUtil;
 use crate::mysqlx::batch_exec::SqlEntity;
+use crate::mysqlx::paginate::{Page, Paginator};
+use crate::mysqlx::sql_builder::WhereArgsBuilder;
+use crate::mysqlx::{ident, validate_ident, IdentError};
+use crate::ymdhms::Ymd;
+
+#[cfg(test)]
+pub(crate) mod fixture;
+pub mod retention;
+pub mod schema;
+
+use self::schema::{KLineSchema, TableNotExist};
 
 #[derive(Debug, sqlx::FromRow, Clone)]
 pub struct KLineItem {
@@ -69,12 +88,20 @@ impl KLineItem {
         }
     }
 
+    pub fn period_value(&self) -> Result<Period, PeriodError> {
+        Period::try_from(self.period)
+    }
+
     pub fn breed(&self) -> String {
         breed::breed_from_symbol(&self.code)
     }
 
     pub fn sql_entity_replace(&self, key: &str, table_name: &str) -> SqlEntity {
         let sql = Self::KLINE_ITEM_REPLACE_INTO_SQL_TEMPLATE.replace("{{table_name}}", table_name);
+        SqlEntity::new(key, &sql, self.replace_args())
+    }
+
+    fn replace_args(&self) -> MySqlArguments {
         let mut args = MySqlArguments::default();
         args.add(&self.code);
         args.add(self.datetime);
@@ -88,7 +115,7 @@ impl KLineItem {
         args.add(self.open_oi);
         args.add(self.close_oi);
         args.add(self.last_item_time);
-        SqlEntity::new(key, &sql, args)
+        args
     }
 }
 
@@ -103,7 +130,7 @@ pub struct KLineItemUtils {
 impl KLineItemUtils {
     pub fn init_one_util(db: &str, default: bool) {
         let mut klius = KLineItemUtils::default();
-        let util = Arc::new(KLineItemUtil::new(db));
+        let util = Arc::new(KLineItemUtil::unchecked(db));
         if default {
             klius.default = Some(util.clone());
         }
@@ -130,21 +157,43 @@ impl KLineItemUtils {
 
 #[derive(Debug)]
 pub struct KLineItemUtil {
-    tbl_tmpl: String,
+    db:           String,
+    auto_migrate: bool,
 }
 
 impl KLineItemUtil {
+    /// 不校验db, 生成sql时db不合法返回错误
+    #[deprecated(note = "使用`try_new`, 创建时校验db")]
     pub fn new(db: &str) -> KLineItemUtil {
-        let tbl_tmpl = if db.is_empty() {
-            "`tbl_code_{{tbl_suffix}}`".to_owned()
-        } else {
-            format!("`{}`.`tbl_code_{{{{tbl_suffix}}}}`", db)
-        };
-        KLineItemUtil { tbl_tmpl }
+        Self::unchecked(db)
     }
 
-    fn table_name(&self, tbl_suffix: &str) -> String {
-        self.tbl_tmpl.replace("{{tbl_suffix}}", tbl_suffix)
+    /// db中的`-`替换为`_`, 同`sql_loader`建库时的库名, 替换后不是合法的标识符时返回错误
+    pub fn try_new(db: &str) -> Result<KLineItemUtil, IdentError> {
+        let util = Self::unchecked(db);
+        if !util.db.is_empty() {
+            validate_ident(&util.db)?;
+        }
+        Ok(util)
+    }
+
+    fn unchecked(db: &str) -> KLineItemUtil {
+        KLineItemUtil {
+            db:           db.replace('-', "_"),
+            auto_migrate: false,
+        }
+    }
+
+    /// 第一次读取旧结构的表时自动补上缺少的列, 默认不开启, 查询时用默认值填充
+    pub fn with_auto_migrate(self, auto_migrate: bool) -> Self {
+        KLineItemUtil {
+            auto_migrate,
+            ..self
+        }
+    }
+
+    fn table_name(&self, tbl_suffix: &str) -> Result<String, IdentError> {
+        ident(&self.db, &format!("tbl_code_{}", tbl_suffix))
     }
 
     // 这块的代码用不到了.
@@ -172,8 +221,26 @@ impl KLineItemUtil {
 
 /// 数据添加相关
 impl KLineItemUtil {
+    /// 表名不校验, 只转义其中的反引号
+    #[deprecated(note = "使用`try_sql_entity_replace`, 表名不合法时返回错误")]
     pub fn sql_entity_replace(&self, tbl_suffix: &str, key: &str, item: &KLineItem) -> SqlEntity {
-        item.sql_entity_replace(key, &self.table_name(tbl_suffix))
+        let quote = |v: &str| format!("`{}`", v.replace('`', "``"));
+        let tbl_name = quote(&format!("tbl_code_{}", tbl_suffix));
+        let table_name = if self.db.is_empty() {
+            tbl_name
+        } else {
+            format!("{}.{}", quote(&self.db), tbl_name)
+        };
+        item.sql_entity_replace(key, &table_name)
+    }
+
+    pub fn try_sql_entity_replace(
+        &self,
+        tbl_suffix: &str,
+        key: &str,
+        item: &KLineItem,
+    ) -> Result<SqlEntity, IdentError> {
+        Ok(item.sql_entity_replace(key, &self.table_name(tbl_suffix)?))
     }
 }
 
@@ -204,25 +271,103 @@ impl KLineItemUtil {
         pool: &MySqlPool,
         tbl_suffix: &str,
     ) -> Result<String, sqlx::Error> {
-        let table_name = self.table_name(tbl_suffix);
+        let table_name = self.table_name(tbl_suffix)?;
         let sql = Self::KLINE_TABLE_CREATE_SQL_TEMPLAGE.replace("{{table_name}}", &table_name);
         sqlx::query(&sql).execute::<_>(pool).await?;
         Ok(table_name)
     }
 }
 
+/// 表结构版本
+impl KLineItemUtil {
+    /// 表结构, 每个表第一次调用时检测后缓存, 开启自动迁移时补上缺少的列
+    ///
+    /// 表不存在时按当前版本, 不缓存, `create_table`建的表是当前版本
+    pub async fn schema(
+        &self,
+        pool: &MySqlPool,
+        tbl_suffix: &str,
+    ) -> Result<KLineSchema, sqlx::Error> {
+        let table_name = self.table_name(tbl_suffix)?;
+        let tbl_name = format!("tbl_code_{}", tbl_suffix);
+        if let Some(schema) = schema::cached_schema(&self.db, &tbl_name) {
+            return Ok(schema);
+        }
+        let Some(mut schema) = schema::detect_schema(pool, &self.db, &tbl_name).await? else {
+            return Ok(KLineSchema::CURRENT);
+        };
+        if !schema.is_current() {
+            if self.auto_migrate {
+                self.alter_table(pool, &table_name, &schema).await?;
+                schema = KLineSchema::CURRENT;
+            } else {
+                warn!(
+                    "{} missing columns {:?}, read with default values",
+                    table_name,
+                    schema.missing_columns()
+                );
+            }
+        }
+        schema::cache_schema(&self.db, &tbl_name, schema);
+        Ok(schema)
+    }
+
+    /// 补上表中缺少的列, 返回补上的列名, 写入旧结构的表前需要先迁移
+    pub async fn migrate_table(
+        &self,
+        pool: &MySqlPool,
+        tbl_suffix: &str,
+    ) -> Result<Vec<&'static str>, sqlx::Error> {
+        let table_name = self.table_name(tbl_suffix)?;
+        let tbl_name = format!("tbl_code_{}", tbl_suffix);
+        let schema = schema::detect_schema(pool, &self.db, &tbl_name)
+            .await?
+            .ok_or_else(|| TableNotExist(table_name.clone()))?;
+        self.alter_table(pool, &table_name, &schema).await?;
+        schema::cache_schema(&self.db, &tbl_name, KLineSchema::CURRENT);
+        Ok(schema.missing_columns())
+    }
+
+    async fn alter_table(
+        &self,
+        pool: &MySqlPool,
+        table_name: &str,
+        schema: &KLineSchema,
+    ) -> Result<(), sqlx::Error> {
+        if let Some(sql) = schema.alter_sql(table_name) {
+            sqlx::query(&sql).execute(pool).await?;
+            info!("{} add columns {:?}", table_name, schema.missing_columns());
+        }
+        Ok(())
+    }
+
+    /// 替换模板中的表名和查询的列
+    async fn select_sql(
+        &self,
+        pool: &MySqlPool,
+        tbl_suffix: &str,
+        template: &str,
+    ) -> Result<String, sqlx::Error> {
+        let schema = self.schema(pool, tbl_suffix).await?;
+        let table_name = self.table_name(tbl_suffix)?;
+        Ok(template
+            .replace("{{table_name}}", &table_name)
+            .replace("{{columns}}", &schema.select_columns()))
+    }
+}
+
 /// 列表相关的操作
 impl KLineItemUtil {
     const KLINE_ITEM_VEC_LATEST_BY_SYMBOL_SQL_TEMPLATE: &'static str =
-        "SELECT * FROM (SELECT code,datetime,period,open,high,low,close,volume,total_volume,open_oi,close_oi,last_item_time FROM {{table_name}} WHERE code=? AND period=? ORDER BY datetime DESC LIMIT ?) AS T ORDER BY datetime";
+        "SELECT * FROM (SELECT {{columns}} FROM {{table_name}} WHERE code=? AND period=? ORDER BY datetime DESC LIMIT ?) AS T ORDER BY datetime";
     const KLINE_ITEM_VEC_LATEST_SQL_TEMPLATE: &'static str =
-        "SELECT * FROM (SELECT code,datetime,period,open,high,low,close,volume,total_volume,open_oi,close_oi,last_item_time FROM {{table_name}} WHERE period=? ORDER BY datetime DESC LIMIT ?) AS T ORDER BY datetime";
+        "SELECT * FROM (SELECT {{columns}} FROM {{table_name}} WHERE period=? ORDER BY datetime DESC LIMIT ?) AS T ORDER BY datetime";
     const KLINE_ITEM_VEC_OLDEST_SQL_TEMPLATE: &'static str =
-        "SELECT code,datetime,period,open,high,low,close,volume,total_volume,open_oi,close_oi,last_item_time FROM {{table_name}} WHERE period=? ORDER BY datetime LIMIT ?";
+        "SELECT {{columns}} FROM {{table_name}} WHERE period=? ORDER BY datetime LIMIT ?";
     const KLINE_ITEM_VEC_RANGE_SQL_TEMPLATE: &'static str =
-        "SELECT code,datetime,period,open,high,low,close,volume,total_volume,open_oi,close_oi,last_item_time FROM {{table_name}} WHERE datetime>=? AND datetime <=? AND period=? ORDER BY datetime LIMIT ?";
+        "SELECT {{columns}} FROM {{table_name}} WHERE datetime>=? AND datetime <=? AND period=? ORDER BY datetime LIMIT ?";
     const KLINE_ITEM_VEC_SQL_TEMPLATE: &'static str =
-        "SELECT code,datetime,period,open,high,low,close,volume,total_volume,open_oi,close_oi,last_item_time FROM {{table_name}} WHERE datetime>=? AND period=? ORDER BY datetime LIMIT ?";
+        "SELECT {{columns}} FROM {{table_name}} WHERE datetime>=? AND period=? ORDER BY datetime LIMIT ?";
 
     /// 大于等于某一时间点的数据列表, 结果按时间正序排序
     pub async fn item_vec_egt_dt(
@@ -233,8 +378,9 @@ impl KLineItemUtil {
         datetime: &str,
         limit: u16,
     ) -> Result<Vec<KLineItem>, sqlx::Error> {
-        let table_name = self.table_name(tbl_suffix);
-        let sql = Self::KLINE_ITEM_VEC_SQL_TEMPLATE.replace("{{table_name}}", &table_name);
+        let sql = self
+            .select_sql(pool, tbl_suffix, Self::KLINE_ITEM_VEC_SQL_TEMPLATE)
+            .await?;
 
         let mut args = MySqlArguments::default();
         args.add(datetime);
@@ -288,8 +434,9 @@ impl KLineItemUtil {
         edatetime: &str,
         limit: u16,
     ) -> Result<Vec<KLineItem>, sqlx::Error> {
-        let table_name = self.table_name(tbl_suffix);
-        let sql = Self::KLINE_ITEM_VEC_RANGE_SQL_TEMPLATE.replace("{{table_name}}", &table_name);
+        let sql = self
+            .select_sql(pool, tbl_suffix, Self::KLINE_ITEM_VEC_RANGE_SQL_TEMPLATE)
+            .await?;
         let mut args = MySqlArguments::default();
         args.add(sdatetime);
         args.add(edatetime);
@@ -326,8 +473,9 @@ impl KLineItemUtil {
         period: u16,
         limit: u16,
     ) -> Result<Vec<KLineItem>, sqlx::Error> {
-        let table_name = self.table_name(tbl_suffix);
-        let sql = Self::KLINE_ITEM_VEC_OLDEST_SQL_TEMPLATE.replace("{{table_name}}", &table_name);
+        let sql = self
+            .select_sql(pool, tbl_suffix, Self::KLINE_ITEM_VEC_OLDEST_SQL_TEMPLATE)
+            .await?;
         let mut args = MySqlArguments::default();
         args.add(period);
         args.add(limit);
@@ -347,8 +495,9 @@ impl KLineItemUtil {
         period: u16,
         limit: u16,
     ) -> Result<Vec<KLineItem>, sqlx::Error> {
-        let table_name = self.table_name(tbl_suffix);
-        let sql = Self::KLINE_ITEM_VEC_LATEST_SQL_TEMPLATE.replace("{{table_name}}", &table_name);
+        let sql = self
+            .select_sql(pool, tbl_suffix, Self::KLINE_ITEM_VEC_LATEST_SQL_TEMPLATE)
+            .await?;
         let mut args = MySqlArguments::default();
         args.add(period);
         args.add(limit);
@@ -369,9 +518,13 @@ impl KLineItemUtil {
         symbol: &str,
         limit: u16,
     ) -> Result<Vec<KLineItem>, sqlx::Error> {
-        let table_name = self.table_name(tbl_suffix);
-        let sql = Self::KLINE_ITEM_VEC_LATEST_BY_SYMBOL_SQL_TEMPLATE
-            .replace("{{table_name}}", &table_name);
+        let sql = self
+            .select_sql(
+                pool,
+                tbl_suffix,
+                Self::KLINE_ITEM_VEC_LATEST_BY_SYMBOL_SQL_TEMPLATE,
+            )
+            .await?;
 
         let mut args = MySqlArguments::default();
         args.add(symbol);
@@ -386,6 +539,66 @@ impl KLineItemUtil {
     }
 }
 
+/// 分页相关的操作
+impl KLineItemUtil {
+    const KLINE_ITEM_PAGE_SQL_TEMPLATE: &'static str = "SELECT {{columns}} FROM {{table_name}}";
+
+    /// 某一合约某一周期的分页器, 按datetime排序
+    ///
+    /// 表结构还没有检测过时按当前版本查询, 见`schema`
+    pub fn paginator(
+        &self,
+        tbl_suffix: &str,
+        code: &str,
+        period: u16,
+        page_size: u32,
+    ) -> Result<Paginator<KLineItem>, IdentError> {
+        let table_name = self.table_name(tbl_suffix)?;
+        let tbl_name = format!("tbl_code_{}", tbl_suffix);
+        let schema = schema::cached_schema(&self.db, &tbl_name).unwrap_or(KLineSchema::CURRENT);
+        let sql = Self::KLINE_ITEM_PAGE_SQL_TEMPLATE
+            .replace("{{table_name}}", &table_name)
+            .replace("{{columns}}", &schema.select_columns());
+        let mut where_builder = WhereArgsBuilder::default();
+        where_builder.add("code", code.to_owned());
+        where_builder.add("period", period);
+        Ok(Paginator::new(&sql, "datetime", page_size).where_args(where_builder))
+    }
+
+    /// keyset分页, after为上一页最后一条数据的时间, 时间正序
+    pub async fn item_page_after(
+        &self,
+        pool: &MySqlPool,
+        tbl_suffix: &str,
+        code: &str,
+        period: u16,
+        after: Option<&NaiveDateTime>,
+        page_size: u32,
+    ) -> Result<Page<KLineItem>, sqlx::Error> {
+        self.schema(pool, tbl_suffix).await?;
+        self.paginator(tbl_suffix, code, period, page_size)?
+            .fetch_after(pool, after.copied())
+            .await
+    }
+
+    /// offset分页, page从0开始, 时间正序, 带总记录数
+    pub async fn item_page_offset(
+        &self,
+        pool: &MySqlPool,
+        tbl_suffix: &str,
+        code: &str,
+        period: u16,
+        page: u64,
+        page_size: u32,
+    ) -> Result<Page<KLineItem>, sqlx::Error> {
+        self.schema(pool, tbl_suffix).await?;
+        self.paginator(tbl_suffix, code, period, page_size)?
+            .with_total()
+            .fetch_offset(pool, page)
+            .await
+    }
+}
+
 impl KLineItemUtil {
     const SYMBOL_VEC_SQL_TEMPLATE: &'static str = "SELECT DISTINCT code FROM {{table_name}}";
 
@@ -395,7 +608,7 @@ impl KLineItemUtil {
         pool: &MySqlPool,
         tbl_suffix: &str,
     ) -> Result<Vec<String>, sqlx::Error> {
-        let table_name = self.table_name(tbl_suffix);
+        let table_name = self.table_name(tbl_suffix)?;
         let sql = Self::SYMBOL_VEC_SQL_TEMPLATE.replace("{{table_name}}", &table_name);
 
         sqlx::query_as::<_, (String,)>(&sql)
@@ -406,19 +619,139 @@ impl KLineItemUtil {
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum KLineItemError {
+    #[error("{0}")]
+    Sqlx(#[from] sqlx::Error),
+
+    #[error("{0}")]
+    Ident(#[from] IdentError),
+
+    #[error("{0}")]
+    KLineTime(#[from] KLineTimeError),
+
+    #[error("Period #{0}# not support rebuild")]
+    PeriodNotSupport(Period),
+}
+
+/// 周期重建
+impl KLineItemUtil {
+    const KLINE_ITEM_1M_DAY_SQL_TEMPLATE: &'static str =
+        "SELECT {{columns}} FROM {{table_name}} WHERE code=? AND period=1 AND datetime>? AND datetime<=? ORDER BY datetime";
+
+    /// 用1m数据重新生成一个合约在交易日范围内的period周期数据
+    ///
+    /// 按交易日读取1m数据, 用`ConvertToXm::time_range_period`的结束时间合成后在一个事务内REPLACE,
+    /// 需要先初始化klinetime, 返回写入的K线数量, 旧结构的表需要先`migrate_table`
+    pub async fn rebuild_period(
+        &self,
+        pool: &MySqlPool,
+        tbl_suffix: &str,
+        code: &str,
+        period: Period,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Result<usize, KLineItemError> {
+        if period == Period::M1 || period > Period::D1 {
+            return Err(KLineItemError::PeriodNotSupport(period));
+        }
+        let table_name = self.table_name(tbl_suffix)?;
+        let select_sql = self
+            .select_sql(pool, tbl_suffix, Self::KLINE_ITEM_1M_DAY_SQL_TEMPLATE)
+            .await?;
+        let replace_sql =
+            KLineItem::KLINE_ITEM_REPLACE_INTO_SQL_TEMPLATE.replace("{{table_name}}", &table_name);
+        // 前一交易日夜盘开始到当前交易日收盘
+        let night_start = NaiveTime::from_hms_opt(20, 0, 0).unwrap();
+        let day_end = NaiveTime::from_hms_opt(16, 0, 0).unwrap();
+        let tdu = TradingDayUtil::current();
+        let convert = ConvertToXm::default();
+        let breed = breed::breed_from_symbol(code);
+
+        let mut total = 0;
+        let mut day = *range.start();
+        while day <= *range.end() {
+            let yyyymmdd = Ymd::from(&day).yyyymmdd;
+            if !tdu.is_td(&yyyymmdd) {
+                day = day.succ_opt().unwrap();
+                continue;
+            }
+            let prev_td = NaiveDate::from(tdu.prev(&yyyymmdd)?);
+            let mut args = MySqlArguments::default();
+            args.add(code);
+            args.add(prev_td.and_time(night_start));
+            args.add(day.and_time(day_end));
+            let bars = sqlx::query_as_with::<_, KLineItem, _>(&select_sql, args)
+                .fetch_all(pool)
+                .await?;
+            let bar_times = bars
+                .iter()
+                .map(|v| {
+                    let range = convert.time_range_period(&breed, period, &v.datetime)?;
+                    Ok((v.datetime, range.end))
+                })
+                .collect::<Result<HashMap<_, _>, KLineTimeError>>()?;
+            let items = aggregate_bars(&bars, period, |v| bar_times.get(v).copied());
+
+            let timer = StageTimer::start(LatencyStage::Persist);
+            let mut transaction = pool.begin().await?;
+            for item in items.iter() {
+                sqlx::query_with(&replace_sql, item.replace_args())
+                    .execute(&mut *transaction)
+                    .await?;
+            }
+            transaction.commit().await?;
+            timer.finish();
+
+            info!(
+                "rebuild {} {} {}: 1m {}, {} {}",
+                table_name,
+                code,
+                day,
+                bars.len(),
+                period,
+                items.len()
+            );
+            total += items.len();
+            day = day.succ_opt().unwrap();
+        }
+        Ok(total)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
-    use chrono::NaiveDate;
+    use chrono::{Duration, NaiveDate};
 
-    use super::KLineItemUtil;
+    use super::{KLineItem, KLineItemError, KLineItemUtil};
+    use crate::mysqlx::batch_exec::BatchExec;
     use crate::mysqlx::MySqlPools;
-    use crate::mysqlx_test_pool::init_test_mysql_pools;
+    use crate::mysqlx_test_pool::{init_test_mysql_pools, test_db_guard};
+    use crate::qh::klinetime::convert_to_xm;
+    use crate::qh::period::Period;
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_util_db() {
+        let kiu = KLineItemUtil::try_new("hq-db").unwrap();
+        assert_eq!(kiu.table_name("agL9").unwrap(), "`hq_db`.`tbl_code_agL9`");
+        assert!(KLineItemUtil::try_new("hq`db").is_err());
+
+        let kiu = KLineItemUtil::new("hq`db");
+        assert!(kiu.table_name("agL9").is_err());
+        let dt = NaiveDate::from_ymd_opt(2024, 6, 3)
+            .unwrap()
+            .and_hms_opt(9, 1, 0)
+            .unwrap();
+        let item = KLineItem::new("ag2408", &dt, 1);
+        let entity = kiu.sql_entity_replace("agL9", "k", &item);
+        assert!(entity.sql().contains("`hq``db`.`tbl_code_agL9`"));
+    }
 
     #[tokio::test]
     async fn test_kline_item_vec() {
         init_test_mysql_pools();
-        let kline_db_util = KLineItemUtil::new("hqdb");
+        let kline_db_util = KLineItemUtil::try_new("hqdb").unwrap();
         let kline_item_stream = kline_db_util
             .item_vec_egt_dt(
                 &MySqlPools::pool_default().await.unwrap(),
@@ -437,7 +770,7 @@ mod tests {
     #[tokio::test]
     async fn test_kline_item_vec_range() {
         init_test_mysql_pools();
-        let kiu = KLineItemUtil::new("hqdb");
+        let kiu = KLineItemUtil::try_new("hqdb").unwrap();
         let kline_item_vec_range = kiu
             .item_vec_range(
                 &MySqlPools::pool_default().await.unwrap(),
@@ -455,10 +788,51 @@ mod tests {
         println!("{}", kline_item_vec_range.len());
     }
 
+    #[tokio::test]
+    async fn test_rebuild_period() {
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        convert_to_xm::init(&MySqlPools::pool_default().await.unwrap())
+            .await
+            .unwrap();
+        let kiu = KLineItemUtil::try_new(guard.db()).unwrap();
+        kiu.create_table(&pool, "agL9").await.unwrap();
+        // 2022-06-20 09:01 ~ 10:15
+        let start = NaiveDate::from_ymd_opt(2022, 6, 20)
+            .unwrap()
+            .and_hms_opt(9, 1, 0)
+            .unwrap();
+        for i in 0..75 {
+            let datetime = start + Duration::try_minutes(i).unwrap();
+            let mut item = KLineItem::new("ag2212", &datetime, 1);
+            item.volume = 1;
+            let entity = kiu.try_sql_entity_replace("agL9", "", &item).unwrap();
+            BatchExec::execute_single(&pool, entity).await.unwrap();
+        }
+        let day = start.date();
+        let count = kiu
+            .rebuild_period(&pool, "agL9", "ag2212", Period::M5, day..=day)
+            .await
+            .unwrap();
+        assert_eq!(count, 15);
+        let items = kiu
+            .item_vec_range(&pool, "agL9", 5, "2022-06-20 09:00:00", "2022-06-20 16:00:00", 100)
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 15);
+        assert!(items.iter().all(|v| v.volume == 5));
+        assert!(matches!(
+            kiu.rebuild_period(&pool, "agL9", "ag2212", Period::M1, day..=day)
+                .await,
+            Err(KLineItemError::PeriodNotSupport(Period::M1))
+        ));
+        guard.cleanup().await;
+    }
+
     #[tokio::test]
     async fn test_kline_item_vec_range_by_time() {
         init_test_mysql_pools();
-        let kiu = KLineItemUtil::new("hqdb");
+        let kiu = KLineItemUtil::try_new("hqdb").unwrap();
         let sdatetime = NaiveDate::from_ymd_opt(2022, 6, 20)
             .unwrap()
             .and_hms_opt(9, 1, 0)
@@ -484,10 +858,51 @@ mod tests {
         println!("{}", kline_item_vec_range.len());
     }
 
+    #[tokio::test]
+    async fn test_legacy_schema() {
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        let table = guard.table("tbl_code_legacy_test");
+        sqlx::query(&format!(
+            "CREATE TABLE {} (`code` varchar(12), `datetime` datetime, `period` int(11), `open` decimal(18,3), `high` decimal(18,3), `low` decimal(18,3), `close` decimal(18,3), `volume` int(11), `open_oi` int(11), `close_oi` int(11), PRIMARY KEY (`code`, `datetime`, `period`))",
+            table
+        ))
+        .execute(&*pool)
+        .await
+        .unwrap();
+        sqlx::query(&format!(
+            "INSERT INTO {} VALUES('ag2212','2022-06-20 09:01:00',1,1,1,1,1,10,100,100)",
+            table
+        ))
+        .execute(&*pool)
+        .await
+        .unwrap();
+
+        let kiu = KLineItemUtil::try_new(guard.db()).unwrap();
+        let schema = kiu.schema(&pool, "legacy_test").await.unwrap();
+        assert_eq!(schema.missing_columns(), ["total_volume", "last_item_time"]);
+        let items = kiu
+            .item_vec_oldest(&pool, "legacy_test", 1, 10)
+            .await
+            .unwrap();
+        assert_eq!(items[0].total_volume, 0);
+        assert_eq!(items[0].last_item_time, items[0].datetime);
+
+        let added = kiu.migrate_table(&pool, "legacy_test").await.unwrap();
+        assert_eq!(added, ["total_volume", "last_item_time"]);
+        assert!(kiu.schema(&pool, "legacy_test").await.unwrap().is_current());
+
+        // 表不存在时不是RowNotFound
+        assert!(kiu.schema(&pool, "not_exist").await.unwrap().is_current());
+        let err = kiu.migrate_table(&pool, "not_exist").await.unwrap_err();
+        assert!(err.to_string().contains("table not exist"), "{}", err);
+        guard.cleanup().await;
+    }
+
     #[tokio::test]
     async fn test_item_vec_oldest() {
         init_test_mysql_pools();
-        let kiu = KLineItemUtil::new("hqdb");
+        let kiu = KLineItemUtil::try_new("hqdb").unwrap();
         let kline_item_vec = kiu
             .item_vec_oldest(&MySqlPools::pool_default().await.unwrap(), "agL9", 5, 100)
             .await
@@ -501,7 +916,7 @@ mod tests {
     #[tokio::test]
     async fn test_item_vec_latest() {
         init_test_mysql_pools();
-        let kiu = KLineItemUtil::new("hqdb");
+        let kiu = KLineItemUtil::try_new("hqdb").unwrap();
         let kline_item_vec = kiu
             .item_vec_latest(&MySqlPools::pool_default().await.unwrap(), "agL9", 1, 10)
             .await
@@ -516,7 +931,7 @@ mod tests {
     async fn test_kline_item_vec_range_by_time_zero() {
         init_test_mysql_pools();
 
-        let kiu = KLineItemUtil::new("hqdb");
+        let kiu = KLineItemUtil::try_new("hqdb").unwrap();
         let sdatetime = NaiveDate::from_ymd_opt(2022, 6, 20)
             .unwrap()
             .and_hms_opt(9, 1, 0)
@@ -546,7 +961,7 @@ mod tests {
     async fn test_kline_item_vec_latest_by_symbol() {
         init_test_mysql_pools();
 
-        let kline_db_util = KLineItemUtil::new("hqdb");
+        let kline_db_util = KLineItemUtil::try_new("hqdb").unwrap();
         let kline_item_stream = kline_db_util
             .item_vec_latest_by_symbol(
                 &MySqlPools::pool_default().await.unwrap(),
@@ -564,11 +979,57 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_item_page_after() {
+        init_test_mysql_pools();
+
+        let kiu = KLineItemUtil::try_new("hqdb").unwrap();
+        let pool = MySqlPools::pool_default().await.unwrap();
+        let page = kiu
+            .item_page_after(&pool, "agL9", "agL9", 1, None, 5)
+            .await
+            .unwrap();
+        for item in page.items.iter() {
+            println!("{}", item);
+        }
+        let after = page.last().map(|v| v.datetime);
+        let page = kiu
+            .item_page_after(&pool, "agL9", "agL9", 1, after.as_ref(), 5)
+            .await
+            .unwrap();
+        println!("has_more: {}", page.has_more);
+        for item in page.items.iter() {
+            println!("{}", item);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_item_page_offset() {
+        init_test_mysql_pools();
+
+        let kiu = KLineItemUtil::try_new("hqdb").unwrap();
+        let page = kiu
+            .item_page_offset(
+                &MySqlPools::pool_default().await.unwrap(),
+                "agL9",
+                "agL9",
+                5,
+                2,
+                10,
+            )
+            .await
+            .unwrap();
+        println!("has_more: {}, total: {:?}", page.has_more, page.total);
+        for item in page.items.iter() {
+            println!("{}", item);
+        }
+    }
+
     #[tokio::test]
     async fn test_symbol_vec() {
         init_test_mysql_pools();
 
-        let kline_db_util = KLineItemUtil::new("hqdb");
+        let kline_db_util = KLineItemUtil::try_new("hqdb").unwrap();
         let symbol_vec = kline_db_util
             .symbol_vec(&MySqlPools::pool_default().await.unwrap(), "agL9")
             .await
@@ -0,0 +1,25 @@
+//! 测试用的K线, 时间从2024-06-03 09:00开始
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+
+use super::KLineItem;
+
+/// 2024-06-03 09:00之后minute分钟
+pub(crate) fn dt(minute: i64) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, 6, 3)
+        .unwrap()
+        .and_hms_opt(9, 0, 0)
+        .unwrap()
+        + Duration::try_minutes(minute).unwrap()
+}
+
+/// dt(minute)的1分钟K线, 开高低收都为price, 其他字段用`..bar(..)`按需覆盖
+pub(crate) fn bar(code: &str, minute: i64, price: impl Into<Decimal>) -> KLineItem {
+    let price = price.into();
+    let mut bar = KLineItem::new(code, &dt(minute), 1);
+    bar.open = price;
+    bar.high = price;
+    bar.low = price;
+    bar.close = price;
+    bar
+}
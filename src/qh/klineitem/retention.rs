@@ -0,0 +1,279 @@
+//! 按周期的保留时间清理K线, 如1m保留2年, 5m保留5年
+//!
+//! 每批删除batch_size条, 批之间暂停一段时间, 避免长时间锁表和主从延迟;
+//! 设置了归档库时先复制到归档库的同名表(不存在时按原表结构创建)再删除
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use log::info;
+use sqlx::mysql::MySqlArguments;
+use sqlx::{Arguments, MySqlPool};
+
+use crate::mysqlx::{ident, validate_ident};
+use crate::qh::period::Period;
+
+/// 清理规则
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    db:         String,
+    rules:      Vec<(Period, chrono::Duration)>,
+    tables:     Vec<String>,
+    archive_db: Option<String>,
+    batch_size: u32,
+    pause:      Duration,
+    dry_run:    bool,
+    now:        Option<NaiveDateTime>,
+}
+
+impl RetentionPolicy {
+    /// db: K线表所在的库
+    pub fn new(db: &str) -> RetentionPolicy {
+        RetentionPolicy {
+            db:         db.to_owned(),
+            rules:      Vec::new(),
+            tables:     Vec::new(),
+            archive_db: None,
+            batch_size: 5000,
+            pause:      Duration::from_millis(100),
+            dry_run:    false,
+            now:        None,
+        }
+    }
+
+    /// period周期保留keep时间内的K线, 同一周期重复设置时覆盖
+    pub fn with_rule(mut self, period: Period, keep: chrono::Duration) -> Self {
+        self.rules.retain(|v| v.0 != period);
+        self.rules.push((period, keep));
+        self.rules.sort_by_key(|v| v.0);
+        self
+    }
+
+    /// 只清理这些表(`tbl_code_{tbl_suffix}`), 默认为库中所有的K线表
+    pub fn with_tables(self, tbl_suffixes: &[&str]) -> Self {
+        RetentionPolicy {
+            tables: tbl_suffixes.iter().map(|v| (*v).to_owned()).collect(),
+            ..self
+        }
+    }
+
+    /// 删除前复制到archive_db的同名表
+    pub fn with_archive(self, archive_db: &str) -> Self {
+        RetentionPolicy {
+            archive_db: Some(archive_db.to_owned()),
+            ..self
+        }
+    }
+
+    pub fn with_batch_size(self, batch_size: u32) -> Self {
+        RetentionPolicy {
+            batch_size: batch_size.max(1),
+            ..self
+        }
+    }
+
+    /// 每批之间的暂停时间
+    pub fn with_pause(self, pause: Duration) -> Self {
+        RetentionPolicy { pause, ..self }
+    }
+
+    /// 只统计会清理的行数, 不修改数据
+    pub fn with_dry_run(self, dry_run: bool) -> Self {
+        RetentionPolicy { dry_run, ..self }
+    }
+
+    /// 计算保留时间的当前时间, 默认为本地时间
+    pub fn with_now(self, now: NaiveDateTime) -> Self {
+        RetentionPolicy {
+            now: Some(now),
+            ..self
+        }
+    }
+
+    /// 各周期清理的截止时间, 早于截止时间的K线被清理
+    pub fn cutoffs(&self) -> Vec<(Period, NaiveDateTime)> {
+        let now = self
+            .now
+            .unwrap_or_else(|| chrono::Local::now().naive_local());
+        self.rules
+            .iter()
+            .map(|(period, keep)| (*period, now - *keep))
+            .collect()
+    }
+}
+
+/// 一个表一个周期的清理结果, dry_run时rows为会清理的行数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SweepStat {
+    pub table:  String,
+    pub period: Period,
+    pub cutoff: NaiveDateTime,
+    pub rows:   u64,
+}
+
+/// 库中所有K线表的后缀
+async fn table_suffixes(pool: &MySqlPool, db: &str) -> Result<Vec<String>, sqlx::Error> {
+    let sql = "SELECT CAST(TABLE_NAME AS CHAR) FROM information_schema.TABLES WHERE \
+               TABLE_SCHEMA=? AND TABLE_TYPE='BASE TABLE' AND TABLE_NAME LIKE 'tbl\\_code\\_%' \
+               ORDER BY TABLE_NAME";
+    let mut args = MySqlArguments::default();
+    args.add(db);
+    let names = sqlx::query_as_with::<_, (String,), _>(sql, args)
+        .fetch_all(pool)
+        .await?;
+    Ok(names
+        .into_iter()
+        .filter_map(|(v,)| v.strip_prefix("tbl_code_").map(str::to_owned))
+        .collect())
+}
+
+fn period_args(period: Period, cutoff: &NaiveDateTime) -> MySqlArguments {
+    let mut args = MySqlArguments::default();
+    args.add(period.minutes());
+    args.add(cutoff);
+    args
+}
+
+/// 按规则清理K线, 返回每个表每个周期清理的行数
+pub async fn sweep(
+    pool: &MySqlPool,
+    policy: &RetentionPolicy,
+) -> Result<Vec<SweepStat>, sqlx::Error> {
+    let tables = if policy.tables.is_empty() {
+        table_suffixes(pool, &policy.db).await?
+    } else {
+        policy.tables.clone()
+    };
+    let archive_db = policy
+        .archive_db
+        .as_deref()
+        .map(validate_ident)
+        .transpose()?;
+    let cutoffs = policy.cutoffs();
+
+    let mut stats = Vec::new();
+    for tbl_suffix in tables.iter() {
+        let name = format!("tbl_code_{}", tbl_suffix);
+        let table = ident(&policy.db, &name)?;
+        let archive = archive_db.map(|db| ident(db, &name)).transpose()?;
+        if let (Some(archive), false) = (&archive, policy.dry_run) {
+            let sql = format!("CREATE TABLE IF NOT EXISTS {} LIKE {}", archive, table);
+            sqlx::query(&sql).execute(pool).await?;
+        }
+        for (period, cutoff) in cutoffs.iter() {
+            let rows = if policy.dry_run {
+                let sql = format!(
+                    "SELECT CAST(COUNT(*) AS UNSIGNED) FROM {} WHERE period=? AND datetime<?",
+                    table
+                );
+                let (rows,) =
+                    sqlx::query_as_with::<_, (u64,), _>(&sql, period_args(*period, cutoff))
+                        .fetch_one(pool)
+                        .await?;
+                rows
+            } else {
+                sweep_batches(pool, policy, &table, archive.as_deref(), *period, cutoff).await?
+            };
+            info!(
+                "retention{} {} {} before {}: {} rows{}",
+                if policy.dry_run { " dry-run" } else { "" },
+                table,
+                period,
+                cutoff,
+                rows,
+                archive
+                    .as_ref()
+                    .map(|v| format!(", archived to {}", v))
+                    .unwrap_or_default()
+            );
+            stats.push(SweepStat {
+                table: table.clone(),
+                period: *period,
+                cutoff: *cutoff,
+                rows,
+            });
+        }
+    }
+    Ok(stats)
+}
+
+/// 按主键顺序分批, 归档和删除在同一个事务内, 选中的是同一批数据
+async fn sweep_batches(
+    pool: &MySqlPool,
+    policy: &RetentionPolicy,
+    table: &str,
+    archive: Option<&str>,
+    period: Period,
+    cutoff: &NaiveDateTime,
+) -> Result<u64, sqlx::Error> {
+    let filter = format!(
+        "FROM {} WHERE period=? AND datetime<? ORDER BY code,datetime LIMIT {}",
+        table, policy.batch_size
+    );
+    let archive_sql = archive.map(|v| format!("INSERT IGNORE INTO {} SELECT * {}", v, filter));
+    let delete_sql = format!("DELETE {}", filter);
+
+    let mut total = 0;
+    loop {
+        let mut transaction = pool.begin().await?;
+        if let Some(sql) = &archive_sql {
+            sqlx::query_with(sql, period_args(period, cutoff))
+                .execute(&mut *transaction)
+                .await?;
+        }
+        let rows = sqlx::query_with(&delete_sql, period_args(period, cutoff))
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+        transaction.commit().await?;
+        total += rows;
+        if rows < policy.batch_size as u64 {
+            break;
+        }
+        tokio::time::sleep(policy.pause).await;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate};
+
+    use super::{sweep, RetentionPolicy};
+    use crate::mysqlx::MySqlPools;
+    use crate::mysqlx_test_pool::init_test_mysql_pools;
+    use crate::qh::period::Period;
+
+    #[test]
+    fn test_cutoffs() {
+        let now = NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let policy = RetentionPolicy::new("hqdb")
+            .with_rule(Period::M5, Duration::days(365 * 5))
+            .with_rule(Period::M1, Duration::days(365))
+            .with_rule(Period::M1, Duration::days(365 * 2))
+            .with_now(now);
+        assert_eq!(
+            policy.cutoffs(),
+            [
+                (Period::M1, now - Duration::days(730)),
+                (Period::M5, now - Duration::days(1825)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sweep_dry_run() {
+        init_test_mysql_pools();
+        let pool = MySqlPools::pool_default().await.unwrap();
+        let policy = RetentionPolicy::new("hqdb")
+            .with_rule(Period::M1, Duration::days(365 * 2))
+            .with_rule(Period::M5, Duration::days(365 * 5))
+            .with_tables(&["agL9"])
+            .with_dry_run(true);
+        let stats = sweep(&pool, &policy).await.unwrap();
+        assert_eq!(stats.len(), 2);
+        println!("{:?}", stats);
+    }
+}
@@ -0,0 +1,211 @@
+//! K线表的结构版本
+//!
+//! 早期的表没有`total_volume`和`last_item_time`两列, 每个表第一次读取时检测一次并缓存,
+//! 缺少的列查询时用默认值填充(`0`, `datetime`), 或者开启自动迁移时`ALTER TABLE`补上
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use sqlx::mysql::MySqlArguments;
+use sqlx::{Arguments, MySqlPool};
+
+/// 表中可能缺少的列: (列名, 缺少时查询用的默认值, 补列的定义)
+const OPTIONAL_COLUMNS: [(&str, &str, &str); 2] = [
+    (
+        "total_volume",
+        "0",
+        "`total_volume` int(11) DEFAULT '0' COMMENT '总成交量' AFTER `volume`",
+    ),
+    (
+        "last_item_time",
+        "`datetime`",
+        "`last_item_time` datetime(6) COMMENT '计算K线数据的最后一条数据的时间' AFTER `close_oi`",
+    ),
+];
+
+/// 表结构, 记录可选列是否存在
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KLineSchema {
+    pub total_volume:   bool,
+    pub last_item_time: bool,
+}
+
+impl KLineSchema {
+    /// 当前版本, 所有列都存在
+    pub const CURRENT: KLineSchema = KLineSchema {
+        total_volume:   true,
+        last_item_time: true,
+    };
+
+    /// 由表中的列名生成
+    pub fn from_columns<S: AsRef<str>>(columns: &[S]) -> KLineSchema {
+        let has = |name: &str| {
+            columns
+                .iter()
+                .any(|v| v.as_ref().eq_ignore_ascii_case(name))
+        };
+        KLineSchema {
+            total_volume:   has("total_volume"),
+            last_item_time: has("last_item_time"),
+        }
+    }
+
+    fn has(&self, column: &str) -> bool {
+        match column {
+            "total_volume" => self.total_volume,
+            "last_item_time" => self.last_item_time,
+            _ => true,
+        }
+    }
+
+    pub fn is_current(&self) -> bool {
+        *self == Self::CURRENT
+    }
+
+    /// 缺少的列
+    pub fn missing_columns(&self) -> Vec<&'static str> {
+        OPTIONAL_COLUMNS
+            .iter()
+            .filter(|(name, ..)| !self.has(name))
+            .map(|(name, ..)| *name)
+            .collect()
+    }
+
+    /// 查询的列, 缺少的列用默认值代替
+    pub fn select_columns(&self) -> String {
+        let column = |name: &str| match OPTIONAL_COLUMNS.iter().find(|(v, ..)| *v == name) {
+            Some((_, default, _)) if !self.has(name) => format!("{} AS {}", default, name),
+            _ => name.to_owned(),
+        };
+        [
+            "code",
+            "datetime",
+            "period",
+            "open",
+            "high",
+            "low",
+            "close",
+            "volume",
+            "total_volume",
+            "open_oi",
+            "close_oi",
+            "last_item_time",
+        ]
+        .iter()
+        .map(|v| column(v))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+
+    /// 补上缺少的列的语句, 已是当前版本时为None
+    pub fn alter_sql(&self, table_name: &str) -> Option<String> {
+        let adds = OPTIONAL_COLUMNS
+            .iter()
+            .filter(|(name, ..)| !self.has(name))
+            .map(|(_, _, define)| format!("ADD COLUMN {}", define))
+            .collect::<Vec<_>>();
+        if adds.is_empty() {
+            return None;
+        }
+        Some(format!("ALTER TABLE {} {}", table_name, adds.join(",")))
+    }
+}
+
+/// 表不存在
+#[derive(Debug, thiserror::Error)]
+#[error("table not exist: {0}")]
+pub struct TableNotExist(pub String);
+
+impl From<TableNotExist> for sqlx::Error {
+    fn from(value: TableNotExist) -> Self {
+        sqlx::Error::Configuration(Box::new(value))
+    }
+}
+
+/// key: (库名, 表名)
+static KLINE_SCHEMAS: OnceLock<RwLock<HashMap<(String, String), KLineSchema>>> = OnceLock::new();
+
+fn kline_schemas() -> &'static RwLock<HashMap<(String, String), KLineSchema>> {
+    KLINE_SCHEMAS.get_or_init(Default::default)
+}
+
+/// 缓存的表结构, db为空时当前数据库由连接决定, 不缓存
+pub(crate) fn cached_schema(db: &str, tbl_name: &str) -> Option<KLineSchema> {
+    if db.is_empty() {
+        return None;
+    }
+    kline_schemas()
+        .read()
+        .unwrap()
+        .get(&(db.to_owned(), tbl_name.to_owned()))
+        .copied()
+}
+
+pub(crate) fn cache_schema(db: &str, tbl_name: &str, schema: KLineSchema) {
+    if db.is_empty() {
+        return;
+    }
+    kline_schemas()
+        .write()
+        .unwrap()
+        .insert((db.to_owned(), tbl_name.to_owned()), schema);
+}
+
+/// 清除缓存, 表结构在外部被修改后下次读取时重新检测
+pub fn clear_schema_cache() {
+    kline_schemas().write().unwrap().clear();
+}
+
+/// 从information_schema读取表结构, db为空时使用连接的当前数据库, 表不存在时为None
+pub(crate) async fn detect_schema(
+    pool: &MySqlPool,
+    db: &str,
+    tbl_name: &str,
+) -> Result<Option<KLineSchema>, sqlx::Error> {
+    let sql = if db.is_empty() {
+        "SELECT column_name FROM information_schema.columns WHERE table_schema=DATABASE() AND table_name=?"
+    } else {
+        "SELECT column_name FROM information_schema.columns WHERE table_schema=? AND table_name=?"
+    };
+    let mut args = MySqlArguments::default();
+    if !db.is_empty() {
+        args.add(db);
+    }
+    args.add(tbl_name);
+    let columns = sqlx::query_as_with::<_, (String,), _>(sql, args)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|v| v.0)
+        .collect::<Vec<_>>();
+    if columns.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(KLineSchema::from_columns(&columns)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KLineSchema;
+
+    #[test]
+    fn test_schema() {
+        let schema = KLineSchema::from_columns(&["code", "datetime", "period", "volume"]);
+        assert!(!schema.is_current());
+        assert_eq!(schema.missing_columns(), ["total_volume", "last_item_time"]);
+        assert_eq!(
+            schema.select_columns(),
+            "code,datetime,period,open,high,low,close,volume,0 AS total_volume,open_oi,close_oi,`datetime` AS last_item_time"
+        );
+        let sql = schema.alter_sql("`hqdb`.`tbl_code_ag`").unwrap();
+        assert!(sql.starts_with("ALTER TABLE `hqdb`.`tbl_code_ag` ADD COLUMN `total_volume`"));
+        assert!(sql.contains(",ADD COLUMN `last_item_time`"));
+
+        let schema = KLineSchema::from_columns(&["TOTAL_VOLUME", "last_item_time"]);
+        assert!(schema.is_current());
+        assert_eq!(schema.alter_sql("t"), None);
+        assert_eq!(
+            schema.select_columns(),
+            "code,datetime,period,open,high,low,close,volume,total_volume,open_oi,close_oi,last_item_time"
+        );
+    }
+}
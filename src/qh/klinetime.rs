@@ -2,10 +2,13 @@
 
 use std::fmt;
 
-use chrono::NaiveDateTime;
+use chrono::{Duration, NaiveDateTime};
 
+pub use self::alignment::{verify_alignment, Alignment, AlignmentReport};
+use super::period::Period;
 use super::trading_day::TradingDayUtilInitError;
 
+mod alignment;
 mod convert_to_1d;
 mod convert_to_1m;
 mod convert_to_1month;
@@ -13,6 +16,8 @@ mod convert_to_1w;
 mod convert_to_30m60m120m;
 mod convert_to_3m5m15m;
 pub mod convert_to_xm;
+pub mod fixture;
+pub mod init_order;
 pub mod tx_time_range;
 
 #[derive(Debug, thiserror::Error)]
@@ -53,11 +58,18 @@ pub enum KLineTimeError {
     #[error("{0}")]
     TradingDayUtilInit(#[from] TradingDayUtilInitError),
 
+    #[error("{0}")]
+    InitOrder(String),
+
     #[error("{0}'s week not had tx day")]
     WeekNotHadTxDay(NaiveDateTime),
+
+    #[error("#{breed}# rangelist #{rangelist}# invalid")]
+    RangeListInvalid { breed: String, rangelist: String },
 }
 
-#[derive(Debug)]
+/// K线的时间范围, start和end都是其中1m K线的时间(闭区间)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct TimeRangeDateTime {
     pub start: NaiveDateTime,
     pub end:   NaiveDateTime,
@@ -67,6 +79,37 @@ impl TimeRangeDateTime {
     pub(crate) fn new(start: NaiveDateTime, end: NaiveDateTime) -> TimeRangeDateTime {
         TimeRangeDateTime { start, end }
     }
+
+    pub fn contains(&self, dt: &NaiveDateTime) -> bool {
+        self.start <= *dt && *dt <= self.end
+    }
+
+    /// 覆盖的自然时长, 包含start那根1m K线的1分钟
+    pub fn duration(&self) -> Duration {
+        self.end - self.start + Duration::minutes(1)
+    }
+
+    /// 交集, 没有重叠时为None
+    pub fn intersect(&self, other: &TimeRangeDateTime) -> Option<TimeRangeDateTime> {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end);
+        (start <= end).then_some(TimeRangeDateTime { start, end })
+    }
+
+    /// 按period的分钟数从start开始切分, 最后一段不足时截止到end
+    ///
+    /// 按自然时间切分, 不排除休市时间
+    pub fn split_by(&self, period: Period) -> Vec<TimeRangeDateTime> {
+        let step = Duration::minutes(period.minutes() as i64);
+        let mut ranges = Vec::new();
+        let mut start = self.start;
+        while start <= self.end {
+            let end = (start + step - Duration::minutes(1)).min(self.end);
+            ranges.push(TimeRangeDateTime { start, end });
+            start += step;
+        }
+        ranges
+    }
 }
 
 impl fmt::Display for TimeRangeDateTime {
@@ -78,3 +121,43 @@ impl fmt::Display for TimeRangeDateTime {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDateTime};
+
+    use super::TimeRangeDateTime;
+    use crate::qh::period::Period;
+
+    fn dt(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_time_range_datetime() {
+        let range = TimeRangeDateTime::new(dt("2024-06-03 09:01"), dt("2024-06-03 09:30"));
+        assert!(range.contains(&dt("2024-06-03 09:01")));
+        assert!(range.contains(&dt("2024-06-03 09:30")));
+        assert!(!range.contains(&dt("2024-06-03 09:31")));
+        assert_eq!(range.duration(), Duration::minutes(30));
+
+        let other = TimeRangeDateTime::new(dt("2024-06-03 09:21"), dt("2024-06-03 10:00"));
+        assert_eq!(
+            range.intersect(&other),
+            Some(TimeRangeDateTime::new(
+                dt("2024-06-03 09:21"),
+                dt("2024-06-03 09:30")
+            ))
+        );
+        let other = TimeRangeDateTime::new(dt("2024-06-03 09:31"), dt("2024-06-03 10:00"));
+        assert_eq!(range.intersect(&other), None);
+
+        let ranges = range.split_by(Period::M15);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(
+            ranges[1].to_string(),
+            "(2024-06-03 09:16:00~2024-06-03 09:30:00)"
+        );
+        assert_eq!(range.split_by(Period::M60), [range]);
+    }
+}
@@ -0,0 +1,210 @@
+//! 外部数据源K线时间与交易时间段的对齐检查
+//!
+//! 本系统的1m K线时间为结束时间, 如09:00~09:01的K线时间为09:01,
+//! 外部数据的时间分别偏移-1m, 0, +1m后与交易时间段比较, 不在交易时间内最少的偏移即为数据的时间标记方式
+use std::fmt;
+
+use chrono::{Duration, NaiveDateTime, NaiveTime, Timelike};
+
+use super::tx_time_range::TxTimeRangeData;
+use super::KLineTimeError;
+
+/// 最多保留的不匹配时间样本
+const MAX_SAMPLES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    /// 使用结束时间, 与本系统一致
+    CloseStamped,
+    /// 使用开始时间, 需要加1分钟
+    OpenStamped,
+    /// 比结束时间晚1分钟, 需要减1分钟
+    ShiftedLate,
+    /// 数据不足以区分(没有交易时间段边界的分钟)或偏移后仍有较多不匹配
+    Unknown,
+}
+
+impl fmt::Display for Alignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Alignment::CloseStamped => "close-stamped",
+            Alignment::OpenStamped => "open-stamped",
+            Alignment::ShiftedLate => "shifted-late-1m",
+            Alignment::Unknown => "unknown",
+        };
+        f.pad(s)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AlignmentReport {
+    pub alignment:  Alignment,
+    pub total:      usize,
+    /// 各偏移(分钟)下不在交易时间内的数量, 偏移为-1, 0, 1
+    pub mismatches: [(i64, usize); 3],
+    /// 建议的修正(加的分钟数), Unknown时为None
+    pub correction: Option<i64>,
+    /// 修正后仍不在交易时间内的时间(修正前的值)
+    pub samples:    Vec<NaiveDateTime>,
+}
+
+impl AlignmentReport {
+    /// 按建议修正时间
+    pub fn correct(&self, dt: &NaiveDateTime) -> NaiveDateTime {
+        match self.correction {
+            Some(minutes) => *dt + Duration::try_minutes(minutes).unwrap(),
+            None => *dt,
+        }
+    }
+}
+
+impl fmt::Display for AlignmentReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} total:{}", self.alignment, self.total)?;
+        for (offset, count) in self.mismatches.iter() {
+            write!(f, " [{:+}m]:{}", offset, count)?;
+        }
+        if let Some(correction) = self.correction {
+            if correction != 0 {
+                write!(f, " suggest: {:+}m", correction)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 检查breed的外部K线时间的对齐方式
+pub fn verify_alignment(
+    breed: &str,
+    timestamps: &[NaiveDateTime],
+) -> Result<AlignmentReport, KLineTimeError> {
+    let ttr = TxTimeRangeData::current();
+    ttr.time_range_vec(breed)?;
+    Ok(classify_alignment(timestamps, |time| {
+        ttr.is_trading_time(breed, time)
+    }))
+}
+
+fn classify_alignment<F>(timestamps: &[NaiveDateTime], is_trading_time: F) -> AlignmentReport
+where
+    F: Fn(&NaiveTime) -> bool,
+{
+    let minute_time = |dt: &NaiveDateTime, offset: i64| {
+        let dt = *dt + Duration::try_minutes(offset).unwrap();
+        dt.time()
+            .with_second(0)
+            .unwrap()
+            .with_nanosecond(0)
+            .unwrap()
+    };
+    let mut mismatches = [(-1, 0), (0, 0), (1, 0)];
+    for (offset, count) in mismatches.iter_mut() {
+        *count = timestamps
+            .iter()
+            .filter(|dt| !is_trading_time(&minute_time(dt, *offset)))
+            .count();
+    }
+    let min = mismatches.iter().map(|v| v.1).min().unwrap_or_default();
+    let best = mismatches
+        .iter()
+        .filter(|v| v.1 == min)
+        .map(|v| v.0)
+        .collect::<Vec<_>>();
+    // 偏移后还有超过1%不匹配时不给建议
+    let clean = min * 100 <= timestamps.len();
+    let (alignment, correction) = match best.as_slice() {
+        _ if timestamps.is_empty() || !clean => (Alignment::Unknown, None),
+        [0, ..] if best.len() > 1 => (Alignment::Unknown, None),
+        [0] => (Alignment::CloseStamped, Some(0)),
+        [1] => (Alignment::OpenStamped, Some(1)),
+        [-1] => (Alignment::ShiftedLate, Some(-1)),
+        _ => (Alignment::Unknown, None),
+    };
+    let offset = correction.unwrap_or_default();
+    let samples = timestamps
+        .iter()
+        .filter(|dt| !is_trading_time(&minute_time(dt, offset)))
+        .take(MAX_SAMPLES)
+        .copied()
+        .collect();
+    AlignmentReport {
+        alignment,
+        total: timestamps.len(),
+        mismatches,
+        correction,
+        samples,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+
+    use super::{classify_alignment, Alignment};
+
+    fn hm(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    /// 09:01~10:15, 10:31~11:30, 13:31~15:00
+    fn is_trading_time(time: &NaiveTime) -> bool {
+        [
+            (hm(9, 1), hm(10, 15)),
+            (hm(10, 31), hm(11, 30)),
+            (hm(13, 31), hm(15, 0)),
+        ]
+        .iter()
+        .any(|(start, end)| (start..=end).contains(&time))
+    }
+
+    /// 按本系统时间生成的一天的1m时间
+    fn grid() -> Vec<NaiveDateTime> {
+        let day = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let mut dt = day.and_time(hm(9, 0));
+        let mut v = vec![];
+        while dt < day.and_time(hm(15, 0)) {
+            dt += Duration::try_minutes(1).unwrap();
+            if is_trading_time(&dt.time()) {
+                v.push(dt);
+            }
+        }
+        v
+    }
+
+    fn shift(v: &[NaiveDateTime], minutes: i64) -> Vec<NaiveDateTime> {
+        v.iter()
+            .map(|dt| *dt + Duration::try_minutes(minutes).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_classify_alignment() {
+        let grid = grid();
+        let report = classify_alignment(&grid, is_trading_time);
+        println!("{}", report);
+        assert_eq!(report.alignment, Alignment::CloseStamped);
+
+        let open = shift(&grid, -1);
+        let report = classify_alignment(&open, is_trading_time);
+        println!("{}", report);
+        assert_eq!(report.alignment, Alignment::OpenStamped);
+        assert_eq!(report.correct(&open[0]), grid[0]);
+
+        let late = shift(&grid, 1);
+        let report = classify_alignment(&late, is_trading_time);
+        println!("{}", report);
+        assert_eq!(report.alignment, Alignment::ShiftedLate);
+        assert!(report.samples.is_empty());
+
+        // 没有边界的分钟, 无法区分
+        let inner = &grid[10..20];
+        let report = classify_alignment(inner, is_trading_time);
+        assert_eq!(report.alignment, Alignment::Unknown);
+        assert_eq!(report.correction, None);
+
+        let shifted = shift(&grid, 30);
+        let report = classify_alignment(&shifted, is_trading_time);
+        println!("{} {:?}", report, report.samples);
+        assert_eq!(report.alignment, Alignment::Unknown);
+    }
+}
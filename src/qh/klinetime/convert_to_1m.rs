@@ -41,10 +41,15 @@ impl ConvertTo1m {
         CONVERT_1M.get().unwrap().clone()
     }
 
+    /// 是否已初始化
+    pub fn is_initialized() -> bool {
+        CONVERT_1M.get().is_some()
+    }
+
     // BreedVec::init
     // TxTimeRangeData::init
     pub fn init() -> Result<(), KLineTimeError> {
-        if !Self::current().is_empty() {
+        if CONVERT_1M.get().is_some() {
             return Ok(());
         }
         let mut tc = ConvertTo1m::default();
@@ -95,10 +100,6 @@ impl ConvertTo1m {
         Ok(())
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.breed_1mtime_hmap.is_empty()
-    }
-
     pub fn to_1m_with_min_dg_day(
         &self,
         breed: &str,
@@ -1,11 +1,17 @@
+//! 30m, 60m, 120m的K线时间, 按数据库中各品种的周期时间段划分
+//!
+//! 交易日的交易时间段变短时(节假日后无夜盘, 夜盘取消, 提前收盘), 按当天实际的交易时间段
+//! 从第一分钟开始重新划分. 启用`hq`时使用`hq::future::trade_day`的夜盘覆盖和提前收盘
 use std::collections::HashMap;
 use std::sync::{Arc, OnceLock};
 
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
 use futures_util::TryStreamExt;
 use sqlx::{FromRow, MySqlPool};
 
+use super::tx_time_range::TxTimeRangeData;
 use super::{KLineTimeError, TimeRangeDateTime};
+use crate::qh::period::Period;
 use crate::qh::trading_day::TradingDayUtil;
 use crate::ymdhms::{Hms, TimeRangeHms, Ymd};
 
@@ -17,13 +23,17 @@ struct DbItem {
 }
 
 // breed,period,vec<TimeRangeHms>
-type StoreData = HashMap<String, HashMap<String, Vec<TimeRangeHms>>>;
+type StoreData = HashMap<String, HashMap<Period, Vec<TimeRangeHms>>>;
 
 impl Extend<DbItem> for StoreData {
     fn extend<T: IntoIterator<Item = DbItem>>(&mut self, iter: T) {
         // 临时共用存储数据的HashMap
         let mut tr_key_vec_tr_hmap = HashMap::new();
         for row in iter {
+            // 不支持的周期不会被查询到, 跳过
+            let Ok(period) = row.period.parse::<Period>() else {
+                continue;
+            };
             let vec_time_range_hms =
                 tr_key_vec_tr_hmap
                     .entry(row.rangelist)
@@ -44,7 +54,7 @@ impl Extend<DbItem> for StoreData {
                     });
             let period_vec_hmap = self.entry(row.breed).or_default();
             period_vec_hmap
-                .entry(row.period)
+                .entry(period)
                 .or_insert_with(|| vec_time_range_hms.to_vec());
         }
     }
@@ -52,9 +62,39 @@ impl Extend<DbItem> for StoreData {
 
 static CONVERT_30M60M120M: OnceLock<Arc<ConvertTo30m60m120m>> = OnceLock::new();
 
+/// night_day(夜盘开始的交易日)的夜盘覆盖
+#[cfg(feature = "hq")]
+fn night_override(night_day: &NaiveDate) -> Option<bool> {
+    crate::hq::future::trade_day::night_override(night_day)
+}
+
+#[cfg(not(feature = "hq"))]
+fn night_override(_night_day: &NaiveDate) -> Option<bool> {
+    None
+}
+
+/// day的夜盘(night为true时day是夜盘开始的交易日)或白盘的提前收盘时间
+#[cfg(feature = "hq")]
+fn early_close(day: &NaiveDate, night: bool) -> Option<NaiveTime> {
+    crate::hq::future::trade_day::early_close(day, night)
+}
+
+#[cfg(not(feature = "hq"))]
+fn early_close(_day: &NaiveDate, _night: bool) -> Option<NaiveTime> {
+    None
+}
+
+/// 一个交易日内的先后顺序, 从18:00开始的分钟数
+fn session_order(time: &NaiveTime) -> i64 {
+    (*time - NaiveTime::from_hms_opt(18, 0, 0).unwrap())
+        .num_minutes()
+        .rem_euclid(24 * 60)
+}
+
 #[derive(Debug)]
 pub(crate) struct ConvertTo30m60m120m {
     tdu:        Arc<TradingDayUtil>,
+    trd:        Arc<TxTimeRangeData>,
     store_data: StoreData,
 }
 
@@ -62,6 +102,7 @@ impl Default for ConvertTo30m60m120m {
     fn default() -> Self {
         Self {
             tdu:        TradingDayUtil::current(),
+            trd:        TxTimeRangeData::current(),
             store_data: Default::default(),
         }
     }
@@ -72,9 +113,14 @@ impl ConvertTo30m60m120m {
         CONVERT_30M60M120M.get().unwrap().clone()
     }
 
-    // TradingDayUtil::init
+    /// 是否已初始化
+    pub fn is_initialized() -> bool {
+        CONVERT_30M60M120M.get().is_some()
+    }
+
+    // TradingDayUtil::init, TxTimeRangeData::init
     pub(crate) async fn init(pool: &MySqlPool) -> Result<(), sqlx::Error> {
-        if !Self::current().store_data.is_empty() {
+        if CONVERT_30M60M120M.get().is_some() {
             return Ok(());
         }
         let mut ct = ConvertTo30m60m120m::default();
@@ -97,7 +143,7 @@ impl ConvertTo30m60m120m {
     pub(crate) fn time_range(
         &self,
         breed: &str,
-        period: &str,
+        period: Period,
         datetime: &NaiveDateTime,
     ) -> Result<TimeRangeDateTime, KLineTimeError> {
         let time_range_hms = self
@@ -107,9 +153,9 @@ impl ConvertTo30m60m120m {
                 breed: breed.to_owned(),
                 scope: "Convert30m60m120m".to_owned(),
             })?
-            .get(period)
+            .get(&period)
             .ok_or(KLineTimeError::PeriodNotExist {
-                period: period.to_owned(),
+                period: period.to_string(),
                 scope:  "Convert30m60m120m".to_owned(),
             })?
             .iter()
@@ -119,6 +165,19 @@ impl ConvertTo30m60m120m {
                 datetime: *datetime,
             })?;
 
+        if let Some(minutes) = self.shortened_minutes(breed, datetime)? {
+            let pv = period.minutes() as usize;
+            let idx = minutes.binary_search(datetime).map_err(|_| {
+                KLineTimeError::DatetimeNotInRange {
+                    breed:    breed.to_owned(),
+                    datetime: *datetime,
+                }
+            })?;
+            let start = idx / pv * pv;
+            let end = (start + pv).min(minutes.len()) - 1;
+            return Ok(TimeRangeDateTime::new(minutes[start], minutes[end]));
+        }
+
         let hms = Hms::from(&datetime.time());
         let s = time_range_hms.start;
         let e = time_range_hms.end;
@@ -156,11 +215,13 @@ impl ConvertTo30m60m120m {
                 }
             } else if hms.hour > 8 {
                 // 白盘时间
-                // 取上一交易日 + 1天
                 let ymd = Ymd::from(&datetime.date());
                 let tdu = &self.tdu;
-                let prev_td = tdu.prev(&ymd.yyyymmdd)?;
-                sdate = NaiveDate::from(prev_td).succ_opt().unwrap();
+                if tdu.has_night(&ymd.yyyymmdd) {
+                    // 取上一交易日 + 1天
+                    let prev_td = tdu.prev(&ymd.yyyymmdd)?;
+                    sdate = NaiveDate::from(prev_td).succ_opt().unwrap();
+                }
             }
         }
         Ok(TimeRangeDateTime::new(
@@ -168,20 +229,93 @@ impl ConvertTo30m60m120m {
             edate.and_time(etime),
         ))
     }
+
+    /// datetime所在交易日实际的1m K线时间(按时间排序), 和完整的交易时间段相同时为None
+    fn shortened_minutes(
+        &self,
+        breed: &str,
+        datetime: &NaiveDateTime,
+    ) -> Result<Option<Vec<NaiveDateTime>>, KLineTimeError> {
+        let tr_vec = self.trd.time_range_vec(breed)?;
+        let breed_has_night = self.trd.is_had_night(breed);
+        let tdu = &self.tdu;
+        let date = datetime.date();
+        let time = datetime.time();
+        let trading_day = if breed_has_night && tr_vec[0].in_range_time(&time) {
+            // 0点之后的属于前一天开始的夜盘, 周六凌晨属于周五的夜盘
+            let night_date = if time.hour() >= 18 {
+                date
+            } else {
+                date.pred_opt().unwrap()
+            };
+            *tdu.next(&Ymd::from(&night_date).yyyymmdd)?
+        } else {
+            Ymd::from(&date)
+        };
+        if !tdu.is_td(&trading_day.yyyymmdd) {
+            return Ok(None);
+        }
+        let trade_date = NaiveDate::from(&trading_day);
+        let night_day = NaiveDate::from(tdu.prev(&trading_day.yyyymmdd)?);
+        let with_night = breed_has_night
+            && night_override(&night_day).unwrap_or_else(|| tdu.has_night(&trading_day.yyyymmdd));
+        let night_close = early_close(&night_day, true).filter(|_| with_night);
+        let day_close = early_close(&trade_date, false);
+        if with_night == breed_has_night && night_close.is_none() && day_close.is_none() {
+            return Ok(None);
+        }
+
+        let mut minutes = Vec::new();
+        for (i, range) in tr_vec.iter().enumerate() {
+            let is_night = breed_has_night && i == 0;
+            if is_night && !with_night {
+                continue;
+            }
+            let (day, close) = if is_night {
+                (night_day, night_close)
+            } else {
+                (trade_date, day_close)
+            };
+            let start = NaiveTime::from(&range.start);
+            let mut end = NaiveTime::from(&range.end);
+            if let Some(close) = close {
+                if session_order(&close) < session_order(&start) {
+                    continue;
+                }
+                if session_order(&close) < session_order(&end) {
+                    end = close;
+                }
+            }
+            let mut dt = day.and_time(start);
+            let end_dt = if end < start {
+                day.succ_opt().unwrap().and_time(end)
+            } else {
+                day.and_time(end)
+            };
+            while dt <= end_dt {
+                minutes.push(dt);
+                dt += Duration::try_minutes(1).unwrap();
+            }
+        }
+        Ok(Some(minutes))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
+    use std::sync::Arc;
 
     use chrono::{Duration, NaiveDate, NaiveTime, Timelike};
     use tokio::runtime::Runtime;
 
-    use super::ConvertTo30m60m120m;
+    use super::{ConvertTo30m60m120m, DbItem, StoreData};
+    #[cfg(feature = "hq")]
+    use crate::hq::future::trade_day::test_overrides;
     use crate::mysqlx::MySqlPools;
     use crate::mysqlx_test_pool::init_test_mysql_pools;
     use crate::qh::klinetime::tx_time_range::TxTimeRangeData;
-    use crate::qh::period::PeriodUtil;
+    use crate::qh::period::Period;
     use crate::qh::trading_day::TradingDayUtil;
 
     #[test]
@@ -229,7 +363,9 @@ mod tests {
         })
     }
 
-    fn test_to_xm_sub(breed: &str, tx_ranges: &str, period: &str, last_vec_len: usize) {
+    fn test_to_xm_sub(breed: &str, tx_ranges: &str, period: Period, last_vec_len: usize) {
+        #[cfg(feature = "hq")]
+        let _overrides = test_overrides::shared();
         println!("=== {} {} {} ===", breed, period, tx_ranges);
         let trd = TxTimeRangeData::current();
         let cvt = ConvertTo30m60m120m::current();
@@ -264,7 +400,7 @@ mod tests {
             }
         }
 
-        let pv = PeriodUtil::pv(period).unwrap();
+        let pv = period.minutes();
         let key_max_idx = key_vec.len() - 1;
         for (idx, key) in key_vec.iter().enumerate() {
             let datetime_vec = xm_vec_map.get(key).unwrap();
@@ -279,7 +415,7 @@ mod tests {
                 len
             );
             let right = if key_max_idx > idx {
-                len == *pv as usize
+                len == pv as usize
             } else {
                 len == last_vec_len
             };
@@ -288,6 +424,94 @@ mod tests {
         println!();
     }
 
+    #[tokio::test]
+    async fn test_no_night() {
+        #[cfg(feature = "hq")]
+        let _overrides = test_overrides::shared();
+        init_test_mysql_pools();
+        let pool = MySqlPools::pool_default().await.unwrap();
+        TradingDayUtil::init(&pool).await.unwrap();
+        TxTimeRangeData::init(&pool).await.unwrap();
+        ConvertTo30m60m120m::init(&pool).await.unwrap();
+        let cvt = ConvertTo30m60m120m::current();
+        // 2022-06-06 端午节后无夜盘, 120m从白盘第一分钟开始重新划分
+        let day = NaiveDate::from_ymd_opt(2022, 6, 6).unwrap();
+        assert!(!TradingDayUtil::current().has_night(&20220606));
+        let dt = day.and_hms_opt(9, 10, 0).unwrap();
+        let tr_dt = cvt.time_range("ag", Period::M120, &dt).unwrap();
+        assert_eq!(tr_dt.start, day.and_hms_opt(9, 1, 0).unwrap());
+        assert_eq!(tr_dt.end, day.and_hms_opt(11, 15, 0).unwrap());
+    }
+
+    #[test]
+    fn test_shortened_session() {
+        use crate::qh::klinetime::fixture;
+
+        #[cfg(feature = "hq")]
+        let _overrides = test_overrides::exclusive();
+
+        let mut store_data = StoreData::default();
+        store_data.extend([
+            DbItem {
+                breed:     "AG".to_owned(),
+                period:    "120m".to_owned(),
+                rangelist: "[(2101,2300),(2301,100),(101,930),(931,1345),(1346,1500)]".to_owned(),
+            },
+            DbItem {
+                breed:     "IC".to_owned(),
+                period:    "60m".to_owned(),
+                rangelist: "[(931,1030),(1031,1130),(1301,1400),(1401,1500)]".to_owned(),
+            },
+        ]);
+        let cvt = ConvertTo30m60m120m {
+            tdu: Arc::new(fixture::trading_day_util(20220530, 20220617, &[20220603]).unwrap()),
+            trd: Arc::new(fixture::tx_time_range_data()),
+            store_data,
+        };
+        let dt = |day: u32, h: u32, m: u32| {
+            NaiveDate::from_ymd_opt(2022, 6, day)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+        let range = |breed: &str, period: Period, datetime| {
+            let tr_dt = cvt.time_range(breed, period, &datetime).unwrap();
+            (tr_dt.start, tr_dt.end)
+        };
+
+        // 有夜盘的交易日按数据库的时间段
+        assert_eq!(range("ag", Period::M120, dt(13, 9, 10)), (dt(11, 1, 1), dt(13, 9, 30)));
+        assert_eq!(range("ag", Period::M120, dt(13, 13, 40)), (dt(13, 9, 31), dt(13, 13, 45)));
+        // 节假日后无夜盘, 从白盘第一分钟开始划分: 75 + 45, 15 + 90
+        assert_eq!(range("ag", Period::M120, dt(6, 9, 10)), (dt(6, 9, 1), dt(6, 11, 15)));
+        assert_eq!(range("ag", Period::M120, dt(6, 13, 40)), (dt(6, 11, 16), dt(6, 15, 0)));
+        // 9:30开盘的品种
+        assert_eq!(range("ic", Period::M60, dt(6, 10, 0)), (dt(6, 9, 31), dt(6, 10, 30)));
+
+        #[cfg(feature = "hq")]
+        {
+            use crate::hq::future::trade_day::{
+                remove_early_close, remove_night_override, set_early_close, set_night_override,
+            };
+
+            // 取消06-13(周一)开始的夜盘, 06-14只有白盘
+            let night_day = NaiveDate::from_ymd_opt(2022, 6, 13).unwrap();
+            set_night_override(night_day, false);
+            assert_eq!(range("ag", Period::M120, dt(14, 9, 10)), (dt(14, 9, 1), dt(14, 11, 15)));
+            remove_night_override(&night_day);
+            assert_eq!(range("ag", Period::M120, dt(14, 9, 10)), (dt(14, 1, 1), dt(14, 9, 30)));
+
+            // 06-15白盘14:00提前收盘, 最后一根K线到14:00
+            let day = NaiveDate::from_ymd_opt(2022, 6, 15).unwrap();
+            set_early_close(day, NaiveTime::from_hms_opt(14, 0, 0).unwrap());
+            assert_eq!(range("ag", Period::M120, dt(15, 13, 40)), (dt(15, 9, 31), dt(15, 13, 45)));
+            assert_eq!(range("ag", Period::M120, dt(15, 13, 50)), (dt(15, 13, 46), dt(15, 14, 0)));
+            assert_eq!(range("ic", Period::M60, dt(15, 13, 50)), (dt(15, 13, 1), dt(15, 14, 0)));
+            assert!(cvt.time_range("ic", Period::M60, &dt(15, 14, 10)).is_err());
+            remove_early_close(&day, false);
+        }
+    }
+
     #[tokio::test]
     async fn test_to_xm_1() {
         init_test_mysql_pools();
@@ -303,9 +527,9 @@ mod tests {
 
         let breed = "IC";
         let tx_ranges = "[(931,1130),(1301,1500)]";
-        test_to_xm_sub(breed, tx_ranges, "30m", 30);
-        test_to_xm_sub(breed, tx_ranges, "60m", 60);
-        test_to_xm_sub(breed, tx_ranges, "120m", 120);
+        test_to_xm_sub(breed, tx_ranges, Period::M30, 30);
+        test_to_xm_sub(breed, tx_ranges, Period::M60, 60);
+        test_to_xm_sub(breed, tx_ranges, Period::M120, 120);
     }
 
     #[tokio::test]
@@ -322,9 +546,9 @@ mod tests {
             .unwrap();
         let breed = "TF";
         let tx_ranges = "[(931,1130),(1301,1515)]";
-        test_to_xm_sub(breed, tx_ranges, "30m", 15);
-        test_to_xm_sub(breed, tx_ranges, "60m", 15);
-        test_to_xm_sub(breed, tx_ranges, "120m", 15);
+        test_to_xm_sub(breed, tx_ranges, Period::M30, 15);
+        test_to_xm_sub(breed, tx_ranges, Period::M60, 15);
+        test_to_xm_sub(breed, tx_ranges, Period::M120, 15);
     }
 
     #[tokio::test]
@@ -341,9 +565,9 @@ mod tests {
             .unwrap();
         let breed = "AP";
         let tx_ranges = "[(901,1015),(1031,1130),(1331,1500)]";
-        test_to_xm_sub(breed, tx_ranges, "30m", 15);
-        test_to_xm_sub(breed, tx_ranges, "60m", 45);
-        test_to_xm_sub(breed, tx_ranges, "120m", 105);
+        test_to_xm_sub(breed, tx_ranges, Period::M30, 15);
+        test_to_xm_sub(breed, tx_ranges, Period::M60, 45);
+        test_to_xm_sub(breed, tx_ranges, Period::M120, 105);
     }
 
     #[tokio::test]
@@ -360,9 +584,9 @@ mod tests {
             .unwrap();
         let breed = "a";
         let tx_ranges = "[(2101,2300),(901,1015),(1031,1130),(1331,1500)]";
-        test_to_xm_sub(breed, tx_ranges, "30m", 15);
-        test_to_xm_sub(breed, tx_ranges, "60m", 45);
-        test_to_xm_sub(breed, tx_ranges, "120m", 105);
+        test_to_xm_sub(breed, tx_ranges, Period::M30, 15);
+        test_to_xm_sub(breed, tx_ranges, Period::M60, 45);
+        test_to_xm_sub(breed, tx_ranges, Period::M120, 105);
     }
 
     #[tokio::test]
@@ -379,9 +603,9 @@ mod tests {
             .unwrap();
         let breed = "ag";
         let tx_ranges = "[(2101,230),(901,1015),(1031,1130),(1331,1500)]";
-        test_to_xm_sub(breed, tx_ranges, "30m", 15);
-        test_to_xm_sub(breed, tx_ranges, "60m", 15);
-        test_to_xm_sub(breed, tx_ranges, "120m", 75);
+        test_to_xm_sub(breed, tx_ranges, Period::M30, 15);
+        test_to_xm_sub(breed, tx_ranges, Period::M60, 15);
+        test_to_xm_sub(breed, tx_ranges, Period::M120, 75);
     }
 
     #[tokio::test]
@@ -398,8 +622,8 @@ mod tests {
             .unwrap();
         let breed = "al";
         let tx_ranges = "[(2101,100),(901,1015),(1031,1130),(1331,1500)]";
-        test_to_xm_sub(breed, tx_ranges, "30m", 15);
-        test_to_xm_sub(breed, tx_ranges, "60m", 45);
-        test_to_xm_sub(breed, tx_ranges, "120m", 105);
+        test_to_xm_sub(breed, tx_ranges, Period::M30, 15);
+        test_to_xm_sub(breed, tx_ranges, Period::M60, 45);
+        test_to_xm_sub(breed, tx_ranges, Period::M120, 105);
     }
 }
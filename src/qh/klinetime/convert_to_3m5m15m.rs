@@ -1,15 +1,14 @@
 use chrono::{Duration, NaiveDateTime, Timelike};
 
 use super::TimeRangeDateTime;
-use crate::qh::period::PeriodUtil;
+use crate::qh::period::Period;
 
 pub(crate) struct ConvertTo3m5m15m;
 
 impl ConvertTo3m5m15m {
     /// time: 必须是经过日夜盘时间修正后的时间.
-    pub(crate) fn time_range(period: &str, time: &NaiveDateTime) -> TimeRangeDateTime {
-        let pv = PeriodUtil::pv(period)
-            .unwrap_or_else(|| panic!("Convert3m5m15m period err: {}", period));
+    pub(crate) fn time_range(period: Period, time: &NaiveDateTime) -> TimeRangeDateTime {
+        let pv = period.minutes();
         let time_offset = time.minute() as u16 % pv;
         let stime_offset;
         let etime_offset;
@@ -38,7 +37,7 @@ mod tests {
     use crate::mysqlx::MySqlPools;
     use crate::mysqlx_test_pool::init_test_mysql_pools;
     use crate::qh::klinetime::tx_time_range::TxTimeRangeData;
-    use crate::qh::period::PeriodUtil;
+    use crate::qh::period::Period;
     use crate::qh::trading_day::TradingDayUtil;
 
     fn test_to_xm_sub(breed: &str, tx_ranges: &str, period: &str) {
@@ -64,7 +63,7 @@ mod tests {
                 } else {
                     sdatetime
                 };
-                let tr_dt = ConvertTo3m5m15m::time_range(period, &datetime);
+                let tr_dt = ConvertTo3m5m15m::time_range(period.parse().unwrap(), &datetime);
                 let key = tr_dt.to_string();
                 if !xm_vec_map.contains_key(&key) {
                     key_vec.push(key.clone());
@@ -75,7 +74,7 @@ mod tests {
             }
         }
 
-        let pv = PeriodUtil::pv(period).unwrap();
+        let pv = period.parse::<Period>().unwrap().minutes();
         for key in key_vec.iter() {
             let datetime_vec = xm_vec_map.get(key).unwrap();
             println!(
@@ -86,7 +85,7 @@ mod tests {
                     .map(|v| { v.format("%Y-%m-%d %H:%M:%S").to_string() })
                     .collect::<Vec<String>>()
             );
-            assert_eq!(datetime_vec.len(), *pv as usize);
+            assert_eq!(datetime_vec.len(), pv as usize);
         }
         println!();
     }
@@ -9,20 +9,16 @@ use super::convert_to_1month::ConvertTo1Month;
 use super::convert_to_1w::ConvertTo1W;
 use super::convert_to_30m60m120m::ConvertTo30m60m120m;
 use super::convert_to_3m5m15m::ConvertTo3m5m15m;
-use super::tx_time_range::TxTimeRangeData;
-use super::{KLineTimeError, TimeRangeDateTime};
-use crate::qh::breed::BreedInfoVec;
-use crate::qh::trading_day::TradingDayUtil;
+use super::{init_order, KLineTimeError, TimeRangeDateTime};
+use crate::cell::init_order::InitOrderError;
+use crate::qh::period::Period;
 
+/// 按依赖顺序初始化, 见`init_order::init_all`
 pub async fn init(pool: &MySqlPool) -> Result<(), KLineTimeError> {
-    BreedInfoVec::init(pool).await?;
-    TradingDayUtil::init(pool).await?;
-    TxTimeRangeData::init(pool).await?;
-
-    ConvertTo1m::init()?;
-    ConvertTo30m60m120m::init(pool).await?;
-
-    Ok(())
+    init_order::init_all(pool).await.map_err(|e| match e {
+        InitOrderError::Init { source, .. } => source,
+        e => KLineTimeError::InitOrder(e.to_string()),
+    })
 }
 
 //TODO: NOT INIT
@@ -54,21 +50,42 @@ impl ConvertToXm {
     }
 
     /// time 必须是tick time经过处理后的1m, 否则不准确
+    /// period按`Period`解析, 见`time_range_period`
     pub fn time_range_xm(
         &self,
         breed: &str,
         period: &str,
         datetime: &NaiveDateTime,
     ) -> Result<TimeRangeDateTime, KLineTimeError> {
-        match period {
-            "3m" | "5m" | "15m" => Ok(ConvertTo3m5m15m::time_range(period, datetime)),
-            "30m" | "60m" | "120m" => self.c30_60_120m.time_range(breed, period, datetime),
-            "1d" => self.c1d.time_range(breed, datetime),
-            "1w" => self.c1w.time_range(breed, datetime),
-            "1mth" | "1month" => self.c1mth.time_range(breed, datetime),
-            _ => Err(KLineTimeError::PeriodNotSupport {
+        let period = period
+            .parse::<Period>()
+            .map_err(|_| KLineTimeError::PeriodNotSupport {
                 period: period.to_owned(),
                 scope:  "convert_xm::time_range_xm".to_owned(),
+            })?;
+        self.time_range_period(breed, period, datetime)
+    }
+
+    /// time 必须是tick time经过处理后的1m, 否则不准确
+    pub fn time_range_period(
+        &self,
+        breed: &str,
+        period: Period,
+        datetime: &NaiveDateTime,
+    ) -> Result<TimeRangeDateTime, KLineTimeError> {
+        match period {
+            Period::M3 | Period::M5 | Period::M15 => {
+                Ok(ConvertTo3m5m15m::time_range(period, datetime))
+            },
+            Period::M30 | Period::M60 | Period::M120 => {
+                self.c30_60_120m.time_range(breed, period, datetime)
+            },
+            Period::D1 => self.c1d.time_range(breed, datetime),
+            Period::W1 => self.c1w.time_range(breed, datetime),
+            Period::Month1 => self.c1mth.time_range(breed, datetime),
+            Period::M1 => Err(KLineTimeError::PeriodNotSupport {
+                period: period.to_string(),
+                scope:  "convert_xm::time_range_period".to_owned(),
             }),
         }
     }
@@ -0,0 +1,246 @@
+//! 不连接数据库的交易时间段及交易日数据, 以及K线时间的性质检查, 用于属性测试(proptest等)
+//!
+//! 检查函数使用传入的`TxTimeRangeData`和`TradingDayUtil`, 不依赖`current()`, 没有MySQL时也可以运行
+use std::collections::HashSet;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+
+use super::tx_time_range::TxTimeRangeData;
+use super::KLineTimeError;
+use crate::qh::trading_day::{TradingDayUtil, TradingDayUtilInitError};
+use crate::ymdhms::{Hms, Ymd};
+
+/// 各种交易时间段的品种
+pub const RANGELISTS: &[(&str, &str)] = &[
+    ("IC", "[(931,1130),(1301,1500)]"),
+    ("TF", "[(931,1130),(1301,1515)]"),
+    ("AP", "[(901,1015),(1031,1130),(1331,1500)]"),
+    ("A", "[(2101,2300),(901,1015),(1031,1130),(1331,1500)]"),
+    ("AL", "[(2101,100),(901,1015),(1031,1130),(1331,1500)]"),
+    ("AG", "[(2101,230),(901,1015),(1031,1130),(1331,1500)]"),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum PropertyError {
+    #[error("{0}")]
+    KLineTime(#[from] KLineTimeError),
+
+    #[error("#{breed}# next_minute not increasing: {datetime} -> {next}")]
+    NotIncreasing {
+        breed:    String,
+        datetime: NaiveDateTime,
+        next:     NaiveDateTime,
+    },
+
+    #[error("#{breed}# {datetime} not trading time")]
+    NotTradingTime {
+        breed:    String,
+        datetime: NaiveDateTime,
+    },
+
+    #[error("#{breed}# {trading_day} minute {datetime} duplicated")]
+    Duplicated {
+        breed:       String,
+        trading_day: u32,
+        datetime:    NaiveDateTime,
+    },
+
+    #[error("#{breed}# {trading_day} minutes passed {end} at {datetime}")]
+    PassedEnd {
+        breed:       String,
+        trading_day: u32,
+        end:         NaiveDateTime,
+        datetime:    NaiveDateTime,
+    },
+}
+
+/// RANGELISTS的交易时间段数据
+pub fn tx_time_range_data() -> TxTimeRangeData {
+    TxTimeRangeData::from_rangelists(RANGELISTS).unwrap()
+}
+
+/// start到end(包含)之间除去周六日和holidays的日期, 格式:20220607
+pub fn weekdays(start: u32, end: u32, holidays: &[u32]) -> Vec<u32> {
+    let end = NaiveDate::from(&Ymd::from_yyyymmdd(end));
+    NaiveDate::from(&Ymd::from_yyyymmdd(start))
+        .iter_days()
+        .take_while(|v| *v <= end)
+        .filter(|v| !matches!(v.weekday(), Weekday::Sat | Weekday::Sun))
+        .map(|v| Ymd::from(&v).yyyymmdd)
+        .filter(|v| !holidays.contains(v))
+        .collect()
+}
+
+/// 用weekdays的结果作为交易日
+pub fn trading_day_util(
+    start: u32,
+    end: u32,
+    holidays: &[u32],
+) -> Result<TradingDayUtil, TradingDayUtilInitError> {
+    TradingDayUtil::from_trading_days(&weekdays(start, end, holidays))
+}
+
+fn hms_datetime(ymd: &Ymd, hms: &Hms) -> NaiveDateTime {
+    NaiveDate::from(ymd).and_time(NaiveTime::from(hms))
+}
+
+/// 交易日所有的1m K线时间, 从第一分钟按next_minute到最后一个交易时间段结束
+///
+/// 检查每个时间是交易时间, 第一个时间is_first_minute, 没有重复, 不超过收盘时间
+pub fn day_minutes(
+    trd: &TxTimeRangeData,
+    tdu: &TradingDayUtil,
+    breed: &str,
+    trading_day: u32,
+) -> Result<Vec<NaiveDateTime>, PropertyError> {
+    let tr_vec = trd.time_range_vec(breed)?;
+    let ymd = Ymd::from_yyyymmdd(trading_day);
+    let first = if !trd.is_had_night(breed) {
+        hms_datetime(&ymd, &tr_vec[0].start)
+    } else if tdu.has_night(&trading_day) {
+        hms_datetime(tdu.prev(&trading_day)?, &tr_vec[0].start)
+    } else {
+        hms_datetime(&ymd, &tr_vec[1].start)
+    };
+    let end = hms_datetime(&ymd, &tr_vec.last().unwrap().end);
+    if !trd.is_first_minute_with(tdu, breed, &trading_day, &first) {
+        return Err(KLineTimeError::DatetimeNotInRange {
+            breed:    breed.to_owned(),
+            datetime: first,
+        }
+        .into());
+    }
+
+    let mut minutes = Vec::new();
+    let mut seen = HashSet::new();
+    let mut datetime = first;
+    loop {
+        if datetime > end {
+            return Err(PropertyError::PassedEnd {
+                breed: breed.to_owned(),
+                trading_day,
+                end,
+                datetime,
+            });
+        }
+        if !trd.is_trading_time(breed, &datetime) {
+            return Err(PropertyError::NotTradingTime {
+                breed: breed.to_owned(),
+                datetime,
+            });
+        }
+        if !seen.insert(datetime) {
+            return Err(PropertyError::Duplicated {
+                breed: breed.to_owned(),
+                trading_day,
+                datetime,
+            });
+        }
+        minutes.push(datetime);
+        if datetime == end {
+            return Ok(minutes);
+        }
+        datetime = trd.next_minute_with(tdu, breed, &datetime)?;
+    }
+}
+
+/// 从start开始调用steps次next_minute, 检查结果递增且是交易时间
+pub fn check_next_minute_increasing(
+    trd: &TxTimeRangeData,
+    tdu: &TradingDayUtil,
+    breed: &str,
+    start: &NaiveDateTime,
+    steps: usize,
+) -> Result<(), PropertyError> {
+    let mut datetime = *start;
+    for _ in 0..steps {
+        let next = trd.next_minute_with(tdu, breed, &datetime)?;
+        if next <= datetime {
+            return Err(PropertyError::NotIncreasing {
+                breed: breed.to_owned(),
+                datetime,
+                next,
+            });
+        }
+        if !trd.is_trading_time(breed, &next) {
+            return Err(PropertyError::NotTradingTime {
+                breed:    breed.to_owned(),
+                datetime: next,
+            });
+        }
+        datetime = next;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::{
+        check_next_minute_increasing, day_minutes, trading_day_util, tx_time_range_data, weekdays,
+        RANGELISTS,
+    };
+    use crate::qh::klinetime::tx_time_range::TxTimeRangeData;
+
+    fn dt(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_fixture() {
+        // 20220603端午节
+        let days = weekdays(20220530, 20220617, &[20220603]);
+        assert_eq!(days.len(), 14);
+        let tdu = trading_day_util(20220530, 20220617, &[20220603]).unwrap();
+        assert_eq!(tdu.next(&20220602).unwrap().yyyymmdd, 20220606);
+        assert_eq!(tdu.next(&20220611).unwrap().yyyymmdd, 20220613);
+        assert_eq!(tdu.prev(&20220606).unwrap().yyyymmdd, 20220602);
+        assert!(!tdu.has_night(&20220606));
+        assert!(tdu.has_night(&20220613));
+
+        let trd = tx_time_range_data();
+        assert!(trd.is_trading_time("ag", &dt("2022-06-01 23:59:59")));
+        assert!(trd.is_trading_time("ag", &dt("2022-06-02 00:00:00")));
+        assert!(!trd.is_trading_time("a", &dt("2022-06-01 23:59:59")));
+        assert!(TxTimeRangeData::from_rangelists(&[("X", "[(901,1015),(1031)]")]).is_err());
+    }
+
+    #[test]
+    fn test_day_minutes() {
+        let trd = tx_time_range_data();
+        let tdu = trading_day_util(20220530, 20220617, &[20220603]).unwrap();
+        let expected = [
+            ("IC", 240, 240),
+            ("TF", 255, 255),
+            ("AP", 225, 225),
+            ("A", 345, 225),
+            ("AL", 465, 225),
+            ("AG", 555, 225),
+        ];
+        for (breed, len, len_after_holiday) in expected {
+            let minutes = day_minutes(&trd, &tdu, breed, 20220613).unwrap();
+            assert_eq!(minutes.len(), len, "{}", breed);
+            let minutes = day_minutes(&trd, &tdu, breed, 20220606).unwrap();
+            assert_eq!(minutes.len(), len_after_holiday, "{}", breed);
+        }
+        let minutes = day_minutes(&trd, &tdu, "AG", 20220613).unwrap();
+        assert_eq!(minutes[0], dt("2022-06-10 21:01:00"));
+        assert!(minutes.contains(&dt("2022-06-11 00:00:00")));
+        assert_eq!(minutes[minutes.len() - 1], dt("2022-06-13 15:00:00"));
+    }
+
+    #[test]
+    fn test_next_minute_increasing() {
+        let trd = tx_time_range_data();
+        let tdu = trading_day_util(20220530, 20220617, &[20220603]).unwrap();
+        for (breed, _) in RANGELISTS {
+            for day in weekdays(20220531, 20220615, &[20220603]) {
+                let minutes = day_minutes(&trd, &tdu, breed, day).unwrap();
+                // 跨过收盘到下一交易日
+                check_next_minute_increasing(&trd, &tdu, breed, &minutes[0], minutes.len() + 10)
+                    .unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! K线时间转换用到的全局数据的初始化顺序
+//!
+//! BreedInfoVec, TradingDayUtil, TxTimeRangeData -> ConvertTo1m
+//! TradingDayUtil, TxTimeRangeData -> ConvertTo30m60m120m
+use std::sync::OnceLock;
+
+use sqlx::MySqlPool;
+
+use super::convert_to_1m::ConvertTo1m;
+use super::convert_to_30m60m120m::ConvertTo30m60m120m;
+use super::tx_time_range::TxTimeRangeData;
+use super::KLineTimeError;
+use crate::cell::init_order::{InitOrderError, InitRegistry, InitStatus};
+use crate::qh::breed::BreedInfoVec;
+use crate::qh::trading_day::TradingDayUtil;
+
+static KLINE_TIME_REGISTRY: OnceLock<InitRegistry<MySqlPool, KLineTimeError>> = OnceLock::new();
+
+fn registry() -> &'static InitRegistry<MySqlPool, KLineTimeError> {
+    KLINE_TIME_REGISTRY.get_or_init(|| {
+        InitRegistry::new()
+            .with_component("BreedInfoVec", &[], |pool| {
+                Box::pin(async move { Ok(BreedInfoVec::init(pool).await?) })
+            })
+            .with_component("TradingDayUtil", &[], |pool| {
+                Box::pin(async move { Ok(TradingDayUtil::init(pool).await?) })
+            })
+            .with_component("TxTimeRangeData", &[], |pool| {
+                Box::pin(async move { Ok(TxTimeRangeData::init(pool).await?) })
+            })
+            .with_component(
+                "ConvertTo1m",
+                &["BreedInfoVec", "TradingDayUtil", "TxTimeRangeData"],
+                |_| Box::pin(async { ConvertTo1m::init() }),
+            )
+            .with_component(
+                "ConvertTo30m60m120m",
+                &["TradingDayUtil", "TxTimeRangeData"],
+                |pool| Box::pin(async move { Ok(ConvertTo30m60m120m::init(pool).await?) }),
+            )
+            .with_probe("BreedInfoVec", BreedInfoVec::is_initialized)
+            .with_probe("TradingDayUtil", TradingDayUtil::is_initialized)
+            .with_probe("TxTimeRangeData", TxTimeRangeData::is_initialized)
+            .with_probe("ConvertTo1m", ConvertTo1m::is_initialized)
+            .with_probe("ConvertTo30m60m120m", ConvertTo30m60m120m::is_initialized)
+    })
+}
+
+/// 按依赖顺序初始化所有组件, 已初始化的跳过
+pub async fn init_all(pool: &MySqlPool) -> Result<(), InitOrderError<KLineTimeError>> {
+    registry().init_all(pool).await
+}
+
+/// 各组件是否已初始化及缺少的依赖, 按各单例的实际状态, 不经过`init_all`初始化的也算
+pub fn diagnostics() -> Vec<InitStatus> {
+    registry().diagnostics()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diagnostics, init_all, registry};
+    use crate::mysqlx::MySqlPools;
+    use crate::mysqlx_test_pool::init_test_mysql_pools;
+
+    #[test]
+    fn test_init_order() {
+        let order = registry().init_order().unwrap();
+        let pos = |name: &str| order.iter().position(|v| *v == name).unwrap();
+        assert!(pos("ConvertTo1m") > pos("BreedInfoVec"));
+        assert!(pos("ConvertTo1m") > pos("TxTimeRangeData"));
+        assert!(pos("ConvertTo30m60m120m") > pos("TradingDayUtil"));
+        assert!(pos("ConvertTo30m60m120m") > pos("TxTimeRangeData"));
+    }
+
+    #[tokio::test]
+    async fn test_init_all() {
+        init_test_mysql_pools();
+        let pool = MySqlPools::pool_default().await.unwrap();
+        init_all(&pool).await.unwrap();
+        for status in diagnostics() {
+            println!("{}", status);
+            assert!(status.initialized);
+        }
+    }
+}
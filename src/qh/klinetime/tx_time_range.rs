@@ -38,7 +38,11 @@ impl BreedTxTimeRange {
     // [(2101,2300),(901,1015),(1031,1130),(1331,1500)]
     // [(2101,100),(901,1015),(1031,1130),(1331,1500)]
     // [(2101,230),(901,1015),(1031,1130),(1331,1500)]
-    fn next_minute(&self, datetime: &NaiveDateTime) -> Result<NaiveDateTime, KLineTimeError> {
+    fn next_minute(
+        &self,
+        tdu: &TradingDayUtil,
+        datetime: &NaiveDateTime,
+    ) -> Result<NaiveDateTime, KLineTimeError> {
         let mut close_idx = None;
         let hhmm = Hms::from(datetime).hhmm;
         for (idx, hms) in self.tr_vec.iter().enumerate() {
@@ -70,8 +74,6 @@ impl BreedTxTimeRange {
 
         let end_hhmm = self.tr_vec.last().unwrap().end.hhmm;
 
-        let tdu = TradingDayUtil::current();
-
         let ymd = &Ymd::from(datetime);
 
         let yyyymmdd = ymd.yyyymmdd;
@@ -120,10 +122,15 @@ impl BreedTxTimeRange {
         false
     }
 
-    fn is_first_minute(&self, trading_day: &u32, time: &impl Timelike) -> bool {
+    fn is_first_minute(
+        &self,
+        tdu: &TradingDayUtil,
+        trading_day: &u32,
+        time: &impl Timelike,
+    ) -> bool {
         let hms: Hms = Hms::from(time);
         if self.has_night {
-            if TradingDayUtil::current().has_night(trading_day) {
+            if tdu.has_night(trading_day) {
                 hms == self.tr_vec[0].start
             } else {
                 hms == self.tr_vec[1].start
@@ -141,13 +148,21 @@ impl BreedTxTimeRange {
 
 impl From<TxTimeRangeDbItem> for BreedTxTimeRange {
     fn from(item: TxTimeRangeDbItem) -> Self {
-        // [(2101,230),(901,1015),(1031,1130),(1331,1500)]
-        let value_vec = item
-            .rangelist
+        BreedTxTimeRange::parse(item.breed, &item.rangelist).unwrap()
+    }
+}
+
+impl BreedTxTimeRange {
+    // [(2101,230),(901,1015),(1031,1130),(1331,1500)]
+    fn parse(breed: String, rangelist: &str) -> Option<BreedTxTimeRange> {
+        let value_vec = rangelist
             .replace([' ', '[', ']', '(', ')'], "")
             .split(',')
-            .map(|v| v.parse::<u16>().unwrap())
-            .collect::<Vec<_>>();
+            .map(|v| v.parse::<u16>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        if value_vec.len() < 2 || value_vec.len() % 2 != 0 {
+            return None;
+        }
         let first_value = value_vec.first().unwrap();
         let second_value = value_vec.get(1).unwrap();
         let need_fix = first_value > second_value;
@@ -172,13 +187,13 @@ impl From<TxTimeRangeDbItem> for BreedTxTimeRange {
             }
             range_end_hmap.insert(ehhmmss, ());
         }
-        BreedTxTimeRange {
-            breed: item.breed,
+        Some(BreedTxTimeRange {
+            breed,
             has_night,
             tr_vec: range_vec,
             tr_vec_fix: range_vec_fix,
             range_end_hmap,
-        }
+        })
     }
 }
 
@@ -193,8 +208,13 @@ impl TxTimeRangeData {
         TX_TIME_RANGE_DATA.get().unwrap().clone()
     }
 
+    /// 是否已初始化
+    pub fn is_initialized() -> bool {
+        TX_TIME_RANGE_DATA.get().is_some()
+    }
+
     pub async fn init(pool: &MySqlPool) -> Result<(), sqlx::Error> {
-        if !Self::current().is_empty() {
+        if TX_TIME_RANGE_DATA.get().is_some() {
             return Ok(());
         }
         let mut tru = TxTimeRangeData::default();
@@ -203,6 +223,25 @@ impl TxTimeRangeData {
         Ok(())
     }
 
+    /// 不连接数据库, 用(品种, 交易时间段)创建, 用于测试, 交易时间段的格式同数据库,
+    /// 如: ("AG", "[(2101,230),(901,1015),(1031,1130),(1331,1500)]")
+    pub fn from_rangelists(items: &[(&str, &str)]) -> Result<TxTimeRangeData, KLineTimeError> {
+        let breed_ttr_hmap = items
+            .iter()
+            .map(|(breed, rangelist)| {
+                let breed = breed.to_uppercase();
+                let ttr = BreedTxTimeRange::parse(breed.clone(), rangelist).ok_or_else(|| {
+                    KLineTimeError::RangeListInvalid {
+                        breed:     breed.clone(),
+                        rangelist: (*rangelist).to_owned(),
+                    }
+                })?;
+                Ok((breed, ttr))
+            })
+            .collect::<Result<HashMap<_, _>, KLineTimeError>>()?;
+        Ok(TxTimeRangeData { breed_ttr_hmap })
+    }
+
     async fn init_from_db(&mut self, pool: &MySqlPool) -> Result<(), sqlx::Error> {
         let sql =
             "SELECT breed,rangelist FROM `hqdb`.`tbl_future_tx_time_range` ORDER BY rangelist";
@@ -261,6 +300,16 @@ impl TxTimeRangeData {
         &self,
         breed: &str,
         datetime: &NaiveDateTime,
+    ) -> Result<NaiveDateTime, KLineTimeError> {
+        self.next_minute_with(&TradingDayUtil::current(), breed, datetime)
+    }
+
+    /// 同next_minute, 使用指定的交易日数据
+    pub fn next_minute_with(
+        &self,
+        tdu: &TradingDayUtil,
+        breed: &str,
+        datetime: &NaiveDateTime,
     ) -> Result<NaiveDateTime, KLineTimeError> {
         self.breed_ttr_hmap
             .get(&breed.to_uppercase())
@@ -268,13 +317,24 @@ impl TxTimeRangeData {
                 breed: breed.to_owned(),
                 scope: "TxTimeRangeDate".to_owned(),
             })
-            .map(|v| v.next_minute(datetime))?
+            .map(|v| v.next_minute(tdu, datetime))?
     }
 
     pub fn is_first_minute(&self, breed: &str, trading_day: &u32, time: &impl Timelike) -> bool {
+        self.is_first_minute_with(&TradingDayUtil::current(), breed, trading_day, time)
+    }
+
+    /// 同is_first_minute, 使用指定的交易日数据
+    pub fn is_first_minute_with(
+        &self,
+        tdu: &TradingDayUtil,
+        breed: &str,
+        trading_day: &u32,
+        time: &impl Timelike,
+    ) -> bool {
         self.breed_ttr_hmap
             .get(&breed.to_uppercase())
-            .map_or(false, |v| v.is_first_minute(trading_day, time))
+            .map_or(false, |v| v.is_first_minute(tdu, trading_day, time))
     }
 
     pub fn is_range_end(&self, breed: &str, time: &impl Timelike) -> bool {
@@ -0,0 +1,232 @@
+//! Tick到K线入库各阶段的耗时统计
+//!
+//! 每个阶段一个按2的幂分桶的直方图(微秒), 分位数取桶的上限, 误差在2倍以内
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+
+use super::klineitem::KLineItem;
+
+const BUCKETS: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LatencyStage {
+    /// BarBuilder处理一个Tick
+    Tick2Bar,
+    /// 小周期合成大周期
+    Aggregate,
+    /// 写入数据库
+    Persist,
+    /// K线最后一个Tick的时间到入库完成, 即入库时K线的滞后
+    EndToEnd,
+}
+
+impl LatencyStage {
+    pub const ALL: [LatencyStage; 4] = [
+        LatencyStage::Tick2Bar,
+        LatencyStage::Aggregate,
+        LatencyStage::Persist,
+        LatencyStage::EndToEnd,
+    ];
+
+    fn idx(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl fmt::Display for LatencyStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            LatencyStage::Tick2Bar => "tick2bar",
+            LatencyStage::Aggregate => "aggregate",
+            LatencyStage::Persist => "persist",
+            LatencyStage::EndToEnd => "end2end",
+        };
+        f.pad(s)
+    }
+}
+
+#[derive(Debug)]
+struct Histogram {
+    buckets: [AtomicU64; BUCKETS],
+    sum_us:  AtomicU64,
+    max_us:  AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            sum_us:  AtomicU64::new(0),
+            max_us:  AtomicU64::new(0),
+        }
+    }
+}
+
+impl Histogram {
+    /// 桶i的范围: [2^(i-1), 2^i)微秒, 桶0为0微秒
+    fn bucket(us: u64) -> usize {
+        ((u64::BITS - us.leading_zeros()) as usize).min(BUCKETS - 1)
+    }
+
+    fn record(&self, d: Duration) {
+        let us = d.as_micros().min(u64::MAX as u128) as u64;
+        self.buckets[Self::bucket(us)].fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        for bucket in self.buckets.iter() {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.sum_us.store(0, Ordering::Relaxed);
+        self.max_us.store(0, Ordering::Relaxed);
+    }
+
+    fn stats(&self, stage: LatencyStage) -> LatencyStats {
+        let counts = self
+            .buckets
+            .iter()
+            .map(|v| v.load(Ordering::Relaxed))
+            .collect::<Vec<_>>();
+        let count = counts.iter().sum::<u64>();
+        let max_us = self.max_us.load(Ordering::Relaxed);
+        let quantile = |q: f64| {
+            if count == 0 {
+                return Duration::ZERO;
+            }
+            let rank = ((count as f64 * q).ceil() as u64).max(1);
+            let mut seen = 0;
+            for (idx, c) in counts.iter().enumerate() {
+                seen += c;
+                if seen >= rank {
+                    let upper = if idx == 0 { 0 } else { (1u64 << idx) - 1 };
+                    return Duration::from_micros(upper.min(max_us));
+                }
+            }
+            Duration::from_micros(max_us)
+        };
+        let mean = self
+            .sum_us
+            .load(Ordering::Relaxed)
+            .checked_div(count)
+            .map(Duration::from_micros)
+            .unwrap_or_default();
+        LatencyStats {
+            stage,
+            count,
+            mean,
+            p50: quantile(0.5),
+            p90: quantile(0.9),
+            p99: quantile(0.99),
+            max: Duration::from_micros(max_us),
+        }
+    }
+}
+
+static HISTOGRAMS: OnceLock<[Histogram; 4]> = OnceLock::new();
+
+fn histograms() -> &'static [Histogram; 4] {
+    HISTOGRAMS.get_or_init(Default::default)
+}
+
+/// 一个阶段的耗时统计
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyStats {
+    pub stage: LatencyStage,
+    pub count: u64,
+    pub mean:  Duration,
+    pub p50:   Duration,
+    pub p90:   Duration,
+    pub p99:   Duration,
+    pub max:   Duration,
+}
+
+impl fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<9} count:{} mean:{:?} p50:{:?} p90:{:?} p99:{:?} max:{:?}",
+            self.stage, self.count, self.mean, self.p50, self.p90, self.p99, self.max
+        )
+    }
+}
+
+pub fn record_latency(stage: LatencyStage, d: Duration) {
+    histograms()[stage.idx()].record(d);
+}
+
+/// K线入库后调用, 记录K线最后一个Tick的时间到当前时间的滞后
+pub fn record_bar_persisted(bar: &KLineItem) {
+    let lag = Local::now().naive_local() - bar.last_item_time;
+    record_latency(LatencyStage::EndToEnd, lag.to_std().unwrap_or_default());
+}
+
+/// 各阶段的耗时统计
+pub fn latency_report() -> Vec<LatencyStats> {
+    let histograms = histograms();
+    LatencyStage::ALL
+        .iter()
+        .map(|stage| histograms[stage.idx()].stats(*stage))
+        .collect()
+}
+
+pub fn reset_latency() {
+    for histogram in histograms() {
+        histogram.reset();
+    }
+}
+
+/// 计时, finish时记录
+#[derive(Debug)]
+pub struct StageTimer {
+    stage: LatencyStage,
+    start: Instant,
+}
+
+impl StageTimer {
+    pub fn start(stage: LatencyStage) -> StageTimer {
+        StageTimer {
+            stage,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn finish(self) -> Duration {
+        let elapsed = self.start.elapsed();
+        record_latency(self.stage, elapsed);
+        elapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Histogram, LatencyStage};
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::default();
+        for us in 1..=100u64 {
+            histogram.record(Duration::from_micros(us));
+        }
+        histogram.record(Duration::from_millis(10));
+        let stats = histogram.stats(LatencyStage::Persist);
+        println!("{}", stats);
+        assert_eq!(stats.count, 101);
+        assert_eq!(stats.max, Duration::from_millis(10));
+        // 50在[32, 64)的桶中
+        assert_eq!(stats.p50, Duration::from_micros(63));
+        assert_eq!(stats.p90, Duration::from_micros(127));
+        assert_eq!(stats.p99, Duration::from_micros(127));
+
+        histogram.reset();
+        assert_eq!(histogram.stats(LatencyStage::Persist).count, 0);
+    }
+}
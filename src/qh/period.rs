@@ -1,37 +1,30 @@
-use std::collections::HashMap;
-use std::sync::OnceLock;
+pub use crate::period::{Period, PeriodError};
 
-static PERIOD_MAP: OnceLock<HashMap<String, u16>> = OnceLock::new();
+/// 和`Period::ALL`一一对应的分钟数, `PeriodUtil::pv`返回其中的引用
+static PERIOD_MINUTES: [u16; 10] = [1, 3, 5, 15, 30, 60, 120, 1440, 10080, 43200];
 
+#[deprecated(note = "使用`Period`")]
 pub struct PeriodUtil;
 
+#[allow(deprecated)]
 impl PeriodUtil {
+    /// 按`Period`解析, 返回分钟数
+    #[deprecated(note = "使用`str::parse::<Period>`和`Period::minutes`")]
     pub fn pv(period: &str) -> Option<&u16> {
-        PERIOD_MAP
-            .get_or_init(|| {
-                let mut hmap = HashMap::new();
-                hmap.insert("1m".to_owned(), 1);
-                hmap.insert("3m".to_owned(), 3);
-                hmap.insert("5m".to_owned(), 5);
-                hmap.insert("15m".to_owned(), 15);
-                hmap.insert("30m".to_owned(), 30);
-                hmap.insert("60m".to_owned(), 60);
-                hmap.insert("120m".to_owned(), 120);
-                hmap.insert("1d".to_owned(), 1440);
-                hmap.insert("1w".to_owned(), 10080); // 60*24*7
-                hmap.insert("1mth".to_owned(), 43200); // 60*24*30
-                hmap.insert("1month".to_owned(), 43200); // 60*24*30
-                hmap
-            })
-            .get(period)
+        let period = period.parse::<Period>().ok()?;
+        Period::ALL
+            .iter()
+            .position(|v| *v == period)
+            .map(|idx| &PERIOD_MINUTES[idx])
     }
 }
 
 #[cfg(test)]
+#[allow(deprecated)]
 mod tests {
     use tokio::runtime::Runtime;
 
-    use super::{PeriodUtil, PERIOD_MAP};
+    use super::{Period, PeriodUtil, PERIOD_MINUTES};
 
     #[test]
     fn test_get_pv() {
@@ -39,7 +32,11 @@ mod tests {
         println!("{:?}", v);
         let v = v.take().unwrap().to_owned();
         println!("{}", v);
-        println!("{:?}", PERIOD_MAP.get());
+        assert_eq!(PeriodUtil::pv("1mth"), Some(&43200));
+        assert_eq!(PeriodUtil::pv("7m"), None);
+        for (idx, period) in Period::ALL.iter().enumerate() {
+            assert_eq!(PERIOD_MINUTES[idx], period.minutes());
+        }
     }
 
     #[test]
@@ -54,7 +51,6 @@ mod tests {
                     println!("{:?}", v);
                     let v = v.take().unwrap().to_owned();
                     println!("{}", v);
-                    println!("{:?}", PERIOD_MAP.get());
                 }))
             }
             for handle in handles {
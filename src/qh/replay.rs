@@ -0,0 +1,376 @@
+//! 回测用的历史K线回放
+//!
+//! 多个合约的K线按时间顺序合并后发送到广播通道, 实盘和回测的消费者订阅同一个通道;
+//! 可以按N倍速回放, 时间按交易时间计算, 收盘, 午休等非交易时间不等待
+//!
+//! 通道中未被所有订阅者接收的K线达到max_lag时暂停发送, 慢的订阅者不会因为Lagged丢K线
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDateTime;
+use sqlx::MySqlPool;
+use tokio::sync::broadcast;
+
+use super::klineitem::{KLineItem, KLineItemUtil};
+use super::klinetime::tx_time_range::TxTimeRangeData;
+use super::period::Period;
+use super::trading_day::TradingDayUtil;
+
+/// 按交易时间计算间隔时最多走的分钟数, 超过时按一个周期计算
+const MAX_SESSION_MINUTES: u32 = 7 * 24 * 60;
+
+/// 通道满时等待订阅者接收的间隔
+const BACKPRESSURE_POLL: Duration = Duration::from_millis(1);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayError {
+    #[error("{0}")]
+    Sqlx(#[from] sqlx::Error),
+
+    #[error("no subscriber")]
+    NoSubscriber,
+}
+
+/// 回放速度
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReplaySpeed {
+    /// 不等待, 尽快发送
+    #[default]
+    Max,
+    /// 交易时间的N倍速
+    Times(f64),
+}
+
+/// 回放完成后的统计
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayStats {
+    pub bars:    u64,
+    pub first:   Option<NaiveDateTime>,
+    pub last:    Option<NaiveDateTime>,
+    pub elapsed: Duration,
+}
+
+/// 两根K线之间经过的交易时间: 从prev按品种的交易时间逐分钟走到dt, 收盘, 午休, 节假日不计
+///
+/// prev不是这个品种的交易时间(多个品种交替)或间隔太长时, 按实际间隔计算, 最多一个周期
+fn session_delta(
+    trd: &TxTimeRangeData,
+    tdu: &TradingDayUtil,
+    breed: &str,
+    prev: &NaiveDateTime,
+    dt: &NaiveDateTime,
+    period: Period,
+) -> Duration {
+    let fallback = || {
+        let period = Duration::from_secs(period.minutes() as u64 * 60);
+        (*dt - *prev).to_std().unwrap_or_default().min(period)
+    };
+    let mut minutes = 0;
+    let mut datetime = *prev;
+    while datetime < *dt {
+        if minutes >= MAX_SESSION_MINUTES {
+            return fallback();
+        }
+        match trd.next_minute_with(tdu, breed, &datetime) {
+            Ok(next) => datetime = next,
+            Err(_) => return fallback(),
+        }
+        minutes += 1;
+    }
+    Duration::from_secs(minutes as u64 * 60)
+}
+
+/// 一个合约的缓存, 用完一页再查下一页
+struct Source {
+    tbl_suffix: String,
+    code:       String,
+    buf:        VecDeque<KLineItem>,
+    after:      Option<NaiveDateTime>,
+    has_more:   bool,
+}
+
+pub struct Feeder {
+    util:      Arc<KLineItemUtil>,
+    period:    Period,
+    sources:   Vec<Source>,
+    start:     Option<NaiveDateTime>,
+    end:       Option<NaiveDateTime>,
+    speed:     ReplaySpeed,
+    page_size: u32,
+    max_lag:   usize,
+    session:   Option<(Arc<TxTimeRangeData>, Arc<TradingDayUtil>)>,
+    bus:       broadcast::Sender<KLineItem>,
+}
+
+impl Feeder {
+    /// bus: K线发送的通道, 可以和实盘共用同一个
+    pub fn new(
+        util: Arc<KLineItemUtil>,
+        period: Period,
+        bus: broadcast::Sender<KLineItem>,
+    ) -> Feeder {
+        Feeder {
+            util,
+            period,
+            sources: Vec::new(),
+            start: None,
+            end: None,
+            speed: ReplaySpeed::default(),
+            page_size: 1000,
+            max_lag: 256,
+            session: None,
+            bus,
+        }
+    }
+
+    /// 添加回放的合约, 数据在表`tbl_code_{tbl_suffix}`中
+    pub fn with_code(mut self, tbl_suffix: &str, code: &str) -> Self {
+        self.sources.push(Source {
+            tbl_suffix: tbl_suffix.to_owned(),
+            code:       code.to_owned(),
+            buf:        VecDeque::new(),
+            after:      None,
+            has_more:   true,
+        });
+        self
+    }
+
+    /// 回放的时间范围, 包含start和end
+    pub fn with_range(self, start: Option<NaiveDateTime>, end: Option<NaiveDateTime>) -> Self {
+        Feeder { start, end, ..self }
+    }
+
+    pub fn with_speed(self, speed: ReplaySpeed) -> Self {
+        Feeder { speed, ..self }
+    }
+
+    pub fn with_page_size(self, page_size: u32) -> Self {
+        Feeder {
+            page_size: page_size.max(1),
+            ..self
+        }
+    }
+
+    /// 通道中最多未接收的K线数, 需要小于通道的容量, 默认256
+    pub fn with_max_lag(self, max_lag: usize) -> Self {
+        Feeder {
+            max_lag: max_lag.max(1),
+            ..self
+        }
+    }
+
+    /// 按倍速回放时计算交易时间用的数据, 不指定时使用`TxTimeRangeData::current()`和
+    /// `TradingDayUtil::current()`
+    pub fn with_session(self, trd: Arc<TxTimeRangeData>, tdu: Arc<TradingDayUtil>) -> Self {
+        Feeder {
+            session: Some((trd, tdu)),
+            ..self
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<KLineItem> {
+        self.bus.subscribe()
+    }
+
+    /// 取合约的下一根K线, 缓存用完时查询下一页, 超出结束时间时为None
+    async fn next_item(
+        &self,
+        pool: &MySqlPool,
+        source: &mut Source,
+    ) -> Result<Option<KLineItem>, ReplayError> {
+        if source.buf.is_empty() && source.has_more {
+            let after = source
+                .after
+                .or_else(|| self.start.map(|v| v - chrono::Duration::seconds(1)));
+            let page = self
+                .util
+                .item_page_after(
+                    pool,
+                    &source.tbl_suffix,
+                    &source.code,
+                    self.period.minutes(),
+                    after.as_ref(),
+                    self.page_size,
+                )
+                .await?;
+            source.has_more = page.has_more;
+            source.after = page.last().map(|v| v.datetime).or(source.after);
+            source.buf.extend(page.items);
+        }
+        let item = source.buf.pop_front();
+        Ok(item.filter(|v| self.end.is_none_or(|end| v.datetime <= end)))
+    }
+
+    /// 等待通道中未接收的K线少于max_lag
+    async fn wait_subscribers(&self) -> Result<(), ReplayError> {
+        while self.bus.len() >= self.max_lag {
+            if self.bus.receiver_count() == 0 {
+                return Err(ReplayError::NoSubscriber);
+            }
+            tokio::time::sleep(BACKPRESSURE_POLL).await;
+        }
+        Ok(())
+    }
+
+    /// 回放所有合约, 同一时间的K线按添加合约的顺序发送
+    pub async fn run(&mut self, pool: &MySqlPool) -> Result<ReplayStats, ReplayError> {
+        let start = Instant::now();
+        let mut sources = std::mem::take(&mut self.sources);
+        let mut heap = BinaryHeap::new();
+        for (idx, source) in sources.iter_mut().enumerate() {
+            if let Some(item) = self.next_item(pool, source).await? {
+                heap.push(Reverse((item.datetime, idx, Wrapper(item))));
+            }
+        }
+
+        let session = match (self.speed, &self.session) {
+            (ReplaySpeed::Max, _) => None,
+            (_, Some(session)) => Some(session.clone()),
+            (_, None) => Some((TxTimeRangeData::current(), TradingDayUtil::current())),
+        };
+        let mut stats = ReplayStats::default();
+        let mut clock = Duration::ZERO;
+        while let Some(Reverse((datetime, idx, Wrapper(item)))) = heap.pop() {
+            if let (ReplaySpeed::Times(times), Some((trd, tdu)), Some(prev)) =
+                (self.speed, &session, stats.last)
+            {
+                let delta = session_delta(trd, tdu, &item.breed(), &prev, &datetime, self.period);
+                clock += delta.div_f64(times.max(f64::MIN_POSITIVE));
+                let wait = clock.saturating_sub(start.elapsed());
+                if !wait.is_zero() {
+                    tokio::time::sleep(wait).await;
+                }
+            }
+            self.wait_subscribers().await?;
+            self.bus.send(item).map_err(|_| ReplayError::NoSubscriber)?;
+            stats.bars += 1;
+            stats.first.get_or_insert(datetime);
+            stats.last = Some(datetime);
+            if let Some(item) = self.next_item(pool, &mut sources[idx]).await? {
+                heap.push(Reverse((item.datetime, idx, Wrapper(item))));
+            }
+        }
+        self.sources = sources;
+        stats.elapsed = start.elapsed();
+        Ok(stats)
+    }
+}
+
+/// 堆中按(时间, 合约序号)排序, 不比较K线本身
+struct Wrapper(KLineItem);
+
+impl PartialEq for Wrapper {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Wrapper {
+}
+
+impl PartialOrd for Wrapper {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Wrapper {
+    fn cmp(&self, _: &Self) -> std::cmp::Ordering {
+        std::cmp::Ordering::Equal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+    use tokio::sync::broadcast;
+
+    use super::{session_delta, Feeder, ReplaySpeed};
+    use crate::mysqlx::MySqlPools;
+    use crate::mysqlx_test_pool::init_test_mysql_pools;
+    use crate::qh::klineitem::KLineItemUtil;
+    use crate::qh::klinetime::fixture::{trading_day_util, tx_time_range_data};
+    use crate::qh::period::Period;
+
+    #[test]
+    fn test_session_delta() {
+        let trd = tx_time_range_data();
+        let tdu = trading_day_util(20220613, 20220624, &[]).unwrap();
+        let dt = |d, h, m| {
+            NaiveDate::from_ymd_opt(2022, 6, d)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+        let minutes = |v: u64| Duration::from_secs(v * 60);
+        let cases = [
+            ("AG", dt(20, 9, 1), dt(20, 9, 2), Period::M1, minutes(1)),
+            // 小节休息, 午休, 收盘到夜盘, 夜盘到周一都只算一分钟
+            ("AG", dt(20, 10, 15), dt(20, 10, 31), Period::M1, minutes(1)),
+            ("AG", dt(20, 11, 30), dt(20, 13, 31), Period::M1, minutes(1)),
+            ("AG", dt(17, 15, 0), dt(17, 21, 1), Period::M1, minutes(1)),
+            ("AG", dt(18, 2, 30), dt(20, 9, 1), Period::M1, minutes(1)),
+            // 中间缺K线时按交易时间计算
+            ("AG", dt(20, 11, 0), dt(20, 13, 35), Period::M1, minutes(35)),
+            ("AG", dt(20, 9, 5), dt(20, 9, 10), Period::M5, minutes(5)),
+            ("AG", dt(20, 9, 5), dt(20, 9, 1), Period::M5, Duration::ZERO),
+            // 不是这个品种的交易时间
+            ("IC", dt(17, 21, 1), dt(20, 9, 31), Period::M1, minutes(1)),
+        ];
+        for (breed, prev, dt, period, expected) in cases {
+            assert_eq!(
+                session_delta(&trd, &tdu, breed, &prev, &dt, period),
+                expected,
+                "{} {} {}",
+                breed,
+                prev,
+                dt
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_feeder() {
+        init_test_mysql_pools();
+        let pool = MySqlPools::pool_default().await.unwrap();
+        let day = NaiveDate::from_ymd_opt(2022, 6, 20).unwrap();
+        // 容量小于回放的K线数, 有背压时订阅者不会Lagged
+        let (bus, _) = broadcast::channel(8);
+        let mut feeder = Feeder::new(Arc::new(KLineItemUtil::try_new("hqdb").unwrap()), Period::M1, bus)
+            .with_code("agL9", "agL9")
+            .with_code("alL9", "alL9")
+            .with_range(day.and_hms_opt(9, 1, 0), day.and_hms_opt(9, 30, 0))
+            .with_speed(ReplaySpeed::Times(6000.0))
+            .with_page_size(7)
+            .with_max_lag(4)
+            .with_session(
+                Arc::new(tx_time_range_data()),
+                Arc::new(trading_day_util(20220613, 20220624, &[]).unwrap()),
+            );
+        let mut rx = feeder.subscribe();
+        let consumer = tokio::spawn(async move {
+            let mut items = Vec::new();
+            loop {
+                match rx.recv().await {
+                    Ok(item) => {
+                        tokio::time::sleep(Duration::from_millis(1)).await;
+                        items.push(item)
+                    },
+                    Err(broadcast::error::RecvError::Closed) => return items,
+                    Err(e) => panic!("{}", e),
+                }
+            }
+        });
+        let stats = feeder.run(&pool).await.unwrap();
+        drop(feeder);
+        let items = consumer.await.unwrap();
+        assert_eq!(items.len() as u64, stats.bars);
+        assert!(items.windows(2).all(|v| v[0].datetime <= v[1].datetime));
+        println!("{:?}", stats);
+    }
+}
@@ -0,0 +1,471 @@
+//! Tick合成K线, 处理K线结束后才到达的迟到Tick
+
+use chrono::{Duration, NaiveDateTime};
+use rust_decimal::Decimal;
+
+use super::klineitem::KLineItem;
+use super::latency::{LatencyStage, StageTimer};
+use super::period::Period;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tick {
+    pub code:          String,
+    pub datetime:      NaiveDateTime,
+    pub price:         Decimal,
+    /// 累计成交量
+    pub total_volume:  i64,
+    pub open_interest: i64,
+}
+
+/// K线已结束后才到达的Tick的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LateTickPolicy {
+    /// 丢弃
+    #[default]
+    Drop,
+    /// 属于上一根已结束K线的Tick, 修正该K线的最高最低价后重新发布, 更早的Tick丢弃
+    AmendLastBar,
+    /// 不修改K线, 通过BarEvent::Correction单独发布
+    SideChannel,
+}
+
+/// 到了K线结束时间还没有Tick时的处理方式, 需要定时调用`BarBuilder::on_timer`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyntheticBarPolicy {
+    /// 不处理, 等下一个Tick
+    #[default]
+    Off,
+    /// 用上一根K线(或with_prev_bar设置的K线)的收盘价补成交量为0的K线
+    PrevClose,
+}
+
+#[derive(Debug, Clone)]
+pub enum BarEvent {
+    /// 当前K线有更新
+    Update(KLineItem),
+    /// K线结束
+    Closed(KLineItem),
+    /// 已结束的K线被迟到Tick修正, 需要重新发布
+    Amended(KLineItem),
+    /// 迟到Tick, 及其所属K线的时间
+    Correction {
+        tick:     Tick,
+        bar_time: NaiveDateTime,
+    },
+    /// 没有Tick时补的K线, 已结束
+    Synthetic(KLineItem),
+}
+
+/// 行情质量统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TickMetrics {
+    pub ticks:        u64,
+    /// 所属K线已结束的Tick
+    pub late:         u64,
+    /// 和上一个Tick完全相同的Tick
+    pub duplicate:    u64,
+    /// 时间比上一个Tick早的Tick
+    pub backward:     u64,
+    /// 不在交易时间内的Tick
+    pub out_of_range: u64,
+    pub dropped:      u64,
+    pub amended:      u64,
+    /// 补的K线数
+    pub synthetic:    u64,
+}
+
+/// Tick合成K线
+/// bar_time: Tick时间转成所属K线的时间, 返回None表示不在交易时间内,
+/// 1m可以使用klinetime中的1m转换
+pub struct BarBuilder<F> {
+    code:             String,
+    period:           Period,
+    bar_time:         F,
+    policy:           LateTickPolicy,
+    synthetic:        SyntheticBarPolicy,
+    current:          Option<KLineItem>,
+    last_closed:      Option<KLineItem>,
+    /// 还没有K线时补K线用的收盘价, 如缓存中的最新K线
+    prev_bar:         Option<KLineItem>,
+    last_tick:        Option<Tick>,
+    /// 当前K线开始前的累计成交量
+    bar_start_volume: i64,
+    metrics:          TickMetrics,
+}
+
+impl<F> BarBuilder<F>
+where
+    F: Fn(&NaiveDateTime) -> Option<NaiveDateTime>,
+{
+    pub fn new(code: &str, period: Period, bar_time: F) -> BarBuilder<F> {
+        BarBuilder {
+            code: code.to_owned(),
+            period,
+            bar_time,
+            policy: LateTickPolicy::default(),
+            synthetic: SyntheticBarPolicy::default(),
+            current: None,
+            last_closed: None,
+            prev_bar: None,
+            last_tick: None,
+            bar_start_volume: 0,
+            metrics: TickMetrics::default(),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: LateTickPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_synthetic_policy(mut self, synthetic: SyntheticBarPolicy) -> Self {
+        self.synthetic = synthetic;
+        self
+    }
+
+    /// 开盘后还没有Tick时补K线用的上一根K线, 如`BarWriteBehind::latest`取得的最新K线
+    pub fn with_prev_bar(mut self, prev_bar: KLineItem) -> Self {
+        self.prev_bar = Some(prev_bar);
+        self
+    }
+
+    pub fn metrics(&self) -> &TickMetrics {
+        &self.metrics
+    }
+
+    pub fn current(&self) -> Option<&KLineItem> {
+        self.current.as_ref()
+    }
+
+    pub fn on_tick(&mut self, tick: Tick) -> Vec<BarEvent> {
+        let timer = StageTimer::start(LatencyStage::Tick2Bar);
+        let events = self.process_tick(tick);
+        timer.finish();
+        events
+    }
+
+    fn process_tick(&mut self, tick: Tick) -> Vec<BarEvent> {
+        self.metrics.ticks += 1;
+        let mut events = Vec::new();
+
+        let mut backward = false;
+        if let Some(last_tick) = &self.last_tick {
+            if *last_tick == tick {
+                self.metrics.duplicate += 1;
+                self.metrics.dropped += 1;
+                return events;
+            }
+            if tick.datetime < last_tick.datetime {
+                self.metrics.backward += 1;
+                backward = true;
+            }
+        }
+
+        let Some(bar_time) = (self.bar_time)(&tick.datetime) else {
+            self.metrics.out_of_range += 1;
+            self.metrics.dropped += 1;
+            return events;
+        };
+
+        let current_time = self.current.as_ref().map(|v| v.datetime);
+        let last_closed_time = self.last_closed.as_ref().map(|v| v.datetime);
+        match current_time {
+            // 所属K线已结束(包括补的K线)
+            None if last_closed_time.is_some_and(|v| bar_time <= v) => {
+                self.on_late_tick(tick, bar_time, &mut events);
+                return events;
+            },
+            Some(current_time) if bar_time < current_time => {
+                self.on_late_tick(tick, bar_time, &mut events);
+                return events;
+            },
+            Some(current_time) if bar_time > current_time => {
+                let closed = self.current.take().unwrap();
+                self.bar_start_volume = closed.total_volume;
+                // 累计成交量已重置(新交易日, 上一交易日最后的K线没有flush)
+                if tick.total_volume < self.bar_start_volume {
+                    self.bar_start_volume = tick.total_volume;
+                }
+                events.push(BarEvent::Closed(closed.clone()));
+                self.last_closed = Some(closed);
+                self.current = Some(self.new_bar(&tick, bar_time));
+            },
+            Some(_) => {
+                let bar = self.current.as_mut().unwrap();
+                bar.high = bar.high.max(tick.price);
+                bar.low = bar.low.min(tick.price);
+                // 时间倒退的Tick只用于修正最高最低价
+                if !backward {
+                    bar.close = tick.price;
+                    bar.total_volume = tick.total_volume;
+                    bar.volume = tick.total_volume - self.bar_start_volume;
+                    bar.close_oi = tick.open_interest;
+                    bar.last_item_time = tick.datetime;
+                }
+            },
+            None => {
+                // 第一个Tick, 或累计成交量已重置(新交易日)
+                if self.last_tick.is_none() || tick.total_volume < self.bar_start_volume {
+                    self.bar_start_volume = tick.total_volume;
+                }
+                self.current = Some(self.new_bar(&tick, bar_time));
+            },
+        }
+        events.push(BarEvent::Update(self.current.clone().unwrap()));
+        if !backward {
+            self.last_tick = Some(tick);
+        }
+        events
+    }
+
+    fn new_bar(&self, tick: &Tick, bar_time: NaiveDateTime) -> KLineItem {
+        let mut bar = KLineItem::new(&self.code, &bar_time, self.period.into());
+        bar.open = tick.price;
+        bar.high = tick.price;
+        bar.low = tick.price;
+        bar.close = tick.price;
+        bar.total_volume = tick.total_volume;
+        bar.volume = tick.total_volume - self.bar_start_volume;
+        bar.open_oi = tick.open_interest;
+        bar.close_oi = tick.open_interest;
+        bar.last_item_time = tick.datetime;
+        bar
+    }
+
+    fn on_late_tick(&mut self, tick: Tick, bar_time: NaiveDateTime, events: &mut Vec<BarEvent>) {
+        self.metrics.late += 1;
+        match self.policy {
+            LateTickPolicy::Drop => {
+                self.metrics.dropped += 1;
+            },
+            LateTickPolicy::AmendLastBar => match self.last_closed.as_mut() {
+                Some(bar) if bar.datetime == bar_time => {
+                    bar.high = bar.high.max(tick.price);
+                    bar.low = bar.low.min(tick.price);
+                    self.metrics.amended += 1;
+                    events.push(BarEvent::Amended(bar.clone()));
+                },
+                _ => {
+                    self.metrics.dropped += 1;
+                },
+            },
+            LateTickPolicy::SideChannel => {
+                events.push(BarEvent::Correction { tick, bar_time });
+            },
+        }
+    }
+
+    /// 在每分钟的边界调用, now为当前时间. 刚结束的K线没有Tick时按SyntheticBarPolicy补K线,
+    /// 之前的K线还没有结束时先结束该K线. 每次最多补一根, 错过的边界不补
+    pub fn on_timer(&mut self, now: NaiveDateTime) -> Vec<BarEvent> {
+        let mut events = Vec::new();
+        if self.synthetic == SyntheticBarPolicy::Off {
+            return events;
+        }
+        let Some(ended) = (self.bar_time)(&(now - Duration::try_seconds(1).unwrap())) else {
+            return events;
+        };
+        if let Some(current) = &self.current {
+            if current.datetime >= ended {
+                return events;
+            }
+            let closed = self.current.take().unwrap();
+            self.bar_start_volume = closed.total_volume;
+            events.push(BarEvent::Closed(closed.clone()));
+            self.last_closed = Some(closed);
+        }
+        if self
+            .last_closed
+            .as_ref()
+            .is_some_and(|v| v.datetime >= ended)
+        {
+            return events;
+        }
+        let Some(prev) = self.last_closed.as_ref().or(self.prev_bar.as_ref()) else {
+            return events;
+        };
+        let bar = self.synthetic_bar(prev, ended);
+        self.metrics.synthetic += 1;
+        events.push(BarEvent::Synthetic(bar.clone()));
+        self.last_closed = Some(bar);
+        events
+    }
+
+    /// 成交量为0, 开高低收都为上一根K线的收盘价, 持仓不变
+    fn synthetic_bar(&self, prev: &KLineItem, bar_time: NaiveDateTime) -> KLineItem {
+        let mut bar = KLineItem::new(&self.code, &bar_time, self.period.into());
+        bar.open = prev.close;
+        bar.high = prev.close;
+        bar.low = prev.close;
+        bar.close = prev.close;
+        bar.total_volume = prev.total_volume;
+        bar.open_oi = prev.close_oi;
+        bar.close_oi = prev.close_oi;
+        bar
+    }
+
+    /// 结束当前K线, 如收盘时
+    pub fn flush(&mut self) -> Option<KLineItem> {
+        let closed = self.current.take()?;
+        self.bar_start_volume = closed.total_volume;
+        self.last_closed = Some(closed.clone());
+        Some(closed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, NaiveDateTime, Timelike};
+    use rust_decimal::Decimal;
+
+    use super::{BarBuilder, BarEvent, LateTickPolicy, SyntheticBarPolicy, Tick};
+    use crate::qh::klineitem::KLineItem;
+    use crate::qh::period::Period;
+
+    fn bar_time(dt: &NaiveDateTime) -> Option<NaiveDateTime> {
+        let minute = dt.with_second(0)?.with_nanosecond(0)?;
+        Some(minute + Duration::try_minutes(1).unwrap())
+    }
+
+    fn tick(hms: (u32, u32, u32), price: i64, total_volume: i64) -> Tick {
+        Tick {
+            code: "ag2408".to_string(),
+            datetime: NaiveDate::from_ymd_opt(2024, 6, 3)
+                .unwrap()
+                .and_hms_opt(hms.0, hms.1, hms.2)
+                .unwrap(),
+            price: Decimal::from(price),
+            total_volume,
+            open_interest: 100,
+        }
+    }
+
+    fn builder(policy: LateTickPolicy) -> BarBuilder<fn(&NaiveDateTime) -> Option<NaiveDateTime>> {
+        let mut builder = BarBuilder::new(
+            "ag2408",
+            Period::M1,
+            bar_time as fn(&NaiveDateTime) -> Option<NaiveDateTime>,
+        )
+        .with_policy(policy);
+        builder.on_tick(tick((9, 0, 1), 100, 10));
+        builder.on_tick(tick((9, 0, 30), 105, 15));
+        builder.on_tick(tick((9, 0, 30), 105, 15));
+        let events = builder.on_tick(tick((9, 1, 2), 103, 20));
+        assert!(
+            matches!(&events[0], BarEvent::Closed(bar) if bar.volume == 5 && bar.high == Decimal::from(105))
+        );
+        builder
+    }
+
+    #[test]
+    fn test_drop() {
+        let mut builder = builder(LateTickPolicy::Drop);
+        let events = builder.on_tick(tick((9, 0, 50), 110, 18));
+        println!("{:?}", events);
+        assert!(events.is_empty());
+        println!("{:?}", builder.metrics());
+        assert_eq!(builder.metrics().late, 1);
+        assert_eq!(builder.metrics().duplicate, 1);
+        assert_eq!(builder.metrics().backward, 1);
+        assert_eq!(builder.metrics().dropped, 2);
+        let bar = builder.flush().unwrap();
+        println!("{}", bar);
+        assert_eq!(bar.volume, 5);
+    }
+
+    #[test]
+    fn test_amend() {
+        let mut builder = builder(LateTickPolicy::AmendLastBar);
+        let events = builder.on_tick(tick((9, 0, 50), 110, 18));
+        println!("{:?}", events);
+        assert!(matches!(&events[0], BarEvent::Amended(bar) if bar.high == Decimal::from(110)));
+        let events = builder.on_tick(tick((8, 58, 50), 90, 1));
+        assert!(events.is_empty());
+        assert_eq!(builder.metrics().amended, 1);
+        assert_eq!(builder.metrics().late, 2);
+    }
+
+    #[test]
+    fn test_side_channel() {
+        let mut builder = builder(LateTickPolicy::SideChannel);
+        let events = builder.on_tick(tick((9, 0, 50), 110, 18));
+        println!("{:?}", events);
+        assert!(
+            matches!(&events[0], BarEvent::Correction { tick, .. } if tick.price == Decimal::from(110))
+        );
+        assert_eq!(builder.current().unwrap().high, Decimal::from(103));
+    }
+
+    #[test]
+    fn test_trading_day_rollover() {
+        let mut builder = BarBuilder::new(
+            "ag2408",
+            Period::M1,
+            bar_time as fn(&NaiveDateTime) -> Option<NaiveDateTime>,
+        );
+        builder.on_tick(tick((14, 59, 30), 100, 1000));
+        // 收盘时没有flush, 下一交易日的累计成交量从头开始
+        let next_day = |hms, price, total_volume| {
+            let mut tick = tick(hms, price, total_volume);
+            tick.datetime += Duration::try_days(1).unwrap();
+            tick
+        };
+        let events = builder.on_tick(next_day((9, 0, 5), 101, 3));
+        assert!(
+            matches!(&events[..], [BarEvent::Closed(closed), BarEvent::Update(bar)] if closed.volume == 0 && bar.volume == 0)
+        );
+        let events = builder.on_tick(next_day((9, 0, 30), 102, 8));
+        assert!(matches!(&events[..], [BarEvent::Update(bar)] if bar.volume == 5));
+        let events = builder.on_tick(next_day((9, 1, 0), 102, 10));
+        assert!(
+            matches!(&events[..], [BarEvent::Closed(closed), BarEvent::Update(bar)] if closed.volume == 5 && bar.volume == 2)
+        );
+    }
+
+    #[test]
+    fn test_synthetic() {
+        let dt = |h, m| {
+            NaiveDate::from_ymd_opt(2024, 6, 3)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+        let bar_time = |dt: &NaiveDateTime| {
+            let minute = dt.with_second(0)?.with_nanosecond(0)?;
+            (minute.hour() >= 9).then(|| minute + Duration::try_minutes(1).unwrap())
+        };
+        let mut prev = KLineItem::new("ag2408", &dt(15, 0), 1);
+        prev.close = Decimal::from(99);
+        prev.close_oi = 80;
+        let mut builder = BarBuilder::new("ag2408", Period::M1, bar_time)
+            .with_synthetic_policy(SyntheticBarPolicy::PrevClose)
+            .with_prev_bar(prev);
+
+        // 开盘前不补
+        assert!(builder.on_timer(dt(9, 0)).is_empty());
+        let events = builder.on_timer(dt(9, 1));
+        assert!(
+            matches!(&events[..], [BarEvent::Synthetic(bar)] if bar.datetime == dt(9, 1) && bar.close == Decimal::from(99) && bar.volume == 0 && bar.close_oi == 80)
+        );
+        assert!(builder.on_timer(dt(9, 1)).is_empty());
+
+        // 已补的K线的Tick为迟到Tick
+        let events = builder.on_tick(tick((9, 0, 59), 100, 10));
+        assert!(events.is_empty());
+        assert_eq!(builder.metrics().late, 1);
+
+        builder.on_tick(tick((9, 1, 5), 101, 10));
+        builder.on_tick(tick((9, 1, 30), 102, 12));
+        assert!(builder.on_timer(dt(9, 1)).is_empty());
+        let events = builder.on_timer(dt(9, 3));
+        assert!(matches!(&events[0], BarEvent::Closed(bar) if bar.datetime == dt(9, 2)));
+        assert!(
+            matches!(&events[1], BarEvent::Synthetic(bar) if bar.datetime == dt(9, 3) && bar.close == Decimal::from(102))
+        );
+        assert_eq!(builder.metrics().synthetic, 2);
+
+        // 补K线后的成交量从上一根实际K线结束时算起
+        let events = builder.on_tick(tick((9, 3, 10), 103, 15));
+        assert!(matches!(&events[0], BarEvent::Update(bar) if bar.volume == 3));
+    }
+}
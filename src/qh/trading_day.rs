@@ -62,12 +62,17 @@ impl TradingDayUtil {
         TRADING_DAY_UTIL.get().unwrap().clone()
     }
 
+    /// 是否已初始化
+    pub fn is_initialized() -> bool {
+        TRADING_DAY_UTIL.get().is_some()
+    }
+
     // pub fn current() -> RwLockReadGuard<'static, TradingDayUtil> {
     //     TRADING_DAY_UTIL.read().unwrap()
     // }
 
     pub async fn init(pool: &MySqlPool) -> Result<(), TradingDayUtilInitError> {
-        if !Self::current().td_vec.is_empty() {
+        if TRADING_DAY_UTIL.get().is_some() {
             return Ok(());
         }
         let mut new_inner = TradingDayUtil::default();
@@ -82,20 +87,35 @@ impl TradingDayUtil {
 
     async fn init_from_db(&mut self, pool: &MySqlPool) -> Result<(), TradingDayUtilInitError> {
         let sql = "SELECT trading_day FROM `hqdb`.`tbl_ths_trading_day` ORDER BY trading_day";
-        let mut db_rows = sqlx::query_as::<_, TradingDayDbItem>(sql).fetch(pool);
-        let mut td_vec: Vec<Ymd> = Vec::new();
+        let td_vec = sqlx::query_as::<_, TradingDayDbItem>(sql)
+            .fetch(pool)
+            .map_ok(Ymd::from)
+            .try_collect::<Vec<_>>()
+            .await?;
+        self.init_from_td_vec(td_vec)
+    }
+
+    /// 不连接数据库, 用交易日列表(格式:20220607)创建, 用于测试
+    ///
+    /// 列表不需要排序, 重复的交易日只保留一个
+    pub fn from_trading_days(days: &[u32]) -> Result<TradingDayUtil, TradingDayUtilInitError> {
+        let mut days = days.to_vec();
+        days.sort_unstable();
+        days.dedup();
+        let mut tdu = TradingDayUtil::default();
+        tdu.init_from_td_vec(days.into_iter().map(Ymd::from_yyyymmdd).collect())?;
+        Ok(tdu)
+    }
 
+    /// td_vec为按顺序的交易日
+    fn init_from_td_vec(&mut self, td_vec: Vec<Ymd>) -> Result<(), TradingDayUtilInitError> {
         let mut day_idx_map: HashMap<u32, DayInfo> = HashMap::new();
         let mut prev_idx = 0;
-        let mut idx = 0;
         let mut prev_date = None;
         let days_1 = Duration::try_days(1).unwrap();
         let days_3 = Duration::try_days(3).unwrap();
-        while let Some(db_item) = db_rows.try_next().await? {
-            let td = Ymd::from(db_item);
-            td_vec.push(td);
-
-            let date = NaiveDate::from(&td);
+        for (idx, td) in td_vec.iter().enumerate() {
+            let date = NaiveDate::from(td);
 
             let has_night = if let Some(prev_date) = prev_date {
                 // 有夜盘的情况
@@ -122,7 +142,6 @@ impl TradingDayUtil {
             day_idx_map.insert(td.yyyymmdd, day_info);
             prev_idx = idx;
             prev_date = Some(date);
-            idx += 1;
         }
         if td_vec.is_empty() {
             return Err(TradingDayUtilInitError::Empty);
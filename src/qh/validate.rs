@@ -0,0 +1,260 @@
+//! 连续合约(L9)在换月前后的K线检查, 结果可序列化给下游复权工具使用
+
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use super::klineitem::KLineItem;
+
+pub mod compare;
+
+pub use self::compare::{compare_bars, CompareConfig, CompareReport, TimeAlign, Tolerance};
+
+/// 换月记录, 一般来自主力合约表
+/// datetime: 新主力合约的第一根K线时间
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RollEvent {
+    pub datetime: NaiveDateTime,
+    pub from:     String,
+    pub to:       String,
+}
+
+/// gap_threshold: 换月前后价格跳空比例超过该值时标记
+/// volume_ratio: 换月后/前平均成交量的比例超过该值或低于其倒数时标记
+/// window: 计算平均成交量的K线数
+#[derive(Debug, Clone)]
+pub struct RollCheckConfig {
+    gap_threshold: Decimal,
+    volume_ratio:  f64,
+    window:        usize,
+}
+
+impl Default for RollCheckConfig {
+    fn default() -> Self {
+        RollCheckConfig {
+            gap_threshold: Decimal::new(2, 2),
+            volume_ratio:  3.0,
+            window:        5,
+        }
+    }
+}
+
+impl RollCheckConfig {
+    pub fn with_gap_threshold(self, gap_threshold: Decimal) -> Self {
+        RollCheckConfig {
+            gap_threshold,
+            ..self
+        }
+    }
+
+    pub fn with_volume_ratio(self, volume_ratio: f64) -> Self {
+        RollCheckConfig {
+            volume_ratio,
+            ..self
+        }
+    }
+
+    pub fn with_window(self, window: usize) -> Self {
+        RollCheckConfig {
+            window: window.max(1),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RollFlag {
+    /// 换月前或后没有K线
+    MissingBars,
+    PriceGap,
+    VolumeJump,
+    VolumeDrop,
+    /// 换月前后的K线时间重复
+    DuplicateTime,
+}
+
+/// 单次换月的检查结果
+#[derive(Debug, Clone, Serialize)]
+pub struct RollCheck {
+    pub roll:          RollEvent,
+    pub bar_before:    Option<NaiveDateTime>,
+    pub bar_after:     Option<NaiveDateTime>,
+    pub prev_close:    Option<Decimal>,
+    pub open:          Option<Decimal>,
+    /// open - prev_close
+    pub gap:           Option<Decimal>,
+    /// gap / prev_close
+    pub gap_ratio:     Option<Decimal>,
+    pub volume_before: f64,
+    pub volume_after:  f64,
+    pub flags:         Vec<RollFlag>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RollReport {
+    pub code:         String,
+    pub bars:         usize,
+    /// 重复的K线时间
+    pub duplicates:   Vec<NaiveDateTime>,
+    /// 比上一根K线时间早的K线时间
+    pub out_of_order: Vec<NaiveDateTime>,
+    pub rolls:        Vec<RollCheck>,
+}
+
+impl RollReport {
+    pub fn is_ok(&self) -> bool {
+        self.duplicates.is_empty()
+            && self.out_of_order.is_empty()
+            && self.rolls.iter().all(|v| v.flags.is_empty())
+    }
+}
+
+/// 检查连续合约的K线, bars按时间排序
+pub fn check_rolls(
+    code: &str,
+    bars: &[KLineItem],
+    rolls: &[RollEvent],
+    config: &RollCheckConfig,
+) -> RollReport {
+    let mut duplicates = Vec::new();
+    let mut out_of_order = Vec::new();
+    for w in bars.windows(2) {
+        if w[1].datetime == w[0].datetime {
+            duplicates.push(w[1].datetime);
+        } else if w[1].datetime < w[0].datetime {
+            out_of_order.push(w[1].datetime);
+        }
+    }
+
+    let rolls = rolls
+        .iter()
+        .map(|roll| check_roll(bars, roll, config))
+        .collect();
+
+    RollReport {
+        code: code.to_owned(),
+        bars: bars.len(),
+        duplicates,
+        out_of_order,
+        rolls,
+    }
+}
+
+fn avg_volume(bars: &[KLineItem]) -> f64 {
+    if bars.is_empty() {
+        return 0.0;
+    }
+    bars.iter().map(|v| v.volume as f64).sum::<f64>() / bars.len() as f64
+}
+
+fn check_roll(bars: &[KLineItem], roll: &RollEvent, config: &RollCheckConfig) -> RollCheck {
+    let idx = bars.partition_point(|v| v.datetime < roll.datetime);
+    let before = idx.checked_sub(1).and_then(|i| bars.get(i));
+    let after = bars.get(idx);
+
+    let mut flags = Vec::new();
+    if before.is_none() || after.is_none() {
+        flags.push(RollFlag::MissingBars);
+    }
+    if after.is_some_and(|after| {
+        bars.get(idx + 1)
+            .is_some_and(|v| v.datetime == after.datetime)
+    }) || before
+        .zip(after)
+        .is_some_and(|(b, a)| b.datetime == a.datetime)
+    {
+        flags.push(RollFlag::DuplicateTime);
+    }
+
+    let prev_close = before.map(|v| v.close);
+    let open = after.map(|v| v.open);
+    let gap = prev_close
+        .zip(open)
+        .map(|(prev_close, open)| open - prev_close);
+    let gap_ratio = gap
+        .zip(prev_close)
+        .and_then(|(gap, prev_close)| gap.checked_div(prev_close));
+    if gap_ratio.is_some_and(|v| v.abs() >= config.gap_threshold) {
+        flags.push(RollFlag::PriceGap);
+    }
+
+    let volume_before = avg_volume(&bars[idx.saturating_sub(config.window)..idx]);
+    let volume_after = avg_volume(&bars[idx..(idx + config.window).min(bars.len())]);
+    if volume_before > 0.0 && volume_after > 0.0 {
+        let ratio = volume_after / volume_before;
+        if ratio >= config.volume_ratio {
+            flags.push(RollFlag::VolumeJump);
+        } else if ratio <= 1.0 / config.volume_ratio {
+            flags.push(RollFlag::VolumeDrop);
+        }
+    }
+
+    RollCheck {
+        roll: roll.clone(),
+        bar_before: before.map(|v| v.datetime),
+        bar_after: after.map(|v| v.datetime),
+        prev_close,
+        open,
+        gap,
+        gap_ratio,
+        volume_before,
+        volume_after,
+        flags,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::{check_rolls, RollCheckConfig, RollEvent, RollFlag};
+    use crate::qh::klineitem::fixture::dt;
+    use crate::qh::klineitem::{fixture, KLineItem};
+
+    fn bar(minute: i64, open: i64, close: i64, volume: i64) -> KLineItem {
+        KLineItem {
+            close: Decimal::from(close),
+            volume,
+            ..fixture::bar("agL9", minute, open)
+        }
+    }
+
+    #[test]
+    fn test_check_rolls() {
+        let bars = vec![
+            bar(1, 100, 100, 10),
+            bar(2, 100, 101, 10),
+            bar(3, 101, 100, 10),
+            bar(4, 110, 111, 50),
+            bar(5, 111, 112, 50),
+            bar(5, 111, 112, 50),
+            bar(6, 112, 112, 50),
+        ];
+        let rolls = vec![
+            RollEvent {
+                datetime: dt(4),
+                from:     "ag2406".to_string(),
+                to:       "ag2408".to_string(),
+            },
+            RollEvent {
+                datetime: dt(10),
+                from:     "ag2408".to_string(),
+                to:       "ag2410".to_string(),
+            },
+        ];
+        let config = RollCheckConfig::default().with_window(3);
+        let report = check_rolls("agL9", &bars, &rolls, &config);
+        println!("{}", serde_yaml::to_string(&report).unwrap());
+        assert!(!report.is_ok());
+        assert_eq!(report.duplicates, vec![dt(5)]);
+
+        let check = &report.rolls[0];
+        assert_eq!(check.bar_before, Some(dt(3)));
+        assert_eq!(check.gap, Some(Decimal::from(10)));
+        assert_eq!(check.gap_ratio, Some(Decimal::new(1, 1)));
+        assert_eq!(check.flags, vec![RollFlag::PriceGap, RollFlag::VolumeJump]);
+
+        assert_eq!(report.rolls[1].flags, vec![RollFlag::MissingBars]);
+    }
+}
@@ -0,0 +1,368 @@
+//! 两个来源的K线逐字段比较, 如自己合成的K线和数据商的K线, 用于日常的数据质量检查
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+
+use chrono::{Duration, DurationRound, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::qh::klineitem::KLineItem;
+
+/// 数值比较的容差, 差值不超过abs或不超过rel*max(|left|, |right|)时视为相同
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Tolerance {
+    pub abs: Decimal,
+    pub rel: Decimal,
+}
+
+impl Tolerance {
+    pub fn abs(abs: Decimal) -> Tolerance {
+        Tolerance {
+            abs,
+            rel: Decimal::ZERO,
+        }
+    }
+
+    pub fn rel(rel: Decimal) -> Tolerance {
+        Tolerance {
+            abs: Decimal::ZERO,
+            rel,
+        }
+    }
+
+    pub fn is_match(&self, left: Decimal, right: Decimal) -> bool {
+        let diff = (left - right).abs();
+        diff <= self.abs || diff <= self.rel * left.abs().max(right.abs())
+    }
+}
+
+/// 两个来源的K线时间对齐方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeAlign {
+    /// 时间完全相同
+    #[default]
+    Exact,
+    /// 都截断到分钟, 忽略秒和毫秒
+    Minute,
+    /// right的时间加上偏移后再比较, 如一边用K线开始时间, 一边用结束时间
+    Shift(Duration),
+}
+
+impl TimeAlign {
+    fn align(&self, datetime: &NaiveDateTime, is_right: bool) -> NaiveDateTime {
+        match self {
+            TimeAlign::Exact => *datetime,
+            TimeAlign::Minute => datetime
+                .duration_trunc(Duration::try_minutes(1).unwrap())
+                .unwrap_or(*datetime),
+            TimeAlign::Shift(offset) if is_right => *datetime + *offset,
+            TimeAlign::Shift(_) => *datetime,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BarField {
+    Open,
+    High,
+    Low,
+    Close,
+    Volume,
+    OpenOi,
+    CloseOi,
+}
+
+impl BarField {
+    const PRICES: [BarField; 4] = [
+        BarField::Open,
+        BarField::High,
+        BarField::Low,
+        BarField::Close,
+    ];
+    const AMOUNTS: [BarField; 3] = [BarField::Volume, BarField::OpenOi, BarField::CloseOi];
+
+    fn value(&self, bar: &KLineItem) -> Decimal {
+        match self {
+            BarField::Open => bar.open,
+            BarField::High => bar.high,
+            BarField::Low => bar.low,
+            BarField::Close => bar.close,
+            BarField::Volume => Decimal::from(bar.volume),
+            BarField::OpenOi => Decimal::from(bar.open_oi),
+            BarField::CloseOi => Decimal::from(bar.close_oi),
+        }
+    }
+}
+
+/// price: 开高低收的容差
+/// amount: 成交量和持仓量的容差
+/// compare_oi: 是否比较持仓量, 有的数据源没有持仓量
+#[derive(Debug, Clone)]
+pub struct CompareConfig {
+    price:      Tolerance,
+    amount:     Tolerance,
+    align:      TimeAlign,
+    compare_oi: bool,
+}
+
+impl Default for CompareConfig {
+    fn default() -> Self {
+        CompareConfig {
+            price:      Tolerance::default(),
+            amount:     Tolerance::default(),
+            align:      TimeAlign::default(),
+            compare_oi: true,
+        }
+    }
+}
+
+impl CompareConfig {
+    pub fn with_price_tolerance(self, price: Tolerance) -> Self {
+        CompareConfig { price, ..self }
+    }
+
+    pub fn with_amount_tolerance(self, amount: Tolerance) -> Self {
+        CompareConfig { amount, ..self }
+    }
+
+    pub fn with_align(self, align: TimeAlign) -> Self {
+        CompareConfig { align, ..self }
+    }
+
+    pub fn with_compare_oi(self, compare_oi: bool) -> Self {
+        CompareConfig { compare_oi, ..self }
+    }
+
+    fn fields(&self) -> impl Iterator<Item = (BarField, &Tolerance)> {
+        let amounts = if self.compare_oi {
+            &BarField::AMOUNTS[..]
+        } else {
+            &BarField::AMOUNTS[..1]
+        };
+        BarField::PRICES
+            .iter()
+            .map(|v| (*v, &self.price))
+            .chain(amounts.iter().map(|v| (*v, &self.amount)))
+    }
+}
+
+/// 单个字段的差异, datetime为对齐后的时间, diff = right - left
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldDiff {
+    pub datetime: NaiveDateTime,
+    pub field:    BarField,
+    pub left:     Decimal,
+    pub right:    Decimal,
+    pub diff:     Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompareReport {
+    pub code:             String,
+    pub period:           i32,
+    pub left_bars:        usize,
+    pub right_bars:       usize,
+    /// 两边都有且所有字段都在容差内的数量
+    pub matched:          usize,
+    /// 只在right中有的K线时间
+    pub missing_left:     Vec<NaiveDateTime>,
+    /// 只在left中有的K线时间
+    pub missing_right:    Vec<NaiveDateTime>,
+    /// 对齐后时间重复的K线, 比较时使用第一根
+    pub left_duplicates:  Vec<NaiveDateTime>,
+    pub right_duplicates: Vec<NaiveDateTime>,
+    pub diffs:            Vec<FieldDiff>,
+}
+
+impl CompareReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing_left.is_empty()
+            && self.missing_right.is_empty()
+            && self.left_duplicates.is_empty()
+            && self.right_duplicates.is_empty()
+            && self.diffs.is_empty()
+    }
+}
+
+/// 按对齐后的时间建索引, 只保留code, period和[sdatetime, edatetime]内的K线
+fn index_bars<'a>(
+    bars: &'a [KLineItem],
+    code: &str,
+    period: i32,
+    range: (&NaiveDateTime, &NaiveDateTime),
+    align: &TimeAlign,
+    is_right: bool,
+) -> (BTreeMap<NaiveDateTime, &'a KLineItem>, Vec<NaiveDateTime>) {
+    let mut index = BTreeMap::new();
+    let mut duplicates = Vec::new();
+    for bar in bars {
+        if bar.code != code || bar.period != period {
+            continue;
+        }
+        let datetime = align.align(&bar.datetime, is_right);
+        if datetime < *range.0 || datetime > *range.1 {
+            continue;
+        }
+        match index.entry(datetime) {
+            Entry::Occupied(_) => duplicates.push(datetime),
+            Entry::Vacant(v) => {
+                v.insert(bar);
+            },
+        }
+    }
+    (index, duplicates)
+}
+
+/// 比较同一合约, 同一周期, 时间范围[sdatetime, edatetime]内的两组K线,
+/// 时间范围按对齐后的时间判断
+pub fn compare_bars(
+    code: &str,
+    period: i32,
+    sdatetime: &NaiveDateTime,
+    edatetime: &NaiveDateTime,
+    left: &[KLineItem],
+    right: &[KLineItem],
+    config: &CompareConfig,
+) -> CompareReport {
+    let range = (sdatetime, edatetime);
+    let (left, left_duplicates) = index_bars(left, code, period, range, &config.align, false);
+    let (right, right_duplicates) = index_bars(right, code, period, range, &config.align, true);
+
+    let missing_left = right
+        .keys()
+        .filter(|v| !left.contains_key(v))
+        .copied()
+        .collect();
+    let mut missing_right = Vec::new();
+    let mut matched = 0;
+    let mut diffs = Vec::new();
+    for (datetime, lbar) in &left {
+        let Some(rbar) = right.get(datetime) else {
+            missing_right.push(*datetime);
+            continue;
+        };
+        let count = diffs.len();
+        for (field, tolerance) in config.fields() {
+            let (lvalue, rvalue) = (field.value(lbar), field.value(rbar));
+            if !tolerance.is_match(lvalue, rvalue) {
+                diffs.push(FieldDiff {
+                    datetime: *datetime,
+                    field,
+                    left: lvalue,
+                    right: rvalue,
+                    diff: rvalue - lvalue,
+                });
+            }
+        }
+        if diffs.len() == count {
+            matched += 1;
+        }
+    }
+
+    CompareReport {
+        code: code.to_owned(),
+        period,
+        left_bars: left.len(),
+        right_bars: right.len(),
+        matched,
+        missing_left,
+        missing_right,
+        left_duplicates,
+        right_duplicates,
+        diffs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDateTime};
+    use rust_decimal::Decimal;
+
+    use super::{compare_bars, BarField, CompareConfig, TimeAlign, Tolerance};
+    use crate::qh::klineitem::{fixture, KLineItem};
+
+    fn dt(minute: i64, second: i64) -> NaiveDateTime {
+        fixture::dt(minute) + Duration::try_seconds(second).unwrap()
+    }
+
+    /// 只比较收盘价和成交量, 开高低为0
+    fn bar(minute: i64, second: i64, close: Decimal, volume: i64) -> KLineItem {
+        KLineItem {
+            datetime: dt(minute, second),
+            close,
+            volume,
+            ..fixture::bar("ag2408", minute, 0)
+        }
+    }
+
+    #[test]
+    fn test_tolerance() {
+        let tolerance = Tolerance::abs(Decimal::new(1, 2));
+        assert!(tolerance.is_match(Decimal::new(1000, 2), Decimal::new(1001, 2)));
+        assert!(!tolerance.is_match(Decimal::new(1000, 2), Decimal::new(1002, 2)));
+        let tolerance = Tolerance::rel(Decimal::new(1, 3));
+        assert!(tolerance.is_match(Decimal::from(1000), Decimal::from(1001)));
+        assert!(!tolerance.is_match(Decimal::from(100), Decimal::from(101)));
+    }
+
+    #[test]
+    fn test_compare_bars() {
+        let left = vec![
+            bar(1, 0, Decimal::new(10000, 2), 10),
+            bar(2, 0, Decimal::new(10100, 2), 10),
+            bar(3, 0, Decimal::new(10200, 2), 10),
+            bar(4, 0, Decimal::new(10300, 2), 10),
+        ];
+        // 数据商的K线带秒, 缺少第4根, 多了第5根
+        let right = vec![
+            bar(1, 59, Decimal::new(10001, 2), 10),
+            bar(2, 59, Decimal::new(10100, 2), 12),
+            bar(3, 59, Decimal::new(10250, 2), 10),
+            bar(3, 59, Decimal::new(10250, 2), 10),
+            bar(5, 59, Decimal::new(10400, 2), 10),
+        ];
+        let (s, e) = (dt(0, 0), dt(10, 0));
+
+        let report = compare_bars(
+            "ag2408",
+            1,
+            &s,
+            &e,
+            &left,
+            &right,
+            &CompareConfig::default(),
+        );
+        assert_eq!(report.matched, 0);
+        assert_eq!(report.missing_left.len(), 4);
+        assert_eq!(report.missing_right.len(), 4);
+
+        let config = CompareConfig::default()
+            .with_align(TimeAlign::Minute)
+            .with_price_tolerance(Tolerance::abs(Decimal::new(1, 2)))
+            .with_amount_tolerance(Tolerance::abs(Decimal::ONE));
+        let report = compare_bars("ag2408", 1, &s, &e, &left, &right, &config);
+        println!("{}", serde_yaml::to_string(&report).unwrap());
+        assert!(!report.is_ok());
+        assert_eq!(report.left_bars, 4);
+        assert_eq!(report.right_bars, 4);
+        assert_eq!(report.matched, 1);
+        assert_eq!(report.missing_left, vec![dt(5, 0)]);
+        assert_eq!(report.missing_right, vec![dt(4, 0)]);
+        assert_eq!(report.right_duplicates, vec![dt(3, 0)]);
+        assert_eq!(report.diffs.len(), 2);
+        assert_eq!(report.diffs[0].field, BarField::Volume);
+        assert_eq!(report.diffs[0].diff, Decimal::from(2));
+        assert_eq!(report.diffs[1].datetime, dt(3, 0));
+        assert_eq!(report.diffs[1].field, BarField::Close);
+        assert_eq!(report.diffs[1].diff, Decimal::new(50, 2));
+
+        // right用K线结束时间
+        let right = vec![bar(2, 0, Decimal::new(10000, 2), 10)];
+        let config = CompareConfig::default()
+            .with_align(TimeAlign::Shift(Duration::try_minutes(-1).unwrap()));
+        let report = compare_bars("ag2408", 1, &s, &dt(1, 0), &left, &right, &config);
+        assert!(report.is_ok());
+        assert_eq!(report.matched, 1);
+    }
+}
@@ -0,0 +1,790 @@
+//! K线的write-behind写入
+//!
+//! 写入时先更新Redis中的最新K线, 再放入有界队列, 后台按批次REPLACE到MySQL.
+//! 队列满时写入等待(背压), 关闭时写完队列中剩余的数据.
+//! 按表分批写入, 一张表重试后仍失败时逐条写入, 只保留写入失败的K线, 下一次和新的K线一起写入;
+//! 失败`max_failures`次后丢弃. 保留的K线占用队列长度, 关闭后可以用`take_retained`取回.
+//! 开启`hq`时可以用`WriteBehindConfig::with_daily_stats`把写入成功的K线累计到每日统计.
+#[cfg(feature = "hq")]
+use std::collections::BTreeSet;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[cfg(feature = "hq")]
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use log::error;
+use redis::aio::ConnectionLike;
+use redis::RedisError;
+use rust_decimal::Decimal;
+use sqlx::MySqlPool;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::sync::{Notify, Semaphore, TryAcquireError};
+use tokio::task::JoinHandle;
+
+use super::klineitem::{KLineItem, KLineItemUtil};
+use super::latency::{record_bar_persisted, LatencyStage, StageTimer};
+#[cfg(feature = "hq")]
+use super::trading_day::TradingDayUtil;
+#[cfg(feature = "hq")]
+use crate::hq::daily_stats::{DailyStatsCollector, DailyStatsUtil};
+use crate::mysqlx::batch_exec::{BatchExec, SqlEntity};
+use crate::timer::Backoff;
+
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, thiserror::Error)]
+pub enum WriteBehindError {
+    #[error("{0}")]
+    Redis(#[from] RedisError),
+
+    #[error("write-behind queue closed")]
+    Closed,
+
+    #[error("write-behind queue full")]
+    Full,
+
+    #[error("latest bar cache err: {0}")]
+    Cache(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct WriteBehindConfig {
+    capacity:       usize,
+    batch_size:     usize,
+    flush_interval: Duration,
+    retry:          Backoff,
+    max_failures:   usize,
+    cache_prefix:   String,
+    #[cfg(feature = "hq")]
+    daily_stats:    Option<(Arc<DailyStatsUtil>, Arc<TradingDayUtil>)>,
+}
+
+impl Default for WriteBehindConfig {
+    fn default() -> Self {
+        WriteBehindConfig {
+            capacity:       10000,
+            batch_size:     500,
+            flush_interval: Duration::from_secs(1),
+            retry:          Backoff::constant(Duration::from_secs(1)).with_max_attempts(3),
+            max_failures:   5,
+            cache_prefix:   "qh:bar:latest".to_owned(),
+            #[cfg(feature = "hq")]
+            daily_stats:    None,
+        }
+    }
+}
+
+impl WriteBehindConfig {
+    /// 队列长度, 包括等待重试的K线, 队列满时write等待
+    pub fn with_capacity(self, capacity: usize) -> Self {
+        WriteBehindConfig {
+            capacity: capacity.max(1),
+            ..self
+        }
+    }
+
+    /// 达到batch_size时立即写入数据库
+    pub fn with_batch_size(self, batch_size: usize) -> Self {
+        WriteBehindConfig {
+            batch_size: batch_size.max(1),
+            ..self
+        }
+    }
+
+    /// 不足batch_size时, 每隔flush_interval写入一次
+    pub fn with_flush_interval(self, flush_interval: Duration) -> Self {
+        WriteBehindConfig {
+            flush_interval,
+            ..self
+        }
+    }
+
+    /// 写入数据库失败时的重试, 超过次数后逐条写入, 保留写入失败的K线, 和下一批一起写入
+    pub fn with_retry(self, max_retries: usize, retry_delay: Duration) -> Self {
+        self.with_backoff(Backoff::constant(retry_delay).with_max_attempts(max_retries))
+    }
+
+    /// 写入数据库失败时按backoff等待后重试, 次数用完后逐条写入, 保留写入失败的K线, 和下一批一起写入
+    pub fn with_backoff(self, retry: Backoff) -> Self {
+        WriteBehindConfig { retry, ..self }
+    }
+
+    /// 一根K线写入失败max_failures次后丢弃, 记录到`WriteBehindStats::dropped`
+    pub fn with_max_failures(self, max_failures: usize) -> Self {
+        WriteBehindConfig {
+            max_failures: max_failures.max(1),
+            ..self
+        }
+    }
+
+    /// 最新K线的key前缀, key为`{prefix}:{code}:{period}`
+    pub fn with_cache_prefix(self, cache_prefix: &str) -> Self {
+        WriteBehindConfig {
+            cache_prefix: cache_prefix.to_owned(),
+            ..self
+        }
+    }
+
+    /// 写入数据库成功的K线累计到每日统计并保存, tdu用于计算K线所属的交易日
+    #[cfg(feature = "hq")]
+    pub fn with_daily_stats(self, util: Arc<DailyStatsUtil>, tdu: Arc<TradingDayUtil>) -> Self {
+        WriteBehindConfig {
+            daily_stats: Some((util, tdu)),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteBehindStats {
+    /// 写入数据库的K线数
+    pub written: usize,
+    pub batches:  usize,
+    /// 重试后仍写入失败, 等待下一次写入的K线数
+    pub retained: usize,
+    /// 失败次数达到max_failures或表名不合法, 丢弃的K线数
+    pub dropped:  usize,
+}
+
+impl std::fmt::Display for WriteBehindStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "written:{} batches:{} retained:{} dropped:{}",
+            self.written, self.batches, self.retained, self.dropped
+        )
+    }
+}
+
+/// (表名后缀, K线)
+type QueueItem = (String, KLineItem);
+
+/// 写入失败保留的K线
+struct RetainedItem {
+    item:     QueueItem,
+    failures: usize,
+}
+
+pub struct BarWriteBehind {
+    config:   WriteBehindConfig,
+    tx:       Sender<QueueItem>,
+    /// 队列和保留的K线共用的名额, 写入成功或丢弃后归还
+    slots:    Arc<Semaphore>,
+    close:    Arc<Notify>,
+    stats:    Arc<Mutex<WriteBehindStats>>,
+    retained: Arc<Mutex<Vec<RetainedItem>>>,
+    handle:   Mutex<Option<JoinHandle<()>>>,
+}
+
+impl BarWriteBehind {
+    /// 启动后台写入任务, 需要在tokio运行时中调用
+    pub fn start(
+        pool: Arc<MySqlPool>,
+        util: Arc<KLineItemUtil>,
+        config: WriteBehindConfig,
+    ) -> BarWriteBehind {
+        let (tx, rx) = mpsc::channel(config.capacity);
+        let slots = Arc::new(Semaphore::new(config.capacity));
+        let close = Arc::new(Notify::new());
+        let stats = Arc::new(Mutex::new(WriteBehindStats::default()));
+        let retained = Arc::new(Mutex::new(Vec::new()));
+        let flusher = Flusher {
+            pool,
+            util,
+            config: config.clone(),
+            slots: slots.clone(),
+            stats: stats.clone(),
+            retained: retained.clone(),
+            #[cfg(feature = "hq")]
+            daily_stats: config.daily_stats.clone().map(|(util, tdu)| DailyStatsSink {
+                util,
+                tdu,
+                collector: DailyStatsCollector::new(),
+                seeded: BTreeSet::new(),
+            }),
+        };
+        let handle = tokio::spawn(flush_loop(flusher, rx, close.clone()));
+        BarWriteBehind {
+            config,
+            tx,
+            slots,
+            close,
+            stats,
+            retained,
+            handle: Mutex::new(Some(handle)),
+        }
+    }
+
+    pub fn cache_key(&self, code: &str, period: i32) -> String {
+        format!("{}:{}:{}", self.config.cache_prefix, code, period)
+    }
+
+    async fn update_cache<C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        item: &KLineItem,
+    ) -> Result<(), WriteBehindError> {
+        let mut cmd = redis::cmd("HSET");
+        cmd.arg(self.cache_key(&item.code, item.period));
+        for (field, value) in to_fields(item) {
+            cmd.arg(field).arg(value);
+        }
+        cmd.query_async::<_, ()>(con).await?;
+        Ok(())
+    }
+
+    /// 更新最新K线后放入队列, 队列满时等待
+    pub async fn write<C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        tbl_suffix: &str,
+        item: KLineItem,
+    ) -> Result<(), WriteBehindError> {
+        self.update_cache(con, &item).await?;
+        self.slots
+            .acquire()
+            .await
+            .map_err(|_| WriteBehindError::Closed)?
+            .forget();
+        self.tx
+            .send((tbl_suffix.to_owned(), item))
+            .await
+            .map_err(|_| WriteBehindError::Closed)
+    }
+
+    /// 同write, 队列满时不等待, 返回Full, 最新K线已更新
+    pub async fn try_write<C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        tbl_suffix: &str,
+        item: KLineItem,
+    ) -> Result<(), WriteBehindError> {
+        self.update_cache(con, &item).await?;
+        self.slots
+            .try_acquire()
+            .map_err(|e| match e {
+                TryAcquireError::NoPermits => WriteBehindError::Full,
+                TryAcquireError::Closed => WriteBehindError::Closed,
+            })?
+            .forget();
+        self.tx
+            .try_send((tbl_suffix.to_owned(), item))
+            .map_err(|e| match e {
+                TrySendError::Full(_) => WriteBehindError::Full,
+                TrySendError::Closed(_) => WriteBehindError::Closed,
+            })
+    }
+
+    /// 缓存中的最新K线
+    pub async fn latest<C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        code: &str,
+        period: i32,
+    ) -> Result<Option<KLineItem>, WriteBehindError> {
+        let fields: HashMap<String, String> = redis::cmd("HGETALL")
+            .arg(self.cache_key(code, period))
+            .query_async(con)
+            .await?;
+        if fields.is_empty() {
+            return Ok(None);
+        }
+        from_fields(code, period, &fields).map(Some)
+    }
+
+    /// 队列中等待写入和保留等待重试的数量
+    pub fn pending(&self) -> usize {
+        self.config.capacity - self.slots.available_permits()
+    }
+
+    pub fn stats(&self) -> WriteBehindStats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// 取回重试后仍写入失败的K线(表名后缀, K线), 取回后不再写入, 一般在shutdown之后调用
+    pub fn take_retained(&self) -> Vec<(String, KLineItem)> {
+        let items = std::mem::take(&mut *self.retained.lock().unwrap());
+        self.stats.lock().unwrap().retained = 0;
+        self.slots.add_permits(items.len());
+        items.into_iter().map(|v| v.item).collect()
+    }
+
+    /// 停止接收新的K线, 写完队列中的数据后返回, 多次调用时只有第一次等待
+    pub async fn shutdown(&self) -> WriteBehindStats {
+        self.slots.close();
+        self.close.notify_one();
+        let handle = self.handle.lock().unwrap().take();
+        if let Some(handle) = handle {
+            if let Err(e) = handle.await {
+                error!("[BarWriteBehind] flush task err: {}", e);
+            }
+        }
+        self.stats()
+    }
+
+    /// 注册到退出钩子, `run_shutdown_hooks`时写完队列中的数据
+    #[cfg(feature = "running")]
+    pub fn register_shutdown(self: &Arc<Self>, name: &str) {
+        let write_behind = self.clone();
+        crate::running::shutdown::on_shutdown(name, move || async move {
+            let stats = write_behind.shutdown().await;
+            log::info!("[BarWriteBehind] shutdown {}", stats);
+        });
+    }
+}
+
+struct Flusher {
+    pool:        Arc<MySqlPool>,
+    util:        Arc<KLineItemUtil>,
+    config:      WriteBehindConfig,
+    slots:       Arc<Semaphore>,
+    stats:       Arc<Mutex<WriteBehindStats>>,
+    retained:    Arc<Mutex<Vec<RetainedItem>>>,
+    #[cfg(feature = "hq")]
+    daily_stats: Option<DailyStatsSink>,
+}
+
+async fn flush_loop(mut flusher: Flusher, mut rx: Receiver<QueueItem>, close: Arc<Notify>) {
+    let config = flusher.config.clone();
+    let mut interval = tokio::time::interval(config.flush_interval);
+    let mut batch = Vec::with_capacity(config.batch_size);
+    let mut closed = false;
+    loop {
+        tokio::select! {
+            item = rx.recv() => match item {
+                Some(item) => {
+                    batch.push(item);
+                    if batch.len() >= config.batch_size {
+                        flusher.flush(&mut batch).await;
+                    }
+                },
+                None => break,
+            },
+            _ = interval.tick() => {
+                flusher.flush(&mut batch).await;
+            },
+            _ = close.notified(), if !closed => {
+                // 不再接收新的数据, 已在队列中的继续recv
+                rx.close();
+                closed = true;
+            },
+        }
+    }
+    flusher.flush(&mut batch).await;
+}
+
+impl Flusher {
+    /// 上次保留的K线和batch一起按表写入, 一张表失败不影响其他表
+    async fn flush(&mut self, batch: &mut Vec<QueueItem>) {
+        let retained = std::mem::take(&mut *self.retained.lock().unwrap());
+        if retained.is_empty() && batch.is_empty() {
+            return;
+        }
+        let mut tables = BTreeMap::<String, Vec<RetainedItem>>::new();
+        for v in retained
+            .into_iter()
+            .chain(batch.drain(..).map(|item| RetainedItem { item, failures: 0 }))
+        {
+            tables.entry(v.item.0.clone()).or_default().push(v);
+        }
+        let mut written = Vec::new();
+        let mut retained = Vec::new();
+        let mut dropped = 0;
+        for (tbl_suffix, items) in tables {
+            let mut entities = Vec::with_capacity(items.len());
+            for v in items.iter() {
+                let item = &v.item.1;
+                let key = format!(
+                    "{}:{}:{}:{}",
+                    tbl_suffix, item.code, item.period, item.datetime
+                );
+                match self.util.try_sql_entity_replace(&tbl_suffix, &key, item) {
+                    Ok(entity) => entities.push(entity),
+                    Err(e) => {
+                        // 表名不合法, 重试也不会成功
+                        error!("[BarWriteBehind] drop {} bars {}: {}", items.len(), tbl_suffix, e);
+                        break;
+                    },
+                }
+            }
+            if entities.len() < items.len() {
+                dropped += items.len();
+                continue;
+            }
+            if self.execute_table(&entities).await {
+                written.extend(items.into_iter().map(|v| v.item));
+                continue;
+            }
+            // 逐条写入, 只保留写入失败的K线
+            for (mut v, entity) in items.into_iter().zip(entities) {
+                match BatchExec::execute_single(&self.pool, entity).await {
+                    Ok(()) => written.push(v.item),
+                    Err(e) => {
+                        v.failures += 1;
+                        if v.failures >= self.config.max_failures {
+                            error!(
+                                "[BarWriteBehind] drop {} {} {} after {} failures: {}",
+                                tbl_suffix, v.item.1.code, v.item.1.datetime, v.failures, e
+                            );
+                            dropped += 1;
+                        } else {
+                            retained.push(v);
+                        }
+                    },
+                }
+            }
+        }
+        for (_, item) in written.iter() {
+            record_bar_persisted(item);
+        }
+        self.slots.add_permits(written.len() + dropped);
+        {
+            let mut stats = self.stats.lock().unwrap();
+            stats.written += written.len();
+            stats.dropped += dropped;
+            stats.retained = retained.len();
+        }
+        if !retained.is_empty() {
+            error!("[BarWriteBehind] retain {} bars", retained.len());
+        }
+        *self.retained.lock().unwrap() = retained;
+        #[cfg(feature = "hq")]
+        if !written.is_empty() {
+            if let Some(daily_stats) = self.daily_stats.as_mut() {
+                daily_stats.record(&self.pool, &written).await;
+            }
+        }
+    }
+
+    /// 一张表的K线在一个事务中写入, 失败时按retry重试, 重试后仍失败返回false
+    async fn execute_table(&mut self, entities: &[SqlEntity]) -> bool {
+        let mut retry = self.config.retry.clone();
+        loop {
+            let mut batch_exec = BatchExec::new(self.pool.clone(), 0);
+            for entity in entities {
+                batch_exec.add(entity.clone());
+            }
+            let timer = StageTimer::start(LatencyStage::Persist);
+            match batch_exec.execute_all().await {
+                Ok(_) => {
+                    timer.finish();
+                    self.stats.lock().unwrap().batches += 1;
+                    return true;
+                },
+                Err(e) => match retry.next() {
+                    Some(delay) => {
+                        error!("[BarWriteBehind] retry {}: {}", retry.attempt(), e);
+                        tokio::time::sleep(delay).await;
+                    },
+                    None => {
+                        error!("[BarWriteBehind] batch of {} failed: {}", entities.len(), e);
+                        return false;
+                    },
+                },
+            }
+        }
+    }
+}
+
+/// 写入成功的K线累计到每日统计
+#[cfg(feature = "hq")]
+struct DailyStatsSink {
+    util:      Arc<DailyStatsUtil>,
+    tdu:       Arc<TradingDayUtil>,
+    collector: DailyStatsCollector,
+    /// 已读入保存的统计的交易日, 只保留最近两个
+    seeded:    BTreeSet<NaiveDate>,
+}
+
+#[cfg(feature = "hq")]
+impl DailyStatsSink {
+    /// 累计后保存, 保存失败的增量留在collector中, 下次一起保存
+    async fn record(&mut self, pool: &MySqlPool, items: &[QueueItem]) {
+        for (_, item) in items {
+            let trade_day = match self.tdu.trading_day_from_datetime(&item.datetime) {
+                Ok(v) => NaiveDate::from(v),
+                Err(e) => {
+                    error!("[BarWriteBehind] daily stats {} {}: {}", item.code, item.datetime, e);
+                    continue;
+                },
+            };
+            if !self.seeded.contains(&trade_day) {
+                // 读入失败时同一增量在数据库中也不会重复累加, 下次再读入
+                match self.util.seed(pool, &mut self.collector, trade_day).await {
+                    Ok(()) => {
+                        self.seeded.insert(trade_day);
+                        while self.seeded.len() > 2 {
+                            self.seeded.pop_first();
+                        }
+                        if let Some(first) = self.seeded.first() {
+                            self.collector.prune(*first);
+                        }
+                    },
+                    Err(e) => error!("[BarWriteBehind] daily stats seed {}: {}", trade_day, e),
+                }
+            }
+            self.collector.record(
+                &item.breed(),
+                trade_day,
+                item.period,
+                item.datetime,
+                item.volume,
+            );
+        }
+        if let Err(e) = self.util.flush(pool, &mut self.collector).await {
+            error!("[BarWriteBehind] daily stats flush: {}", e);
+        }
+    }
+}
+
+fn to_fields(item: &KLineItem) -> [(&'static str, String); 10] {
+    [
+        (
+            "datetime",
+            item.datetime.format(DATETIME_FORMAT).to_string(),
+        ),
+        ("open", item.open.to_string()),
+        ("high", item.high.to_string()),
+        ("low", item.low.to_string()),
+        ("close", item.close.to_string()),
+        ("volume", item.volume.to_string()),
+        ("total_volume", item.total_volume.to_string()),
+        ("open_oi", item.open_oi.to_string()),
+        ("close_oi", item.close_oi.to_string()),
+        (
+            "last_item_time",
+            item.last_item_time.format(DATETIME_FORMAT).to_string(),
+        ),
+    ]
+}
+
+fn from_fields(
+    code: &str,
+    period: i32,
+    fields: &HashMap<String, String>,
+) -> Result<KLineItem, WriteBehindError> {
+    let get = |field: &str| {
+        fields
+            .get(field)
+            .ok_or_else(|| WriteBehindError::Cache(format!("{} missing", field)))
+    };
+    let err = |field: &str, e: &dyn std::fmt::Display| {
+        WriteBehindError::Cache(format!("{}: {}", field, e))
+    };
+    let datetime = |field: &str| {
+        NaiveDateTime::parse_from_str(get(field)?, DATETIME_FORMAT).map_err(|e| err(field, &e))
+    };
+    let decimal = |field: &str| get(field)?.parse::<Decimal>().map_err(|e| err(field, &e));
+    let int = |field: &str| get(field)?.parse::<i64>().map_err(|e| err(field, &e));
+    let mut item = KLineItem::new(code, &datetime("datetime")?, period);
+    item.open = decimal("open")?;
+    item.high = decimal("high")?;
+    item.low = decimal("low")?;
+    item.close = decimal("close")?;
+    item.volume = int("volume")?;
+    item.total_volume = int("total_volume")?;
+    item.open_oi = int("open_oi")?;
+    item.close_oi = int("close_oi")?;
+    item.last_item_time = datetime("last_item_time")?;
+    Ok(item)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    use super::{from_fields, to_fields, BarWriteBehind, WriteBehindConfig, WriteBehindError};
+    use crate::mysqlx_test_pool::test_db_guard;
+    use crate::qh::klineitem::{fixture, KLineItem, KLineItemUtil};
+    use crate::redis::RedisClients;
+
+    fn item(minute: i64) -> KLineItem {
+        KLineItem {
+            open: Decimal::new(78005, 1),
+            high: Decimal::from(7810),
+            low: Decimal::from(7799),
+            volume: 12,
+            total_volume: 120,
+            ..fixture::bar("ag2408", minute, 7805)
+        }
+    }
+
+    #[test]
+    fn test_cache_fields() {
+        let item = item(1);
+        let fields = to_fields(&item)
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), v))
+            .collect::<HashMap<_, _>>();
+        let cached = from_fields("ag2408", 1, &fields).unwrap();
+        assert_eq!(cached.to_string(), item.to_string());
+        let mut fields = fields;
+        fields.remove("close");
+        assert!(from_fields("ag2408", 1, &fields).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_behind() {
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        RedisClients::init_clients("./_cfg/c-redis-rs.yaml").unwrap();
+        let mut con = RedisClients::client()
+            .get_multiplexed_tokio_connection()
+            .await
+            .unwrap();
+        let util = Arc::new(KLineItemUtil::try_new(guard.db()).unwrap());
+        util.create_table(&pool, "agL9").await.unwrap();
+        let write_behind = BarWriteBehind::start(
+            pool.clone(),
+            util.clone(),
+            WriteBehindConfig::default()
+                .with_batch_size(2)
+                .with_flush_interval(Duration::from_millis(100)),
+        );
+        for minute in 1..=3 {
+            write_behind
+                .write(&mut con, "agL9", item(minute))
+                .await
+                .unwrap();
+        }
+        let latest = write_behind
+            .latest(&mut con, "ag2408", 1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest.datetime, item(3).datetime);
+        let stats = write_behind.shutdown().await;
+        println!("{}", stats);
+        assert_eq!((stats.written, stats.retained), (3, 0));
+        assert!(write_behind.write(&mut con, "agL9", item(4)).await.is_err());
+
+        // 表不存在时写入失败, 保留在retained中, 之后其他表的K线照常写入
+        let write_behind = BarWriteBehind::start(
+            pool.clone(),
+            util.clone(),
+            WriteBehindConfig::default()
+                .with_retry(1, Duration::from_millis(10))
+                .with_flush_interval(Duration::from_millis(50)),
+        );
+        write_behind
+            .write(&mut con, "missing", item(5))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        write_behind
+            .write(&mut con, "agL9", item(6))
+            .await
+            .unwrap();
+        let stats = write_behind.shutdown().await;
+        assert_eq!((stats.written, stats.retained), (1, 1));
+        let written = util
+            .item_vec_latest(&pool, "agL9", 1, 10)
+            .await
+            .unwrap();
+        assert!(written.iter().any(|v| v.datetime == item(6).datetime));
+        assert_eq!(write_behind.pending(), 1);
+        let retained = write_behind.take_retained();
+        assert_eq!(retained.len(), 1);
+        assert_eq!(retained[0].1.datetime, item(5).datetime);
+        assert_eq!((write_behind.stats().retained, write_behind.pending()), (0, 0));
+
+        // 保留的K线占用队列长度
+        let write_behind = BarWriteBehind::start(
+            pool.clone(),
+            util.clone(),
+            WriteBehindConfig::default()
+                .with_capacity(1)
+                .with_retry(0, Duration::from_millis(10))
+                .with_max_failures(100)
+                .with_flush_interval(Duration::from_millis(50)),
+        );
+        write_behind
+            .try_write(&mut con, "missing", item(7))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(write_behind.stats().retained, 1);
+        assert!(matches!(
+            write_behind.try_write(&mut con, "agL9", item(8)).await,
+            Err(WriteBehindError::Full)
+        ));
+        write_behind.shutdown().await;
+        assert_eq!(write_behind.take_retained().len(), 1);
+
+        // 失败max_failures次后丢弃, 归还队列长度
+        let write_behind = BarWriteBehind::start(
+            pool,
+            util,
+            WriteBehindConfig::default()
+                .with_retry(0, Duration::from_millis(10))
+                .with_max_failures(1),
+        );
+        write_behind
+            .write(&mut con, "missing", item(9))
+            .await
+            .unwrap();
+        let stats = write_behind.shutdown().await;
+        assert_eq!((stats.retained, stats.dropped), (0, 1));
+        assert_eq!(write_behind.pending(), 0);
+        guard.cleanup().await;
+    }
+
+    #[cfg(feature = "hq")]
+    #[tokio::test]
+    async fn test_write_behind_daily_stats() {
+        use crate::hq::daily_stats::DailyStatsUtil;
+        use crate::qh::trading_day::TradingDayUtil;
+
+        let guard = test_db_guard().await;
+        let pool = guard.pool();
+        RedisClients::init_clients("./_cfg/c-redis-rs.yaml").unwrap();
+        let mut con = RedisClients::client()
+            .get_multiplexed_tokio_connection()
+            .await
+            .unwrap();
+        let util = Arc::new(KLineItemUtil::try_new(guard.db()).unwrap());
+        util.create_table(&pool, "agL9").await.unwrap();
+        let stats_util = Arc::new(DailyStatsUtil::new(guard.db()).unwrap());
+        stats_util.create_table(&pool).await.unwrap();
+        let tdu = TradingDayUtil::from_trading_days(&[20240531, 20240603, 20240604]).unwrap();
+        let config = WriteBehindConfig::default()
+            .with_flush_interval(Duration::from_millis(50))
+            .with_daily_stats(stats_util.clone(), Arc::new(tdu));
+        let write_behind = BarWriteBehind::start(pool.clone(), util.clone(), config.clone());
+        for minute in 1..=3 {
+            write_behind
+                .write(&mut con, "agL9", item(minute))
+                .await
+                .unwrap();
+        }
+        write_behind.shutdown().await;
+
+        // 重启后重新写入已统计的K线不重复累计
+        let write_behind = BarWriteBehind::start(pool.clone(), util, config);
+        for minute in 2..=4 {
+            write_behind
+                .write(&mut con, "agL9", item(minute))
+                .await
+                .unwrap();
+        }
+        write_behind.shutdown().await;
+
+        let day = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let stats = stats_util
+            .stats_by_day(&pool, day, Some("ag"))
+            .await
+            .unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].bar_count, stats[0].volume), (4, 48));
+        assert_eq!(stats[0].last_time, item(4).datetime);
+        guard.cleanup().await;
+    }
+}
@@ -2,14 +2,26 @@ use std::collections::HashMap;
 use std::path::Path;
 use std::sync::{Arc, OnceLock, PoisonError, RwLockReadGuard, RwLockWriteGuard};
 
+use log::warn;
+use redis::aio::MultiplexedConnection;
 use redis::{
     Client, ConnectionAddr, ConnectionInfo, IntoConnectionInfo, RedisConnectionInfo, RedisError,
     RedisResult,
 };
 use serde::Deserialize;
 
+pub use self::batch::{batch_get, BatchGet, BatchGetResult, BatchPipeline};
+pub use self::keyspace::{Keyspace, KeyspaceError};
+pub use self::leaderboard::{Leaderboard, LeaderboardError, RankOrder};
+pub use self::tiered::{Consistency, TieredCache};
+use crate::timer::Backoff;
 use crate::yaml::{parse_from_file, YamlError};
 
+pub mod batch;
+pub mod keyspace;
+pub mod leaderboard;
+pub mod tiered;
+
 #[derive(Debug, Deserialize, Clone)]
 struct RedisConnInfo {
     #[serde(rename = "default")]
@@ -105,13 +117,49 @@ impl RedisClients {
     }
 }
 
+/// 建立异步连接, 失败时按backoff等待后重连, 次数用完时返回最后一次的错误
+pub async fn connect_with_backoff(
+    client: &Client,
+    mut backoff: Backoff,
+) -> RedisResult<MultiplexedConnection> {
+    loop {
+        match client.get_multiplexed_tokio_connection().await {
+            Ok(con) => return Ok(con),
+            Err(e) => match backoff.next() {
+                Some(delay) => {
+                    warn!(
+                        "redis connect err: {}, retry {} after {:?}",
+                        e,
+                        backoff.attempt(),
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                },
+                None => return Err(e),
+            },
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
+    use std::time::{Duration, Instant};
+
     use redis::Commands;
 
-    use super::conn_config_from_file;
+    use super::{conn_config_from_file, connect_with_backoff};
     use crate::redis::RedisClients;
+    use crate::timer::Backoff;
+
+    #[tokio::test]
+    async fn test_connect_with_backoff() {
+        let client = redis::Client::open("redis://127.0.0.1:1/").unwrap();
+        let backoff = Backoff::constant(Duration::from_millis(20)).with_max_attempts(2);
+        let start = Instant::now();
+        assert!(connect_with_backoff(&client, backoff).await.is_err());
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
 
     #[test]
     fn test_read_conn_config() {
@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+
+use redis::{ConnectionLike, FromRedisValue, Pipeline, RedisError, RedisResult, Value};
+
+/// 每次MGET或每个pipeline的key数量
+pub const DEFAULT_CHUNK_SIZE: usize = 500;
+
+/// 批量获取的结果
+/// misses: 不存在的key(值为nil或空的集合), errors: 值转换失败的key
+#[derive(Debug)]
+pub struct BatchGetResult<T> {
+    pub values: HashMap<String, T>,
+    pub misses: Vec<String>,
+    pub errors: Vec<(String, RedisError)>,
+}
+
+impl<T> Default for BatchGetResult<T> {
+    fn default() -> Self {
+        BatchGetResult {
+            values: HashMap::new(),
+            misses: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl<T> BatchGetResult<T> {
+    pub fn get(&self, key: &str) -> Option<&T> {
+        self.values.get(key)
+    }
+
+    /// 所有key都取到了值
+    pub fn is_complete(&self) -> bool {
+        self.misses.is_empty() && self.errors.is_empty()
+    }
+}
+
+/// 批量获取, key较多时按chunk_size分成多次MGET, 一次MGET只有一次往返
+#[derive(Debug, Clone)]
+pub struct BatchGet {
+    keys:       Vec<String>,
+    chunk_size: usize,
+}
+
+impl Default for BatchGet {
+    fn default() -> Self {
+        BatchGet {
+            keys:       Vec::new(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+impl BatchGet {
+    pub fn new() -> BatchGet {
+        BatchGet::default()
+    }
+
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    pub fn key(mut self, key: impl AsRef<str>) -> Self {
+        self.keys.push(key.as_ref().to_owned());
+        self
+    }
+
+    pub fn keys<I, K>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<str>,
+    {
+        self.keys
+            .extend(keys.into_iter().map(|v| v.as_ref().to_owned()));
+        self
+    }
+
+    pub fn query<T, C>(&self, con: &mut C) -> RedisResult<BatchGetResult<T>>
+    where
+        T: FromRedisValue,
+        C: ConnectionLike,
+    {
+        let mut result = BatchGetResult::default();
+        for keys in self.keys.chunks(self.chunk_size) {
+            let values: Vec<Value> = redis::cmd("MGET").arg(keys).query(con)?;
+            decode_values(keys, values, &mut result);
+        }
+        Ok(result)
+    }
+
+    pub async fn query_async<T, C>(&self, con: &mut C) -> RedisResult<BatchGetResult<T>>
+    where
+        T: FromRedisValue,
+        C: redis::aio::ConnectionLike,
+    {
+        let mut result = BatchGetResult::default();
+        for keys in self.keys.chunks(self.chunk_size) {
+            let values: Vec<Value> = redis::cmd("MGET").arg(keys).query_async(con).await?;
+            decode_values(keys, values, &mut result);
+        }
+        Ok(result)
+    }
+}
+
+/// 对每个key执行同一个命令, 如`HGETALL key`, `HGET key field`,
+/// key较多时按chunk_size分成多个pipeline, 一个pipeline只有一次往返
+#[derive(Debug, Clone)]
+pub struct BatchPipeline {
+    cmd:        String,
+    args:       Vec<String>,
+    keys:       Vec<String>,
+    chunk_size: usize,
+}
+
+impl BatchPipeline {
+    pub fn new(cmd: &str) -> BatchPipeline {
+        BatchPipeline {
+            cmd:        cmd.to_owned(),
+            args:       Vec::new(),
+            keys:       Vec::new(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// key后面的参数, 每个key相同, 如HGET的field
+    pub fn arg(mut self, arg: impl AsRef<str>) -> Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    pub fn key(mut self, key: impl AsRef<str>) -> Self {
+        self.keys.push(key.as_ref().to_owned());
+        self
+    }
+
+    pub fn keys<I, K>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<str>,
+    {
+        self.keys
+            .extend(keys.into_iter().map(|v| v.as_ref().to_owned()));
+        self
+    }
+
+    fn pipeline(&self, keys: &[String]) -> Pipeline {
+        let mut pipe = redis::pipe();
+        for key in keys {
+            pipe.cmd(&self.cmd).arg(key).arg(&self.args);
+        }
+        pipe
+    }
+
+    pub fn query<T, C>(&self, con: &mut C) -> RedisResult<BatchGetResult<T>>
+    where
+        T: FromRedisValue,
+        C: ConnectionLike,
+    {
+        let mut result = BatchGetResult::default();
+        for keys in self.keys.chunks(self.chunk_size) {
+            let values: Vec<Value> = self.pipeline(keys).query(con)?;
+            decode_values(keys, values, &mut result);
+        }
+        Ok(result)
+    }
+
+    pub async fn query_async<T, C>(&self, con: &mut C) -> RedisResult<BatchGetResult<T>>
+    where
+        T: FromRedisValue,
+        C: redis::aio::ConnectionLike,
+    {
+        let mut result = BatchGetResult::default();
+        for keys in self.keys.chunks(self.chunk_size) {
+            let values: Vec<Value> = self.pipeline(keys).query_async(con).await?;
+            decode_values(keys, values, &mut result);
+        }
+        Ok(result)
+    }
+}
+
+fn decode_values<T>(keys: &[String], values: Vec<Value>, result: &mut BatchGetResult<T>)
+where
+    T: FromRedisValue,
+{
+    for (key, value) in keys.iter().zip(values) {
+        match value {
+            // 空的集合在redis中就是不存在
+            Value::Nil => result.misses.push(key.clone()),
+            Value::Bulk(v) if v.is_empty() => result.misses.push(key.clone()),
+            value => match T::from_redis_value(&value) {
+                Ok(v) => {
+                    result.values.insert(key.clone(), v);
+                },
+                Err(e) => result.errors.push((key.clone(), e)),
+            },
+        }
+    }
+}
+
+/// 批量获取key的值, 见`BatchGet`
+pub fn batch_get<T, C, K>(con: &mut C, keys: &[K]) -> RedisResult<BatchGetResult<T>>
+where
+    T: FromRedisValue,
+    C: ConnectionLike,
+    K: AsRef<str>,
+{
+    BatchGet::new().keys(keys).query(con)
+}
+
+#[cfg(test)]
+mod tests {
+    use redis::{Commands, Value};
+
+    use std::collections::HashMap;
+
+    use super::{batch_get, decode_values, BatchGet, BatchGetResult, BatchPipeline};
+    use crate::redis::RedisClients;
+
+    #[test]
+    fn test_decode_values() {
+        let keys = ["a", "b", "c"].map(|v| v.to_string());
+        let values = vec![
+            Value::Data(b"1".to_vec()),
+            Value::Nil,
+            Value::Data(b"x".to_vec()),
+        ];
+        let mut result = BatchGetResult::<i64>::default();
+        decode_values(&keys, values, &mut result);
+        println!("{:?}", result);
+        assert_eq!(result.get("a"), Some(&1));
+        assert_eq!(result.misses, vec!["b".to_string()]);
+        assert_eq!(result.errors.len(), 1);
+        assert!(!result.is_complete());
+
+        let values = vec![
+            Value::Bulk(vec![Value::Data(b"f".to_vec()), Value::Data(b"1".to_vec())]),
+            Value::Bulk(vec![]),
+        ];
+        let mut result = BatchGetResult::<HashMap<String, i64>>::default();
+        decode_values(&keys[..2], values, &mut result);
+        assert_eq!(result.get("a").unwrap()["f"], 1);
+        assert_eq!(result.misses, vec!["b".to_string()]);
+    }
+
+    #[test]
+    fn test_batch_pipeline() {
+        RedisClients::init_clients("./_cfg/c-redis-rs.yaml").unwrap();
+        let client = RedisClients::client();
+        let mut con = client.get_connection().unwrap();
+        let keys = (0..5)
+            .map(|i| format!("Tmp:batch:hash:{}", i))
+            .collect::<Vec<_>>();
+        let _: () = con.del(&keys).unwrap();
+        for key in keys.iter().take(3) {
+            let _: () = con.hset(key, "f", key).unwrap();
+        }
+        let result = BatchPipeline::new("HGETALL")
+            .chunk_size(2)
+            .keys(&keys)
+            .query::<HashMap<String, String>, _>(&mut con)
+            .unwrap();
+        assert_eq!(result.values.len(), 3);
+        assert_eq!(result.misses, keys[3..].to_vec());
+        let result = BatchPipeline::new("HGET")
+            .arg("f")
+            .keys(&keys)
+            .query::<String, _>(&mut con)
+            .unwrap();
+        assert_eq!(result.get(&keys[0]), Some(&keys[0]));
+        assert_eq!(result.misses.len(), 2);
+        let _: () = con.del(&keys).unwrap();
+    }
+
+    #[test]
+    fn test_batch_get() {
+        RedisClients::init_clients("./_cfg/c-redis-rs.yaml").unwrap();
+        let client = RedisClients::client();
+        let mut con = client.get_connection().unwrap();
+        let keys = (0..5)
+            .map(|i| format!("Tmp:batch:{}", i))
+            .collect::<Vec<_>>();
+        for key in keys.iter().take(3) {
+            let _: () = con.set(key, key).unwrap();
+        }
+        let result = batch_get::<String, _, _>(&mut con, &keys).unwrap();
+        println!("{:?}", result);
+        let result = BatchGet::new()
+            .chunk_size(2)
+            .keys(&keys)
+            .query::<String, _>(&mut con)
+            .unwrap();
+        println!("{:?}", result);
+    }
+}
@@ -0,0 +1,269 @@
+//! key命名空间及保存数据的格式版本
+//!
+//! key格式: `app:env:domain:part1:part2...`, 如`hq:prod:kline:ag2408:5`
+//! 值格式: `KS` + 版本号(u16 LE) + bincode数据, 没有`KS`头的旧数据版本为0
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use redis::{ConnectionLike, RedisError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+const MAGIC: &[u8; 2] = b"KS";
+const HEADER_LEN: usize = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeyspaceError {
+    #[error("{0}")]
+    Redis(#[from] RedisError),
+
+    #[error("{0}")]
+    Bincode(#[from] bincode::Error),
+
+    #[error("migrate from version {version} err: {msg}")]
+    Migration { version: u16, msg: String },
+}
+
+/// 把from版本的数据转换为from+1版本的数据
+type MigrateFn = dyn Fn(&[u8]) -> Result<Vec<u8>, String> + Send + Sync;
+
+/// 解码结果
+#[derive(Debug, PartialEq)]
+pub enum Decoded<T> {
+    /// 当前版本的数据
+    Current(T),
+    /// 经过迁移的旧版本数据, 需要写回
+    Migrated(T),
+    /// 没有对应迁移方法的旧版本数据或更新版本的数据, 按不存在处理
+    Stale(u16),
+}
+
+#[derive(Clone)]
+pub struct Keyspace {
+    app:        String,
+    env:        String,
+    version:    u16,
+    migrations: HashMap<u16, Arc<MigrateFn>>,
+}
+
+impl std::fmt::Debug for Keyspace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut migrations = self.migrations.keys().collect::<Vec<_>>();
+        migrations.sort();
+        f.debug_struct("Keyspace")
+            .field("app", &self.app)
+            .field("env", &self.env)
+            .field("version", &self.version)
+            .field("migrations", &migrations)
+            .finish()
+    }
+}
+
+impl Keyspace {
+    pub fn new(app: &str, env: &str, version: u16) -> Keyspace {
+        Keyspace {
+            app: app.to_owned(),
+            env: env.to_owned(),
+            version,
+            migrations: HashMap::new(),
+        }
+    }
+
+    /// 添加from版本到from+1版本的迁移
+    pub fn with_migration<F>(mut self, from: u16, f: F) -> Self
+    where
+        F: Fn(&[u8]) -> Result<Vec<u8>, String> + Send + Sync + 'static,
+    {
+        self.migrations.insert(from, Arc::new(f));
+        self
+    }
+
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// `app:env:domain:parts...`
+    pub fn key(&self, domain: &str, parts: &[&str]) -> String {
+        let mut key = format!("{}:{}:{}", self.app, self.env, domain);
+        for part in parts {
+            key.push(':');
+            key.push_str(part);
+        }
+        key
+    }
+
+    /// 匹配domain下所有key的pattern, 用于SCAN
+    pub fn pattern(&self, domain: &str) -> String {
+        format!("{}:{}:{}:*", self.app, self.env, domain)
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, KeyspaceError> {
+        let mut bytes = Vec::with_capacity(64);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bincode::serialize_into(&mut bytes, value)?;
+        Ok(bytes)
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<Decoded<T>, KeyspaceError> {
+        let (mut version, payload) = split_version(bytes);
+        if version == self.version {
+            return Ok(Decoded::Current(bincode::deserialize(payload)?));
+        }
+        if version > self.version {
+            return Ok(Decoded::Stale(version));
+        }
+        let mut payload = payload.to_vec();
+        while version < self.version {
+            let Some(migrate) = self.migrations.get(&version) else {
+                return Ok(Decoded::Stale(version));
+            };
+            payload = migrate(&payload).map_err(|msg| KeyspaceError::Migration { version, msg })?;
+            version += 1;
+        }
+        Ok(Decoded::Migrated(bincode::deserialize(&payload)?))
+    }
+
+    /// 读取数据, 旧版本的数据迁移后写回, 无法迁移的旧数据删除
+    pub fn get<T, C>(&self, con: &mut C, key: &str) -> Result<Option<T>, KeyspaceError>
+    where
+        T: Serialize + DeserializeOwned,
+        C: ConnectionLike,
+    {
+        let bytes: Option<Vec<u8>> = redis::cmd("GET").arg(key).query(con)?;
+        let Some(bytes) = bytes else {
+            return Ok(None);
+        };
+        match self.decode(&bytes)? {
+            Decoded::Current(value) => Ok(Some(value)),
+            Decoded::Migrated(value) => {
+                redis::cmd("SET")
+                    .arg(key)
+                    .arg(self.encode(&value)?)
+                    .arg("KEEPTTL")
+                    .query::<()>(con)?;
+                Ok(Some(value))
+            },
+            Decoded::Stale(version) => {
+                if version < self.version {
+                    redis::cmd("DEL").arg(key).query::<()>(con)?;
+                }
+                Ok(None)
+            },
+        }
+    }
+
+    /// 保存数据, ttl为None时不过期
+    pub fn set<T, C>(
+        &self,
+        con: &mut C,
+        key: &str,
+        value: &T,
+        ttl: Option<Duration>,
+    ) -> Result<(), KeyspaceError>
+    where
+        T: Serialize,
+        C: ConnectionLike,
+    {
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(key).arg(self.encode(value)?);
+        if let Some(ttl) = ttl {
+            cmd.arg("PX").arg(ttl.as_millis() as u64);
+        }
+        cmd.query::<()>(con)?;
+        Ok(())
+    }
+}
+
+fn split_version(bytes: &[u8]) -> (u16, &[u8]) {
+    if bytes.len() >= HEADER_LEN && bytes.starts_with(MAGIC) {
+        let version = u16::from_le_bytes([bytes[2], bytes[3]]);
+        (version, &bytes[HEADER_LEN..])
+    } else {
+        (0, bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Decoded, Keyspace};
+    use crate::redis::RedisClients;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct QuoteV1 {
+        price: i64,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct QuoteV2 {
+        price:  i64,
+        volume: i64,
+    }
+
+    fn keyspace_v2() -> Keyspace {
+        Keyspace::new("hq", "test", 2).with_migration(1, |bytes| {
+            let v1: QuoteV1 = bincode::deserialize(bytes).map_err(|e| e.to_string())?;
+            let v2 = QuoteV2 {
+                price:  v1.price,
+                volume: 0,
+            };
+            bincode::serialize(&v2).map_err(|e| e.to_string())
+        })
+    }
+
+    #[test]
+    fn test_key() {
+        let ks = Keyspace::new("hq", "prod", 1);
+        assert_eq!(ks.key("kline", &["ag2408", "5"]), "hq:prod:kline:ag2408:5");
+        assert_eq!(ks.pattern("kline"), "hq:prod:kline:*");
+    }
+
+    #[test]
+    fn test_decode_migrate() {
+        let v1 = Keyspace::new("hq", "test", 1);
+        let bytes = v1.encode(&QuoteV1 { price: 100 }).unwrap();
+
+        let v2 = keyspace_v2();
+        let decoded = v2.decode::<QuoteV2>(&bytes).unwrap();
+        assert_eq!(
+            decoded,
+            Decoded::Migrated(QuoteV2 {
+                price:  100,
+                volume: 0,
+            })
+        );
+
+        let bytes = v2
+            .encode(&QuoteV2 {
+                price:  1,
+                volume: 2,
+            })
+            .unwrap();
+        assert!(matches!(
+            v2.decode::<QuoteV2>(&bytes).unwrap(),
+            Decoded::Current(_)
+        ));
+        // 旧版本读取新版本的数据
+        assert_eq!(v1.decode::<QuoteV1>(&bytes).unwrap(), Decoded::Stale(2));
+        // 没有迁移方法
+        let legacy = bincode::serialize(&QuoteV1 { price: 1 }).unwrap();
+        assert_eq!(v2.decode::<QuoteV2>(&legacy).unwrap(), Decoded::Stale(0));
+    }
+
+    #[test]
+    fn test_get_set() {
+        RedisClients::init_clients("./_cfg/c-redis-rs.yaml").unwrap();
+        let client = RedisClients::client();
+        let mut con = client.get_connection().unwrap();
+
+        let key = Keyspace::new("hq", "test", 1).key("quote", &["ag2408"]);
+        Keyspace::new("hq", "test", 1)
+            .set(&mut con, &key, &QuoteV1 { price: 100 }, None)
+            .unwrap();
+        let value = keyspace_v2().get::<QuoteV2, _>(&mut con, &key).unwrap();
+        println!("{:?}", value);
+    }
+}
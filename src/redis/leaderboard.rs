@@ -0,0 +1,262 @@
+//! 基于有序集合(ZSET)的排行榜, 如按成交量, 流动性给合约排名
+//!
+//! 成员用bincode编码后保存, 相同的成员编码后相同, 同一成员只有一个分数
+use std::marker::PhantomData;
+
+use redis::{ConnectionLike, RedisError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LeaderboardError {
+    #[error("{0}")]
+    Redis(#[from] RedisError),
+
+    #[error("{0}")]
+    Bincode(#[from] bincode::Error),
+}
+
+/// 排名的顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RankOrder {
+    /// 分数高的在前
+    #[default]
+    Desc,
+    /// 分数低的在前
+    Asc,
+}
+
+#[derive(Debug, Clone)]
+pub struct Leaderboard<T> {
+    key:     String,
+    order:   RankOrder,
+    max_len: Option<usize>,
+    _member: PhantomData<fn() -> T>,
+}
+
+impl<T> Leaderboard<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    pub fn new(key: &str) -> Leaderboard<T> {
+        Leaderboard {
+            key:     key.to_owned(),
+            order:   RankOrder::default(),
+            max_len: None,
+            _member: PhantomData,
+        }
+    }
+
+    pub fn with_order(self, order: RankOrder) -> Self {
+        Leaderboard { order, ..self }
+    }
+
+    /// 只保留排名前max_len的成员, 每次更新后删除多出的成员
+    pub fn with_max_len(self, max_len: usize) -> Self {
+        Leaderboard {
+            max_len: Some(max_len.max(1)),
+            ..self
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn trim_cmd(&self) -> Option<redis::Cmd> {
+        let max_len = self.max_len? as isize;
+        let mut cmd = redis::cmd("ZREMRANGEBYRANK");
+        // ZREMRANGEBYRANK按分数从低到高的排名删除
+        match self.order {
+            RankOrder::Desc => cmd.arg(&self.key).arg(0).arg(-max_len - 1),
+            RankOrder::Asc => cmd.arg(&self.key).arg(max_len).arg(-1),
+        };
+        Some(cmd)
+    }
+
+    /// 设置成员的分数, 不存在时添加
+    pub fn update<C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        score: f64,
+        member: &T,
+    ) -> Result<(), LeaderboardError> {
+        self.update_many(con, &[(score, member)])
+    }
+
+    /// 一次往返设置多个成员的分数
+    pub fn update_many<C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        members: &[(f64, &T)],
+    ) -> Result<(), LeaderboardError> {
+        if members.is_empty() {
+            return Ok(());
+        }
+        let mut zadd = redis::cmd("ZADD");
+        zadd.arg(&self.key);
+        for (score, member) in members {
+            zadd.arg(*score).arg(bincode::serialize(member)?);
+        }
+        let mut pipe = redis::pipe();
+        pipe.add_command(zadd).ignore();
+        if let Some(trim) = self.trim_cmd() {
+            pipe.add_command(trim).ignore();
+        }
+        pipe.query::<()>(con)?;
+        Ok(())
+    }
+
+    /// 累加成员的分数, 不存在时从0开始, 返回累加后的分数
+    pub fn incr<C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        delta: f64,
+        member: &T,
+    ) -> Result<f64, LeaderboardError> {
+        let mut pipe = redis::pipe();
+        pipe.cmd("ZINCRBY")
+            .arg(&self.key)
+            .arg(delta)
+            .arg(bincode::serialize(member)?);
+        if let Some(trim) = self.trim_cmd() {
+            pipe.add_command(trim).ignore();
+        }
+        let (score,): (f64,) = pipe.query(con)?;
+        Ok(score)
+    }
+
+    /// 排名前n的成员及分数
+    pub fn top_n<C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        n: usize,
+    ) -> Result<Vec<(T, f64)>, LeaderboardError> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let cmd = match self.order {
+            RankOrder::Desc => "ZREVRANGE",
+            RankOrder::Asc => "ZRANGE",
+        };
+        let values: Vec<(Vec<u8>, f64)> = redis::cmd(cmd)
+            .arg(&self.key)
+            .arg(0)
+            .arg(n as isize - 1)
+            .arg("WITHSCORES")
+            .query(con)?;
+        values
+            .into_iter()
+            .map(|(member, score)| Ok((bincode::deserialize(&member)?, score)))
+            .collect()
+    }
+
+    /// 成员的排名, 从0开始, 不存在时为None
+    pub fn rank<C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        member: &T,
+    ) -> Result<Option<u64>, LeaderboardError> {
+        let cmd = match self.order {
+            RankOrder::Desc => "ZREVRANK",
+            RankOrder::Asc => "ZRANK",
+        };
+        let rank = redis::cmd(cmd)
+            .arg(&self.key)
+            .arg(bincode::serialize(member)?)
+            .query(con)?;
+        Ok(rank)
+    }
+
+    pub fn score<C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        member: &T,
+    ) -> Result<Option<f64>, LeaderboardError> {
+        let score = redis::cmd("ZSCORE")
+            .arg(&self.key)
+            .arg(bincode::serialize(member)?)
+            .query(con)?;
+        Ok(score)
+    }
+
+    pub fn remove<C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        member: &T,
+    ) -> Result<bool, LeaderboardError> {
+        let n: usize = redis::cmd("ZREM")
+            .arg(&self.key)
+            .arg(bincode::serialize(member)?)
+            .query(con)?;
+        Ok(n > 0)
+    }
+
+    pub fn len<C: ConnectionLike>(&self, con: &mut C) -> Result<usize, LeaderboardError> {
+        Ok(redis::cmd("ZCARD").arg(&self.key).query(con)?)
+    }
+
+    /// 删除整个排行榜, 如每个交易日开始时
+    pub fn clear<C: ConnectionLike>(&self, con: &mut C) -> Result<(), LeaderboardError> {
+        redis::cmd("DEL").arg(&self.key).query::<()>(con)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Leaderboard, RankOrder};
+    use crate::redis::{Keyspace, RedisClients};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Contract {
+        exchange: String,
+        code:     String,
+    }
+
+    fn contract(code: &str) -> Contract {
+        Contract {
+            exchange: "SHFE".to_string(),
+            code:     code.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_leaderboard() {
+        RedisClients::init_clients("./_cfg/c-redis-rs.yaml").unwrap();
+        let mut con = RedisClients::client().get_connection().unwrap();
+        let key = Keyspace::new("hq", "test", 1).key("rank", &["volume"]);
+        let board = Leaderboard::<Contract>::new(&key).with_max_len(2);
+        board.clear(&mut con).unwrap();
+
+        board.update(&mut con, 100.0, &contract("ag2408")).unwrap();
+        board
+            .update_many(
+                &mut con,
+                &[(300.0, &contract("cu2407")), (50.0, &contract("zn2407"))],
+            )
+            .unwrap();
+        assert_eq!(board.len(&mut con).unwrap(), 2);
+        assert_eq!(
+            board.top_n(&mut con, 10).unwrap(),
+            [(contract("cu2407"), 300.0), (contract("ag2408"), 100.0)]
+        );
+        assert_eq!(board.rank(&mut con, &contract("ag2408")).unwrap(), Some(1));
+        assert_eq!(board.rank(&mut con, &contract("zn2407")).unwrap(), None);
+
+        assert_eq!(
+            board.incr(&mut con, 250.0, &contract("ag2408")).unwrap(),
+            350.0
+        );
+        let board = board.with_order(RankOrder::Asc);
+        assert_eq!(board.rank(&mut con, &contract("ag2408")).unwrap(), Some(1));
+        assert!(board.remove(&mut con, &contract("ag2408")).unwrap());
+        assert_eq!(
+            board.score(&mut con, &contract("cu2407")).unwrap(),
+            Some(300.0)
+        );
+        board.clear(&mut con).unwrap();
+    }
+}
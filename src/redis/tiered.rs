@@ -0,0 +1,312 @@
+//! 两级缓存: L1为进程内的SizeHashMap, L2为redis
+//!
+//! redis不可用时自动降级为只用L1, 按backoff的间隔尝试恢复,
+//! 降级期间的写入记录为脏数据, 恢复后写回redis. 脏数据单独保存, 从L1移除后不会丢失.
+//! L1和redis连接使用不同的锁, 读L1时不用等待redis的IO
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use redis::{Client, Connection, RedisError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::keyspace::{Keyspace, KeyspaceError};
+use crate::sizehmap::SizeHashMap;
+use crate::timer::Backoff;
+
+/// 写入的一致性
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Consistency {
+    /// 同时写入L1和redis
+    #[default]
+    WriteThrough,
+    /// 只写入L1, 调用`flush`时写入redis
+    WriteBack,
+}
+
+struct LocalState<V> {
+    l1:    SizeHashMap<String, V>,
+    /// 还没有写入redis的值, 带写入的序号, 写回后值没有再修改时才移除
+    dirty: HashMap<String, (u64, V)>,
+}
+
+struct RedisState {
+    con:      Option<Connection>,
+    backoff:  Backoff,
+    retry_at: Option<Instant>,
+}
+
+pub struct TieredCache<V> {
+    client:          Arc<Client>,
+    keyspace:        Keyspace,
+    consistency:     Consistency,
+    ttl:             Option<Duration>,
+    connect_timeout: Duration,
+    healthy:         AtomicBool,
+    seq:             AtomicU64,
+    local:           Mutex<LocalState<V>>,
+    redis:           Mutex<RedisState>,
+}
+
+impl<V> Debug for TieredCache<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TieredCache")
+            .field("keyspace", &self.keyspace)
+            .field("consistency", &self.consistency)
+            .field("ttl", &self.ttl)
+            .field("healthy", &self.healthy.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl<V> TieredCache<V>
+where
+    V: Clone + Debug + Serialize + DeserializeOwned,
+{
+    /// l1_capacity: L1最多保存的数量, 超出时移除最早添加的
+    pub fn new(client: Arc<Client>, keyspace: Keyspace, l1_capacity: usize) -> TieredCache<V> {
+        TieredCache {
+            client,
+            keyspace,
+            consistency: Consistency::default(),
+            ttl: None,
+            connect_timeout: Duration::from_secs(1),
+            healthy: AtomicBool::new(true),
+            seq: AtomicU64::new(0),
+            local: Mutex::new(LocalState {
+                l1:    SizeHashMap::with_capacity(l1_capacity.max(1)),
+                dirty: HashMap::new(),
+            }),
+            redis: Mutex::new(RedisState {
+                con:      None,
+                backoff:  Backoff::exponential(Duration::from_millis(500))
+                    .with_max(Duration::from_secs(30)),
+                retry_at: None,
+            }),
+        }
+    }
+
+    pub fn with_consistency(self, consistency: Consistency) -> Self {
+        TieredCache {
+            consistency,
+            ..self
+        }
+    }
+
+    /// redis中数据的过期时间
+    pub fn with_ttl(self, ttl: Duration) -> Self {
+        TieredCache {
+            ttl: Some(ttl),
+            ..self
+        }
+    }
+
+    pub fn with_connect_timeout(self, connect_timeout: Duration) -> Self {
+        TieredCache {
+            connect_timeout,
+            ..self
+        }
+    }
+
+    /// 降级后尝试恢复的间隔, 默认从500ms开始指数增长, 最长30s; 次数用完后不再尝试
+    pub fn with_backoff(self, backoff: Backoff) -> Self {
+        self.redis.lock().unwrap().backoff = backoff;
+        self
+    }
+
+    /// redis是否可用, 为false时只使用L1
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    /// 还没有写入redis的数量
+    pub fn dirty_len(&self) -> usize {
+        self.local.lock().unwrap().dirty.len()
+    }
+
+    /// 先查L1和脏数据, 没有时查redis并放入L1, redis不可用时只查本地
+    pub fn get(&self, key: &str) -> Option<V> {
+        {
+            let local = self.local.lock().unwrap();
+            if let Some(value) = local.l1.get(key) {
+                return Some(value.clone());
+            }
+            if let Some((_, value)) = local.dirty.get(key) {
+                return Some(value.clone());
+            }
+        }
+        let keyspace = &self.keyspace;
+        let value = self
+            .with_redis(|con| keyspace.get::<V, _>(con, key))
+            .flatten()?;
+        self.local
+            .lock()
+            .unwrap()
+            .l1
+            .insert(key.to_owned(), value.clone());
+        Some(value)
+    }
+
+    /// 写入L1, WriteThrough时同时写入redis, 写入失败时记为脏数据
+    pub fn set(&self, key: &str, value: V) {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        {
+            let mut local = self.local.lock().unwrap();
+            local.l1.insert(key.to_owned(), value.clone());
+            local.dirty.insert(key.to_owned(), (seq, value.clone()));
+        }
+        if self.consistency == Consistency::WriteThrough {
+            let (keyspace, ttl) = (&self.keyspace, self.ttl);
+            if self
+                .with_redis(|con| keyspace.set(con, key, &value, ttl))
+                .is_some()
+            {
+                self.clean(key, seq);
+            }
+        }
+    }
+
+    /// 把脏数据写入redis, 返回写入的数量
+    pub fn flush(&self) -> usize {
+        let dirty = self
+            .local
+            .lock()
+            .unwrap()
+            .dirty
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<Vec<_>>();
+        let mut count = 0;
+        for (key, (seq, value)) in dirty {
+            let (keyspace, ttl) = (&self.keyspace, self.ttl);
+            if self
+                .with_redis(|con| keyspace.set(con, &key, &value, ttl))
+                .is_none()
+            {
+                break;
+            }
+            self.clean(&key, seq);
+            count += 1;
+        }
+        count
+    }
+
+    /// 写入redis后移除脏数据, 期间又修改过的保留
+    fn clean(&self, key: &str, seq: u64) {
+        let mut local = self.local.lock().unwrap();
+        if local.dirty.get(key).is_some_and(|(v, _)| *v == seq) {
+            local.dirty.remove(key);
+        }
+    }
+
+    /// 使用redis连接执行f, redis不可用或出错时返回None, 只持有redis连接的锁
+    fn with_redis<T, F>(&self, f: F) -> Option<T>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, KeyspaceError>,
+    {
+        let (result, recovered) = {
+            let mut redis = self.redis.lock().unwrap();
+            if !self.is_healthy() && redis.retry_at.is_none_or(|v| Instant::now() < v) {
+                return None;
+            }
+            if redis.con.is_none() {
+                match self
+                    .client
+                    .get_connection_with_timeout(self.connect_timeout)
+                {
+                    Ok(con) => redis.con = Some(con),
+                    Err(e) => {
+                        self.degrade(&mut redis, &e);
+                        return None;
+                    },
+                }
+            }
+            match f(redis.con.as_mut().unwrap()) {
+                Ok(v) => {
+                    let recovered = !self.healthy.swap(true, Ordering::Relaxed);
+                    if recovered {
+                        redis.backoff.reset();
+                        redis.retry_at = None;
+                    }
+                    (v, recovered)
+                },
+                Err(KeyspaceError::Redis(e)) => {
+                    self.degrade(&mut redis, &e);
+                    return None;
+                },
+                Err(e) => {
+                    warn!("tiered cache value err: {}", e);
+                    return None;
+                },
+            }
+        };
+        if recovered {
+            info!("redis recovered, dirty: {}", self.dirty_len());
+            if self.consistency == Consistency::WriteThrough {
+                self.flush();
+            }
+        }
+        Some(result)
+    }
+
+    fn degrade(&self, redis: &mut RedisState, err: &RedisError) {
+        if self.healthy.swap(false, Ordering::Relaxed) {
+            warn!("redis unavailable, use local cache only: {}", err);
+        }
+        redis.con = None;
+        // 次数用完后不再尝试恢复
+        redis.retry_at = redis.backoff.next().map(|v| Instant::now() + v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use redis::Client;
+
+    use super::{Consistency, TieredCache};
+    use crate::redis::{Keyspace, RedisClients};
+    use crate::timer::Backoff;
+
+    #[test]
+    fn test_degrade() {
+        // 没有服务的端口, 连接立即失败
+        let client = Arc::new(Client::open("redis://127.0.0.1:1/").unwrap());
+        let cache = TieredCache::<i64>::new(client, Keyspace::new("hq", "test", 1), 2)
+            .with_backoff(Backoff::constant(Duration::from_secs(60)));
+        assert!(cache.is_healthy());
+        cache.set("a", 1);
+        assert!(!cache.is_healthy());
+        cache.set("b", 2);
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("c"), None);
+        assert_eq!(cache.dirty_len(), 2);
+        // 超出L1容量, a从L1移除, 脏数据还在
+        cache.set("c", 3);
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.flush(), 0);
+        assert_eq!(cache.dirty_len(), 3);
+    }
+
+    #[test]
+    fn test_tiered() {
+        RedisClients::init_clients("./_cfg/c-redis-rs.yaml").unwrap();
+        let keyspace = Keyspace::new("hq", "test", 1);
+        let key = keyspace.key("tiered", &["ag2408"]);
+        let cache = TieredCache::<i64>::new(RedisClients::client(), keyspace.clone(), 10)
+            .with_consistency(Consistency::WriteBack);
+        cache.set(&key, 100);
+        assert_eq!(cache.dirty_len(), 1);
+        assert_eq!(cache.flush(), 1);
+        assert!(cache.is_healthy());
+
+        let other = TieredCache::<i64>::new(RedisClients::client(), keyspace, 10);
+        assert_eq!(other.get(&key), Some(100));
+    }
+}
@@ -4,6 +4,15 @@ use std::path::Path;
 
 use sysinfo::ProcessRefreshKind;
 
+pub mod crash;
+pub mod preflight;
+pub mod shutdown;
+pub mod supervisor;
+pub mod watchdog;
+
+pub use self::preflight::preflight;
+pub use self::supervisor::Supervisor;
+
 #[cfg(windows)]
 fn name_wrapper(name: &str) -> Cow<'_, str> {
     if name.ends_with(".exe") {
@@ -0,0 +1,276 @@
+//! 在任务边界捕获panic, 转为eyre错误, 并写入崩溃报告文件
+//!
+//! 报告包含panic信息, backtrace和日志文件的最后几行, 开启tracing-init时还包含内存中最近的日志,
+//! 写完后请求退出
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::{Read, Seek, SeekFrom};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Once, OnceLock};
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use eyre::eyre;
+
+use super::shutdown::request_shutdown;
+use crate::AResult;
+
+/// 默认附带的日志行数
+const DEFAULT_TAIL_LINES: usize = 50;
+
+/// 崩溃报告配置
+#[derive(Debug, Clone)]
+pub struct CrashReportConfig {
+    dir:        PathBuf,
+    log_file:   Option<PathBuf>,
+    tail_lines: usize,
+}
+
+impl CrashReportConfig {
+    pub fn new(dir: impl AsRef<Path>) -> CrashReportConfig {
+        CrashReportConfig {
+            dir:        dir.as_ref().to_path_buf(),
+            log_file:   None,
+            tail_lines: DEFAULT_TAIL_LINES,
+        }
+    }
+
+    /// 报告中附带这个日志文件的最后几行
+    pub fn with_log_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.log_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn with_tail_lines(mut self, lines: usize) -> Self {
+        self.tail_lines = lines;
+        self
+    }
+}
+
+static CONFIG: OnceLock<CrashReportConfig> = OnceLock::new();
+
+thread_local! {
+    /// panic hook中捕获的backtrace, 在catch_unwind之后取出
+    static LAST_BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// 设置报告的配置, 并安装记录backtrace的panic hook(保留原来的hook), 只有第一次有效
+pub fn init_crash_report(config: CrashReportConfig) {
+    let _ = CONFIG.set(config);
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let prev = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let backtrace = Backtrace::force_capture().to_string();
+            LAST_BACKTRACE.with(|v| *v.borrow_mut() = Some(backtrace));
+            prev(info);
+        }));
+    });
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "unknown panic"
+    }
+}
+
+/// 从文件末尾往前每次读取的字节数
+const TAIL_BLOCK_SIZE: u64 = 8192;
+
+/// 文件的最后lines行, 从文件末尾往前读, 不读入整个文件
+fn log_tail(path: &Path, lines: usize) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let mut pos = file.metadata().ok()?.len();
+    let mut buf = Vec::new();
+    while pos > 0 && buf.iter().filter(|v| **v == b'\n').count() <= lines {
+        let size = pos.min(TAIL_BLOCK_SIZE);
+        pos -= size;
+        let mut block = vec![0; size as usize];
+        file.seek(SeekFrom::Start(pos)).ok()?;
+        file.read_exact(&mut block).ok()?;
+        block.extend_from_slice(&buf);
+        buf = block;
+    }
+    let content = String::from_utf8_lossy(&buf);
+    let all = content.lines().collect::<Vec<_>>();
+    Some(all[all.len().saturating_sub(lines)..].join("\n"))
+}
+
+/// 文件名中只保留字母, 数字, -, _
+fn file_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn write_report(config: &CrashReportConfig, name: &str, content: &str) -> AResult<PathBuf> {
+    fs::create_dir_all(&config.dir)
+        .map_err(|e| eyre!("创建崩溃报告目录失败: {} {}", config.dir.display(), e))?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    let path = config.dir.join(format!(
+        "crash-{}-{}{:03}.log",
+        file_safe(name),
+        now.as_secs(),
+        now.subsec_millis()
+    ));
+    fs::write(&path, content).map_err(|e| eyre!("写入崩溃报告失败: {} {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// panic转为eyre错误, 写入报告并请求退出
+fn on_panic(name: &str, payload: Box<dyn Any + Send>) -> eyre::Report {
+    let msg = panic_message(payload.as_ref());
+    let backtrace = LAST_BACKTRACE
+        .with(|v| v.borrow_mut().take())
+        .unwrap_or_else(|| "backtrace not captured, call init_crash_report first".to_owned());
+    let mut content = format!(
+        "task: {}\npanic: {}\n\nbacktrace:\n{}\n",
+        name, msg, backtrace
+    );
+
+    let mut report_msg = format!("task {} panicked: {}", name, msg);
+    if let Some(config) = CONFIG.get() {
+        if let Some(tail) = config
+            .log_file
+            .as_deref()
+            .and_then(|v| log_tail(v, config.tail_lines))
+        {
+            content.push_str("\nlog tail:\n");
+            content.push_str(&tail);
+            content.push('\n');
+        }
+        #[cfg(feature = "tracing-init")]
+        {
+            let events = crate::tracing_init::ring_buffer_events();
+            if !events.is_empty() {
+                content.push_str("\nrecent events:\n");
+                content.push_str(&events.join("\n"));
+                content.push('\n');
+            }
+        }
+        match write_report(config, name, &content) {
+            Ok(path) => report_msg.push_str(&format!(", report: {}", path.display())),
+            Err(err) => report_msg.push_str(&format!(", write report err: {}", err)),
+        }
+    }
+    request_shutdown(&report_msg);
+    eyre!("{}\n{}", report_msg, backtrace)
+}
+
+/// 执行f, panic时转为错误
+pub fn catch_panic<R>(name: &str, f: impl FnOnce() -> AResult<R>) -> AResult<R> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| Err(on_panic(name, payload)))
+}
+
+/// 捕获future中panic的包装
+pub struct CatchPanic<F> {
+    name: String,
+    fut:  Pin<Box<F>>,
+}
+
+impl<F, R> Future for CatchPanic<F>
+where
+    F: Future<Output = AResult<R>>,
+{
+    type Output = AResult<R>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        match panic::catch_unwind(AssertUnwindSafe(|| this.fut.as_mut().poll(cx))) {
+            Ok(poll) => poll,
+            Err(payload) => Poll::Ready(Err(on_panic(&this.name, payload))),
+        }
+    }
+}
+
+/// 包装future, poll时panic转为错误, 如: `tokio::spawn(catch_panic_async("sync", task))`
+pub fn catch_panic_async<F, R>(name: &str, fut: F) -> CatchPanic<F>
+where
+    F: Future<Output = AResult<R>>,
+{
+    CatchPanic {
+        name: name.to_owned(),
+        fut:  Box::pin(fut),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    use super::{catch_panic, catch_panic_async, init_crash_report, log_tail, CrashReportConfig};
+    use crate::running::shutdown::shutdown_requested;
+
+    #[test]
+    fn test_catch_panic() {
+        let dir = "./_data/crash-test";
+        let log_file = format!("{}/app.log", dir);
+        fs::create_dir_all(dir).unwrap();
+        fs::write(&log_file, "line1\nline2\nline3\n").unwrap();
+        init_crash_report(
+            CrashReportConfig::new(dir)
+                .with_log_file(&log_file)
+                .with_tail_lines(2),
+        );
+
+        assert_eq!(catch_panic("ok", || Ok(1)).unwrap(), 1);
+        let err = catch_panic::<()>("sync task", || panic!("boom")).unwrap_err();
+        assert!(err.to_string().contains("task sync task panicked: boom"));
+        assert!(shutdown_requested().is_some());
+
+        let report = fs::read_dir(dir)
+            .unwrap()
+            .map(|v| v.unwrap().path())
+            .find(|v| v.to_string_lossy().contains("crash-sync_task-"))
+            .unwrap();
+        let content = fs::read_to_string(report).unwrap();
+        assert!(content.contains("panic: boom"));
+        assert!(content.contains("log tail:\nline2\nline3"));
+        assert!(!content.contains("line1"));
+
+        let fut = pin!(catch_panic_async::<_, ()>("async", async {
+            panic!("async boom")
+        }));
+        let Poll::Ready(result) = fut.poll(&mut Context::from_waker(Waker::noop())) else {
+            panic!("future not ready");
+        };
+        assert!(result.unwrap_err().to_string().contains("async boom"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_log_tail() {
+        let dir = "./_data/crash-tail-test";
+        let log_file = format!("{}/app.log", dir);
+        fs::create_dir_all(dir).unwrap();
+        // 超过一次读取的大小
+        let content = (0..2000).map(|v| format!("line{}\n", v)).collect::<String>();
+        fs::write(&log_file, content).unwrap();
+        let path = std::path::Path::new(&log_file);
+        assert_eq!(log_tail(path, 2).unwrap(), "line1998\nline1999");
+        let tail = log_tail(path, 1500).unwrap();
+        assert_eq!(tail.lines().count(), 1500);
+        assert!(tail.starts_with("line500\n"));
+        assert_eq!(log_tail(path, 5000).unwrap().lines().count(), 2000);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
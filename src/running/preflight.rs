@@ -0,0 +1,261 @@
+//! 启动自检: 主循环开始前运行注册的检查(数据库连接, 表是否存在, 交易日历, 磁盘空间, 时钟),
+//! 输出检查结果表格, 有失败时返回包含所有失败项的错误
+//!
+//! ```ignore
+//! preflight::register(Check::db_reachable(pool.clone()));
+//! preflight::register(Check::disk_space("/data", 10 << 30));
+//! running::preflight().await?;
+//! ```
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use eyre::eyre;
+use sysinfo::Disks;
+
+use crate::human::{format_bytes, Table};
+use crate::AResult;
+
+type CheckFuture = Pin<Box<dyn Future<Output = AResult<String>> + Send>>;
+
+type CheckFn = Box<dyn FnOnce() -> CheckFuture + Send>;
+
+/// 一项检查, 成功时返回的文本显示在结果表格的detail列
+pub struct Check {
+    name: String,
+    run:  CheckFn,
+}
+
+impl Check {
+    pub fn new<F, Fut>(name: &str, f: F) -> Check
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = AResult<String>> + Send + 'static,
+    {
+        Check {
+            name: name.to_owned(),
+            run:  Box::new(move || Box::pin(f())),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// path所在磁盘的可用空间不少于min_available字节
+    pub fn disk_space(path: impl Into<PathBuf>, min_available: u64) -> Check {
+        let path = path.into();
+        Check::new(&format!("disk {}", path.display()), move || async move {
+            let available = available_space(&path)?;
+            if available < min_available {
+                Err(eyre!(
+                    "可用空间不足: {} < {}",
+                    format_bytes(available),
+                    format_bytes(min_available)
+                ))?;
+            }
+            Ok(format!("可用 {}", format_bytes(available)))
+        })
+    }
+
+    /// 数据库可以连接并执行语句
+    #[cfg(feature = "mysqlx")]
+    pub fn db_reachable(pool: std::sync::Arc<sqlx::MySqlPool>) -> Check {
+        Check::new("db reachable", move || async move {
+            let (version,) = sqlx::query_as::<_, (String,)>("SELECT VERSION()")
+                .fetch_one(&*pool)
+                .await?;
+            Ok(format!("mysql {}", version))
+        })
+    }
+
+    /// 本机时间和数据库时间相差不超过max_skew
+    #[cfg(feature = "mysqlx")]
+    pub fn clock_sanity(pool: std::sync::Arc<sqlx::MySqlPool>, max_skew: Duration) -> Check {
+        Check::new("clock sanity", move || async move {
+            let (db_ts,) =
+                sqlx::query_as::<_, (f64,)>("SELECT CAST(UNIX_TIMESTAMP(NOW(6)) AS DOUBLE)")
+                    .fetch_one(&*pool)
+                    .await?;
+            let local_ts = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs_f64();
+            let skew = Duration::from_secs_f64((local_ts - db_ts).abs());
+            if skew > max_skew {
+                Err(eyre!(
+                    "本机与数据库时间相差 {} > {}",
+                    crate::human::format_duration(&skew),
+                    crate::human::format_duration(&max_skew)
+                ))?;
+            }
+            Ok(format!("相差 {}ms", skew.as_millis()))
+        })
+    }
+
+    /// SqlLoader中定义的表(不含模板表)都已存在
+    #[cfg(all(feature = "mysqlx", feature = "sql-loader"))]
+    pub fn required_tables(pool: std::sync::Arc<sqlx::MySqlPool>) -> Check {
+        Check::new("required tables", move || async move {
+            let loader =
+                crate::sql_loader::SqlLoader::try_get().ok_or(eyre!("SqlLoader未初始化"))?;
+            let tables = loader.table_names();
+            let mut missing = Vec::new();
+            for (db, name) in tables.iter() {
+                let (count,) = sqlx::query_as::<_, (i64,)>(
+                    "SELECT COUNT(*) FROM information_schema.TABLES WHERE TABLE_SCHEMA=? AND \
+                     TABLE_NAME=?",
+                )
+                .bind(db)
+                .bind(name)
+                .fetch_one(&*pool)
+                .await?;
+                if count == 0 {
+                    missing.push(format!("{}.{}", db, name));
+                }
+            }
+            if !missing.is_empty() {
+                Err(eyre!("表不存在: {}", missing.join(", ")))?;
+            }
+            Ok(format!("{} tables", tables.len()))
+        })
+    }
+
+    /// 交易日历已初始化, 且至少包含今天之后min_days_ahead个交易日
+    #[cfg(feature = "hq")]
+    pub fn calendar_fresh(min_days_ahead: usize) -> Check {
+        Check::new("calendar fresh", move || async move {
+            let items = crate::hq::future::trade_day::items().ok_or(eyre!("交易日历未初始化"))?;
+            let today = chrono::Local::now().date_naive();
+            let ahead = items.iter().filter(|v| v.td_day > today).count();
+            let last = items
+                .last()
+                .map(|v| v.td_day.to_string())
+                .unwrap_or_default();
+            if ahead < min_days_ahead {
+                Err(eyre!(
+                    "今天之后只有{}个交易日, 最后一个交易日: {}",
+                    ahead,
+                    last
+                ))?;
+            }
+            Ok(format!("至 {}", last))
+        })
+    }
+}
+
+/// 挂载点最长的包含path的磁盘的可用空间
+fn available_space(path: &Path) -> AResult<u64> {
+    let path = path
+        .canonicalize()
+        .map_err(|e| eyre!("{} {}", path.display(), e))?;
+    let disks = Disks::new_with_refreshed_list();
+    let disk = disks
+        .list()
+        .iter()
+        .filter(|v| path.starts_with(v.mount_point()))
+        .max_by_key(|v| v.mount_point().as_os_str().len())
+        .ok_or(eyre!("找不到所在磁盘: {}", path.display()))?;
+    Ok(disk.available_space())
+}
+
+static CHECKS: OnceLock<Mutex<Vec<Check>>> = OnceLock::new();
+
+fn checks_lock() -> &'static Mutex<Vec<Check>> {
+    CHECKS.get_or_init(Default::default)
+}
+
+/// 注册检查, 在下一次preflight时按注册顺序运行
+pub fn register(check: Check) {
+    checks_lock().lock().unwrap().push(check);
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name:    String,
+    pub passed:  bool,
+    /// 成功时为检查返回的文本, 失败时为错误
+    pub detail:  String,
+    pub elapsed: Duration,
+}
+
+/// 运行并清空已注册的检查, 输出结果表格
+///
+/// 所有检查都会运行, 有失败时返回包含所有失败项的错误
+pub async fn preflight() -> AResult<Vec<CheckResult>> {
+    let checks = std::mem::take(&mut *checks_lock().lock().unwrap());
+    let results = run_checks(checks).await;
+
+    let mut table = Table::new(&["check", "result", "detail", "elapsed"]).with_right_align(&[3]);
+    for r in results.iter() {
+        table.push_row(vec![
+            r.name.clone(),
+            if r.passed { "pass" } else { "FAIL" }.to_owned(),
+            r.detail.clone(),
+            format!("{}ms", r.elapsed.as_millis()),
+        ]);
+    }
+    println!("{}", table);
+
+    let failed = results
+        .iter()
+        .filter(|v| !v.passed)
+        .map(|v| format!("{}: {}", v.name, v.detail))
+        .collect::<Vec<_>>();
+    if !failed.is_empty() {
+        return Err(eyre!(
+            "preflight failed {}/{}:\n{}",
+            failed.len(),
+            results.len(),
+            failed.join("\n")
+        ));
+    }
+    Ok(results)
+}
+
+async fn run_checks(checks: Vec<Check>) -> Vec<CheckResult> {
+    let mut results = Vec::with_capacity(checks.len());
+    for check in checks {
+        let start = Instant::now();
+        let r = (check.run)().await;
+        let (passed, detail) = match r {
+            Ok(detail) => (true, detail),
+            Err(e) => (false, format!("{:#}", e)),
+        };
+        results.push(CheckResult {
+            name: check.name,
+            passed,
+            detail,
+            elapsed: start.elapsed(),
+        });
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use eyre::eyre;
+
+    use super::{preflight, register, Check};
+
+    #[tokio::test]
+    async fn test_preflight() {
+        register(Check::new("ok", || async { Ok("fine".to_owned()) }));
+        register(Check::disk_space(std::env::temp_dir(), 1));
+        let results = preflight().await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|v| v.passed));
+
+        register(Check::new("ok", || async { Ok("fine".to_owned()) }));
+        register(Check::new("bad", || async { Err(eyre!("broken")) }));
+        register(Check::disk_space(std::env::temp_dir(), u64::MAX));
+        let err = preflight().await.unwrap_err().to_string();
+        assert!(err.starts_with("preflight failed 2/3"), "{}", err);
+        assert!(err.contains("bad: broken"));
+        assert!(err.contains("disk "));
+
+        // 检查运行后清空
+        assert!(preflight().await.unwrap().is_empty());
+    }
+}
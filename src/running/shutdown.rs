@@ -0,0 +1,123 @@
+//! 退出前执行的钩子, 如写入缓存中的数据, 关闭连接
+//!
+//! 钩子按注册的相反顺序执行, 每个钩子只执行一次. `request_shutdown`唤醒`wait_shutdown`,
+//! 主程序中用`shutdown_on_request`等待退出请求后执行钩子
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Mutex, OnceLock};
+
+use tokio::sync::watch;
+
+type HookFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type Hook = Box<dyn FnOnce() -> HookFuture + Send>;
+
+static HOOKS: OnceLock<Mutex<Vec<(String, Hook)>>> = OnceLock::new();
+
+fn hooks() -> &'static Mutex<Vec<(String, Hook)>> {
+    HOOKS.get_or_init(Default::default)
+}
+
+/// 注册退出钩子
+pub fn on_shutdown<F, Fut>(name: &str, hook: F)
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let hook: Hook = Box::new(move || Box::pin(hook()));
+    hooks().lock().unwrap().push((name.to_owned(), hook));
+}
+
+/// 已注册的钩子数量
+pub fn shutdown_hook_count() -> usize {
+    hooks().lock().unwrap().len()
+}
+
+static SHUTDOWN_REASON: OnceLock<String> = OnceLock::new();
+static SHUTDOWN_TX: OnceLock<watch::Sender<bool>> = OnceLock::new();
+
+fn shutdown_tx() -> &'static watch::Sender<bool> {
+    SHUTDOWN_TX.get_or_init(|| watch::channel(false).0)
+}
+
+/// 请求退出, 只记录第一次的原因, 唤醒所有`wait_shutdown`
+pub fn request_shutdown(reason: &str) {
+    let _ = SHUTDOWN_REASON.set(reason.to_owned());
+    shutdown_tx().send_replace(true);
+}
+
+/// 已请求退出时返回原因
+pub fn shutdown_requested() -> Option<&'static str> {
+    SHUTDOWN_REASON.get().map(|v| v.as_str())
+}
+
+/// 等待退出请求, 返回原因, 已请求过时直接返回
+pub async fn wait_shutdown() -> &'static str {
+    let mut rx = shutdown_tx().subscribe();
+    // sender是静态的, 不会关闭
+    let _ = rx.wait_for(|v| *v).await;
+    shutdown_requested().unwrap_or_default()
+}
+
+/// 等待退出请求后执行所有钩子, 返回(原因, 执行的钩子名称)
+pub async fn shutdown_on_request() -> (&'static str, Vec<String>) {
+    let reason = wait_shutdown().await;
+    (reason, run_shutdown_hooks().await)
+}
+
+/// 执行所有已注册的钩子, 返回执行的钩子名称
+pub async fn run_shutdown_hooks() -> Vec<String> {
+    let hook_vec = std::mem::take(&mut *hooks().lock().unwrap());
+    let mut names = Vec::with_capacity(hook_vec.len());
+    for (name, hook) in hook_vec.into_iter().rev() {
+        hook().await;
+        names.push(name);
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Waker};
+
+    use super::{
+        on_shutdown, request_shutdown, run_shutdown_hooks, shutdown_hook_count,
+        shutdown_requested, wait_shutdown,
+    };
+
+    #[test]
+    fn test_shutdown_hooks() {
+        let count = Arc::new(AtomicUsize::new(0));
+        for name in ["first", "second"] {
+            let count = count.clone();
+            on_shutdown(name, move || async move {
+                count.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        assert_eq!(shutdown_hook_count(), 2);
+
+        // 钩子中没有等待, 一次poll就能完成
+        let mut fut = pin!(run_shutdown_hooks());
+        let Poll::Ready(names) = fut.as_mut().poll(&mut Context::from_waker(Waker::noop())) else {
+            panic!("hooks not ready");
+        };
+        assert_eq!(names, ["second", "first"]);
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+        assert_eq!(shutdown_hook_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_wait_shutdown() {
+        // 钩子是全局的, 这里不执行钩子, 以免影响test_shutdown_hooks
+        let waiter = tokio::spawn(wait_shutdown());
+        tokio::task::yield_now().await;
+        request_shutdown("test");
+        let reason = waiter.await.unwrap();
+        // 其他测试可能先请求过退出
+        assert_eq!(Some(reason), shutdown_requested());
+        assert!(!reason.is_empty());
+    }
+}
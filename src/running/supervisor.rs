@@ -0,0 +1,133 @@
+//! 任务出错后按`Backoff`等待并重新启动
+//!
+//! 只重启返回错误的任务, panic不捕获: 需要崩溃报告时在factory中用`catch_panic_async`包装,
+//! panic后会请求退出, 不应再重启
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+use crate::timer::Backoff;
+use crate::AResult;
+
+/// 默认运行超过这个时间后出错时重新计数
+const DEFAULT_STABLE_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct Supervisor {
+    name:         String,
+    backoff:      Backoff,
+    stable_after: Duration,
+}
+
+impl Supervisor {
+    pub fn new(name: &str, backoff: Backoff) -> Supervisor {
+        Supervisor {
+            name: name.to_owned(),
+            backoff,
+            stable_after: DEFAULT_STABLE_AFTER,
+        }
+    }
+
+    /// 任务运行超过stable_after后出错时, 等待时间和次数从头计算
+    pub fn with_stable_after(self, stable_after: Duration) -> Self {
+        Supervisor {
+            stable_after,
+            ..self
+        }
+    }
+
+    /// 运行factory生成的任务, 正常结束时返回Ok, 重启次数用完时返回最后一次的错误
+    pub async fn run<F, Fut>(&self, mut factory: F) -> AResult<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = AResult<()>>,
+    {
+        let mut backoff = self.backoff.clone();
+        loop {
+            let start = Instant::now();
+            let e = match factory().await {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            };
+            if start.elapsed() >= self.stable_after {
+                backoff.reset();
+            }
+            let Some(delay) = backoff.next() else {
+                return Err(e);
+            };
+            warn!(
+                "task {} err: {}, restart {} after {:?}",
+                self.name,
+                e,
+                backoff.attempt(),
+                delay
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use eyre::eyre;
+
+    use super::Supervisor;
+    use crate::timer::Backoff;
+
+    #[tokio::test]
+    async fn test_supervisor() {
+        let supervisor = Supervisor::new(
+            "sync",
+            Backoff::constant(Duration::from_millis(1)).with_max_attempts(2),
+        );
+        let mut runs = 0;
+        supervisor
+            .run(|| {
+                runs += 1;
+                let n = runs;
+                async move {
+                    if n < 3 {
+                        Err(eyre!("fail {}", n))
+                    } else {
+                        Ok(())
+                    }
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(runs, 3);
+
+        let mut runs = 0;
+        let err = supervisor
+            .run(|| {
+                runs += 1;
+                let n = runs;
+                async move { Err(eyre!("fail {}", n)) }
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "fail 3");
+
+        // 每次运行都超过stable_after, 一直重启
+        let supervisor = supervisor.with_stable_after(Duration::ZERO);
+        let mut runs = 0;
+        supervisor
+            .run(|| {
+                runs += 1;
+                let n = runs;
+                async move {
+                    if n < 5 {
+                        Err(eyre!("fail {}", n))
+                    } else {
+                        Ok(())
+                    }
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(runs, 5);
+    }
+}
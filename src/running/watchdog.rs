@@ -0,0 +1,196 @@
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use eyre::eyre;
+use log::warn;
+
+use crate::AResult;
+
+/// 心跳的输出目标
+#[derive(Debug, Clone)]
+pub enum WatchdogTarget {
+    /// 每次心跳更新文件的修改时间, 文件不存在时创建
+    File(PathBuf),
+    /// 通过`$NOTIFY_SOCKET`发送`WATCHDOG=1`给systemd
+    Systemd,
+}
+
+impl WatchdogTarget {
+    fn beat(&self) -> AResult<()> {
+        match self {
+            WatchdogTarget::File(path) => touch(path),
+            WatchdogTarget::Systemd => sd_notify("WATCHDOG=1"),
+        }
+    }
+}
+
+fn touch(path: &Path) -> AResult<()> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            fs::create_dir_all(parent)
+                .map_err(|e| eyre!("创建心跳文件目录失败: {} {}", parent.display(), e))?;
+        }
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| eyre!("打开心跳文件失败: {} {}", path.display(), e))?;
+    file.set_modified(SystemTime::now())
+        .map_err(|e| eyre!("更新心跳文件时间失败: {} {}", path.display(), e))?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn sd_notify(state: &str) -> AResult<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let socket_path =
+        std::env::var_os("NOTIFY_SOCKET").ok_or(eyre!("环境变量NOTIFY_SOCKET不存在"))?;
+    let socket = UnixDatagram::unbound().map_err(|e| eyre!("创建notify socket失败: {}", e))?;
+
+    let socket_path_str = socket_path.to_string_lossy();
+    #[cfg(target_os = "linux")]
+    if let Some(name) = socket_path_str.strip_prefix('@') {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::net::SocketAddr;
+
+        let addr = SocketAddr::from_abstract_name(name.as_bytes())
+            .map_err(|e| eyre!("notify socket地址错误: {} {}", socket_path_str, e))?;
+        socket
+            .send_to_addr(state.as_bytes(), &addr)
+            .map_err(|e| eyre!("发送sd_notify失败: {} {}", socket_path_str, e))?;
+        return Ok(());
+    }
+
+    socket
+        .send_to(state.as_bytes(), &socket_path)
+        .map_err(|e| eyre!("发送sd_notify失败: {} {}", socket_path_str, e))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn sd_notify(_state: &str) -> AResult<()> {
+    Err(eyre!("当前系统不支持sd_notify"))
+}
+
+/// 看门狗心跳
+/// 后台线程按interval定时发送心跳, 长时间的循环中可以调用`beat`手动发送.
+/// Drop时停止后台线程.
+#[derive(Debug)]
+pub struct Watchdog {
+    target: WatchdogTarget,
+    stop:   Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub fn start(interval: Duration, target: WatchdogTarget) -> AResult<Watchdog> {
+        target.beat()?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let target = target.clone();
+            let stop = stop.clone();
+            thread::Builder::new()
+                .name("watchdog".to_string())
+                .spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        thread::park_timeout(interval);
+                        if stop.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        if let Err(e) = target.beat() {
+                            warn!("watchdog beat err: {}", e);
+                        }
+                    }
+                })
+                .map_err(|e| eyre!("启动看门狗线程失败: {}", e))?
+        };
+
+        Ok(Watchdog {
+            target,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    /// 手动发送一次心跳
+    pub fn beat(&self) -> AResult<()> {
+        self.target.beat()
+    }
+
+    pub fn target(&self) -> &WatchdogTarget {
+        &self.target
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::time::{Duration, SystemTime};
+
+    use super::{Watchdog, WatchdogTarget};
+
+    #[test]
+    fn test_watchdog_file() {
+        let path = std::env::temp_dir()
+            .join("common-rs-watchdog")
+            .join("heartbeat");
+        let _ = fs::remove_file(&path);
+
+        let watchdog = Watchdog::start(
+            Duration::from_millis(50),
+            WatchdogTarget::File(path.clone()),
+        )
+        .unwrap();
+        let modified = || fs::metadata(&path).unwrap().modified().unwrap();
+        // 把修改时间改到一小时前, 后台线程的心跳要把它更新
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        let set_old = || {
+            OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(old)
+                .unwrap()
+        };
+        set_old();
+        assert_eq!(modified(), old);
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(modified() > old);
+
+        set_old();
+        watchdog.beat().unwrap();
+        assert!(modified() > old);
+
+        // 停止后不再更新
+        drop(watchdog);
+        set_old();
+        std::thread::sleep(Duration::from_millis(150));
+        assert_eq!(modified(), old);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_watchdog_systemd_no_socket() {
+        std::env::remove_var("NOTIFY_SOCKET");
+        let r = Watchdog::start(Duration::from_secs(1), WatchdogTarget::Systemd);
+        assert!(r.is_err());
+    }
+}
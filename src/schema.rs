@@ -0,0 +1,441 @@
+//! 配置文件的结构校验
+//! 在反序列化之前按声明的结构(必须的key, 类型, 枚举值)校验文档, 一次性返回所有问题及完整的key路径.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    Null,
+    Bool,
+    Int,
+    Float,
+    String,
+    Datetime,
+    Seq,
+    Map,
+    Other,
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ValueKind::Null => "null",
+            ValueKind::Bool => "bool",
+            ValueKind::Int => "int",
+            ValueKind::Float => "float",
+            ValueKind::String => "string",
+            ValueKind::Datetime => "datetime",
+            ValueKind::Seq => "seq",
+            ValueKind::Map => "map",
+            ValueKind::Other => "other",
+        };
+        f.write_str(s)
+    }
+}
+
+/// 可以被校验的文档节点, yaml和toml的Value都实现了该trait
+pub trait SchemaValue {
+    fn kind(&self) -> ValueKind;
+
+    /// Map的字段, 非Map返回None
+    fn field(&self, key: &str) -> Option<&Self>;
+
+    /// Map的所有key
+    fn keys(&self) -> Vec<String>;
+
+    /// Seq的所有元素
+    fn items(&self) -> Vec<&Self>;
+
+    fn as_str(&self) -> Option<&str>;
+}
+
+#[derive(Debug, Clone)]
+pub enum Schema {
+    Any,
+    Bool,
+    Int,
+    /// 整数也可以
+    Float,
+    String,
+    Datetime,
+    /// 字符串, 且必须为其中之一
+    Enum(Vec<String>),
+    Seq(Box<Schema>),
+    /// key任意, value为同一结构, 如: HashMap<String, PoolConfig>
+    Map(Box<Schema>),
+    Table(TableSchema),
+}
+
+impl Schema {
+    pub fn enums(values: &[&str]) -> Schema {
+        Schema::Enum(values.iter().map(|v| v.to_string()).collect())
+    }
+
+    pub fn seq(item: Schema) -> Schema {
+        Schema::Seq(Box::new(item))
+    }
+
+    pub fn map(value: Schema) -> Schema {
+        Schema::Map(Box::new(value))
+    }
+
+    fn expect_kind(&self) -> &'static str {
+        match self {
+            Schema::Any => "any",
+            Schema::Bool => "bool",
+            Schema::Int => "int",
+            Schema::Float => "float",
+            Schema::String => "string",
+            Schema::Datetime => "datetime",
+            Schema::Enum(_) => "string",
+            Schema::Seq(_) => "seq",
+            Schema::Map(_) | Schema::Table(_) => "map",
+        }
+    }
+
+    /// 校验文档, 返回所有的问题
+    pub fn validate<V: SchemaValue>(&self, value: &V) -> Result<(), SchemaError> {
+        let mut problems = Vec::new();
+        self.validate_inner(value, "", &mut problems);
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(SchemaError { problems })
+        }
+    }
+
+    fn validate_inner<V: SchemaValue>(
+        &self,
+        value: &V,
+        path: &str,
+        problems: &mut Vec<SchemaProblem>,
+    ) {
+        let kind = value.kind();
+        let kind_ok = match self {
+            Schema::Any => true,
+            Schema::Bool => kind == ValueKind::Bool,
+            Schema::Int => kind == ValueKind::Int,
+            Schema::Float => kind == ValueKind::Float || kind == ValueKind::Int,
+            Schema::String | Schema::Enum(_) => kind == ValueKind::String,
+            Schema::Datetime => kind == ValueKind::Datetime || kind == ValueKind::String,
+            Schema::Seq(_) => kind == ValueKind::Seq,
+            Schema::Map(_) | Schema::Table(_) => kind == ValueKind::Map,
+        };
+        if !kind_ok {
+            problems.push(SchemaProblem::new(
+                path,
+                format!("expect {}, found {}", self.expect_kind(), kind),
+            ));
+            return;
+        }
+
+        match self {
+            Schema::Enum(values) => {
+                let v = value.as_str().unwrap_or_default();
+                if !values.iter().any(|e| e == v) {
+                    problems.push(SchemaProblem::new(
+                        path,
+                        format!("\"{}\" not in [{}]", v, values.join(", ")),
+                    ));
+                }
+            },
+            Schema::Seq(item) => {
+                for (idx, v) in value.items().into_iter().enumerate() {
+                    item.validate_inner(v, &format!("{}[{}]", path, idx), problems);
+                }
+            },
+            Schema::Map(item) => {
+                for key in value.keys() {
+                    if let Some(v) = value.field(&key) {
+                        item.validate_inner(v, &join_path(path, &key), problems);
+                    }
+                }
+            },
+            Schema::Table(table) => table.validate_inner(value, path, problems),
+            _ => {},
+        }
+    }
+}
+
+impl From<TableSchema> for Schema {
+    fn from(value: TableSchema) -> Self {
+        Schema::Table(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct FieldSchema {
+    name:     String,
+    schema:   Schema,
+    required: bool,
+}
+
+/// 固定字段的Map
+#[derive(Debug, Clone, Default)]
+pub struct TableSchema {
+    fields:       Vec<FieldSchema>,
+    deny_unknown: bool,
+}
+
+impl TableSchema {
+    pub fn new() -> TableSchema {
+        TableSchema::default()
+    }
+
+    pub fn required(mut self, name: &str, schema: impl Into<Schema>) -> Self {
+        self.fields.push(FieldSchema {
+            name:     name.to_owned(),
+            schema:   schema.into(),
+            required: true,
+        });
+        self
+    }
+
+    pub fn optional(mut self, name: &str, schema: impl Into<Schema>) -> Self {
+        self.fields.push(FieldSchema {
+            name:     name.to_owned(),
+            schema:   schema.into(),
+            required: false,
+        });
+        self
+    }
+
+    /// 未声明的字段也报错
+    pub fn deny_unknown(mut self) -> Self {
+        self.deny_unknown = true;
+        self
+    }
+
+    fn validate_inner<V: SchemaValue>(
+        &self,
+        value: &V,
+        path: &str,
+        problems: &mut Vec<SchemaProblem>,
+    ) {
+        for field in self.fields.iter() {
+            let field_path = join_path(path, &field.name);
+            match value.field(&field.name) {
+                Some(v) if v.kind() == ValueKind::Null && !field.required => {},
+                Some(v) => field.schema.validate_inner(v, &field_path, problems),
+                None if field.required => {
+                    problems.push(SchemaProblem::new(&field_path, "missing required key"))
+                },
+                None => {},
+            }
+        }
+        if self.deny_unknown {
+            for key in value.keys() {
+                if !self.fields.iter().any(|f| f.name == key) {
+                    problems.push(SchemaProblem::new(&join_path(path, &key), "unknown key"));
+                }
+            }
+        }
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaProblem {
+    pub path:    String,
+    pub message: String,
+}
+
+impl SchemaProblem {
+    fn new(path: &str, message: impl Into<String>) -> SchemaProblem {
+        let path = if path.is_empty() { "." } else { path };
+        SchemaProblem {
+            path:    path.to_owned(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SchemaProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub struct SchemaError {
+    pub problems: Vec<SchemaProblem>,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "schema validation failed, {} problem(s):",
+            self.problems.len()
+        )?;
+        for problem in self.problems.iter() {
+            write!(f, "\n  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl SchemaValue for serde_yaml::Value {
+    fn kind(&self) -> ValueKind {
+        use serde_yaml::Value;
+        match self {
+            Value::Null => ValueKind::Null,
+            Value::Bool(_) => ValueKind::Bool,
+            Value::Number(n) if n.is_i64() || n.is_u64() => ValueKind::Int,
+            Value::Number(_) => ValueKind::Float,
+            Value::String(_) => ValueKind::String,
+            Value::Sequence(_) => ValueKind::Seq,
+            Value::Mapping(_) => ValueKind::Map,
+            Value::Tagged(_) => ValueKind::Other,
+        }
+    }
+
+    fn field(&self, key: &str) -> Option<&Self> {
+        self.as_mapping().and_then(|v| v.get(key))
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.as_mapping()
+            .map(|v| {
+                v.keys()
+                    .map(|k| match k.as_str() {
+                        Some(k) => k.to_owned(),
+                        None => serde_yaml::to_string(k)
+                            .unwrap_or_default()
+                            .trim()
+                            .to_owned(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn items(&self) -> Vec<&Self> {
+        self.as_sequence()
+            .map(|v| v.iter().collect())
+            .unwrap_or_default()
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        serde_yaml::Value::as_str(self)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl SchemaValue for toml::Value {
+    fn kind(&self) -> ValueKind {
+        use toml::Value;
+        match self {
+            Value::String(_) => ValueKind::String,
+            Value::Integer(_) => ValueKind::Int,
+            Value::Float(_) => ValueKind::Float,
+            Value::Boolean(_) => ValueKind::Bool,
+            Value::Datetime(_) => ValueKind::Datetime,
+            Value::Array(_) => ValueKind::Seq,
+            Value::Table(_) => ValueKind::Map,
+        }
+    }
+
+    fn field(&self, key: &str) -> Option<&Self> {
+        self.as_table().and_then(|v| v.get(key))
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.as_table()
+            .map(|v| v.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn items(&self) -> Vec<&Self> {
+        self.as_array()
+            .map(|v| v.iter().collect())
+            .unwrap_or_default()
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        toml::Value::as_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Schema, TableSchema};
+
+    fn pool_schema() -> Schema {
+        Schema::map(
+            TableSchema::new()
+                .optional("default", Schema::Bool)
+                .required("host", Schema::String)
+                .required("port", Schema::Int)
+                .required("charset", Schema::enums(&["utf8", "utf8mb4"]))
+                .optional("tags", Schema::seq(Schema::String))
+                .into(),
+        )
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_validate_yaml() {
+        let yaml_str = r#"
+        local-db:
+          default: true
+          host: 127.0.0.1
+          port: 3306
+          charset: utf8
+        bad-db:
+          host: 127.0.0.1
+          port: "3306"
+          charset: gbk
+          tags: [a, 1]
+        "#;
+        let value = serde_yaml::from_str::<serde_yaml::Value>(yaml_str).unwrap();
+        let err = pool_schema().validate(&value).unwrap_err();
+        println!("{}", err);
+        let paths = err
+            .problems
+            .iter()
+            .map(|v| v.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec!["bad-db.port", "bad-db.charset", "bad-db.tags[1]"]
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_validate_toml() {
+        let toml_str = r#"
+        [local-db]
+        host = "127.0.0.1"
+        port = 3306
+        charset = "utf8mb4"
+
+        [bad-db]
+        port = 3306
+        charset = "utf8"
+        "#;
+        let value = toml::from_str::<toml::Value>(toml_str).unwrap();
+        let err = pool_schema().validate(&value).unwrap_err();
+        println!("{}", err);
+        assert_eq!(err.problems.len(), 1);
+        assert_eq!(err.problems[0].path, "bad-db.host");
+
+        let schema: Schema = TableSchema::new()
+            .required("a", Schema::Int)
+            .deny_unknown()
+            .into();
+        let value = toml::from_str::<toml::Value>("a = 1\nb = 2").unwrap();
+        let err = schema.validate(&value).unwrap_err();
+        println!("{}", err);
+        assert_eq!(err.problems[0].path, "b");
+    }
+}
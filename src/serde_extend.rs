@@ -1,4 +1,8 @@
 pub mod chrono;
+pub mod env_default;
+pub mod flag_set;
+pub mod human;
 pub mod int;
+pub mod lenient_vec;
 pub mod path_plain;
 pub mod string;
@@ -0,0 +1,244 @@
+//! 配置文件中没有的字段从环境变量取值, 用于密码等不放在配置文件中的字段
+//!
+//! ```ignore
+//! env_default!(MysqlPasswd, "MYSQL_PASSWD");
+//! env_default!(ApiToken, "API_TOKEN");
+//!
+//! #[derive(Deserialize)]
+//! struct Config {
+//!     // 配置中没有时取环境变量, 环境变量也没有时报错
+//!     passwd: EnvOr<String, MysqlPasswd>,
+//!     // 环境变量也没有时为None
+//!     token:  EnvOption<String, ApiToken>,
+//!     // 值为`${MYSQL_URL}`时取环境变量
+//!     #[serde(deserialize_with = "common_rs::serde_extend::env_default::expand")]
+//!     url:    String,
+//! }
+//! ```
+//!
+//! 环境变量存在但解析失败时反序列化报错, 不用默认值代替
+use std::env;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::str::FromStr;
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer};
+
+/// 环境变量名, 用`env_default!`生成
+pub trait EnvKey {
+    const KEY: &'static str;
+}
+
+/// 环境变量的值, 不存在时为None, 解析失败时报错
+pub fn env_value<T>(key: &str) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match env::var(key) {
+        Ok(v) => v
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| format!("env {} invalid: {}", key, e)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(format!("env {} invalid: {}", key, e)),
+    }
+}
+
+/// `${KEY}`形式的值取环境变量KEY, 环境变量不存在时报错, 其他值原样返回
+pub fn expand<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    match s.strip_prefix("${").and_then(|v| v.strip_suffix('}')) {
+        Some(key) => {
+            env::var(key).map_err(|_| serde::de::Error::custom(format!("env {} not set", key)))
+        },
+        None => Ok(s),
+    }
+}
+
+/// 配置中没有或为null时取环境变量`K::KEY`, 环境变量不存在时报错
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvOr<T, K> {
+    value: T,
+    key:   PhantomData<K>,
+}
+
+impl<T, K> EnvOr<T, K> {
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, K> Deref for EnvOr<T, K> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<'de, T, K> Deserialize<'de> for EnvOr<T, K>
+where
+    T: Deserialize<'de> + FromStr,
+    T::Err: fmt::Display,
+    K: EnvKey,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = deserializer
+            .deserialize_option(EnvVisitor::<T, K>(PhantomData))?
+            .ok_or_else(|| de::Error::custom(format!("env {} not set", K::KEY)))?;
+        Ok(EnvOr {
+            value,
+            key: PhantomData,
+        })
+    }
+}
+
+/// 配置中没有或为null时取环境变量`K::KEY`, 环境变量不存在时为None
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvOption<T, K> {
+    value: Option<T>,
+    key:   PhantomData<K>,
+}
+
+impl<T, K> EnvOption<T, K> {
+    pub fn into_inner(self) -> Option<T> {
+        self.value
+    }
+}
+
+impl<T, K> Deref for EnvOption<T, K> {
+    type Target = Option<T>;
+
+    fn deref(&self) -> &Option<T> {
+        &self.value
+    }
+}
+
+impl<'de, T, K> Deserialize<'de> for EnvOption<T, K>
+where
+    T: Deserialize<'de> + FromStr,
+    T::Err: fmt::Display,
+    K: EnvKey,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = deserializer.deserialize_option(EnvVisitor::<T, K>(PhantomData))?;
+        Ok(EnvOption {
+            value,
+            key: PhantomData,
+        })
+    }
+}
+
+/// 字段缺失时serde按`visit_none`处理, 这时取环境变量
+struct EnvVisitor<T, K>(PhantomData<(T, K)>);
+
+impl<'de, T, K> Visitor<'de> for EnvVisitor<T, K>
+where
+    T: Deserialize<'de> + FromStr,
+    T::Err: fmt::Display,
+    K: EnvKey,
+{
+    type Value = Option<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a value or env {}", K::KEY)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        env_value(K::KEY).map_err(E::custom)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.visit_none()
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(Some)
+    }
+}
+
+/// 生成实现`EnvKey`的类型`$name`, 用于`EnvOr<T, $name>`和`EnvOption<T, $name>`
+#[macro_export]
+macro_rules! env_default {
+    ($name:ident, $key:literal) => {
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct $name;
+
+        impl $crate::serde_extend::env_default::EnvKey for $name {
+            const KEY: &'static str = $key;
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::{EnvOption, EnvOr};
+
+    crate::env_default!(Passwd, "COMMON_RS_TEST_PASSWD");
+    crate::env_default!(Port, "COMMON_RS_TEST_PORT");
+    crate::env_default!(Token, "COMMON_RS_TEST_TOKEN");
+
+    #[derive(Debug, Deserialize)]
+    struct Config {
+        passwd: EnvOr<String, Passwd>,
+        port:   EnvOr<u16, Port>,
+        token:  EnvOption<String, Token>,
+        #[serde(deserialize_with = "super::expand")]
+        url:    String,
+    }
+
+    #[test]
+    fn test_env_default() {
+        std::env::set_var("COMMON_RS_TEST_PASSWD", "secret");
+        std::env::set_var("COMMON_RS_TEST_PORT", "3307");
+        std::env::set_var("COMMON_RS_TEST_URL", "mysql://localhost");
+        std::env::remove_var("COMMON_RS_TEST_TOKEN");
+
+        let config = serde_yaml::from_str::<Config>("url: ${COMMON_RS_TEST_URL}").unwrap();
+        assert_eq!(*config.passwd, "secret");
+        assert_eq!(*config.port, 3307);
+        assert_eq!(*config.token, None);
+        assert_eq!(config.url, "mysql://localhost");
+
+        // 配置中有的字段不取环境变量
+        let config = serde_yaml::from_str::<Config>("passwd: abc\nport: 3306\nurl: x").unwrap();
+        assert_eq!(*config.passwd, "abc");
+        assert_eq!(*config.port, 3306);
+        assert_eq!(config.url, "x");
+
+        std::env::set_var("COMMON_RS_TEST_TOKEN", "t");
+        let config = toml::from_str::<Config>(r#"url = "x""#).unwrap();
+        assert_eq!(config.token.as_deref(), Some("t"));
+
+        assert!(serde_yaml::from_str::<Config>("url: ${COMMON_RS_TEST_NOT_SET}").is_err());
+
+        // 环境变量解析失败时报错
+        std::env::set_var("COMMON_RS_TEST_PORT", "33o7");
+        let err = serde_yaml::from_str::<Config>("url: x").unwrap_err();
+        assert!(err.to_string().contains("COMMON_RS_TEST_PORT"), "{}", err);
+
+        // 密码没有配置也没有环境变量时报错, 不为空字符串
+        std::env::set_var("COMMON_RS_TEST_PORT", "3307");
+        std::env::remove_var("COMMON_RS_TEST_PASSWD");
+        let err = serde_yaml::from_str::<Config>("url: x").unwrap_err();
+        assert!(err.to_string().contains("COMMON_RS_TEST_PASSWD not set"), "{}", err);
+    }
+}
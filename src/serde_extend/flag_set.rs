@@ -0,0 +1,320 @@
+//! 逗号分隔的标志集合, 如"night,day,auction", 反序列化为HashSet或bitflags, 也可以写成列表
+//!
+//! `hash_set`, `bits`遇到未知的标志时返回错误;
+//! `hash_set_lenient`, `bits_lenient`跳过未知的标志, 记录到当前线程, 通过`take_unknown`取出,
+//! 用于旧程序读取新增了标志的配置
+//!
+//! ```ignore
+//! #[derive(Deserialize, Serialize)]
+//! struct Config {
+//!     #[serde(with = "flag_set::bits")]
+//!     sessions: Sessions,
+//!     #[serde(with = "flag_set::hash_set_lenient")]
+//!     features: HashSet<Feature>,
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::str::FromStr;
+
+use bitflags::Flags;
+use serde::{Deserialize, Serializer};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StrOrSeq {
+    Str(String),
+    Seq(Vec<String>),
+}
+
+impl StrOrSeq {
+    /// 去掉空白和空项
+    fn into_names(self) -> Vec<String> {
+        let names = match self {
+            StrOrSeq::Str(s) => s.split(',').map(str::to_owned).collect(),
+            StrOrSeq::Seq(v) => v,
+        };
+        names
+            .into_iter()
+            .map(|v| v.trim().to_owned())
+            .filter(|v| !v.is_empty())
+            .collect()
+    }
+}
+
+/// 未知标志的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownFlag {
+    /// 返回错误
+    #[default]
+    Error,
+    /// 跳过, 记录到`take_unknown`
+    Ignore,
+}
+
+thread_local! {
+    static UNKNOWN: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 取出当前线程lenient反序列化跳过的标志
+pub fn take_unknown() -> Vec<String> {
+    UNKNOWN.with(|v| std::mem::take(&mut *v.borrow_mut()))
+}
+
+fn on_unknown(name: String, policy: UnknownFlag, error: String) -> Result<(), String> {
+    match policy {
+        UnknownFlag::Error => Err(error),
+        UnknownFlag::Ignore => {
+            UNKNOWN.with(|v| v.borrow_mut().push(name));
+            Ok(())
+        },
+    }
+}
+
+/// 解析"a,b,c"为HashSet, 元素通过FromStr解析
+pub fn parse_set<T>(s: &str, policy: UnknownFlag) -> Result<HashSet<T>, String>
+where
+    T: FromStr + Eq + Hash,
+    T::Err: Display,
+{
+    names_to_set(StrOrSeq::Str(s.to_owned()).into_names(), policy)
+}
+
+/// 解析"a,b,c"为bitflags, 标志名不区分大小写
+pub fn parse_bits<T: Flags>(s: &str, policy: UnknownFlag) -> Result<T, String> {
+    names_to_bits(StrOrSeq::Str(s.to_owned()).into_names(), policy)
+}
+
+fn names_to_set<T>(names: Vec<String>, policy: UnknownFlag) -> Result<HashSet<T>, String>
+where
+    T: FromStr + Eq + Hash,
+    T::Err: Display,
+{
+    let mut set = HashSet::with_capacity(names.len());
+    for name in names {
+        match name.parse::<T>() {
+            Ok(v) => {
+                set.insert(v);
+            },
+            Err(e) => {
+                let error = format!("unknown flag: {}, {}", name, e);
+                on_unknown(name, policy, error)?
+            },
+        }
+    }
+    Ok(set)
+}
+
+fn names_to_bits<T: Flags>(names: Vec<String>, policy: UnknownFlag) -> Result<T, String> {
+    let mut bits = T::empty();
+    for name in names {
+        match T::FLAGS
+            .iter()
+            .find(|v| !v.name().is_empty() && v.name().eq_ignore_ascii_case(&name))
+        {
+            Some(flag) => bits = bits.union(T::from_bits_retain(flag.value().bits())),
+            None => {
+                let expected = T::FLAGS
+                    .iter()
+                    .map(|v| v.name())
+                    .filter(|v| !v.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let error = format!("unknown flag: {}, expected: {}", name, expected);
+                on_unknown(name, policy, error)?
+            },
+        }
+    }
+    Ok(bits)
+}
+
+/// 按名称排序后用逗号连接, 输出稳定
+fn serialize_set<S, T>(set: &HashSet<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Display,
+{
+    let mut names = set.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    names.sort();
+    serializer.serialize_str(&names.join(","))
+}
+
+/// 按定义顺序输出标志名, 没有名称的位忽略
+fn serialize_bits<S, T>(bits: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Flags,
+{
+    let names = bits.iter_names().map(|(name, _)| name).collect::<Vec<_>>();
+    serializer.serialize_str(&names.join(","))
+}
+
+macro_rules! set_module {
+    ($name:ident, $policy:expr) => {
+        pub mod $name {
+            use std::collections::HashSet;
+            use std::fmt::Display;
+            use std::hash::Hash;
+            use std::str::FromStr;
+
+            use serde::{Deserialize, Deserializer, Serializer};
+
+            use super::{names_to_set, StrOrSeq};
+
+            pub fn serialize<S, T>(set: &HashSet<T>, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+                T: Display,
+            {
+                super::serialize_set(set, serializer)
+            }
+
+            pub fn deserialize<'de, D, T>(deserializer: D) -> Result<HashSet<T>, D::Error>
+            where
+                D: Deserializer<'de>,
+                T: FromStr + Eq + Hash,
+                T::Err: Display,
+            {
+                let names = StrOrSeq::deserialize(deserializer)?.into_names();
+                names_to_set(names, $policy).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+macro_rules! bits_module {
+    ($name:ident, $policy:expr) => {
+        pub mod $name {
+            use bitflags::Flags;
+            use serde::{Deserialize, Deserializer, Serializer};
+
+            use super::{names_to_bits, StrOrSeq};
+
+            pub fn serialize<S, T>(bits: &T, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+                T: Flags,
+            {
+                super::serialize_bits(bits, serializer)
+            }
+
+            pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+            where
+                D: Deserializer<'de>,
+                T: Flags,
+            {
+                let names = StrOrSeq::deserialize(deserializer)?.into_names();
+                names_to_bits(names, $policy).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+set_module!(hash_set, super::UnknownFlag::Error);
+set_module!(hash_set_lenient, super::UnknownFlag::Ignore);
+bits_module!(bits, super::UnknownFlag::Error);
+bits_module!(bits_lenient, super::UnknownFlag::Ignore);
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::fmt;
+    use std::str::FromStr;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{parse_bits, take_unknown, UnknownFlag};
+
+    bitflags::bitflags! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        struct Sessions: u8 {
+            const NIGHT = 1;
+            const DAY = 1 << 1;
+            const AUCTION = 1 << 2;
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Feature {
+        Color,
+        Json,
+    }
+
+    impl FromStr for Feature {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "color" => Ok(Feature::Color),
+                "json" => Ok(Feature::Json),
+                _ => Err("expected: color,json".into()),
+            }
+        }
+    }
+
+    impl fmt::Display for Feature {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(match self {
+                Feature::Color => "color",
+                Feature::Json => "json",
+            })
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Config {
+        #[serde(with = "super::bits")]
+        sessions: Sessions,
+        #[serde(with = "super::hash_set")]
+        features: HashSet<Feature>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct LenientConfig {
+        #[serde(with = "super::bits_lenient")]
+        sessions: Sessions,
+        #[serde(with = "super::hash_set_lenient")]
+        features: HashSet<Feature>,
+    }
+
+    #[test]
+    fn test_flag_set() {
+        let config =
+            serde_yaml::from_str::<Config>("sessions: night, day,auction\nfeatures: [json]")
+                .unwrap();
+        assert_eq!(config.sessions, Sessions::all());
+        assert_eq!(config.features, HashSet::from([Feature::Json]));
+
+        let config = Config {
+            sessions: Sessions::DAY | Sessions::NIGHT,
+            features: HashSet::from([Feature::Json, Feature::Color]),
+        };
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        assert_eq!(yaml, "sessions: NIGHT,DAY\nfeatures: color,json\n");
+
+        let config = serde_yaml::from_str::<Config>("sessions: ''\nfeatures: ''").unwrap();
+        assert!(config.sessions.is_empty() && config.features.is_empty());
+
+        let err = serde_yaml::from_str::<Config>("sessions: day,close\nfeatures: ''")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown flag: close"), "{}", err);
+        assert!(serde_yaml::from_str::<Config>("sessions: ''\nfeatures: trace").is_err());
+
+        let config =
+            serde_yaml::from_str::<LenientConfig>("sessions: day,close\nfeatures: trace,color")
+                .unwrap();
+        assert_eq!(config.sessions, Sessions::DAY);
+        assert_eq!(config.features, HashSet::from([Feature::Color]));
+        assert_eq!(take_unknown(), ["close", "trace"]);
+        assert!(take_unknown().is_empty());
+
+        assert_eq!(
+            parse_bits::<Sessions>("Night", UnknownFlag::Error).unwrap(),
+            Sessions::NIGHT
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! 配置中的时间长度和字节数, 可以写成"30s", "15m", "1GiB", 兼容原来的整数写法(秒, 字节)
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StrOrInt {
+    Int(u64),
+    Str(String),
+}
+
+pub mod duration {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::StrOrInt;
+    use crate::human::{format_duration, parse_duration};
+
+    pub fn serialize<S>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&format_duration(d))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match StrOrInt::deserialize(deserializer)? {
+            StrOrInt::Int(secs) => Ok(Duration::from_secs(secs)),
+            StrOrInt::Str(s) => parse_duration(&s).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// 空字符串为None, 字段不存在时需要加#[serde(default)]
+pub mod opt_duration {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::StrOrInt;
+    use crate::human::{format_duration, parse_duration};
+
+    pub fn serialize<S>(d: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match d {
+            Some(d) => serializer.serialize_str(&format_duration(d)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<StrOrInt>::deserialize(deserializer)? {
+            None => Ok(None),
+            Some(StrOrInt::Int(secs)) => Ok(Some(Duration::from_secs(secs))),
+            Some(StrOrInt::Str(s)) if s.is_empty() => Ok(None),
+            Some(StrOrInt::Str(s)) => parse_duration(&s)
+                .map(Some)
+                .map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+pub mod byte_size {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::StrOrInt;
+    use crate::human::{format_bytes, parse_bytes};
+
+    pub fn serialize<S>(bytes: &u64, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&format_bytes(*bytes))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<u64, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match StrOrInt::deserialize(deserializer)? {
+            StrOrInt::Int(bytes) => Ok(bytes),
+            StrOrInt::Str(s) => parse_bytes(&s).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// 空字符串为None, 字段不存在时需要加#[serde(default)]
+pub mod opt_byte_size {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::StrOrInt;
+    use crate::human::{format_bytes, parse_bytes};
+
+    pub fn serialize<S>(bytes: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match bytes {
+            Some(bytes) => serializer.serialize_str(&format_bytes(*bytes)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<StrOrInt>::deserialize(deserializer)? {
+            None => Ok(None),
+            Some(StrOrInt::Int(bytes)) => Ok(Some(bytes)),
+            Some(StrOrInt::Str(s)) if s.is_empty() => Ok(None),
+            Some(StrOrInt::Str(s)) => parse_bytes(&s).map(Some).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Config {
+        #[serde(with = "super::duration")]
+        timeout:  Duration,
+        #[serde(with = "super::duration")]
+        legacy:   Duration,
+        #[serde(with = "super::byte_size")]
+        max_size: u64,
+        #[serde(default, with = "super::opt_duration")]
+        idle:     Option<Duration>,
+        #[serde(default, with = "super::opt_byte_size")]
+        buffer:   Option<u64>,
+    }
+
+    #[test]
+    fn test_toml() {
+        let config = toml::from_str::<Config>(
+            "timeout = \"1h30m\"\nlegacy = 30\nmax_size = \"1GiB\"\nbuffer = \"64KiB\"\n",
+        )
+        .unwrap();
+        println!("{:?}", config);
+        assert_eq!(
+            config,
+            Config {
+                timeout:  Duration::from_secs(5400),
+                legacy:   Duration::from_secs(30),
+                max_size: 1 << 30,
+                idle:     None,
+                buffer:   Some(64 * 1024),
+            }
+        );
+        assert!(toml::from_str::<Config>("timeout = \"1x\"\nlegacy = 1\nmax_size = 1\n").is_err());
+    }
+
+    #[test]
+    fn test_yaml() {
+        let config = serde_yaml::from_str::<Config>(
+            "timeout: 15m\nlegacy: 10\nmax_size: 512MB\nidle: 500ms\n",
+        )
+        .unwrap();
+        assert_eq!(config.timeout, Duration::from_secs(900));
+        assert_eq!(config.idle, Some(Duration::from_millis(500)));
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        println!("{}", yaml);
+        assert_eq!(serde_yaml::from_str::<Config>(&yaml).unwrap(), config);
+    }
+}
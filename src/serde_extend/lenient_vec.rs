@@ -0,0 +1,199 @@
+//! 反序列化列表时跳过解析失败的元素
+//!
+//! 元素先解析为通用的值再转换为T, 转换失败的元素跳过并记录警告,
+//! 列表本身格式错误(如不是列表)时仍返回错误
+//!
+//! ```ignore
+//! #[derive(Deserialize)]
+//! struct Data {
+//!     #[serde(with = "lenient_vec")]
+//!     items: Vec<Item>,
+//! }
+//! let data = serde_json::from_str::<Data>(s)?;
+//! let warnings = lenient_vec::take_warnings();
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use serde::de::{DeserializeOwned, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::Value;
+
+/// 跳过的元素
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LenientWarning {
+    /// 元素在列表中的位置
+    pub index: usize,
+    pub error: String,
+}
+
+impl fmt::Display for LenientWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.index, self.error)
+    }
+}
+
+thread_local! {
+    static WARNINGS: RefCell<Vec<LenientWarning>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 取出当前线程`deserialize`记录的警告
+pub fn take_warnings() -> Vec<LenientWarning> {
+    WARNINGS.with(|v| std::mem::take(&mut *v.borrow_mut()))
+}
+
+/// 带警告的列表, 不需要通过线程变量取警告时使用
+#[derive(Debug, Clone, PartialEq)]
+pub struct LenientVec<T> {
+    pub items:    Vec<T>,
+    pub warnings: Vec<LenientWarning>,
+}
+
+impl<T> Default for LenientVec<T> {
+    fn default() -> Self {
+        LenientVec {
+            items:    Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl<T> Deref for LenientVec<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.items
+    }
+}
+
+impl<T> LenientVec<T> {
+    pub fn into_inner(self) -> Vec<T> {
+        self.items
+    }
+}
+
+struct LenientVisitor<T>(PhantomData<T>);
+
+impl<'de, T: DeserializeOwned> Visitor<'de> for LenientVisitor<T> {
+    type Value = LenientVec<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut r = LenientVec::default();
+        if let Some(size) = seq.size_hint() {
+            r.items.reserve(size);
+        }
+        let mut index = 0;
+        while let Some(value) = seq.next_element::<Value>()? {
+            match T::deserialize(value) {
+                Ok(item) => r.items.push(item),
+                Err(e) => r.warnings.push(LenientWarning {
+                    index,
+                    error: e.to_string(),
+                }),
+            }
+            index += 1;
+        }
+        Ok(r)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for LenientVec<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(LenientVisitor(PhantomData))
+    }
+}
+
+impl<T: Serialize> Serialize for LenientVec<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.items.serialize(serializer)
+    }
+}
+
+pub fn serialize<S, T>(v: &[T], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize,
+{
+    v.serialize(serializer)
+}
+
+/// 跳过的元素记录到当前线程, 通过`take_warnings`取出
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let LenientVec { items, warnings } = LenientVec::deserialize(deserializer)?;
+    if !warnings.is_empty() {
+        WARNINGS.with(|v| v.borrow_mut().extend(warnings));
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::{take_warnings, LenientVec};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Item {
+        code:  String,
+        price: f64,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Data {
+        #[serde(with = "super")]
+        items: Vec<Item>,
+    }
+
+    const YAML: &str = r#"
+items:
+  - code: ag2408
+    price: 7800
+  - code: au2408
+    price: abc
+  - [1, 2]
+  - code: cu2408
+    price: 78000.5
+"#;
+
+    #[test]
+    fn test_lenient_vec() {
+        let data = serde_yaml::from_str::<Data>(YAML).unwrap();
+        assert_eq!(data.items.len(), 2);
+        assert_eq!(data.items[1].code, "cu2408");
+        let warnings = take_warnings();
+        for warning in warnings.iter() {
+            println!("{}", warning);
+        }
+        assert_eq!(
+            warnings.iter().map(|v| v.index).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert!(take_warnings().is_empty());
+
+        let items = serde_yaml::from_str::<LenientVec<i32>>("[1, x, 3]").unwrap();
+        assert_eq!(*items, vec![1, 3]);
+        assert_eq!(items.warnings.len(), 1);
+
+        assert!(serde_yaml::from_str::<Data>("items: 1").is_err());
+    }
+}
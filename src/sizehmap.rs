@@ -120,6 +120,67 @@ where
     // }
 }
 
+#[cfg(feature = "sizehmap-persist")]
+mod persist {
+    use std::fmt::Debug;
+    use std::fs::{self, File};
+    use std::hash::Hash;
+    use std::io::{self, BufReader, BufWriter, Write};
+    use std::path::Path;
+
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    use super::SizeHashMap;
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum SizeHashMapPersistError {
+        #[error("{0}")]
+        Io(#[from] io::Error),
+
+        #[error("{0}")]
+        Bincode(#[from] bincode::Error),
+    }
+
+    impl<K, V> SizeHashMap<K, V>
+    where
+        K: Eq + Hash + Debug + Clone + Serialize + DeserializeOwned,
+        V: Debug + Serialize + DeserializeOwned,
+    {
+        /// 用bincode保存到文件, 按key的顺序保存, 先写临时文件再改名, 避免写一半的文件被加载
+        pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SizeHashMapPersistError> {
+            let path = path.as_ref();
+            let items = self
+                .key_vec
+                .iter()
+                .filter_map(|k| self.hmap.get(k).map(|v| (k, v)))
+                .collect::<Vec<_>>();
+            let tmp_path = path.with_extension("tmp");
+            {
+                let mut writer = BufWriter::new(File::create(&tmp_path)?);
+                bincode::serialize_into(&mut writer, &(self.capacity, items))?;
+                writer.flush()?;
+            }
+            fs::rename(&tmp_path, path)?;
+            Ok(())
+        }
+
+        /// 从save保存的文件恢复, capacity和key的顺序与保存时相同
+        pub fn load(path: impl AsRef<Path>) -> Result<SizeHashMap<K, V>, SizeHashMapPersistError> {
+            let reader = BufReader::new(File::open(path)?);
+            let (capacity, items): (usize, Vec<(K, V)>) = bincode::deserialize_from(reader)?;
+            let mut size_hmap = SizeHashMap::with_capacity(capacity);
+            for (k, v) in items {
+                size_hmap.insert(k, v);
+            }
+            Ok(size_hmap)
+        }
+    }
+}
+
+#[cfg(feature = "sizehmap-persist")]
+pub use persist::SizeHashMapPersistError;
+
 #[cfg(test)]
 mod tests {
 
@@ -201,4 +262,22 @@ mod tests {
             .join(",");
         println!("{}, {:?}", str, map.key_vec);
     }
+
+    #[cfg(feature = "sizehmap-persist")]
+    #[test]
+    fn test_save_load() {
+        let path = std::env::temp_dir().join("common-rs-sizehmap.bin");
+        let mut map = SizeHashMap::with_capacity(3);
+        for i in 0..5 {
+            map.insert(format!("k{}", i), i);
+        }
+        map.save(&path).unwrap();
+
+        let loaded = SizeHashMap::<String, i32>::load(&path).unwrap();
+        println!("{:?}", loaded);
+        assert_eq!(loaded.keys(), map.keys());
+        assert_eq!(loaded.last(), Some(&4));
+        assert_eq!(loaded.capacity, 3);
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,92 @@
+//! 库名, 表名, 列名的校验和引用, 避免配置中的名称拼接到SQL中造成注入
+
+/// MySQL标识符的最大长度
+const MAX_IDENT_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum IdentError {
+    #[error("identifier is empty")]
+    Empty,
+    #[error("identifier too long: {0}")]
+    TooLong(String),
+    #[error("invalid identifier: {0}")]
+    Invalid(String),
+}
+
+#[cfg(feature = "mysqlx")]
+impl From<IdentError> for sqlx::Error {
+    fn from(value: IdentError) -> Self {
+        sqlx::Error::Configuration(Box::new(value))
+    }
+}
+
+/// 只允许字母, 数字, _, $
+pub fn validate_ident(name: &str) -> Result<&str, IdentError> {
+    if name.is_empty() {
+        return Err(IdentError::Empty);
+    }
+    if name.len() > MAX_IDENT_LEN {
+        return Err(IdentError::TooLong(name.to_owned()));
+    }
+    if !name
+        .bytes()
+        .all(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'$')
+    {
+        return Err(IdentError::Invalid(name.to_owned()));
+    }
+    Ok(name)
+}
+
+/// `db`.`table`, db为空时为`table`
+pub fn ident(db: &str, table: &str) -> Result<String, IdentError> {
+    let table = validate_ident(table)?;
+    if db.is_empty() {
+        Ok(format!("`{}`", table))
+    } else {
+        Ok(format!("`{}`.`{}`", validate_ident(db)?, table))
+    }
+}
+
+/// `column`
+pub fn column(name: &str) -> Result<String, IdentError> {
+    Ok(format!("`{}`", validate_ident(name)?))
+}
+
+/// `db.tbl`或`tbl`, 校验后转为`` `db`.`tbl` ``
+pub fn table_ident(table: &str) -> Result<String, IdentError> {
+    match table.split_once('.') {
+        Some((db, tbl)) => ident(db, tbl),
+        None => ident("", table),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{column, ident, table_ident, IdentError};
+
+    #[test]
+    fn test_ident() {
+        assert_eq!(
+            ident("hqdb", "tbl_code_agL9").unwrap(),
+            "`hqdb`.`tbl_code_agL9`"
+        );
+        assert_eq!(ident("", "tbl").unwrap(), "`tbl`");
+        assert_eq!(column("TotalVolume").unwrap(), "`TotalVolume`");
+        assert_eq!(ident("hqdb", ""), Err(IdentError::Empty));
+        assert!(matches!(
+            ident("hqdb", "tbl`; DROP TABLE x; --"),
+            Err(IdentError::Invalid(_))
+        ));
+        assert!(matches!(ident("hq-db", "tbl"), Err(IdentError::Invalid(_))));
+        assert!(matches!(
+            column(&"a".repeat(65)),
+            Err(IdentError::TooLong(_))
+        ));
+        assert_eq!(table_ident("hqdb.tbl").unwrap(), "`hqdb`.`tbl`");
+        assert_eq!(table_ident("tbl").unwrap(), "`tbl`");
+        assert!(matches!(
+            table_ident("`tbl` WHERE 1=1 OR `a`"),
+            Err(IdentError::Invalid(_))
+        ));
+    }
+}
@@ -10,6 +10,7 @@ use itertools::Itertools;
 use serde::Deserialize;
 
 use crate::serde_extend::string::opt_str;
+use crate::sql_ident::{column, ident};
 use crate::{toml, AResult};
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -43,15 +44,23 @@ struct LoadDataInfile {
 }
 
 impl LoadDataInfile {
-    fn field_map(v: &str) -> Cow<'_, str> {
-        if v.starts_with('@') {
-            Cow::Borrowed(v)
+    fn field_map(v: &str) -> AResult<Cow<'_, str>> {
+        if let Some(var) = v.strip_prefix('@') {
+            ident("", var)?;
+            Ok(Cow::Borrowed(v))
         } else {
-            Cow::Owned(format!("`{}`", v.replace('-', "_")))
+            Ok(Cow::Owned(column(&v.replace('-', "_"))?))
         }
     }
 
-    fn sql(&self, ldi_file: &str, database: &str, tbl_name: &str) -> AResult<String> {
+    /// format不为None时文件的列和转义, 行结束符按format, 不使用配置中的col-*和ignore-rows
+    fn sql(
+        &self,
+        ldi_file: &str,
+        database: &str,
+        tbl_name: &str,
+        format: Option<&LdiFormat>,
+    ) -> AResult<String> {
         let database = database.replace('-', "_");
         let tbl_name = tbl_name.replace('-', "_");
         let mut s = String::new();
@@ -59,18 +68,34 @@ impl LoadDataInfile {
         if self.is_local {
             writeln!(s, "  LOCAL")?;
         }
-        writeln!(s, "  INFILE '{}'", ldi_file)?;
+        writeln!(s, "  INFILE '{}'", escape_sql_str(ldi_file))?;
         writeln!(s, "  REPLACE")?;
-        writeln!(s, "  INTO TABLE `{}`.`{}`", database, tbl_name)?;
+        writeln!(s, "  INTO TABLE {}", ident(&database, &tbl_name)?)?;
         writeln!(s, "  COLUMNS")?;
         let fields_terminated = if let Some(fields_terminated) = self.columns_terminated.as_ref() {
             fields_terminated.as_str()
         } else {
             ","
         };
-        writeln!(s, "    TERMINATED BY '{}'", fields_terminated)?;
+        writeln!(s, "    TERMINATED BY '{}'", escape_sql_str(fields_terminated))?;
+        if let Some(format) = format {
+            let escaped_by = format
+                .escaped_by
+                .map(|v| escape_sql_str(&v.to_string()))
+                .unwrap_or_default();
+            writeln!(s, "    ESCAPED BY '{}'", escaped_by)?;
+            writeln!(s, "  LINES")?;
+            writeln!(
+                s,
+                "    TERMINATED BY '{}'",
+                escape_sql_str(&format.lines_terminated)
+            )?;
+        }
 
-        let ignore_rows = self.ignore_rows.unwrap_or_default();
+        let ignore_rows = match format {
+            Some(_) => 0,
+            None => self.ignore_rows.unwrap_or_default(),
+        };
 
         writeln!(s, "  IGNORE {} ROWS", ignore_rows)?;
 
@@ -79,19 +104,31 @@ impl LoadDataInfile {
             .iter()
             .filter(|(v, _)| v.starts_with("col-"))
             .collect::<IndexMap<_, _>>();
-        let fields_str = if let Some(column_count) = self.file_column_count {
+        let fields_str = if let Some(format) = format {
+            format
+                .columns
+                .iter()
+                .map(|v| Ok(column(v)?))
+                .collect::<AResult<Vec<_>>>()?
+                .join(",")
+        } else if let Some(column_count) = self.file_column_count {
             let dummy = String::from("@dummy");
             let mut fields = vec![];
             for idx in 0..column_count {
                 let field = col_map
                     .get(&format!("col-{}", idx))
                     .map(|&v| Self::field_map(v))
+                    .transpose()?
                     .unwrap_or(Cow::Borrowed(&dummy));
                 fields.push(field)
             }
             fields.iter().map(|v| v.as_ref()).join(",")
         } else {
-            col_map.values().map(|&v| Self::field_map(v)).join(",")
+            col_map
+                .values()
+                .map(|&v| Self::field_map(v))
+                .collect::<AResult<Vec<_>>>()?
+                .join(",")
         };
         write!(s, "  ({})", fields_str)?;
 
@@ -99,10 +136,8 @@ impl LoadDataInfile {
             .col_set_map
             .iter()
             .filter(|(v, _)| v.starts_with("set-"))
-            .map(|(k, v)| {
-                let field = &k[4..].replace('-', "_");
-                format!("`{}` = {}", field, v)
-            })
+            .map(|(k, v)| Ok(format!("{} = {}", column(&k[4..].replace('-', "_"))?, v)))
+            .collect::<AResult<Vec<_>>>()?
             .join(",\n    ");
         if !set_map_str.is_empty() {
             writeln!(s)?;
@@ -115,6 +150,30 @@ impl LoadDataInfile {
     }
 }
 
+/// 文件的格式, 由程序生成的文件用这里的格式代替LOAD DATA配置中的列映射, 见`SqlLoader::load_data_infile_with`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LdiFormat {
+    /// 文件中每一列对应的表的列名
+    pub columns:          Vec<String>,
+    /// 转义字符, None时不转义(ESCAPED BY '')
+    pub escaped_by:       Option<char>,
+    pub lines_terminated: String,
+}
+
+/// 单引号字符串的转义, 只用于不能绑定参数的语句, 如LOAD DATA, CREATE TABLE中的DEFAULT和COMMENT
+fn escape_sql_str(v: &str) -> String {
+    v.chars()
+        .map(|c| match c {
+            '\\' => "\\\\".to_owned(),
+            '\'' => "\\'".to_owned(),
+            '\n' => "\\n".to_owned(),
+            '\r' => "\\r".to_owned(),
+            '\t' => "\\t".to_owned(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone, Deserialize)]
 struct Database {
     #[serde(rename = "name")]
@@ -130,7 +189,11 @@ impl Database {
         let mut content = String::new();
         // CREATE DATABASE IF NOT EXISTS `{db_name}` DEFAULT CHARACTER SET {charset} DEFAULT COLLATE {collation};
         let db_name = self.name.replace('-', "_");
-        write!(content, "CREATE DATABASE IF NOT EXISTS `{}`", db_name)?;
+        write!(
+            content,
+            "CREATE DATABASE IF NOT EXISTS {}",
+            ident("", &db_name)?
+        )?;
         if let Some(charset) = &self.charset {
             write!(content, " DEFAULT CHARACTER SET {}", charset)?;
         }
@@ -155,6 +218,8 @@ struct Table {
     // #[serde(rename = "tbl-index", default, with = "vec_vec_str")]
     #[serde(rename = "tbl-index", default)]
     index:       Vec<Vec<String>>,
+    #[serde(rename = "tbl-comment", default, with = "opt_str")]
+    comment:     Option<String>,
     #[serde(flatten)]
     field:       IndexMap<String, Field>,
 }
@@ -183,6 +248,13 @@ impl Table {
                 }
             }
         }
+        for (name, field) in self.field.iter() {
+            for v in [&field.charset, &field.collation].into_iter().flatten() {
+                if !is_charset_name(v) {
+                    Err(eyre!("error charset/collation of {}: {}", name, v))?;
+                }
+            }
+        }
         Ok(())
     }
 
@@ -212,13 +284,13 @@ impl Table {
         let mut content = String::new();
         writeln!(
             content,
-            "CREATE TABLE IF NOT EXISTS `{}`.`{}` (",
-            db_name, tbl_name
+            "CREATE TABLE IF NOT EXISTS {} (",
+            ident(&db_name, &tbl_name)?
         )?;
         let is_exist_p_key = !self.private_key.is_empty();
         let is_exist_index = !self.index.is_empty();
         for (idx, (name, field)) in self.field.iter().enumerate() {
-            let field = field.with_name(name).unwrap();
+            let field = field.with_name(name)?;
             let suffix = if idx != self.field.len() - 1 || is_exist_p_key || is_exist_index {
                 ","
             } else {
@@ -230,7 +302,8 @@ impl Table {
             let p_key = self
                 .private_key
                 .iter()
-                .map(|v| format!("`{}`", v.replace('-', "_")))
+                .map(|v| column(&v.replace('-', "_")))
+                .collect::<Result<Vec<_>, _>>()?
                 .join(",");
             let suffix = if is_exist_index { "," } else { "" };
             writeln!(content, "  PRIMARY KEY({}){}", p_key, suffix)?;
@@ -239,13 +312,18 @@ impl Table {
             for (idx, index) in self.index.iter().enumerate() {
                 let index = index
                     .iter()
-                    .map(|v| format!("`{}`", v.replace('-', "_")))
+                    .map(|v| column(&v.replace('-', "_")))
+                    .collect::<Result<Vec<_>, _>>()?
                     .join(",");
                 let suffix = if idx == self.index.len() - 1 { "" } else { "," };
                 writeln!(content, "  INDEX({}){}", index, suffix)?;
             }
         }
-        write!(content, ") ENGINE=INNODB DEFAULT CHARSET=utf8;")?;
+        write!(content, ") ENGINE=INNODB DEFAULT CHARSET=utf8")?;
+        if let Some(comment) = &self.comment {
+            write!(content, " COMMENT='{}'", escape_sql_str(comment))?;
+        }
+        write!(content, ";")?;
 
         Ok(content)
     }
@@ -263,20 +341,35 @@ struct Field {
     on_update:  Option<String>,
     #[serde(rename = "comment", default, with = "opt_str")]
     comment:    Option<String>,
+    #[serde(rename = "charset", default, with = "opt_str")]
+    charset:    Option<String>,
+    #[serde(rename = "collation", default, with = "opt_str")]
+    collation:  Option<String>,
+}
+
+/// 字符集和排序规则名称只能是字母数字下划线, 如utf8mb4, utf8mb4_general_ci
+fn is_charset_name(v: &str) -> bool {
+    !v.is_empty() && v.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
 impl Field {
     fn with_name(&self, name: &str) -> AResult<String> {
         let mut content = String::new();
-        let name = name.replace('-', "_");
+        let name = column(&name.replace('-', "_"))?;
         let field_type = self.field_type.to_uppercase();
-        write!(content, "`{}` {}", name, field_type)?;
+        write!(content, "{} {}", name, field_type)?;
+        if let Some(charset) = &self.charset {
+            write!(content, " CHARACTER SET {}", charset)?;
+        }
+        if let Some(collation) = &self.collation {
+            write!(content, " COLLATE {}", collation)?;
+        }
         if self.not_null {
             write!(content, " NOT NULL")?;
         }
         if let Some(default) = &self.default {
             if field_type.contains("CHAR") || field_type.contains("VARCHAR") {
-                write!(content, " DEFAULT '{}'", default)?;
+                write!(content, " DEFAULT '{}'", escape_sql_str(default))?;
             } else {
                 write!(content, " DEFAULT {}", default)?;
             }
@@ -285,7 +378,7 @@ impl Field {
             write!(content, " ON UPDATE {}", on_update)?;
         }
         if let Some(comment) = &self.comment {
-            write!(content, " COMMENT '{}'", comment)?;
+            write!(content, " COMMENT '{}'", escape_sql_str(comment))?;
         }
 
         Ok(content)
@@ -377,6 +470,23 @@ impl SqlLoader {
         SQL_LOADER.get().unwrap()
     }
 
+    /// 未初始化时为None
+    pub fn try_get<'a>() -> Option<&'a SqlLoader> {
+        SQL_LOADER.get()
+    }
+
+    /// 非模板表的(库名, 表名), 与建表语句中的名称一致, 没有设置库的表不包含在内
+    pub fn table_names(&self) -> Vec<(String, String)> {
+        self.table
+            .iter()
+            .filter(|v| !v.is_template)
+            .filter_map(|v| {
+                let db = v.database.as_ref()?.replace('-', "_");
+                Some((db, v.name.replace('-', "_")))
+            })
+            .collect()
+    }
+
     pub fn database_create_sql_vec(&self) -> Vec<String> {
         let mut sql_vec = vec![];
         for db in self.database.iter() {
@@ -435,9 +545,34 @@ impl SqlLoader {
             .get(ldi_name)
             .ok_or_eyre(format!("error load data infile name: {}", ldi_name))?;
 
-        let sql = ldi.sql(ldi_file, database, tbl_name)?;
+        let sql = ldi.sql(ldi_file, database, tbl_name, None)?;
         Ok(sql)
     }
+
+    /// 同`load_data_infile`, 文件的列, 转义字符和行结束符按format, 配置中的LOCAL, 列分隔符和SET不变
+    pub fn load_data_infile_with(
+        &self,
+        ldi_name: &str,
+        ldi_file: &str,
+        database: &str,
+        tbl_name: &str,
+        format: &LdiFormat,
+    ) -> AResult<String> {
+        let ldi = self
+            .ldi_hamp
+            .get(ldi_name)
+            .ok_or_eyre(format!("error load data infile name: {}", ldi_name))?;
+        ldi.sql(ldi_file, database, tbl_name, Some(format))
+    }
+
+    /// LOAD DATA配置的列分隔符, 默认为`,`
+    pub fn load_data_infile_terminated(&self, ldi_name: &str) -> AResult<&str> {
+        let ldi = self
+            .ldi_hamp
+            .get(ldi_name)
+            .ok_or_eyre(format!("error load data infile name: {}", ldi_name))?;
+        Ok(ldi.columns_terminated.as_deref().unwrap_or(","))
+    }
 }
 
 #[cfg(test)]
@@ -446,7 +581,7 @@ mod tests {
 
     use indexmap::IndexMap;
 
-    use super::{Field, SqlLoader};
+    use super::{Field, LdiFormat, LoadDataInfile, SqlLoader, Table};
 
     #[test]
     fn test_field() {
@@ -456,10 +591,44 @@ mod tests {
             default:    Some("".into()),
             on_update:  None,
             comment:    Some("这是一个测试".into()),
+            charset:    None,
+            collation:  None,
         };
         println!("{:?}", field_info.with_name("bbb-bbb"))
     }
 
+    #[test]
+    fn test_comment_charset() {
+        let tbl = ::toml::from_str::<Table>(
+            r#"
+tbl-database = "tmp"
+tbl-name = "tbl-note"
+tbl-private-key = ["id"]
+tbl-comment = "策略's备注"
+id = { type = "int", not-null = true }
+note = { type = "VARCHAR(200)", charset = "utf8mb4", collation = "utf8mb4_general_ci", comment = "备注" }
+"#,
+        )
+        .unwrap();
+        tbl.vaildate().unwrap();
+        assert_eq!(
+            tbl.sql(None, None).unwrap(),
+            "CREATE TABLE IF NOT EXISTS `tmp`.`tbl_note` (\n  `id` INT NOT NULL,\n  `note` \
+             VARCHAR(200) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci COMMENT '备注',\n  \
+             PRIMARY KEY(`id`)\n) ENGINE=INNODB DEFAULT CHARSET=utf8 COMMENT='策略\\'s备注';"
+        );
+
+        let tbl = ::toml::from_str::<Table>(
+            r#"
+tbl-name = "tbl-note"
+tbl-private-key = []
+note = { type = "VARCHAR(200)", charset = "utf8mb4; DROP" }
+"#,
+        )
+        .unwrap();
+        assert!(tbl.vaildate().is_err());
+    }
+
     #[test]
     fn test2() {
         let ddl_info = SqlLoader::load("./_data/db-sql.toml");
@@ -529,6 +698,40 @@ mod tests {
         println!("{}", sql)
     }
 
+    #[test]
+    fn test_ldi_format() {
+        let ldi = ::toml::from_str::<LoadDataInfile>(
+            r#"
+ldi-name = "ldi-stage"
+ldi-local = true
+ldi-ignore-rows = 1
+col-0 = "code"
+set-update-time = "NOW()"
+"#,
+        )
+        .unwrap();
+        let format = LdiFormat {
+            columns:          vec!["code".to_owned(), "trade_day".to_owned()],
+            escaped_by:       Some('\\'),
+            lines_terminated: "\r\n".to_owned(),
+        };
+        let sql = ldi.sql("a.csv", "db", "tbl", Some(&format)).unwrap();
+        assert_eq!(
+            sql,
+            "LOAD DATA\n  LOCAL\n  INFILE 'a.csv'\n  REPLACE\n  INTO TABLE `db`.`tbl`\n  COLUMNS\n    \
+             TERMINATED BY ','\n    ESCAPED BY '\\\\'\n  LINES\n    TERMINATED BY '\\r\\n'\n  IGNORE 0 \
+             ROWS\n  (`code`,`trade_day`)\n  SET\n    `update_time` = NOW();"
+        );
+        let format = LdiFormat {
+            escaped_by: None,
+            ..format
+        };
+        let sql = ldi.sql("a.csv", "db", "tbl", Some(&format)).unwrap();
+        assert!(sql.contains("ESCAPED BY ''"), "{}", sql);
+        let sql = ldi.sql("a.csv", "db", "tbl", None).unwrap();
+        assert!(sql.contains("IGNORE 1 ROWS\n  (`code`)"), "{}", sql);
+    }
+
     #[test]
     fn test1() {
         let solar_distance = BTreeMap::from([
@@ -1,10 +1,24 @@
 use std::time::Duration;
 
+#[cfg(feature = "hq")]
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
 use futures_util::Future;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::error::SendError;
 use tokio::time::Instant;
 
+pub use self::backoff::{Backoff, Jitter};
+#[cfg(feature = "hq")]
+pub use self::countdown::{countdown_to_close, countdown_to_open, Countdown};
+pub use self::steady::{steady_interval, ClockStep, SteadyInterval};
+#[cfg(feature = "hq")]
+use crate::hq::future::time_range::{time_range_by_group, TimeRangeError};
+
+pub mod backoff;
+#[cfg(feature = "hq")]
+pub mod countdown;
+pub mod steady;
+
 #[derive(Debug)]
 pub struct Timer {
     // stop_tx:  Option<oneshot::Sender<u8>>,
@@ -68,6 +82,88 @@ impl Timer {
     }
 }
 
+/// 按交易日重复执行的定时器, Drop或stop后停止
+#[derive(Debug)]
+pub struct TradingDayTimer {
+    stop_tx: mpsc::Sender<()>,
+}
+
+impl TradingDayTimer {
+    pub async fn stop(&mut self) {
+        if let Err(err) = self.stop_tx.send(()).await {
+            println!("#: TradingDayTimer stop err: {}", err);
+        }
+    }
+}
+
+/// 等待收市时最长的等待时间, 之后重新计算, 等待期间设置的提前收盘也能生效
+#[cfg(feature = "hq")]
+const CLOSE_RECHECK: Duration = Duration::from_secs(60);
+
+/// 每个交易日收市后offset执行一次f, 参数为交易日
+///
+/// group: 交易时间段组, 见`time_range::groups`, 组内品种的收市时间相同(15:00或15:15).
+/// 收市时间按每个交易日实际的交易时间段计算, 提前收盘见`trade_day::set_early_close`, 非交易日不执行
+#[cfg(feature = "hq")]
+pub fn at_trading_day_close<F, Fut>(
+    group: &str,
+    offset: Duration,
+    mut f: F,
+) -> Result<TradingDayTimer, TimeRangeError>
+where
+    F: FnMut(NaiveDate) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let time_range = time_range_by_group(group)?;
+    let offset = chrono::Duration::from_std(offset).unwrap_or_default();
+    let (stop_tx, mut stop_rx) = mpsc::channel::<()>(1);
+    tokio::spawn(async move {
+        let close_time = |day: &NaiveDate| {
+            if !time_range.calendar().is_trade_day(day) {
+                return None;
+            }
+            time_range.session_times(day).last().map(|v| v.1)
+        };
+        loop {
+            let now = Local::now().naive_local();
+            let Some(run_at) = next_close_run(&now, offset, close_time) else {
+                println!("#: TradingDayTimer no trading day after {}", now);
+                break;
+            };
+            let wait = (run_at - now).to_std().unwrap_or_default();
+            tokio::select! {
+                () = tokio::time::sleep(wait.min(CLOSE_RECHECK)) => {
+                    if wait <= CLOSE_RECHECK {
+                        f(run_at.date()).await;
+                    }
+                }
+                _ = stop_rx.recv() => {
+                    break;
+                }
+            }
+        }
+    });
+    Ok(TradingDayTimer { stop_tx })
+}
+
+/// now之后第一个交易日收市时间加offset的时间, close_time为非交易日时返回None,
+/// 一年内没有交易日时返回None
+#[cfg(feature = "hq")]
+fn next_close_run<F>(
+    now: &NaiveDateTime,
+    offset: chrono::Duration,
+    close_time: F,
+) -> Option<NaiveDateTime>
+where
+    F: Fn(&NaiveDate) -> Option<NaiveTime>,
+{
+    now.date()
+        .iter_days()
+        .take(366)
+        .filter_map(|day| close_time(&day).map(|v| day.and_time(v) + offset))
+        .find(|run_at| run_at > now)
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -147,6 +243,47 @@ mod tests {
         a.stop().await;
     }
 
+    #[cfg(feature = "hq")]
+    #[test]
+    fn test_next_close_run() {
+        use chrono::{Datelike, NaiveDate, NaiveTime, Weekday};
+
+        use super::next_close_run;
+
+        let close = NaiveTime::from_hms_opt(15, 15, 0).unwrap();
+        // 6月10日假期, 6月4日13:00提前收盘
+        let close_time = |day: &NaiveDate| {
+            if matches!(day.weekday(), Weekday::Sat | Weekday::Sun)
+                || *day == NaiveDate::from_ymd_opt(2024, 6, 10).unwrap()
+            {
+                None
+            } else if *day == NaiveDate::from_ymd_opt(2024, 6, 4).unwrap() {
+                NaiveTime::from_hms_opt(13, 0, 0)
+            } else {
+                Some(close)
+            }
+        };
+        let offset = chrono::Duration::try_minutes(30).unwrap();
+        let dt = |d: u32, h: u32, m: u32| {
+            NaiveDate::from_ymd_opt(2024, 6, d)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+        // 交易日收市前
+        let r = next_close_run(&dt(3, 14, 0), offset, close_time);
+        assert_eq!(r, Some(dt(3, 15, 45)));
+        // 执行后到下一个交易日, 按当天提前收盘的时间
+        let r = next_close_run(&dt(3, 15, 45), offset, close_time);
+        assert_eq!(r, Some(dt(4, 13, 30)));
+        let r = next_close_run(&dt(4, 13, 30), offset, close_time);
+        assert_eq!(r, Some(dt(5, 15, 45)));
+        // 周五之后跳过周末和6月10日假期
+        let r = next_close_run(&dt(7, 16, 0), offset, close_time);
+        assert_eq!(r, Some(dt(11, 15, 45)));
+        assert_eq!(next_close_run(&dt(7, 16, 0), offset, |_| None), None);
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn test_timer_in_hashmap() {
         println!("######################");
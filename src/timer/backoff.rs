@@ -0,0 +1,197 @@
+//! 重试的等待时间: 指数增长, 可选随机抖动, 有上限和最大次数
+//!
+//! ```toml
+//! [retry]
+//! initial_ms = 100
+//! max_ms = 30000
+//! multiplier = 2.0
+//! jitter = "decorrelated"
+//! max_attempts = 5
+//! ```
+use std::time::Duration;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// 随机抖动, 避免多个客户端同时重试
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Jitter {
+    /// 不抖动
+    #[default]
+    None,
+    /// 0 ~ 指数增长的值
+    Full,
+    /// initial ~ 上一次的3倍
+    Decorrelated,
+}
+
+/// 迭代返回每次重试前的等待时间, 达到最大次数后返回None
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Backoff {
+    initial_ms:   u64,
+    max_ms:       u64,
+    multiplier:   f64,
+    jitter:       Jitter,
+    /// None时不限制次数
+    max_attempts: Option<usize>,
+    #[serde(skip)]
+    attempt:      usize,
+    #[serde(skip)]
+    prev_ms:      u64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial_ms:   100,
+            max_ms:       30000,
+            multiplier:   2.0,
+            jitter:       Jitter::None,
+            max_attempts: None,
+            attempt:      0,
+            prev_ms:      0,
+        }
+    }
+}
+
+impl Backoff {
+    /// 指数增长, 每次乘以2
+    pub fn exponential(initial: Duration) -> Backoff {
+        Backoff {
+            initial_ms: initial.as_millis() as u64,
+            ..Default::default()
+        }
+    }
+
+    /// 固定间隔
+    pub fn constant(delay: Duration) -> Backoff {
+        Backoff {
+            initial_ms: delay.as_millis() as u64,
+            max_ms: delay.as_millis() as u64,
+            multiplier: 1.0,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_max(self, max: Duration) -> Self {
+        Backoff {
+            max_ms: max.as_millis() as u64,
+            ..self
+        }
+    }
+
+    pub fn with_multiplier(self, multiplier: f64) -> Self {
+        Backoff {
+            multiplier: multiplier.max(1.0),
+            ..self
+        }
+    }
+
+    pub fn with_jitter(self, jitter: Jitter) -> Self {
+        Backoff { jitter, ..self }
+    }
+
+    pub fn with_max_attempts(self, max_attempts: usize) -> Self {
+        Backoff {
+            max_attempts: Some(max_attempts),
+            ..self
+        }
+    }
+
+    pub fn max_attempts(&self) -> Option<usize> {
+        self.max_attempts
+    }
+
+    /// 已经返回的次数
+    pub fn attempt(&self) -> usize {
+        self.attempt
+    }
+
+    /// 重新开始计数, 如连接成功后
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+        self.prev_ms = 0;
+    }
+
+    fn exponential_ms(&self) -> u64 {
+        let ms = self.initial_ms as f64 * self.multiplier.powi(self.attempt as i32);
+        (ms.min(self.max_ms as f64) as u64).max(self.initial_ms.min(self.max_ms))
+    }
+}
+
+impl Iterator for Backoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        if self.max_attempts.is_some_and(|v| self.attempt >= v) {
+            return None;
+        }
+        let ms = match self.jitter {
+            Jitter::None => self.exponential_ms(),
+            Jitter::Full => rand::thread_rng().gen_range(0..=self.exponential_ms()),
+            Jitter::Decorrelated => {
+                let upper = self.prev_ms.max(self.initial_ms).saturating_mul(3);
+                rand::thread_rng()
+                    .gen_range(self.initial_ms..=upper.max(self.initial_ms))
+                    .min(self.max_ms)
+            },
+        };
+        self.attempt += 1;
+        self.prev_ms = ms;
+        Some(Duration::from_millis(ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Backoff, Jitter};
+
+    #[test]
+    fn test_exponential() {
+        let delays = Backoff::exponential(Duration::from_millis(100))
+            .with_max(Duration::from_millis(500))
+            .with_max_attempts(5)
+            .map(|v| v.as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [100, 200, 400, 500, 500]);
+
+        let mut backoff = Backoff::constant(Duration::from_secs(1)).with_max_attempts(2);
+        assert_eq!(backoff.by_ref().count(), 2);
+        backoff.reset();
+        assert_eq!(backoff.next(), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_jitter() {
+        let backoff = Backoff::exponential(Duration::from_millis(100))
+            .with_max(Duration::from_millis(1000))
+            .with_max_attempts(20);
+        for delay in backoff.clone().with_jitter(Jitter::Full) {
+            assert!(delay <= Duration::from_millis(1000));
+        }
+        for delay in backoff.with_jitter(Jitter::Decorrelated) {
+            assert!(delay >= Duration::from_millis(100));
+            assert!(delay <= Duration::from_millis(1000));
+        }
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_serde() {
+        let backoff = toml::from_str::<Backoff>(
+            "initial_ms = 50\nmax_ms = 200\njitter = \"full\"\nmax_attempts = 3",
+        )
+        .unwrap();
+        assert_eq!(
+            backoff,
+            Backoff::exponential(Duration::from_millis(50))
+                .with_max(Duration::from_millis(200))
+                .with_jitter(Jitter::Full)
+                .with_max_attempts(3)
+        );
+    }
+}
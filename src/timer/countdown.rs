@@ -0,0 +1,141 @@
+//! 距离收盘/开盘的倒计时, 按交易日历(节假日, 取消夜盘, 提前收盘)计算, 用于状态栏, 监控页面
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, NaiveTime};
+
+use crate::hq::future::time_range::{time_range_by_breed, TimeRange, TimeRangeError};
+
+/// 向前后查找交易时间段的自然日数, 覆盖春节等长假
+const SEARCH_DAYS: usize = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Countdown {
+    /// 收盘或开盘的时间
+    pub target:     NaiveDateTime,
+    /// 距离target的时间
+    pub remaining:  Duration,
+    /// now是否在交易时间段内
+    pub in_session: bool,
+}
+
+/// now所在交易时间段的收盘时间(如10:15, 11:30, 15:00), 不在交易时间段内时为下一个时间段的收盘时间
+///
+/// 查找范围内没有交易日时为None
+pub fn countdown_to_close(
+    breed: &str,
+    now: &NaiveDateTime,
+) -> Result<Option<Countdown>, TimeRangeError> {
+    let time_range = time_range_by_breed(breed)?;
+    Ok(countdown(&sessions(&time_range, now), now, true))
+}
+
+/// 下一个交易时间段的开盘时间, 在交易时间段内时为当前时间段之后的开盘时间
+///
+/// 查找范围内没有交易日时为None
+pub fn countdown_to_open(
+    breed: &str,
+    now: &NaiveDateTime,
+) -> Result<Option<Countdown>, TimeRangeError> {
+    let time_range = time_range_by_breed(breed)?;
+    Ok(countdown(&sessions(&time_range, now), now, false))
+}
+
+/// now前后的实际交易时间段(开盘, 收盘), 按时间排序
+fn sessions(time_range: &TimeRange, now: &NaiveDateTime) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let night_start = NaiveTime::from_hms_opt(18, 0, 0).unwrap();
+    let start = now.date() - chrono::Duration::days(5);
+    let calendar = time_range.calendar();
+    let mut sessions = start
+        .iter_days()
+        .take(SEARCH_DAYS)
+        .filter(|v| calendar.is_trade_day(v))
+        .flat_map(|trade_date| {
+            let night_day = calendar.prev_trade_day(&trade_date);
+            time_range
+                .session_times(&trade_date)
+                .into_iter()
+                .filter_map(move |(open_time, close_time)| {
+                    let day = if open_time >= night_start {
+                        night_day?
+                    } else {
+                        trade_date
+                    };
+                    let close_day = if close_time < open_time {
+                        day.succ_opt()?
+                    } else {
+                        day
+                    };
+                    Some((day.and_time(open_time), close_day.and_time(close_time)))
+                })
+        })
+        .collect::<Vec<_>>();
+    sessions.sort();
+    sessions
+}
+
+fn countdown(
+    sessions: &[(NaiveDateTime, NaiveDateTime)],
+    now: &NaiveDateTime,
+    to_close: bool,
+) -> Option<Countdown> {
+    let idx = sessions.iter().position(|(_, close)| close > now)?;
+    let (open, close) = sessions[idx];
+    let in_session = open <= *now;
+    let target = match (to_close, in_session) {
+        (true, _) => close,
+        (false, false) => open,
+        (false, true) => sessions.get(idx + 1)?.0,
+    };
+    Some(Countdown {
+        target,
+        remaining: (target - *now).to_std().unwrap_or_default(),
+        in_session,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use super::countdown;
+
+    #[test]
+    fn test_countdown() {
+        let dt = |d: u32, h: u32, m: u32| {
+            NaiveDate::from_ymd_opt(2024, 6, d)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+        // 6月7日(周五)夜盘取消, 6月10日假期
+        let sessions = [
+            (dt(7, 9, 0), dt(7, 10, 15)),
+            (dt(7, 10, 30), dt(7, 11, 30)),
+            (dt(7, 13, 30), dt(7, 15, 0)),
+            (dt(11, 9, 0), dt(11, 10, 15)),
+        ];
+        let r = countdown(&sessions, &dt(7, 10, 0), true).unwrap();
+        assert_eq!(r.target, dt(7, 10, 15));
+        assert_eq!(r.remaining, Duration::from_secs(15 * 60));
+        assert!(r.in_session);
+
+        let r = countdown(&sessions, &dt(7, 10, 0), false).unwrap();
+        assert_eq!(r.target, dt(7, 10, 30));
+
+        // 休市中
+        let r = countdown(&sessions, &dt(7, 10, 15), false).unwrap();
+        assert_eq!((r.target, r.in_session), (dt(7, 10, 30), false));
+        let r = countdown(&sessions, &dt(7, 10, 15), true).unwrap();
+        assert_eq!(r.target, dt(7, 11, 30));
+
+        // 收盘后跳过周末和假期
+        let r = countdown(&sessions, &dt(7, 15, 0), false).unwrap();
+        assert_eq!(r.target, dt(11, 9, 0));
+        assert_eq!(r.remaining, Duration::from_secs((3 * 24 + 18) * 3600));
+
+        assert!(countdown(&sessions, &dt(11, 9, 30), false).is_none());
+        assert!(countdown(&sessions, &dt(11, 10, 15), true).is_none());
+    }
+}
@@ -0,0 +1,350 @@
+//! 不受系统时间调整影响的定时间隔
+//!
+//! 运行环境没有夏令时, 但NTP会直接跳变系统时间. 按系统时间`sleep until`的循环在时间往前跳时会跳过,
+//! 往回跳时会重复执行. `SteadyInterval`的间隔用tokio的单调时钟计算, 系统时间跳变不影响间隔;
+//! 需要和K线对齐时开启`with_wall_align`, 每次触发对齐到系统时间(默认+8时区)的period整数倍,
+//! 检测到跳变后调用`on_clock_step`并重新对齐, 对齐后的时间不会早于上一次触发的时间, 不会重复触发;
+//! 往回跳后系统时间还没到下一个边界时, 等到系统时间到达边界后才触发
+use std::fmt::Debug;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
+
+/// 检测到的系统时间跳变
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockStep {
+    /// 两次触发间单调时钟经过的时间
+    pub monotonic: Duration,
+    /// 系统时间比单调时钟多走的毫秒数, 负数为往回跳
+    pub offset_ms: i64,
+}
+
+type StepHook = Box<dyn FnMut(ClockStep) + Send>;
+
+/// 默认按交易所的+8时区对齐
+const DEFAULT_UTC_OFFSET_SECS: i32 = 8 * 3600;
+
+pub struct SteadyInterval {
+    interval:       Interval,
+    period:         Duration,
+    wall_align:     bool,
+    /// 对齐使用的时区, 和UTC相差的秒数
+    utc_offset:     i32,
+    step_threshold: Duration,
+    on_step:        Option<StepHook>,
+    /// 上一次触发的单调时间和系统时间
+    last:           Option<(Instant, SystemTime)>,
+    /// 对齐时上一次触发对应的系统时间边界
+    last_boundary:  Option<SystemTime>,
+}
+
+impl Debug for SteadyInterval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SteadyInterval")
+            .field("period", &self.period)
+            .field("wall_align", &self.wall_align)
+            .field("utc_offset", &self.utc_offset)
+            .field("step_threshold", &self.step_threshold)
+            .field("last_boundary", &self.last_boundary)
+            .finish()
+    }
+}
+
+/// 每隔period触发一次, 第一次立即触发, 错过的触发直接跳过, 不会补发
+///
+/// period为0时panic
+pub fn steady_interval(period: Duration) -> SteadyInterval {
+    let mut interval = time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    SteadyInterval {
+        interval,
+        period,
+        wall_align: false,
+        utc_offset: DEFAULT_UTC_OFFSET_SECS,
+        step_threshold: Duration::from_secs(1),
+        on_step: None,
+        last: None,
+        last_boundary: None,
+    }
+}
+
+impl SteadyInterval {
+    /// 对齐到系统时间的period整数倍, 第一次在下一个边界触发, 时区见`with_utc_offset`
+    pub fn with_wall_align(mut self) -> Self {
+        self.wall_align = true;
+        self.resync();
+        self
+    }
+
+    /// 对齐使用的时区和UTC相差的秒数, 默认为+8时区, period不能整除时区偏移时(如按天对齐)才有区别
+    pub fn with_utc_offset(mut self, utc_offset: i32) -> Self {
+        self.utc_offset = utc_offset;
+        self.resync();
+        self
+    }
+
+    /// 系统时间和单调时钟的差超过该值时视为跳变, 默认1秒
+    pub fn with_step_threshold(self, step_threshold: Duration) -> Self {
+        SteadyInterval {
+            step_threshold,
+            ..self
+        }
+    }
+
+    /// 检测到系统时间跳变时调用, 在tick中调用
+    pub fn on_clock_step<F>(self, f: F) -> Self
+    where
+        F: FnMut(ClockStep) + Send + 'static,
+    {
+        SteadyInterval {
+            on_step: Some(Box::new(f)),
+            ..self
+        }
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// 按当前系统时间重新对齐下一次触发, 未开启对齐时不处理
+    pub fn resync(&mut self) {
+        if !self.wall_align {
+            return;
+        }
+        let now = SystemTime::now();
+        let boundary = next_boundary(now, self.period, self.utc_offset, self.last_boundary);
+        let wait = boundary.duration_since(now).unwrap_or_default();
+        self.interval.reset_at(Instant::now() + wait);
+    }
+
+    /// 等待下一次触发, 返回触发时的系统时间, 对齐时返回对齐的边界
+    pub async fn tick(&mut self) -> SystemTime {
+        let instant = self.interval.tick().await;
+        let wall = SystemTime::now();
+        let step = self.last.and_then(|(last_instant, last_wall)| {
+            detect_step(instant - last_instant, last_wall, wall, self.step_threshold)
+        });
+        self.last = Some((instant, wall));
+        if let Some(step) = step {
+            if let Some(f) = self.on_step.as_mut() {
+                f(step);
+            }
+        }
+        if !self.wall_align {
+            return wall;
+        }
+        let boundary = align_boundary(wall, self.period, self.utc_offset, self.last_boundary);
+        self.last_boundary = Some(boundary);
+        // 往回跳后系统时间还没到边界, 等到边界再触发
+        let waited = wait_wall(boundary).await;
+        // 单调时钟和系统时间有偏差时(跳变或慢慢漂移), 下一次按系统时间重新对齐
+        if waited || step.is_some() || wall_offset(wall, boundary) > self.period / 10 {
+            self.resync();
+        }
+        boundary
+    }
+}
+
+/// 对齐时区中的纳秒数
+fn local_nanos(time: SystemTime, utc_offset: i32) -> i128 {
+    let nanos = match time.duration_since(UNIX_EPOCH) {
+        Ok(v) => v.as_nanos() as i128,
+        Err(e) => -(e.duration().as_nanos() as i128),
+    };
+    nanos + utc_offset as i128 * 1_000_000_000
+}
+
+fn from_local_nanos(nanos: i128, utc_offset: i32) -> SystemTime {
+    let nanos = nanos - utc_offset as i128 * 1_000_000_000;
+    let duration = Duration::from_nanos(nanos.unsigned_abs() as u64);
+    if nanos >= 0 {
+        UNIX_EPOCH + duration
+    } else {
+        UNIX_EPOCH - duration
+    }
+}
+
+/// now之后第一个period整数倍的时间, 不早于last + period
+fn next_boundary(
+    now: SystemTime,
+    period: Duration,
+    utc_offset: i32,
+    last: Option<SystemTime>,
+) -> SystemTime {
+    let period_nanos = period.as_nanos().max(1) as i128;
+    let now_nanos = local_nanos(now, utc_offset);
+    let boundary = from_local_nanos(
+        (now_nanos + period_nanos - 1).div_euclid(period_nanos) * period_nanos,
+        utc_offset,
+    );
+    match last {
+        Some(last) if boundary <= last => last + period,
+        _ => boundary,
+    }
+}
+
+/// 离wall最近的period整数倍的时间
+fn nearest_boundary(wall: SystemTime, period: Duration, utc_offset: i32) -> SystemTime {
+    let period_nanos = period.as_nanos().max(1) as i128;
+    let nanos = local_nanos(wall, utc_offset);
+    from_local_nanos(
+        (nanos + period_nanos / 2).div_euclid(period_nanos) * period_nanos,
+        utc_offset,
+    )
+}
+
+/// 触发对应的边界, 不早于last + period, 往回跳后可能晚于wall
+fn align_boundary(
+    wall: SystemTime,
+    period: Duration,
+    utc_offset: i32,
+    last: Option<SystemTime>,
+) -> SystemTime {
+    let boundary = nearest_boundary(wall, period, utc_offset);
+    match last {
+        Some(last) if boundary <= last => last + period,
+        _ => boundary,
+    }
+}
+
+/// 等到系统时间到达boundary, 等待期间时间再跳变时重新计算, 有等待时返回true
+async fn wait_wall(boundary: SystemTime) -> bool {
+    let mut waited = false;
+    while let Ok(wait) = boundary.duration_since(SystemTime::now()) {
+        if wait.is_zero() {
+            break;
+        }
+        time::sleep(wait).await;
+        waited = true;
+    }
+    waited
+}
+
+fn wall_offset(wall: SystemTime, boundary: SystemTime) -> Duration {
+    match wall.duration_since(boundary) {
+        Ok(v) => v,
+        Err(e) => e.duration(),
+    }
+}
+
+/// 两次触发间系统时间走过的时间和单调时钟相差超过threshold时为跳变
+fn detect_step(
+    monotonic: Duration,
+    last_wall: SystemTime,
+    wall: SystemTime,
+    threshold: Duration,
+) -> Option<ClockStep> {
+    let wall_ms = match wall.duration_since(last_wall) {
+        Ok(v) => v.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
+    };
+    let offset_ms = wall_ms - monotonic.as_millis() as i64;
+    (offset_ms.unsigned_abs() as u128 > threshold.as_millis()).then_some(ClockStep {
+        monotonic,
+        offset_ms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::{
+        align_boundary, detect_step, nearest_boundary, next_boundary, steady_interval, ClockStep,
+    };
+
+    fn secs(secs: u64, millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_boundary() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(next_boundary(secs(130, 0), minute, 0, None), secs(180, 0));
+        assert_eq!(next_boundary(secs(120, 0), minute, 0, None), secs(120, 0));
+        // 时间往回跳, 不重复触发已触发的边界
+        assert_eq!(
+            next_boundary(secs(100, 0), minute, 0, Some(secs(120, 0))),
+            secs(180, 0)
+        );
+        assert_eq!(nearest_boundary(secs(119, 900), minute, 0), secs(120, 0));
+        assert_eq!(nearest_boundary(secs(120, 200), minute, 0), secs(120, 0));
+
+        // 往回跳后边界晚于系统时间, tick中等到系统时间到达边界
+        assert_eq!(
+            align_boundary(secs(100, 0), minute, 0, Some(secs(120, 0))),
+            secs(180, 0)
+        );
+
+        // 按+8时区对齐, 一天的边界为UTC 16:00
+        let day = Duration::from_secs(86400);
+        let offset = 8 * 3600;
+        assert_eq!(next_boundary(secs(3600, 0), day, 0, None), secs(86400, 0));
+        assert_eq!(
+            next_boundary(secs(3600, 0), day, offset, None),
+            secs(16 * 3600, 0)
+        );
+        assert_eq!(
+            nearest_boundary(secs(86400 + 16 * 3600 + 1, 0), day, offset),
+            secs(86400 + 16 * 3600, 0)
+        );
+        // 2小时的边界在+8时区的偶数点
+        let hours2 = Duration::from_secs(7200);
+        assert_eq!(
+            next_boundary(secs(3600 * 3 + 10, 0), hours2, offset, None),
+            secs(3600 * 4, 0)
+        );
+        assert_eq!(
+            next_boundary(secs(3600 * 3 + 10, 0), hours2, 3600 * 9, None),
+            secs(3600 * 5, 0)
+        );
+    }
+
+    #[test]
+    fn test_detect_step() {
+        let threshold = Duration::from_secs(1);
+        let monotonic = Duration::from_secs(60);
+        assert_eq!(
+            detect_step(monotonic, secs(60, 0), secs(120, 300), threshold),
+            None
+        );
+        assert_eq!(
+            detect_step(monotonic, secs(60, 0), secs(125, 0), threshold),
+            Some(ClockStep {
+                monotonic,
+                offset_ms: 5000,
+            })
+        );
+        assert_eq!(
+            detect_step(monotonic, secs(60, 0), secs(50, 0), threshold),
+            Some(ClockStep {
+                monotonic,
+                offset_ms: -70000,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_steady_interval() {
+        let period = Duration::from_millis(50);
+        let mut interval = steady_interval(period);
+        let start = tokio::time::Instant::now();
+        for _ in 0..4 {
+            interval.tick().await;
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= period * 3 && elapsed < period * 6);
+
+        let mut interval = steady_interval(period).with_wall_align();
+        let mut last = None;
+        for _ in 0..3 {
+            let boundary = interval.tick().await;
+            let nanos = boundary.duration_since(UNIX_EPOCH).unwrap().as_nanos();
+            assert_eq!(nanos % period.as_nanos(), 0);
+            if let Some(last) = last {
+                assert!(boundary > last);
+            }
+            last = Some(boundary);
+        }
+    }
+}
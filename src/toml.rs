@@ -2,11 +2,13 @@ use std::path::Path;
 use std::{fs, io};
 
 use log::debug;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use thiserror::Error;
 use toml::Deserializer;
 
 use crate::path_plain::{HomeDirNotFound, PathPlainExt};
+use crate::schema::{Schema, SchemaError};
 
 #[derive(Debug, Error)]
 pub enum TomlParseError {
@@ -16,6 +18,10 @@ pub enum TomlParseError {
     SerdeToml(#[from] toml::de::Error),
     #[error("{0}")]
     PathPlain(#[from] HomeDirNotFound),
+    #[error("{0}")]
+    Schema(#[from] SchemaError),
+    #[error("profile not found: {0}")]
+    ProfileNotFound(String),
 }
 
 fn from_str<'de, T>(s: &str) -> Result<T, toml::de::Error>
@@ -51,6 +57,61 @@ where
     Ok(r)
 }
 
+/// 先按schema校验文档, 有问题时返回所有问题的key路径, 通过后再反序列化
+pub fn parse_from_file_with_schema<P, R>(path: P, schema: &Schema) -> Result<R, TomlParseError>
+where
+    P: AsRef<Path>,
+    R: DeserializeOwned,
+{
+    let path = path.plain()?;
+    let file_content = fs::read_to_string(path)?;
+    let value = from_str::<toml::Value>(&file_content)?;
+    schema.validate(&value)?;
+    let r = from_str::<R>(&file_content)?;
+    Ok(r)
+}
+
+/// 把`[profiles.<profile>]`深度合并到基础文档上, 并移除`profiles`
+///
+/// 表按key递归合并, 其他值(包括数组)直接替换
+fn apply_profile(mut value: toml::Value, profile: &str) -> Result<toml::Value, TomlParseError> {
+    let profiles = value.as_table_mut().and_then(|v| v.remove("profiles"));
+    let selected = profiles
+        .and_then(|mut v| v.as_table_mut().and_then(|v| v.remove(profile)))
+        .ok_or_else(|| TomlParseError::ProfileNotFound(profile.to_owned()))?;
+    merge_value(&mut value, selected);
+    Ok(value)
+}
+
+fn merge_value(base: &mut toml::Value, over: toml::Value) {
+    match (base, over) {
+        (toml::Value::Table(base), toml::Value::Table(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(v) => merge_value(v, value),
+                    None => {
+                        base.insert(key, value);
+                    },
+                }
+            }
+        },
+        (base, over) => *base = over,
+    }
+}
+
+/// 按环境选择配置, 如`parse_with_profile::<AppConfig>(path, "prod")`,
+/// `[profiles.prod]`合并到基础文档后再反序列化, profile不存在时返回错误
+pub fn parse_with_profile<R>(path: impl AsRef<Path>, profile: &str) -> Result<R, TomlParseError>
+where
+    R: DeserializeOwned,
+{
+    let path = path.plain()?;
+    let file_content = fs::read_to_string(path)?;
+    let value = from_str::<toml::Value>(&file_content)?;
+    let r = apply_profile(value, profile)?.try_into::<R>()?;
+    Ok(r)
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(unused)]
@@ -59,7 +120,11 @@ mod tests {
 
     use serde::{Deserialize, Serialize};
 
-    use crate::toml::parse_from_file;
+    use crate::schema::{Schema, TableSchema};
+    use crate::toml::{
+        apply_profile, parse_from_file, parse_from_file_with_schema, parse_with_profile,
+        TomlParseError,
+    };
 
     #[test]
     fn test_read() {
@@ -73,6 +138,81 @@ mod tests {
         println!("{:?}", tmp)
     }
 
+    #[test]
+    fn test_parse_with_schema() {
+        let schema = Schema::map(
+            TableSchema::new()
+                .required("host", Schema::String)
+                .required("port", Schema::Int)
+                .required("not-exist-key", Schema::String)
+                .into(),
+        );
+        let r = parse_from_file_with_schema::<_, toml::Value>("./_data/db-conn.toml", &schema);
+        println!("{}", r.as_ref().unwrap_err());
+        assert!(matches!(r, Err(TomlParseError::Schema(_))));
+    }
+
+    const PROFILE_TOML: &str = r#"
+        name = "hq"
+        ports = [3306, 3307]
+
+        [db]
+        host = "127.0.0.1"
+        user = "dev"
+        pool = { max = 5, min = 1 }
+
+        [profiles.prod]
+        ports = [3306]
+        db.host = "10.0.0.2"
+        db.pool.max = 20
+
+        [profiles.dev]
+        name = "hq-dev"
+        "#;
+
+    #[test]
+    fn test_apply_profile() {
+        let value = toml::from_str::<toml::Value>(PROFILE_TOML).unwrap();
+        let prod = apply_profile(value.clone(), "prod").unwrap();
+        assert!(prod.get("profiles").is_none());
+        assert_eq!(prod["name"].as_str(), Some("hq"));
+        assert_eq!(prod["ports"].as_array().unwrap().len(), 1);
+        assert_eq!(prod["db"]["host"].as_str(), Some("10.0.0.2"));
+        assert_eq!(prod["db"]["user"].as_str(), Some("dev"));
+        assert_eq!(prod["db"]["pool"]["max"].as_integer(), Some(20));
+        assert_eq!(prod["db"]["pool"]["min"].as_integer(), Some(1));
+
+        let dev = apply_profile(value.clone(), "dev").unwrap();
+        assert_eq!(dev["name"].as_str(), Some("hq-dev"));
+        assert_eq!(dev["db"]["host"].as_str(), Some("127.0.0.1"));
+
+        let r = apply_profile(value, "test");
+        assert!(matches!(r, Err(TomlParseError::ProfileNotFound(v)) if v == "test"));
+    }
+
+    #[test]
+    fn test_parse_with_profile() {
+        #[derive(Debug, Deserialize)]
+        struct Db {
+            host: String,
+            user: String,
+        }
+        #[derive(Debug, Deserialize)]
+        struct Config {
+            name:  String,
+            ports: Vec<u16>,
+            db:    Db,
+        }
+        let path = std::env::temp_dir().join("common-rs-test-profile.toml");
+        std::fs::write(&path, PROFILE_TOML).unwrap();
+        let config = parse_with_profile::<Config>(&path, "prod").unwrap();
+        assert_eq!(config.name, "hq");
+        assert_eq!(config.ports, vec![3306]);
+        assert_eq!(config.db.host, "10.0.0.2");
+        assert_eq!(config.db.user, "dev");
+        std::fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_cow() {
         #[derive(Debug, Deserialize)]
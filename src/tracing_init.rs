@@ -1,23 +1,51 @@
 use std::borrow::Cow;
-use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{fs, io};
 
 use rolling_file::{BasicRollingFileAppender, RollingConditionBasic};
+use time::formatting::Formattable;
 use time::macros::format_description;
 use time::UtcOffset;
+use tracing::Subscriber;
 use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
 use tracing_error::ErrorLayer;
-use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::filter::{FilterExt, LevelFilter, Targets};
 use tracing_subscriber::fmt::format::{DefaultFields, Format, Full};
 use tracing_subscriber::fmt::time::OffsetTime;
-use tracing_subscriber::fmt::Layer;
-use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::fmt::{Layer, MakeWriter};
+use tracing_subscriber::layer::{Layer as _, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{fmt, Registry};
 
+use self::compress::CompressRollingFileAppender;
+pub use self::compress::LogCompression;
+pub use self::dynamic_file::{field_file_handle, FieldFileHandle};
+use self::dynamic_file::{DynamicFileLayer, DynamicFileWriter};
+use self::global_fields::GlobalFieldsFormat;
+pub use self::request_id::current_request_id;
+use self::request_id::RequestIdLayer;
+use self::ring_buffer::RingBufferLayer;
+pub use self::ring_buffer::{flush_ring_buffer, ring_buffer_events};
+pub use self::sampling::Sampling;
+use self::sampling::SamplingFilter;
+use self::span_timing::SpanTimingLayer;
+pub use self::span_timing::{metrics_snapshot, SpanTimingStats};
 use self::tracing_file::TracingFileLayer;
-
+pub use self::write_error::file_write_errors;
+use self::write_error::{fallback_dir, set_fallback_dir, FallbackWriter};
+
+mod compress;
+mod dynamic_file;
+mod global_fields;
+mod request_id;
+mod ring_buffer;
+mod sampling;
+mod span_timing;
 mod tracing_file;
+mod write_error;
 
 pub struct TracingConfig<'a> {
     max_files:         usize,
@@ -28,10 +56,19 @@ pub struct TracingConfig<'a> {
     console_target:    bool,
     file_enable:       bool,
     file_dir:          Cow<'a, Path>,
+    fallback_dir:      Option<Cow<'a, Path>>,
     file_name:         Cow<'a, str>,
     file_line_info:    bool,
     file_target:       bool,
     field_files:       Vec<Cow<'a, str>>,
+    dynamic_files:     bool,
+    compression:       LogCompression,
+    span_timing:       bool,
+    span_timing_log:   Option<Duration>,
+    global_fields:     Vec<(String, String)>,
+    request_id:        Option<Cow<'a, str>>,
+    ring_buffer:       Option<(usize, LevelFilter)>,
+    sampling:          Vec<(Cow<'a, str>, LevelFilter, Sampling)>,
 }
 
 impl Default for TracingConfig<'_> {
@@ -45,10 +82,19 @@ impl Default for TracingConfig<'_> {
             console_target:    true,
             file_enable:       false,
             file_dir:          Default::default(),
+            fallback_dir:      None,
             file_name:         "run.log".into(),
             file_line_info:    true,
             file_target:       true,
             field_files:       Vec::new(),
+            dynamic_files:     false,
+            compression:       LogCompression::None,
+            span_timing:       false,
+            span_timing_log:   None,
+            global_fields:     Vec::new(),
+            request_id:        None,
+            ring_buffer:       None,
+            sampling:          Vec::new(),
         }
     }
 }
@@ -117,6 +163,14 @@ impl<'a> TracingConfig<'a> {
         }
     }
 
+    /// 日志目录写入失败(磁盘满, 没有权限等)时切换到的目录, 切换后不再切回
+    pub fn with_fallback_dir<P: AsRef<Path>>(self, dir: P) -> TracingConfig<'a> {
+        TracingConfig {
+            fallback_dir: Some(Cow::from(dir.as_ref().to_owned())),
+            ..self
+        }
+    }
+
     pub fn with_file_name(self, file_name: &'a str) -> TracingConfig<'a> {
         TracingConfig {
             file_name: file_name.into(),
@@ -145,6 +199,83 @@ impl<'a> TracingConfig<'a> {
         }
     }
 
+    /// 开启运行时添加/关闭按`logfile`字段写入的日志文件, 通过`field_file_handle`管理,
+    /// 需要同时开启文件日志
+    pub fn with_dynamic_field_files(self, dynamic_files: bool) -> TracingConfig<'a> {
+        TracingConfig {
+            dynamic_files,
+            ..self
+        }
+    }
+
+    /// 日志文件滚动后压缩, 压缩后的文件也计入max_files
+    pub fn with_compression(self, compression: LogCompression) -> TracingConfig<'a> {
+        TracingConfig {
+            compression,
+            ..self
+        }
+    }
+
+    /// 统计span的耗时, 通过`metrics_snapshot`获取
+    pub fn with_span_timing(self, span_timing: bool) -> TracingConfig<'a> {
+        TracingConfig {
+            span_timing,
+            ..self
+        }
+    }
+
+    /// 开启span耗时统计, 并每隔interval打印一次
+    pub fn with_span_timing_log(self, interval: Duration) -> TracingConfig<'a> {
+        TracingConfig {
+            span_timing: true,
+            span_timing_log: Some(interval),
+            ..self
+        }
+    }
+
+    /// 每条日志(控制台和文件)都加上的固定字段, 如服务名, 主机名, 版本
+    pub fn with_global_fields(self, fields: &[(&str, &str)]) -> TracingConfig<'a> {
+        TracingConfig {
+            global_fields: fields
+                .iter()
+                .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+                .collect(),
+            ..self
+        }
+    }
+
+    /// 开启关联ID, 声明了field字段的span内的日志都带上该字段, 通过`current_request_id`获取
+    ///
+    /// span的字段有值时使用该值, 为`tracing::field::Empty`时自动生成
+    pub fn with_request_id(self, field: &'a str) -> TracingConfig<'a> {
+        TracingConfig {
+            request_id: Some(field.into()),
+            ..self
+        }
+    }
+
+    /// 在内存中保留最近capacity条level及以上的日志(可以低于level_filter),
+    /// 通过`ring_buffer_events`或`flush_ring_buffer`在崩溃时取出
+    pub fn with_ring_buffer(self, capacity: usize, level: LevelFilter) -> TracingConfig<'a> {
+        TracingConfig {
+            ring_buffer: Some((capacity, level)),
+            ..self
+        }
+    }
+
+    /// 对target(含子模块)中level或更详细级别的日志采样, 只影响控制台和文件, 不影响ring buffer
+    ///
+    /// 如`with_sampling("ticks", LevelFilter::DEBUG, Sampling::OneIn(100))`
+    pub fn with_sampling(
+        mut self,
+        target: &'a str,
+        level: LevelFilter,
+        sampling: Sampling,
+    ) -> TracingConfig<'a> {
+        self.sampling.push((target.into(), level, sampling));
+        self
+    }
+
     pub fn add_target(&mut self, target: &'a str) {
         self.target_filters.push((target.into(), self.level_filter));
     }
@@ -179,7 +310,14 @@ pub fn tracing_init(config: &TracingConfig) -> Option<Vec<WorkerGuard>> {
             .with_file(config.console_line_info)
             .with_line_number(config.console_line_info)
             .with_target(config.console_target)
-            .with_timer(timer.clone());
+            .with_timer(timer.clone())
+            .map_event_format(|f| {
+                GlobalFieldsFormat::new(
+                    f.with_ansi(true),
+                    &config.global_fields,
+                    config.request_id.as_deref(),
+                )
+            });
         Some(layer)
     } else {
         None
@@ -210,8 +348,16 @@ pub fn tracing_init(config: &TracingConfig) -> Option<Vec<WorkerGuard>> {
     //     .with_timer(timer)
     //     .with_writer(non_blocking_appender);
 
-    let (file_append_layer, field_file_layer_vec, guard_vec) = if config.file_enable {
-        let _ = fs::create_dir_all(config.file_dir.as_ref());
+    let (file_append_layer, field_file_layer_vec, dynamic_file_layer, guard_vec) = if config
+        .file_enable
+    {
+        // 创建失败时打开文件会失败, 由rolling_appender切换到备用目录
+        if let Err(e) = fs::create_dir_all(config.file_dir.as_ref()) {
+            eprintln!("log dir create error: {}, {}", config.file_dir.display(), e);
+        }
+        if let Some(dir) = config.fallback_dir.as_deref() {
+            set_fallback_dir(dir);
+        }
         let FileAppenderLayerWorkerGuard(file_appender_layer, worker_guard) =
             file_appender_layer_worker_guard(config.file_name.as_ref(), config, timer.clone());
         let mut guard_vec = vec![worker_guard];
@@ -231,13 +377,25 @@ pub fn tracing_init(config: &TracingConfig) -> Option<Vec<WorkerGuard>> {
             None
         };
 
+        let dynamic_file_layer = config.dynamic_files.then(|| {
+            DynamicFileLayer::new(
+                file_fmt_layer(config, timer.clone(), DynamicFileWriter),
+                "logfile",
+                config.file_dir.to_path_buf(),
+                config.max_files,
+                config.compression,
+                config.field_files.iter().map(|v| v.to_string()).collect(),
+            )
+        });
+
         (
             Some(file_appender_layer),
             Some(field_file_layer_vec),
+            dynamic_file_layer,
             Some(guard_vec),
         )
     } else {
-        (None, None, None)
+        (None, None, None, None)
     };
 
     let targets = if config.target_filters.is_empty() {
@@ -246,13 +404,37 @@ pub fn tracing_init(config: &TracingConfig) -> Option<Vec<WorkerGuard>> {
         Targets::from_iter(config.target_filters.clone())
     };
 
+    let span_timing_layer = config
+        .span_timing
+        .then(|| SpanTimingLayer::new(config.span_timing_log));
+
+    let request_id_layer = config.request_id.as_deref().map(RequestIdLayer::new);
+
+    // 开启ring buffer时全局级别放宽到两者中较详细的, 控制台和文件单独按targets过滤
+    let (level_filter, ring_buffer_layer) = match config.ring_buffer {
+        Some((capacity, level)) => (
+            config.level_filter.max(level),
+            Some(RingBufferLayer::new(capacity).with_filter(level)),
+        ),
+        None => (config.level_filter, None),
+    };
+
     // XXX console_layer放到file_appender_layer和field_file_layer_vec前面, 会影响文件打印的内容.
     Registry::default()
-        .with(config.level_filter)
-        .with(file_append_layer)
-        .with(field_file_layer_vec)
-        .with(console_layer)
-        .with(targets)
+        .with(level_filter)
+        .with(
+            tracing_subscriber::Layer::and_then(
+                tracing_subscriber::Layer::and_then(
+                    tracing_subscriber::Layer::and_then(file_append_layer, field_file_layer_vec),
+                    dynamic_file_layer,
+                ),
+                console_layer,
+            )
+            .with_filter(targets.and(SamplingFilter::new(&config.sampling))),
+        )
+        .with(ring_buffer_layer)
+        .with(span_timing_layer)
+        .with(request_id_layer)
         // ErrorLayer 可以让 color-eyre 获取到 span 的信息
         .with(ErrorLayer::default())
         .init();
@@ -261,35 +443,94 @@ pub fn tracing_init(config: &TracingConfig) -> Option<Vec<WorkerGuard>> {
 }
 
 struct FileAppenderLayerWorkerGuard<S, T>(
-    Layer<S, DefaultFields, Format<Full, OffsetTime<T>>, NonBlocking>,
+    Layer<S, DefaultFields, GlobalFieldsFormat<Format<Full, OffsetTime<T>>>, NonBlocking>,
     WorkerGuard,
 );
 
-fn file_appender_layer_worker_guard<P, S, T>(
-    file_name: P,
+/// 按天滚动的日志文件
+fn file_appender(
+    path: PathBuf,
+    max_files: usize,
+    compression: LogCompression,
+) -> io::Result<Box<dyn Write + Send>> {
+    let appender: Box<dyn Write + Send> = match compression {
+        LogCompression::None => Box::new(BasicRollingFileAppender::new(
+            path,
+            RollingConditionBasic::new().daily(),
+            max_files,
+        )?),
+        compression => Box::new(CompressRollingFileAppender::new(
+            path,
+            RollingConditionBasic::new().daily(),
+            max_files,
+            compression,
+        )?),
+    };
+    Ok(appender)
+}
+
+/// 后台线程写入的日志文件, 写入失败时计数, 配置了备用目录时切换过去
+fn rolling_appender(
+    path: PathBuf,
+    max_files: usize,
+    compression: LogCompression,
+) -> io::Result<(NonBlocking, WorkerGuard)> {
+    let writer = FallbackWriter::open(
+        path,
+        max_files,
+        compression,
+        fallback_dir().map(Path::to_path_buf),
+    )?;
+    Ok(tracing_appender::non_blocking(writer))
+}
+
+fn file_fmt_layer<S, T, W>(
     config: &TracingConfig,
     timer: OffsetTime<T>,
-) -> FileAppenderLayerWorkerGuard<S, T>
+    writer: W,
+) -> Layer<S, DefaultFields, GlobalFieldsFormat<Format<Full, OffsetTime<T>>>, W>
 where
-    P: AsRef<Path>,
+    S: Subscriber + for<'s> LookupSpan<'s>,
+    T: Formattable + 'static,
+    W: for<'w> MakeWriter<'w> + 'static,
 {
-    let directory = config.file_dir.as_ref();
-    let file_appender = BasicRollingFileAppender::new(
-        directory.join(file_name),
-        RollingConditionBasic::new().daily(),
-        config.max_files,
-    )
-    .unwrap();
-
-    let (non_blocking_appender, file_worker_guard) = tracing_appender::non_blocking(file_appender);
-
-    let file_appender_layer = fmt::layer()
+    fmt::layer()
         .with_ansi(false)
         .with_file(config.file_line_info)
         .with_line_number(config.file_line_info)
         .with_target(config.file_target)
         .with_timer(timer)
-        .with_writer(non_blocking_appender);
+        .with_writer(writer)
+        .map_event_format(|f| {
+            GlobalFieldsFormat::new(
+                f.with_ansi(false),
+                &config.global_fields,
+                config.request_id.as_deref(),
+            )
+        })
+}
+
+fn file_appender_layer_worker_guard<P, S, T>(
+    file_name: P,
+    config: &TracingConfig,
+    timer: OffsetTime<T>,
+) -> FileAppenderLayerWorkerGuard<S, T>
+where
+    P: AsRef<Path>,
+    S: Subscriber + for<'s> LookupSpan<'s>,
+    T: Formattable + 'static,
+{
+    let path = config.file_dir.join(file_name);
+    // 日志目录和备用目录都打不开时丢弃这个文件的日志, 不影响控制台和其它文件
+    let (non_blocking_appender, file_worker_guard) =
+        match rolling_appender(path.clone(), config.max_files, config.compression) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("log file disabled: {}, {}", path.display(), e);
+                tracing_appender::non_blocking(io::sink())
+            },
+        };
+    let file_appender_layer = file_fmt_layer(config, timer, non_blocking_appender);
     FileAppenderLayerWorkerGuard(file_appender_layer, file_worker_guard)
 }
 
@@ -298,9 +539,12 @@ mod tests {
     use std::path::Path;
 
     use tracing::level_filters::LevelFilter;
-    use tracing::{info, span, Level};
+    use tracing::{debug, info, span, trace, Level};
 
-    use super::{tracing_init, TracingConfig};
+    use super::{
+        current_request_id, field_file_handle, flush_ring_buffer, ring_buffer_events, tracing_init,
+        Sampling, TracingConfig,
+    };
 
     #[test]
     fn test_path() {
@@ -322,7 +566,12 @@ mod tests {
             .with_file_dir("./_logs")
             .with_console_line_info(false)
             .with_field_files(&field_files)
-            .with_file_line_info(false);
+            .with_dynamic_field_files(true)
+            .with_file_line_info(false)
+            .with_global_fields(&[("service", "ingest"), ("host", "localhost")])
+            .with_request_id("request_id")
+            .with_ring_buffer(100, LevelFilter::TRACE)
+            .with_sampling("ticks", LevelFilter::DEBUG, Sampling::OneIn(10));
 
         let _worker_guard_vec = tracing_init(&log_config);
 
@@ -341,6 +590,55 @@ mod tests {
             info!("this is msg 2 in file2");
             info!("this is msg 3 in file2");
         });
+
+        span!(Level::DEBUG, "tick", request_id = tracing::field::Empty).in_scope(|| {
+            let request_id = current_request_id();
+            assert!(request_id.is_some());
+            span!(Level::DEBUG, "stage").in_scope(|| {
+                assert_eq!(current_request_id(), request_id);
+                info!("this is msg with request id");
+            });
+        });
+        span!(Level::DEBUG, "bar", request_id = "bar-1").in_scope(|| {
+            assert_eq!(current_request_id().as_deref(), Some("bar-1"));
+        });
+        assert!(current_request_id().is_none());
+
+        info!(logfile = "file1", "this is event msg in file1");
+        span!(Level::DEBUG, "xxx", logfile = "file1").in_scope(|| {
+            info!(logfile = "file2", "this is event msg in file2");
+        });
+
+        // 运行时添加的文件
+        let handle = field_file_handle().unwrap();
+        assert!(!handle.add("file1").unwrap());
+        assert!(handle.add("strategy1").unwrap());
+        assert!(!handle.add("strategy1").unwrap());
+        span!(Level::DEBUG, "strategy", logfile = "strategy1").in_scope(|| {
+            info!("this is msg in strategy1");
+        });
+        info!(logfile = "strategy2", "this is msg in strategy2, dropped");
+        assert_eq!(handle.files(), ["strategy1"]);
+        assert!(handle.remove("strategy1"));
+        assert!(!handle.remove("strategy1"));
+        info!(logfile = "strategy1", "this is msg after close, dropped");
+        let content = std::fs::read_to_string("./_logs/strategy1.log").unwrap();
+        assert!(content.contains("this is msg in strategy1"));
+        assert!(!content.contains("after close"));
+        assert!(!Path::new("./_logs/strategy2.log").exists());
+
+        // 文件中只有第1和第11条, ring buffer中全部都有
+        for i in 0..20 {
+            debug!(target: "ticks", i, "this is sampled tick");
+        }
+
+        // 低于文件级别的日志只在ring buffer中
+        trace!(step = 1, "this is trace msg");
+        let events = ring_buffer_events();
+        assert!(events
+            .last()
+            .is_some_and(|v| v.contains("TRACE") && v.contains("this is trace msg step=1")));
+        assert_eq!(flush_ring_buffer("./_logs/ring.log").unwrap(), events.len());
     }
 
     #[allow(unused)]
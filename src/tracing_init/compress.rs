@@ -0,0 +1,264 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rolling_file::{RollingCondition, RollingConditionBasic, RollingFileAppender};
+use time::macros::format_description;
+use time::{OffsetDateTime, UtcOffset};
+
+/// 日志文件滚动后的压缩方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogCompression {
+    #[default]
+    None,
+    Gzip,
+}
+
+impl LogCompression {
+    fn extension(&self) -> &'static str {
+        match self {
+            LogCompression::None => "",
+            LogCompression::Gzip => "gz",
+        }
+    }
+}
+
+/// 记录是否发生了滚动
+struct NotifyRollingCondition {
+    inner:   RollingConditionBasic,
+    rotated: bool,
+}
+
+impl RollingCondition for NotifyRollingCondition {
+    fn should_rollover(
+        &mut self,
+        now: &chrono::DateTime<chrono::Local>,
+        current_filesize: u64,
+    ) -> bool {
+        let r = self.inner.should_rollover(now, current_filesize);
+        if r {
+            self.rotated = true;
+        }
+        r
+    }
+}
+
+/// 滚动后压缩的文件appender
+/// 滚动后的文件(base.1)重命名为 base.时间, 交给后台线程压缩成 base.时间.gz 并删除原文件,
+/// 压缩后的文件数量超过max_files时删除最老的文件.
+/// 后台线程在appender drop时结束(会等待未完成的压缩), appender由tracing_init返回的WorkerGuard持有.
+pub(crate) struct CompressRollingFileAppender {
+    appender:  RollingFileAppender<NotifyRollingCondition>,
+    base_path: PathBuf,
+    sender:    Option<mpsc::Sender<PathBuf>>,
+    handle:    Option<JoinHandle<()>>,
+}
+
+impl CompressRollingFileAppender {
+    pub(crate) fn new(
+        path: impl AsRef<Path>,
+        condition: RollingConditionBasic,
+        max_files: usize,
+        compression: LogCompression,
+    ) -> io::Result<CompressRollingFileAppender> {
+        let base_path = path.as_ref().to_path_buf();
+        let condition = NotifyRollingCondition {
+            inner:   condition,
+            rotated: false,
+        };
+        // 滚动后的文件会马上被移走, 只需要保留base.1
+        let appender = RollingFileAppender::new(&base_path, condition, 1)?;
+
+        let (sender, receiver) = mpsc::channel::<PathBuf>();
+        let handle = {
+            let base_path = base_path.clone();
+            thread::Builder::new()
+                .name("log-compress".to_string())
+                .spawn(move || {
+                    for path in receiver {
+                        if let Err(e) = compress_file(&path, compression) {
+                            eprintln!(
+                                "WARNING: Failed to compress logfile {}: {}",
+                                path.display(),
+                                e
+                            );
+                        }
+                        if let Err(e) = remove_old_files(&base_path, compression, max_files) {
+                            eprintln!(
+                                "WARNING: Failed to remove old logfile {}: {}",
+                                base_path.display(),
+                                e
+                            );
+                        }
+                    }
+                })?
+        };
+
+        Ok(CompressRollingFileAppender {
+            appender,
+            base_path,
+            sender: Some(sender),
+            handle: Some(handle),
+        })
+    }
+
+    fn after_rotate(&mut self) {
+        let rotated = PathBuf::from(format!("{}.1", self.base_path.display()));
+        if !rotated.exists() {
+            return;
+        }
+        let time_format = format_description!("[year][month][day]-[hour][minute][second]");
+        let now = OffsetDateTime::now_utc().to_offset(UtcOffset::from_hms(8, 0, 0).unwrap());
+        let suffix = now.format(time_format).unwrap_or_default();
+        let mut target = PathBuf::from(format!("{}.{}", self.base_path.display(), suffix));
+        let mut idx = 1;
+        while target.exists() {
+            target = PathBuf::from(format!("{}.{}-{}", self.base_path.display(), suffix, idx));
+            idx += 1;
+        }
+        if let Err(e) = fs::rename(&rotated, &target) {
+            eprintln!(
+                "WARNING: Failed to rename logfile {}: {}",
+                rotated.display(),
+                e
+            );
+            return;
+        }
+        if let Some(sender) = self.sender.as_ref() {
+            let _ = sender.send(target);
+        }
+    }
+
+    #[cfg(test)]
+    fn rollover(&mut self) -> io::Result<()> {
+        self.appender.rollover()?;
+        self.after_rotate();
+        Ok(())
+    }
+}
+
+impl Write for CompressRollingFileAppender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let r = self.appender.write(buf);
+        let condition = self.appender.condition_mut();
+        if condition.rotated {
+            condition.rotated = false;
+            self.after_rotate();
+        }
+        r
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.appender.flush()
+    }
+}
+
+impl Drop for CompressRollingFileAppender {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn compress_file(path: &Path, compression: LogCompression) -> io::Result<()> {
+    match compression {
+        LogCompression::None => Ok(()),
+        LogCompression::Gzip => {
+            let target = PathBuf::from(format!("{}.{}", path.display(), compression.extension()));
+            let mut reader = BufReader::new(File::open(path)?);
+            let writer = BufWriter::new(File::create(&target)?);
+            let mut encoder = GzEncoder::new(writer, Compression::default());
+            io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?.flush()?;
+            fs::remove_file(path)
+        },
+    }
+}
+
+/// 删除超过max_files的已滚动文件, 按修改时间从老到新删除
+fn remove_old_files(
+    base_path: &Path,
+    compression: LogCompression,
+    max_files: usize,
+) -> io::Result<()> {
+    let dir = match base_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let prefix = format!(
+        "{}.",
+        base_path
+            .file_name()
+            .map(|v| v.to_string_lossy().to_string())
+            .unwrap_or_default()
+    );
+    let suffix = format!(".{}", compression.extension());
+
+    let mut files = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(&prefix) || !name.ends_with(&suffix) {
+                return None;
+            }
+            let modified = entry.metadata().and_then(|v| v.modified()).ok()?;
+            Some((modified, name))
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let remove_count = files.len().saturating_sub(max_files);
+    for (_, name) in files.iter().take(remove_count) {
+        fs::remove_file(dir.join(name))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use rolling_file::RollingConditionBasic;
+
+    use super::{CompressRollingFileAppender, LogCompression};
+
+    #[test]
+    fn test_compress_rollover() {
+        let dir = std::env::temp_dir().join("common-rs-log-compress");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut appender = CompressRollingFileAppender::new(
+            dir.join("run.log"),
+            RollingConditionBasic::new().daily(),
+            2,
+            LogCompression::Gzip,
+        )
+        .unwrap();
+        for i in 0..3 {
+            writeln!(appender, "line {}", i).unwrap();
+            appender.rollover().unwrap();
+        }
+        writeln!(appender, "line last").unwrap();
+        drop(appender);
+
+        let mut files = fs::read_dir(&dir)
+            .unwrap()
+            .map(|v| v.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        files.sort();
+        println!("{:?}", files);
+        assert_eq!(files.len(), 3);
+        assert!(files.contains(&"run.log".to_string()));
+        assert_eq!(files.iter().filter(|v| v.ends_with(".gz")).count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
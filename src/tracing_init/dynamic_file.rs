@@ -0,0 +1,219 @@
+//! 运行时添加/关闭按`logfile`字段写入的日志文件
+//!
+//! 所有动态文件共用一个格式化的layer, 事件所属的文件在`on_event`中确定后放在线程变量中,
+//! 写入时由`DynamicFileWriter`取出对应文件的writer, 没有对应的文件时丢弃
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use tracing::level_filters::LevelFilter;
+use tracing::{span, Subscriber};
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_subscriber::fmt::writer::OptionalWriter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use super::tracing_file::{value_in_attrs, value_in_event};
+use super::{rolling_appender, LogCompression};
+
+static FIELD_FILE_HANDLE: OnceLock<FieldFileHandle> = OnceLock::new();
+
+thread_local! {
+    static CURRENT_WRITER: RefCell<Option<NonBlocking>> = const { RefCell::new(None) };
+}
+
+type FileMap = Arc<RwLock<HashMap<String, (NonBlocking, WorkerGuard)>>>;
+
+/// 初始化时开启了动态日志文件才有
+pub fn field_file_handle() -> Option<&'static FieldFileHandle> {
+    FIELD_FILE_HANDLE.get()
+}
+
+/// 管理运行时添加的日志文件, 文件名为`{value}.log`, 和主日志在同一个目录
+pub struct FieldFileHandle {
+    dir:         PathBuf,
+    max_files:   usize,
+    compression: LogCompression,
+    /// 初始化时固定的文件, 不能再添加或关闭
+    fixed:       Vec<String>,
+    files:       FileMap,
+}
+
+impl FieldFileHandle {
+    /// 添加日志文件, 之后`logfile = value`的日志写入该文件, 已存在时返回false
+    pub fn add(&self, value: &str) -> io::Result<bool> {
+        if self.fixed.iter().any(|v| v == value) {
+            return Ok(false);
+        }
+        let mut files = self.files.write().unwrap();
+        if files.contains_key(value) {
+            return Ok(false);
+        }
+        let path = self.dir.join(format!("{}.log", value));
+        let entry = rolling_appender(path, self.max_files, self.compression)?;
+        files.insert(value.to_owned(), entry);
+        Ok(true)
+    }
+
+    /// 关闭日志文件, 已写入的日志会刷到文件中, 不存在时返回false
+    pub fn remove(&self, value: &str) -> bool {
+        let entry = self.files.write().unwrap().remove(value);
+        entry.is_some()
+    }
+
+    /// 运行时添加的文件, 按名称排序
+    pub fn files(&self) -> Vec<String> {
+        let mut files = self
+            .files
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
+    /// 关闭所有运行时添加的文件, 在进程退出前调用, 避免丢失还未写入的日志
+    pub fn close_all(&self) {
+        self.files.write().unwrap().clear();
+    }
+}
+
+/// 写入当前事件对应的文件
+#[derive(Clone, Copy)]
+pub(crate) struct DynamicFileWriter;
+
+impl<'a> MakeWriter<'a> for DynamicFileWriter {
+    type Writer = OptionalWriter<NonBlocking>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        CURRENT_WRITER.with_borrow(|v| match v {
+            Some(writer) => OptionalWriter::some(writer.clone()),
+            None => OptionalWriter::none(),
+        })
+    }
+}
+
+/// span中字段的值
+struct DynamicFileValue(String);
+
+pub(crate) struct DynamicFileLayer<T> {
+    layer: T,
+    field: String,
+    files: FileMap,
+}
+
+impl<T> DynamicFileLayer<T> {
+    /// 创建layer并设置全局的handle, 只能调用一次
+    pub(crate) fn new(
+        layer: T,
+        field: &str,
+        dir: PathBuf,
+        max_files: usize,
+        compression: LogCompression,
+        fixed: Vec<String>,
+    ) -> DynamicFileLayer<T> {
+        let files = FileMap::default();
+        let handle = FieldFileHandle {
+            dir,
+            max_files,
+            compression,
+            fixed,
+            files: files.clone(),
+        };
+        let _ = FIELD_FILE_HANDLE.set(handle);
+        DynamicFileLayer {
+            layer,
+            field: field.to_owned(),
+            files,
+        }
+    }
+}
+
+impl<S, T> Layer<S> for DynamicFileLayer<T>
+where
+    T: Layer<S>,
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_layer(&mut self, subscriber: &mut S) {
+        self.layer.on_layer(subscriber)
+    }
+
+    fn register_callsite(
+        &self,
+        metadata: &'static tracing::Metadata<'static>,
+    ) -> tracing::subscriber::Interest {
+        self.layer.register_callsite(metadata)
+    }
+
+    fn enabled(&self, metadata: &tracing::Metadata<'_>, ctx: Context<'_, S>) -> bool {
+        self.layer.enabled(metadata, ctx)
+    }
+
+    fn event_enabled(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) -> bool {
+        self.layer.event_enabled(event, ctx)
+    }
+
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(value) = value_in_attrs(attrs, &self.field) {
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(DynamicFileValue(value));
+            }
+        }
+        self.layer.on_new_span(attrs, id, ctx)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        self.layer.max_level_hint()
+    }
+
+    fn on_record(&self, span: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        self.layer.on_record(span, values, ctx)
+    }
+
+    fn on_follows_from(&self, span: &span::Id, follows: &span::Id, ctx: Context<'_, S>) {
+        self.layer.on_follows_from(span, follows, ctx)
+    }
+
+    /// 事件自身带有字段时按事件的值, 否则按最近的带有该字段的span的值
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        let value = value_in_event(event, &self.field).or_else(|| {
+            ctx.event_span(event)?.scope().find_map(|v| {
+                v.extensions()
+                    .get::<DynamicFileValue>()
+                    .map(|v| v.0.clone())
+            })
+        });
+        let Some(value) = value else {
+            return;
+        };
+        let writer = match self.files.read().unwrap().get(&value) {
+            Some((writer, _)) => writer.clone(),
+            None => return,
+        };
+        CURRENT_WRITER.with_borrow_mut(|v| *v = Some(writer));
+        self.layer.on_event(event, ctx);
+        CURRENT_WRITER.with_borrow_mut(|v| *v = None);
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        self.layer.on_enter(id, ctx)
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        self.layer.on_exit(id, ctx)
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        self.layer.on_close(id, ctx)
+    }
+
+    unsafe fn downcast_raw(&self, id: std::any::TypeId) -> Option<*const ()> {
+        self.layer.downcast_raw(id)
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt::{self, Write};
+
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+use super::request_id::RequestId;
+
+/// 在每条日志的末尾加上固定的字段, 如`service="ingest" host="h1"`
+///
+/// 内部的格式需要明确设置ansi, 先格式化到缓存中, 缓存的Writer不带ansi标记
+///
+/// 设置了request_id时, 再加上所在span的关联ID
+pub(crate) struct GlobalFieldsFormat<F> {
+    inner:      F,
+    fields:     String,
+    request_id: Option<String>,
+}
+
+impl<F> GlobalFieldsFormat<F> {
+    pub(crate) fn new(inner: F, fields: &[(String, String)], request_id: Option<&str>) -> Self {
+        let mut s = String::new();
+        for (key, value) in fields {
+            let _ = write!(s, " {}={:?}", key, value);
+        }
+        GlobalFieldsFormat {
+            inner,
+            fields: s,
+            request_id: request_id.map(|v| v.to_owned()),
+        }
+    }
+}
+
+impl<S, N, F> FormatEvent<S, N> for GlobalFieldsFormat<F>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+    F: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let request_id = self.request_id.as_ref().and_then(|field| {
+            let id = ctx
+                .event_scope()?
+                .find_map(|span| span.extensions().get::<RequestId>().map(|v| v.0.clone()))?;
+            Some((field, id))
+        });
+        if self.fields.is_empty() && request_id.is_none() {
+            return self.inner.format_event(ctx, writer, event);
+        }
+        let mut buf = String::with_capacity(256);
+        self.inner.format_event(ctx, Writer::new(&mut buf), event)?;
+        let newline = buf.ends_with('\n');
+        if newline {
+            buf.pop();
+        }
+        buf.push_str(&self.fields);
+        if let Some((field, id)) = request_id {
+            let _ = write!(buf, " {}={:?}", field, id);
+        }
+        if newline {
+            buf.push('\n');
+        }
+        writer.write_str(&buf)
+    }
+}
@@ -0,0 +1,99 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Span, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{Layer, Registry};
+
+/// span的关联ID, 保存在span的extensions中
+#[derive(Debug, Clone)]
+pub(crate) struct RequestId(pub(crate) String);
+
+struct RequestIdVisitor<'a> {
+    field: &'a str,
+    value: Option<String>,
+}
+
+impl Visit for RequestIdVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == self.field {
+            self.value = Some(format!("{:?}", value));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == self.field {
+            self.value = Some(value.to_string());
+        }
+    }
+}
+
+/// 给声明了关联ID字段的span设置ID, span内(包括子span)的日志都带上这个ID
+///
+/// `info_span!("tick", request_id = %id)`使用给定的ID,
+/// `info_span!("tick", request_id = tracing::field::Empty)`时自动生成
+pub(crate) struct RequestIdLayer {
+    field:  String,
+    prefix: String,
+    seq:    AtomicU64,
+}
+
+impl RequestIdLayer {
+    pub(crate) fn new(field: &str) -> RequestIdLayer {
+        RequestIdLayer {
+            field:  field.to_owned(),
+            prefix: format!("{:x}", std::process::id()),
+            seq:    AtomicU64::new(0),
+        }
+    }
+
+    fn next_id(&self) -> String {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed) + 1;
+        format!("{}-{:x}", self.prefix, seq)
+    }
+}
+
+impl<S> Layer<S> for RequestIdLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().fields().field(&self.field).is_none() {
+            return;
+        }
+        let mut visitor = RequestIdVisitor {
+            field: &self.field,
+            value: None,
+        };
+        attrs.record(&mut visitor);
+        let value = visitor.value.unwrap_or_else(|| self.next_id());
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(RequestId(value));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = RequestIdVisitor {
+            field: &self.field,
+            value: None,
+        };
+        values.record(&mut visitor);
+        if let (Some(value), Some(span)) = (visitor.value, ctx.span(id)) {
+            span.extensions_mut().replace(RequestId(value));
+        }
+    }
+}
+
+/// 当前span的关联ID, 没有开启或不在带ID的span中时返回None
+pub fn current_request_id() -> Option<String> {
+    Span::current().with_subscriber(|(id, dispatch)| {
+        let registry = dispatch.downcast_ref::<Registry>()?;
+        registry
+            .span(id)?
+            .scope()
+            .find_map(|span| span.extensions().get::<RequestId>().map(|v| v.0.clone()))
+    })?
+}
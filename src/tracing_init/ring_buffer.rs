@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+use std::fmt::{Debug, Write as _};
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use time::macros::{format_description, offset};
+use time::OffsetDateTime;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+/// 最近的日志, 初始化时开启了ring buffer才有
+static RING_BUFFER: OnceLock<Arc<Mutex<VecDeque<String>>>> = OnceLock::new();
+
+struct EventVisitor {
+    message: String,
+    fields:  String,
+}
+
+impl Visit for EventVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// 在内存中保留最近capacity条日志, 级别单独过滤, 可以低于文件的级别
+pub(crate) struct RingBufferLayer {
+    capacity: usize,
+    buffer:   Arc<Mutex<VecDeque<String>>>,
+}
+
+impl RingBufferLayer {
+    pub(crate) fn new(capacity: usize) -> RingBufferLayer {
+        let buffer = RING_BUFFER
+            .get_or_init(|| Arc::new(Mutex::new(VecDeque::with_capacity(capacity))))
+            .clone();
+        RingBufferLayer {
+            capacity: capacity.max(1),
+            buffer,
+        }
+    }
+}
+
+impl<S> Layer<S> for RingBufferLayer
+where
+    S: Subscriber,
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = EventVisitor {
+            message: String::new(),
+            fields:  String::new(),
+        };
+        event.record(&mut visitor);
+        let time = OffsetDateTime::now_utc()
+            .to_offset(offset!(+8))
+            .format(format_description!(
+                "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]"
+            ))
+            .unwrap_or_default();
+        let metadata = event.metadata();
+        let line = format!(
+            "{} {:>5} {}: {}{}",
+            time,
+            metadata.level(),
+            metadata.target(),
+            visitor.message,
+            visitor.fields
+        );
+        let mut buffer = self.buffer.lock().unwrap();
+        if buffer.len() >= self.capacity {
+            buffer.pop_front();
+        }
+        buffer.push_back(line);
+    }
+}
+
+/// 内存中最近的日志, 按时间顺序, 没有开启时为空
+pub fn ring_buffer_events() -> Vec<String> {
+    RING_BUFFER
+        .get()
+        .map(|v| v.lock().unwrap().iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// 把内存中最近的日志写入文件, 返回写入的条数
+pub fn flush_ring_buffer(path: impl AsRef<Path>) -> io::Result<usize> {
+    let events = ring_buffer_events();
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    let mut file = fs::File::create(path)?;
+    for event in events.iter() {
+        writeln!(file, "{}", event)?;
+    }
+    Ok(events.len())
+}
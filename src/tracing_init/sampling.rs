@@ -0,0 +1,148 @@
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use tracing::{Event, Metadata};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Filter};
+
+/// 高频日志的采样方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sampling {
+    /// 每N条保留1条(第1条, 第N+1条...)
+    OneIn(u64),
+    /// 每秒最多保留N条
+    PerSecond(u64),
+}
+
+struct SamplingRule {
+    target:   String,
+    level:    LevelFilter,
+    sampling: Sampling,
+    count:    AtomicU64,
+    // (秒, 这一秒内保留的数量)
+    window:   Mutex<(u64, u64)>,
+}
+
+impl SamplingRule {
+    /// target相同或是子模块, 且是level或更详细级别的日志
+    fn matches(&self, metadata: &Metadata<'_>) -> bool {
+        let target = metadata.target();
+        let target_match = target == self.target
+            || target
+                .strip_prefix(self.target.as_str())
+                .is_some_and(|v| v.starts_with("::"));
+        target_match && self.level <= *metadata.level()
+    }
+
+    fn keep(&self, start: &Instant) -> bool {
+        match self.sampling {
+            Sampling::OneIn(n) => self
+                .count
+                .fetch_add(1, Ordering::Relaxed)
+                .is_multiple_of(n.max(1)),
+            Sampling::PerSecond(n) => {
+                let second = start.elapsed().as_secs();
+                let mut window = self.window.lock().unwrap();
+                if window.0 != second {
+                    *window = (second, 0);
+                }
+                window.1 += 1;
+                window.1 <= n
+            },
+        }
+    }
+}
+
+/// 控制台和文件的采样过滤, 不匹配任何规则的日志不受影响, 匹配多个规则时使用第一个
+pub(crate) struct SamplingFilter {
+    rules: Vec<SamplingRule>,
+    start: Instant,
+}
+
+impl SamplingFilter {
+    pub(crate) fn new(rules: &[(Cow<'_, str>, LevelFilter, Sampling)]) -> SamplingFilter {
+        SamplingFilter {
+            rules: rules
+                .iter()
+                .map(|(target, level, sampling)| SamplingRule {
+                    target:   target.to_string(),
+                    level:    *level,
+                    sampling: *sampling,
+                    count:    AtomicU64::new(0),
+                    window:   Mutex::new((0, 0)),
+                })
+                .collect(),
+            start: Instant::now(),
+        }
+    }
+}
+
+impl<S> Filter<S> for SamplingFilter {
+    fn enabled(&self, _meta: &Metadata<'_>, _cx: &Context<'_, S>) -> bool {
+        true
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, _cx: &Context<'_, S>) -> bool {
+        match self.rules.iter().find(|v| v.matches(event.metadata())) {
+            Some(rule) => rule.keep(&self.start),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::sync::{Arc, Mutex};
+
+    use tracing::{debug, info};
+    use tracing_subscriber::filter::LevelFilter;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::{Layer, Registry};
+
+    use super::{Sampling, SamplingFilter};
+
+    struct CountLayer(Arc<Mutex<Vec<String>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for CountLayer {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(event.metadata().target().to_owned());
+        }
+    }
+
+    #[test]
+    fn test_sampling() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let rules = [
+            (Cow::from("ticks"), LevelFilter::DEBUG, Sampling::OneIn(10)),
+            (
+                Cow::from("bars"),
+                LevelFilter::DEBUG,
+                Sampling::PerSecond(3),
+            ),
+        ];
+        let subscriber = Registry::default()
+            .with(CountLayer(events.clone()).with_filter(SamplingFilter::new(&rules)));
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..25 {
+                debug!(target: "ticks", i);
+                debug!(target: "ticks::ag", i);
+                debug!(target: "bars", i);
+                // 高于采样级别的不受影响
+                info!(target: "ticks", i);
+                debug!(target: "ticksx", i);
+            }
+        });
+        let events = events.lock().unwrap();
+        let count = |target: &str| events.iter().filter(|v| *v == target).count();
+        // ticks和ticks::ag共用一个计数
+        assert_eq!(count("ticks") + count("ticks::ag"), 25 + 5);
+        assert!(count("bars") <= 6);
+        assert_eq!(count("ticksx"), 25);
+    }
+}
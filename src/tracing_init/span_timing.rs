@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use tracing::span::{Attributes, Id};
+use tracing::{info, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// 分桶的比例, 分位数的相对误差约为1%
+const GAMMA: f64 = 1.02;
+
+/// 对数分桶的分位数统计
+#[derive(Debug, Clone, Default)]
+struct QuantileSketch {
+    buckets: BTreeMap<i32, u64>,
+    count:   u64,
+    sum:     f64,
+    min:     f64,
+    max:     f64,
+}
+
+impl QuantileSketch {
+    fn bucket(v: f64) -> i32 {
+        if v <= 1.0 {
+            0
+        } else {
+            (v.ln() / GAMMA.ln()).ceil() as i32
+        }
+    }
+
+    fn add(&mut self, v: f64) {
+        *self.buckets.entry(Self::bucket(v)).or_default() += 1;
+        if self.count == 0 {
+            self.min = v;
+            self.max = v;
+        } else {
+            self.min = self.min.min(v);
+            self.max = self.max.max(v);
+        }
+        self.count += 1;
+        self.sum += v;
+    }
+
+    /// q: 0.0~1.0
+    fn quantile(&self, q: f64) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64).round() as u64;
+        if rank == 0 {
+            return self.min;
+        }
+        if rank == self.count - 1 {
+            return self.max;
+        }
+        let mut seen = 0;
+        for (idx, count) in self.buckets.iter() {
+            seen += count;
+            if seen > rank {
+                // 取桶的中间值
+                let v = 2.0 * GAMMA.powi(*idx) / (GAMMA + 1.0);
+                return v.clamp(self.min, self.max);
+            }
+        }
+        self.max
+    }
+}
+
+/// 一个(target, span名)的耗时统计
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanTimingStats {
+    pub target: String,
+    pub name:   String,
+    pub count:  u64,
+    pub total:  Duration,
+    pub min:    Duration,
+    pub max:    Duration,
+    pub p50:    Duration,
+    pub p90:    Duration,
+    pub p99:    Duration,
+}
+
+impl std::fmt::Display for SpanTimingStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}::{} n:{} total:{:.3?} min:{:.3?} p50:{:.3?} p90:{:.3?} p99:{:.3?} max:{:.3?}",
+            self.target,
+            self.name,
+            self.count,
+            self.total,
+            self.min,
+            self.p50,
+            self.p90,
+            self.p99,
+            self.max
+        )
+    }
+}
+
+type SketchMap = HashMap<(&'static str, &'static str), QuantileSketch>;
+
+static SPAN_TIMINGS: OnceLock<Mutex<SketchMap>> = OnceLock::new();
+
+fn span_timings() -> &'static Mutex<SketchMap> {
+    SPAN_TIMINGS.get_or_init(Default::default)
+}
+
+/// 当前的span耗时统计, 按target, span名排序
+pub fn metrics_snapshot() -> Vec<SpanTimingStats> {
+    let nanos = |v: f64| Duration::from_nanos(v as u64);
+    let mut stats = span_timings()
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|((target, name), sketch)| SpanTimingStats {
+            target: target.to_string(),
+            name:   name.to_string(),
+            count:  sketch.count,
+            total:  nanos(sketch.sum),
+            min:    nanos(sketch.min),
+            max:    nanos(sketch.max),
+            p50:    nanos(sketch.quantile(0.5)),
+            p90:    nanos(sketch.quantile(0.9)),
+            p99:    nanos(sketch.quantile(0.99)),
+        })
+        .collect::<Vec<_>>();
+    stats.sort_by(|a, b| (&a.target, &a.name).cmp(&(&b.target, &b.name)));
+    stats
+}
+
+struct SpanStart(Instant);
+
+/// 记录span从创建到关闭的时间
+/// log_interval: 不为None时, 间隔这个时间后在span关闭时打印一次统计
+pub(crate) struct SpanTimingLayer {
+    log_interval: Option<Duration>,
+    last_log:     Mutex<Instant>,
+}
+
+impl SpanTimingLayer {
+    pub(crate) fn new(log_interval: Option<Duration>) -> SpanTimingLayer {
+        SpanTimingLayer {
+            log_interval,
+            last_log: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn should_log(&self) -> bool {
+        let Some(log_interval) = self.log_interval else {
+            return false;
+        };
+        let mut last_log = self.last_log.lock().unwrap();
+        if last_log.elapsed() < log_interval {
+            return false;
+        }
+        *last_log = Instant::now();
+        true
+    }
+}
+
+impl<S> Layer<S> for SpanTimingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanStart(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(elapsed) = span.extensions().get::<SpanStart>().map(|v| v.0.elapsed()) else {
+            return;
+        };
+        let metadata = span.metadata();
+        span_timings()
+            .lock()
+            .unwrap()
+            .entry((metadata.target(), metadata.name()))
+            .or_default()
+            .add(elapsed.as_nanos() as f64);
+        drop(span);
+
+        if self.should_log() {
+            for stats in metrics_snapshot() {
+                info!(target: "span_timing", "{}", stats);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::{span, Level};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    use super::{metrics_snapshot, QuantileSketch, SpanTimingLayer};
+
+    #[test]
+    fn test_sketch() {
+        let mut sketch = QuantileSketch::default();
+        for v in 1..=1000 {
+            sketch.add(v as f64 * 1000.0);
+        }
+        for (q, expected) in [(0.5, 500_000.0), (0.9, 900_000.0), (0.99, 990_000.0)] {
+            let v = sketch.quantile(q);
+            println!("{} {}", q, v);
+            assert!((v - expected).abs() / expected < 0.02);
+        }
+        assert_eq!(sketch.quantile(0.0), 1000.0);
+        assert_eq!(sketch.quantile(1.0), 1_000_000.0);
+    }
+
+    #[test]
+    fn test_layer() {
+        let subscriber = Registry::default().with(SpanTimingLayer::new(None));
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..3 {
+                span!(Level::INFO, "span_timing_test").in_scope(|| {
+                    std::thread::sleep(std::time::Duration::from_millis(2));
+                });
+            }
+        });
+        let stats = metrics_snapshot()
+            .into_iter()
+            .find(|v| v.name == "span_timing_test")
+            .unwrap();
+        println!("{}", stats);
+        assert_eq!(stats.count, 3);
+        assert!(stats.min >= std::time::Duration::from_millis(2));
+    }
+}
@@ -44,6 +44,41 @@ fn value_in_record(record: &Record<'_>, field: &str, value: &str) -> bool {
     visitor.matched
 }
 
+/// 取字段的字符串值, `%`格式的字段通过Debug取得
+struct FieldStrVisitor<'a> {
+    field: &'a str,
+    value: Option<String>,
+}
+
+impl Visit for FieldStrVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == self.field && self.value.is_none() {
+            self.value = Some(format!("{:?}", value));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == self.field {
+            self.value = Some(value.to_string());
+        }
+    }
+}
+
+pub(super) fn value_in_event(event: &tracing::Event<'_>, field: &str) -> Option<String> {
+    let mut visitor = FieldStrVisitor { field, value: None };
+    event.record(&mut visitor);
+    visitor.value
+}
+
+pub(super) fn value_in_attrs(attrs: &span::Attributes<'_>, field: &str) -> Option<String> {
+    let mut visitor = FieldStrVisitor { field, value: None };
+    attrs.record(&mut visitor);
+    visitor.value
+}
+
+/// 按字段值把日志写入对应的文件
+///
+/// 事件自身带有该字段时按事件的值, 否则按所在span的值
 pub struct TracingFileLayer<T> {
     layer: T,
     field: String,
@@ -110,7 +145,9 @@ where
     }
 
     fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
-        let is_on_event = if let Some(span) = ctx.event_span(event) {
+        let is_on_event = if let Some(value) = value_in_event(event, &self.field) {
+            value == self.value
+        } else if let Some(span) = ctx.event_span(event) {
             span.scope().from_root().any(|v| {
                 if let Some(enabled) = v.extensions().get::<TracingFileLayerEnabled>() {
                     enabled.0 == self.value
@@ -0,0 +1,256 @@
+//! 日志文件写入失败(磁盘满, 没有权限等)的检测
+//!
+//! 写入失败时计数, 每个文件第一次失败时在控制台警告;
+//! 配置了备用目录时切换到备用目录中的同名文件, 重新写入失败的内容.
+//! 创建日志文件失败时同样计数, 直接打开备用目录中的文件
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use super::{file_appender, LogCompression};
+
+static WRITE_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+static FALLBACK_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// 日志文件写入失败的次数, 包括切换到备用目录后的失败
+pub fn file_write_errors() -> u64 {
+    WRITE_ERRORS.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_fallback_dir(dir: &Path) {
+    let _ = FALLBACK_DIR.set(dir.to_path_buf());
+}
+
+pub(crate) fn fallback_dir() -> Option<&'static Path> {
+    FALLBACK_DIR.get().map(|v| v.as_path())
+}
+
+/// 备用目录中与path同名的文件
+fn fallback_path(dir: &Path, path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|v| v.to_os_string())
+        .unwrap_or_else(|| OsString::from("run.log"));
+    dir.join(file_name)
+}
+
+fn open_in(
+    dir: &Path,
+    path: &Path,
+    max_files: usize,
+    compression: LogCompression,
+) -> io::Result<Box<dyn Write + Send>> {
+    std::fs::create_dir_all(dir)
+        .and_then(|_| file_appender(path.to_path_buf(), max_files, compression))
+}
+
+pub(crate) struct FallbackWriter {
+    inner:       Box<dyn Write + Send>,
+    path:        PathBuf,
+    max_files:   usize,
+    compression: LogCompression,
+    /// 未切换时为备用目录
+    fallback:    Option<PathBuf>,
+    warned:      bool,
+}
+
+impl FallbackWriter {
+    pub(crate) fn new(
+        inner: Box<dyn Write + Send>,
+        path: PathBuf,
+        max_files: usize,
+        compression: LogCompression,
+        fallback: Option<PathBuf>,
+    ) -> FallbackWriter {
+        FallbackWriter {
+            inner,
+            path,
+            max_files,
+            compression,
+            fallback,
+            warned: false,
+        }
+    }
+
+    /// 打开path, 失败时打开备用目录中的同名文件
+    pub(crate) fn open(
+        path: PathBuf,
+        max_files: usize,
+        compression: LogCompression,
+        fallback: Option<PathBuf>,
+    ) -> io::Result<FallbackWriter> {
+        match file_appender(path.clone(), max_files, compression) {
+            Ok(inner) => Ok(FallbackWriter::new(
+                inner,
+                path,
+                max_files,
+                compression,
+                fallback,
+            )),
+            Err(e) => {
+                let errors = WRITE_ERRORS.fetch_add(1, Ordering::Relaxed) + 1;
+                eprintln!(
+                    "log open error: {}, {}, total errors: {}",
+                    path.display(),
+                    e,
+                    errors
+                );
+                let Some(dir) = fallback else {
+                    return Err(e);
+                };
+                let fallback_path = fallback_path(&dir, &path);
+                let inner = open_in(&dir, &fallback_path, max_files, compression)?;
+                eprintln!(
+                    "log switch to fallback: {} -> {}",
+                    path.display(),
+                    fallback_path.display()
+                );
+                Ok(FallbackWriter::new(
+                    inner,
+                    fallback_path,
+                    max_files,
+                    compression,
+                    None,
+                ))
+            },
+        }
+    }
+
+    /// 记录错误, 切换到备用目录成功时返回Ok, 调用方重试
+    fn on_error(&mut self, e: io::Error) -> io::Result<()> {
+        let errors = WRITE_ERRORS.fetch_add(1, Ordering::Relaxed) + 1;
+        if !self.warned {
+            self.warned = true;
+            eprintln!(
+                "log write error: {}, {}, total errors: {}",
+                self.path.display(),
+                e,
+                errors
+            );
+        }
+        let Some(dir) = self.fallback.take() else {
+            return Err(e);
+        };
+        let path = fallback_path(&dir, &self.path);
+        let inner = open_in(&dir, &path, self.max_files, self.compression);
+        match inner {
+            Ok(inner) => {
+                eprintln!(
+                    "log switch to fallback: {} -> {}",
+                    self.path.display(),
+                    path.display()
+                );
+                self.inner = inner;
+                self.path = path;
+                self.warned = false;
+                Ok(())
+            },
+            Err(fallback_err) => {
+                eprintln!("log fallback error: {}, {}", path.display(), fallback_err);
+                Err(e)
+            },
+        }
+    }
+}
+
+impl Write for FallbackWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.inner.write(buf) {
+            Ok(n) => Ok(n),
+            Err(e) => {
+                self.on_error(e)?;
+                self.inner.write(buf)
+            },
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.inner.flush() {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                self.on_error(e)?;
+                self.inner.flush()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::{self, Write};
+
+    use super::{file_write_errors, FallbackWriter};
+    use crate::tracing_init::LogCompression;
+
+    struct FullDisk;
+
+    impl Write for FullDisk {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("no space left on device"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_fallback_writer() {
+        let dir = std::env::temp_dir().join("common-rs-log-fallback");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut writer = FallbackWriter::new(
+            Box::new(FullDisk),
+            "/nonexistent/run.log".into(),
+            3,
+            LogCompression::None,
+            None,
+        );
+        let errors = file_write_errors();
+        assert!(writer.write_all(b"lost\n").is_err());
+        assert!(file_write_errors() > errors);
+
+        let mut writer = FallbackWriter::new(
+            Box::new(FullDisk),
+            "/nonexistent/run.log".into(),
+            3,
+            LogCompression::None,
+            Some(dir.clone()),
+        );
+        let errors = file_write_errors();
+        writer.write_all(b"kept\n").unwrap();
+        writer.flush().unwrap();
+        assert!(file_write_errors() > errors);
+        assert_eq!(fs::read_to_string(dir.join("run.log")).unwrap(), "kept\n");
+        drop(writer);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_fallback() {
+        let dir = std::env::temp_dir().join("common-rs-log-open-fallback");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // 父路径是普通文件, 打不开
+        let blocked = dir.join("blocked");
+        fs::write(&blocked, "").unwrap();
+        let path = blocked.join("run.log");
+
+        let errors = file_write_errors();
+        assert!(FallbackWriter::open(path.clone(), 3, LogCompression::None, None).is_err());
+        assert!(file_write_errors() > errors);
+
+        let fallback = dir.join("fallback");
+        let mut writer =
+            FallbackWriter::open(path, 3, LogCompression::None, Some(fallback.clone())).unwrap();
+        writer.write_all(b"kept\n").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(fs::read_to_string(fallback.join("run.log")).unwrap(), "kept\n");
+        drop(writer);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
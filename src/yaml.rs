@@ -3,10 +3,12 @@ use std::path::Path;
 use std::{fs, io};
 
 use log::debug;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::path_plain::{HomeDirNotFound, PathPlainExt};
+use crate::schema::{Schema, SchemaError};
 
 #[derive(Debug, Error)]
 pub enum YamlError {
@@ -16,6 +18,8 @@ pub enum YamlError {
     SerdeYaml(#[from] ::serde_yaml::Error),
     #[error("{0}")]
     PathPlain(#[from] HomeDirNotFound),
+    #[error("{0}")]
+    Schema(#[from] SchemaError),
 }
 
 pub fn parse_from_file<'de, P, R>(path: P) -> Result<R, YamlError>
@@ -61,6 +65,21 @@ where
     Ok(r)
 }
 
+/// 先按schema校验文档, 有问题时返回所有问题的key路径, 通过后再反序列化
+pub fn parse_from_file_with_schema<P, R>(path: P, schema: &Schema) -> Result<R, YamlError>
+where
+    P: AsRef<Path>,
+    R: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let path = path.plain()?;
+    let file_content = fs::read_to_string(path)?;
+    let value = serde_yaml::from_str::<serde_yaml::Value>(&file_content)?;
+    schema.validate(&value)?;
+    let r = serde_yaml::from_value::<R>(value)?;
+    Ok(r)
+}
+
 pub fn write_to_file<P, T>(path: P, value: T) -> Result<(), YamlError>
 where
     P: AsRef<Path>,
@@ -80,7 +99,8 @@ mod tests {
     use indexmap::{indexmap, IndexMap};
     use serde::{Deserialize, Serialize};
 
-    use crate::yaml::{parse_from_file, write_to_file};
+    use crate::schema::{Schema, TableSchema};
+    use crate::yaml::{parse_from_file, parse_from_file_with_schema, write_to_file, YamlError};
 
     #[derive(Debug, Deserialize, Serialize)]
     struct IndexMapTmp {
@@ -124,6 +144,31 @@ mod tests {
         write_to_file("./_data/yaml-write.yaml", &tmp).unwrap();
     }
 
+    #[test]
+    fn test_parse_with_schema() {
+        let schema = Schema::map(
+            TableSchema::new()
+                .optional("default", Schema::Bool)
+                .required("host", Schema::String)
+                .required("port", Schema::Int)
+                .required("user", Schema::String)
+                .required("passwd", Schema::String)
+                .required("charset", Schema::String)
+                .required("collation", Schema::String)
+                .required("min-conns", Schema::Int)
+                .required("max-conns", Schema::Int)
+                .required("acquire-timeout-secs", Schema::Int)
+                .required("idle-timeout-secs", Schema::Int)
+                .required("log-sql", Schema::Bool)
+                .required("not-exist-key", Schema::String)
+                .into(),
+        );
+        let r =
+            parse_from_file_with_schema::<_, serde_yaml::Value>("./_data/db-conn.yaml", &schema);
+        println!("{}", r.as_ref().unwrap_err());
+        assert!(matches!(r, Err(YamlError::Schema(_))));
+    }
+
     #[allow(unused)]
     #[derive(Debug, Deserialize)]
     struct AppConfig<'a> {
@@ -1,6 +1,9 @@
 use std::fmt;
 
-use chrono::{Datelike, NaiveDate, NaiveTime, Timelike};
+use chrono::{Datelike, Days, NaiveDate, NaiveTime, Timelike};
+
+#[cfg(feature = "qh")]
+use crate::qh::trading_day::TradingDayUtil;
 
 // pub trait DateConvert: Datelike {
 //     fn to_yyyymmdd(&self) -> u32 {
@@ -127,7 +130,7 @@ impl<T: Timelike> From<&T> for Hms {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Ymd {
     pub yyyymmdd: u32,
     pub year:     u16,
@@ -158,6 +161,54 @@ impl Ymd {
             day,
         }
     }
+
+    /// ISO周, 周一为一周的开始, 年初的几天可能属于上一年的最后一周
+    pub fn iso_week(&self) -> YearWeek {
+        let week = NaiveDate::from(self).iso_week();
+        YearWeek {
+            year: week.year() as u16,
+            week: week.week() as u8,
+        }
+    }
+
+    /// 月的分组, yyyymm
+    pub fn month_bucket(&self) -> u32 {
+        self.yyyymmdd / 100
+    }
+
+    /// 所在ISO周(周一到周日)的最后一个交易日, 作为周K线的分组, 这一周没有交易日时为None
+    pub fn week_bucket_by<F>(&self, is_trading_day: F) -> Option<Ymd>
+    where
+        F: Fn(&Ymd) -> bool,
+    {
+        let date = NaiveDate::from(self);
+        let sunday = date + Days::new(6 - date.weekday().num_days_from_monday() as u64);
+        sunday
+            .iter_days()
+            .rev()
+            .take(7)
+            .map(|v| Ymd::from(&v))
+            .find(|v| is_trading_day(v))
+    }
+
+    /// 同`week_bucket_by`, 交易日由TradingDayUtil判断
+    #[cfg(feature = "qh")]
+    pub fn week_bucket(&self, tdu: &TradingDayUtil) -> Option<Ymd> {
+        self.week_bucket_by(|v| tdu.is_td(&v.yyyymmdd))
+    }
+}
+
+/// ISO周, 如2024-W23
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct YearWeek {
+    pub year: u16,
+    pub week: u8,
+}
+
+impl fmt::Display for YearWeek {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-W{:02}", self.year, self.week)
+    }
 }
 
 impl fmt::Debug for Ymd {
@@ -192,9 +243,9 @@ impl<T: Datelike> From<&T> for Ymd {
 
 #[cfg(test)]
 mod tests {
-    use chrono::{Duration, NaiveDate, NaiveTime};
+    use chrono::{Datelike, Duration, NaiveDate, NaiveTime};
 
-    use super::{Hms, Ymd};
+    use super::{Hms, YearWeek, Ymd};
 
     #[test]
     fn test_ymd_to_naive_date_success() {
@@ -213,6 +264,44 @@ mod tests {
         println!("{:?}", date);
     }
 
+    #[test]
+    fn test_ymd_bucket() {
+        let ymd = Ymd::from_yyyymmdd(20240605);
+        assert_eq!(
+            ymd.iso_week(),
+            YearWeek {
+                year: 2024,
+                week: 23,
+            }
+        );
+        assert_eq!(ymd.iso_week().to_string(), "2024-W23");
+        // 年初属于上一年的最后一周
+        assert_eq!(
+            Ymd::from_yyyymmdd(20210103).iso_week().to_string(),
+            "2020-W53"
+        );
+        assert_eq!(ymd.month_bucket(), 202406);
+
+        // 周五为假期时, 周四为这一周的最后一个交易日
+        let is_trading_day = |v: &Ymd| {
+            let date = NaiveDate::from(v);
+            date.weekday().number_from_monday() <= 5 && v.yyyymmdd != 20240607
+        };
+        assert_eq!(
+            ymd.week_bucket_by(is_trading_day),
+            Some(Ymd::from_yyyymmdd(20240606))
+        );
+        assert_eq!(
+            Ymd::from_yyyymmdd(20240609).week_bucket_by(is_trading_day),
+            Some(Ymd::from_yyyymmdd(20240606))
+        );
+        assert_eq!(
+            Ymd::from_yyyymmdd(20240610).week_bucket_by(is_trading_day),
+            Some(Ymd::from_yyyymmdd(20240614))
+        );
+        assert_eq!(ymd.week_bucket_by(|_| false), None);
+    }
+
     #[test]
     fn test_hms_to_naive_time_success() {
         let hms = Hms::from_hms(23, 59, 59);